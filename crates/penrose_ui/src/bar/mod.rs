@@ -4,9 +4,11 @@ use crate::{
     Result,
 };
 use penrose::{
+    builtin::actions::presentation::PresentationMode,
     core::{State, WindowManager},
+    extensions::hooks::system_tray,
     pure::geometry::Rect,
-    x::{event::XEvent, Atom, ClientConfig, Prop, WinType, XConn},
+    x::{event::XEvent, Atom, Backend, ClientConfig, Prop, WinType, XConn},
     Color, Xid,
 };
 use std::fmt;
@@ -35,6 +37,8 @@ pub struct StatusBar<X: XConn> {
     h: f64,
     bg: Color,
     active_screen: usize,
+    tray_width: Option<u32>,
+    hidden: bool,
 }
 
 impl<X: XConn> fmt::Debug for StatusBar<X> {
@@ -46,6 +50,8 @@ impl<X: XConn> fmt::Debug for StatusBar<X> {
             .field("hpx", &self.hpx)
             .field("bg", &self.bg)
             .field("active_screen", &self.active_screen)
+            .field("tray_width", &self.tray_width)
+            .field("hidden", &self.hidden)
             .finish()
     }
 }
@@ -71,6 +77,8 @@ impl<X: XConn> StatusBar<X> {
             h: h as f64,
             bg: bg.into(),
             active_screen: 0,
+            tray_width: None,
+            hidden: false,
         };
 
         fonts.iter().for_each(|f| bar.draw.register_font(f));
@@ -78,6 +86,17 @@ impl<X: XConn> StatusBar<X> {
         Ok(bar)
     }
 
+    /// Reserve space of the given width at the right hand side of the bar on the first
+    /// screen for docking a [SystemTray][0].
+    ///
+    /// The system tray itself needs to be added to the window manager separately.
+    ///
+    ///   [0]: penrose::extensions::hooks::SystemTray
+    pub fn with_system_tray(mut self, w: u32) -> Self {
+        self.tray_width = Some(w);
+        self
+    }
+
     pub fn add_to(self, mut wm: WindowManager<X>) -> WindowManager<X>
     where
         X: 'static,
@@ -203,6 +222,15 @@ pub fn startup_hook<X: XConn + 'static>(state: &mut State<X>, x: &X) -> penrose:
         return Err(penrose::Error::NoScreens);
     }
 
+    if let (Some(tw), Some(&(id, w))) = (bar.tray_width, bar.screens.first()) {
+        let tw = tw.min(w as u32);
+        bar.screens[0].1 = w - tw as f64;
+        let region = Rect::new(w as u32 - tw, 0, tw, bar.hpx);
+        if let Err(e) = system_tray::dock_into(id, region, state, x) {
+            error!(%e, "unable to dock system tray into status bar");
+        }
+    }
+
     info!("running startup widget hooks");
     for w in bar.widgets.iter_mut() {
         if let Err(e) = w.on_startup(state, x) {
@@ -218,12 +246,26 @@ pub fn startup_hook<X: XConn + 'static>(state: &mut State<X>, x: &X) -> penrose:
 }
 
 /// Run any widget refresh actions and then redraw if needed
+///
+/// The bar is hidden while a [PresentationMode] that hides status bars is active.
 pub fn refresh_hook<X: XConn + 'static>(state: &mut State<X>, x: &X) -> penrose::Result<()> {
     let s = state.extension::<StatusBar<X>>()?;
     let mut bar = s.borrow_mut();
 
     bar.active_screen = state.client_set.current_screen().index();
 
+    let hide = PresentationMode::current(state).is_some_and(|p| p.hides_bar());
+    if hide != bar.hidden {
+        bar.hidden = hide;
+        for &(id, _) in bar.screens.iter() {
+            if hide {
+                x.unmap(id)?;
+            } else {
+                x.map(id)?;
+            }
+        }
+    }
+
     for w in bar.widgets.iter_mut() {
         if let Err(e) = w.on_refresh(state, x) {
             error!(%e, "error running widget refresh hook");
@@ -3,8 +3,9 @@ use cairo::{Matrix, Operator, XCBConnection, XCBDrawable, XCBSurface, XCBVisualT
 use pango::{EllipsizeMode, FontDescription, SCALE};
 use pangocairo::functions::{create_layout, show_layout};
 use penrose::{
+    core::Theme,
     pure::geometry::Rect,
-    x::{WinType, XConn},
+    x::{Backend, WinType, XConn},
     x11rb::XcbConn,
     Color, Xid,
 };
@@ -42,6 +43,19 @@ pub struct TextStyle {
     pub padding: (f64, f64),
 }
 
+impl TextStyle {
+    /// A [TextStyle] using the bar colors of the given [Theme].
+    pub fn from_theme(theme: &Theme, font: impl Into<String>, point_size: i32) -> Self {
+        Self {
+            font: font.into(),
+            point_size,
+            fg: theme.bar_fg,
+            bg: Some(theme.bar_bg),
+            padding: (2.0, 2.0),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Draw {
     pub conn: XcbConn,
@@ -65,7 +65,11 @@ fn main() -> Result<()> {
 
     let conn = RustConn::new()?;
     let key_bindings = parse_keybindings_with_xmodmap(raw_key_bindings())?;
-    let wm = WindowManager::new(Config::default(), key_bindings, HashMap::new(), conn)?;
+    let config = Config {
+        replace_existing_wm: std::env::args().any(|arg| arg == "--replace"),
+        ..Config::default()
+    };
+    let wm = WindowManager::new(config, key_bindings, HashMap::new(), conn)?;
 
     wm.run()
 }
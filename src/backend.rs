@@ -0,0 +1,258 @@
+//! The display server operations required for managing client windows.
+//!
+//! [Backend] covers the operations that the core of penrose needs in order to manage,
+//! position and focus client windows and to receive events, none of which depend on
+//! details of the X11 protocol. Everything that is specific to X (atoms, properties,
+//! selections, reparenting etc) lives in [XConn][crate::x::XConn], which extends [Backend].
+//!
+//! Splitting things this way keeps the surface area that a non-X back end (such as one
+//! based on wlroots) needs to provide to a minimum: anything written purely in terms of
+//! [Backend] and [BackendExt] does not need to know which display server it is talking to.
+use crate::{
+    core::bindings::{keycodes_from_xmodmap, KeyCode, MouseState},
+    pure::{
+        geometry::{Point, Rect},
+        OutputInfo,
+    },
+    x::{ClientConfig, ClientConfigBatch, Cursor, XEvent},
+    Result, Xid,
+};
+use std::{
+    collections::HashMap,
+    os::unix::io::RawFd,
+    time::{Duration, Instant},
+};
+
+// How often the default implementation of Backend::next_event_or_wakeup checks for a wakeup
+const WAKEUP_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The core operations required from a display server in order to run the window manager.
+///
+/// See the [module level docs][self] for details.
+pub trait Backend {
+    /// Ask the display server for the dimensions of each currently available screen.
+    fn screen_details(&self) -> Result<Vec<Rect>>;
+
+    /// Ask the display server for the current (x, y) coordinate of the mouse cursor.
+    fn cursor_position(&self) -> Result<Point>;
+
+    /// Ask the display server for details of the output backing each currently available screen.
+    ///
+    /// The returned outputs must be in the same order as the results of `screen_details`.
+    /// The default implementation names each screen by its index and marks the first
+    /// screen as primary.
+    fn output_details(&self) -> Result<Vec<OutputInfo>> {
+        let outputs = self
+            .screen_details()?
+            .into_iter()
+            .enumerate()
+            .map(|(i, r)| OutputInfo::from_geometry(format!("screen-{i}"), i == 0, r))
+            .collect();
+
+        Ok(outputs)
+    }
+
+    /// Grab the specified key and mouse states, intercepting them for processing within
+    /// the window manager itself.
+    ///
+    /// Any grabs from a previous call are released so that this can be used to re-grab
+    /// bindings after the keyboard mapping has changed.
+    fn grab(&self, key_codes: &[KeyCode], mouse_states: &[MouseState]) -> Result<()>;
+
+    /// A mapping of key names to key codes for the current keyboard layout.
+    ///
+    /// This is used to resolve string key bindings and to re-resolve them when a
+    /// [XEvent::KeymapChange] is received. The default implementation makes use of
+    /// [keycodes_from_xmodmap].
+    fn keycodes(&self) -> Result<HashMap<String, u8>> {
+        keycodes_from_xmodmap()
+    }
+
+    /// Block and wait for the next event from the display server so it can be processed.
+    fn next_event(&self) -> Result<XEvent>;
+
+    /// Flush any pending requests to the display server.
+    fn flush(&self);
+
+    /// Look up the current dimensions and position of a given client window.
+    fn client_geometry(&self, client: Xid) -> Result<Rect>;
+
+    /// Ask the display server for the IDs of all currently known client windows
+    fn existing_clients(&self) -> Result<Vec<Xid>>;
+
+    /// Map the given client window to the screen with its current geometry, making it visible.
+    fn map(&self, client: Xid) -> Result<()>;
+
+    /// Unmap the given client window from the screen, hiding it.
+    fn unmap(&self, client: Xid) -> Result<()>;
+
+    /// Kill the given client window, closing it.
+    fn kill(&self, client: Xid) -> Result<()>;
+
+    /// Set input focus to be held by the given client window.
+    fn focus(&self, client: Xid) -> Result<()>;
+
+    /// Set the [ClientConfig] for a given client window.
+    fn set_client_config(&self, client: Xid, data: &[ClientConfig]) -> Result<()>;
+
+    /// Apply all of the changes in a [ClientConfigBatch] and then flush them to the display server.
+    ///
+    /// The default implementation calls `set_client_config` once for each client in the batch.
+    fn set_client_config_batch(&self, batch: ClientConfigBatch) -> Result<()> {
+        for (id, confs) in batch.iter() {
+            self.set_client_config(id, confs)?;
+        }
+        self.flush();
+
+        Ok(())
+    }
+
+    /// Reposition the mouse cursor to the given (x, y) coordinates within the specified window.
+    /// This method should not be called directly: use `warp_pointer_to_window` or `warp_pointer_to_screen`
+    /// instead.
+    fn warp_pointer(&self, id: Xid, x: i16, y: i16) -> Result<()>;
+
+    /// Actively grab the pointer so that all motion and button release events are reported
+    /// to the window manager until [Backend::ungrab_pointer] is called.
+    ///
+    /// This is intended for use while dragging with the mouse, where the pointer may leave
+    /// the window that the drag started in. The default implementation does nothing.
+    fn grab_pointer(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Set the [Cursor] displayed while the mouse pointer is over the given window.
+    ///
+    /// The default implementation does nothing.
+    fn set_cursor(&self, id: Xid, cursor: Cursor) -> Result<()> {
+        let _ = (id, cursor);
+        Ok(())
+    }
+
+    /// Change the [Cursor] displayed for the currently active pointer grab, such as the
+    /// grab that is active while a mouse binding is being dragged.
+    ///
+    /// The default implementation does nothing.
+    fn set_active_grab_cursor(&self, cursor: Cursor) -> Result<()> {
+        let _ = cursor;
+        Ok(())
+    }
+
+    /// Release an active pointer grab from [Backend::grab_pointer].
+    ///
+    /// The default implementation does nothing.
+    fn ungrab_pointer(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Actively grab the keyboard so that all key presses are reported to the window manager
+    /// until [Backend::ungrab_keyboard] is called.
+    ///
+    /// This is used while waiting for the next key of a key chord. The default implementation
+    /// does nothing.
+    fn grab_keyboard(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Release an active keyboard grab from [Backend::grab_keyboard].
+    ///
+    /// The default implementation does nothing.
+    fn ungrab_keyboard(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Wait for the next event from the display server, returning `None` if no event is
+    /// received before `deadline`.
+    ///
+    /// The default implementation blocks on [Backend::next_event] without a timeout.
+    fn next_event_before(&self, deadline: Instant) -> Result<Option<XEvent>> {
+        let _ = deadline;
+        self.next_event().map(Some)
+    }
+
+    /// Return the next event from the display server if one is already available, without
+    /// blocking to wait for one.
+    ///
+    /// This is used to read ahead when events arrive in bursts so that redundant events can
+    /// be coalesced. The default implementation always returns `None`.
+    fn poll_for_event(&self) -> Result<Option<XEvent>> {
+        Ok(None)
+    }
+
+    /// Wait for the next event from the display server, returning `None` if no event is
+    /// received before `deadline` (if there is one) or if the `wakeup` file descriptor (if
+    /// there is one) becomes readable first. Data waiting to be read from `wakeup` should be
+    /// left for the caller to handle.
+    ///
+    /// This is used by the main loop of the window manager so that work submitted from other
+    /// threads can be run without waiting for the next X event. Implementations should wait
+    /// on the connection to the display server and `wakeup` at the same time (for example
+    /// using `poll`) rather than busy waiting.
+    ///
+    /// The default implementation is unable to wait on `wakeup` so instead calls
+    /// [Backend::next_event_before], waking up at a fixed interval to allow the caller to
+    /// check for work while `wakeup` is provided.
+    fn next_event_or_wakeup(
+        &self,
+        deadline: Option<Instant>,
+        wakeup: Option<RawFd>,
+    ) -> Result<Option<XEvent>> {
+        let poll = wakeup.map(|_| Instant::now() + WAKEUP_POLL_INTERVAL);
+
+        match [deadline, poll].into_iter().flatten().min() {
+            Some(deadline) => self.next_event_before(deadline),
+            None => self.next_event().map(Some),
+        }
+    }
+}
+
+/// Extended functionality for [Backend] impls that is derived from the core operations.
+pub trait BackendExt: Backend {
+    /// Restack the given windows in, each one above the last.
+    fn restack<'a, I>(&self, mut ids: I) -> Result<()>
+    where
+        I: Iterator<Item = &'a Xid>,
+    {
+        let mut previous = match ids.next() {
+            Some(id) => *id,
+            None => return Ok(()), // nothing to stack
+        };
+
+        for &id in ids {
+            self.set_client_config(id, &[ClientConfig::StackAbove(previous)])?;
+            previous = id;
+        }
+
+        Ok(())
+    }
+
+    /// Warp the mouse cursor to the center of the given client window.
+    fn warp_pointer_to_window(&self, id: Xid) -> Result<()> {
+        let r = self.client_geometry(id)?;
+
+        self.warp_pointer(id, r.w as i16 / 2, r.h as i16 / 2)
+    }
+}
+
+// Auto impl BackendExt for all Backend impls
+impl<T> BackendExt for T where T: Backend {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::mock::{Request, ScriptedConn};
+
+    #[test]
+    fn restack_stacks_each_window_above_the_last() {
+        let conn = ScriptedConn::new(vec![]);
+        conn.restack([Xid(1), Xid(2), Xid(3)].iter()).unwrap();
+
+        assert_eq!(
+            conn.requests(),
+            vec![
+                Request::SetClientConfig(Xid(2), vec![ClientConfig::StackAbove(Xid(1))]),
+                Request::SetClientConfig(Xid(3), vec![ClientConfig::StackAbove(Xid(2))]),
+            ]
+        );
+    }
+}
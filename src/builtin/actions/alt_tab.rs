@@ -0,0 +1,207 @@
+//! An "alt-tab" style window switcher over the focus history.
+//!
+//! Pressing the key bound to [alt_tab] selects the previously focused client, with each
+//! further press (while the modifiers for the binding are still held) moving the selection
+//! on through the focus history. Releasing the modifiers focuses the selected client,
+//! switching to its workspace if required. [alt_tab_reverse] moves the selection in the
+//! opposite direction so that `M-S-Tab` can be used to step back.
+//!
+//! ```no_run
+//! # use penrose::{builtin::actions::alt_tab::{alt_tab, alt_tab_reverse}, map, x11rb::RustConn};
+//! # use penrose::core::bindings::KeyEventHandler;
+//! # use std::collections::HashMap;
+//! let bindings: HashMap<String, Box<dyn KeyEventHandler<RustConn>>> = map! {
+//!     map_keys: |k: &str| k.to_owned();
+//!
+//!     "A-Tab" => alt_tab(true),
+//!     "A-S-Tab" => alt_tab_reverse(true),
+//! };
+//! ```
+//!
+//! When `show_list` is set, the candidate clients are listed in an overlay (drawn in the same
+//! way as [binding hints][crate::core::hints]) while the selection is being made.
+use crate::{
+    builtin::actions::key_handler,
+    core::{
+        bindings::KeyEventHandler,
+        hints::{BindingHint, BindingHintsConfig},
+        State,
+    },
+    x::{ClientConfig, XConn, XConnExt},
+    Result, Xid,
+};
+use tracing::debug;
+
+// The selection currently being made
+#[derive(Debug, Default)]
+struct AltTabState {
+    candidates: Vec<Xid>,
+    selected: usize,
+    overlay: Option<Xid>,
+}
+
+/// Select the next client in the focus history, focusing it once the modifiers held for
+/// this binding are released.
+///
+/// See the [module level docs][self] for details.
+pub fn alt_tab<X: XConn + 'static>(show_list: bool) -> Box<dyn KeyEventHandler<X>> {
+    switcher(show_list, false)
+}
+
+/// Select the previous client in the focus history, focusing it once the modifiers held for
+/// this binding are released.
+///
+/// See the [module level docs][self] for details.
+pub fn alt_tab_reverse<X: XConn + 'static>(show_list: bool) -> Box<dyn KeyEventHandler<X>> {
+    switcher(show_list, true)
+}
+
+fn switcher<X: XConn + 'static>(show_list: bool, reverse: bool) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let s = state.extension_or_default::<AltTabState>();
+        let mut s = s.borrow_mut();
+
+        if s.candidates.is_empty() {
+            s.candidates = state.focus_history();
+            let focused = state.client_set.current_client().copied();
+            s.candidates.retain(|&id| Some(id) != focused);
+            s.candidates.extend(focused);
+            s.candidates.rotate_right(focused.is_some() as usize);
+            if s.candidates.len() < 2 {
+                s.candidates.clear();
+                return Ok(());
+            }
+            s.selected = 0;
+        }
+
+        let n = s.candidates.len();
+        s.selected = if reverse {
+            (s.selected + n - 1) % n
+        } else {
+            (s.selected + 1) % n
+        };
+        debug!(selected = %s.candidates[s.selected], "alt-tab selection updated");
+
+        if show_list {
+            draw_list(&mut s, state, x)?;
+        }
+        drop(s);
+
+        state.on_modifier_release(key_handler(commit));
+
+        Ok(())
+    })
+}
+
+fn draw_list<X: XConn>(s: &mut AltTabState, state: &State<X>, x: &X) -> Result<()> {
+    let entries = s
+        .candidates
+        .iter()
+        .enumerate()
+        .map(|(i, &id)| BindingHint {
+            key: if i == s.selected { ">" } else { "" }.to_owned(),
+            description: format!(
+                "[{}] {}",
+                state.client_set.tag_for_client(&id).unwrap_or_default(),
+                x.window_title(id).unwrap_or_default()
+            ),
+        })
+        .collect();
+
+    let conf = state.config.binding_hints.clone().unwrap_or_default();
+    let hints = BindingHintsConfig::hints(&conf, entries, state.focused_screen_rect());
+
+    let id = match s.overlay {
+        Some(id) => id,
+        None => match x.create_overlay(state.root(), hints.r)? {
+            Some(id) => {
+                x.map(id)?;
+                x.set_client_config(id, &[ClientConfig::StackTop])?;
+                s.overlay = Some(id);
+                id
+            }
+            None => return Ok(()),
+        },
+    };
+
+    x.draw_binding_hints(id, &hints)
+}
+
+fn commit<X: XConn + 'static>(state: &mut State<X>, x: &X) -> Result<()> {
+    let s = match state.remove_extension::<AltTabState>() {
+        Some(s) => s,
+        None => return Ok(()),
+    };
+
+    if let Some(id) = s.overlay {
+        x.destroy_window(id)?;
+    }
+
+    match s.candidates.get(s.selected) {
+        Some(id) if state.client_set.contains(id) => {
+            debug!(%id, "alt-tab focusing selected client");
+            x.modify_and_refresh(state, |cs| cs.focus_client(id))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{bindings::KeyCode, WindowManager},
+        x::{
+            fixtures::{requests_matching, ScriptedWm},
+            mock::{Request, ScriptedConn},
+            XEvent,
+        },
+    };
+    use simple_test_case::test_case;
+
+    const ALT_TAB: KeyCode = KeyCode { mask: 8, code: 23 };
+    const ALT_SHIFT_TAB: KeyCode = KeyCode { mask: 9, code: 23 };
+    const ALT_RELEASE: KeyCode = KeyCode { mask: 8, code: 64 };
+    const SHIFT_RELEASE: KeyCode = KeyCode { mask: 9, code: 50 };
+
+    // Clients 1 to 3 are focused in order
+    fn alt_tab_wm(events: Vec<XEvent>, show_list: bool) -> WindowManager<ScriptedConn> {
+        ScriptedWm::new()
+            .with_keycodes(&[("Tab", 23), ("Alt_L", 64), ("Shift_L", 50)])
+            .with_clients(3)
+            .with_events(events)
+            .with_key(ALT_TAB, alt_tab(show_list))
+            .with_key(ALT_SHIFT_TAB, alt_tab_reverse(show_list))
+            .run()
+    }
+
+    #[test_case(vec![XEvent::KeyPress(ALT_TAB)], 3; "modifier held")]
+    #[test_case(vec![XEvent::KeyPress(ALT_TAB), XEvent::KeyRelease(ALT_RELEASE)], 2; "single press")]
+    #[test_case(vec![XEvent::KeyPress(ALT_TAB), XEvent::KeyPress(ALT_TAB), XEvent::KeyRelease(ALT_RELEASE)], 1; "two presses")]
+    #[test_case(vec![XEvent::KeyPress(ALT_TAB), XEvent::KeyPress(ALT_TAB), XEvent::KeyPress(ALT_TAB), XEvent::KeyRelease(ALT_RELEASE)], 3; "wraps")]
+    #[test_case(vec![XEvent::KeyPress(ALT_SHIFT_TAB), XEvent::KeyRelease(SHIFT_RELEASE), XEvent::KeyRelease(ALT_RELEASE)], 1; "reverse")]
+    #[test_case(vec![XEvent::KeyPress(ALT_TAB), XEvent::KeyRelease(ALT_RELEASE), XEvent::KeyPress(ALT_TAB), XEvent::KeyRelease(ALT_RELEASE)], 3; "repeated switch toggles")]
+    #[test]
+    fn alt_tab_cycles_focus_history(events: Vec<XEvent>, expected: u32) {
+        let wm = alt_tab_wm(events, false);
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(expected)));
+    }
+
+    #[test]
+    fn alt_tab_list_is_shown_while_selecting() {
+        let events = vec![
+            XEvent::KeyPress(ALT_TAB),
+            XEvent::KeyPress(ALT_TAB),
+            XEvent::KeyRelease(ALT_RELEASE),
+        ];
+        let wm = alt_tab_wm(events, true);
+
+        let drawn = requests_matching(&wm, |r| matches!(r, Request::DrawBindingHints(..)));
+        let destroyed = requests_matching(&wm, |r| matches!(r, Request::DestroyWindow(..)));
+
+        assert_eq!(drawn.len(), 2);
+        assert_eq!(destroyed.len(), 1);
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(1)));
+    }
+}
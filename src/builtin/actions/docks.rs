@@ -0,0 +1,138 @@
+//! Showing and hiding dock windows such as status bars.
+use crate::{
+    builtin::{actions::key_handler, layout::messages::ReserveSpace},
+    core::{bindings::KeyEventHandler, State},
+    x::{atom::Atom, property::MapState, Prop, XConn, XConnExt},
+    Result, Xid,
+};
+use std::collections::HashMap;
+use tracing::debug;
+
+// The docks hidden on each screen, by screen index
+#[derive(Debug, Default)]
+struct HiddenDocks(HashMap<usize, Vec<Xid>>);
+
+/// The mapped windows with a _NET_WM_WINDOW_TYPE of _NET_WM_WINDOW_TYPE_DOCK that overlap the
+/// given screen.
+pub fn docks_on_screen<X: XConn>(state: &State<X>, x: &X, index: usize) -> Result<Vec<Xid>> {
+    let r = match state.client_set.screens().find(|s| s.index() == index) {
+        Some(s) => s.geometry(),
+        None => return Ok(vec![]),
+    };
+
+    let mut docks = Vec::new();
+    for id in x.existing_clients()? {
+        if x.get_window_attributes(id)?.map_state != MapState::Viewable {
+            continue;
+        }
+
+        let is_dock = matches!(
+            x.get_prop(id, Atom::NetWmWindowType.as_ref())?,
+            Some(Prop::Atom(types)) if types.iter().any(|t| t == Atom::NetWindowTypeDock.as_ref())
+        );
+
+        if is_dock && x.client_geometry(id)?.intersects(&r) {
+            docks.push(id);
+        }
+    }
+
+    Ok(docks)
+}
+
+/// Hide the dock windows (such as status bars) on the focused screen, or show them again if
+/// they were previously hidden using this action.
+///
+/// Space reserved for the docks is given back to the layout of the workspace on the screen:
+/// either by disabling [ReserveTop][crate::builtin::layout::transformers::ReserveTop] (for
+/// the built in status bar) or by recalculating the screen's workarea if
+/// [Config::respect_struts][crate::core::Config::respect_struts] is set.
+pub fn toggle_docks<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state: &mut State<X>, x: &X| {
+        let index = state.client_set.current_screen().index();
+        let hidden = state.extension_or_default::<HiddenDocks>();
+        let previously_hidden = hidden.borrow_mut().0.remove(&index);
+
+        let visible = match previously_hidden {
+            Some(ids) => {
+                debug!(?ids, %index, "showing docks");
+                for id in ids {
+                    x.map(id)?;
+                }
+                true
+            }
+            None => {
+                let ids = docks_on_screen(state, x, index)?;
+                debug!(?ids, %index, "hiding docks");
+                for &id in ids.iter() {
+                    x.unmap(id)?;
+                }
+                hidden.borrow_mut().0.insert(index, ids);
+                false
+            }
+        };
+
+        state
+            .client_set
+            .current_workspace_mut()
+            .broadcast_message(ReserveSpace(visible));
+
+        if state.config.respect_struts {
+            x.update_workarea(state)?;
+        }
+
+        x.refresh(state)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::bindings::KeyCode,
+        pure::geometry::Rect,
+        x::{
+            fixtures::{ScriptedWm, SCREEN},
+            mock::Request,
+            property::{WindowAttributes, WindowClass},
+            XEvent,
+        },
+    };
+    use simple_test_case::test_case;
+
+    const KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+
+    #[test_case(0, Rect::new(0, 20, 1000, 780); "docks shown")]
+    #[test_case(1, SCREEN; "docks hidden")]
+    #[test_case(2, Rect::new(0, 20, 1000, 780); "docks shown again")]
+    #[test]
+    fn toggling_docks_updates_the_workarea(presses: usize, expected: Rect) {
+        let dock_attrs = WindowAttributes::new(true, MapState::Viewable, WindowClass::InputOutput);
+        let dock_type = Prop::Atom(vec![Atom::NetWindowTypeDock.as_ref().to_owned()]);
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| {
+                conn.with_existing_client(Xid(9), Rect::new(0, 0, 1000, 20))
+                    .with_attributes(Xid(9), dock_attrs)
+                    .with_prop(Xid(9), Atom::NetWmWindowType.as_ref(), dock_type)
+                    .with_prop(
+                        Xid(9),
+                        Atom::NetWmStrut.as_ref(),
+                        Prop::Cardinal(vec![0, 0, 20, 0]),
+                    )
+            })
+            .with_clients(1)
+            .with_events((0..presses).map(|_| XEvent::KeyPress(KEY)))
+            .with_key(KEY, toggle_docks())
+            .with_config(|c| c.respect_struts = true)
+            .run();
+
+        assert_eq!(wm.state.client_set.current_screen().workarea(), expected);
+        assert_eq!(wm.state.diff.after.positions, vec![(Xid(1), expected)]);
+
+        let Rect { x, y, w, h } = expected;
+        let workarea = wm.x().requests().into_iter().rev().find_map(|r| match r {
+            Request::SetProp(_, name, p) if name == Atom::NetWorkarea.as_ref() => Some(p),
+            _ => None,
+        });
+        assert_eq!(workarea, Some(Prop::Cardinal([x, y, w, h].repeat(9))));
+    }
+}
@@ -1,9 +1,18 @@
 //! Actions for manipulating floating windows.
 use crate::{
-    builtin::actions::{key_handler, modify_with},
-    core::bindings::KeyEventHandler,
-    x::{XConn, XConnExt},
+    builtin::actions::{drag_bindings, key_handler, modify_with, mouse_handler},
+    core::{
+        bindings::{
+            KeyEventHandler, MouseBindings, MouseEvent, MouseEventHandler, MouseEventKind,
+            MouseState,
+        },
+        State,
+    },
+    pure::geometry::{Point, Rect, RelativeRect},
+    x::{Cursor, XConn, XConnExt},
+    Result, Xid,
 };
+use std::{cell::RefCell, rc::Rc};
 
 /// Resize a currently floating window by a given (width, height) delta
 ///
@@ -43,17 +52,167 @@ pub fn reposition<X: XConn>(dx: i32, dy: i32) -> Box<dyn KeyEventHandler<X>> {
     })
 }
 
-/// Move the currently focused windo to the floating layer in its current on screen position
+/// Regions of the screen that a floating window can be snapped to using [snap_focused].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FloatSnap {
+    /// Fill the left half of the screen
+    LeftHalf,
+    /// Fill the right half of the screen
+    RightHalf,
+    /// Fill the top half of the screen
+    TopHalf,
+    /// Fill the bottom half of the screen
+    BottomHalf,
+    /// Fill the top left quarter of the screen
+    TopLeftQuarter,
+    /// Fill the top right quarter of the screen
+    TopRightQuarter,
+    /// Fill the bottom left quarter of the screen
+    BottomLeftQuarter,
+    /// Fill the bottom right quarter of the screen
+    BottomRightQuarter,
+    /// Move to the top left corner of the screen, maintaining the current size
+    TopLeftCorner,
+    /// Move to the top right corner of the screen, maintaining the current size
+    TopRightCorner,
+    /// Move to the bottom left corner of the screen, maintaining the current size
+    BottomLeftCorner,
+    /// Move to the bottom right corner of the screen, maintaining the current size
+    BottomRightCorner,
+    /// Center on the screen, maintaining the current size
+    Center,
+}
+
+impl FloatSnap {
+    /// The new geometry for a window currently at `r` when snapped to this region of `screen`.
+    pub fn apply(&self, r: Rect, screen: Rect) -> Rect {
+        use FloatSnap::*;
+
+        let Rect { x, y, w, h } = screen;
+        let (lw, th) = (w / 2, h / 2);
+        let (rw, bh) = (w - lw, h - th);
+        let (cw, ch) = (r.w.min(w), r.h.min(h));
+
+        match self {
+            LeftHalf => Rect::new(x, y, lw, h),
+            RightHalf => Rect::new(x + lw, y, rw, h),
+            TopHalf => Rect::new(x, y, w, th),
+            BottomHalf => Rect::new(x, y + th, w, bh),
+            TopLeftQuarter => Rect::new(x, y, lw, th),
+            TopRightQuarter => Rect::new(x + lw, y, rw, th),
+            BottomLeftQuarter => Rect::new(x, y + th, lw, bh),
+            BottomRightQuarter => Rect::new(x + lw, y + th, rw, bh),
+            TopLeftCorner => Rect::new(x, y, cw, ch),
+            TopRightCorner => Rect::new(x + w - cw, y, cw, ch),
+            BottomLeftCorner => Rect::new(x, y + h - ch, cw, ch),
+            BottomRightCorner => Rect::new(x + w - cw, y + h - ch, cw, ch),
+            Center => Rect::new(x + (w - cw) / 2, y + (h - ch) / 2, cw, ch),
+        }
+    }
+}
+
+/// Snap the currently focused window to a region of the screen if it is floating.
+///
+/// Any space reserved on the screen by external status bars and docks is left uncovered (see
+/// [XConnExt::effective_screen_rect]).
+pub fn snap_focused<X: XConn>(snap: FloatSnap) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state, x: &X| {
+        let id = match state.client_set.current_client() {
+            Some(&id) if state.client_set.floating.contains_key(&id) => id,
+            _ => return Ok(()),
+        };
+
+        let screen = state.client_set.screens.focus.r;
+        let effective = x.effective_screen_rect(state, screen)?;
+
+        x.modify_and_refresh(state, |cs| {
+            cs.floating.entry(id).and_modify(|r| {
+                *r = r.apply_as_rect(&screen, |r| snap.apply(r, effective));
+            });
+        })
+    })
+}
+
+/// Move the currently focused window to the floating layer.
+///
+/// If the window was previously floating then its last floating position is restored,
+/// otherwise it is floated in its current on screen position.
 pub fn float_focused<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state, x: &X| match state.client_set.current_client() {
+        Some(&id) => float_in_place(id, state, x),
+        None => Ok(()),
+    })
+}
+
+fn float_in_place<X: XConn>(id: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+    let r = match state.client_set.previous_float_position(&id) {
+        Some(r) => r,
+        None => on_screen_client_geometry(id, state, x)?,
+    };
+
+    x.modify_and_refresh(state, |cs| cs.float_unchecked(id, r))
+}
+
+// The on screen geometry of the client window itself, excluding the frame (and titlebar) of
+// reparented clients. Client windows are positioned relative to their frame.
+fn on_screen_client_geometry<X: XConn>(id: Xid, state: &State<X>, x: &X) -> Result<Rect> {
+    let r = x.client_geometry(id)?;
+
+    match state.frame_for(id) {
+        Some(frame) => {
+            let f = x.client_geometry(frame)?;
+            Ok(Rect::new(f.x + r.x, f.y + r.y, r.w, r.h))
+        }
+        None => Ok(r),
+    }
+}
+
+/// Toggle the currently focused window between the floating and tiled layers.
+///
+/// Windows are floated in the same way as [float_focused].
+pub fn toggle_floating_focused<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
     key_handler(|state, x: &X| {
         let id = match state.client_set.current_client() {
             Some(&id) => id,
             None => return Ok(()),
         };
 
-        let r = x.client_geometry(id)?;
+        if state.client_set.floating.contains_key(&id) {
+            x.modify_and_refresh(state, |cs| {
+                cs.sink(&id);
+            })
+        } else {
+            float_in_place(id, state, x)
+        }
+    })
+}
 
-        x.modify_and_refresh(state, |cs| cs.float_unchecked(id, r))
+// A position centered on the screen taking up the given fraction of its width and height
+fn centered(w: f64, h: f64) -> RelativeRect {
+    let (w, h) = (w.clamp(0.0, 1.0), h.clamp(0.0, 1.0));
+
+    RelativeRect::new((1.0 - w) / 2.0, (1.0 - h) / 2.0, w, h)
+}
+
+/// Float the currently focused window in the center of the screen, taking up the given
+/// fraction (in the range `0.0..=1.0`) of the screen's width and height.
+///
+/// The position is stored relative to the screen so it is preserved if the window is later
+/// moved to a different screen.
+pub fn float_centered<X: XConn>(w: f64, h: f64) -> Box<dyn KeyEventHandler<X>> {
+    modify_with(move |cs| {
+        if let Some(&id) = cs.current_client() {
+            cs.float_unchecked(id, centered(w, h));
+        }
+    })
+}
+
+/// Float the currently focused window at the given position on the screen.
+pub fn float_at<X: XConn>(r: Rect) -> Box<dyn KeyEventHandler<X>> {
+    modify_with(move |cs| {
+        if let Some(&id) = cs.current_client() {
+            cs.float_unchecked(id, r);
+        }
     })
 }
 
@@ -84,5 +243,415 @@ pub fn float_all<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
 
 /// Sink all floating windows back into their tiled positions
 pub fn sink_all<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
-    modify_with(|cs| cs.floating.clear())
+    modify_with(|cs| {
+        let ids: Vec<_> = cs.floating.keys().copied().collect();
+        for id in ids.iter() {
+            cs.sink(id);
+        }
+    })
+}
+
+// The smallest width or height that a window can be resized to using the mouse
+const MIN_DRAG_SIZE: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DragKind {
+    Move,
+    Resize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Drag {
+    id: Xid,
+    start: Point,
+    r: Rect,
+}
+
+// A single drag handler is bound for press, motion and release of the same mouse state so that
+// the in progress drag can be shared between them.
+#[derive(Debug, Clone)]
+struct DragHandler {
+    kind: DragKind,
+    snap_threshold: u32,
+    drag: Rc<RefCell<Option<Drag>>>,
+}
+
+impl DragHandler {
+    fn start<X: XConn>(&mut self, evt: &MouseEvent, state: &mut State<X>, x: &X) -> Result<()> {
+        let id = evt.id;
+        if !state.client_set.contains(&id) {
+            return Ok(());
+        }
+
+        let r = x.client_geometry(state.outer_window(id))?;
+        *self.drag.borrow_mut() = Some(Drag {
+            id,
+            start: evt.rpt,
+            r,
+        });
+
+        let cursor = match self.kind {
+            DragKind::Move => Cursor::Move,
+            DragKind::Resize => Cursor::Resize,
+        };
+        x.set_active_grab_cursor(cursor)?;
+
+        x.modify_and_refresh(state, |cs| {
+            cs.focus_client(&id);
+            if !cs.floating.contains_key(&id) {
+                cs.float_unchecked(id, r);
+            }
+        })
+    }
+
+    fn update<X: XConn>(&mut self, evt: &MouseEvent, state: &mut State<X>, x: &X) -> Result<()> {
+        let Drag { id, start, mut r } = match *self.drag.borrow() {
+            Some(drag) => drag,
+            None => return Ok(()),
+        };
+
+        let dx = evt.rpt.x as i32 - start.x as i32;
+        let dy = evt.rpt.y as i32 - start.y as i32;
+
+        match self.kind {
+            DragKind::Move => r.reposition(dx, dy),
+            DragKind::Resize => {
+                r.resize(dx, dy);
+                r.w = r.w.max(MIN_DRAG_SIZE);
+                r.h = r.h.max(MIN_DRAG_SIZE);
+            }
+        }
+
+        let targets: Vec<Rect> = state
+            .client_set
+            .screens()
+            .map(|s| s.r)
+            .chain(
+                state
+                    .diff
+                    .after
+                    .positions
+                    .iter()
+                    .filter(|&&(c, _)| c != id)
+                    .map(|&(_, r)| r),
+            )
+            .collect();
+
+        let r = snap(r, &targets, self.snap_threshold, self.kind);
+
+        x.modify_and_refresh(state, |cs| cs.float_unchecked(id, r))
+    }
+}
+
+impl<X: XConn> MouseEventHandler<X> for DragHandler {
+    fn call(&mut self, evt: &MouseEvent, state: &mut State<X>, x: &X) -> Result<()> {
+        match evt.kind {
+            MouseEventKind::Press => self.start(evt, state, x),
+            MouseEventKind::Motion => self.update(evt, state, x),
+            MouseEventKind::Release => {
+                self.drag.borrow_mut().take();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Mouse bindings for moving a client by dragging it with the given [MouseState] held.
+///
+/// Clients that are not already floating will be floated in their current position when the
+/// drag begins. Edges of the client will snap to the edges of screens and other visible
+/// clients when they are within `snap_threshold` pixels of one another: a threshold of 0
+/// disables snapping.
+///
+/// ```no_run
+/// # use penrose::{builtin::actions::floating::drag_to_move, core::bindings::*, x11rb::RustConn};
+/// let mut mouse_bindings: MouseBindings<RustConn> = MouseBindings::new();
+/// mouse_bindings.extend(drag_to_move(
+///     MouseState::new(MouseButton::Left, vec![ModifierKey::Meta]),
+///     10,
+/// ));
+/// ```
+pub fn drag_to_move<X: XConn>(state: MouseState, snap_threshold: u32) -> MouseBindings<X> {
+    let handler = DragHandler {
+        kind: DragKind::Move,
+        snap_threshold,
+        drag: Default::default(),
+    };
+
+    drag_bindings(handler, state)
+}
+
+/// Mouse bindings for resizing a client by dragging its bottom right corner with the given
+/// [MouseState] held.
+///
+/// See [drag_to_move] for details of how floating and edge snapping are handled.
+pub fn drag_to_resize<X: XConn>(state: MouseState, snap_threshold: u32) -> MouseBindings<X> {
+    let handler = DragHandler {
+        kind: DragKind::Resize,
+        snap_threshold,
+        drag: Default::default(),
+    };
+
+    drag_bindings(handler, state)
+}
+
+/// A border mouse binding for resizing a client by dragging the edge or corner of its border
+/// that was pressed, keeping the opposite edges in place.
+///
+/// Clients that are not already floating will be floated in their current position when the
+/// drag begins. This should be bound to a button press using
+/// [add_border_bindings][crate::core::WindowManager::add_border_bindings]:
+///
+/// ```no_run
+/// # use penrose::{builtin::actions::floating::border_resize, core::{bindings::*, WindowManager}, x11rb::RustConn};
+/// # fn example(wm: &mut WindowManager<RustConn>) {
+/// let mut border_bindings: MouseBindings<RustConn> = MouseBindings::new();
+/// border_bindings.insert(
+///     (MouseEventKind::Press, MouseState::new(MouseButton::Left, vec![])),
+///     border_resize(),
+/// );
+/// wm.add_border_bindings(border_bindings);
+/// # }
+/// ```
+pub fn border_resize<X: XConn>() -> Box<dyn MouseEventHandler<X>> {
+    mouse_handler(|evt: &MouseEvent, state: &mut State<X>, x: &X| {
+        let (id, edge) = match evt.border {
+            Some(edge) if state.client_set.contains(&evt.id) => (evt.id, edge),
+            _ => return Ok(()),
+        };
+
+        let start = x.client_geometry(state.outer_window(id))?;
+        x.set_active_grab_cursor(Cursor::Resize)?;
+        x.modify_and_refresh(state, |cs| {
+            cs.focus_client(&id);
+            if !cs.floating.contains_key(&id) {
+                cs.float_unchecked(id, start);
+            }
+        })?;
+
+        let (mut dx, mut dy) = (0, 0);
+        state.start_drag(Box::new(
+            move |ddx: i32, ddy: i32, _: &MouseEvent, state: &mut State<X>, x: &X| {
+                dx += ddx;
+                dy += ddy;
+                let r = edge.resize(start, dx, dy, MIN_DRAG_SIZE);
+
+                x.modify_and_refresh(state, |cs| cs.float_unchecked(id, r))
+            },
+        ));
+
+        Ok(())
+    })
+}
+
+// The offset needed to move one of `edges` on to the closest of `targets` if it is within
+// `threshold` pixels.
+fn snap_offset(edges: &[i64], targets: &[i64], threshold: u32) -> Option<i64> {
+    edges
+        .iter()
+        .flat_map(|e| targets.iter().map(move |t| t - e))
+        .filter(|d| d.unsigned_abs() <= threshold as u64)
+        .min_by_key(|d| d.abs())
+}
+
+// Snap the edges of `r` to the edges of `targets`. When moving, either edge on each axis is
+// able to snap. When resizing only the right and bottom edges are affected.
+fn snap(r: Rect, targets: &[Rect], threshold: u32, kind: DragKind) -> Rect {
+    if threshold == 0 {
+        return r;
+    }
+
+    let xs: Vec<i64> = targets
+        .iter()
+        .flat_map(|t| [t.x as i64, t.x as i64 + t.w as i64])
+        .collect();
+    let ys: Vec<i64> = targets
+        .iter()
+        .flat_map(|t| [t.y as i64, t.y as i64 + t.h as i64])
+        .collect();
+
+    let (x, y, w, h) = (r.x as i64, r.y as i64, r.w as i64, r.h as i64);
+
+    match kind {
+        DragKind::Move => {
+            let dx = snap_offset(&[x, x + w], &xs, threshold).unwrap_or(0);
+            let dy = snap_offset(&[y, y + h], &ys, threshold).unwrap_or(0);
+
+            Rect::new((x + dx).max(0) as u32, (y + dy).max(0) as u32, r.w, r.h)
+        }
+
+        DragKind::Resize => {
+            let dw = snap_offset(&[x + w], &xs, threshold).unwrap_or(0);
+            let dh = snap_offset(&[y + h], &ys, threshold).unwrap_or(0);
+            let w = (w + dw).max(MIN_DRAG_SIZE as i64) as u32;
+            let h = (h + dh).max(MIN_DRAG_SIZE as i64) as u32;
+
+            Rect::new(r.x, r.y, w, h)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::bindings::{
+            KeyCode, ModifierKey, MouseBindings, MouseButton, MouseEventKind, MouseState,
+        },
+        x::{
+            fixtures::{meta_mouse_event, motion, ScriptedWm},
+            mock::ScriptedConn,
+            property::{MapState, WindowAttributes, WindowClass},
+            Atom, Prop, XEvent,
+        },
+    };
+    use simple_test_case::test_case;
+    use std::collections::HashMap;
+
+    const SCREEN: Rect = Rect {
+        x: 0,
+        y: 0,
+        w: 1000,
+        h: 800,
+    };
+
+    #[test_case(Rect::new(5, 7, 100, 100), Rect::new(0, 0, 100, 100); "near top left")]
+    #[test_case(Rect::new(895, 697, 100, 100), Rect::new(900, 700, 100, 100); "near bottom right")]
+    #[test_case(Rect::new(50, 50, 100, 100), Rect::new(50, 50, 100, 100); "outside threshold")]
+    #[test]
+    fn move_snaps_to_screen_edges(r: Rect, expected: Rect) {
+        assert_eq!(snap(r, &[SCREEN], 10, DragKind::Move), expected);
+    }
+
+    #[test_case(0.5, 0.5, Rect::new(250, 200, 500, 400); "half")]
+    #[test_case(1.0, 1.0, SCREEN; "full")]
+    #[test_case(2.0, 0.5, Rect::new(0, 200, 1000, 400); "clamped")]
+    #[test]
+    fn centered_is_centered(w: f64, h: f64, expected: Rect) {
+        assert_eq!(centered(w, h).applied_to(&SCREEN), expected);
+    }
+
+    #[test]
+    fn move_snaps_to_the_closest_edge() {
+        let other = Rect::new(200, 0, 100, 100);
+        let r = Rect::new(306, 3, 100, 100);
+
+        assert_eq!(
+            snap(r, &[SCREEN, other], 10, DragKind::Move),
+            Rect::new(300, 0, 100, 100)
+        );
+    }
+
+    #[test]
+    fn resize_only_moves_the_bottom_right_corner() {
+        let r = Rect::new(5, 5, 992, 200);
+
+        assert_eq!(
+            snap(r, &[SCREEN], 10, DragKind::Resize),
+            Rect::new(5, 5, 995, 200)
+        );
+    }
+
+    #[test_case(FloatSnap::LeftHalf, Rect::new(0, 0, 500, 800); "left half")]
+    #[test_case(FloatSnap::BottomHalf, Rect::new(0, 400, 1000, 400); "bottom half")]
+    #[test_case(FloatSnap::TopRightQuarter, Rect::new(500, 0, 500, 400); "top right quarter")]
+    #[test_case(FloatSnap::BottomLeftQuarter, Rect::new(0, 400, 500, 400); "bottom left quarter")]
+    #[test_case(FloatSnap::TopLeftCorner, Rect::new(0, 0, 200, 100); "top left corner")]
+    #[test_case(FloatSnap::BottomRightCorner, Rect::new(800, 700, 200, 100); "bottom right corner")]
+    #[test_case(FloatSnap::Center, Rect::new(400, 350, 200, 100); "center")]
+    #[test]
+    fn float_snap_apply(snap: FloatSnap, expected: Rect) {
+        let r = Rect::new(123, 456, 200, 100);
+
+        assert_eq!(snap.apply(r, SCREEN), expected);
+    }
+
+    #[test]
+    fn float_snap_corners_shrink_to_fit_the_screen() {
+        let r = Rect::new(0, 0, 2000, 100);
+
+        assert_eq!(
+            FloatSnap::TopRightCorner.apply(r, SCREEN),
+            Rect::new(0, 0, 1000, 100)
+        );
+    }
+
+    #[test]
+    fn float_in_place_uses_the_client_geometry_inside_its_frame() {
+        let mut wm = ScriptedWm::new().with_frame(Xid(1), Xid(10)).build();
+        wm.state.client_set.insert(Xid(1));
+        let x = &ScriptedConn::new(vec![SCREEN])
+            .with_client(Xid(10), Rect::new(100, 100, 400, 320))
+            .with_client(Xid(1), Rect::new(0, 20, 400, 300));
+
+        float_in_place(Xid(1), &mut wm.state, x).unwrap();
+
+        let r = wm.state.client_set.floating[&Xid(1)].applied_to(&SCREEN);
+        assert_eq!(r, Rect::new(100, 120, 400, 300));
+    }
+
+    #[test]
+    fn zero_threshold_disables_snapping() {
+        let r = Rect::new(1, 1, 100, 100);
+
+        assert_eq!(snap(r, &[SCREEN], 0, DragKind::Move), r);
+    }
+
+    #[test]
+    fn border_resize_moves_the_pressed_edge() {
+        let press = |kind, x, y| meta_mouse_event(Xid(1), MouseButton::Left, kind, x, y);
+        let mut wm = ScriptedWm::new()
+            .with_clients(1)
+            .with_events([
+                press(MouseEventKind::Press, 0, 400),
+                motion(100, 420),
+                press(MouseEventKind::Release, 100, 420),
+            ])
+            .build();
+        let mut border_bindings: MouseBindings<ScriptedConn> = HashMap::new();
+        border_bindings.insert(
+            (
+                MouseEventKind::Press,
+                MouseState::new(MouseButton::Left, vec![ModifierKey::Meta]),
+            ),
+            border_resize(),
+        );
+        wm.add_border_bindings(border_bindings);
+        let wm = wm.run_script().unwrap();
+
+        let r = wm.state.client_set.floating.get(&Xid(1)).copied();
+        let screen = wm.state.client_set.current_screen().geometry();
+        let r = r.map(|r| r.applied_to(&screen));
+        assert_eq!(r, Some(Rect::new(100, 0, 896, 796)));
+    }
+
+    #[test_case(vec![0, 0, 20, 0], Rect::new(0, 20, 500, 780); "top strut")]
+    #[test_case(vec![0, 0, 0, 30], Rect::new(0, 0, 500, 770); "bottom strut")]
+    #[test_case(vec![40, 0, 0, 0], Rect::new(40, 0, 480, 800); "left strut")]
+    #[test_case(vec![0, 0, 20, 0, 0, 0, 0, 0, 0, 499, 0, 0], Rect::new(0, 20, 500, 780); "overlapping partial strut")]
+    #[test_case(vec![0, 0, 20, 0, 0, 0, 0, 0, 1500, 1999, 0, 0], Rect::new(0, 0, 500, 800); "non-overlapping partial strut")]
+    #[test]
+    fn snapping_respects_struts(strut: Vec<u32>, expected: Rect) {
+        let (float_key, snap_key) = (KeyCode { mask: 0, code: 43 }, KeyCode { mask: 0, code: 44 });
+        let bar_attrs = WindowAttributes::new(true, MapState::Viewable, WindowClass::InputOutput);
+        let atom = if strut.len() == 12 {
+            Atom::NetWmStrutPartial
+        } else {
+            Atom::NetWmStrut
+        };
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| {
+                conn.with_existing_client(Xid(9), Rect::new(0, 0, 1000, 20))
+                    .with_attributes(Xid(9), bar_attrs)
+                    .with_prop(Xid(9), atom.as_ref(), Prop::Cardinal(strut))
+            })
+            .with_clients(1)
+            .with_events([XEvent::KeyPress(float_key), XEvent::KeyPress(snap_key)])
+            .with_key(float_key, float_at(Rect::new(100, 100, 200, 200)))
+            .with_key(snap_key, snap_focused(FloatSnap::LeftHalf))
+            .run();
+
+        let r = wm.state.client_set.floating[&Xid(1)].applied_to(&SCREEN);
+        assert_eq!(r, expected);
+    }
 }
@@ -0,0 +1,179 @@
+//! Helpers for binding the XF86 media keys found on most keyboards (volume, brightness and
+//! media player controls).
+//!
+//! Media keys do not have a consistent key code across keyboards so they need to be resolved
+//! against the current keyboard mapping in the same way as any other key binding. The
+//! [media_key_bindings] function will do this for you, binding each media key that is present
+//! on the current keyboard to its [MediaKey::default_command]:
+//!
+//! ```no_run
+//! # use penrose::{builtin::actions::media::media_key_bindings, core::bindings::parse_keybindings, x11rb::RustConn, Result};
+//! # use std::collections::HashMap;
+//! # fn example() -> Result<()> {
+//! let conn = RustConn::new()?;
+//! let mut key_bindings = parse_keybindings(HashMap::<String, _>::new(), &conn)?;
+//! key_bindings.extend(media_key_bindings(&conn)?);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! If you would prefer to run your own commands, [media_key_codes] can be used to look up the
+//! [KeyCode] for each key instead.
+use crate::{
+    builtin::actions::key_handler,
+    core::bindings::{KeyBindings, KeyCode, KeyEventHandler},
+    util,
+    x::XConn,
+    Result,
+};
+use std::collections::HashMap;
+use strum::{EnumIter, IntoEnumIterator};
+use tracing::trace;
+
+/// The XF86 media keys with a default action.
+#[derive(Debug, EnumIter, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub enum MediaKey {
+    /// XF86AudioRaiseVolume
+    RaiseVolume,
+    /// XF86AudioLowerVolume
+    LowerVolume,
+    /// XF86AudioMute
+    Mute,
+    /// XF86AudioMicMute
+    MicMute,
+    /// XF86AudioPlay
+    Play,
+    /// XF86AudioPause
+    Pause,
+    /// XF86AudioStop
+    Stop,
+    /// XF86AudioNext
+    Next,
+    /// XF86AudioPrev
+    Prev,
+    /// XF86MonBrightnessUp
+    BrightnessUp,
+    /// XF86MonBrightnessDown
+    BrightnessDown,
+}
+
+impl MediaKey {
+    /// The name of the keysym for this key, as reported by `xmodmap -pke`.
+    pub fn keysym(&self) -> &'static str {
+        match self {
+            Self::RaiseVolume => "XF86AudioRaiseVolume",
+            Self::LowerVolume => "XF86AudioLowerVolume",
+            Self::Mute => "XF86AudioMute",
+            Self::MicMute => "XF86AudioMicMute",
+            Self::Play => "XF86AudioPlay",
+            Self::Pause => "XF86AudioPause",
+            Self::Stop => "XF86AudioStop",
+            Self::Next => "XF86AudioNext",
+            Self::Prev => "XF86AudioPrev",
+            Self::BrightnessUp => "XF86MonBrightnessUp",
+            Self::BrightnessDown => "XF86MonBrightnessDown",
+        }
+    }
+
+    /// The command run for this key by [media_key_bindings].
+    ///
+    /// Volume is controlled using `pactl`, media players using `playerctl` and screen
+    /// brightness using `brightnessctl`.
+    pub fn default_command(&self) -> &'static str {
+        match self {
+            Self::RaiseVolume => "pactl set-sink-volume @DEFAULT_SINK@ +5%",
+            Self::LowerVolume => "pactl set-sink-volume @DEFAULT_SINK@ -5%",
+            Self::Mute => "pactl set-sink-mute @DEFAULT_SINK@ toggle",
+            Self::MicMute => "pactl set-source-mute @DEFAULT_SOURCE@ toggle",
+            Self::Play | Self::Pause => "playerctl play-pause",
+            Self::Stop => "playerctl stop",
+            Self::Next => "playerctl next",
+            Self::Prev => "playerctl previous",
+            Self::BrightnessUp => "brightnessctl set +5%",
+            Self::BrightnessDown => "brightnessctl set 5%-",
+        }
+    }
+
+    /// The [KeyCode] for this key under the given keyboard mapping, if the keyboard has it.
+    pub fn key_code(&self, known_codes: &HashMap<String, u8>) -> Option<KeyCode> {
+        known_codes
+            .get(self.keysym())
+            .map(|&code| KeyCode { mask: 0, code })
+    }
+}
+
+/// Look up the [KeyCode] of each [MediaKey] that is present in the given keyboard mapping.
+///
+/// See [Backend::keycodes][crate::backend::Backend::keycodes] for obtaining the current
+/// keyboard mapping.
+pub fn media_key_codes(known_codes: &HashMap<String, u8>) -> HashMap<MediaKey, KeyCode> {
+    MediaKey::iter()
+        .filter_map(|k| k.key_code(known_codes).map(|code| (k, code)))
+        .collect()
+}
+
+/// Spawn the [MediaKey::default_command] for the given key as part of a key binding.
+pub fn spawn_media_command<X: XConn>(key: MediaKey) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |_, _| util::spawn(key.default_command()))
+}
+
+/// [KeyBindings] running the [MediaKey::default_command] of each media key present in the
+/// keyboard mapping of the given [XConn]. Keys that the keyboard does not have are skipped.
+pub fn media_key_bindings<X: XConn>(x: &X) -> Result<KeyBindings<X>> {
+    let codes = media_key_codes(&x.keycodes()?);
+
+    let bindings = MediaKey::iter()
+        .filter_map(|k| match codes.get(&k) {
+            Some(&code) => Some((code, spawn_media_command(k))),
+            None => {
+                trace!(keysym = k.keysym(), "media key not present in keymap");
+                None
+            }
+        })
+        .collect();
+
+    Ok(bindings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::mock::ScriptedConn;
+
+    fn codes() -> HashMap<String, u8> {
+        [
+            ("XF86AudioRaiseVolume", 123),
+            ("XF86AudioLowerVolume", 122),
+            ("XF86MonBrightnessUp", 233),
+            ("a", 38),
+        ]
+        .into_iter()
+        .map(|(name, code)| (name.to_string(), code))
+        .collect()
+    }
+
+    #[test]
+    fn only_present_media_keys_are_resolved() {
+        let resolved = media_key_codes(&codes());
+        let expected: HashMap<_, _> = [
+            (MediaKey::RaiseVolume, KeyCode { mask: 0, code: 123 }),
+            (MediaKey::LowerVolume, KeyCode { mask: 0, code: 122 }),
+            (MediaKey::BrightnessUp, KeyCode { mask: 0, code: 233 }),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(resolved, expected);
+    }
+
+    #[test]
+    fn media_key_bindings_skip_missing_keys() {
+        let conn = ScriptedConn::new(vec![]).with_keycodes(codes());
+        let bindings = media_key_bindings(&conn).unwrap();
+
+        let mut bound: Vec<u8> = bindings.keys().map(|k| k.code).collect();
+        bound.sort();
+
+        assert_eq!(bound, vec![122, 123, 233]);
+    }
+}
@@ -1,13 +1,29 @@
 //! Helpers and pre-defined actions for use in user defined key bindings
 use crate::{
-    core::{bindings::KeyEventHandler, layout::IntoMessage, ClientSet, State},
+    core::{
+        bindings::{
+            Described, KeyEventHandler, MouseBindings, MouseEvent, MouseEventHandler,
+            MouseEventKind, MouseState, Submap,
+        },
+        layout::IntoMessage,
+        metrics::Metrics,
+        ClientSet, State,
+    },
     util,
     x::{XConn, XConnExt},
-    Result,
+    Color, Result, Xid,
 };
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 use tracing::info;
 
+pub mod alt_tab;
+pub mod docks;
 pub mod floating;
+pub mod media;
+pub mod overview;
+pub mod pip;
+pub mod pointer;
+pub mod presentation;
 
 // NOTE: this is here to force the correct lifetime requirements on closures being
 //       used as handlers. The generic impl in crate::bindings for functions of the
@@ -22,6 +38,121 @@ where
     Box::new(f)
 }
 
+/// Attach a human readable description to a key binding, for use in generating a cheatsheet
+/// of the available bindings (see [describe_bindings][crate::core::bindings::describe_bindings]).
+pub fn describe<X: XConn + 'static>(
+    description: impl Into<String>,
+    handler: Box<dyn KeyEventHandler<X>>,
+) -> Box<dyn KeyEventHandler<X>> {
+    Box::new(Described::new(description, handler))
+}
+
+/// Run a key binding the number of times given by the count typed before it, or once if no
+/// count was given (see [Mode::with_count_prefix][crate::core::bindings::Mode::with_count_prefix]).
+pub fn repeat<X: XConn + 'static>(
+    mut handler: Box<dyn KeyEventHandler<X>>,
+) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        for _ in 0..state.count().unwrap_or(1) {
+            handler.call(state, x)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Construct a [MouseEventHandler] from a closure or free function
+pub fn mouse_handler<F, X>(f: F) -> Box<dyn MouseEventHandler<X>>
+where
+    F: FnMut(&MouseEvent, &mut State<X>, &X) -> Result<()> + 'static,
+    X: XConn,
+{
+    Box::new(f)
+}
+
+/// Run one of two mouse handlers depending on whether the mouse event was over the root
+/// window or over a managed client.
+///
+/// This allows for the same [MouseState] to be bound to different actions for the root
+/// window and for clients, such as scrolling with a modifier held to cycle workspaces when
+/// over the root window or to adjust the layout when over a client. Events over anything
+/// else (unmanaged windows such as status bars) are ignored.
+///
+/// ```no_run
+/// # use penrose::{builtin::actions::{mouse_handler, root_or_client}, core::{bindings::*, State}, x::XConnExt, x11rb::RustConn};
+/// let mut mouse_bindings: MouseBindings<RustConn> = MouseBindings::new();
+/// mouse_bindings.insert(
+///     (
+///         MouseEventKind::Press,
+///         MouseState::new(MouseButton::ScrollUp, vec![ModifierKey::Meta]),
+///     ),
+///     root_or_client(
+///         mouse_handler(|_, state: &mut State<RustConn>, x: &RustConn| {
+///             x.modify_and_refresh(state, |cs| cs.toggle_tag())
+///         }),
+///         mouse_handler(|_, state: &mut State<RustConn>, x: &RustConn| {
+///             x.modify_and_refresh(state, |cs| cs.swap_up())
+///         }),
+///     ),
+/// );
+/// ```
+pub fn root_or_client<X: XConn + 'static>(
+    mut on_root: Box<dyn MouseEventHandler<X>>,
+    mut on_client: Box<dyn MouseEventHandler<X>>,
+) -> Box<dyn MouseEventHandler<X>> {
+    mouse_handler(move |evt: &MouseEvent, state: &mut State<X>, x: &X| {
+        if state.is_root(evt.id) {
+            on_root.call(evt, state, x)
+        } else if state.client_set.contains(&evt.id) {
+            on_client.call(evt, state, x)
+        } else {
+            Ok(())
+        }
+    })
+}
+
+/// Bind a [Submap] of further key bindings to be entered by pressing a prefix key.
+///
+/// ```no_run
+/// # use penrose::{builtin::actions::{submap, spawn}, core::bindings::KeyEventHandler, map, x11rb::RustConn};
+/// # use std::collections::HashMap;
+/// # fn example() -> HashMap<&'static str, Box<dyn KeyEventHandler<RustConn>>> {
+/// // Press "M-a" followed by "g" or "f" to launch a program
+/// map! {
+///     "M-a" => submap(map! {
+///         "g" => spawn("gimp"),
+///         "f" => spawn("firefox"),
+///     }),
+/// }
+/// # }
+/// ```
+pub fn submap<S, X>(
+    bindings: HashMap<S, Box<dyn KeyEventHandler<X>>>,
+) -> Box<dyn KeyEventHandler<X>>
+where
+    S: AsRef<str>,
+    X: XConn + 'static,
+{
+    Box::new(Submap::new(bindings))
+}
+
+/// Enter the named binding [Mode][crate::core::bindings::Mode].
+pub fn enter_mode<X: XConn>(name: &'static str) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, _: &X| {
+        state.enter_mode(name);
+        Ok(())
+    })
+}
+
+/// Exit the current binding [Mode][crate::core::bindings::Mode], restoring the default
+/// key bindings.
+pub fn exit_mode<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state: &mut State<X>, _: &X| {
+        state.exit_mode();
+        Ok(())
+    })
+}
+
 /// Mutate the [ClientSet] and refresh the on screen state
 pub fn modify_with<F, X>(f: F) -> Box<dyn KeyEventHandler<X>>
 where
@@ -31,6 +162,26 @@ where
     Box::new(move |s: &mut State<X>, x: &X| x.modify_and_refresh(s, f.clone()))
 }
 
+// Bind the same handler for press, motion and release of a given mouse state. This is used by
+// handlers that need to track a drag from start to finish.
+pub(crate) fn drag_bindings<H, X>(handler: H, state: MouseState) -> MouseBindings<X>
+where
+    H: MouseEventHandler<X> + Clone + 'static,
+    X: XConn,
+{
+    [
+        MouseEventKind::Press,
+        MouseEventKind::Motion,
+        MouseEventKind::Release,
+    ]
+    .into_iter()
+    .map(|kind| {
+        let h: Box<dyn MouseEventHandler<X>> = Box::new(handler.clone());
+        ((kind, state.clone()), h)
+    })
+    .collect()
+}
+
 /// Send a message to the currently active layout
 pub fn send_layout_message<F, M, X>(f: F) -> Box<dyn KeyEventHandler<X>>
 where
@@ -67,11 +218,321 @@ where
     key_handler(move |_, _| util::spawn(program))
 }
 
+/// Spawn an external program as part of a key binding unless an instance of it is already
+/// running (see [util::Spawn::spawn_once]).
+pub fn spawn_once<X>(program: &'static str) -> Box<dyn KeyEventHandler<X>>
+where
+    X: XConn,
+{
+    key_handler(move |_, _| util::spawn_once(program))
+}
+
+/// Write the current [Metrics] to the log, optionally resetting them afterwards.
+///
+/// This is a no-op if no [Metrics] extension has been added.
+pub fn log_metrics<X>(reset: bool) -> Box<dyn KeyEventHandler<X>>
+where
+    X: XConn,
+{
+    key_handler(move |state, _| {
+        if let Ok(m) = state.extension::<Metrics>() {
+            let mut m = m.borrow_mut();
+            info!(metrics = %m.report(), "current latency metrics");
+            if reset {
+                m.reset();
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Increase the opacity of the currently focused window by `delta`, up to fully opaque.
+///
+/// **NOTE**: This requires you to be running a compositor that honours _NET_WM_WINDOW_OPACITY.
+pub fn increase_opacity<X: XConn>(delta: f64) -> Box<dyn KeyEventHandler<X>> {
+    adjust_focused_opacity(delta)
+}
+
+/// Decrease the opacity of the currently focused window by `delta`, down to fully transparent.
+///
+/// **NOTE**: This requires you to be running a compositor that honours _NET_WM_WINDOW_OPACITY.
+pub fn decrease_opacity<X: XConn>(delta: f64) -> Box<dyn KeyEventHandler<X>> {
+    adjust_focused_opacity(-delta)
+}
+
+fn adjust_focused_opacity<X: XConn>(delta: f64) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let id = match state.client_set.current_client() {
+            Some(&id) => id,
+            None => return Ok(()),
+        };
+
+        let opacity = x.client_opacity(id)? + delta;
+        x.set_client_opacity(id, opacity)?;
+
+        // Compositors read the property from the top-level window
+        if let Some(frame) = state.frame_for(id) {
+            x.set_client_opacity(frame, opacity)?;
+        }
+        x.flush();
+
+        Ok(())
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SwapDrag {
+    id: Xid,
+    target: Option<Xid>,
+}
+
+#[derive(Debug, Clone)]
+struct SwapHandler {
+    hint: Color,
+    drag: Rc<RefCell<Option<SwapDrag>>>,
+}
+
+impl SwapHandler {
+    fn start<X: XConn>(&mut self, evt: &MouseEvent, state: &mut State<X>, x: &X) -> Result<()> {
+        let id = evt.id;
+        if !state.client_set.contains(&id) || state.client_set.floating.contains_key(&id) {
+            return Ok(());
+        }
+
+        *self.drag.borrow_mut() = Some(SwapDrag { id, target: None });
+        x.set_client_border_color(state.outer_window(id), self.hint)?;
+        x.flush();
+
+        Ok(())
+    }
+
+    fn update<X: XConn>(&mut self, evt: &MouseEvent, state: &mut State<X>, x: &X) -> Result<()> {
+        let mut drag = self.drag.borrow_mut();
+        let SwapDrag { id, target } = match drag.as_mut() {
+            Some(drag) => drag,
+            None => return Ok(()),
+        };
+
+        let cs = &state.client_set;
+        let new_target = state
+            .diff
+            .after
+            .positions
+            .iter()
+            .find(|&&(c, r)| c != *id && !cs.floating.contains_key(&c) && r.contains_point(evt.rpt))
+            .map(|&(c, _)| c);
+
+        if new_target == *target {
+            return Ok(());
+        }
+
+        if let Some(prev) = target.take() {
+            reset_border(prev, state, x)?;
+        }
+        if let Some(c) = new_target {
+            x.set_client_border_color(state.outer_window(c), self.hint)?;
+        }
+        *target = new_target;
+        x.flush();
+
+        Ok(())
+    }
+
+    fn finish<X: XConn>(&mut self, state: &mut State<X>, x: &X) -> Result<()> {
+        let SwapDrag { id, target } = match self.drag.borrow_mut().take() {
+            Some(drag) => drag,
+            None => return Ok(()),
+        };
+
+        reset_border(id, state, x)?;
+
+        match target {
+            Some(target) => {
+                reset_border(target, state, x)?;
+                x.modify_and_refresh(state, |cs| {
+                    cs.swap_clients(&id, &target);
+                    cs.focus_client(&id);
+                })
+            }
+            None => {
+                x.flush();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<X: XConn> MouseEventHandler<X> for SwapHandler {
+    fn call(&mut self, evt: &MouseEvent, state: &mut State<X>, x: &X) -> Result<()> {
+        match evt.kind {
+            MouseEventKind::Press => self.start(evt, state, x),
+            MouseEventKind::Motion => self.update(evt, state, x),
+            MouseEventKind::Release => self.finish(state, x),
+        }
+    }
+}
+
+fn reset_border<X: XConn>(id: Xid, state: &State<X>, x: &X) -> Result<()> {
+    let focused = state.client_set.current_client() == Some(&id);
+
+    x.set_client_border_color(state.outer_window(id), state.border_color(id, focused))
+}
+
+/// Mouse bindings for swapping tiled clients by dragging one and dropping it over another
+/// with the given [MouseState] held.
+///
+/// While dragging, the border of the dragged client and of the client currently under the
+/// pointer are set to `hint` to indicate which clients will be swapped.
+///
+/// ```no_run
+/// # use penrose::{builtin::actions::drag_to_swap, core::bindings::*, x11rb::RustConn};
+/// let mut mouse_bindings: MouseBindings<RustConn> = MouseBindings::new();
+/// mouse_bindings.extend(drag_to_swap(
+///     MouseState::new(MouseButton::Middle, vec![ModifierKey::Meta]),
+///     0xfabd2fff,
+/// ));
+/// ```
+pub fn drag_to_swap<X: XConn>(state: MouseState, hint: impl Into<Color>) -> MouseBindings<X> {
+    let handler = SwapHandler {
+        hint: hint.into(),
+        drag: Default::default(),
+    };
+
+    drag_bindings(handler, state)
+}
+
+/// Focus the next empty workspace after the current one (see
+/// [next_empty_tag][crate::pure::StackSet::next_empty_tag]).
+pub fn focus_next_empty_workspace<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(
+        |state: &mut State<X>, x: &X| match state.client_set.next_empty_tag() {
+            Some(tag) => x.modify_and_refresh(state, |cs| cs.focus_tag(&tag)),
+            None => Ok(()),
+        },
+    )
+}
+
+/// Move the focused client to the next empty workspace after the current one (see
+/// [next_empty_tag][crate::pure::StackSet::next_empty_tag]).
+pub fn send_to_next_empty_workspace<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(
+        |state: &mut State<X>, x: &X| match state.client_set.next_empty_tag() {
+            Some(tag) => x.modify_and_refresh(state, |cs| cs.move_focused_to_tag(&tag)),
+            None => Ok(()),
+        },
+    )
+}
+
+/// Kill the focused client along with all other members of its window group (see
+/// [group_members][crate::pure::StackSet::group_members]).
+pub fn kill_focused_group<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| cs.kill_focused_group())
+}
+
+/// Move the focused client along with all other members of its window group to the workspace
+/// with the given tag (see [group_members][crate::pure::StackSet::group_members]).
+pub fn move_focused_group_to_tag<X: XConn>(tag: impl Into<String>) -> Box<dyn KeyEventHandler<X>> {
+    let tag = tag.into();
+
+    modify_with(move |cs| cs.move_focused_group_to_tag(&tag))
+}
+
+// The client displaced from the master position by the last use of zoom
+#[derive(Debug, Default)]
+struct Zoom {
+    displaced: Option<Xid>,
+}
+
+/// Swap the focused client with the master client, leaving focus on the new master.
+///
+/// When the master client is already focused it is swapped with the client it displaced the
+/// last time this action was used, falling back to the next client in the stack (in the
+/// style of XMonad's `dwmpromote`).
+pub fn zoom<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state: &mut State<X>, x: &X| {
+        let (focus, head, next) = match state.client_set.current_stack() {
+            Some(s) if s.len() > 1 => (s.focus, *s.head(), s.iter().nth(1).copied()),
+            _ => return Ok(()),
+        };
+
+        let zoom = state.extension_or_default::<Zoom>();
+        let promoted = if focus != head {
+            focus
+        } else {
+            match zoom.borrow().displaced {
+                Some(id) if id != head && state.client_set.current_workspace().contains(&id) => id,
+                _ => match next {
+                    Some(id) => id,
+                    None => return Ok(()),
+                },
+            }
+        };
+        zoom.borrow_mut().displaced = Some(head);
+
+        info!(%promoted, displaced = %head, "zooming client to master");
+        x.modify_and_refresh(state, |cs| {
+            cs.swap_clients(&head, &promoted);
+            cs.focus_client(&promoted);
+        })
+    })
+}
+
 /// Exit penrose
 ///
-/// Immediately exit the window manager with exit code 0.
+/// Cleanly exit the window manager once the current event has been handled, running the
+/// shutdown hook (see [State::exit]).
 pub fn exit<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
-    key_handler(|_, _| std::process::exit(0))
+    key_handler(|s: &mut State<X>, _| {
+        s.exit();
+        Ok(())
+    })
+}
+
+/// Restart penrose in place
+///
+/// Re-run the current binary once the current event has been handled, preserving the
+/// position of all clients (see [WindowManager::restart][crate::core::WindowManager::restart]).
+#[cfg(feature = "restart")]
+pub fn restart<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|s: &mut State<X>, _| {
+        s.request_restart();
+        Ok(())
+    })
+}
+
+/// Focus the client that has been waiting for attention the longest (see
+/// [State::urgent_clients]), switching to its workspace if needed.
+///
+/// Focusing a client clears its urgency so repeated calls cycle through all urgent clients.
+pub fn focus_next_urgent<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|s: &mut State<X>, x: &X| match s.urgent_clients().first() {
+        Some(&id) => x.modify_and_refresh(s, |cs| cs.focus_client(&id)),
+        None => Ok(()),
+    })
+}
+
+/// Toggle whether or not the focused client is sticky (see [State::set_sticky]).
+pub fn toggle_sticky<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|s: &mut State<X>, _| {
+        if let Some(id) = s.focused_client() {
+            let sticky = !s.is_sticky(id);
+            info!(%id, sticky, "toggling sticky client");
+            s.set_sticky(id, sticky);
+        }
+
+        Ok(())
+    })
+}
+
+/// Toggle whether or not focus follows the mouse (see [State::set_focus_follow_mouse]).
+pub fn toggle_focus_follow_mouse<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|s: &mut State<X>, _| {
+        let enabled = !s.config.focus_follow_mouse;
+        info!(enabled, "toggling focus follows mouse");
+        s.set_focus_follow_mouse(enabled);
+        Ok(())
+    })
 }
 
 /// Info log the current window manager [State] for debugging purposes.
@@ -81,3 +542,232 @@ pub fn log_current_state<X: XConn + std::fmt::Debug>() -> Box<dyn KeyEventHandle
         Ok(())
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{
+            bindings::{KeyCode, ModifierKey, MouseButton},
+            Config, WindowManager,
+        },
+        pure::geometry::Point,
+        x::{
+            event::{PointerChange, PropertyEvent},
+            fixtures::{ScriptedWm, SCREEN},
+            mock::{Request, ScriptedConn},
+            property::WmHints,
+            Atom, Prop, XEvent,
+        },
+    };
+    use simple_test_case::test_case;
+
+    const KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+
+    // Clients 1 and 3 are in the window group led by client 1 and client 3 is focused
+    fn group_wm(handler: Box<dyn KeyEventHandler<ScriptedConn>>) -> WindowManager<ScriptedConn> {
+        let group = WmHints::try_from_bytes(&[0b1000000, 0, 0, 0, 0, 0, 0, 0, 1]).unwrap();
+
+        ScriptedWm::new()
+            .with_conn(|conn| conn.with_prop(Xid(3), Atom::WmHints.as_ref(), Prop::WmHints(group)))
+            .with_clients(3)
+            .with_key(KEY, handler)
+            .with_events([XEvent::KeyPress(KEY)])
+            .run()
+    }
+
+    #[test]
+    fn kill_focused_group_kills_all_group_members() {
+        let wm = group_wm(kill_focused_group());
+
+        let killed: Vec<Request> = wm
+            .x()
+            .requests()
+            .into_iter()
+            .filter(|r| matches!(r, Request::Kill(_)))
+            .collect();
+
+        assert_eq!(killed, vec![Request::Kill(Xid(3)), Request::Kill(Xid(1))]);
+        assert!(wm.state.client_set.contains(&Xid(2)));
+    }
+
+    #[test_case("1", vec![Xid(2)]; "current workspace")]
+    #[test_case("2", vec![Xid(3), Xid(1)]; "target workspace")]
+    #[test]
+    fn move_focused_group_to_tag_moves_all_group_members(tag: &str, expected: Vec<Xid>) {
+        let wm = group_wm(move_focused_group_to_tag("2"));
+
+        let clients: Vec<Xid> = wm
+            .state
+            .client_set
+            .workspace(tag)
+            .unwrap()
+            .clients()
+            .copied()
+            .collect();
+
+        assert_eq!(clients, expected);
+    }
+
+    #[test]
+    fn drag_to_swap_keeps_focus_on_the_dragged_client() {
+        let mouse_state = MouseState::new(MouseButton::Middle, vec![ModifierKey::Meta]);
+        let drag = |kind, x| {
+            XEvent::MouseEvent(MouseEvent::new(
+                Xid(1),
+                x,
+                400,
+                x,
+                400,
+                mouse_state.clone(),
+                kind,
+            ))
+        };
+
+        // Client 1 is mapped last so it is focused and in the main position on the left
+        let conn = ScriptedConn::new(vec![SCREEN]).with_events([
+            XEvent::MapRequest(Xid(2)),
+            XEvent::MapRequest(Xid(1)),
+            drag(MouseEventKind::Press, 100),
+            drag(MouseEventKind::Motion, 700),
+            drag(MouseEventKind::Release, 700),
+        ]);
+        let mouse_bindings = drag_to_swap(mouse_state.clone(), 0xff0000ff);
+        let wm = WindowManager::new(Config::default(), HashMap::new(), mouse_bindings, conn)
+            .unwrap()
+            .run_script()
+            .unwrap();
+
+        let clients: Vec<Xid> = wm
+            .state
+            .client_set
+            .current_workspace()
+            .clients()
+            .copied()
+            .collect();
+        assert_eq!(clients, vec![Xid(2), Xid(1)]);
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(1)));
+    }
+
+    fn opacity_of(wm: &WindowManager<ScriptedConn>, id: Xid) -> f64 {
+        match wm
+            .x()
+            .get_prop(id, Atom::NetWmWindowOpacity.as_ref())
+            .unwrap()
+        {
+            Some(Prop::Cardinal(vals)) => vals[0] as f64 / u32::MAX as f64,
+            p => panic!("unexpected opacity for {id}: {p:?}"),
+        }
+    }
+
+    #[test_case(None, decrease_opacity(0.25), 0.75; "unset is treated as opaque")]
+    #[test_case(Some(0.5), increase_opacity(0.25), 0.75; "increase")]
+    #[test_case(Some(0.5), decrease_opacity(0.25), 0.25; "decrease")]
+    #[test_case(Some(0.5), increase_opacity(1.0), 1.0; "clamped to opaque")]
+    #[test_case(Some(0.5), decrease_opacity(1.0), 0.0; "clamped to transparent")]
+    #[test]
+    fn opacity_is_adjusted_for_the_focused_client(
+        initial: Option<f64>,
+        handler: Box<dyn KeyEventHandler<ScriptedConn>>,
+        expected: f64,
+    ) {
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| match initial {
+                Some(o) => {
+                    conn.set_client_opacity(Xid(1), o).unwrap();
+                    conn
+                }
+                None => conn,
+            })
+            .with_clients(1)
+            .with_key(KEY, handler)
+            .with_events([XEvent::KeyPress(KEY)])
+            .run();
+
+        assert!((opacity_of(&wm, Xid(1)) - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn opacity_is_set_on_the_frame_of_framed_clients() {
+        let wm = ScriptedWm::new()
+            .with_frame(Xid(1), Xid(10))
+            .with_clients(1)
+            .with_key(KEY, decrease_opacity(0.5))
+            .with_events([XEvent::KeyPress(KEY)])
+            .run();
+
+        assert!((opacity_of(&wm, Xid(1)) - 0.5).abs() < 1e-6);
+        assert!((opacity_of(&wm, Xid(10)) - 0.5).abs() < 1e-6);
+    }
+
+    #[test_case(0, Xid(1); "enabled")]
+    #[test_case(1, Xid(2); "disabled")]
+    #[test_case(2, Xid(1); "re-enabled")]
+    #[test]
+    fn focus_follow_mouse_can_be_toggled(presses: usize, expected: Xid) {
+        let enter = XEvent::Enter(PointerChange {
+            id: Xid(1),
+            abs: Point::new(10, 10),
+            relative: Point::new(10, 10),
+            same_screen: true,
+        });
+        let wm = ScriptedWm::new()
+            .with_clients(2)
+            .with_events((0..presses).map(|_| XEvent::KeyPress(KEY)))
+            .with_events([enter])
+            .with_key(KEY, toggle_focus_follow_mouse())
+            .run();
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&expected));
+    }
+
+    #[test_case(0, Xid(3), vec![Xid(2), Xid(1)]; "no presses")]
+    #[test_case(1, Xid(2), vec![Xid(1)]; "one press")]
+    #[test_case(2, Xid(1), vec![]; "two presses")]
+    #[test]
+    fn focus_next_urgent_cycles_urgent_clients(presses: usize, focused: Xid, urgent: Vec<Xid>) {
+        let hints = WmHints::try_from_bytes(&[0b0100000000, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        let notify = |id| {
+            XEvent::PropertyNotify(PropertyEvent {
+                id,
+                atom: Atom::WmHints.as_ref().to_owned(),
+                is_root: false,
+            })
+        };
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| {
+                conn.with_prop(Xid(1), Atom::WmHints.as_ref(), Prop::WmHints(hints.clone()))
+                    .with_prop(Xid(2), Atom::WmHints.as_ref(), Prop::WmHints(hints))
+            })
+            .with_clients(3)
+            .with_events([notify(Xid(2)), notify(Xid(1))])
+            .with_events((0..presses).map(|_| XEvent::KeyPress(KEY)))
+            .with_key(KEY, focus_next_urgent())
+            .run();
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&focused));
+        assert_eq!(wm.state.urgent_clients(), urgent);
+    }
+
+    #[test_case(0, 1, vec![2, 3, 1]; "master swaps with next")]
+    #[test_case(2, 1, vec![1, 2, 3]; "promote")]
+    #[test_case(2, 2, vec![3, 2, 1]; "master swaps with displaced")]
+    #[test_case(2, 3, vec![1, 2, 3]; "repeated swaps toggle")]
+    #[test]
+    fn zoom_promotes_and_swaps_with_master(focus_downs: usize, zooms: usize, expected: Vec<u32>) {
+        const FOCUS_KEY: KeyCode = KeyCode { mask: 0, code: 43 };
+        let wm = ScriptedWm::new()
+            .with_clients(3)
+            .with_events((0..focus_downs).map(|_| XEvent::KeyPress(FOCUS_KEY)))
+            .with_events((0..zooms).map(|_| XEvent::KeyPress(KEY)))
+            .with_key(KEY, zoom())
+            .with_key(FOCUS_KEY, modify_with(|cs| cs.focus_down()))
+            .run();
+
+        let cs = &wm.state.client_set;
+        let clients: Vec<Xid> = cs.current_workspace().clients().copied().collect();
+        let expected: Vec<Xid> = expected.into_iter().map(Xid).collect();
+        assert_eq!(clients, expected);
+        assert_eq!(cs.current_client(), expected.first());
+    }
+}
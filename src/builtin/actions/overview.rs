@@ -0,0 +1,386 @@
+//! An "expose" style overview for picking a client to focus.
+//!
+//! Opening the overview temporarily arranges the clients on the focused workspace (and
+//! optionally the clients on all hidden workspaces) in a grid of shrunk down cells covering
+//! the focused screen. A client can then be picked using the keyboard or mouse, after which
+//! the original layout is restored and the picked client is focused:
+//!
+//!   - `Tab`, `j`, `l` and the `Right` / `Down` arrow keys move focus to the next client
+//!   - `k`, `h` and the `Left` / `Up` arrow keys move focus to the previous client
+//!   - `1` to `9` pick the client in that cell of the grid
+//!   - `Return` picks the focused client
+//!   - `Escape` closes the overview without changing focus
+//!   - Clicking on a cell picks the client in that cell
+//!
+//! ```no_run
+//! # use penrose::{builtin::actions::overview::overview, map, x11rb::RustConn};
+//! # use penrose::core::bindings::KeyEventHandler;
+//! # use std::collections::HashMap;
+//! let bindings: HashMap<String, Box<dyn KeyEventHandler<RustConn>>> = map! {
+//!     map_keys: |k: &str| k.to_owned();
+//!
+//!     "M-o" => overview(false),
+//!     "M-S-o" => overview(true),
+//! };
+//! ```
+use crate::{
+    builtin::actions::key_handler,
+    core::{
+        bindings::{KeyCode, KeyEventHandler, MouseEventKind},
+        hooks::{EventHook, NamedHook},
+        ClientSet, State,
+    },
+    pure::{
+        geometry::{Rect, RelativeRect},
+        Position, Stack,
+    },
+    x::{XConn, XConnExt, XEvent, XEventKind},
+    Result, Xid,
+};
+use std::collections::{HashMap, HashSet};
+use tracing::info;
+
+const OVERVIEW_HOOK: &str = "overview";
+
+// The gap in pixels left around each cell of the overview grid
+const CELL_GAP: u32 = 20;
+
+/// Open the overview for the focused workspace, or for the focused workspace along with all
+/// hidden workspaces if `all_workspaces` is true.
+///
+/// See the [module level docs][self] for details.
+pub fn overview<X: XConn>(all_workspaces: bool) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        if state.has_hook(OVERVIEW_HOOK) {
+            return Ok(());
+        }
+
+        let saved = Saved::new(&state.client_set, all_workspaces);
+        saved.gather(&mut state.client_set);
+
+        let clients: Vec<Xid> = state
+            .client_set
+            .current_workspace()
+            .clients()
+            .copied()
+            .collect();
+        if clients.is_empty() {
+            saved.restore(&mut state.client_set);
+            return Ok(());
+        }
+
+        info!(
+            n_clients = clients.len(),
+            all_workspaces, "opening overview"
+        );
+        let screen = state.client_set.current_screen().workarea();
+        let cells: Vec<(Xid, Rect)> = clients
+            .iter()
+            .copied()
+            .zip(grid(clients.len(), screen))
+            .collect();
+
+        x.modify_and_refresh(state, |cs| {
+            for &(id, r) in cells.iter() {
+                _ = cs.float(id, r);
+            }
+        })?;
+
+        x.grab_keyboard()?;
+        x.grab_pointer()?;
+        let keycodes = x.keycodes()?;
+        state.add_named_hook(
+            OVERVIEW_HOOK,
+            NamedHook::Event(Box::new(Overview {
+                saved,
+                cells,
+                keycodes,
+            })),
+        );
+
+        Ok(())
+    })
+}
+
+/// Whether or not the overview is currently open.
+pub fn is_overview_open<X: XConn>(state: &State<X>) -> bool {
+    state.has_hook(OVERVIEW_HOOK)
+}
+
+// Split `r` into a grid of (at least) `n` evenly sized cells, filling rows first.
+fn grid(n: usize, r: Rect) -> Vec<Rect> {
+    let cols = (n as f64).sqrt().ceil().max(1.0) as u32;
+    let rows = (n as u32).div_ceil(cols);
+
+    r.as_rows(rows)
+        .into_iter()
+        .flat_map(|row| row.as_columns(cols))
+        .map(|cell| {
+            let mut cell = cell.shrink_in(CELL_GAP);
+            cell.reposition(CELL_GAP as i32, CELL_GAP as i32);
+            cell
+        })
+        .take(n)
+        .collect()
+}
+
+// The parts of the client set modified while the overview is open
+#[derive(Debug)]
+struct Saved {
+    stacks: Vec<(String, Option<Stack<Xid>>)>,
+    floating: HashMap<Xid, RelativeRect>,
+}
+
+impl Saved {
+    // The focused workspace is always saved first
+    fn new(cs: &ClientSet, all_workspaces: bool) -> Self {
+        let mut tags = vec![cs.current_tag().to_owned()];
+        if all_workspaces {
+            let visible: HashSet<&str> =
+                cs.on_screen_workspaces().map(|w| w.tag.as_str()).collect();
+            tags.extend(
+                cs.ordered_tags()
+                    .into_iter()
+                    .filter(|t| !visible.contains(t.as_str())),
+            );
+        }
+
+        let stacks = tags
+            .into_iter()
+            .map(|t| {
+                let stack = cs.workspace(&t).and_then(|w| w.stack.clone());
+                (t, stack)
+            })
+            .collect();
+
+        Self {
+            stacks,
+            floating: cs.floating.clone(),
+        }
+    }
+
+    // Move the clients from all saved workspaces onto the focused workspace
+    fn gather(&self, cs: &mut ClientSet) {
+        let ws = cs.current_workspace_mut();
+        for (_, stack) in self.stacks.iter().skip(1) {
+            for &id in stack.iter().flat_map(|s| s.iter()) {
+                ws.stack = match ws.stack.take() {
+                    Some(mut s) => {
+                        s.insert_at(Position::Tail, id);
+                        Some(s)
+                    }
+                    None => Some(Stack::new([], id, [])),
+                };
+            }
+        }
+
+        for (tag, _) in self.stacks.iter().skip(1) {
+            if let Some(w) = cs.workspace_mut(tag) {
+                w.stack = None;
+            }
+        }
+    }
+
+    // Put every client back where it was, keeping any clients that were added to the focused
+    // workspace while the overview was open and dropping any that have been removed.
+    fn restore(self, cs: &mut ClientSet) {
+        let saved: HashSet<Xid> = self
+            .stacks
+            .iter()
+            .flat_map(|(_, s)| s.iter().flat_map(|s| s.iter().copied()))
+            .collect();
+        let live: HashSet<Xid> = cs.clients().copied().collect();
+        let added: Vec<Xid> = cs
+            .current_workspace()
+            .clients()
+            .filter(|id| !saved.contains(id))
+            .copied()
+            .collect();
+
+        let mut floating: HashMap<Xid, RelativeRect> = self
+            .floating
+            .into_iter()
+            .filter(|(id, _)| live.contains(id))
+            .collect();
+        floating.extend(
+            cs.floating
+                .iter()
+                .filter(|(id, _)| !saved.contains(id))
+                .map(|(&id, &r)| (id, r)),
+        );
+        cs.floating = floating;
+
+        for (tag, stack) in self.stacks {
+            if let Some(w) = cs.workspace_mut(&tag) {
+                w.stack = stack.and_then(|s| s.filter(|id| live.contains(id)));
+            }
+        }
+
+        for id in added {
+            cs.insert_at(Position::Tail, id);
+        }
+    }
+}
+
+// The open overview, picking a client from the keyboard and mouse
+struct Overview {
+    saved: Saved,
+    cells: Vec<(Xid, Rect)>,
+    keycodes: HashMap<String, u8>,
+}
+
+impl Overview {
+    fn is_key(&self, code: KeyCode, names: &[&str]) -> bool {
+        names
+            .iter()
+            .any(|name| self.keycodes.get(*name) == Some(&code.code))
+    }
+
+    // The client in the given (1 indexed) cell of the grid
+    fn numbered_cell(&self, code: KeyCode) -> Option<Xid> {
+        (1..=9)
+            .find(|n| self.is_key(code, &[&n.to_string()]))
+            .and_then(|n| self.cells.get(n - 1))
+            .map(|&(id, _)| id)
+    }
+
+    // Restore the original layout, focusing the picked client if there is one.
+    fn close<X: XConn>(&mut self, pick: Option<Xid>, state: &mut State<X>, x: &X) -> Result<()> {
+        info!(?pick, "closing overview");
+        state.remove_hook(OVERVIEW_HOOK);
+        x.ungrab_pointer()?;
+        x.ungrab_keyboard()?;
+
+        let mut saved = Some(Saved {
+            stacks: std::mem::take(&mut self.saved.stacks),
+            floating: std::mem::take(&mut self.saved.floating),
+        });
+
+        x.modify_and_refresh(state, |cs| {
+            if let Some(saved) = saved.take() {
+                saved.restore(cs);
+            }
+            if let Some(id) = pick {
+                cs.focus_client(&id);
+            }
+        })
+    }
+}
+
+impl<X: XConn> EventHook<X> for Overview {
+    fn call(&mut self, event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
+        match event {
+            XEvent::KeyPress(code) => {
+                let code = *code;
+                if self.is_key(code, &["Escape"]) {
+                    self.close(None, state, x)?;
+                } else if self.is_key(code, &["Return", "KP_Enter"]) {
+                    let pick = state.client_set.current_client().copied();
+                    self.close(pick, state, x)?;
+                } else if let Some(id) = self.numbered_cell(code) {
+                    self.close(Some(id), state, x)?;
+                } else if self.is_key(code, &["Tab", "j", "l", "Right", "Down"]) {
+                    x.modify_and_refresh(state, |cs| cs.focus_down())?;
+                } else if self.is_key(code, &["k", "h", "Left", "Up"]) {
+                    x.modify_and_refresh(state, |cs| cs.focus_up())?;
+                }
+            }
+
+            XEvent::MouseEvent(e) if e.kind == MouseEventKind::Press => {
+                let pick = self
+                    .cells
+                    .iter()
+                    .find(|(_, r)| r.contains_point(e.rpt))
+                    .map(|&(id, _)| id);
+
+                if pick.is_some() {
+                    self.close(pick, state, x)?;
+                }
+            }
+
+            _ => return Ok(true),
+        }
+
+        Ok(false)
+    }
+
+    fn wants(&self, kind: XEventKind) -> bool {
+        matches!(kind, XEventKind::KeyPress | XEventKind::MouseEvent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::WindowManager,
+        x::{fixtures::ScriptedWm, mock::ScriptedConn},
+    };
+    use simple_test_case::test_case;
+
+    #[test_case(1, vec![Rect::new(20, 20, 960, 760)]; "single client")]
+    #[test_case(
+        3,
+        vec![
+            Rect::new(20, 20, 460, 360),
+            Rect::new(520, 20, 460, 360),
+            Rect::new(20, 420, 460, 360),
+        ];
+        "partial last row"
+    )]
+    #[test]
+    fn grid_cells(n: usize, expected: Vec<Rect>) {
+        assert_eq!(grid(n, Rect::new(0, 0, 1000, 800)), expected);
+    }
+
+    const OPEN: KeyCode = KeyCode { mask: 0, code: 42 };
+
+    fn run(all_workspaces: bool, keys: &[u8]) -> WindowManager<ScriptedConn> {
+        let mut wm = ScriptedWm::new()
+            .with_keycodes(&[("2", 11), ("Tab", 23), ("Return", 36), ("Escape", 9)])
+            .with_clients(2)
+            .with_key(OPEN, overview(all_workspaces))
+            .with_events([XEvent::KeyPress(OPEN)])
+            .with_events(
+                keys.iter()
+                    .map(|&code| XEvent::KeyPress(KeyCode { mask: 0, code })),
+            )
+            .build();
+        wm.state.client_set.insert_as_focus_for("2", Xid(3));
+
+        wm.run_script().unwrap()
+    }
+
+    #[test]
+    fn clients_are_gathered_and_floated_while_open() {
+        let wm = run(true, &[]);
+        let cs = &wm.state.client_set;
+
+        assert!(is_overview_open(&wm.state));
+        assert_eq!(cs.current_workspace().clients().count(), 3);
+        assert_eq!(cs.floating.len(), 3);
+    }
+
+    #[test_case(false, &[9], Xid(2), "1"; "cancel")]
+    #[test_case(false, &[23, 36], Xid(1), "1"; "pick focused")]
+    #[test_case(true, &[11], Xid(1), "1"; "pick numbered cell")]
+    #[test_case(true, &[23, 23, 36], Xid(3), "2"; "pick from other workspace")]
+    #[test]
+    fn closing_restores_the_original_layout(
+        all_workspaces: bool,
+        keys: &[u8],
+        focused: Xid,
+        tag: &str,
+    ) {
+        let wm = run(all_workspaces, keys);
+        let cs = &wm.state.client_set;
+
+        assert!(!is_overview_open(&wm.state));
+        assert!(cs.floating.is_empty());
+        assert_eq!(cs.tag_for_client(&Xid(1)), Some("1"));
+        assert_eq!(cs.tag_for_client(&Xid(2)), Some("1"));
+        assert_eq!(cs.tag_for_client(&Xid(3)), Some("2"));
+        assert_eq!(cs.current_client(), Some(&focused));
+        assert_eq!(cs.current_tag(), tag);
+    }
+}
@@ -0,0 +1,232 @@
+//! A "picture in picture" mode for keeping a single client visible while working.
+//!
+//! The client is floated in a small region in one corner of the screen, kept above other
+//! clients and follows the focused screen as it switches between workspaces. Toggling
+//! picture in picture mode off again restores the floating state the client had before,
+//! leaving it on the current workspace.
+//!
+//! ```no_run
+//! # use penrose::{builtin::actions::pip::{toggle_pip, PipConfig}, map, x11rb::RustConn};
+//! # use penrose::pure::geometry::Corner;
+//! # use penrose::core::bindings::KeyEventHandler;
+//! # use std::collections::HashMap;
+//! let bindings: HashMap<String, Box<dyn KeyEventHandler<RustConn>>> = map! {
+//!     map_keys: |k: &str| k.to_owned();
+//!
+//!     "M-p" => toggle_pip(PipConfig { corner: Corner::TopRight, ..Default::default() }),
+//! };
+//! ```
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, hooks::NamedHook, State},
+    pure::{
+        geometry::{Corner, Rect, RelativeRect},
+        Position,
+    },
+    stack,
+    x::{ClientConfig, XConn, XConnExt},
+    Result, Xid,
+};
+use tracing::info;
+
+const PIP_HOOK: &str = "picture-in-picture";
+
+/// Where and how large to make a picture in picture client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PipConfig {
+    /// The corner of the screen to place the client in
+    pub corner: Corner,
+    /// The width of the client as a fraction of the width of the screen
+    pub width: f64,
+    /// The height of the client as a fraction of the height of the screen
+    pub height: f64,
+    /// The gap in pixels to leave between the client and the edges of the screen
+    pub gap: u32,
+}
+
+impl Default for PipConfig {
+    fn default() -> Self {
+        Self {
+            corner: Corner::default(),
+            width: 0.25,
+            height: 0.25,
+            gap: 10,
+        }
+    }
+}
+
+impl PipConfig {
+    /// The position of a picture in picture client within the given screen region.
+    pub fn rect_within(&self, screen: Rect) -> Rect {
+        let w = (screen.w as f64 * self.width) as u32;
+        let h = (screen.h as f64 * self.height) as u32;
+        let left = screen.x + self.gap;
+        let right = (screen.x + screen.w).saturating_sub(w + self.gap);
+        let top = screen.y + self.gap;
+        let bottom = (screen.y + screen.h).saturating_sub(h + self.gap);
+
+        let (x, y) = match self.corner {
+            Corner::TopLeft => (left, top),
+            Corner::TopRight => (right, top),
+            Corner::BottomLeft => (left, bottom),
+            Corner::BottomRight => (right, bottom),
+        };
+
+        Rect::new(x, y, w, h)
+    }
+}
+
+// The client currently in picture in picture mode and its previous floating position
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pip {
+    client: Xid,
+    floating: Option<RelativeRect>,
+}
+
+/// The client currently in picture in picture mode, if there is one.
+pub fn pip_client<X: XConn>(state: &State<X>) -> Option<Xid> {
+    state
+        .extension::<Pip>()
+        .ok()
+        .map(|p| p.borrow().client)
+        .filter(|id| state.client_set.contains(id))
+}
+
+/// Place the focused client in picture in picture mode, or restore the current picture in
+/// picture client if there is one.
+///
+/// See the [module level docs][self] for details.
+pub fn toggle_pip<X: XConn + 'static>(config: PipConfig) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        if let Some(pip) = state.remove_extension::<Pip>() {
+            state.remove_hook(PIP_HOOK);
+            if !state.client_set.contains(&pip.client) {
+                return Ok(());
+            }
+
+            info!(id = %pip.client, "exiting picture in picture mode");
+            return x.modify_and_refresh(state, |cs| match pip.floating {
+                Some(r) => {
+                    cs.floating.insert(pip.client, r);
+                }
+                None => {
+                    cs.sink(&pip.client);
+                }
+            });
+        }
+
+        let client = match state.client_set.current_client() {
+            Some(&id) => id,
+            None => return Ok(()),
+        };
+
+        info!(id = %client, "entering picture in picture mode");
+        let floating = state.client_set.floating.get(&client).copied();
+        state.add_extension(Pip { client, floating });
+        state.add_named_hook(
+            PIP_HOOK,
+            NamedHook::WorkspaceSwitch(Box::new(follow_screen)),
+        );
+
+        let r = config.rect_within(state.client_set.current_screen().workarea());
+        x.modify_and_refresh(state, |cs| {
+            _ = cs.float(client, r);
+        })?;
+
+        raise(client, state, x)
+    })
+}
+
+// Keep the picture in picture client on whichever workspace is shown on its screen, without
+// taking focus from the clients on that workspace.
+fn follow_screen<X: XConn>(
+    _: usize,
+    old: &str,
+    new: &str,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let client = match pip_client(state) {
+        Some(id) if state.client_set.tag_for_client(&id) == Some(old) => id,
+        _ => return Ok(()),
+    };
+
+    let cs = &mut state.client_set;
+    if let Some(ws) = cs.workspace_mut(old) {
+        ws.remove(&client);
+    }
+    if let Some(ws) = cs.workspace_mut(new) {
+        ws.stack = match ws.stack.take() {
+            Some(mut s) => {
+                s.insert_at(Position::Tail, client);
+                Some(s)
+            }
+            None => Some(stack!(client)),
+        };
+    }
+
+    x.refresh(state)?;
+    raise(client, state, x)
+}
+
+fn raise<X: XConn>(client: Xid, state: &State<X>, x: &X) -> Result<()> {
+    x.set_client_config(state.outer_window(client), &[ClientConfig::StackTop])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::actions::modify_with,
+        core::bindings::KeyCode,
+        x::{fixtures::ScriptedWm, mock::ScriptedConn, XEvent},
+    };
+    use simple_test_case::test_case;
+
+    const SCREEN: Rect = Rect {
+        x: 0,
+        y: 0,
+        w: 1000,
+        h: 800,
+    };
+
+    #[test_case(Corner::TopLeft, Rect::new(10, 10, 250, 200); "top left")]
+    #[test_case(Corner::TopRight, Rect::new(740, 10, 250, 200); "top right")]
+    #[test_case(Corner::BottomLeft, Rect::new(10, 590, 250, 200); "bottom left")]
+    #[test_case(Corner::BottomRight, Rect::new(740, 590, 250, 200); "bottom right")]
+    #[test]
+    fn rect_within(corner: Corner, expected: Rect) {
+        let config = PipConfig {
+            corner,
+            ..Default::default()
+        };
+
+        assert_eq!(config.rect_within(SCREEN), expected);
+    }
+
+    #[test]
+    fn pip_client_follows_workspace_switches() {
+        const PIP_KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+        const SWITCH_KEY: KeyCode = KeyCode { mask: 0, code: 43 };
+        let mut wm = ScriptedWm::new()
+            .with_clients(2)
+            .with_events([XEvent::KeyPress(PIP_KEY), XEvent::KeyPress(SWITCH_KEY)])
+            .with_key(PIP_KEY, toggle_pip(PipConfig::default()))
+            .with_key(SWITCH_KEY, modify_with(|cs| cs.focus_tag("2")))
+            .run();
+
+        let cs = &wm.state.client_set;
+        assert_eq!(pip_client(&wm.state), Some(Xid(2)));
+        assert_eq!(cs.tag_for_client(&Xid(2)), Some("2"));
+        assert_eq!(
+            cs.floating[&Xid(2)].applied_to(&SCREEN),
+            Rect::new(740, 590, 250, 200)
+        );
+
+        toggle_pip(PipConfig::default())
+            .call(&mut wm.state, &ScriptedConn::new(vec![]))
+            .unwrap();
+        assert_eq!(pip_client(&wm.state), None);
+        assert!(!wm.state.client_set.floating.contains_key(&Xid(2)));
+    }
+}
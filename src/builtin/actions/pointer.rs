@@ -0,0 +1,122 @@
+//! Actions for moving the mouse pointer out of the way and bringing it back again.
+//!
+//! These are intended for keyboard driven setups where the cursor is mostly a distraction:
+//! [banish_pointer] moves it to a corner of the focused screen, [recall_pointer] brings it
+//! back to the focused client and [focus_screen_under_pointer] focuses whichever screen the
+//! pointer is currently on.
+//!
+//! ```no_run
+//! # use penrose::{builtin::actions::pointer::*, map, x11rb::RustConn};
+//! # use penrose::pure::geometry::Corner;
+//! # use penrose::core::bindings::KeyEventHandler;
+//! # use std::collections::HashMap;
+//! let bindings: HashMap<String, Box<dyn KeyEventHandler<RustConn>>> = map! {
+//!     map_keys: |k: &str| k.to_owned();
+//!
+//!     "M-b" => banish_pointer(Corner::BottomRight),
+//!     "M-S-b" => recall_pointer(),
+//!     "M-A-b" => focus_screen_under_pointer(),
+//! };
+//! ```
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, handle::set_screen_from_point, State},
+    pure::geometry::Corner,
+    x::{XConn, XConnExt},
+};
+use tracing::debug;
+
+/// Move the pointer to the given corner of the focused screen.
+pub fn banish_pointer<X: XConn>(corner: Corner) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let p = state.focused_screen_rect().corner_point(corner);
+        debug!(?corner, ?p, "banishing pointer");
+
+        x.warp_pointer(state.root(), p.x as i16, p.y as i16)
+    })
+}
+
+/// Move the pointer back to the focused client, or the center of the focused screen if
+/// there is no focused client.
+pub fn recall_pointer<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state: &mut State<X>, x: &X| {
+        let index = state.client_set.current_screen().index();
+
+        x.warp_pointer_to_screen(state, index)
+    })
+}
+
+/// Focus the screen that currently contains the pointer.
+pub fn focus_screen_under_pointer<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state: &mut State<X>, x: &X| {
+        let p = x.cursor_position()?;
+
+        set_screen_from_point(p, state, x)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::WindowManager,
+        pure::geometry::{Point, Rect},
+        x::{
+            fixtures::ScriptedWm,
+            mock::{Request, ScriptedConn},
+        },
+        Xid,
+    };
+
+    fn screens() -> Vec<Rect> {
+        vec![Rect::new(0, 0, 1000, 800), Rect::new(1000, 0, 1000, 800)]
+    }
+
+    fn warps(x: &ScriptedConn) -> Vec<Request> {
+        x.requests()
+            .into_iter()
+            .filter(|r| matches!(r, Request::WarpPointer(..)))
+            .collect()
+    }
+
+    fn wm() -> WindowManager<ScriptedConn> {
+        ScriptedWm::new()
+            .with_conn(|_| ScriptedConn::new(screens()))
+            .run()
+    }
+
+    #[test]
+    fn banish_pointer_warps_to_the_corner_of_the_focused_screen() {
+        let mut wm = wm();
+        wm.state.client_set.focus_screen(1);
+        let x = &ScriptedConn::new(screens());
+
+        banish_pointer(Corner::BottomRight)
+            .call(&mut wm.state, x)
+            .unwrap();
+
+        let root = wm.state.root();
+        assert_eq!(warps(x), vec![Request::WarpPointer(root, 1999, 799)]);
+    }
+
+    #[test]
+    fn recall_pointer_warps_to_the_focused_client() {
+        let mut wm = wm();
+        wm.state.client_set.insert(Xid(1));
+        let x = &ScriptedConn::new(screens()).with_client(Xid(1), Rect::new(0, 0, 400, 300));
+
+        recall_pointer().call(&mut wm.state, x).unwrap();
+
+        assert_eq!(warps(x), vec![Request::WarpPointer(Xid(1), 200, 150)]);
+    }
+
+    #[test]
+    fn focus_screen_under_pointer_focuses_the_screen_containing_the_pointer() {
+        let mut wm = wm();
+        let x = &ScriptedConn::new(screens()).with_cursor_position(Point::new(1500, 400));
+
+        focus_screen_under_pointer().call(&mut wm.state, x).unwrap();
+
+        assert_eq!(wm.state.client_set.current_screen().index(), 1);
+    }
+}
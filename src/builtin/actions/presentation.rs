@@ -0,0 +1,155 @@
+//! A presentation mode for screen sharing and projectors.
+//!
+//! While active, gaps and window borders are removed and clients trying to steal focus are
+//! not marked as urgent (see [State::suppress_urgency]). Optionally, space reserved for status
+//! bars by [ReserveTop] is released and status bars that support it are hidden: status bars
+//! should check [PresentationMode::hides_bar] when deciding whether or not to be visible.
+//!
+//! Everything is restored to how it was when presentation mode is toggled off again.
+//!
+//!   [ReserveTop]: crate::builtin::layout::transformers::ReserveTop
+use crate::{
+    builtin::{actions::key_handler, layout::messages::ReserveSpace},
+    core::{bindings::KeyEventHandler, State},
+    x::{XConn, XConnExt},
+};
+use tracing::info;
+
+/// The settings saved when presentation mode is entered, stored as a [State] extension for as
+/// long as presentation mode is active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresentationMode {
+    hide_bar: bool,
+    border_width: u32,
+    outer_gap: u32,
+    inner_gap: u32,
+}
+
+impl PresentationMode {
+    /// Whether or not status bars should be hidden while presentation mode is active.
+    pub fn hides_bar(&self) -> bool {
+        self.hide_bar
+    }
+
+    /// The current presentation mode settings if presentation mode is active.
+    pub fn current<X: XConn>(state: &State<X>) -> Option<Self> {
+        state.extension::<Self>().ok().map(|p| *p.borrow())
+    }
+}
+
+/// Enter presentation mode if it is not currently active, otherwise exit it and restore the
+/// previous appearance settings.
+///
+/// See the [module level docs][self] for details.
+pub fn toggle_presentation_mode<X: XConn>(hide_bar: bool) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        match state.remove_extension::<PresentationMode>() {
+            Some(saved) => exit_presentation_mode(saved, state),
+            None => enter_presentation_mode(hide_bar, state),
+        }
+
+        x.refresh(state)
+    })
+}
+
+fn enter_presentation_mode<X: XConn>(hide_bar: bool, state: &mut State<X>) {
+    info!(%hide_bar, "entering presentation mode");
+    let theme = &state.config.theme;
+    state.add_extension(PresentationMode {
+        hide_bar,
+        border_width: theme.border_width,
+        outer_gap: theme.outer_gap,
+        inner_gap: theme.inner_gap,
+    });
+
+    state.set_border_width(0);
+    state.set_gaps(0, 0);
+    state.suppress_urgency(true);
+    if hide_bar {
+        broadcast_reserve_space(false, state);
+    }
+}
+
+fn exit_presentation_mode<X: XConn>(saved: PresentationMode, state: &mut State<X>) {
+    info!("exiting presentation mode");
+    state.set_border_width(saved.border_width);
+    state.set_gaps(saved.outer_gap, saved.inner_gap);
+    state.suppress_urgency(false);
+    if saved.hide_bar {
+        broadcast_reserve_space(true, state);
+    }
+}
+
+fn broadcast_reserve_space<X: XConn>(enabled: bool, state: &mut State<X>) {
+    for w in state.client_set.workspaces_mut() {
+        w.broadcast_message(ReserveSpace(enabled));
+    }
+}
+
+/// Whether or not presentation mode is currently active.
+pub fn is_presenting<X: XConn>(state: &State<X>) -> bool {
+    state.extension::<PresentationMode>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::layout::{transformers::ReserveTop, Monocle},
+        core::{bindings::KeyCode, Theme, WindowManager},
+        stack,
+        x::{
+            fixtures::{ScriptedWm, SCREEN},
+            mock::{Request, ScriptedConn},
+            ClientConfig, XEvent,
+        },
+        Xid,
+    };
+
+    const KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+
+    fn presentation_wm(presses: usize) -> WindowManager<ScriptedConn> {
+        let theme = Theme::default().with_gaps(10, 5);
+
+        ScriptedWm::new()
+            .with_clients(1)
+            .with_events((0..presses).map(|_| XEvent::KeyPress(KEY)))
+            .with_key(KEY, toggle_presentation_mode(true))
+            .with_config(|c| {
+                c.default_layouts = stack!(theme.gaps(ReserveTop::wrap(Monocle::boxed(), 20)));
+                c.theme = theme;
+            })
+            .run()
+    }
+
+    #[test]
+    fn presentation_mode_can_be_toggled() {
+        let initial = presentation_wm(0);
+        let presenting = presentation_wm(1);
+        let restored = presentation_wm(2);
+
+        assert!(is_presenting(&presenting.state));
+        assert!(PresentationMode::current(&presenting.state)
+            .unwrap()
+            .hides_bar());
+        assert!(presenting.state.urgency_suppressed);
+        assert_eq!(presenting.state.config.theme.border_width, 0);
+        assert_eq!(
+            presenting.state.diff.after.positions,
+            vec![(Xid(1), SCREEN)]
+        );
+        let requests = presenting.x().requests();
+        assert!(requests.contains(&Request::SetClientConfig(
+            Xid(1),
+            vec![ClientConfig::BorderPx(0)]
+        )));
+
+        assert!(!is_presenting(&restored.state));
+        assert!(!restored.state.urgency_suppressed);
+        assert_eq!(restored.state.config.theme, initial.state.config.theme);
+        assert_eq!(
+            restored.state.diff.after.positions,
+            initial.state.diff.after.positions
+        );
+    }
+}
@@ -44,6 +44,23 @@ msg!(Mirror);
 pub struct UnwrapTransformer;
 msg!(UnwrapTransformer);
 
+/// Set the outer and inner gap sizes (in pixels) used by a [crate::builtin::layout::transformers::Gaps]
+/// transformer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SetGaps {
+    /// The new outer gap size in pixels
+    pub outer_px: u32,
+    /// The new inner gap size in pixels
+    pub inner_px: u32,
+}
+msg!(SetGaps);
+
+/// Enable or disable the space reserved for status bars by a
+/// [crate::builtin::layout::transformers::ReserveTop] transformer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReserveSpace(pub bool);
+msg!(ReserveSpace);
+
 /// A [crate::core::layout::Message] sent when a [crate::core::layout::Layout] is no longer visible (e.g.
 /// Layout changed on a visible [crate::pure::Workspace] or the workspace itself becoming hidden).
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
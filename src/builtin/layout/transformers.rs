@@ -1,6 +1,7 @@
 //! Built-in layout transformers.
 use crate::{
-    core::layout::{Layout, LayoutTransformer},
+    builtin::layout::messages::{ReserveSpace, SetGaps},
+    core::layout::{Layout, LayoutTransformer, Message},
     pure::geometry::Rect,
     simple_transformer, Xid,
 };
@@ -111,6 +112,17 @@ impl LayoutTransformer for Gaps {
             .map(|(id, r)| (id, shrink(r, self.inner_px)))
             .collect()
     }
+
+    fn passthrough_message(&mut self, m: &Message) -> Option<Box<dyn Layout>> {
+        if let Some(&SetGaps { outer_px, inner_px }) = m.downcast_ref() {
+            self.outer_px = outer_px;
+            self.inner_px = inner_px;
+        } else if let Some(new) = self.layout.handle_message(m) {
+            self.layout = new;
+        }
+
+        None
+    }
 }
 
 /// Reserve `px` pixels at the top of the screen.
@@ -122,12 +134,18 @@ pub struct ReserveTop {
     pub layout: Box<dyn Layout>,
     /// The number of pixels to reserve at the top of the screen
     pub px: u32,
+    /// Whether the reserved space is currently in use (see [ReserveSpace])
+    pub enabled: bool,
 }
 
 impl ReserveTop {
     /// Wrap an existing [Layout] with the given reserved area.
     pub fn wrap(layout: Box<dyn Layout>, px: u32) -> Box<dyn Layout> {
-        Box::new(Self { layout, px })
+        Box::new(Self {
+            layout,
+            px,
+            enabled: true,
+        })
     }
 }
 
@@ -141,7 +159,7 @@ impl LayoutTransformer for ReserveTop {
     }
 
     fn transform_initial(&self, mut r: Rect) -> Rect {
-        if r.w == 0 || r.h == 0 {
+        if r.w == 0 || r.h == 0 || !self.enabled {
             return r;
         }
 
@@ -150,11 +168,22 @@ impl LayoutTransformer for ReserveTop {
 
         r
     }
+
+    fn passthrough_message(&mut self, m: &Message) -> Option<Box<dyn Layout>> {
+        if let Some(&ReserveSpace(enabled)) = m.downcast_ref() {
+            self.enabled = enabled;
+        } else if let Some(new) = self.layout.handle_message(m) {
+            self.layout = new;
+        }
+
+        None
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{builtin::layout::Monocle, core::layout::IntoMessage, pure::Stack};
     use simple_test_case::test_case;
 
     #[test_case(Rect::new(0, 0, 100, 200), Rect::new(0, 0, 100, 200); "fullscreen is idempotent")]
@@ -182,4 +211,35 @@ mod tests {
 
         assert_eq!(transformed, vec![(Xid(1), expected)]);
     }
+
+    #[test]
+    fn set_gaps_updates_gap_sizes() {
+        let mut l = Gaps::wrap(Monocle::boxed(), 0, 0);
+        l.handle_message(
+            &SetGaps {
+                outer_px: 10,
+                inner_px: 5,
+            }
+            .into_message(),
+        );
+
+        let s = Stack::new([], Xid(1), []);
+        let (_, positions) = l.layout(&s, Rect::new(0, 0, 100, 200));
+
+        assert_eq!(positions, vec![(Xid(1), Rect::new(15, 15, 70, 170))]);
+    }
+
+    #[test_case(true, Rect::new(0, 20, 100, 180); "enabled")]
+    #[test_case(false, Rect::new(0, 0, 100, 200); "disabled")]
+    #[test]
+    fn reserve_space_toggles_reserved_area(enabled: bool, expected: Rect) {
+        let mut l = ReserveTop::wrap(Monocle::boxed(), 20);
+        l.handle_message(&ReserveSpace(false).into_message());
+        l.handle_message(&ReserveSpace(enabled).into_message());
+
+        let s = Stack::new([], Xid(1), []);
+        let (_, positions) = l.layout(&s, Rect::new(0, 0, 100, 200));
+
+        assert_eq!(positions, vec![(Xid(1), expected)]);
+    }
 }
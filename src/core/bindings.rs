@@ -1,17 +1,17 @@
 //! Setting up and responding to user defined key/mouse bindings
 use crate::{
-    core::{State, Xid},
-    pure::geometry::Point,
-    x::XConn,
+    core::{hints::BindingHint, hooks::StateHook, State, Xid},
+    pure::geometry::{Point, Rect},
+    x::{Query, XConn},
     Error, Result,
 };
 #[cfg(feature = "keysyms")]
 use penrose_keysyms::XKeySym;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, convert::TryFrom, fmt, process::Command};
+use std::{collections::HashMap, convert::TryFrom, fmt, process::Command, time::Instant};
 use strum::{EnumIter, IntoEnumIterator};
-use tracing::trace;
+use tracing::{trace, warn};
 
 /// Run the xmodmap command to dump the system keymap table.
 ///
@@ -43,7 +43,7 @@ pub fn keycodes_from_xmodmap() -> Result<HashMap<String, u8>> {
     Ok(m)
 }
 
-fn parse_binding(pattern: &str, known_codes: &HashMap<String, u8>) -> Result<KeyCode> {
+pub(crate) fn parse_binding(pattern: &str, known_codes: &HashMap<String, u8>) -> Result<KeyCode> {
     let mut parts: Vec<&str> = pattern.split('-').collect();
     let name = parts.remove(parts.len() - 1);
 
@@ -70,6 +70,8 @@ fn parse_binding(pattern: &str, known_codes: &HashMap<String, u8>) -> Result<Key
 /// Parse string format key bindings into [KeyCode] based [KeyBindings] using
 /// the command line `xmodmap` utility.
 ///
+/// An error is returned if two of the given specs resolve to the same key.
+///
 /// See [keycodes_from_xmodmap] for details of how `xmodmap` is used.
 pub fn parse_keybindings_with_xmodmap<S, X>(
     str_bindings: HashMap<S, Box<dyn KeyEventHandler<X>>>,
@@ -80,12 +82,150 @@ where
 {
     let m = keycodes_from_xmodmap()?;
 
-    str_bindings
+    resolve_bindings(str_bindings, &m)
+}
+
+/// Parse string format key bindings into [KeyCode] based [KeyBindings] using the
+/// keyboard mapping reported by the given [XConn].
+///
+/// An error is returned if two of the given specs resolve to the same key.
+///
+/// See [Backend::keycodes][crate::backend::Backend::keycodes] for details of how the keyboard mapping is obtained.
+pub fn parse_keybindings<S, X>(
+    str_bindings: HashMap<S, Box<dyn KeyEventHandler<X>>>,
+    x: &X,
+) -> Result<KeyBindings<X>>
+where
+    S: AsRef<str>,
+    X: XConn,
+{
+    let m = x.keycodes()?;
+
+    resolve_bindings(str_bindings, &m)
+}
+
+// Parse string format key bindings, along with the bindings of any submaps they contain.
+fn resolve_bindings<S, X>(
+    str_bindings: HashMap<S, Box<dyn KeyEventHandler<X>>>,
+    known_codes: &HashMap<String, u8>,
+) -> Result<KeyBindings<X>>
+where
+    S: AsRef<str>,
+    X: XConn,
+{
+    let resolved = resolve_named_bindings(str_bindings, known_codes)?;
+
+    Ok(resolved.into_iter().map(|(_, k, v)| (k, v)).collect())
+}
+
+// Resolved key bindings along with the spec that each was parsed from
+type NamedBindings<X> = Vec<(String, KeyCode, Box<dyn KeyEventHandler<X>>)>;
+
+// Parse string format key bindings, keeping the spec that each binding was parsed from and
+// returning an error if two specs resolve to the same key.
+fn resolve_named_bindings<S, X>(
+    str_bindings: HashMap<S, Box<dyn KeyEventHandler<X>>>,
+    known_codes: &HashMap<String, u8>,
+) -> Result<NamedBindings<X>>
+where
+    S: AsRef<str>,
+    X: XConn,
+{
+    let mut seen: HashMap<KeyCode, String> = HashMap::new();
+    let mut resolved = Vec::with_capacity(str_bindings.len());
+
+    for (s, mut v) in str_bindings {
+        let spec = s.as_ref().to_owned();
+        let k = resolve_binding(&spec, &mut v, known_codes)?;
+
+        if let Some(other) = seen.insert(k, spec.clone()) {
+            let (first, second) = if other < spec {
+                (other, spec)
+            } else {
+                (spec, other)
+            };
+
+            return Err(Error::ConflictingKeyBindings { first, second });
+        }
+
+        resolved.push((spec, k, v));
+    }
+
+    Ok(resolved)
+}
+
+// Parse a single string format key binding, resolving the bindings of its submap if it has one.
+pub(crate) fn resolve_binding<X: XConn>(
+    pattern: &str,
+    handler: &mut Box<dyn KeyEventHandler<X>>,
+    known_codes: &HashMap<String, u8>,
+) -> Result<KeyCode> {
+    if let Some(submap) = handler.submap() {
+        submap.resolve(known_codes)?;
+    }
+
+    parse_binding(pattern, known_codes)
+}
+
+/// A pending change to the default key bindings, requested through [State].
+#[derive(Debug)]
+pub(crate) enum BindingChange<X: XConn> {
+    Bind(String, Box<dyn KeyEventHandler<X>>),
+    Unbind(String),
+}
+
+/// Re-resolve the key codes of existing bindings after the keyboard mapping has changed.
+///
+/// Each binding is mapped back to the key name it was bound to under the old mapping and
+/// then looked up in the new one. Bindings that can not be resolved are left untouched.
+pub(crate) fn remap_key_bindings<V>(
+    bindings: HashMap<KeyCode, V>,
+    old_codes: &HashMap<String, u8>,
+    new_codes: &HashMap<String, u8>,
+) -> HashMap<KeyCode, V> {
+    bindings
         .into_iter()
-        .map(|(s, v)| parse_binding(s.as_ref(), &m).map(|k| (k, v)))
+        .map(|(k, v)| {
+            let mut names: Vec<&String> = old_codes
+                .iter()
+                .filter(|&(_, &code)| code == k.code)
+                .map(|(name, _)| name)
+                .collect();
+            names.sort();
+
+            let code = names
+                .into_iter()
+                .find_map(|name| new_codes.get(name))
+                .copied()
+                .unwrap_or(k.code);
+
+            if code != k.code {
+                trace!(from = k.code, to = code, "remapping keybinding");
+            }
+
+            (KeyCode { mask: k.mask, code }, v)
+        })
         .collect()
 }
 
+/// Re-resolve the key codes of the bindings in any submaps after the keyboard mapping
+/// has changed.
+///
+/// See [remap_key_bindings] for details of how bindings are re-resolved.
+pub(crate) fn remap_submaps<X: XConn>(
+    bindings: &mut KeyBindings<X>,
+    old_codes: &HashMap<String, u8>,
+    new_codes: &HashMap<String, u8>,
+) {
+    for handler in bindings.values_mut() {
+        if let Some(submap) = handler.submap() {
+            let inner = std::mem::take(&mut submap.bindings);
+            submap.bindings = remap_key_bindings(inner, old_codes, new_codes);
+            remap_submaps(&mut submap.bindings, old_codes, new_codes);
+        }
+    }
+}
+
 /// Some action to be run by a user key binding
 pub trait KeyEventHandler<X>
 where
@@ -93,6 +233,21 @@ where
 {
     /// Call this handler with the current window manager state
     fn call(&mut self, state: &mut State<X>, x: &X) -> Result<()>;
+
+    /// The [Submap] entered by this binding if it is the prefix of a key chord.
+    ///
+    /// The default implementation returns `None`.
+    fn submap(&mut self) -> Option<&mut Submap<X>> {
+        None
+    }
+
+    /// A human readable description of what this binding does.
+    ///
+    /// The default implementation returns `None`. See [describe][crate::builtin::actions::describe]
+    /// for adding a description to an existing handler.
+    fn description(&self) -> Option<&str> {
+        None
+    }
 }
 
 impl<X: XConn> fmt::Debug for Box<dyn KeyEventHandler<X>> {
@@ -114,6 +269,540 @@ where
 /// User defined key bindings
 pub type KeyBindings<X> = HashMap<KeyCode, Box<dyn KeyEventHandler<X>>>;
 
+/// A [KeyEventHandler] with a human readable description attached.
+///
+/// See [describe][crate::builtin::actions::describe].
+pub struct Described<X>
+where
+    X: XConn,
+{
+    description: String,
+    inner: Box<dyn KeyEventHandler<X>>,
+}
+
+impl<X: XConn> fmt::Debug for Described<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Described")
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+impl<X: XConn> Described<X> {
+    /// Attach a description to an existing handler.
+    pub fn new(description: impl Into<String>, inner: Box<dyn KeyEventHandler<X>>) -> Self {
+        Self {
+            description: description.into(),
+            inner,
+        }
+    }
+}
+
+impl<X: XConn> KeyEventHandler<X> for Described<X> {
+    fn call(&mut self, state: &mut State<X>, x: &X) -> Result<()> {
+        self.inner.call(state, x)
+    }
+
+    fn submap(&mut self) -> Option<&mut Submap<X>> {
+        self.inner.submap()
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some(&self.description)
+    }
+}
+
+/// A human readable description of a single key binding, for generating a cheatsheet of the
+/// available bindings.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BindingDescription {
+    /// The sequence of key specs needed to trigger the binding: bindings within a [Submap]
+    /// are preceded by the prefix keys of the submap
+    pub keys: Vec<String>,
+    /// The description of the binding, if it has one
+    pub description: Option<String>,
+}
+
+/// Describe each of the given string format key bindings (including the bindings of any
+/// submaps they contain) in order to generate a cheatsheet, sorted by key spec.
+///
+/// ```
+/// # use penrose::{builtin::actions::{describe, spawn}, core::bindings::*, map, x11rb::RustConn};
+/// # use std::collections::HashMap;
+/// let mut bindings: HashMap<&str, Box<dyn KeyEventHandler<RustConn>>> = map! {
+///     "M-Return" => describe("open a terminal", spawn("st")),
+///     "M-semicolon" => spawn("dmenu_run"),
+/// };
+///
+/// for d in describe_bindings(&mut bindings) {
+///     println!("{:<20} {}", d.keys.join(" "), d.description.unwrap_or_default());
+/// }
+/// ```
+pub fn describe_bindings<S, X>(
+    str_bindings: &mut HashMap<S, Box<dyn KeyEventHandler<X>>>,
+) -> Vec<BindingDescription>
+where
+    S: AsRef<str>,
+    X: XConn,
+{
+    let mut descriptions = Vec::new();
+    for (spec, handler) in str_bindings.iter_mut() {
+        push_descriptions(vec![spec.as_ref().to_owned()], handler, &mut descriptions);
+    }
+    descriptions.sort();
+
+    descriptions
+}
+
+fn push_descriptions<X: XConn>(
+    keys: Vec<String>,
+    handler: &mut Box<dyn KeyEventHandler<X>>,
+    descriptions: &mut Vec<BindingDescription>,
+) {
+    let description = handler.description().map(|s| s.to_owned());
+
+    if let Some(submap) = handler.submap() {
+        for (spec, inner) in submap.named_bindings_mut() {
+            let mut keys = keys.clone();
+            keys.push(spec);
+            push_descriptions(keys, inner, descriptions);
+        }
+    }
+
+    descriptions.push(BindingDescription { keys, description });
+}
+
+/// A set of key bindings that is entered by pressing a prefix key, allowing for multi-key
+/// sequences ("key chords") such as `M-a` followed by `g`.
+///
+/// While a submap is active the keyboard is grabbed so that the next key press is looked up
+/// in the submap rather than being sent to the focused client. Pressing `Escape`, pressing a
+/// key that has no binding in the submap or waiting longer than
+/// [Config::chord_timeout][crate::core::Config::chord_timeout] cancels the chord. Submaps
+/// may be nested in order to bind longer sequences.
+///
+/// The bindings of a submap are given in the same string format as top level bindings and
+/// are resolved to [KeyCode]s along with them by [parse_keybindings] or
+/// [parse_keybindings_with_xmodmap].
+pub struct Submap<X>
+where
+    X: XConn,
+{
+    unresolved: HashMap<String, Box<dyn KeyEventHandler<X>>>,
+    bindings: KeyBindings<X>,
+    names: HashMap<KeyCode, String>,
+}
+
+impl<X: XConn> fmt::Debug for Submap<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Submap")
+            .field("unresolved", &self.unresolved.keys().collect::<Vec<_>>())
+            .field("bindings", &self.bindings.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<X: XConn> Submap<X> {
+    /// Create a new submap from string format key bindings.
+    pub fn new<S>(str_bindings: HashMap<S, Box<dyn KeyEventHandler<X>>>) -> Self
+    where
+        S: AsRef<str>,
+    {
+        Self {
+            unresolved: str_bindings
+                .into_iter()
+                .map(|(s, v)| (s.as_ref().to_string(), v))
+                .collect(),
+            bindings: HashMap::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Create a new submap from bindings that have already been resolved to [KeyCode]s.
+    pub fn from_key_bindings(bindings: KeyBindings<X>) -> Self {
+        Self {
+            unresolved: HashMap::new(),
+            bindings,
+            names: HashMap::new(),
+        }
+    }
+
+    /// The resolved bindings for this submap.
+    pub fn bindings(&self) -> &KeyBindings<X> {
+        &self.bindings
+    }
+
+    pub(crate) fn bindings_mut(&mut self) -> &mut KeyBindings<X> {
+        &mut self.bindings
+    }
+
+    /// The key spec used for the given binding in this submap, if it was bound by name.
+    pub fn name_for(&self, key: &KeyCode) -> Option<&str> {
+        self.names.get(key).map(|s| s.as_str())
+    }
+
+    // The bindings of this submap along with the spec used for each of them, falling back to
+    // a description of the raw key code for bindings that were not bound by name.
+    fn named_bindings_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (String, &mut Box<dyn KeyEventHandler<X>>)> {
+        let names = &self.names;
+        let resolved = self.bindings.iter_mut().map(move |(k, v)| {
+            let name = match names.get(k) {
+                Some(name) => name.clone(),
+                None => format!("{k:?}"),
+            };
+
+            (name, v)
+        });
+
+        self.unresolved
+            .iter_mut()
+            .map(|(s, v)| (s.clone(), v))
+            .chain(resolved)
+    }
+
+    fn resolve(&mut self, known_codes: &HashMap<String, u8>) -> Result<()> {
+        let unresolved = std::mem::take(&mut self.unresolved);
+
+        for (spec, k, v) in resolve_named_bindings(unresolved, known_codes)? {
+            // Modifier keys are skipped and Escape cancels the chord while a submap is active
+            let escape = known_codes.get("Escape") == Some(&k.code);
+            if escape || is_modifier_key(k.code, known_codes) {
+                warn!(%spec, "binding in submap is shadowed and will never be run");
+            }
+
+            self.bindings.insert(k, v);
+            self.names.insert(k, spec);
+        }
+
+        Ok(())
+    }
+}
+
+/// A named set of key bindings that replaces the default key bindings while it is active,
+/// similar to modes in i3.
+///
+/// Modes are added to a [WindowManager][crate::core::WindowManager] using
+/// [add_mode][crate::core::WindowManager::add_mode] and are entered and exited using
+/// [State::enter_mode] and [State::exit_mode]. Only the bindings of the active mode are
+/// grabbed while it is active so you will want to include a binding to exit the mode.
+/// Hooks can be set to run when the mode is entered or exited in order to update a status
+/// bar or on screen indicator.
+pub struct Mode<X>
+where
+    X: XConn,
+{
+    name: String,
+    pub(crate) bindings: KeyBindings<X>,
+    pub(crate) on_enter: Option<Box<dyn StateHook<X>>>,
+    pub(crate) on_exit: Option<Box<dyn StateHook<X>>>,
+    pub(crate) count_prefix: bool,
+}
+
+impl<X: XConn> fmt::Debug for Mode<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mode")
+            .field("name", &self.name)
+            .field("bindings", &self.bindings.keys().collect::<Vec<_>>())
+            .field("count_prefix", &self.count_prefix)
+            .finish()
+    }
+}
+
+impl<X: XConn> Mode<X> {
+    /// Create a new mode with the given name and key bindings.
+    pub fn new(name: impl Into<String>, bindings: KeyBindings<X>) -> Self {
+        Self {
+            name: name.into(),
+            bindings,
+            on_enter: None,
+            on_exit: None,
+            count_prefix: false,
+        }
+    }
+
+    /// Set a hook to run each time this mode is entered.
+    pub fn on_enter<H>(mut self, hook: H) -> Self
+    where
+        H: StateHook<X> + 'static,
+    {
+        self.on_enter = Some(hook.boxed());
+        self
+    }
+
+    /// Set a hook to run each time this mode is exited.
+    pub fn on_exit<H>(mut self, hook: H) -> Self
+    where
+        H: StateHook<X> + 'static,
+    {
+        self.on_exit = Some(hook.boxed());
+        self
+    }
+
+    /// Allow a numeric count to be typed before running a binding in this mode.
+    ///
+    /// While the mode is active, pressing digit keys that are not bound in the mode builds up
+    /// a count that is available to the next binding that is run using [State::count], in the
+    /// same way as counts in vim. See [repeat][crate::builtin::actions::repeat] for running an
+    /// action the given number of times. Pressing an unbound key discards the count.
+    pub fn with_count_prefix(mut self) -> Self {
+        self.count_prefix = true;
+        self
+    }
+
+    /// The name of this mode.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The key bindings that are active while in this mode.
+    pub fn bindings(&self) -> &KeyBindings<X> {
+        &self.bindings
+    }
+}
+
+/// A set of key bindings that take precedence over the default key bindings while the
+/// focused client matches a [Query].
+///
+/// Client bindings are added to a [WindowManager][crate::core::WindowManager] using
+/// [add_client_bindings][crate::core::WindowManager::add_client_bindings] and are checked
+/// in the order they were added each time a key is pressed. Keys that are not bound for
+/// the focused client fall through to the default key bindings. Client bindings are not
+/// used while a binding [Mode] is active.
+pub struct ClientBindings<X>
+where
+    X: XConn,
+{
+    query: Box<dyn Query<X>>,
+    pub(crate) bindings: KeyBindings<X>,
+}
+
+impl<X: XConn> fmt::Debug for ClientBindings<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientBindings")
+            .field("bindings", &self.bindings.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<X: XConn> ClientBindings<X> {
+    /// Create a new set of key bindings that are active while the focused client matches
+    /// the given query.
+    pub fn new<Q>(query: Q, bindings: KeyBindings<X>) -> Self
+    where
+        Q: Query<X> + 'static,
+    {
+        Self {
+            query: Box::new(query),
+            bindings,
+        }
+    }
+
+    /// The key bindings that are active while the focused client matches.
+    pub fn bindings(&self) -> &KeyBindings<X> {
+        &self.bindings
+    }
+
+    // Whether these bindings bind the given key and apply to the given client
+    pub(crate) fn applies_to(&self, key: &KeyCode, client: Xid, x: &X) -> bool {
+        if !self.bindings.contains_key(key) {
+            return false;
+        }
+
+        match self.query.run(client, x) {
+            Ok(matches) => matches,
+            Err(e) => {
+                warn!(%e, %client, "unable to run query for client key bindings");
+                false
+            }
+        }
+    }
+}
+
+/// The key names of modifier keys, which are ignored while waiting for the next key of a
+/// key chord so that modified keys can be used within submaps.
+const MODIFIER_KEY_NAMES: &[&str] = &[
+    "Shift_L",
+    "Shift_R",
+    "Control_L",
+    "Control_R",
+    "Alt_L",
+    "Alt_R",
+    "Meta_L",
+    "Meta_R",
+    "Super_L",
+    "Super_R",
+    "Hyper_L",
+    "Hyper_R",
+    "ISO_Level3_Shift",
+    "Mode_switch",
+    "Caps_Lock",
+    "Num_Lock",
+];
+
+/// A key chord that is waiting for its next key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Chord {
+    /// The index of the [ClientBindings] the chord was started from, if it was not started
+    /// from the default bindings
+    pub(crate) client_bindings: Option<usize>,
+    /// The prefix keys pressed so far, identifying the active [Submap]
+    pub(crate) path: Vec<KeyCode>,
+    /// When the chord should be cancelled if no further keys have been pressed
+    pub(crate) deadline: Instant,
+}
+
+/// Whether or not the given key code is a modifier key under the given keyboard mapping.
+pub(crate) fn is_modifier_key(code: KeyCodeValue, known_codes: &HashMap<String, u8>) -> bool {
+    MODIFIER_KEY_NAMES
+        .iter()
+        .any(|&name| known_codes.get(name) == Some(&code))
+}
+
+/// The key names of modifier keys that set one of the [ModifierKey] masks under the default
+/// X modifier mapping.
+const MODIFIER_KEY_MASKS: &[(&str, ModifierKey)] = &[
+    ("Shift_L", ModifierKey::Shift),
+    ("Shift_R", ModifierKey::Shift),
+    ("Control_L", ModifierKey::Ctrl),
+    ("Control_R", ModifierKey::Ctrl),
+    ("Alt_L", ModifierKey::Alt),
+    ("Alt_R", ModifierKey::Alt),
+    ("Meta_L", ModifierKey::Alt),
+    ("Meta_R", ModifierKey::Alt),
+    ("Super_L", ModifierKey::Meta),
+    ("Super_R", ModifierKey::Meta),
+    ("Hyper_L", ModifierKey::Meta),
+    ("Hyper_R", ModifierKey::Meta),
+];
+
+/// The modifier mask set by the given key code under the given keyboard mapping, if it is a
+/// modifier key.
+pub(crate) fn modifier_key_mask(
+    code: KeyCodeValue,
+    known_codes: &HashMap<String, u8>,
+) -> Option<KeyCodeMask> {
+    MODIFIER_KEY_MASKS
+        .iter()
+        .find(|&&(name, _)| known_codes.get(name) == Some(&code))
+        .map(|&(_, m)| u16::from(m))
+}
+
+/// A handler waiting to be run once the modifiers held when it was registered have been
+/// released.
+#[derive(Debug)]
+pub(crate) struct ModifierRelease<X: XConn> {
+    /// The modifiers that need to be released
+    pub(crate) mask: KeyCodeMask,
+    /// Whether or not the keyboard has been grabbed so we are notified of key releases
+    pub(crate) grabbed: bool,
+    pub(crate) handler: Box<dyn KeyEventHandler<X>>,
+}
+
+impl<X: XConn> ModifierRelease<X> {
+    pub(crate) fn new(mask: KeyCodeMask, handler: Box<dyn KeyEventHandler<X>>) -> Self {
+        let modifiers = ModifierKey::iter().fold(0, |acc, m| acc | u16::from(m));
+
+        Self {
+            mask: mask & modifiers,
+            grabbed: false,
+            handler,
+        }
+    }
+}
+
+/// Look up the bindings of the [Submap] reached by following the given path of prefix keys.
+pub(crate) fn submap_bindings<'a, X: XConn>(
+    bindings: &'a mut KeyBindings<X>,
+    path: &[KeyCode],
+) -> Option<&'a mut KeyBindings<X>> {
+    let mut current = bindings;
+    for key in path.iter() {
+        current = current.get_mut(key)?.submap()?.bindings_mut();
+    }
+
+    Some(current)
+}
+
+/// The [BindingHint]s to display for the given bindings, sorted by key.
+///
+/// Bindings without a name in `names` are named by reverse lookup of their key code.
+pub(crate) fn binding_hints<X: XConn>(
+    bindings: &mut KeyBindings<X>,
+    names: &HashMap<KeyCode, String>,
+    known_codes: &HashMap<String, u8>,
+) -> Vec<BindingHint> {
+    let mut hints: Vec<BindingHint> = bindings
+        .iter_mut()
+        .map(|(k, v)| {
+            let key = match names.get(k) {
+                Some(name) => name.clone(),
+                None => key_code_name(k, known_codes),
+            };
+            let description = match v.description().map(|s| s.to_string()) {
+                Some(desc) => desc,
+                None if v.submap().is_some() => "+prefix".to_string(),
+                None => String::new(),
+            };
+
+            BindingHint { key, description }
+        })
+        .collect();
+
+    hints.sort();
+
+    hints
+}
+
+/// The [BindingHint]s for the [Submap] reached by following the given (non-empty) path of
+/// prefix keys.
+pub(crate) fn submap_hints<X: XConn>(
+    bindings: &mut KeyBindings<X>,
+    path: &[KeyCode],
+    known_codes: &HashMap<String, u8>,
+) -> Option<Vec<BindingHint>> {
+    let (last, prefix) = path.split_last()?;
+    let submap = submap_bindings(bindings, prefix)?.get_mut(last)?.submap()?;
+
+    Some(binding_hints(
+        &mut submap.bindings,
+        &submap.names,
+        known_codes,
+    ))
+}
+
+// A key spec for the given key code under the current keyboard mapping. Where multiple key
+// names share a code we prefer the shortest, lower case name.
+fn key_code_name(k: &KeyCode, known_codes: &HashMap<String, u8>) -> String {
+    let modifiers: String = ModifierKey::iter()
+        .filter(|m| m.was_held(k.mask))
+        .map(|m| format!("{}-", m.spec_char()))
+        .collect();
+
+    let name = known_codes
+        .iter()
+        .filter(|&(_, &code)| code == k.code)
+        .map(|(name, _)| name)
+        .min_by_key(|name| (name.len(), name.chars().any(char::is_uppercase), *name));
+
+    match name {
+        Some(name) => format!("{modifiers}{name}"),
+        None => format!("{modifiers}<{}>", k.code),
+    }
+}
+
+impl<X: XConn> KeyEventHandler<X> for Submap<X> {
+    // Entering the submap is handled by the window manager itself
+    fn call(&mut self, _: &mut State<X>, _: &X) -> Result<()> {
+        Ok(())
+    }
+
+    fn submap(&mut self) -> Option<&mut Submap<X>> {
+        Some(self)
+    }
+}
+
 /// An action to be run in response to a mouse event
 pub trait MouseEventHandler<X>
 where
@@ -139,6 +828,79 @@ where
     }
 }
 
+/// A handler for an in progress mouse drag, started from a [MouseEventHandler] using
+/// [State::start_drag].
+///
+/// While a drag is active the pointer is grabbed and all pointer motion is reported to the
+/// drag handler rather than to any mouse bindings until a mouse button is released.
+pub trait DragHandler<X>
+where
+    X: XConn,
+{
+    /// Called for each motion of the pointer with the change in position since the previous
+    /// motion (or the start of the drag).
+    fn motion(
+        &mut self,
+        dx: i32,
+        dy: i32,
+        evt: &MouseEvent,
+        state: &mut State<X>,
+        x: &X,
+    ) -> Result<()>;
+
+    /// Called once when a mouse button is released, ending the drag.
+    ///
+    /// The default implementation does nothing.
+    fn release(&mut self, evt: &MouseEvent, state: &mut State<X>, x: &X) -> Result<()> {
+        let _ = (evt, state, x);
+        Ok(())
+    }
+}
+
+impl<X: XConn> fmt::Debug for Box<dyn DragHandler<X>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DragHandler").finish()
+    }
+}
+
+impl<F, X> DragHandler<X> for F
+where
+    F: FnMut(i32, i32, &MouseEvent, &mut State<X>, &X) -> Result<()>,
+    X: XConn,
+{
+    fn motion(
+        &mut self,
+        dx: i32,
+        dy: i32,
+        evt: &MouseEvent,
+        state: &mut State<X>,
+        x: &X,
+    ) -> Result<()> {
+        (self)(dx, dy, evt, state, x)
+    }
+}
+
+/// A mouse drag that is in progress.
+#[derive(Debug)]
+pub(crate) struct ActiveDrag<X: XConn> {
+    pub(crate) handler: Box<dyn DragHandler<X>>,
+    /// The mouse state that started the drag
+    pub(crate) mouse_state: MouseState,
+    /// The last known position of the pointer
+    pub(crate) last: Point,
+    /// Whether or not the pointer has been grabbed for the drag
+    pub(crate) grabbed: bool,
+}
+
+/// The most recent mouse button press, used for detecting multiple clicks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LastClick {
+    pub(crate) id: Xid,
+    pub(crate) button: MouseButton,
+    pub(crate) at: Instant,
+    pub(crate) clicks: u8,
+}
+
 /// User defined mouse bindings
 pub type MouseBindings<X> = HashMap<(MouseEventKind, MouseState), Box<dyn MouseEventHandler<X>>>;
 
@@ -219,6 +981,164 @@ impl KeyCode {
     }
 }
 
+/// A key binding specification such as `"M-S-Return"` that has been checked at compile time.
+///
+/// The modifiers and overall shape of the spec are validated when the spec is parsed, which
+/// happens during compilation when using the [key][crate::key] and [keymap][crate::keymap]
+/// macros. With the `keysyms` feature enabled the key name is also checked against the known
+/// X keysym names, otherwise it is only checked at runtime. In either case the name depends on
+/// the keyboard mapping of the running X server and is only resolved to a [KeyCode] once that
+/// is available (see [KeySpec::resolve]).
+///
+/// As a [KeySpec] can be used as a string, maps of specs can be passed directly to
+/// [parse_keybindings] and [parse_keybindings_with_xmodmap].
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct KeySpec {
+    spec: &'static str,
+    mask: KeyCodeMask,
+    name_start: usize,
+}
+
+impl KeySpec {
+    /// Parse a key binding spec of the form `"M-S-Return"`.
+    ///
+    /// Modifiers are given as `C` (control), `A` (alt), `S` (shift) and `M` (meta), each
+    /// followed by a `-`, and must come before the name of the key being bound.
+    ///
+    /// # Panics
+    /// This function will panic if the spec is malformed. When evaluated in a const context
+    /// (as it is by the [key][crate::key] macro) this results in a compile error.
+    pub const fn parse(spec: &'static str) -> Self {
+        match Self::try_parse(spec) {
+            Ok(k) => k,
+            Err(msg) => panic!("{}", msg),
+        }
+    }
+
+    const fn try_parse(spec: &'static str) -> std::result::Result<Self, &'static str> {
+        let bytes = spec.as_bytes();
+        let mut mask = 0;
+        let mut start = 0;
+
+        loop {
+            let mut end = start;
+            while end < bytes.len() && bytes[end] != b'-' {
+                end += 1;
+            }
+
+            if end == bytes.len() {
+                break;
+            } else if end - start != 1 {
+                return Err("invalid key spec: modifiers must be one of C, A, S or M");
+            }
+
+            let bit = match ModifierKey::from_spec_char(bytes[start]) {
+                Some(m) => m.mask(),
+                None => return Err("invalid key spec: modifiers must be one of C, A, S or M"),
+            };
+
+            if mask & bit != 0 {
+                return Err("invalid key spec: duplicate modifier");
+            }
+
+            mask |= bit;
+            start = end + 1;
+        }
+
+        if start == bytes.len() {
+            return Err("invalid key spec: missing key name");
+        }
+
+        let mut i = start;
+        while i < bytes.len() {
+            if !bytes[i].is_ascii_graphic() {
+                return Err("invalid key spec: key names must be printable ascii");
+            }
+            i += 1;
+        }
+
+        if !is_known_key_name(bytes, start) {
+            return Err("invalid key spec: unknown key name");
+        }
+
+        Ok(Self {
+            spec,
+            mask,
+            name_start: start,
+        })
+    }
+
+    /// The modifier mask required by this spec
+    pub fn mask(&self) -> KeyCodeMask {
+        self.mask
+    }
+
+    /// The name of the key being bound, without any modifiers
+    pub fn name(&self) -> &'static str {
+        &self.spec[self.name_start..]
+    }
+
+    /// Resolve this spec to a [KeyCode] using the given keyboard mapping.
+    pub fn resolve(&self, known_codes: &HashMap<String, u8>) -> Result<KeyCode> {
+        match known_codes.get(self.name()) {
+            Some(&code) => Ok(KeyCode {
+                mask: self.mask,
+                code,
+            }),
+            None => Err(Error::UnknownKeyName {
+                name: self.name().to_owned(),
+            }),
+        }
+    }
+}
+
+// Check the name starting at the given offset into a key spec against the known keysym names.
+// This needs to be a const fn so we have to compare the raw bytes ourselves.
+#[cfg(feature = "keysyms")]
+const fn is_known_key_name(spec: &[u8], start: usize) -> bool {
+    let len = spec.len() - start;
+    let mut i = 0;
+
+    'names: while i < penrose_keysyms::KEYSYM_NAMES.len() {
+        let name = penrose_keysyms::KEYSYM_NAMES[i].as_bytes();
+        i += 1;
+        if name.len() != len {
+            continue;
+        }
+
+        let mut j = 0;
+        while j < len {
+            if name[j] != spec[start + j] {
+                continue 'names;
+            }
+            j += 1;
+        }
+
+        return true;
+    }
+
+    false
+}
+
+// Without the keysyms feature we have no list of names to check against so key names are
+// only validated when they are resolved at runtime.
+#[cfg(not(feature = "keysyms"))]
+const fn is_known_key_name(_spec: &[u8], _start: usize) -> bool {
+    true
+}
+
+impl AsRef<str> for KeySpec {
+    fn as_ref(&self) -> &str {
+        self.spec
+    }
+}
+
+impl fmt::Display for KeySpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.spec)
+    }
+}
+
 /// Known mouse buttons for binding actions
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -233,6 +1153,10 @@ pub enum MouseButton {
     ScrollUp,
     /// 5
     ScrollDown,
+    /// 6
+    ScrollLeft,
+    /// 7
+    ScrollRight,
 }
 
 impl From<MouseButton> for u8 {
@@ -243,6 +1167,8 @@ impl From<MouseButton> for u8 {
             MouseButton::Right => 3,
             MouseButton::ScrollUp => 4,
             MouseButton::ScrollDown => 5,
+            MouseButton::ScrollLeft => 6,
+            MouseButton::ScrollRight => 7,
         }
     }
 }
@@ -257,6 +1183,8 @@ impl TryFrom<u8> for MouseButton {
             3 => Ok(Self::Right),
             4 => Ok(Self::ScrollUp),
             5 => Ok(Self::ScrollDown),
+            6 => Ok(Self::ScrollLeft),
+            7 => Ok(Self::ScrollRight),
             _ => Err(Error::UnknownMouseButton { button: n }),
         }
     }
@@ -280,16 +1208,39 @@ impl ModifierKey {
     fn was_held(&self, mask: u16) -> bool {
         mask & u16::from(*self) > 0
     }
-}
 
-impl From<ModifierKey> for u16 {
-    fn from(m: ModifierKey) -> u16 {
-        (match m {
+    const fn mask(self) -> u16 {
+        match self {
             ModifierKey::Shift => 1 << 0,
             ModifierKey::Ctrl => 1 << 2,
             ModifierKey::Alt => 1 << 3,
             ModifierKey::Meta => 1 << 6,
-        }) as u16
+        }
+    }
+
+    const fn from_spec_char(c: u8) -> Option<Self> {
+        match c {
+            b'C' => Some(Self::Ctrl),
+            b'A' => Some(Self::Alt),
+            b'S' => Some(Self::Shift),
+            b'M' => Some(Self::Meta),
+            _ => None,
+        }
+    }
+
+    const fn spec_char(self) -> char {
+        match self {
+            ModifierKey::Ctrl => 'C',
+            ModifierKey::Alt => 'A',
+            ModifierKey::Shift => 'S',
+            ModifierKey::Meta => 'M',
+        }
+    }
+}
+
+impl From<ModifierKey> for u16 {
+    fn from(m: ModifierKey) -> u16 {
+        m.mask()
     }
 }
 
@@ -315,13 +1266,35 @@ pub struct MouseState {
     pub button: MouseButton,
     /// All [ModifierKey]s being held
     pub modifiers: Vec<ModifierKey>,
+    /// The number of consecutive presses of the button, such as 2 for a double click.
+    ///
+    /// Presses count as consecutive if they are made on the same window within
+    /// [Config::double_click_timeout][crate::core::Config::double_click_timeout] of each
+    /// other. Only press events are counted: this is always 1 for release and motion events.
+    pub clicks: u8,
 }
 
 impl MouseState {
-    /// Construct a new MouseState
+    /// Construct a new MouseState for a single click
     pub fn new(button: MouseButton, mut modifiers: Vec<ModifierKey>) -> Self {
         modifiers.sort();
-        Self { button, modifiers }
+        Self {
+            button,
+            modifiers,
+            clicks: 1,
+        }
+    }
+
+    /// Set the number of consecutive clicks this MouseState matches.
+    ///
+    /// Bindings for multiple clicks take precedence over the single click binding for the same
+    /// button and modifiers, which still runs for the first click. Double clicking on a
+    /// titlebar with no modifiers held will run a binding set for
+    /// `MouseState::new(MouseButton::Left, vec![]).with_clicks(2)` in place of focusing the
+    /// client.
+    pub fn with_clicks(mut self, clicks: u8) -> Self {
+        self.clicks = clicks;
+        self
     }
 
     /// Parse raw mouse state values into a [MouseState]
@@ -329,6 +1302,7 @@ impl MouseState {
         Ok(Self {
             button: MouseButton::try_from(detail)?,
             modifiers: ModifierKey::iter().filter(|m| m.was_held(state)).collect(),
+            clicks: 1,
         })
     }
 
@@ -371,6 +1345,11 @@ pub struct MouseEvent {
     pub state: MouseState,
     /// Was this press, release or motion?
     pub kind: MouseEventKind,
+    /// The edge of the client's border that the event landed on, if any.
+    ///
+    /// This is only set for events dispatched to border bindings (see
+    /// [WindowManager::add_border_bindings][crate::core::WindowManager::add_border_bindings]).
+    pub border: Option<BorderEdge>,
 }
 
 impl MouseEvent {
@@ -390,6 +1369,688 @@ impl MouseEvent {
             wpt: Point::new(ex as u32, ey as u32),
             state,
             kind,
+            border: None,
+        }
+    }
+}
+
+// The distance from a corner of a window's border within which a press on the border counts as
+// being on the corner rather than the edge
+const BORDER_CORNER_SIZE: u32 = 16;
+
+/// An edge or corner of a client's border.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BorderEdge {
+    /// The top edge
+    Top,
+    /// The bottom edge
+    Bottom,
+    /// The left edge
+    Left,
+    /// The right edge
+    Right,
+    /// The top left corner
+    TopLeft,
+    /// The top right corner
+    TopRight,
+    /// The bottom left corner
+    BottomLeft,
+    /// The bottom right corner
+    BottomRight,
+}
+
+impl BorderEdge {
+    /// The edge of a border `border` pixels wide around the outside of `outer` that contains
+    /// the point `p`, if any.
+    ///
+    /// Points on the border within 16 pixels of a corner (or the border width if that is
+    /// larger) are treated as being on that corner.
+    pub fn from_point(p: Point, outer: Rect, border: u32) -> Option<Self> {
+        let within = |r: Rect| p.x >= r.x && p.x < r.x + r.w && p.y >= r.y && p.y < r.y + r.h;
+        let inner = Rect::new(
+            outer.x + border,
+            outer.y + border,
+            outer.w.saturating_sub(2 * border),
+            outer.h.saturating_sub(2 * border),
+        );
+        if border == 0 || !within(outer) || within(inner) {
+            return None;
+        }
+
+        let corner = border.max(BORDER_CORNER_SIZE);
+        let left = p.x < outer.x + corner;
+        let right = p.x >= (outer.x + outer.w).saturating_sub(corner);
+        let top = p.y < outer.y + corner;
+        let bottom = p.y >= (outer.y + outer.h).saturating_sub(corner);
+
+        let edge = match (top, bottom, left, right) {
+            (true, _, true, _) => Self::TopLeft,
+            (true, _, _, true) => Self::TopRight,
+            (_, true, true, _) => Self::BottomLeft,
+            (_, true, _, true) => Self::BottomRight,
+            _ if p.y < inner.y => Self::Top,
+            _ if p.y >= inner.y + inner.h => Self::Bottom,
+            _ if p.x < inner.x => Self::Left,
+            _ => Self::Right,
+        };
+
+        Some(edge)
+    }
+
+    /// Resize `r` by moving this edge (or both edges meeting at this corner) by the given
+    /// deltas, keeping the opposite edges in place.
+    ///
+    /// The width and height of the returned [Rect] will be at least `min_size`.
+    pub fn resize(&self, r: Rect, dx: i32, dy: i32, min_size: u32) -> Rect {
+        use BorderEdge::*;
+
+        let (x1, y1) = (r.x as i64, r.y as i64);
+        let (x2, y2) = (x1 + r.w as i64, y1 + r.h as i64);
+        let min = min_size as i64;
+        let (dx, dy) = (dx as i64, dy as i64);
+
+        let (x1, x2) = match self {
+            Left | TopLeft | BottomLeft => ((x1 + dx).clamp(0, x2 - min), x2),
+            Right | TopRight | BottomRight => (x1, (x2 + dx).max(x1 + min)),
+            Top | Bottom => (x1, x2),
+        };
+        let (y1, y2) = match self {
+            Top | TopLeft | TopRight => ((y1 + dy).clamp(0, y2 - min), y2),
+            Bottom | BottomLeft | BottomRight => (y1, (y2 + dy).max(y1 + min)),
+            Left | Right => (y1, y2),
+        };
+
+        Rect::new(x1 as u32, y1 as u32, (x2 - x1) as u32, (y2 - y1) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::actions::{enter_mode, exit_mode, modify_with, repeat, submap},
+        core::{hints::BindingHintsConfig, Config, WindowManager},
+        x::{
+            fixtures::{
+                last_grabbed_keys, record, record_key, recorded, requests_matching, ScriptedWm,
+            },
+            mock::{Request, ScriptedConn, FIRST_WINDOW_ID},
+            XEvent,
+        },
+    };
+    use simple_test_case::test_case;
+
+    fn codes(pairs: &[(&str, u8)]) -> HashMap<String, u8> {
+        pairs.iter().map(|&(s, c)| (s.to_owned(), c)).collect()
+    }
+
+    #[test]
+    fn key_spec_parses_modifiers_and_name() {
+        let k = KeySpec::parse("M-S-Return");
+
+        assert_eq!(k.mask(), 64 | 1);
+        assert_eq!(k.name(), "Return");
+        assert_eq!(k.to_string(), "M-S-Return");
+    }
+
+    #[test]
+    fn key_spec_without_modifiers() {
+        let k = KeySpec::parse("XF86AudioMute");
+
+        assert_eq!(k.mask(), 0);
+        assert_eq!(k.name(), "XF86AudioMute");
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("M-"; "missing name")]
+    #[test_case("M--"; "dash as name")]
+    #[test_case("X-Return"; "unknown modifier")]
+    #[test_case("Super-Return"; "long modifier")]
+    #[test_case("M-M-Return"; "duplicate modifier")]
+    #[test_case("M-Ret urn"; "whitespace")]
+    #[test]
+    fn invalid_key_specs_are_rejected(spec: &'static str) {
+        assert!(KeySpec::try_parse(spec).is_err());
+    }
+
+    #[cfg(feature = "keysyms")]
+    #[test_case("M-Retrun"; "misspelled")]
+    #[test_case("M-return"; "wrong case")]
+    #[test_case("M-Returns"; "prefix of name")]
+    #[test]
+    fn unknown_key_names_are_rejected(spec: &'static str) {
+        assert_eq!(
+            KeySpec::try_parse(spec),
+            Err("invalid key spec: unknown key name")
+        );
+    }
+
+    #[test]
+    fn key_spec_resolves_against_known_codes() {
+        let known = codes(&[("Return", 36)]);
+
+        assert_eq!(
+            KeySpec::parse("C-Return").resolve(&known).unwrap(),
+            KeyCode { mask: 4, code: 36 }
+        );
+        assert!(matches!(
+            KeySpec::parse("C-Escape").resolve(&known),
+            Err(Error::UnknownKeyName { .. })
+        ));
+    }
+
+    fn noop<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+        Box::new(|_: &mut State<X>, _: &X| Ok(()))
+    }
+
+    #[test]
+    fn conflicting_bindings_are_an_error() {
+        let known = codes(&[("a", 38)]);
+        let mut str_bindings: HashMap<&str, Box<dyn KeyEventHandler<ScriptedConn>>> =
+            HashMap::new();
+        str_bindings.insert("M-S-a", noop());
+        str_bindings.insert("S-M-a", noop());
+
+        match resolve_bindings(str_bindings, &known) {
+            Err(Error::ConflictingKeyBindings { first, second }) => {
+                assert_eq!((first.as_str(), second.as_str()), ("M-S-a", "S-M-a"))
+            }
+            res => panic!("expected a conflict, got {res:?}"),
         }
     }
+
+    #[test]
+    fn bindings_can_be_described() {
+        let mut inner: HashMap<&str, Box<dyn KeyEventHandler<ScriptedConn>>> = HashMap::new();
+        inner.insert("g", Box::new(Described::new("launch gimp", noop())));
+        let mut str_bindings: HashMap<&str, Box<dyn KeyEventHandler<ScriptedConn>>> =
+            HashMap::new();
+        str_bindings.insert("M-j", Box::new(Described::new("focus down", noop())));
+        str_bindings.insert(
+            "M-a",
+            Box::new(Described::new("launch", Box::new(Submap::new(inner)))),
+        );
+        str_bindings.insert("M-k", noop());
+
+        let descriptions = describe_bindings(&mut str_bindings);
+        let expected = vec![
+            (vec!["M-a"], Some("launch")),
+            (vec!["M-a", "g"], Some("launch gimp")),
+            (vec!["M-j"], Some("focus down")),
+            (vec!["M-k"], None),
+        ];
+
+        let got: Vec<_> = descriptions
+            .iter()
+            .map(|d| {
+                let keys: Vec<&str> = d.keys.iter().map(|s| s.as_str()).collect();
+                (keys, d.description.as_deref())
+            })
+            .collect();
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn submaps_keep_the_names_of_resolved_bindings() {
+        let known = codes(&[("g", 42)]);
+        let mut inner: HashMap<&str, Box<dyn KeyEventHandler<ScriptedConn>>> = HashMap::new();
+        inner.insert("S-g", noop());
+        let mut submap = Submap::new(inner);
+        submap.resolve(&known).unwrap();
+
+        assert_eq!(submap.name_for(&KeyCode { mask: 1, code: 42 }), Some("S-g"));
+    }
+
+    #[test]
+    fn remap_key_bindings_follows_key_names() {
+        let old = codes(&[("a", 38), ("A", 38), ("o", 32), ("O", 32)]);
+        let new = codes(&[("a", 38), ("A", 38), ("o", 39), ("O", 39)]);
+        let bindings: HashMap<_, _> = [
+            (KeyCode { mask: 8, code: 38 }, "a"),
+            (KeyCode { mask: 8, code: 32 }, "o"),
+        ]
+        .into_iter()
+        .collect();
+
+        let remapped = remap_key_bindings(bindings, &old, &new);
+
+        assert_eq!(remapped.get(&KeyCode { mask: 8, code: 38 }), Some(&"a"));
+        assert_eq!(remapped.get(&KeyCode { mask: 8, code: 39 }), Some(&"o"));
+        assert_eq!(remapped.len(), 2);
+    }
+
+    #[test]
+    fn remap_key_bindings_leaves_unknown_keys_untouched() {
+        let old = codes(&[("a", 38)]);
+        let new = codes(&[("b", 38)]);
+        let bindings: HashMap<_, _> = [(KeyCode { mask: 0, code: 38 }, ())].into_iter().collect();
+
+        let remapped = remap_key_bindings(bindings.clone(), &old, &new);
+
+        assert_eq!(remapped, bindings);
+    }
+
+    #[test_case(BorderEdge::Right, 20, 10, Rect::new(10, 10, 120, 100); "right ignores dy")]
+    #[test_case(BorderEdge::TopLeft, 20, -5, Rect::new(30, 5, 80, 105); "top left")]
+    #[test_case(BorderEdge::Left, 200, 0, Rect::new(100, 10, 10, 100); "min size")]
+    #[test_case(BorderEdge::Top, 0, -50, Rect::new(10, 0, 100, 110); "clamped to origin")]
+    #[test]
+    fn border_edge_resize(edge: BorderEdge, dx: i32, dy: i32, expected: Rect) {
+        let r = Rect::new(10, 10, 100, 100);
+
+        assert_eq!(edge.resize(r, dx, dy, 10), expected);
+    }
+
+    const KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+    const PREFIX: KeyCode = KeyCode { mask: 64, code: 38 };
+    const SHIFT: KeyCode = KeyCode { mask: 0, code: 50 };
+    const ESCAPE: KeyCode = KeyCode { mask: 0, code: 9 };
+    const UNBOUND: KeyCode = KeyCode { mask: 0, code: 99 };
+
+    // "M-a g" moves focus from client 2 to client 1
+    fn chord_wm(events: Vec<XEvent>) -> ScriptedWm {
+        let mut inner: HashMap<&str, Box<dyn KeyEventHandler<ScriptedConn>>> = HashMap::new();
+        inner.insert("g", modify_with(|cs| cs.focus_down()));
+
+        ScriptedWm::new()
+            .with_keycodes(&[("a", 38), ("g", 42), ("Shift_L", 50), ("Escape", 9)])
+            .with_clients(2)
+            .with_events(events)
+            .with_key_spec("M-a", submap(inner))
+    }
+
+    #[test_case(vec![XEvent::KeyPress(PREFIX), XEvent::KeyPress(KEY)], true; "chord")]
+    #[test_case(vec![XEvent::KeyPress(PREFIX), XEvent::KeyPress(SHIFT), XEvent::KeyPress(KEY)], true; "modifiers are ignored")]
+    #[test_case(vec![XEvent::KeyPress(KEY)], false; "chord key without prefix")]
+    #[test_case(vec![XEvent::KeyPress(PREFIX), XEvent::KeyPress(ESCAPE), XEvent::KeyPress(KEY)], false; "escape cancels")]
+    #[test_case(vec![XEvent::KeyPress(PREFIX), XEvent::KeyPress(UNBOUND), XEvent::KeyPress(KEY)], false; "unbound key cancels")]
+    #[test]
+    fn key_chords(events: Vec<XEvent>, should_run: bool) {
+        let wm = chord_wm(events).run();
+        let expected = if should_run { Xid(1) } else { Xid(2) };
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&expected));
+    }
+
+    fn keyboard_grabs(wm: &WindowManager<ScriptedConn>) -> Vec<Request> {
+        requests_matching(wm, |r| {
+            matches!(r, Request::GrabKeyboard | Request::UngrabKeyboard)
+        })
+    }
+
+    #[test]
+    fn key_chords_grab_and_release_the_keyboard() {
+        let wm = chord_wm(vec![XEvent::KeyPress(PREFIX), XEvent::KeyPress(KEY)]).run();
+
+        assert_eq!(
+            keyboard_grabs(&wm),
+            vec![Request::GrabKeyboard, Request::UngrabKeyboard]
+        );
+    }
+
+    #[test]
+    fn key_chords_time_out() {
+        let wm = chord_wm(vec![XEvent::KeyPress(PREFIX)]).run();
+
+        assert_eq!(wm.x().requests().last(), Some(&Request::UngrabKeyboard));
+    }
+
+    fn overlay_requests(wm: &WindowManager<ScriptedConn>) -> Vec<Request> {
+        requests_matching(wm, |r| {
+            matches!(
+                r,
+                Request::CreateOverlay(..)
+                    | Request::DrawBindingHints(..)
+                    | Request::DestroyWindow(_)
+            )
+        })
+    }
+
+    fn with_binding_hints(c: &mut Config<ScriptedConn>) {
+        c.binding_hints = Some(BindingHintsConfig::default());
+    }
+
+    #[test]
+    fn binding_hints_are_not_shown_by_default() {
+        let wm = chord_wm(vec![XEvent::KeyPress(PREFIX)]).run();
+
+        assert!(overlay_requests(&wm).is_empty());
+    }
+
+    #[test_case(vec![XEvent::KeyPress(PREFIX)]; "timeout")]
+    #[test_case(vec![XEvent::KeyPress(PREFIX), XEvent::KeyPress(KEY)]; "selection")]
+    #[test_case(vec![XEvent::KeyPress(PREFIX), XEvent::KeyPress(ESCAPE)]; "escape")]
+    #[test]
+    fn binding_hints_are_shown_for_key_chords(events: Vec<XEvent>) {
+        let wm = chord_wm(events).with_config(with_binding_hints).run();
+
+        let id = Xid(FIRST_WINDOW_ID);
+        let r = Rect::new(300, 764, 400, 36);
+        assert_eq!(
+            overlay_requests(&wm),
+            vec![
+                Request::CreateOverlay(id, r),
+                Request::DrawBindingHints(id, vec!["g".to_string()]),
+                Request::DestroyWindow(id),
+            ]
+        );
+    }
+
+    const MODE_KEY: KeyCode = KeyCode { mask: 64, code: 27 };
+    const MODE_FOCUS_KEY: KeyCode = KeyCode { mask: 0, code: 43 };
+
+    // KEY moves focus down outside of the mode, MODE_FOCUS_KEY does so inside it
+    fn mode_wm(
+        mode: &'static str,
+        events: Vec<XEvent>,
+        conf: ScriptedWm,
+    ) -> WindowManager<ScriptedConn> {
+        let mut wm = conf
+            .with_clients(2)
+            .with_events(events)
+            .with_key(KEY, modify_with(|cs| cs.focus_down()))
+            .with_key(MODE_KEY, enter_mode(mode))
+            .build();
+
+        let mut mode_bindings: KeyBindings<ScriptedConn> = HashMap::new();
+        mode_bindings.insert(ESCAPE, exit_mode());
+        mode_bindings.insert(MODE_FOCUS_KEY, modify_with(|cs| cs.focus_down()));
+        let mode = Mode::new("resize", mode_bindings)
+            .on_enter(|s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                record(s, "entered");
+                Ok(())
+            })
+            .on_exit(|s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                record(s, "exited");
+                Ok(())
+            });
+        wm.add_mode(mode);
+
+        wm.run_script().unwrap()
+    }
+
+    #[test]
+    fn binding_hints_are_shown_while_a_mode_is_active() {
+        let events = vec![
+            XEvent::KeyPress(MODE_KEY),
+            XEvent::KeyPress(MODE_FOCUS_KEY),
+            XEvent::KeyPress(ESCAPE),
+        ];
+        let wm = mode_wm(
+            "resize",
+            events,
+            ScriptedWm::new().with_config(with_binding_hints),
+        );
+
+        let id = Xid(FIRST_WINDOW_ID);
+        let r = Rect::new(300, 746, 400, 54);
+        assert_eq!(
+            overlay_requests(&wm),
+            vec![
+                Request::CreateOverlay(id, r),
+                Request::DrawBindingHints(id, vec!["<43>".to_string(), "<9>".to_string()]),
+                Request::DestroyWindow(id),
+            ]
+        );
+    }
+
+    #[test_case(vec![], None; "default bindings")]
+    #[test_case(vec![XEvent::KeyPress(MODE_KEY)], Some("resize"); "entering a mode")]
+    #[test_case(vec![XEvent::KeyPress(MODE_KEY), XEvent::KeyPress(ESCAPE)], None; "exiting a mode")]
+    #[test]
+    fn binding_modes(events: Vec<XEvent>, expected: Option<&str>) {
+        let wm = mode_wm("resize", events, ScriptedWm::new());
+
+        assert_eq!(wm.state.current_mode(), expected);
+    }
+
+    #[test]
+    fn binding_modes_replace_the_active_bindings() {
+        let events = vec![
+            XEvent::KeyPress(MODE_KEY),
+            XEvent::KeyPress(KEY),
+            XEvent::KeyPress(MODE_FOCUS_KEY),
+        ];
+        let wm = mode_wm("resize", events, ScriptedWm::new());
+
+        // KEY is not bound in the mode so focus only moves once
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(1)));
+        assert_eq!(last_grabbed_keys(&wm), vec![ESCAPE, MODE_FOCUS_KEY]);
+    }
+
+    #[test]
+    fn binding_mode_hooks_are_run() {
+        let events = vec![XEvent::KeyPress(MODE_KEY), XEvent::KeyPress(ESCAPE)];
+        let wm = mode_wm("resize", events, ScriptedWm::new());
+
+        assert_eq!(recorded(&wm), vec!["entered", "exited"]);
+    }
+
+    #[test_case(vec![XEvent::KeyPress(MODE_KEY)], vec![ESCAPE]; "enter")]
+    #[test_case(vec![XEvent::KeyPress(MODE_KEY), XEvent::KeyPress(ESCAPE)], vec![MODE_KEY, KEY]; "exit")]
+    #[test]
+    fn failing_mode_hooks_still_switch_the_grabbed_keys(
+        events: Vec<XEvent>,
+        expected: Vec<KeyCode>,
+    ) {
+        let mut wm = ScriptedWm::new()
+            .with_events(events)
+            .with_key(KEY, modify_with(|cs| cs.focus_down()))
+            .with_key(MODE_KEY, enter_mode("failing"))
+            .build();
+
+        let mut mode_bindings: KeyBindings<ScriptedConn> = HashMap::new();
+        mode_bindings.insert(ESCAPE, exit_mode());
+        let mode = Mode::new("failing", mode_bindings)
+            .on_enter(|_: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                Err(Error::Custom("enter".to_owned()))
+            })
+            .on_exit(|_: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                Err(Error::Custom("exit".to_owned()))
+            });
+        wm.add_mode(mode);
+        let wm = wm.run_script().unwrap();
+
+        assert_eq!(last_grabbed_keys(&wm), expected);
+    }
+
+    #[test]
+    fn unknown_binding_modes_are_ignored() {
+        let wm = mode_wm(
+            "unknown",
+            vec![XEvent::KeyPress(MODE_KEY)],
+            ScriptedWm::new(),
+        );
+
+        assert_eq!(wm.state.current_mode(), None);
+    }
+
+    const ONE: KeyCode = KeyCode { mask: 0, code: 10 };
+    const TWO: KeyCode = KeyCode { mask: 0, code: 11 };
+    const ZERO: KeyCode = KeyCode { mask: 0, code: 19 };
+    const COUNTED_KEY: KeyCode = KeyCode { mask: 64, code: 44 };
+
+    // Client 3 is focused and the "normal" mode is active
+    fn count_wm(keys: Vec<KeyCode>) -> WindowManager<ScriptedConn> {
+        let mut wm = ScriptedWm::new()
+            .with_keycodes(&[("1", 10), ("2", 11), ("0", 19), ("j", 44)])
+            .with_clients(3)
+            .with_events([MODE_KEY].into_iter().chain(keys).map(XEvent::KeyPress))
+            .with_key(MODE_KEY, enter_mode("normal"))
+            .build();
+
+        let mut mode_bindings: KeyBindings<ScriptedConn> = HashMap::new();
+        mode_bindings.insert(COUNTED_KEY, repeat(modify_with(|cs| cs.focus_down())));
+        wm.add_mode(Mode::new("normal", mode_bindings).with_count_prefix());
+
+        wm.run_script().unwrap()
+    }
+
+    #[test_case(vec![COUNTED_KEY], Xid(2); "no count")]
+    #[test_case(vec![TWO, COUNTED_KEY], Xid(1); "single digit")]
+    #[test_case(vec![ONE, ZERO, COUNTED_KEY], Xid(2); "multiple digits")]
+    #[test_case(vec![ZERO, COUNTED_KEY], Xid(2); "leading zero is ignored")]
+    #[test_case(vec![TWO, UNBOUND, COUNTED_KEY], Xid(2); "unbound key discards count")]
+    #[test_case(vec![TWO, COUNTED_KEY, COUNTED_KEY], Xid(3); "count is reset")]
+    #[test]
+    fn count_prefix(keys: Vec<KeyCode>, expected: Xid) {
+        let wm = count_wm(keys);
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&expected));
+        assert_eq!(wm.state.count(), None);
+    }
+
+    #[test]
+    fn count_prefix_modes_grab_digits() {
+        let wm = count_wm(vec![]);
+
+        assert_eq!(last_grabbed_keys(&wm), vec![ONE, TWO, ZERO, COUNTED_KEY]);
+    }
+
+    const UNBIND: KeyCode = KeyCode { mask: 0, code: 43 };
+    const BOUND_AT_RUNTIME: KeyCode = KeyCode { mask: 64, code: 44 };
+
+    // KEY binds "M-j" to move focus down and UNBIND removes it again
+    fn bind_wm(events: Vec<XEvent>) -> WindowManager<ScriptedConn> {
+        ScriptedWm::new()
+            .with_keycodes(&[("g", 42), ("h", 43), ("j", 44)])
+            .with_clients(2)
+            .with_events(events)
+            .with_key_spec(
+                "g",
+                Box::new(|s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                    s.bind_key("M-j", modify_with(|cs| cs.focus_down()));
+                    Ok(())
+                }),
+            )
+            .with_key_spec(
+                "h",
+                Box::new(|s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                    s.unbind_key("M-j");
+                    Ok(())
+                }),
+            )
+            .run()
+    }
+
+    #[test_case(vec![XEvent::KeyPress(BOUND_AT_RUNTIME)], false; "not bound initially")]
+    #[test_case(vec![XEvent::KeyPress(KEY), XEvent::KeyPress(BOUND_AT_RUNTIME)], true; "bound at runtime")]
+    #[test_case(vec![XEvent::KeyPress(KEY), XEvent::KeyPress(UNBIND), XEvent::KeyPress(BOUND_AT_RUNTIME)], false; "unbound at runtime")]
+    #[test]
+    fn runtime_key_bindings(events: Vec<XEvent>, should_run: bool) {
+        let wm = bind_wm(events);
+        let expected = if should_run { Xid(1) } else { Xid(2) };
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&expected));
+    }
+
+    #[test]
+    fn runtime_key_bindings_are_grabbed() {
+        let wm = bind_wm(vec![XEvent::KeyPress(KEY)]);
+
+        assert_eq!(last_grabbed_keys(&wm), vec![KEY, UNBIND, BOUND_AT_RUNTIME]);
+    }
+
+    struct IsClient(Xid);
+
+    impl Query<ScriptedConn> for IsClient {
+        fn run(&self, id: Xid, _: &ScriptedConn) -> Result<bool> {
+            Ok(id == self.0)
+        }
+    }
+
+    const CLIENT_KEY: KeyCode = KeyCode { mask: 64, code: 24 };
+
+    // KEY moves focus down unless client 1 is focused, in which case it is recorded instead
+    fn client_bindings_wm(events: Vec<XEvent>) -> WindowManager<ScriptedConn> {
+        let mut wm = ScriptedWm::new()
+            .with_clients(2)
+            .with_events(events)
+            .with_key(KEY, modify_with(|cs| cs.focus_down()))
+            .build();
+
+        let mut client_bindings: KeyBindings<ScriptedConn> = HashMap::new();
+        client_bindings.insert(KEY, record_key("client binding"));
+        client_bindings.insert(CLIENT_KEY, modify_with(|cs| cs.swap_down()));
+        wm.add_client_bindings(ClientBindings::new(IsClient(Xid(1)), client_bindings));
+
+        wm.run_script().unwrap()
+    }
+
+    #[test]
+    fn client_bindings_fall_through_for_other_clients() {
+        // Xid(2) is focused so the default binding is run, moving focus to Xid(1)
+        let wm = client_bindings_wm(vec![XEvent::KeyPress(KEY)]);
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(1)));
+        assert!(recorded(&wm).is_empty());
+    }
+
+    #[test]
+    fn client_bindings_take_precedence_for_matching_clients() {
+        let wm = client_bindings_wm(vec![XEvent::KeyPress(KEY), XEvent::KeyPress(KEY)]);
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(1)));
+        assert_eq!(recorded(&wm), vec!["client binding"]);
+    }
+
+    #[test]
+    fn client_bindings_are_grabbed() {
+        let wm = client_bindings_wm(vec![]);
+
+        assert_eq!(last_grabbed_keys(&wm), vec![CLIENT_KEY, KEY]);
+    }
+
+    const ALT_TAB: KeyCode = KeyCode { mask: 8, code: 23 };
+    const ALT_SHIFT_TAB: KeyCode = KeyCode { mask: 9, code: 23 };
+    const ALT_RELEASE: KeyCode = KeyCode { mask: 8, code: 64 };
+    const SHIFT_RELEASE: KeyCode = KeyCode { mask: 9, code: 50 };
+    const TAB: KeyCode = KeyCode { mask: 0, code: 23 };
+
+    // Each binding moves focus down once all held modifiers have been released
+    fn release_wm(events: Vec<XEvent>) -> WindowManager<ScriptedConn> {
+        let mut conf = ScriptedWm::new()
+            .with_keycodes(&[("Tab", 23), ("Alt_L", 64), ("Shift_L", 50)])
+            .with_clients(2)
+            .with_events(events);
+        for k in [ALT_TAB, ALT_SHIFT_TAB, TAB] {
+            conf = conf.with_key(
+                k,
+                Box::new(|s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                    s.on_modifier_release(modify_with(|cs| cs.focus_down()));
+                    Ok(())
+                }),
+            );
+        }
+
+        conf.run()
+    }
+
+    #[test_case(vec![XEvent::KeyPress(ALT_TAB)], false; "modifier held")]
+    #[test_case(vec![XEvent::KeyPress(ALT_TAB), XEvent::KeyRelease(ALT_RELEASE)], true; "modifier released")]
+    #[test_case(vec![XEvent::KeyPress(ALT_SHIFT_TAB), XEvent::KeyRelease(SHIFT_RELEASE)], false; "other modifiers released")]
+    #[test_case(vec![XEvent::KeyPress(ALT_SHIFT_TAB), XEvent::KeyRelease(SHIFT_RELEASE), XEvent::KeyRelease(ALT_RELEASE)], true; "all modifiers released")]
+    #[test_case(vec![XEvent::KeyPress(TAB)], true; "no modifiers held")]
+    #[test]
+    fn modifier_release_handlers(events: Vec<XEvent>, should_run: bool) {
+        let wm = release_wm(events);
+        let expected = if should_run { Xid(1) } else { Xid(2) };
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&expected));
+    }
+
+    #[test]
+    fn modifier_release_handlers_grab_the_keyboard() {
+        let wm = release_wm(vec![
+            XEvent::KeyPress(ALT_TAB),
+            XEvent::KeyPress(ALT_TAB),
+            XEvent::KeyRelease(ALT_RELEASE),
+        ]);
+
+        assert_eq!(
+            keyboard_grabs(&wm),
+            vec![Request::GrabKeyboard, Request::UngrabKeyboard]
+        );
+    }
 }
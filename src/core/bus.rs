@@ -0,0 +1,285 @@
+//! A typed message bus for communicating between hooks and extensions.
+//!
+//! Extensions often need to react to things happening in other extensions without
+//! depending on each other directly: a status bar wanting to know when a scratchpad is
+//! toggled, for example. Any `'static` type can be used as a message: publishers send
+//! values of that type using [State::publish] and subscribers register a handler for it
+//! using [State::subscribe]. Neither side needs to know anything about the other beyond the
+//! message type itself.
+//!
+//! ```no_run
+//! # use penrose::{core::State, x::XConn, Result};
+//! // Defined somewhere that both extensions can see
+//! #[derive(Debug)]
+//! pub struct ScratchpadToggled {
+//!     pub name: String,
+//!     pub visible: bool,
+//! }
+//!
+//! // In the scratchpad extension
+//! fn toggle<X: XConn>(state: &mut State<X>, _: &X) -> Result<()> {
+//!     // ...
+//!     state.publish(ScratchpadToggled { name: "terminal".to_owned(), visible: true });
+//!
+//!     Ok(())
+//! }
+//!
+//! // In the bar extension
+//! fn startup<X: XConn + 'static>(state: &mut State<X>, _: &X) -> Result<()> {
+//!     state.subscribe(|msg: &ScratchpadToggled, state: &mut State<X>, x: &X| {
+//!         // Redraw the bar...
+//!         Ok(())
+//!     });
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Published messages are queued and delivered by the window manager once the hook or
+//! action that published them has returned, in the order that they were published.
+//! Subscribers for a given message type are run in the order that they subscribed.
+//!
+//!   [State::publish]: crate::core::State::publish
+//!   [State::subscribe]: crate::core::State::subscribe
+use crate::{core::State, x::XConn, Result};
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::VecDeque,
+    fmt,
+};
+
+/// A unique identifier for a subscriber added with [State::subscribe], allowing it to be
+/// removed using [State::unsubscribe].
+///
+///   [State::subscribe]: crate::core::State::subscribe
+///   [State::unsubscribe]: crate::core::State::unsubscribe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SubscriptionId(u64);
+
+pub(crate) type Handler<X> = Box<dyn FnMut(&dyn Any, &mut State<X>, &X) -> Result<()>>;
+
+// A published message along with the name of its type for logging
+pub(crate) struct Message {
+    pub(crate) type_name: &'static str,
+    pub(crate) value: Box<dyn Any>,
+}
+
+struct Subscriber<X>
+where
+    X: XConn,
+{
+    id: SubscriptionId,
+    type_id: TypeId,
+    // Taken out while the handler is being run
+    handler: Option<Handler<X>>,
+}
+
+/// Queued messages and the subscribers they will be delivered to.
+pub(crate) struct MessageBus<X>
+where
+    X: XConn,
+{
+    next_id: u64,
+    subscribers: Vec<Subscriber<X>>,
+    queue: VecDeque<Message>,
+}
+
+impl<X: XConn> Default for MessageBus<X> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            subscribers: Vec::new(),
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl<X: XConn> fmt::Debug for MessageBus<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let queued: Vec<_> = self.queue.iter().map(|m| m.type_name).collect();
+
+        f.debug_struct("MessageBus")
+            .field("subscribers", &self.subscribers.len())
+            .field("queued", &queued)
+            .finish()
+    }
+}
+
+impl<X> MessageBus<X>
+where
+    X: XConn,
+{
+    pub(crate) fn publish<M: Any>(&mut self, msg: M) {
+        self.queue.push_back(Message {
+            type_name: type_name::<M>(),
+            value: Box::new(msg),
+        });
+    }
+
+    pub(crate) fn subscribe<M, F>(&mut self, mut f: F) -> SubscriptionId
+    where
+        M: Any,
+        F: FnMut(&M, &mut State<X>, &X) -> Result<()> + 'static,
+    {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+
+        let handler: Handler<X> = Box::new(move |msg, state, x| match msg.downcast_ref::<M>() {
+            Some(msg) => f(msg, state, x),
+            None => Ok(()),
+        });
+
+        self.subscribers.push(Subscriber {
+            id,
+            type_id: TypeId::of::<M>(),
+            handler: Some(handler),
+        });
+
+        id
+    }
+
+    pub(crate) fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let before = self.subscribers.len();
+        self.subscribers.retain(|s| s.id != id);
+
+        self.subscribers.len() != before
+    }
+
+    pub(crate) fn is_subscribed(&self, id: SubscriptionId) -> bool {
+        self.subscribers.iter().any(|s| s.id == id)
+    }
+
+    /// The next message to be delivered, if there is one.
+    pub(crate) fn next_message(&mut self) -> Option<Message> {
+        self.queue.pop_front()
+    }
+
+    /// The subscribers to the given message, in the order they should be run.
+    pub(crate) fn subscribers_for(&self, msg: &Message) -> Vec<SubscriptionId> {
+        let type_id = msg.value.as_ref().type_id();
+
+        self.subscribers
+            .iter()
+            .filter(|s| s.type_id == type_id)
+            .map(|s| s.id)
+            .collect()
+    }
+
+    pub(crate) fn take(&mut self, id: SubscriptionId) -> Option<Handler<X>> {
+        self.subscribers
+            .iter_mut()
+            .find(|s| s.id == id)
+            .and_then(|s| s.handler.take())
+    }
+
+    // Put back a handler that has been run unless it unsubscribed while it was running
+    pub(crate) fn restore(&mut self, id: SubscriptionId, handler: Handler<X>) {
+        if let Some(s) = self.subscribers.iter_mut().find(|s| s.id == id) {
+            s.handler = Some(handler);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::actions::key_handler,
+        core::bindings::KeyCode,
+        x::{fixtures::ScriptedWm, mock::ScriptedConn, XEvent},
+    };
+    use std::{cell::Cell, rc::Rc};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Ping(u8);
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Pong(u8);
+
+    fn noop<M>(_: &M, _: &mut State<ScriptedConn>, _: &ScriptedConn) -> Result<()> {
+        Ok(())
+    }
+
+    #[test]
+    fn messages_are_only_routed_to_subscribers_of_their_type() {
+        let mut bus: MessageBus<ScriptedConn> = MessageBus::default();
+        let a = bus.subscribe(noop::<Ping>);
+        let b = bus.subscribe(noop::<Pong>);
+        let c = bus.subscribe(noop::<Ping>);
+
+        bus.publish(Ping(0));
+        bus.publish(Pong(0));
+
+        let ping = bus.next_message().unwrap();
+        let pong = bus.next_message().unwrap();
+
+        assert_eq!(bus.subscribers_for(&ping), vec![a, c]);
+        assert_eq!(bus.subscribers_for(&pong), vec![b]);
+        assert!(bus.next_message().is_none());
+    }
+
+    #[test]
+    fn handlers_unsubscribed_while_running_are_not_restored() {
+        let mut bus: MessageBus<ScriptedConn> = MessageBus::default();
+        let id = bus.subscribe(noop::<Ping>);
+
+        let handler = bus.take(id).unwrap();
+        assert!(bus.unsubscribe(id));
+        bus.restore(id, handler);
+
+        assert!(!bus.is_subscribed(id));
+    }
+
+    const KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+
+    #[test]
+    fn published_messages_are_delivered_to_subscribers() {
+        let mut wm = ScriptedWm::new()
+            .with_events([XEvent::KeyPress(KEY), XEvent::KeyPress(KEY)])
+            .with_key(
+                KEY,
+                key_handler(|s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                    let n = s.extension_or_default::<Vec<Pong>>().borrow().len() as u8;
+                    s.publish(Ping(n));
+                    Ok(())
+                }),
+            )
+            .build();
+
+        // Subscribers can publish messages of their own
+        wm.state
+            .subscribe(|p: &Ping, s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                s.publish(Pong(p.0));
+                Ok(())
+            });
+        let pongs =
+            wm.state
+                .subscribe(|p: &Pong, s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                    s.extension::<Vec<Pong>>()?.borrow_mut().push(*p);
+                    Ok(())
+                });
+        // Subscribers can remove themselves while running
+        let once = Rc::new(Cell::new(None));
+        let id = once.clone();
+        let count = Rc::new(Cell::new(0));
+        let c = count.clone();
+        let sub = wm.state.subscribe(
+            move |_: &Ping, s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                c.set(c.get() + 1);
+                if let Some(id) = id.get() {
+                    s.unsubscribe(id);
+                }
+                Ok(())
+            },
+        );
+        once.set(Some(sub));
+
+        let wm = wm.run_script().unwrap();
+
+        let received = wm.state.extension::<Vec<Pong>>().unwrap();
+        assert_eq!(*received.borrow(), vec![Pong(0), Pong(1)]);
+        assert_eq!(count.get(), 1);
+        assert!(wm.state.is_subscribed(pongs));
+        assert!(!wm.state.is_subscribed(sub));
+    }
+}
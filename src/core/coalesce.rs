@@ -0,0 +1,128 @@
+//! Coalescing bursts of redundant events before they are handled.
+//!
+//! Some events are generated in bursts where only the last one actually matters: plugging
+//! in a dock with multiple monitors attached results in a [XEvent::RandrNotify] for each
+//! output (along with a [XEvent::ConfigureNotify] for the root window) and restacking
+//! clients under the mouse pointer results in a [XEvent::Enter] for each window the pointer
+//! ends up over as the stack changes. Handling each of these in turn triggers a refresh for
+//! every one of them, so any events of this kind that are already queued are collapsed down
+//! to the last one before they are handled.
+use crate::x::XEvent;
+use std::collections::VecDeque;
+
+/// The maximum number of queued events that will be read ahead in order to coalesce them.
+pub(crate) const MAX_COALESCED_EVENTS: usize = 256;
+
+#[derive(Debug, Default)]
+struct Seen {
+    screens: bool,
+    screen_change: bool,
+    enter: bool,
+}
+
+impl Seen {
+    // Whether or not this event should be kept, given that `self` tracks which events
+    // have been seen later on in the queue.
+    fn keep(&mut self, event: &XEvent) -> bool {
+        match event {
+            XEvent::RandrNotify => !std::mem::replace(&mut self.screens, true),
+            XEvent::ConfigureNotify(e) if e.is_root => !std::mem::replace(&mut self.screens, true),
+            XEvent::ScreenChange => !std::mem::replace(&mut self.screen_change, true),
+            XEvent::Enter(_) => !std::mem::replace(&mut self.enter, true),
+
+            // Focus following the mouse needs to be correct for user input so we only
+            // drop Enter events that are superseded before the next input event
+            XEvent::KeyPress(_) | XEvent::KeyRelease(_) | XEvent::MouseEvent(_) => {
+                self.enter = false;
+                true
+            }
+
+            _ => true,
+        }
+    }
+}
+
+/// Drop any queued events that are made redundant by a later event in the queue:
+///
+/// - Screen changes ([XEvent::RandrNotify] or a [XEvent::ConfigureNotify] for the root
+///   window) all result in re-detecting the current screens so only the last is kept.
+/// - Only the last [XEvent::ScreenChange] is kept.
+/// - An [XEvent::Enter] is dropped if the pointer enters another window before the next
+///   key press, key release or mouse event.
+pub(crate) fn coalesce(events: &mut VecDeque<XEvent>) {
+    let mut seen = Seen::default();
+    let mut keep: Vec<bool> = events.iter().rev().map(|e| seen.keep(e)).collect();
+    keep.reverse();
+
+    let mut keep = keep.into_iter();
+    events.retain(|_| keep.next().unwrap_or(true));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::bindings::KeyCode,
+        pure::geometry::{Point, Rect},
+        x::event::{ConfigureEvent, PointerChange},
+        Xid,
+    };
+    use simple_test_case::test_case;
+
+    const KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+
+    fn enter(id: u32) -> XEvent {
+        XEvent::Enter(PointerChange {
+            id: Xid(id),
+            abs: Point::new(0, 0),
+            relative: Point::new(0, 0),
+            same_screen: true,
+        })
+    }
+
+    fn configure(id: u32, is_root: bool) -> XEvent {
+        XEvent::ConfigureNotify(ConfigureEvent {
+            id: Xid(id),
+            r: Rect::new(0, 0, 100, 100),
+            is_root,
+        })
+    }
+
+    #[test_case(
+        vec![XEvent::RandrNotify, XEvent::RandrNotify, XEvent::RandrNotify],
+        vec![XEvent::RandrNotify];
+        "randr burst"
+    )]
+    #[test_case(
+        vec![XEvent::RandrNotify, configure(1, false), configure(0, true), XEvent::MapRequest(Xid(2))],
+        vec![configure(1, false), configure(0, true), XEvent::MapRequest(Xid(2))];
+        "root configure replaces randr"
+    )]
+    #[test_case(
+        vec![XEvent::ScreenChange, XEvent::RandrNotify, XEvent::ScreenChange],
+        vec![XEvent::RandrNotify, XEvent::ScreenChange];
+        "screen change"
+    )]
+    #[test_case(
+        vec![enter(1), enter(2), XEvent::Destroy(Xid(3)), enter(4)],
+        vec![XEvent::Destroy(Xid(3)), enter(4)];
+        "enter burst"
+    )]
+    #[test_case(
+        vec![enter(1), XEvent::KeyPress(KEY), enter(2)],
+        vec![enter(1), XEvent::KeyPress(KEY), enter(2)];
+        "enter before input is kept"
+    )]
+    #[test_case(
+        vec![XEvent::MapRequest(Xid(1)), XEvent::MapRequest(Xid(1))],
+        vec![XEvent::MapRequest(Xid(1)), XEvent::MapRequest(Xid(1))];
+        "other events are untouched"
+    )]
+    #[test]
+    fn coalesce_works(events: Vec<XEvent>, expected: Vec<XEvent>) {
+        let mut events: VecDeque<XEvent> = events.into();
+        coalesce(&mut events);
+
+        assert_eq!(Vec::from(events), expected);
+    }
+}
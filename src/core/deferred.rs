@@ -0,0 +1,479 @@
+//! Running slow work off of the main thread and applying the result to [State] later.
+//!
+//! Hooks and key bindings are run on the main thread of the window manager, so anything that
+//! takes a long time to complete (HTTP requests, shelling out and waiting for the output,
+//! reading large files...) will block handling of X events until it finishes. Instead, this
+//! sort of work can be run on a worker thread using [State::spawn_background] with the result
+//! being applied to the [State] from the main thread once it is ready:
+//!
+//! ```no_run
+//! # use penrose::{core::State, x::XConn, Result};
+//! # use std::process::Command;
+//! fn update_hostname<X: XConn + 'static>(state: &mut State<X>, _: &X) -> Result<()> {
+//!     state.spawn_background(
+//!         || Command::new("hostname").output().ok(),
+//!         |output, state: &mut State<X>, _: &X| {
+//!             if let Some(output) = output {
+//!                 let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+//!                 state.add_extension(name);
+//!             }
+//!
+//!             Ok(())
+//!         },
+//!     );
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! For more control over how worker threads are run, a [DeferredSender] can be obtained from
+//! [State::deferred_sender] and moved to another thread in order to submit closures to be run
+//! against the [State].
+//!
+//! A sender can also be obtained from [WindowManager::deferred_sender] before the window
+//! manager is started, allowing other threads (timers, IPC listeners, async runtimes...) to
+//! inject work into the main loop for as long as it is running. As well as closures, any
+//! `Send` value can be injected as a user-defined event using [DeferredSender::publish], in
+//! which case it is delivered to subscribers on the [message bus][crate::core::bus]:
+//!
+//! ```no_run
+//! # use penrose::{core::{State, WindowManager}, x::XConn, Result};
+//! # use std::{thread, time::Duration};
+//! #[derive(Debug)]
+//! struct Tick(u64);
+//!
+//! fn run<X: XConn + 'static>(mut wm: WindowManager<X>) -> Result<()> {
+//!     wm.state.subscribe(|t: &Tick, _: &mut State<X>, _: &X| {
+//!         println!("tick {}", t.0);
+//!         Ok(())
+//!     });
+//!
+//!     let tx = wm.deferred_sender();
+//!     thread::spawn(move || {
+//!         for n in 0.. {
+//!             thread::sleep(Duration::from_secs(1));
+//!             tx.publish(Tick(n));
+//!         }
+//!     });
+//!
+//!     wm.run()
+//! }
+//! ```
+//!
+//! Hooks that want to trigger behaviour that itself requires a refresh (killing a client,
+//! switching to another tag...) can run into ordering problems if they do so directly, as the
+//! hook may itself be running as part of a refresh. Instead, actions can be queued using
+//! [State::queue_action] to be run on the main thread once handling of the current event
+//! (including any refresh) has completed:
+//!
+//! ```no_run
+//! # use penrose::{builtin::actions::modify_with, core::{bindings::KeyEventHandler, State}, x::XConn, Result};
+//! fn switch_to_3<X: XConn + 'static>(state: &mut State<X>, _: &X) -> Result<()> {
+//!     let mut action = modify_with(|cs| cs.focus_tag("3"));
+//!     state.queue_action(move |state, x| action.call(state, x));
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Queued actions are run in the order that they were queued. Actions queued while running a
+//! queued action are run on the next iteration of the event loop.
+//!
+//! Submitted closures are run by the window manager in between handling events in the order
+//! that they were submitted. As with scheduled hooks, if you modify the [State] from a
+//! deferred closure you will need to call [XConnExt::refresh][crate::x::XConnExt::refresh]
+//! yourself in order for your changes to be reflected on screen.
+//!
+//!   [State]: crate::core::State
+//!   [State::spawn_background]: crate::core::State::spawn_background
+//!   [State::deferred_sender]: crate::core::State::deferred_sender
+//!   [State::queue_action]: crate::core::State::queue_action
+//!   [WindowManager::deferred_sender]: crate::core::WindowManager::deferred_sender
+use crate::{core::State, x::XConn, Result};
+use std::{
+    any::Any,
+    collections::VecDeque,
+    fmt,
+    io::{ErrorKind, Read, Write},
+    os::unix::{
+        io::{AsRawFd, RawFd},
+        net::UnixStream,
+    },
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+};
+use tracing::warn;
+
+/// A closure submitted from another thread to be run against the window manager [State].
+///
+///   [State]: crate::core::State
+pub type Deferred<X> = Box<dyn FnOnce(&mut State<X>, &X) -> Result<()> + Send>;
+
+/// An action queued from the main thread using [State::queue_action].
+///
+///   [State::queue_action]: crate::core::State::queue_action
+pub type QueuedAction<X> = Box<dyn FnOnce(&mut State<X>, &X) -> Result<()>>;
+
+/// A handle for submitting closures to be run against the window manager [State] from
+/// another thread.
+///
+/// Sending a closure wakes up the window manager if it is currently waiting for the next
+/// event from the X server. Backends that are unable to wait on the wakeup signal (see
+/// [Backend::next_event_or_wakeup]) will instead periodically wake up to check for new
+/// closures while any senders are alive, so avoid holding on to senders for longer than you
+/// need to.
+///
+///   [State]: crate::core::State
+///   [Backend::next_event_or_wakeup]: crate::x::Backend::next_event_or_wakeup
+pub struct DeferredSender<X>
+where
+    X: XConn,
+{
+    tx: Sender<Deferred<X>>,
+    // Written to in order to wake up the main loop. The number of references is also used
+    // to track how many senders are alive so that we know when to wait for work.
+    waker: Arc<UnixStream>,
+}
+
+impl<X: XConn> Clone for DeferredSender<X> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            waker: Arc::clone(&self.waker),
+        }
+    }
+}
+
+impl<X: XConn> fmt::Debug for DeferredSender<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeferredSender").finish()
+    }
+}
+
+impl<X> DeferredSender<X>
+where
+    X: XConn,
+{
+    /// Submit a closure to be run against the window manager [State] from the main thread.
+    ///
+    /// If the window manager has exited then the closure is dropped without being run.
+    ///
+    ///   [State]: crate::core::State
+    pub fn send<F>(&self, f: F)
+    where
+        F: FnOnce(&mut State<X>, &X) -> Result<()> + Send + 'static,
+    {
+        if self.tx.send(Box::new(f)).is_err() {
+            warn!("window manager has exited: dropping deferred closure");
+            return;
+        }
+
+        // If the socket buffer is full then there is already a wakeup pending
+        if let Err(e) = (&*self.waker).write(&[0]) {
+            if e.kind() != ErrorKind::WouldBlock {
+                warn!(%e, "unable to wake up the window manager");
+            }
+        }
+    }
+
+    /// Publish a message to the window manager's message bus from the main thread, where it
+    /// will be delivered to any subscribers for its type.
+    ///
+    /// If the window manager has exited then the message is dropped without being delivered.
+    /// See [State::publish] for details.
+    ///
+    ///   [State::publish]: crate::core::State::publish
+    pub fn publish<M>(&self, msg: M)
+    where
+        M: Any + Send,
+    {
+        self.send(move |state, _| {
+            state.publish(msg);
+            Ok(())
+        });
+    }
+}
+
+/// The receiving end of deferred closures submitted via a [DeferredSender] along with any
+/// actions queued from the main thread.
+pub(crate) struct DeferredQueue<X>
+where
+    X: XConn,
+{
+    tx: Sender<Deferred<X>>,
+    rx: Receiver<Deferred<X>>,
+    received: VecDeque<Deferred<X>>,
+    waker: Arc<UnixStream>,
+    wakeup: UnixStream,
+    actions: VecDeque<QueuedAction<X>>,
+}
+
+impl<X: XConn> fmt::Debug for DeferredQueue<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeferredQueue")
+            .field("senders", &self.senders())
+            .field("actions", &self.actions.len())
+            .finish()
+    }
+}
+
+impl<X> DeferredQueue<X>
+where
+    X: XConn,
+{
+    pub(crate) fn new() -> Result<Self> {
+        let (tx, rx) = channel();
+        let (waker, wakeup) = UnixStream::pair()?;
+        waker.set_nonblocking(true)?;
+        wakeup.set_nonblocking(true)?;
+
+        Ok(Self {
+            tx,
+            rx,
+            received: VecDeque::new(),
+            waker: Arc::new(waker),
+            wakeup,
+            actions: VecDeque::new(),
+        })
+    }
+
+    pub(crate) fn sender(&self) -> DeferredSender<X> {
+        DeferredSender {
+            tx: self.tx.clone(),
+            waker: Arc::clone(&self.waker),
+        }
+    }
+
+    // The number of live DeferredSenders
+    fn senders(&self) -> usize {
+        Arc::strong_count(&self.waker) - 1
+    }
+
+    /// Whether or not there are any senders alive that could still submit a closure.
+    pub(crate) fn is_waiting(&self) -> bool {
+        self.senders() > 0
+    }
+
+    /// The file descriptor that becomes readable when a closure is submitted.
+    pub(crate) fn wakeup_fd(&self) -> RawFd {
+        self.wakeup.as_raw_fd()
+    }
+
+    /// Whether or not there are closures that have been submitted but not yet run.
+    pub(crate) fn has_pending(&mut self) -> bool {
+        self.received.extend(self.rx.try_iter());
+
+        !self.received.is_empty()
+    }
+
+    /// The next closure that has been submitted, if there is one.
+    ///
+    /// Any pending wakeups are cleared before checking for closures so that a closure
+    /// submitted after this returns `None` will always wake up the main loop again.
+    pub(crate) fn try_next(&mut self) -> Option<Deferred<X>> {
+        let mut buf = [0; 64];
+        while matches!((&self.wakeup).read(&mut buf), Ok(n) if n > 0) {}
+
+        self.received
+            .pop_front()
+            .or_else(|| self.rx.try_recv().ok())
+    }
+
+    pub(crate) fn push_action(&mut self, action: QueuedAction<X>) {
+        self.actions.push_back(action);
+    }
+
+    /// Whether or not there are any queued actions waiting to be run.
+    pub(crate) fn has_actions(&self) -> bool {
+        !self.actions.is_empty()
+    }
+
+    /// Take the actions that are currently queued, leaving any actions queued while running
+    /// them for the next iteration of the event loop.
+    pub(crate) fn take_actions(&mut self) -> VecDeque<QueuedAction<X>> {
+        std::mem::take(&mut self.actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::actions::key_handler,
+        core::{bindings::KeyCode, Xid},
+        x::{
+            fixtures::{record, recorded, ScriptedWm},
+            mock::ScriptedConn,
+            XEvent,
+        },
+    };
+    use nix::poll::{poll, PollFd, PollFlags};
+    use std::thread;
+
+    #[test]
+    fn queue_is_only_waiting_while_senders_are_alive() {
+        let q: DeferredQueue<ScriptedConn> = DeferredQueue::new().unwrap();
+        assert!(!q.is_waiting());
+
+        let tx = q.sender();
+        let tx2 = tx.clone();
+        assert!(q.is_waiting());
+
+        drop(tx);
+        assert!(q.is_waiting());
+
+        drop(tx2);
+        assert!(!q.is_waiting());
+    }
+
+    #[test]
+    fn closures_sent_from_other_threads_are_received() {
+        let mut q: DeferredQueue<ScriptedConn> = DeferredQueue::new().unwrap();
+        let tx = q.sender();
+
+        thread::spawn(move || {
+            tx.send(|_, _| Ok(()));
+            tx.send(|_, _| Err(crate::Error::Custom("second".to_owned())));
+        })
+        .join()
+        .unwrap();
+
+        let first = q.try_next();
+        let second = q.try_next();
+
+        assert!(first.is_some());
+        assert!(second.is_some());
+        assert!(q.try_next().is_none());
+        assert!(!q.is_waiting());
+    }
+
+    fn is_woken(q: &DeferredQueue<ScriptedConn>) -> bool {
+        let mut fds = [PollFd::new(q.wakeup_fd(), PollFlags::POLLIN)];
+
+        poll(&mut fds, 0).unwrap() > 0
+    }
+
+    #[test]
+    fn sending_wakes_up_the_queue_until_it_is_drained() {
+        let mut q: DeferredQueue<ScriptedConn> = DeferredQueue::new().unwrap();
+        let tx = q.sender();
+        assert!(!is_woken(&q));
+
+        tx.send(|_, _| Ok(()));
+        tx.send(|_, _| Ok(()));
+        assert!(is_woken(&q));
+        assert!(q.has_pending());
+
+        assert!(q.try_next().is_some());
+        assert!(!is_woken(&q));
+        assert!(q.try_next().is_some());
+        assert!(!q.has_pending());
+    }
+
+    #[test]
+    fn actions_queued_while_draining_are_left_for_later() {
+        let mut q: DeferredQueue<ScriptedConn> = DeferredQueue::new().unwrap();
+        q.push_action(Box::new(|_, _| Ok(())));
+        q.push_action(Box::new(|_, _| Ok(())));
+
+        let actions = q.take_actions();
+        q.push_action(Box::new(|_, _| Ok(())));
+
+        assert_eq!(actions.len(), 2);
+        assert!(q.has_actions());
+        assert_eq!(q.take_actions().len(), 1);
+        assert!(!q.has_actions());
+    }
+
+    const KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+
+    #[test]
+    fn background_work_is_applied_to_state() {
+        let wm = ScriptedWm::new()
+            .with_events([XEvent::KeyPress(KEY)])
+            .with_key(
+                KEY,
+                key_handler(|s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                    s.spawn_background(
+                        || 6 * 7,
+                        |n, s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                            record(s, n.to_string());
+                            Ok(())
+                        },
+                    );
+                    Ok(())
+                }),
+            )
+            .run();
+
+        assert_eq!(recorded(&wm), vec!["42"]);
+        assert!(!wm.state.deferred.is_waiting());
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Ping(u8);
+
+    #[test]
+    fn messages_injected_from_other_threads_are_delivered() {
+        let mut wm = ScriptedWm::new()
+            .with_events([XEvent::KeyPress(KEY)])
+            .build();
+        wm.state
+            .subscribe(|p: &Ping, s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                record(s, p.0.to_string());
+                Ok(())
+            });
+
+        let tx = wm.deferred_sender();
+        thread::spawn(move || {
+            tx.publish(Ping(1));
+            tx.send(|s, _| {
+                s.publish(Ping(2));
+                Ok(())
+            });
+            tx.publish(Ping(3));
+        })
+        .join()
+        .unwrap();
+        let wm = wm.run_script().unwrap();
+
+        assert_eq!(recorded(&wm), vec!["1", "2", "3"]);
+        assert!(!wm.state.deferred.is_waiting());
+    }
+
+    #[test]
+    fn queued_actions_run_after_the_current_event() {
+        fn push(label: &'static str, s: &mut State<ScriptedConn>) -> Result<()> {
+            record(s, label);
+            Ok(())
+        }
+
+        let wm = ScriptedWm::new()
+            .with_clients(1)
+            .with_config(|c| {
+                c.compose_or_set_manage_hook(
+                    |_: Xid, s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                        s.queue_action(|s, _| {
+                            s.queue_action(|s, _| push("nested", s));
+                            push("first", s)
+                        });
+                        s.queue_action(|s, _| push("second", s));
+                        push("manage", s)
+                    },
+                );
+                c.compose_or_set_refresh_hook(|s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                    push("refresh", s)
+                });
+            })
+            .run();
+
+        let runs = recorded(&wm);
+        let first = runs.iter().position(|r| r == "first").unwrap();
+        let manage = runs.iter().position(|r| r == "manage").unwrap();
+        assert!(runs[manage..first].iter().any(|r| r == "refresh"));
+        assert_eq!(&runs[first..], &["first", "second", "nested"]);
+        assert!(!wm.state.deferred.has_actions());
+    }
+}
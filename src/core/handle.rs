@@ -1,18 +1,31 @@
 //! XEvent handlers for use in the main event loop;
 use crate::{
     core::{
-        bindings::{KeyBindings, KeyCode, MouseBindings, MouseEvent},
+        bindings::{
+            is_modifier_key, modifier_key_mask, remap_key_bindings, remap_submaps, submap_bindings,
+            BorderEdge, Chord, ClientBindings, KeyBindings, KeyCode, LastClick, Mode,
+            MouseBindings, MouseButton, MouseEvent, MouseEventKind, MouseState,
+        },
+        hooks::run_hook,
         State, Xid,
     },
-    pure::geometry::Point,
+    pure::{
+        geometry::{Point, Rect},
+        OutputChanges, OutputInfo,
+    },
     x::{
         atom::Atom,
-        event::{ClientMessage, ClientMessageKind, PointerChange},
+        draw_titlebar,
+        event::{
+            ClientMessage, ClientMessageKind, ExposeEvent, MotionEvent, PointerChange,
+            PropertyEvent,
+        },
         property::{Prop, WmHints},
         XConn, XConnExt,
     },
     Result,
 };
+use std::{collections::HashMap, time::Instant};
 use tracing::{error, info, trace};
 
 // Currently no client messages are handled by default (see the ewmh extension for some examples of messages
@@ -24,16 +37,155 @@ pub(crate) fn client_message<X: XConn>(msg: ClientMessage, _: &mut State<X>, _:
     Ok(())
 }
 
+// Client bindings are checked before the default bindings when starting a new key sequence and
+// key chords continue from whichever set of bindings they were started in.
 pub(crate) fn keypress<X: XConn>(
     key: KeyCode,
     bindings: &mut KeyBindings<X>,
+    client_bindings: &mut [ClientBindings<X>],
+    chord: &mut Option<Chord>,
+    keycodes: &HashMap<String, u8>,
     state: &mut State<X>,
     x: &X,
 ) -> Result<()> {
-    if let Some(action) = bindings.get_mut(&key) {
-        trace!(?key, "running user keybinding");
-        if let Err(error) = action.call(state, x) {
-            error!(%error, ?key, "error running user keybinding");
+    let (root, path) = match chord.take() {
+        Some(c) if is_modifier_key(key.code, keycodes) => {
+            // Wait for the key that is being modified
+            *chord = Some(c);
+            return Ok(());
+        }
+
+        Some(c) if keycodes.get("Escape") == Some(&key.code) => {
+            trace!(path = ?c.path, "key chord cancelled");
+            state.count = None;
+            return x.ungrab_keyboard();
+        }
+
+        Some(c) => (c.client_bindings, c.path),
+        None => {
+            let focused = state.client_set.current_client().copied();
+            let root = focused.and_then(|id| {
+                client_bindings
+                    .iter()
+                    .position(|cb| cb.applies_to(&key, id, x))
+            });
+
+            (root, vec![])
+        }
+    };
+    let in_chord = !path.is_empty();
+
+    if let Some(ix) = root {
+        trace!(?key, index = ix, "using client key bindings");
+    }
+    let bindings = match root {
+        Some(ix) => &mut client_bindings[ix].bindings,
+        None => bindings,
+    };
+
+    let action = match submap_bindings(bindings, &path).and_then(|b| b.get_mut(&key)) {
+        Some(action) => action,
+        None if in_chord => {
+            trace!(
+                ?key,
+                ?path,
+                "no binding for key in submap: cancelling key chord"
+            );
+            state.count = None;
+            return x.ungrab_keyboard();
+        }
+        None => {
+            state.count = None;
+            return Ok(());
+        }
+    };
+
+    if action.submap().is_some() {
+        trace!(?key, "entering key chord submap");
+        if !in_chord {
+            x.grab_keyboard()?;
+        }
+        let mut path = path;
+        path.push(key);
+        *chord = Some(Chord {
+            client_bindings: root,
+            path,
+            deadline: Instant::now() + state.config.chord_timeout,
+        });
+
+        return Ok(());
+    }
+
+    if in_chord {
+        x.ungrab_keyboard()?;
+    }
+
+    trace!(?key, count = ?state.count, "running user keybinding");
+    let res = action.call(state, x);
+    state.count = None;
+    if let Err(error) = res {
+        error!(%error, ?key, "error running user keybinding");
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+// Add an unbound digit key to the count prefix being typed, returning whether or not the key
+// was consumed. A count can not start with 0.
+pub(crate) fn count_digit<X: XConn>(
+    key: KeyCode,
+    bindings: &KeyBindings<X>,
+    keycodes: &HashMap<String, u8>,
+    state: &mut State<X>,
+) -> bool {
+    if key.mask != 0 || bindings.contains_key(&key) {
+        return false;
+    }
+
+    let digit = (0..=9).find(|d| keycodes.get(&d.to_string()) == Some(&key.code));
+    match (digit, state.count) {
+        (None, _) | (Some(0), None) => false,
+        (Some(d), count) => {
+            let count = count.unwrap_or(0).saturating_mul(10).saturating_add(d);
+            trace!(count, "updating count prefix");
+            state.count = Some(count);
+            true
+        }
+    }
+}
+
+// Run the pending modifier release handler (if there is one) once all of the modifiers that were
+// held when it was registered have been released. The state of a key release event includes
+// the modifier being released so that is masked out before checking what is still held.
+pub(crate) fn key_release<X: XConn>(
+    key: KeyCode,
+    keycodes: &HashMap<String, u8>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let released = match modifier_key_mask(key.code, keycodes) {
+        Some(mask) => mask,
+        None => return Ok(()),
+    };
+
+    let still_held = match &state.modifier_release {
+        Some(r) => key.mask & !released & r.mask,
+        None => return Ok(()),
+    };
+
+    if still_held != 0 {
+        return Ok(());
+    }
+
+    if let Some(mut r) = state.modifier_release.take() {
+        if r.grabbed {
+            x.ungrab_keyboard()?;
+        }
+
+        trace!(?key, "modifiers released: running key release handler");
+        if let Err(error) = r.handler.call(state, x) {
+            error!(%error, ?key, "error running key release handler");
             return Err(error);
         }
     }
@@ -41,22 +193,283 @@ pub(crate) fn keypress<X: XConn>(
     Ok(())
 }
 
+// The caller is responsible for re-grabbing the active bindings once they have been remapped
+pub(crate) fn keymap_change<X: XConn>(
+    key_bindings: &mut KeyBindings<X>,
+    client_bindings: &mut [ClientBindings<X>],
+    modes: &mut HashMap<String, Mode<X>>,
+    keycodes: &mut HashMap<String, u8>,
+    x: &X,
+) -> Result<()> {
+    trace!("keyboard mapping changed: re-resolving key bindings");
+    let new_codes = x.keycodes()?;
+    let all_bindings = std::iter::once(key_bindings)
+        .chain(client_bindings.iter_mut().map(|cb| &mut cb.bindings))
+        .chain(modes.values_mut().map(|m| &mut m.bindings));
+
+    for bindings in all_bindings {
+        let current = std::mem::take(bindings);
+        *bindings = remap_key_bindings(current, keycodes, &new_codes);
+        remap_submaps(bindings, keycodes, &new_codes);
+    }
+    *keycodes = new_codes;
+
+    Ok(())
+}
+
 pub(crate) fn mouse_event<X: XConn>(
     e: MouseEvent,
     bindings: &mut MouseBindings<X>,
+    border_bindings: &mut MouseBindings<X>,
     state: &mut State<X>,
     x: &X,
 ) -> Result<()> {
-    if let Some(action) = bindings.get_mut(&(e.kind, e.state.clone())) {
+    let mut e = e;
+    // Pointer events over a framed client are reported against the frame
+    let framed = state.client_for_frame(e.id);
+    if let Some(client) = framed {
+        e.id = client;
+    }
+
+    if state.drag.is_some() {
+        return drag_event(&e, state, x);
+    }
+
+    if e.kind == MouseEventKind::Press {
+        e.state.clicks = count_clicks(&e, state);
+
+        if !border_bindings.is_empty() && state.client_set.contains(&e.id) {
+            e.border = border_edge(&e, state, x)?;
+        }
+    }
+
+    if e.border.is_some() {
+        let key = binding_key(border_bindings, &e);
+        if let Some(action) = border_bindings.get_mut(&key) {
+            trace!(id = %e.id, border = ?e.border, "running border mouse binding");
+            if let Err(error) = action.call(&e, state, x) {
+                error!(%error, ?e, "error running user border mouse binding");
+                return Err(error);
+            }
+
+            return Ok(());
+        }
+    }
+
+    let key = binding_key(bindings, &e);
+    if let Some(action) = bindings.get_mut(&key) {
         if let Err(error) = action.call(&e, state, x) {
             error!(%error, ?e, "error running user mouse binding");
             return Err(error);
         }
+
+        return Ok(());
+    }
+
+    // Unbound left clicks on a frame are handled by its titlebar
+    if framed.is_some()
+        && e.kind == MouseEventKind::Press
+        && e.state.button == MouseButton::Left
+        && e.state.modifiers.is_empty()
+    {
+        return titlebar_click(e.id, e.wpt, state, x);
+    }
+
+    Ok(())
+}
+
+// The binding for the given event, falling back to the single click binding if there isn't one
+// for the number of clicks that have been made
+fn binding_key<X: XConn>(
+    bindings: &MouseBindings<X>,
+    e: &MouseEvent,
+) -> (MouseEventKind, MouseState) {
+    let mut key = (e.kind, e.state.clone());
+    if !bindings.contains_key(&key) {
+        key.1.clicks = 1;
+    }
+
+    key
+}
+
+// The edge of the border of the given client's outermost window that the event landed on
+fn border_edge<X: XConn>(e: &MouseEvent, state: &State<X>, x: &X) -> Result<Option<BorderEdge>> {
+    let bw = state.config.theme.border_width;
+    let r = x.client_geometry(state.outer_window(e.id))?;
+    let outer = Rect::new(r.x, r.y, r.w + 2 * bw, r.h + 2 * bw);
+
+    Ok(BorderEdge::from_point(e.rpt, outer, bw))
+}
+
+// Track consecutive presses of the same button on the same window, returning how many there
+// have been including this one
+fn count_clicks<X: XConn>(e: &MouseEvent, state: &mut State<X>) -> u8 {
+    let now = Instant::now();
+    let clicks = match state.last_click {
+        Some(c)
+            if c.id == e.id
+                && c.button == e.state.button
+                && now.duration_since(c.at) < state.config.double_click_timeout =>
+        {
+            c.clicks.saturating_add(1)
+        }
+        _ => 1,
+    };
+
+    state.last_click = Some(LastClick {
+        id: e.id,
+        button: e.state.button,
+        at: now,
+        clicks,
+    });
+
+    clicks
+}
+
+// While a drag is active all pointer motion is passed to its handler until a button is released
+fn drag_event<X: XConn>(e: &MouseEvent, state: &mut State<X>, x: &X) -> Result<()> {
+    match e.kind {
+        MouseEventKind::Press => Ok(()),
+
+        MouseEventKind::Motion => {
+            let mut drag = match state.drag.take() {
+                Some(drag) => drag,
+                None => return Ok(()),
+            };
+
+            let dx = e.rpt.x as i32 - drag.last.x as i32;
+            let dy = e.rpt.y as i32 - drag.last.y as i32;
+            drag.last = e.rpt;
+            let res = drag.handler.motion(dx, dy, e, state, x);
+
+            // The handler may have replaced the drag with a new one
+            if state.drag.is_none() {
+                state.drag = Some(drag);
+            }
+
+            res
+        }
+
+        MouseEventKind::Release => {
+            let mut drag = match state.drag.take() {
+                Some(drag) => drag,
+                None => return Ok(()),
+            };
+
+            if drag.grabbed {
+                x.ungrab_pointer()?;
+            }
+
+            trace!(?e, "mouse drag finished");
+            drag.handler.release(e, state, x)
+        }
+    }
+}
+
+// Clicking on a titlebar focuses its client unless the click was on the close button
+fn titlebar_click<X: XConn>(client: Xid, p: Point, state: &mut State<X>, x: &X) -> Result<()> {
+    let on_close_button = match (&state.config.titlebar, state.frame_for(client)) {
+        (Some(tb), Some(frame)) => {
+            let w = x.client_geometry(frame)?.w;
+            tb.close_button_region(w)
+                .map(|r| r.contains_point(p))
+                .unwrap_or(false)
+        }
+        _ => false,
+    };
+
+    if on_close_button {
+        trace!(%client, "titlebar close button clicked");
+        x.kill(client)
+    } else {
+        x.set_active_client(client, state)
+    }
+}
+
+pub(crate) fn expose<X: XConn>(e: &ExposeEvent, state: &mut State<X>, x: &X) -> Result<()> {
+    // Only redraw once we have the last expose event in a series
+    if e.count > 0 {
+        return Ok(());
+    }
+
+    match state.client_for_frame(e.id) {
+        Some(client) => draw_titlebar(client, state, x),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn property_notify<X: XConn>(
+    e: &PropertyEvent,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let is_title = e.atom == Atom::WmName.as_ref() || e.atom == Atom::NetWmName.as_ref();
+
+    if is_title && state.frame_for(e.id).is_some() {
+        draw_titlebar(e.id, state, x)?;
+    }
+
+    // Clients whose size hints change need repositioning even though their layout position
+    // is unchanged
+    let is_size_hints = e.atom == Atom::WmNormalHints.as_ref();
+    let visible = state.diff.after.positions.iter().any(|&(c, _)| c == e.id);
+    if is_size_hints && visible {
+        trace!(id = %e.id, "client size hints updated");
+        state.stale_positions.insert(e.id);
+        x.refresh(state)?;
+    }
+
+    // Urgency is shared by all members of a window group
+    let is_hints = e.atom == Atom::WmHints.as_ref();
+    let focused = state.focused_client();
+    if is_hints && state.client_set.contains(&e.id) && focused != Some(e.id) {
+        let urgent = x.group_is_urgent(e.id, &state.client_set)?;
+        trace!(id = %e.id, urgent, "client urgency hint updated");
+
+        for c in state.client_set.group_members(&e.id) {
+            if Some(c) == focused {
+                continue;
+            }
+
+            state.set_urgent(c, urgent);
+            let color = if urgent {
+                state.config.theme.urgent_border
+            } else {
+                state.border_color(c, false)
+            };
+            x.set_client_border_color(state.outer_window(c), color)?;
+        }
     }
 
     Ok(())
 }
 
+// Motion is passed on to mouse bindings for the first held button (if any)
+pub(crate) fn motion<X: XConn>(
+    e: MotionEvent,
+    bindings: &mut MouseBindings<X>,
+    border_bindings: &mut MouseBindings<X>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let button = match (e.buttons.first(), &state.drag) {
+        (Some(&button), _) => button,
+        (None, Some(drag)) => drag.mouse_state.button,
+        (None, None) => return Ok(()),
+    };
+
+    let evt = MouseEvent {
+        id: e.id,
+        rpt: e.rpt,
+        wpt: e.wpt,
+        state: MouseState::new(button, e.modifiers),
+        kind: MouseEventKind::Motion,
+        border: None,
+    };
+
+    mouse_event(evt, bindings, border_bindings, state, x)
+}
+
 pub(crate) fn map_request<X: XConn>(client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
     trace!(?client, "handling new map request");
     let attrs = x.get_window_attributes(client)?;
@@ -71,6 +484,9 @@ pub(crate) fn map_request<X: XConn>(client: Xid, state: &mut State<X>, x: &X) ->
 
 pub(crate) fn destroy<X: XConn>(client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
     trace!(?client, "destroying client");
+    if let Some(frame) = state.frames.remove(&client) {
+        x.destroy_window(frame)?;
+    }
     x.unmanage(client, state)?;
     state.mapped.remove(&client);
     state.pending_unmap.remove(&client);
@@ -80,6 +496,11 @@ pub(crate) fn destroy<X: XConn>(client: Xid, state: &mut State<X>, x: &X) -> Res
 
 // Expected unmap events are tracked in pending_unmap. We ignore expected unmaps.
 pub(crate) fn unmap_notify<X: XConn>(client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+    // Frames are only ever unmapped by us when hiding the client they contain
+    if state.client_for_frame(client).is_some() {
+        return Ok(());
+    }
+
     let expected = *state.pending_unmap.get(&client).unwrap_or(&0);
 
     if expected == 0 {
@@ -129,7 +550,7 @@ pub(crate) fn enter<X: XConn>(p: PointerChange, state: &mut State<X>, x: &X) ->
 }
 
 pub(crate) fn leave<X: XConn>(p: PointerChange, state: &mut State<X>, x: &X) -> Result<()> {
-    if p.id == state.root() && !p.same_screen {
+    if state.is_root(p.id) && !p.same_screen {
         x.focus(p.id)?;
         set_screen_from_point(p.abs, state, x)?;
     }
@@ -139,10 +560,43 @@ pub(crate) fn leave<X: XConn>(p: PointerChange, state: &mut State<X>, x: &X) ->
 
 pub(crate) fn detect_screens<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
     info!("re-detecting screens");
-    let rects = x.screen_details()?;
-    info!(?rects, "found screens");
+    let outputs = x.output_details()?;
+    info!(?outputs, "found screens");
+
+    let previous: Vec<OutputInfo> = state
+        .client_set
+        .screens()
+        .filter_map(|s| s.output().cloned())
+        .collect();
+    let changes = OutputChanges::between(&previous, &outputs);
+
+    let policy = state.config.hotplug_policy;
+    state
+        .client_set
+        .update_screens_with_outputs(outputs, policy)?;
+
+    if state.config.respect_struts {
+        x.update_workarea(state)?;
+    }
 
-    state.client_set.update_screens(rects)
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let mut hook = state.config.monitor_change_hook.take();
+    if let Some(ref mut h) = hook {
+        trace!(?changes, "running user monitor change hook");
+        if !run_hook("user monitor change hook", state, |s| {
+            h.call(&changes, s, x)
+        })
+        .1
+        {
+            hook = None;
+        }
+    }
+    state.config.monitor_change_hook = hook;
+
+    Ok(())
 }
 
 pub(crate) fn screen_change<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
@@ -150,7 +604,7 @@ pub(crate) fn screen_change<X: XConn>(state: &mut State<X>, x: &X) -> Result<()>
     set_screen_from_point(x.cursor_position()?, state, x)
 }
 
-fn set_screen_from_point<X: XConn>(p: Point, state: &mut State<X>, x: &X) -> Result<()> {
+pub(crate) fn set_screen_from_point<X: XConn>(p: Point, state: &mut State<X>, x: &X) -> Result<()> {
     x.modify_and_refresh(state, |cs| {
         let index = cs
             .screens()
@@ -162,3 +616,305 @@ fn set_screen_from_point<X: XConn>(p: Point, state: &mut State<X>, x: &X) -> Res
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::actions::{mouse_handler, root_or_client},
+        core::{
+            bindings::{DragHandler, ModifierKey},
+            WindowManager,
+        },
+        x::{
+            fixtures::{
+                meta_mouse_event, monocle_titlebars, motion, record, recorded, requests_matching,
+                ScriptedWm,
+            },
+            mock::{Request, ScriptedConn},
+            XEvent,
+        },
+    };
+    use simple_test_case::test_case;
+    use std::time::Duration;
+
+    fn meta(button: MouseButton) -> MouseState {
+        MouseState::new(button, vec![ModifierKey::Meta])
+    }
+
+    fn meta_press(id: Xid, button: MouseButton) -> XEvent {
+        meta_mouse_event(id, button, MouseEventKind::Press, 0, 0)
+    }
+
+    // Scrolling with Meta held records the window being scrolled over
+    fn scroll_wm(event: XEvent) -> WindowManager<ScriptedConn> {
+        let mut conf = ScriptedWm::new().with_clients(2).with_events([event]);
+        for button in [MouseButton::ScrollUp, MouseButton::ScrollRight] {
+            conf = conf.with_mouse(
+                MouseEventKind::Press,
+                meta(button),
+                root_or_client(
+                    mouse_handler(|_, s: &mut State<ScriptedConn>, _| {
+                        record(s, "root");
+                        Ok(())
+                    }),
+                    mouse_handler(|e, s: &mut State<ScriptedConn>, _| {
+                        record(s, format!("client {}", e.id));
+                        Ok(())
+                    }),
+                ),
+            );
+        }
+
+        conf.run()
+    }
+
+    #[test_case(MouseButton::ScrollUp; "vertical")]
+    #[test_case(MouseButton::ScrollRight; "horizontal")]
+    #[test]
+    fn scrolling_over_the_root_window(button: MouseButton) {
+        let wm = scroll_wm(meta_press(Xid(0), button));
+
+        assert_eq!(recorded(&wm), vec!["root"]);
+    }
+
+    #[test]
+    fn scrolling_over_a_client() {
+        let wm = scroll_wm(meta_press(Xid(1), MouseButton::ScrollUp));
+
+        assert_eq!(recorded(&wm), vec![format!("client {}", Xid(1))]);
+    }
+
+    #[test]
+    fn scrolling_over_unmanaged_windows_is_ignored() {
+        let wm = scroll_wm(meta_press(Xid(42), MouseButton::ScrollUp));
+
+        assert!(recorded(&wm).is_empty());
+    }
+
+    // Client 1 is framed by Xid(10) and client 2 is focused before the titlebar of client 1
+    // is clicked
+    fn titlebar_click_wm(conf: ScriptedWm) -> WindowManager<ScriptedConn> {
+        let state = MouseState::new(MouseButton::Left, vec![]);
+        let press = MouseEvent::new(Xid(10), 10, 5, 10, 5, state, MouseEventKind::Press);
+
+        conf.with_config(monocle_titlebars)
+            .with_frame(Xid(1), Xid(10))
+            .with_clients(2)
+            .with_events([XEvent::MouseEvent(press)])
+            .run()
+    }
+
+    #[test]
+    fn unbound_titlebar_clicks_focus_the_client() {
+        let wm = titlebar_click_wm(ScriptedWm::new());
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(1)));
+    }
+
+    #[test]
+    fn user_mouse_bindings_take_priority_over_titlebar_clicks() {
+        let conf = ScriptedWm::new().with_mouse(
+            MouseEventKind::Press,
+            MouseState::new(MouseButton::Left, vec![]),
+            mouse_handler(|_, s: &mut State<ScriptedConn>, _| {
+                record(s, "clicked");
+                Ok(())
+            }),
+        );
+
+        let wm = titlebar_click_wm(conf);
+
+        assert_eq!(recorded(&wm), vec!["clicked"]);
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(2)));
+    }
+
+    // Meta + Left / Right clicks are recorded as "single" and Meta + Left double clicks
+    // as "double"
+    fn click_wm(events: Vec<XEvent>) -> ScriptedWm {
+        let mut conf = ScriptedWm::new().with_clients(2).with_events(events);
+        for button in [MouseButton::Left, MouseButton::Right] {
+            conf = conf.with_mouse(
+                MouseEventKind::Press,
+                meta(button),
+                mouse_handler(|_, s: &mut State<ScriptedConn>, _| {
+                    record(s, "single");
+                    Ok(())
+                }),
+            );
+        }
+
+        conf.with_mouse(
+            MouseEventKind::Press,
+            meta(MouseButton::Left).with_clicks(2),
+            mouse_handler(|_, s: &mut State<ScriptedConn>, _| {
+                record(s, "double");
+                Ok(())
+            }),
+        )
+    }
+
+    #[test_case(vec![meta_press(Xid(1), MouseButton::Left)], vec!["single"]; "single click")]
+    #[test_case(vec![meta_press(Xid(1), MouseButton::Left), meta_press(Xid(1), MouseButton::Left)], vec!["single", "double"]; "double click")]
+    #[test_case(vec![meta_press(Xid(1), MouseButton::Left), meta_press(Xid(2), MouseButton::Left)], vec!["single", "single"]; "different windows")]
+    #[test_case(vec![meta_press(Xid(1), MouseButton::Right), meta_press(Xid(1), MouseButton::Left)], vec!["single", "single"]; "different buttons")]
+    #[test_case(vec![meta_press(Xid(1), MouseButton::Right), meta_press(Xid(1), MouseButton::Right)], vec!["single", "single"]; "unbound double click")]
+    #[test]
+    fn double_clicks(events: Vec<XEvent>, expected: Vec<&str>) {
+        let wm = click_wm(events).run();
+
+        assert_eq!(recorded(&wm), expected);
+    }
+
+    #[test]
+    fn double_click_timeout_is_respected() {
+        let wm = click_wm(vec![
+            meta_press(Xid(1), MouseButton::Left),
+            meta_press(Xid(1), MouseButton::Left),
+        ])
+        .with_config(|c| c.double_click_timeout = Duration::ZERO)
+        .run();
+
+        assert_eq!(recorded(&wm), vec!["single", "single"]);
+    }
+
+    #[test]
+    fn double_click_bindings_share_single_click_grabs() {
+        let wm = click_wm(vec![]).run();
+
+        let last_grab = requests_matching(&wm, |r| matches!(r, Request::Grab(..))).pop();
+        let expected = vec![meta(MouseButton::Left), meta(MouseButton::Right)];
+        assert_eq!(last_grab, Some(Request::Grab(vec![], expected)));
+    }
+
+    #[derive(Debug, Default, Clone, Copy, PartialEq)]
+    struct DragTotal {
+        dx: i32,
+        dy: i32,
+        motions: usize,
+        released: bool,
+    }
+
+    struct TotalDrag;
+
+    impl DragHandler<ScriptedConn> for TotalDrag {
+        fn motion(
+            &mut self,
+            dx: i32,
+            dy: i32,
+            _: &MouseEvent,
+            state: &mut State<ScriptedConn>,
+            _: &ScriptedConn,
+        ) -> Result<()> {
+            let total = state.extension_or_default::<DragTotal>();
+            let mut total = total.borrow_mut();
+            total.dx += dx;
+            total.dy += dy;
+            total.motions += 1;
+
+            Ok(())
+        }
+
+        fn release(
+            &mut self,
+            _: &MouseEvent,
+            state: &mut State<ScriptedConn>,
+            _: &ScriptedConn,
+        ) -> Result<()> {
+            state
+                .extension_or_default::<DragTotal>()
+                .borrow_mut()
+                .released = true;
+
+            Ok(())
+        }
+    }
+
+    fn drag_event(kind: MouseEventKind, x: i16, y: i16) -> XEvent {
+        meta_mouse_event(Xid(1), MouseButton::Left, kind, x, y)
+    }
+
+    // Meta + Left starts a TotalDrag over client 1
+    fn drag_wm(events: Vec<XEvent>) -> WindowManager<ScriptedConn> {
+        ScriptedWm::new()
+            .with_clients(1)
+            .with_events(events)
+            .with_mouse(
+                MouseEventKind::Press,
+                meta(MouseButton::Left),
+                mouse_handler(|_, s: &mut State<ScriptedConn>, _| {
+                    s.start_drag(Box::new(TotalDrag));
+                    Ok(())
+                }),
+            )
+            .build()
+    }
+
+    #[test]
+    fn drags_receive_motion_deltas() {
+        let wm = drag_wm(vec![
+            drag_event(MouseEventKind::Press, 10, 10),
+            motion(15, 12),
+            motion(20, 20),
+            drag_event(MouseEventKind::Release, 20, 20),
+            motion(50, 50),
+        ])
+        .run_script()
+        .unwrap();
+
+        let total = *wm.state.extension::<DragTotal>().unwrap().borrow();
+        let expected = DragTotal {
+            dx: 10,
+            dy: 10,
+            motions: 2,
+            released: true,
+        };
+        assert_eq!(total, expected);
+    }
+
+    #[test]
+    fn drags_grab_the_pointer() {
+        let wm = drag_wm(vec![
+            drag_event(MouseEventKind::Press, 10, 10),
+            motion(15, 12),
+            drag_event(MouseEventKind::Release, 15, 12),
+        ])
+        .run_script()
+        .unwrap();
+
+        let grabs = requests_matching(&wm, |r| {
+            matches!(r, Request::GrabPointer | Request::UngrabPointer)
+        });
+        assert_eq!(grabs, vec![Request::GrabPointer, Request::UngrabPointer]);
+    }
+
+    #[test_case(1, 400, Some(BorderEdge::Left); "left edge")]
+    #[test_case(999, 1, Some(BorderEdge::TopRight); "top right corner")]
+    #[test_case(500, 799, Some(BorderEdge::Bottom); "bottom edge")]
+    #[test_case(500, 400, None; "inside the client")]
+    #[test]
+    fn border_bindings(x: i16, y: i16, expected: Option<BorderEdge>) {
+        let mut wm = drag_wm(vec![drag_event(MouseEventKind::Press, x, y)]);
+        let mut border_bindings: MouseBindings<ScriptedConn> = HashMap::new();
+        border_bindings.insert(
+            (MouseEventKind::Press, meta(MouseButton::Left)),
+            mouse_handler(|e, s: &mut State<ScriptedConn>, _| {
+                record(s, format!("{:?}", e.border));
+                Ok(())
+            }),
+        );
+        wm.add_border_bindings(border_bindings);
+        let wm = wm.run_script().unwrap();
+
+        match expected {
+            Some(edge) => {
+                assert_eq!(recorded(&wm), vec![format!("{:?}", Some(edge))]);
+                assert!(wm.state.drag.is_none());
+            }
+            None => {
+                assert!(recorded(&wm).is_empty());
+                assert!(wm.state.drag.is_some());
+            }
+        }
+    }
+}
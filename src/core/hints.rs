@@ -0,0 +1,134 @@
+//! Overlay hints listing the key bindings that are available after pressing a prefix key or
+//! entering a binding mode.
+use crate::{pure::geometry::Rect, Color};
+
+/// Configuration for the binding hint overlay, similar to which-key in emacs.
+///
+/// When enabled, an overlay listing the available keys (along with their descriptions if
+/// they have been set using [describe][crate::builtin::actions::describe]) is shown while
+/// waiting for the next key of a key chord and while a binding mode is active. The overlay
+/// is hidden once the chord is completed, cancelled or times out and when the mode is
+/// exited. Requires support from the [XConn][crate::x::XConn] being used (see
+/// [XConn::create_overlay][crate::x::XConn::create_overlay]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BindingHintsConfig {
+    /// The text color
+    pub fg: Color,
+    /// The background color
+    pub bg: Color,
+    /// The maximum width of the overlay in pixels
+    pub width: u32,
+    /// The height of each line of the overlay in pixels
+    pub line_height: u32,
+}
+
+impl Default for BindingHintsConfig {
+    fn default() -> Self {
+        Self {
+            fg: "#ebdbb2".try_into().expect("valid hex code"),
+            bg: "#282828".try_into().expect("valid hex code"),
+            width: 400,
+            line_height: 18,
+        }
+    }
+}
+
+impl BindingHintsConfig {
+    /// Build the [BindingHints] to draw for the given entries, centered along the bottom
+    /// edge of `screen`.
+    pub fn hints(&self, entries: Vec<BindingHint>, screen: Rect) -> BindingHints {
+        // Half a line of padding above and below the entries
+        let h = (self.line_height * (entries.len() as u32 + 1)).min(screen.h);
+        let w = self.width.min(screen.w);
+        let r = Rect::new(screen.x + (screen.w - w) / 2, screen.y + screen.h - h, w, h);
+
+        BindingHints {
+            entries,
+            r,
+            line_height: self.line_height,
+            fg: self.fg,
+            bg: self.bg,
+        }
+    }
+}
+
+/// A single available key binding to show in a binding hint overlay.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BindingHint {
+    /// The key to press
+    pub key: String,
+    /// A description of what the binding does
+    pub description: String,
+}
+
+/// Everything required to draw a binding hint overlay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BindingHints {
+    /// The available bindings
+    pub entries: Vec<BindingHint>,
+    /// The position of the overlay on the screen
+    pub r: Rect,
+    /// The height of each line in pixels
+    pub line_height: u32,
+    /// The text color
+    pub fg: Color,
+    /// The background color
+    pub bg: Color,
+}
+
+impl BindingHints {
+    /// The lines of text to display, with keys padded so that descriptions line up.
+    pub fn lines(&self) -> Vec<String> {
+        let key_width = self
+            .entries
+            .iter()
+            .map(|e| e.key.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        self.entries
+            .iter()
+            .map(|e| format!("{:<key_width$}  {}", e.key, e.description))
+            .map(|s| s.trim_end().to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hint(key: &str, description: &str) -> BindingHint {
+        BindingHint {
+            key: key.to_string(),
+            description: description.to_string(),
+        }
+    }
+
+    #[test]
+    fn hints_are_centered_along_the_bottom_of_the_screen() {
+        let conf = BindingHintsConfig::default();
+        let entries = vec![hint("g", "gimp"), hint("f", "firefox")];
+
+        let hints = conf.hints(entries, Rect::new(1000, 0, 1000, 800));
+
+        assert_eq!(hints.r, Rect::new(1300, 746, 400, 54));
+    }
+
+    #[test]
+    fn lines_align_descriptions() {
+        let conf = BindingHintsConfig::default();
+        let entries = vec![
+            hint("g", "gimp"),
+            hint("S-Return", "terminal"),
+            hint("x", ""),
+        ];
+
+        let hints = conf.hints(entries, Rect::new(0, 0, 1000, 800));
+
+        assert_eq!(
+            hints.lines(),
+            vec!["g         gimp", "S-Return  terminal", "x"]
+        );
+    }
+}
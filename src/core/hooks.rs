@@ -1,15 +1,27 @@
 //! Traits for writing and composing hooks
 use crate::{
-    core::State,
-    x::{XConn, XEvent},
+    core::{idle::IdleEvent, State},
+    pure::OutputChanges,
+    x::{XConn, XEvent, XEventKind},
     Result, Xid,
 };
-use std::fmt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+};
+use tracing::{error, warn};
 
 /// Handle an [XEvent], return `true` if default event handling should be run afterwards.
 ///
 /// This hook is called before incoming XEvents are processed by the default event handling
 /// logic.
+///
+/// By default event hooks are run for every event. Hooks that only care about specific kinds
+/// of event should override [EventHook::wants] (or be wrapped using [EventHook::only_for]) so
+/// that they are skipped for high frequency events such as pointer motion.
 pub trait EventHook<X>
 where
     X: XConn,
@@ -17,6 +29,25 @@ where
     /// Run this hook
     fn call(&mut self, event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool>;
 
+    /// Whether or not this hook should be run for events of the given kind.
+    ///
+    /// The default implementation returns `true` for all events.
+    fn wants(&self, kind: XEventKind) -> bool {
+        let _ = kind;
+        true
+    }
+
+    /// Only run this hook for events of the given kinds.
+    fn only_for(self, kinds: &[XEventKind]) -> FilteredEventHook<X>
+    where
+        Self: Sized + 'static,
+    {
+        FilteredEventHook {
+            kinds: kinds.to_vec(),
+            inner: Box::new(self),
+        }
+    }
+
     /// Convert to a trait object
     fn boxed(self) -> Box<dyn EventHook<X>>
     where
@@ -73,12 +104,45 @@ where
     X: XConn,
 {
     fn call(&mut self, event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
-        if self.first.call(event, state, x)? {
+        let kind = event.kind();
+        if self.first.wants(kind) && !self.first.call(event, state, x)? {
+            return Ok(false);
+        }
+
+        if self.second.wants(kind) {
             self.second.call(event, state, x)
         } else {
-            Ok(false)
+            Ok(true)
         }
     }
+
+    fn wants(&self, kind: XEventKind) -> bool {
+        self.first.wants(kind) || self.second.wants(kind)
+    }
+}
+
+/// An [EventHook] that is only run for specific kinds of event, created using
+/// [EventHook::only_for].
+#[derive(Debug)]
+pub struct FilteredEventHook<X>
+where
+    X: XConn,
+{
+    kinds: Vec<XEventKind>,
+    inner: Box<dyn EventHook<X>>,
+}
+
+impl<X> EventHook<X> for FilteredEventHook<X>
+where
+    X: XConn,
+{
+    fn call(&mut self, event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
+        self.inner.call(event, state, x)
+    }
+
+    fn wants(&self, kind: XEventKind) -> bool {
+        self.kinds.contains(&kind) && self.inner.wants(kind)
+    }
 }
 
 impl<F, X> EventHook<X> for F
@@ -171,6 +235,544 @@ where
     }
 }
 
+/// The final known state of a client that has been removed from the window manager state,
+/// passed to [UnmanageHook]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnmanagedClient {
+    /// The ID of the client window
+    pub id: Xid,
+    /// The tag of the workspace the client was on
+    pub tag: String,
+    /// Whether or not the client was floating
+    pub floating: bool,
+    /// Whether or not the client was fullscreen
+    pub fullscreen: bool,
+    /// The client this client was transient for, if any
+    pub transient_for: Option<Xid>,
+    /// The index of the X screen the client was on
+    pub x_screen: Option<usize>,
+}
+
+/// Action to run when a client is removed from the window manager state after being
+/// destroyed or unmapped.
+///
+/// Unmanage hooks are run after the client has been removed and the on screen X state has
+/// been refreshed so the client window itself may no longer exist.
+pub trait UnmanageHook<X>
+where
+    X: XConn,
+{
+    /// Run this hook
+    fn call(&mut self, client: &UnmanagedClient, state: &mut State<X>, x: &X) -> Result<()>;
+
+    /// Convert to a trait object
+    fn boxed(self) -> Box<dyn UnmanageHook<X>>
+    where
+        Self: Sized + 'static,
+    {
+        Box::new(self)
+    }
+
+    /// Compose this hook with another [UnmanageHook].
+    fn then<H>(self, next: H) -> ComposedUnmanageHook<X>
+    where
+        H: UnmanageHook<X> + 'static,
+        Self: Sized + 'static,
+    {
+        ComposedUnmanageHook {
+            first: Box::new(self),
+            second: Box::new(next),
+        }
+    }
+
+    /// Compose this hook with a boxed [UnmanageHook].
+    fn then_boxed(self, next: Box<dyn UnmanageHook<X>>) -> Box<dyn UnmanageHook<X>>
+    where
+        Self: Sized + 'static,
+        X: 'static,
+    {
+        Box::new(ComposedUnmanageHook {
+            first: Box::new(self),
+            second: next,
+        })
+    }
+}
+
+impl<X: XConn> fmt::Debug for Box<dyn UnmanageHook<X>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnmanageHook").finish()
+    }
+}
+
+/// The result of composing two unmanage hooks using `then`
+#[derive(Debug)]
+pub struct ComposedUnmanageHook<X>
+where
+    X: XConn,
+{
+    first: Box<dyn UnmanageHook<X>>,
+    second: Box<dyn UnmanageHook<X>>,
+}
+
+impl<X> UnmanageHook<X> for ComposedUnmanageHook<X>
+where
+    X: XConn,
+{
+    fn call(&mut self, client: &UnmanagedClient, state: &mut State<X>, x: &X) -> Result<()> {
+        self.first.call(client, state, x)?;
+        self.second.call(client, state, x)
+    }
+}
+
+impl<F, X> UnmanageHook<X> for F
+where
+    F: FnMut(&UnmanagedClient, &mut State<X>, &X) -> Result<()>,
+    X: XConn,
+{
+    fn call(&mut self, client: &UnmanagedClient, state: &mut State<X>, x: &X) -> Result<()> {
+        (self)(client, state, x)
+    }
+}
+
+/// Action to run when the focused client changes, receiving the previously focused client and
+/// the newly focused client.
+///
+/// Focus change hooks are run as part of refreshing the on screen X state, after input focus
+/// has been set and before the refresh hook is run.
+pub trait FocusChangeHook<X>
+where
+    X: XConn,
+{
+    /// Run this hook
+    fn call(
+        &mut self,
+        old: Option<Xid>,
+        new: Option<Xid>,
+        state: &mut State<X>,
+        x: &X,
+    ) -> Result<()>;
+
+    /// Convert to a trait object
+    fn boxed(self) -> Box<dyn FocusChangeHook<X>>
+    where
+        Self: Sized + 'static,
+    {
+        Box::new(self)
+    }
+
+    /// Compose this hook with another [FocusChangeHook].
+    fn then<H>(self, next: H) -> ComposedFocusChangeHook<X>
+    where
+        H: FocusChangeHook<X> + 'static,
+        Self: Sized + 'static,
+    {
+        ComposedFocusChangeHook {
+            first: Box::new(self),
+            second: Box::new(next),
+        }
+    }
+
+    /// Compose this hook with a boxed [FocusChangeHook].
+    fn then_boxed(self, next: Box<dyn FocusChangeHook<X>>) -> Box<dyn FocusChangeHook<X>>
+    where
+        Self: Sized + 'static,
+        X: 'static,
+    {
+        Box::new(ComposedFocusChangeHook {
+            first: Box::new(self),
+            second: next,
+        })
+    }
+}
+
+impl<X: XConn> fmt::Debug for Box<dyn FocusChangeHook<X>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FocusChangeHook").finish()
+    }
+}
+
+/// The result of composing two focus change hooks using `then`
+#[derive(Debug)]
+pub struct ComposedFocusChangeHook<X>
+where
+    X: XConn,
+{
+    first: Box<dyn FocusChangeHook<X>>,
+    second: Box<dyn FocusChangeHook<X>>,
+}
+
+impl<X> FocusChangeHook<X> for ComposedFocusChangeHook<X>
+where
+    X: XConn,
+{
+    fn call(
+        &mut self,
+        old: Option<Xid>,
+        new: Option<Xid>,
+        state: &mut State<X>,
+        x: &X,
+    ) -> Result<()> {
+        self.first.call(old, new, state, x)?;
+        self.second.call(old, new, state, x)
+    }
+}
+
+impl<F, X> FocusChangeHook<X> for F
+where
+    F: FnMut(Option<Xid>, Option<Xid>, &mut State<X>, &X) -> Result<()>,
+    X: XConn,
+{
+    fn call(
+        &mut self,
+        old: Option<Xid>,
+        new: Option<Xid>,
+        state: &mut State<X>,
+        x: &X,
+    ) -> Result<()> {
+        (self)(old, new, state, x)
+    }
+}
+
+/// Action to run when the user becomes idle or active again (see the [idle][0] module docs).
+///
+///   [0]: crate::core::idle
+pub trait IdleHook<X>
+where
+    X: XConn,
+{
+    /// Run this hook
+    fn call(&mut self, event: IdleEvent, state: &mut State<X>, x: &X) -> Result<()>;
+
+    /// Convert to a trait object
+    fn boxed(self) -> Box<dyn IdleHook<X>>
+    where
+        Self: Sized + 'static,
+    {
+        Box::new(self)
+    }
+
+    /// Compose this hook with another [IdleHook].
+    fn then<H>(self, next: H) -> ComposedIdleHook<X>
+    where
+        H: IdleHook<X> + 'static,
+        Self: Sized + 'static,
+    {
+        ComposedIdleHook {
+            first: Box::new(self),
+            second: Box::new(next),
+        }
+    }
+
+    /// Compose this hook with a boxed [IdleHook].
+    fn then_boxed(self, next: Box<dyn IdleHook<X>>) -> Box<dyn IdleHook<X>>
+    where
+        Self: Sized + 'static,
+        X: 'static,
+    {
+        Box::new(ComposedIdleHook {
+            first: Box::new(self),
+            second: next,
+        })
+    }
+}
+
+impl<X: XConn> fmt::Debug for Box<dyn IdleHook<X>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdleHook").finish()
+    }
+}
+
+/// The result of composing two idle hooks using `then`
+#[derive(Debug)]
+pub struct ComposedIdleHook<X>
+where
+    X: XConn,
+{
+    first: Box<dyn IdleHook<X>>,
+    second: Box<dyn IdleHook<X>>,
+}
+
+impl<X> IdleHook<X> for ComposedIdleHook<X>
+where
+    X: XConn,
+{
+    fn call(&mut self, event: IdleEvent, state: &mut State<X>, x: &X) -> Result<()> {
+        self.first.call(event, state, x)?;
+        self.second.call(event, state, x)
+    }
+}
+
+impl<F, X> IdleHook<X> for F
+where
+    F: FnMut(IdleEvent, &mut State<X>, &X) -> Result<()>,
+    X: XConn,
+{
+    fn call(&mut self, event: IdleEvent, state: &mut State<X>, x: &X) -> Result<()> {
+        (self)(event, state, x)
+    }
+}
+
+/// Action to run when the set of connected outputs (monitors) changes, with details of the
+/// outputs that were added, removed or resized.
+pub trait MonitorChangeHook<X>
+where
+    X: XConn,
+{
+    /// Run this hook
+    fn call(&mut self, changes: &OutputChanges, state: &mut State<X>, x: &X) -> Result<()>;
+
+    /// Convert to a trait object
+    fn boxed(self) -> Box<dyn MonitorChangeHook<X>>
+    where
+        Self: Sized + 'static,
+    {
+        Box::new(self)
+    }
+
+    /// Compose this hook with another [MonitorChangeHook].
+    fn then<H>(self, next: H) -> ComposedMonitorChangeHook<X>
+    where
+        H: MonitorChangeHook<X> + 'static,
+        Self: Sized + 'static,
+    {
+        ComposedMonitorChangeHook {
+            first: Box::new(self),
+            second: Box::new(next),
+        }
+    }
+
+    /// Compose this hook with a boxed [MonitorChangeHook].
+    fn then_boxed(self, next: Box<dyn MonitorChangeHook<X>>) -> Box<dyn MonitorChangeHook<X>>
+    where
+        Self: Sized + 'static,
+        X: 'static,
+    {
+        Box::new(ComposedMonitorChangeHook {
+            first: Box::new(self),
+            second: next,
+        })
+    }
+}
+
+impl<X: XConn> fmt::Debug for Box<dyn MonitorChangeHook<X>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MonitorChangeHook").finish()
+    }
+}
+
+/// The result of composing two monitor change hooks using `then`
+#[derive(Debug)]
+pub struct ComposedMonitorChangeHook<X>
+where
+    X: XConn,
+{
+    first: Box<dyn MonitorChangeHook<X>>,
+    second: Box<dyn MonitorChangeHook<X>>,
+}
+
+impl<X> MonitorChangeHook<X> for ComposedMonitorChangeHook<X>
+where
+    X: XConn,
+{
+    fn call(&mut self, changes: &OutputChanges, state: &mut State<X>, x: &X) -> Result<()> {
+        self.first.call(changes, state, x)?;
+        self.second.call(changes, state, x)
+    }
+}
+
+impl<F, X> MonitorChangeHook<X> for F
+where
+    F: FnMut(&OutputChanges, &mut State<X>, &X) -> Result<()>,
+    X: XConn,
+{
+    fn call(&mut self, changes: &OutputChanges, state: &mut State<X>, x: &X) -> Result<()> {
+        (self)(changes, state, x)
+    }
+}
+
+/// Action to run when the active layout of a visible workspace changes, receiving the tag of
+/// the workspace along with the names of the previous and new layouts.
+///
+/// Layout change hooks are run as part of refreshing the on screen X state, before the refresh
+/// hook is run.
+pub trait LayoutChangeHook<X>
+where
+    X: XConn,
+{
+    /// Run this hook
+    fn call(&mut self, tag: &str, old: &str, new: &str, state: &mut State<X>, x: &X) -> Result<()>;
+
+    /// Convert to a trait object
+    fn boxed(self) -> Box<dyn LayoutChangeHook<X>>
+    where
+        Self: Sized + 'static,
+    {
+        Box::new(self)
+    }
+
+    /// Compose this hook with another [LayoutChangeHook].
+    fn then<H>(self, next: H) -> ComposedLayoutChangeHook<X>
+    where
+        H: LayoutChangeHook<X> + 'static,
+        Self: Sized + 'static,
+    {
+        ComposedLayoutChangeHook {
+            first: Box::new(self),
+            second: Box::new(next),
+        }
+    }
+
+    /// Compose this hook with a boxed [LayoutChangeHook].
+    fn then_boxed(self, next: Box<dyn LayoutChangeHook<X>>) -> Box<dyn LayoutChangeHook<X>>
+    where
+        Self: Sized + 'static,
+        X: 'static,
+    {
+        Box::new(ComposedLayoutChangeHook {
+            first: Box::new(self),
+            second: next,
+        })
+    }
+}
+
+impl<X: XConn> fmt::Debug for Box<dyn LayoutChangeHook<X>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LayoutChangeHook").finish()
+    }
+}
+
+/// The result of composing two layout change hooks using `then`
+#[derive(Debug)]
+pub struct ComposedLayoutChangeHook<X>
+where
+    X: XConn,
+{
+    first: Box<dyn LayoutChangeHook<X>>,
+    second: Box<dyn LayoutChangeHook<X>>,
+}
+
+impl<X> LayoutChangeHook<X> for ComposedLayoutChangeHook<X>
+where
+    X: XConn,
+{
+    fn call(&mut self, tag: &str, old: &str, new: &str, state: &mut State<X>, x: &X) -> Result<()> {
+        self.first.call(tag, old, new, state, x)?;
+        self.second.call(tag, old, new, state, x)
+    }
+}
+
+impl<F, X> LayoutChangeHook<X> for F
+where
+    F: FnMut(&str, &str, &str, &mut State<X>, &X) -> Result<()>,
+    X: XConn,
+{
+    fn call(&mut self, tag: &str, old: &str, new: &str, state: &mut State<X>, x: &X) -> Result<()> {
+        (self)(tag, old, new, state, x)
+    }
+}
+
+/// Action to run when a screen switches to showing a different workspace, receiving the index
+/// of the screen along with the tags of the previous and new workspaces.
+///
+/// Workspace switch hooks are run as part of refreshing the on screen X state, before the
+/// refresh hook is run.
+pub trait WorkspaceSwitchHook<X>
+where
+    X: XConn,
+{
+    /// Run this hook
+    fn call(
+        &mut self,
+        screen: usize,
+        old: &str,
+        new: &str,
+        state: &mut State<X>,
+        x: &X,
+    ) -> Result<()>;
+
+    /// Convert to a trait object
+    fn boxed(self) -> Box<dyn WorkspaceSwitchHook<X>>
+    where
+        Self: Sized + 'static,
+    {
+        Box::new(self)
+    }
+
+    /// Compose this hook with another [WorkspaceSwitchHook].
+    fn then<H>(self, next: H) -> ComposedWorkspaceSwitchHook<X>
+    where
+        H: WorkspaceSwitchHook<X> + 'static,
+        Self: Sized + 'static,
+    {
+        ComposedWorkspaceSwitchHook {
+            first: Box::new(self),
+            second: Box::new(next),
+        }
+    }
+
+    /// Compose this hook with a boxed [WorkspaceSwitchHook].
+    fn then_boxed(self, next: Box<dyn WorkspaceSwitchHook<X>>) -> Box<dyn WorkspaceSwitchHook<X>>
+    where
+        Self: Sized + 'static,
+        X: 'static,
+    {
+        Box::new(ComposedWorkspaceSwitchHook {
+            first: Box::new(self),
+            second: next,
+        })
+    }
+}
+
+impl<X: XConn> fmt::Debug for Box<dyn WorkspaceSwitchHook<X>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WorkspaceSwitchHook").finish()
+    }
+}
+
+/// The result of composing two workspace switch hooks using `then`
+#[derive(Debug)]
+pub struct ComposedWorkspaceSwitchHook<X>
+where
+    X: XConn,
+{
+    first: Box<dyn WorkspaceSwitchHook<X>>,
+    second: Box<dyn WorkspaceSwitchHook<X>>,
+}
+
+impl<X> WorkspaceSwitchHook<X> for ComposedWorkspaceSwitchHook<X>
+where
+    X: XConn,
+{
+    fn call(
+        &mut self,
+        screen: usize,
+        old: &str,
+        new: &str,
+        state: &mut State<X>,
+        x: &X,
+    ) -> Result<()> {
+        self.first.call(screen, old, new, state, x)?;
+        self.second.call(screen, old, new, state, x)
+    }
+}
+
+impl<F, X> WorkspaceSwitchHook<X> for F
+where
+    F: FnMut(usize, &str, &str, &mut State<X>, &X) -> Result<()>,
+    X: XConn,
+{
+    fn call(
+        &mut self,
+        screen: usize,
+        old: &str,
+        new: &str,
+        state: &mut State<X>,
+        x: &X,
+    ) -> Result<()> {
+        (self)(screen, old, new, state, x)
+    }
+}
+
 /// An arbitrary action that can be run and modify [State]
 pub trait StateHook<X>
 where
@@ -247,3 +849,506 @@ where
         (self)(state, x)
     }
 }
+
+/// A hook that can be added to the window manager at runtime under a given name using
+/// [State::add_named_hook], allowing it to later be replaced or removed using
+/// [State::remove_hook].
+///
+/// Named hooks are run after the corresponding hook set in the [Config][0] (if there is one)
+/// in the order that they were added.
+///
+///   [0]: crate::core::Config
+pub enum NamedHook<X>
+where
+    X: XConn,
+{
+    /// Run as an [EventHook]. Default event handling is skipped if any event hook returns
+    /// `false`.
+    Event(Box<dyn EventHook<X>>),
+    /// Run as a [ManageHook]
+    Manage(Box<dyn ManageHook<X>>),
+    /// Run as a [StateHook] each time the on screen X state is refreshed
+    Refresh(Box<dyn StateHook<X>>),
+    /// Run as a [FocusChangeHook]
+    FocusChange(Box<dyn FocusChangeHook<X>>),
+    /// Run as an [UnmanageHook]
+    Unmanage(Box<dyn UnmanageHook<X>>),
+    /// Run as a [LayoutChangeHook]
+    LayoutChange(Box<dyn LayoutChangeHook<X>>),
+    /// Run as a [WorkspaceSwitchHook]
+    WorkspaceSwitch(Box<dyn WorkspaceSwitchHook<X>>),
+}
+
+impl<X: XConn> fmt::Debug for NamedHook<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::Event(_) => "Event",
+            Self::Manage(_) => "Manage",
+            Self::Refresh(_) => "Refresh",
+            Self::FocusChange(_) => "FocusChange",
+            Self::Unmanage(_) => "Unmanage",
+            Self::LayoutChange(_) => "LayoutChange",
+            Self::WorkspaceSwitch(_) => "WorkspaceSwitch",
+        };
+
+        f.debug_tuple("NamedHook").field(&kind).finish()
+    }
+}
+
+/// The named hooks that have been added to the window manager. Hooks are taken out while
+/// they are being run so that they are able to add and remove named hooks themselves.
+#[derive(Debug)]
+pub(crate) struct NamedHooks<X>
+where
+    X: XConn,
+{
+    hooks: Vec<(String, Option<NamedHook<X>>)>,
+}
+
+impl<X: XConn> Default for NamedHooks<X> {
+    fn default() -> Self {
+        Self { hooks: Vec::new() }
+    }
+}
+
+impl<X> NamedHooks<X>
+where
+    X: XConn,
+{
+    /// Add a hook, replacing and returning any existing hook with the same name. Replaced
+    /// hooks keep their position in the run order.
+    pub(crate) fn insert(&mut self, name: String, hook: NamedHook<X>) -> Option<NamedHook<X>> {
+        match self.hooks.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, h)) => h.replace(hook),
+            None => {
+                self.hooks.push((name, Some(hook)));
+                None
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) -> Option<NamedHook<X>> {
+        let ix = self.hooks.iter().position(|(n, _)| n == name)?;
+
+        self.hooks.remove(ix).1
+    }
+
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.hooks.iter().any(|(n, _)| n == name)
+    }
+
+    pub(crate) fn names(&self) -> Vec<String> {
+        self.hooks.iter().map(|(n, _)| n.clone()).collect()
+    }
+
+    fn take(&mut self, name: &str) -> Option<NamedHook<X>> {
+        self.hooks
+            .iter_mut()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, h)| h.take())
+    }
+
+    // Put back a hook that has been run unless it was replaced or removed while it was running
+    fn restore(&mut self, name: &str, hook: NamedHook<X>) {
+        if let Some((_, h @ None)) = self.hooks.iter_mut().find(|(n, _)| n == name) {
+            *h = Some(hook);
+        }
+    }
+}
+
+/// Run `f` for each named hook in turn, removing any hooks for which it returns `false`.
+pub(crate) fn run_named_hooks<X, F>(state: &mut State<X>, x: &X, mut f: F)
+where
+    X: XConn,
+    F: FnMut(&str, &mut NamedHook<X>, &mut State<X>, &X) -> bool,
+{
+    for name in state.named_hooks.names() {
+        if let Some(mut hook) = state.named_hooks.take(&name) {
+            if f(&name, &mut hook, state, x) {
+                state.named_hooks.restore(&name, hook);
+            } else {
+                state.named_hooks.remove(&name);
+            }
+        }
+    }
+}
+
+/// What to do when a user hook panics.
+///
+/// Unless the policy is [HookPanicPolicy::Exit], panics are caught and logged so that a
+/// single misbehaving hook is not able to bring down the window manager. Note that a hook
+/// panicking part way through modifying the [State] may leave it partially updated.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookPanicPolicy {
+    /// Log the panic and continue running the hook in future
+    #[default]
+    Log,
+    /// Log the panic and stop running the hook once it has panicked the given number of times
+    DisableAfter(u32),
+    /// Allow the panic to propagate, exiting the window manager
+    Exit,
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown panic"
+    }
+}
+
+/// Run a user hook, logging any error it returns and handling panics according to the
+/// [HookPanicPolicy] set in the [Config][crate::core::Config].
+///
+/// Returns the value returned by the hook (if it ran successfully) and whether or not the
+/// hook should continue to be run in future.
+pub(crate) fn run_hook<X, T, F>(desc: &str, state: &mut State<X>, f: F) -> (Option<T>, bool)
+where
+    X: XConn,
+    F: FnOnce(&mut State<X>) -> Result<T>,
+{
+    let payload = match panic::catch_unwind(AssertUnwindSafe(|| f(state))) {
+        Ok(Ok(t)) => return (Some(t), true),
+        Ok(Err(e)) => {
+            error!(%e, "error returned from {desc}");
+            return (None, true);
+        }
+        Err(payload) => payload,
+    };
+
+    let policy = state.config.hook_panic_policy;
+    if policy == HookPanicPolicy::Exit {
+        panic::resume_unwind(payload);
+    }
+
+    let panics = state.hook_panics.entry(desc.to_owned()).or_default();
+    *panics += 1;
+    error!(
+        msg = panic_message(payload.as_ref()),
+        panics = *panics,
+        "{desc} panicked"
+    );
+
+    match policy {
+        HookPanicPolicy::DisableAfter(n) if *panics >= n => {
+            warn!("disabling {desc} after {panics} panics");
+            (None, false)
+        }
+        _ => (None, true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::{
+            actions::modify_with,
+            layout::{MainAndStack, Monocle},
+        },
+        core::{bindings::KeyCode, layout::Layout},
+        pure::{geometry::Rect, OutputChanges, OutputInfo, ResizedOutput},
+        stack,
+        x::{
+            fixtures::{record, recorded, ScriptedWm, SCREEN},
+            mock::ScriptedConn,
+            XEvent, XEventKind,
+        },
+    };
+    use simple_test_case::test_case;
+
+    fn refresh() -> NamedHook<ScriptedConn> {
+        NamedHook::Refresh(Box::new(|_: &mut State<ScriptedConn>, _: &ScriptedConn| {
+            Ok(())
+        }))
+    }
+
+    #[test]
+    fn replacing_a_named_hook_keeps_its_position() {
+        let mut hooks = NamedHooks::default();
+        assert!(hooks.insert("a".to_owned(), refresh()).is_none());
+        assert!(hooks.insert("b".to_owned(), refresh()).is_none());
+        assert!(hooks.insert("a".to_owned(), refresh()).is_some());
+
+        assert_eq!(hooks.names(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn removing_a_running_hook_prevents_it_being_restored() {
+        let mut hooks = NamedHooks::default();
+        hooks.insert("a".to_owned(), refresh());
+
+        let h = hooks.take("a").unwrap();
+        assert!(hooks.remove("a").is_none());
+        hooks.restore("a", h);
+
+        assert!(!hooks.contains("a"));
+    }
+
+    #[test]
+    fn replacing_a_running_hook_keeps_the_replacement() {
+        let mut hooks = NamedHooks::default();
+        hooks.insert("a".to_owned(), refresh());
+
+        let h = hooks.take("a").unwrap();
+        hooks.insert(
+            "a".to_owned(),
+            NamedHook::Event(Box::new(
+                |_: &XEvent, _: &mut State<ScriptedConn>, _: &ScriptedConn| Ok(true),
+            )),
+        );
+        hooks.restore("a", h);
+
+        assert!(matches!(hooks.take("a"), Some(NamedHook::Event(_))));
+    }
+
+    const SWITCH_KEY: KeyCode = KeyCode { mask: 0, code: 43 };
+    const LAYOUT_KEY: KeyCode = KeyCode { mask: 0, code: 44 };
+
+    // SWITCH_KEY focuses workspace 2 and LAYOUT_KEY toggles between two layouts
+    fn layout_wm(keys: Vec<KeyCode>) -> ScriptedWm {
+        ScriptedWm::new()
+            .with_events(keys.into_iter().map(XEvent::KeyPress))
+            .with_key(SWITCH_KEY, modify_with(|cs| cs.focus_tag("2")))
+            .with_key(LAYOUT_KEY, modify_with(|cs| cs.next_layout()))
+            .with_config(|c| {
+                c.default_layouts = stack!(
+                    Box::new(MainAndStack::default()) as Box<dyn Layout>,
+                    Monocle::boxed()
+                );
+            })
+    }
+
+    #[test]
+    fn workspace_switch_hooks_are_run() {
+        let wm = layout_wm(vec![SWITCH_KEY, SWITCH_KEY])
+            .with_config(|c| {
+                c.compose_or_set_workspace_switch_hook(
+                    |screen: usize,
+                     old: &str,
+                     new: &str,
+                     s: &mut State<ScriptedConn>,
+                     _: &ScriptedConn| {
+                        record(s, format!("{screen} {old} {new}"));
+                        Ok(())
+                    },
+                )
+            })
+            .run();
+
+        assert_eq!(recorded(&wm), vec!["0 1 2"]);
+    }
+
+    #[test]
+    fn layout_change_hooks_are_run() {
+        let wm = layout_wm(vec![LAYOUT_KEY, SWITCH_KEY, LAYOUT_KEY])
+            .with_config(|c| {
+                c.compose_or_set_layout_change_hook(
+                    |tag: &str,
+                     old: &str,
+                     new: &str,
+                     s: &mut State<ScriptedConn>,
+                     _: &ScriptedConn| {
+                        record(s, format!("{tag} {old} {new}"));
+                        Ok(())
+                    },
+                )
+            })
+            .run();
+
+        assert_eq!(recorded(&wm), vec!["1 Side Mono", "2 Side Mono"]);
+    }
+
+    const FOCUS_KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+
+    type FocusChanges = Vec<(Option<Xid>, Option<Xid>)>;
+
+    #[test]
+    fn focus_change_hooks_are_run() {
+        let wm = ScriptedWm::new()
+            .with_events([
+                XEvent::MapRequest(Xid(1)),
+                XEvent::MapRequest(Xid(2)),
+                XEvent::KeyPress(FOCUS_KEY),
+                XEvent::Destroy(Xid(1)),
+                XEvent::Destroy(Xid(2)),
+            ])
+            .with_key(FOCUS_KEY, modify_with(|cs| cs.focus_down()))
+            .with_config(|c| {
+                c.compose_or_set_focus_change_hook(
+                    |old, new, s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                        s.extension_or_default::<FocusChanges>()
+                            .borrow_mut()
+                            .push((old, new));
+                        Ok(())
+                    },
+                )
+            })
+            .run();
+
+        let changes = wm.state.extension::<FocusChanges>().unwrap();
+        let expected = vec![
+            (None, Some(Xid(1))),
+            (Some(Xid(1)), Some(Xid(2))),
+            (Some(Xid(2)), Some(Xid(1))),
+            (Some(Xid(1)), Some(Xid(2))),
+            (Some(Xid(2)), None),
+        ];
+        assert_eq!(*changes.borrow(), expected);
+    }
+
+    #[test]
+    fn unmanage_hooks_are_run_for_managed_clients() {
+        let wm = ScriptedWm::new()
+            .with_events([
+                XEvent::MapRequest(Xid(1)),
+                XEvent::MapRequest(Xid(2)),
+                XEvent::KeyPress(FOCUS_KEY),
+                XEvent::Destroy(Xid(1)),
+                XEvent::UnmapNotify(Xid(2)),
+                XEvent::Destroy(Xid(3)),
+            ])
+            .with_key(FOCUS_KEY, modify_with(|cs| cs.focus_down()))
+            .with_config(|c| {
+                c.compose_or_set_unmanage_hook(
+                    |c: &UnmanagedClient, s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                        record(s, c.id.to_string());
+                        Ok(())
+                    },
+                )
+            })
+            .run();
+
+        assert_eq!(recorded(&wm), vec!["1", "2"]);
+    }
+
+    #[test]
+    fn named_hooks_can_remove_themselves() {
+        let mut wm = ScriptedWm::new().with_clients(2).build();
+
+        let once = |s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+            s.remove_hook("once");
+            record(s, "once");
+            Ok(())
+        };
+        let every = |s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+            record(s, "every");
+            Ok(())
+        };
+        wm.state
+            .add_named_hook("once", NamedHook::Refresh(once.boxed()));
+        wm.state
+            .add_named_hook("every", NamedHook::Refresh(every.boxed()));
+        let wm = wm.run_script().unwrap();
+
+        assert_eq!(recorded(&wm), vec!["once", "every", "every", "every"]);
+        assert!(!wm.state.has_hook("once"));
+        assert!(wm.state.has_hook("every"));
+    }
+
+    #[test_case(HookPanicPolicy::Log, 4; "log")]
+    #[test_case(HookPanicPolicy::DisableAfter(2), 2; "disable after")]
+    #[test]
+    fn panicking_hooks_are_isolated(policy: HookPanicPolicy, expected_runs: usize) {
+        let wm = ScriptedWm::new()
+            .with_events([
+                XEvent::MapRequest(Xid(1)),
+                XEvent::MapRequest(Xid(2)),
+                XEvent::KeyPress(FOCUS_KEY),
+            ])
+            .with_key(FOCUS_KEY, modify_with(|cs| cs.focus_down()))
+            .with_config(|c| {
+                c.hook_panic_policy = policy;
+                c.compose_or_set_refresh_hook(
+                    |s: &mut State<ScriptedConn>, _: &ScriptedConn| -> Result<()> {
+                        record(s, "refresh");
+                        panic!("refresh hook panicked")
+                    },
+                );
+            })
+            .run();
+
+        assert_eq!(recorded(&wm).len(), expected_runs);
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(1)));
+    }
+
+    #[test]
+    fn monitor_change_hooks_receive_output_changes() {
+        let second = Rect::new(1000, 0, 800, 600);
+        let wm = ScriptedWm::new()
+            .with_events([XEvent::RandrNotify, XEvent::RandrNotify])
+            .with_config(|c| {
+                c.compose_or_set_startup_hook(
+                    move |_: &mut State<ScriptedConn>, x: &ScriptedConn| {
+                        x.set_screens(vec![Rect::new(0, 0, 1200, 800), second]);
+                        Ok(())
+                    },
+                );
+                c.compose_or_set_monitor_change_hook(
+                    |c: &OutputChanges, s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                        s.extension_or_default::<Vec<OutputChanges>>()
+                            .borrow_mut()
+                            .push(c.clone());
+                        Ok(())
+                    },
+                );
+            })
+            .run();
+
+        // The second RandrNotify has no changes so the hook is only run once
+        let changes = wm.state.extension::<Vec<OutputChanges>>().unwrap();
+        assert_eq!(
+            *changes.borrow(),
+            vec![OutputChanges {
+                added: vec![OutputInfo::from_geometry("screen-1", false, second)],
+                removed: vec![],
+                resized: vec![ResizedOutput {
+                    name: "screen-0".to_string(),
+                    old: SCREEN,
+                    new: Rect::new(0, 0, 1200, 800),
+                }],
+            }]
+        );
+        assert_eq!(wm.state.client_set.screens().count(), 2);
+    }
+
+    fn record_kind(name: &'static str) -> impl EventHook<ScriptedConn> {
+        move |e: &XEvent, s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+            record(s, format!("{name} {:?}", e.kind()));
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn event_hooks_only_run_for_the_events_they_want() {
+        let mut wm = ScriptedWm::new()
+            .with_events([XEvent::KeyPress(FOCUS_KEY), XEvent::MapRequest(Xid(1))])
+            .with_config(|c| {
+                c.compose_or_set_event_hook(record_kind("all"));
+                c.compose_or_set_event_hook(record_kind("map").only_for(&[XEventKind::MapRequest]));
+            })
+            .build();
+        wm.state.add_named_hook(
+            "keys",
+            NamedHook::Event(
+                record_kind("keys")
+                    .only_for(&[XEventKind::KeyPress])
+                    .boxed(),
+            ),
+        );
+        let wm = wm.run_script().unwrap();
+
+        assert_eq!(
+            recorded(&wm),
+            vec![
+                "all KeyPress",
+                "keys KeyPress",
+                "map MapRequest",
+                "all MapRequest",
+            ]
+        );
+    }
+}
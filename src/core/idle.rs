@@ -0,0 +1,175 @@
+//! Tracking how long the user has been idle for in order to run [IdleHook]s.
+//!
+//! When an [IdleHook] has been set in the [Config] along with one or more
+//! [idle thresholds][Config::idle_thresholds], the window manager periodically checks how long
+//! it has been since the last keyboard or mouse input (see [XConn::idle_time]). The hook is run
+//! with [IdleEvent::Idle] each time one of the thresholds is passed and with
+//! [IdleEvent::Active] once the user is active again after having been idle.
+//!
+//!   [IdleHook]: crate::core::hooks::IdleHook
+//!   [Config]: crate::core::Config
+//!   [Config::idle_thresholds]: crate::core::Config::idle_thresholds
+//!   [XConn::idle_time]: crate::x::XConn::idle_time
+use std::time::{Duration, Instant};
+
+/// How often to check for the user becoming active again once they have been idle.
+pub(crate) const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A change in whether or not the user is idle, passed to an
+/// [IdleHook][crate::core::hooks::IdleHook].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdleEvent {
+    /// The user has been idle for at least the given threshold
+    Idle(Duration),
+    /// The user is active again after having been idle
+    Active,
+}
+
+/// Which idle thresholds have been passed since the user was last active.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct IdleTracker {
+    fired: usize,
+    last: Duration,
+    pub(crate) next_check: Option<Instant>,
+}
+
+impl IdleTracker {
+    /// Update the tracker with the current idle time, returning the resulting events.
+    ///
+    /// `thresholds` must be sorted in ascending order.
+    pub(crate) fn update(&mut self, idle: Duration, thresholds: &[Duration]) -> Vec<IdleEvent> {
+        let mut events = Vec::new();
+
+        if idle < self.last && self.fired > 0 {
+            events.push(IdleEvent::Active);
+            self.fired = 0;
+        }
+
+        while self.fired < thresholds.len() && idle >= thresholds[self.fired] {
+            events.push(IdleEvent::Idle(thresholds[self.fired]));
+            self.fired += 1;
+        }
+
+        self.last = idle;
+
+        events
+    }
+
+    /// How long to wait before checking the idle time again.
+    ///
+    /// While the user is idle we need to poll in order to detect them becoming active again,
+    /// otherwise we can wait until the next threshold would be reached.
+    pub(crate) fn next_check_after(&self, thresholds: &[Duration]) -> Option<Duration> {
+        let remaining = thresholds
+            .get(self.fired)
+            .map(|t| t.saturating_sub(self.last));
+
+        match (self.fired, remaining) {
+            (0, None) => None,
+            (0, Some(d)) => Some(d),
+            (_, None) => Some(IDLE_POLL_INTERVAL),
+            (_, Some(d)) => Some(d.min(IDLE_POLL_INTERVAL)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::State,
+        x::{fixtures::ScriptedWm, mock::ScriptedConn},
+    };
+    use simple_test_case::test_case;
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    const THRESHOLDS: [Duration; 2] = [Duration::from_secs(60), Duration::from_secs(300)];
+
+    #[test_case(vec![10, 20], vec![]; "not idle")]
+    #[test_case(vec![10, 60], vec![IdleEvent::Idle(secs(60))]; "first threshold")]
+    #[test_case(vec![10, 70, 80], vec![IdleEvent::Idle(secs(60))]; "thresholds only fire once")]
+    #[test_case(
+        vec![301],
+        vec![IdleEvent::Idle(secs(60)), IdleEvent::Idle(secs(300))];
+        "multiple thresholds at once"
+    )]
+    #[test_case(vec![70, 1], vec![IdleEvent::Idle(secs(60)), IdleEvent::Active]; "activity")]
+    #[test_case(
+        vec![70, 1, 65],
+        vec![IdleEvent::Idle(secs(60)), IdleEvent::Active, IdleEvent::Idle(secs(60))];
+        "idle again"
+    )]
+    #[test_case(vec![30, 1], vec![]; "activity without being idle")]
+    #[test]
+    fn update_works(idle_times: Vec<u64>, expected: Vec<IdleEvent>) {
+        let mut t = IdleTracker::default();
+        let events: Vec<_> = idle_times
+            .into_iter()
+            .flat_map(|idle| t.update(secs(idle), &THRESHOLDS))
+            .collect();
+
+        assert_eq!(events, expected);
+    }
+
+    #[test_case(10, Some(secs(50)); "waits for first threshold")]
+    #[test_case(70, Some(IDLE_POLL_INTERVAL); "polls while idle")]
+    #[test_case(299, Some(IDLE_POLL_INTERVAL); "polls while idle before next threshold")]
+    #[test_case(400, Some(IDLE_POLL_INTERVAL); "polls after last threshold")]
+    #[test]
+    fn next_check_after_works(idle: u64, expected: Option<Duration>) {
+        let mut t = IdleTracker::default();
+        t.update(secs(idle), &THRESHOLDS);
+
+        assert_eq!(t.next_check_after(&THRESHOLDS), expected);
+    }
+
+    #[test]
+    fn next_check_after_without_thresholds_is_none() {
+        let t = IdleTracker::default();
+
+        assert_eq!(t.next_check_after(&[]), None);
+    }
+
+    #[test]
+    fn idle_hooks_run_for_thresholds_and_activity() {
+        let mut wm = ScriptedWm::new()
+            .with_conn(|conn| {
+                conn.with_idle_times([secs(10), secs(70), secs(400), secs(2), secs(65)])
+            })
+            .with_config(|c| {
+                c.idle_thresholds = vec![secs(300), secs(60)];
+                c.compose_or_set_idle_hook(
+                    |e: IdleEvent, s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                        s.extension_or_default::<Vec<IdleEvent>>()
+                            .borrow_mut()
+                            .push(e);
+                        Ok(())
+                    },
+                );
+            })
+            .build();
+
+        for _ in 0..5 {
+            wm.poll_idle();
+        }
+        assert!(wm.state.idle.next_check.is_some());
+
+        // Once idle time is no longer reported we stop checking
+        wm.poll_idle();
+        assert!(wm.state.idle.next_check.is_none());
+
+        let events = wm.state.extension::<Vec<IdleEvent>>().unwrap();
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                IdleEvent::Idle(secs(60)),
+                IdleEvent::Idle(secs(300)),
+                IdleEvent::Active,
+                IdleEvent::Idle(secs(60)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Checking the internal consistency of the window manager [State].
+//!
+//! Bugs that corrupt the [State] (a client being tracked on two workspaces, a window being
+//! mapped without being managed...) often only show up as visible misbehaviour long after
+//! the event that caused them. [State::check_invariants] can be used to validate the
+//! current state at any point, and enabling the `invariant-checks` feature will check the
+//! state after every event that is handled, logging any violations that are found. In
+//! debug builds, violations will also cause the window manager to panic so that they are
+//! caught as close as possible to their cause.
+//!
+//!   [State]: crate::core::State
+//!   [State::check_invariants]: crate::core::State::check_invariants
+use crate::{core::State, x::XConn, Xid};
+use std::collections::{HashMap, HashSet};
+
+/// A violated invariant found by [State::check_invariants].
+///
+///   [State::check_invariants]: crate::core::State::check_invariants
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvariantViolation {
+    /// A window that the window manager has mapped is not being managed
+    #[error("mapped window {0} is not in the client set")]
+    UnmanagedMappedWindow(Xid),
+
+    /// A client is present on more than one workspace
+    #[error("client {id} is on multiple workspaces: {tags:?}")]
+    ClientOnMultipleWorkspaces {
+        /// The client
+        id: Xid,
+        /// The tags of each workspace the client was found on
+        tags: Vec<String>,
+    },
+
+    /// A client is marked as floating without being managed
+    #[error("floating client {0} is not in the client set")]
+    UnmanagedFloatingClient(Xid),
+
+    /// A client is marked as fullscreen without being managed
+    #[error("fullscreen client {0} is not in the client set")]
+    UnmanagedFullscreenClient(Xid),
+
+    /// A frame window is being tracked for a client that is not being managed
+    #[error("framed client {0} is not in the client set")]
+    UnmanagedFramedClient(Xid),
+
+    /// The focused client of the latest snapshot is not one of the clients in that snapshot
+    #[error("snapshot focused client {0} is not in the snapshot")]
+    SnapshotFocusMissing(Xid),
+
+    /// A client appears more than once in the latest snapshot
+    #[error("client {0} appears multiple times in the snapshot")]
+    SnapshotDuplicateClient(Xid),
+
+    /// A client in the latest snapshot is also listed as having been killed
+    #[error("killed client {0} is still present in the snapshot")]
+    SnapshotKilledClientPresent(Xid),
+}
+
+impl<X> State<X>
+where
+    X: XConn,
+{
+    /// Check the internal consistency of this [State], returning any violated invariants.
+    ///
+    /// See the [invariants][crate::core::invariants] module docs for details.
+    pub fn check_invariants(&self) -> Vec<InvariantViolation> {
+        use InvariantViolation::*;
+
+        let cs = &self.client_set;
+        let mut violations = Vec::new();
+
+        let mut seen: HashMap<Xid, Vec<String>> = HashMap::new();
+        for w in cs.workspaces() {
+            for &id in w.clients() {
+                seen.entry(id).or_default().push(w.tag.clone());
+            }
+        }
+        let mut duplicates: Vec<_> = seen.into_iter().filter(|(_, t)| t.len() > 1).collect();
+        duplicates.sort();
+        violations.extend(
+            duplicates
+                .into_iter()
+                .map(|(id, tags)| ClientOnMultipleWorkspaces { id, tags }),
+        );
+
+        let unmanaged = |ids: &mut dyn Iterator<Item = &Xid>| -> Vec<Xid> {
+            let mut ids: Vec<Xid> = ids.filter(|id| !cs.contains(id)).copied().collect();
+            ids.sort();
+            ids
+        };
+        violations.extend(
+            unmanaged(&mut self.mapped.iter())
+                .into_iter()
+                .map(UnmanagedMappedWindow),
+        );
+        violations.extend(
+            unmanaged(&mut cs.floating.keys())
+                .into_iter()
+                .map(UnmanagedFloatingClient),
+        );
+        violations.extend(
+            unmanaged(&mut cs.fullscreen.keys())
+                .into_iter()
+                .map(UnmanagedFullscreenClient),
+        );
+        violations.extend(
+            unmanaged(&mut self.frames.keys())
+                .into_iter()
+                .map(UnmanagedFramedClient),
+        );
+
+        let snapshot = &self.diff.after;
+        let mut clients = HashSet::new();
+        for &id in snapshot.all_clients() {
+            if !clients.insert(id) {
+                violations.push(SnapshotDuplicateClient(id));
+            }
+        }
+        if let Some(id) = snapshot.focused_client {
+            if !clients.contains(&id) {
+                violations.push(SnapshotFocusMissing(id));
+            }
+        }
+        violations.extend(
+            snapshot
+                .killed_clients
+                .iter()
+                .filter(|id| clients.contains(id))
+                .map(|&id| SnapshotKilledClientPresent(id)),
+        );
+
+        violations
+    }
+
+    // Log (and in debug builds panic on) any violated invariants.
+    #[cfg(feature = "invariant-checks")]
+    pub(crate) fn enforce_invariants(&self) {
+        let violations = self.check_invariants();
+        if violations.is_empty() {
+            return;
+        }
+
+        for v in violations.iter() {
+            tracing::error!(%v, event = ?self.current_event, "state invariant violated");
+        }
+
+        if cfg!(debug_assertions) {
+            panic!("state invariants violated: {violations:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InvariantViolation::*, *};
+    use crate::{
+        pure::{geometry::RelativeRect, Stack},
+        x::{fixtures::ScriptedWm, mock::ScriptedConn},
+    };
+
+    fn state() -> State<ScriptedConn> {
+        ScriptedWm::new().with_clients(2).run().state
+    }
+
+    #[test]
+    fn a_normally_running_state_has_no_violations() {
+        assert_eq!(state().check_invariants(), vec![]);
+    }
+
+    #[test]
+    fn corrupt_state_is_detected() {
+        let mut s = state();
+        s.client_set.workspace_mut("2").unwrap().stack = Some(Stack::new([], Xid(1), []));
+        s.mapped.insert(Xid(3));
+        s.client_set
+            .floating
+            .insert(Xid(4), RelativeRect::new(0.0, 0.0, 0.5, 0.5));
+        s.diff.after.focused_client = Some(Xid(5));
+
+        assert_eq!(
+            s.check_invariants(),
+            vec![
+                ClientOnMultipleWorkspaces {
+                    id: Xid(1),
+                    tags: vec!["1".to_owned(), "2".to_owned()]
+                },
+                UnmanagedMappedWindow(Xid(3)),
+                UnmanagedFloatingClient(Xid(4)),
+                SnapshotFocusMissing(Xid(5)),
+            ]
+        );
+    }
+}
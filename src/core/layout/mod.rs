@@ -164,6 +164,26 @@ impl LayoutStack {
             }
         }
     }
+
+    /// The names of all layouts in this stack, in order.
+    pub fn names(&self) -> Vec<String> {
+        self.iter().map(|l| l.name()).collect()
+    }
+
+    /// Focus the first [Layout] in this stack with the given name.
+    ///
+    /// Returns `false` and leaves the focus unchanged if there is no such layout.
+    pub fn focus_layout(&mut self, name: &str) -> bool {
+        if !self.iter().any(|l| l.name() == name) {
+            return false;
+        }
+
+        while self.focus.name() != name {
+            self.focus_down();
+        }
+
+        true
+    }
 }
 
 impl Layout for LayoutStack {
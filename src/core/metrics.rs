@@ -0,0 +1,255 @@
+//! Latency metrics for diagnosing slow event handling.
+//!
+//! Adding a [Metrics] [State] extension to the window manager will record how long it takes to
+//! handle each [XEvent] (grouped by its [XEventKind][crate::x::XEventKind]) and how long each
+//! refresh of the X state takes. Wrapping your [XConn] in a [TimedConn][crate::x::TimedConn]
+//! and sharing its metrics with the window manager additionally records the time taken by each
+//! round trip to the X server.
+//!
+//! ```no_run
+//! # use penrose::{core::{metrics::Metrics, WindowManager}, x::TimedConn, x11rb::RustConn};
+//! # use std::collections::HashMap;
+//! # fn example() -> penrose::Result<()> {
+//! let conn = TimedConn::new(RustConn::new()?);
+//! let metrics = conn.metrics();
+//! let mut wm = WindowManager::new(Default::default(), HashMap::new(), HashMap::new(), conn)?;
+//! wm.add_shared_extension(metrics);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The current values can be written to the log using the
+//! [log_metrics][crate::builtin::actions::log_metrics] action or inspected directly via
+//! [State::extension].
+//!
+//!   [XEvent]: crate::x::XEvent
+use crate::{core::State, x::XConn};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// The upper bounds (in microseconds) of the buckets used by a [Histogram]. Durations
+/// above the last bound are counted in a final overflow bucket.
+pub const BUCKET_BOUNDS_US: [u64; 14] = [
+    50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000,
+    1_000_000,
+];
+
+/// A histogram of recorded durations using the fixed buckets in [BUCKET_BOUNDS_US].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; BUCKET_BOUNDS_US.len() + 1],
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    /// Record a single duration.
+    pub fn record(&mut self, d: Duration) {
+        let us = d.as_micros();
+        let ix = BUCKET_BOUNDS_US
+            .iter()
+            .position(|&b| us <= b as u128)
+            .unwrap_or(BUCKET_BOUNDS_US.len());
+
+        self.buckets[ix] += 1;
+        self.count += 1;
+        self.total += d;
+        self.max = self.max.max(d);
+    }
+
+    /// The number of durations recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The sum of all recorded durations.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// The largest duration recorded.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// The mean of all recorded durations.
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+
+        self.total / self.count as u32
+    }
+
+    /// The per-bucket counts alongside the upper bound of each bucket. The overflow bucket
+    /// has an upper bound of `None`.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        BUCKET_BOUNDS_US
+            .iter()
+            .map(|&b| Some(Duration::from_micros(b)))
+            .chain(std::iter::once(None))
+            .zip(self.buckets.iter().copied())
+    }
+
+    /// An upper bound for the given percentile (in the range `0.0..=100.0`) of the recorded
+    /// durations, accurate to the size of the bucket it falls in.
+    ///
+    /// Values falling in the overflow bucket are reported as the largest recorded duration.
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+
+        let target = ((p.clamp(0.0, 100.0) / 100.0) * self.count as f64).ceil() as u64;
+        let mut seen = 0;
+        for (bound, n) in self.buckets() {
+            seen += n;
+            if seen >= target.max(1) {
+                return bound.map(|b| b.min(self.max)).unwrap_or(self.max);
+            }
+        }
+
+        self.max
+    }
+}
+
+/// Named latency [Histogram]s for use as a [State] extension.
+///
+/// See the [module level docs][self] for details.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    histograms: BTreeMap<String, Histogram>,
+}
+
+impl Metrics {
+    /// Record a duration against the named histogram.
+    pub fn record(&mut self, name: impl Into<String>, d: Duration) {
+        self.histograms.entry(name.into()).or_default().record(d);
+    }
+
+    /// Run `f`, recording how long it took against the named histogram.
+    pub fn time<T>(&mut self, name: impl Into<String>, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let res = f();
+        self.record(name, start.elapsed());
+
+        res
+    }
+
+    /// The histogram with the given name if anything has been recorded for it.
+    pub fn histogram(&self, name: &str) -> Option<&Histogram> {
+        self.histograms.get(name)
+    }
+
+    /// Iterate over all histograms in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Histogram)> {
+        self.histograms.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Discard everything recorded so far.
+    pub fn reset(&mut self) {
+        self.histograms.clear();
+    }
+
+    /// A human readable summary of all histograms, one per line.
+    pub fn report(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, h) in self.iter() {
+            writeln!(
+                f,
+                "{name}: count={} mean={:?} p50={:?} p99={:?} max={:?}",
+                h.count(),
+                h.mean(),
+                h.percentile(50.0),
+                h.percentile(99.0),
+                h.max()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<X> State<X>
+where
+    X: XConn,
+{
+    // Record a duration if a Metrics extension has been added
+    pub(crate) fn record_metric(&self, name: impl FnOnce() -> String, d: Duration) {
+        if let Ok(m) = self.extension::<Metrics>() {
+            if let Ok(mut m) = m.try_borrow_mut() {
+                m.record(name(), d);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn histogram_summary_stats_are_correct() {
+        let mut h = Histogram::default();
+        for n in [1, 2, 3, 10] {
+            h.record(ms(n));
+        }
+
+        assert_eq!(h.count(), 4);
+        assert_eq!(h.total(), ms(16));
+        assert_eq!(h.mean(), ms(4));
+        assert_eq!(h.max(), ms(10));
+    }
+
+    #[test_case(0.0, ms(1); "min")]
+    #[test_case(50.0, ms(1); "p50")]
+    #[test_case(75.0, Duration::from_micros(2_500); "p75")]
+    #[test_case(90.0, ms(2_000); "overflow reports max")]
+    #[test_case(100.0, ms(2_000); "p100")]
+    #[test]
+    fn percentiles_are_bucket_upper_bounds(p: f64, expected: Duration) {
+        let mut h = Histogram::default();
+        for d in [ms(1), ms(1), ms(2), ms(2_000)] {
+            h.record(d);
+        }
+
+        assert_eq!(h.percentile(p), expected);
+    }
+
+    #[test]
+    fn empty_histograms_report_zero() {
+        let h = Histogram::default();
+
+        assert_eq!(h.mean(), Duration::ZERO);
+        assert_eq!(h.percentile(99.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn metrics_are_recorded_by_name() {
+        let mut m = Metrics::default();
+        m.record("b", ms(1));
+        m.record("a", ms(2));
+        m.record("a", ms(4));
+
+        let names: Vec<_> = m.iter().map(|(name, h)| (name, h.count())).collect();
+        assert_eq!(names, vec![("a", 2), ("b", 1)]);
+        assert_eq!(m.histogram("a").unwrap().mean(), ms(3));
+
+        m.reset();
+        assert!(m.histogram("a").is_none());
+    }
+}
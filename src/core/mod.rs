@@ -1,31 +1,69 @@
 //! Core data structures and user facing functionality for the window manager
 use crate::{
-    pure::{Diff, StackSet, Workspace},
-    x::{manage_without_refresh, Atom, Prop, XConn, XConnExt, XEvent},
+    builtin::layout::messages::SetGaps,
+    pure::{
+        geometry::{Point, Rect},
+        Diff, HotplugPolicy, Position, Screen, Stack, StackSet, Workspace,
+    },
+    util,
+    x::{
+        atom::AUTO_FLOAT_WINDOW_TYPES, manage_without_refresh, property::WmState, Atom,
+        ClientConfig, Cursor, PositionSettings, Prop, Query, XConn, XConnExt, XEvent,
+    },
     Color, Error, Result,
 };
 use anymap::{any::Any, AnyMap};
-use nix::sys::signal::{signal, SigHandler, Signal};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::{
     any::TypeId,
     cell::RefCell,
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     fmt,
     ops::Deref,
+    os::unix::io::RawFd,
+    panic::{self, AssertUnwindSafe},
     sync::Arc,
+    thread,
+    time::{Duration, Instant},
 };
-use tracing::{error, info, span, trace, Level};
+use tracing::{error, info, span, trace, warn, Level};
 
 pub mod bindings;
+pub mod bus;
+pub(crate) mod coalesce;
+pub mod deferred;
 pub(crate) mod handle;
+pub mod hints;
 pub mod hooks;
+pub mod idle;
+pub mod invariants;
 pub mod layout;
+pub mod metrics;
+pub mod restart;
+pub mod schedule;
+pub mod theme;
+pub mod titlebar;
 
-use bindings::{KeyBindings, MouseBindings};
-use hooks::{EventHook, ManageHook, StateHook};
+use bindings::{
+    binding_hints, parse_binding, resolve_binding, submap_hints, ActiveDrag, BindingChange, Chord,
+    ClientBindings, DragHandler, KeyBindings, KeyCode, KeyEventHandler, LastClick, Mode,
+    ModifierRelease, MouseBindings, MouseState,
+};
+use bus::{MessageBus, SubscriptionId};
+use coalesce::{coalesce, MAX_COALESCED_EVENTS};
+use deferred::{DeferredQueue, DeferredSender};
+use hints::{BindingHints, BindingHintsConfig};
+use hooks::{
+    run_hook, run_named_hooks, EventHook, FocusChangeHook, HookPanicPolicy, IdleHook,
+    LayoutChangeHook, ManageHook, MonitorChangeHook, NamedHook, NamedHooks, StateHook,
+    UnmanageHook, WorkspaceSwitchHook,
+};
+use idle::IdleTracker;
 use layout::LayoutStack;
+use schedule::{ScheduleId, Scheduler};
+pub use theme::Theme;
+use titlebar::TitlebarConfig;
 
 /// An X11 ID for a given resource
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -76,10 +114,36 @@ where
     pub client_set: ClientSet,
     pub(crate) extensions: AnyMap,
     pub(crate) root: Xid,
+    pub(crate) roots: Vec<Xid>,
+    pub(crate) client_x_screens: HashMap<Xid, usize>,
     pub(crate) mapped: HashSet<Xid>,
     pub(crate) pending_unmap: HashMap<Xid, usize>,
+    pub(crate) frames: HashMap<Xid, Xid>, // Reparented clients and their frame windows
     pub(crate) current_event: Option<XEvent>,
     pub(crate) diff: Diff<Xid>,
+    pub(crate) mode: Option<String>,
+    pub(crate) binding_changes: Vec<BindingChange<X>>,
+    pub(crate) modifier_release: Option<ModifierRelease<X>>,
+    pub(crate) drag: Option<ActiveDrag<X>>,
+    pub(crate) last_click: Option<LastClick>,
+    pub(crate) count: Option<u32>,
+    pub(crate) scheduler: Scheduler<X>,
+    pub(crate) deferred: DeferredQueue<X>,
+    pub(crate) named_hooks: NamedHooks<X>,
+    pub(crate) hook_panics: HashMap<String, u32>,
+    pub(crate) idle: IdleTracker,
+    pub(crate) bus: MessageBus<X>,
+    pub(crate) exit_requested: bool,
+    #[cfg(feature = "restart")]
+    pub(crate) restart_requested: bool,
+    pub(crate) appearance_changed: bool,
+    pub(crate) position_settings: Option<PositionSettings>,
+    pub(crate) stale_positions: HashSet<Xid>,
+    pub(crate) urgency_suppressed: bool,
+    pub(crate) focus_history: VecDeque<Xid>,
+    pub(crate) urgent: Vec<Xid>,
+    pub(crate) sticky: Vec<Xid>,
+    pub(crate) border_overrides: HashMap<Xid, (Color, Color)>,
     // pub(crate) mouse_focused: bool,
     // pub(crate) mouse_position: Option<(Point, Point)>,
 }
@@ -93,16 +157,571 @@ where
         self.root
     }
 
+    /// The Xids of the root windows for each X screen being managed, in screen order.
+    ///
+    /// For most setups (including Xinerama / RandR multi-monitor setups) this will only
+    /// contain [State::root].
+    pub fn roots(&self) -> &[Xid] {
+        &self.roots
+    }
+
+    /// Whether or not the given Xid is the root window of one of the X screens being managed.
+    pub fn is_root(&self, id: Xid) -> bool {
+        self.roots.contains(&id)
+    }
+
+    /// The index of the X screen with the given root window.
+    pub fn x_screen_for_root(&self, root: Xid) -> Option<usize> {
+        self.roots.iter().position(|&r| r == root)
+    }
+
+    /// The index of the X screen that the given client belongs to if it is being managed.
+    pub fn x_screen_for_client(&self, client: Xid) -> Option<usize> {
+        self.client_x_screens.get(&client).copied()
+    }
+
+    // The root window of the X screen a client belongs to, defaulting to the primary root
+    pub(crate) fn root_for_client(&self, client: Xid) -> Xid {
+        self.x_screen_for_client(client)
+            .and_then(|ix| self.roots.get(ix).copied())
+            .unwrap_or(self.root)
+    }
+
+    /// The currently focused client, if there is one.
+    pub fn focused_client(&self) -> Option<Xid> {
+        self.client_set.current_client().copied()
+    }
+
+    /// The geometry of the currently focused screen.
+    pub fn focused_screen_rect(&self) -> Rect {
+        self.client_set.current_screen().geometry()
+    }
+
+    /// The tag of the currently focused workspace.
+    pub fn focused_tag(&self) -> &str {
+        self.client_set.current_tag()
+    }
+
+    /// The screen that the given client is currently visible on.
+    ///
+    /// Returns `None` if the client is not being managed or is on a hidden workspace.
+    pub fn screen_of_client(&self, client: Xid) -> Option<&Screen<Xid>> {
+        self.client_set
+            .screens()
+            .find(|s| s.workspace.contains(&client))
+    }
+
+    /// The managed clients that have held focus, most recently focused first.
+    ///
+    /// This includes clients on hidden workspaces. Clients that have never been focused are
+    /// not included.
+    pub fn focus_history(&self) -> Vec<Xid> {
+        self.focus_history
+            .iter()
+            .filter(|id| self.client_set.contains(id))
+            .copied()
+            .collect()
+    }
+
+    // Move the currently focused client to the front of the focus history, dropping any
+    // clients that are no longer managed.
+    pub(crate) fn update_focus_history(&mut self) {
+        let cs = &self.client_set;
+        self.focus_history.retain(|id| cs.contains(id));
+
+        if let Some(&id) = cs.current_client() {
+            if self.focus_history.front() != Some(&id) {
+                self.focus_history.retain(|&c| c != id);
+                self.focus_history.push_front(id);
+            }
+        }
+    }
+
+    /// The managed clients that currently need attention, in the order that they became urgent.
+    ///
+    /// A client is urgent if it has set the urgency flag in its `WM_HINTS` or if it has
+    /// been marked as urgent after trying to steal focus (see [FocusStealingPolicy]). Clients
+    /// stop being urgent once they are focused.
+    pub fn urgent_clients(&self) -> Vec<Xid> {
+        self.urgent
+            .iter()
+            .filter(|id| self.client_set.contains(id))
+            .copied()
+            .collect()
+    }
+
+    // Record whether or not a client needs attention, preserving the order in which currently
+    // urgent clients became urgent.
+    pub(crate) fn set_urgent(&mut self, client: Xid, urgent: bool) {
+        if !urgent {
+            self.urgent.retain(|&id| id != client);
+        } else if !self.urgent.contains(&client) {
+            self.urgent.push(client);
+        }
+    }
+
+    // Clear the urgency of the currently focused client, dropping any clients that are no
+    // longer managed. Returns the focused client if it was previously urgent.
+    pub(crate) fn clear_focused_urgency(&mut self) -> Option<Xid> {
+        let cs = &self.client_set;
+        self.urgent.retain(|id| cs.contains(id));
+
+        let focused = cs.current_client().copied()?;
+        let was_urgent = self.urgent.contains(&focused);
+        self.set_urgent(focused, false);
+
+        was_urgent.then_some(focused)
+    }
+
+    /// The managed clients that are currently sticky, in the order that they were made sticky.
+    ///
+    /// Sticky clients stay visible on the screen they are shown on when that screen switches
+    /// to a different workspace (see [State::set_sticky]).
+    pub fn sticky_clients(&self) -> Vec<Xid> {
+        self.sticky
+            .iter()
+            .filter(|id| self.client_set.contains(id))
+            .copied()
+            .collect()
+    }
+
+    /// Whether or not the given client is currently sticky.
+    pub fn is_sticky(&self, client: Xid) -> bool {
+        self.sticky.contains(&client)
+    }
+
+    /// Set whether or not the given client is sticky.
+    ///
+    /// When a screen switches to a different workspace, any sticky clients on the workspace
+    /// it was previously showing are moved to the new workspace without taking focus.
+    pub fn set_sticky(&mut self, client: Xid, sticky: bool) {
+        if !sticky {
+            self.sticky.retain(|&id| id != client);
+        } else if !self.sticky.contains(&client) {
+            self.sticky.push(client);
+        }
+    }
+
+    // Move sticky clients from workspaces that have just been switched away from to the
+    // workspace now shown on the same screen, dropping any clients that are no longer managed.
+    pub(crate) fn move_sticky_clients(&mut self) {
+        let cs = &mut self.client_set;
+        self.sticky.retain(|id| cs.contains(id));
+        if self.sticky.is_empty() {
+            return;
+        }
+
+        let mut moves = Vec::new();
+        for prev in self.diff.after.screens() {
+            let tag = match cs.screens().find(|s| s.index() == prev.screen) {
+                Some(s) if s.workspace.tag != prev.tag => s.workspace.tag.clone(),
+                _ => continue,
+            };
+
+            for &id in self.sticky.iter() {
+                if cs.tag_for_client(&id) == Some(prev.tag.as_str()) {
+                    moves.push((id, prev.tag.clone(), tag.clone()));
+                }
+            }
+        }
+
+        for (id, old, new) in moves {
+            trace!(%id, %old, %new, "moving sticky client");
+            if let Some(ws) = cs.workspace_mut(&old) {
+                ws.remove(&id);
+            }
+            if let Some(ws) = cs.workspace_mut(&new) {
+                ws.stack = match ws.stack.take() {
+                    Some(mut s) => {
+                        s.insert_at(Position::Tail, id);
+                        Some(s)
+                    }
+                    None => Some(Stack::new([], id, [])),
+                };
+            }
+        }
+    }
+
+    /// The border color to use for the given client depending on whether or not it has focus,
+    /// taking into account any matching [BorderRule].
+    pub fn border_color(&self, client: Xid, focused: bool) -> Color {
+        let theme = &self.config.theme;
+
+        match (self.border_overrides.get(&client), focused) {
+            (Some(&(_, color)), true) => color,
+            (Some(&(color, _)), false) => color,
+            (None, true) => theme.focused_border,
+            (None, false) => theme.normal_border,
+        }
+    }
+
     /// The set of all client windows currently mapped to a screen.
     pub fn mapped_clients(&self) -> &HashSet<Xid> {
         &self.mapped
     }
 
+    /// The frame window wrapping the given client if it has been reparented.
+    ///
+    /// See [Config::reparent_clients] for details.
+    pub fn frame_for(&self, client: Xid) -> Option<Xid> {
+        self.frames.get(&client).copied()
+    }
+
+    /// The client wrapped by the given frame window if it is a frame.
+    pub fn client_for_frame(&self, frame: Xid) -> Option<Xid> {
+        self.frames
+            .iter()
+            .find(|&(_, &f)| f == frame)
+            .map(|(&c, _)| c)
+    }
+
+    // The top level window for a client: its frame if it has one or otherwise the client itself
+    pub(crate) fn outer_window(&self, client: Xid) -> Xid {
+        self.frame_for(client).unwrap_or(client)
+    }
+
     /// The event currently being processed.
     pub fn current_event(&self) -> Option<&XEvent> {
         self.current_event.as_ref()
     }
 
+    /// The name of the binding [Mode] that is currently active, if any.
+    pub fn current_mode(&self) -> Option<&str> {
+        self.mode.as_deref()
+    }
+
+    /// The numeric count typed before the key binding currently being run, if any.
+    ///
+    /// Counts can only be typed while a [Mode] that allows them is active (see
+    /// [Mode::with_count_prefix]).
+    pub fn count(&self) -> Option<u32> {
+        self.count
+    }
+
+    /// Schedule a [StateHook] to be run once after the given delay, returning a [ScheduleId]
+    /// that can be used to cancel it.
+    ///
+    /// See the [schedule] module docs for details.
+    pub fn schedule_once<H>(&mut self, delay: Duration, hook: H) -> ScheduleId
+    where
+        H: StateHook<X> + 'static,
+    {
+        self.scheduler
+            .add(Instant::now() + delay, None, Box::new(hook))
+    }
+
+    /// Schedule a [StateHook] to be run repeatedly at the given interval, returning a
+    /// [ScheduleId] that can be used to cancel it.
+    ///
+    /// See the [schedule] module docs for details.
+    pub fn schedule_every<H>(&mut self, interval: Duration, hook: H) -> ScheduleId
+    where
+        H: StateHook<X> + 'static,
+    {
+        self.scheduler
+            .add(Instant::now() + interval, Some(interval), Box::new(hook))
+    }
+
+    /// Cancel a hook previously scheduled using [State::schedule_once] or
+    /// [State::schedule_every].
+    ///
+    /// Cancelling a hook that has already run or been cancelled is a no-op.
+    pub fn cancel_scheduled(&mut self, id: ScheduleId) {
+        self.scheduler.cancel(id);
+    }
+
+    /// Whether or not the given hook is still scheduled to run.
+    pub fn is_scheduled(&self, id: ScheduleId) -> bool {
+        self.scheduler.is_scheduled(id)
+    }
+
+    /// Add a [NamedHook] to be run alongside the hooks set in the [Config], returning the
+    /// hook that was previously registered under `name` if there was one.
+    ///
+    /// ```no_run
+    /// # use penrose::{core::{hooks::{NamedHook, StateHook}, State}, x::XConn, Result};
+    /// fn update_bar<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    ///     // ...
+    /// #   Ok(())
+    /// }
+    ///
+    /// fn enable_bar<X: XConn + 'static>(state: &mut State<X>, _: &X) -> Result<()> {
+    ///     let hook = NamedHook::Refresh(update_bar.boxed());
+    ///     state.add_named_hook("my-bar-updater", hook);
+    ///
+    ///     Ok(())
+    /// }
+    ///
+    /// fn disable_bar<X: XConn>(state: &mut State<X>, _: &X) -> Result<()> {
+    ///     state.remove_hook("my-bar-updater");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn add_named_hook(
+        &mut self,
+        name: impl Into<String>,
+        hook: NamedHook<X>,
+    ) -> Option<NamedHook<X>> {
+        self.named_hooks.insert(name.into(), hook)
+    }
+
+    /// Remove the [NamedHook] registered under `name`, returning it if it is not currently
+    /// running.
+    pub fn remove_hook(&mut self, name: &str) -> Option<NamedHook<X>> {
+        self.named_hooks.remove(name)
+    }
+
+    /// Whether or not there is a [NamedHook] registered under `name`.
+    pub fn has_hook(&self, name: &str) -> bool {
+        self.named_hooks.contains(name)
+    }
+
+    /// Request that the window manager exits once the current event has been handled.
+    ///
+    /// [WindowManager::run] will return after running the [Config::shutdown_hook] and
+    /// releasing all key and mouse grabs. If [Config::release_clients_on_exit] is set then
+    /// all clients are also mapped and removed from their frames so that another window
+    /// manager is able to take over.
+    pub fn exit(&mut self) {
+        self.exit_requested = true;
+    }
+
+    /// Request that the window manager restarts in place once the current event has been
+    /// handled (see [WindowManager::restart]).
+    #[cfg(feature = "restart")]
+    pub fn request_restart(&mut self) {
+        self.restart_requested = true;
+    }
+
+    /// Publish a message to any subscribers of its type.
+    ///
+    /// Messages are delivered once the current hook or action has returned. See the [bus]
+    /// module docs for details.
+    pub fn publish<M: Any>(&mut self, msg: M) {
+        self.bus.publish(msg);
+    }
+
+    /// Run the given handler for each message of type `M` that is published, returning a
+    /// [SubscriptionId] that can be used to unsubscribe.
+    ///
+    /// See the [bus] module docs for details.
+    pub fn subscribe<M, F>(&mut self, handler: F) -> SubscriptionId
+    where
+        M: Any,
+        F: FnMut(&M, &mut State<X>, &X) -> Result<()> + 'static,
+    {
+        self.bus.subscribe(handler)
+    }
+
+    /// Remove a subscriber added using [State::subscribe], returning whether or not it was
+    /// still subscribed.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        self.bus.unsubscribe(id)
+    }
+
+    /// Whether or not the given subscriber is still subscribed.
+    pub fn is_subscribed(&self, id: SubscriptionId) -> bool {
+        self.bus.is_subscribed(id)
+    }
+
+    /// Get a [DeferredSender] that can be moved to another thread in order to submit
+    /// closures to be run against this [State].
+    ///
+    /// See the [deferred] module docs for details.
+    pub fn deferred_sender(&self) -> DeferredSender<X> {
+        self.deferred.sender()
+    }
+
+    /// Queue an action to be run on the main thread once handling of the current event
+    /// (including any refresh) has completed.
+    ///
+    /// See the [deferred] module docs for details.
+    pub fn queue_action<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut State<X>, &X) -> Result<()> + 'static,
+    {
+        self.deferred.push_action(Box::new(f));
+    }
+
+    /// Run `work` on a new thread, passing its result to `apply` to be run against this
+    /// [State] on the main thread once it is ready.
+    ///
+    /// See the [deferred] module docs for details.
+    pub fn spawn_background<T, W, A>(&self, work: W, apply: A)
+    where
+        T: Send + 'static,
+        W: FnOnce() -> T + Send + 'static,
+        A: FnOnce(T, &mut State<X>, &X) -> Result<()> + Send + 'static,
+        X: 'static,
+    {
+        let tx = self.deferred_sender();
+
+        thread::spawn(move || {
+            let res = work();
+            tx.send(move |state, x| apply(res, state, x));
+        });
+    }
+
+    /// Enter the named binding [Mode], replacing the active key bindings with those of the
+    /// mode once the current event has been handled.
+    ///
+    /// Requests to enter a mode that has not been added to the [WindowManager] are ignored.
+    pub fn enter_mode(&mut self, name: impl Into<String>) {
+        self.mode = Some(name.into());
+    }
+
+    /// Exit the current binding [Mode] (if there is one), restoring the default key bindings
+    /// once the current event has been handled.
+    pub fn exit_mode(&mut self) {
+        self.mode = None;
+    }
+
+    /// Bind a key spec such as `"M-S-Return"` to the given handler in the default key
+    /// bindings, replacing any existing binding for that key.
+    ///
+    /// The spec is resolved against the current keyboard mapping and keys are re-grabbed
+    /// once the current event has been handled.
+    pub fn bind_key(&mut self, spec: impl Into<String>, handler: Box<dyn KeyEventHandler<X>>) {
+        self.binding_changes
+            .push(BindingChange::Bind(spec.into(), handler));
+    }
+
+    /// Run the given handler once the modifiers held for the key binding currently being
+    /// run have all been released, replacing any handler that is already waiting.
+    ///
+    /// This allows for "alt-tab" style bindings where repeated presses of a key update a
+    /// selection while a modifier is held and the selection is committed when the modifier
+    /// is released. The keyboard is grabbed until the modifiers are released. If no modifiers
+    /// are held (or there is no current key press) the handler is run once the current event
+    /// has been handled.
+    pub fn on_modifier_release(&mut self, handler: Box<dyn KeyEventHandler<X>>) {
+        let mask = match &self.current_event {
+            Some(XEvent::KeyPress(k)) => k.mask,
+            _ => 0,
+        };
+        let grabbed = self.modifier_release.as_ref().is_some_and(|r| r.grabbed);
+
+        let mut release = ModifierRelease::new(mask, handler);
+        release.grabbed = grabbed;
+        self.modifier_release = Some(release);
+    }
+
+    /// Start a mouse drag, passing all pointer motion to the given [DragHandler] until a
+    /// mouse button is released.
+    ///
+    /// This should be called from a [MouseEventHandler][bindings::MouseEventHandler] bound
+    /// to a mouse button press. The pointer is grabbed for the duration of the drag once the
+    /// current event has been handled.
+    pub fn start_drag(&mut self, handler: Box<dyn DragHandler<X>>) {
+        let (last, mouse_state) = match &self.current_event {
+            Some(XEvent::MouseEvent(e)) => (e.rpt, e.state.clone()),
+            _ => {
+                warn!("mouse drags can only be started while handling a mouse event");
+                return;
+            }
+        };
+        let grabbed = self.drag.as_ref().is_some_and(|d| d.grabbed);
+
+        self.drag = Some(ActiveDrag {
+            handler,
+            mouse_state,
+            last,
+            grabbed,
+        });
+    }
+
+    /// Change the border colors used for clients, re-applying them to all managed clients on
+    /// the next refresh.
+    pub fn set_border_colors(&mut self, normal: impl Into<Color>, focused: impl Into<Color>) {
+        self.config.theme.normal_border = normal.into();
+        self.config.theme.focused_border = focused.into();
+        self.appearance_changed = true;
+    }
+
+    /// Change the border width used for clients, re-applying it to all managed clients on
+    /// the next refresh.
+    pub fn set_border_width(&mut self, px: u32) {
+        self.config.theme.border_width = px;
+        self.appearance_changed = true;
+    }
+
+    /// Change the gap sizes used by any [Gaps][crate::builtin::layout::transformers::Gaps]
+    /// layouts on all workspaces. Clients are repositioned on the next refresh.
+    ///
+    /// To change all appearance settings at once, see [State::set_theme].
+    ///
+    /// ```no_run
+    /// # use penrose::{builtin::actions::key_handler, x::{XConn, XConnExt}};
+    /// # fn example<X: XConn>() {
+    /// let toggle_theme = key_handler(|state, x: &X| {
+    ///     if state.config.theme.border_width == 2 {
+    ///         state.set_border_colors(0xeeeeeeff, 0x458588ff);
+    ///         state.set_border_width(4);
+    ///         state.set_gaps(10, 5);
+    ///     } else {
+    ///         state.set_border_colors(0x3c3836ff, 0xcc241dff);
+    ///         state.set_border_width(2);
+    ///         state.set_gaps(0, 0);
+    ///     }
+    ///
+    ///     x.refresh(state)
+    /// });
+    /// # }
+    /// ```
+    pub fn set_gaps(&mut self, outer_px: u32, inner_px: u32) {
+        self.config.theme.outer_gap = outer_px;
+        self.config.theme.inner_gap = inner_px;
+        for w in self.client_set.workspaces_mut() {
+            w.broadcast_message(SetGaps { outer_px, inner_px });
+        }
+    }
+
+    /// Replace the current [Theme], re-applying it to all managed clients on the next refresh.
+    ///
+    /// The new gap sizes are sent to all layouts (see [State::set_gaps]) along with the theme
+    /// itself as a layout message. If titlebars are enabled then their colors are also
+    /// updated to match the new theme.
+    pub fn set_theme(&mut self, theme: Theme) {
+        if let Some(t) = self.config.titlebar.as_mut() {
+            let themed = theme.titlebar();
+            t.focused_fg = themed.focused_fg;
+            t.focused_bg = themed.focused_bg;
+            t.normal_fg = themed.normal_fg;
+            t.normal_bg = themed.normal_bg;
+        }
+
+        self.set_gaps(theme.outer_gap, theme.inner_gap);
+        for w in self.client_set.workspaces_mut() {
+            w.broadcast_message(theme.clone());
+        }
+        self.config.theme = theme;
+        self.appearance_changed = true;
+    }
+
+    /// Suppress (or stop suppressing) clients being marked as urgent when they try to steal
+    /// focus. While suppressed, such requests are ignored rather than following the
+    /// [FocusRequestAction] set in the [FocusStealingPolicy].
+    pub fn suppress_urgency(&mut self, suppress: bool) {
+        self.urgency_suppressed = suppress;
+    }
+
+    /// Enable or disable [Config::focus_follow_mouse] at runtime.
+    ///
+    /// While disabled, focus is only changed by key and mouse bindings so that the pointer can
+    /// be moved across other clients (for example while dragging and dropping) without them
+    /// taking focus.
+    pub fn set_focus_follow_mouse(&mut self, enabled: bool) {
+        self.config.focus_follow_mouse = enabled;
+    }
+
+    /// Remove the binding for a key spec such as `"M-S-Return"` from the default key
+    /// bindings once the current event has been handled.
+    pub fn unbind_key(&mut self, spec: impl Into<String>) {
+        self.binding_changes
+            .push(BindingChange::Unbind(spec.into()));
+    }
+
     /// Get access to a shared state extension.
     ///
     /// To add an extension to [State] before starting the Window Manager, see the
@@ -152,6 +771,139 @@ where
     pub fn add_extension<E: Any>(&mut self, extension: E) {
         self.extensions.insert(Arc::new(RefCell::new(extension)));
     }
+
+    /// Add a typed [State] extension that is already shared with something else, such as
+    /// the [Metrics][metrics::Metrics] recorded by a [TimedConn][crate::x::TimedConn].
+    pub fn add_shared_extension<E: Any>(&mut self, extension: Arc<RefCell<E>>) {
+        self.extensions.insert(extension);
+    }
+}
+
+/// What to do with a request from a client to take input focus.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FocusRequestAction {
+    /// Give focus to the client
+    #[default]
+    Focus,
+    /// Leave focus where it is and mark the client as demanding attention
+    MarkUrgent,
+    /// Leave focus where it is
+    Ignore,
+}
+
+/// How requests from clients to take input focus should be handled, either when they are
+/// first mapped or when they send a `_NET_ACTIVE_WINDOW` client message.
+///
+/// A request is considered to be stealing focus if the `_NET_WM_USER_TIME` of the requesting
+/// client (or the timestamp of the message for `_NET_ACTIVE_WINDOW`) is older than the
+/// `_NET_WM_USER_TIME` of the currently focused client: in other words, the user has
+/// interacted with the focused client since the requesting client was last used. Requests
+/// where either timestamp is unknown are always allowed.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct FocusStealingPolicy {
+    /// The action to take for requests that would steal focus
+    pub on_steal: FocusRequestAction,
+    /// Window classes that are always given focus when they request it
+    pub always_focus_classes: Vec<String>,
+}
+
+impl FocusStealingPolicy {
+    /// Determine how to handle a focus request from a client with the given classes.
+    ///
+    /// A requested time of 0 indicates that the client should not be focused when it is
+    /// first mapped, as described in the EWMH spec for `_NET_WM_USER_TIME`.
+    pub fn action_for(
+        &self,
+        classes: &[String],
+        requested_time: Option<u32>,
+        focused_time: Option<u32>,
+    ) -> FocusRequestAction {
+        if classes
+            .iter()
+            .any(|c| self.always_focus_classes.contains(c))
+        {
+            return FocusRequestAction::Focus;
+        }
+
+        match (requested_time, focused_time) {
+            (Some(0), _) => self.on_steal,
+            // X server timestamps wrap around so we need to compare them as a signed difference
+            (Some(req), Some(foc)) if (req.wrapping_sub(foc) as i32) < 0 => self.on_steal,
+            _ => FocusRequestAction::Focus,
+        }
+    }
+}
+
+/// An action to take for clients arriving on a particular workspace when they are first
+/// managed (see [Config::workspace_rules]).
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WorkspaceRule {
+    /// Float the client at the position it requested
+    Float,
+    /// Float the client in the center of the screen, scaled to the given width and height
+    /// ratios of the screen
+    FloatCentered(f64, f64),
+    /// Float the client at a fixed position
+    FloatAt(Rect),
+    /// Tile the client even if it would otherwise have been floated
+    Tile,
+    /// Make the client fullscreen
+    Fullscreen,
+}
+
+/// Border colors to use for clients matching a [Query] in place of those set in the [Theme]
+/// (see [Config::border_rules]).
+///
+/// ```no_run
+/// # use penrose::{core::BorderRule, x::query::ClassName, x11rb::RustConn};
+/// let rule: BorderRule<RustConn> = BorderRule::new(ClassName("Emacs"), 0x98971aff)
+///     .focused(0xb8bb26ff);
+/// ```
+pub struct BorderRule<X>
+where
+    X: XConn,
+{
+    pub(crate) query: Box<dyn Query<X>>,
+    pub(crate) normal: Color,
+    pub(crate) focused: Color,
+}
+
+impl<X> fmt::Debug for BorderRule<X>
+where
+    X: XConn,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BorderRule")
+            .field("normal", &self.normal)
+            .field("focused", &self.focused)
+            .finish()
+    }
+}
+
+impl<X> BorderRule<X>
+where
+    X: XConn,
+{
+    /// Use the given border color for clients matching `query` whether or not they have
+    /// focus.
+    pub fn new(query: impl Query<X> + 'static, color: impl Into<Color>) -> Self {
+        let color = color.into();
+
+        Self {
+            query: Box::new(query),
+            normal: color,
+            focused: color,
+        }
+    }
+
+    /// Use a different border color for matching clients while they have focus.
+    pub fn focused(mut self, color: impl Into<Color>) -> Self {
+        self.focused = color.into();
+        self
+    }
 }
 
 /// The user specified config options for how the window manager should run
@@ -159,28 +911,129 @@ pub struct Config<X>
 where
     X: XConn,
 {
-    /// The RGBA color to use for normal (unfocused) window borders
-    pub normal_border: Color,
-    /// The RGBA color to use for the focused window border
-    pub focused_border: Color,
-    /// The width in pixels to use for drawing window borders
-    pub border_width: u32,
+    /// The colors, border width and gaps to use for clients and decorations
+    pub theme: Theme,
+    /// The radius in pixels to use for rounding the corners of managed windows.
+    ///
+    /// Requires the X server to support the Shape extension. Clients filling an entire
+    /// screen (such as fullscreen windows) are never rounded.
+    pub rounded_corners: Option<u32>,
+    /// Whether or not tiled clients should respect their min and max size hints.
+    ///
+    /// When enabled, clients with a max size smaller than the position assigned to them by
+    /// the current layout are centered within it rather than being stretched to fill it.
+    /// When disabled (the default), tiled clients have their size hints applied in the same
+    /// way as floating clients.
+    pub respect_size_hints: bool,
+    /// Whether or not managed clients should be reparented into a frame window owned by the
+    /// window manager.
+    ///
+    /// Frames are used for drawing window decorations and to prevent clients from overriding
+    /// the border settings applied by the window manager. Requires support from the [XConn]
+    /// being used (see [XConn::create_frame]).
+    pub reparent_clients: bool,
+    /// Titlebars to draw for clients when [Config::reparent_clients] is enabled.
+    pub titlebar: Option<TitlebarConfig>,
     /// Whether or not the mouse entering a new window should set focus
     pub focus_follow_mouse: bool,
+    /// Whether or not the pointer should be warped to the centre of the newly focused client
+    /// (or screen if it is empty) when focus is changed by something other than the mouse.
+    ///
+    /// Focus changes resulting from pointer events are never warped so that keyboard
+    /// navigation does not fight with [Config::focus_follow_mouse].
+    pub warp_pointer_on_focus: bool,
+    /// How long to wait for the next key of a key chord before cancelling it
+    /// (see [Submap][bindings::Submap]).
+    pub chord_timeout: Duration,
+    /// The maximum time between presses of a mouse button for them to count towards a
+    /// double click (see [MouseState::clicks][bindings::MouseState::clicks]).
+    pub double_click_timeout: Duration,
+    /// An overlay listing the available keys to show while waiting for the next key of a key
+    /// chord or while a binding mode is active.
+    pub binding_hints: Option<BindingHintsConfig>,
+    /// Whether or not to replace an already running window manager on startup.
+    ///
+    /// The running window manager needs to support the ICCCM WM_Sn manager selection
+    /// for this to work.
+    pub replace_existing_wm: bool,
     /// The stack of layouts to use for each workspace
     pub default_layouts: LayoutStack,
     /// The ordered set of workspace tags to use on window manager startup
     pub tags: Vec<String>,
     /// Window classes that should always be assigned floating positions rather than tiled
     pub floating_classes: Vec<String>,
+    /// Values of _NET_WM_WINDOW_TYPE that should always be assigned floating positions rather
+    /// than tiled.
+    ///
+    /// Defaults to [AUTO_FLOAT_WINDOW_TYPES][crate::x::atom::AUTO_FLOAT_WINDOW_TYPES].
+    pub floating_window_types: Vec<Atom>,
+    /// Whether or not clients that set the same min and max size in their WM_NORMAL_HINTS
+    /// should be assigned floating positions rather than tiled.
+    pub float_fixed_size_clients: bool,
+    /// [WorkspaceRule]s to apply to new clients based on the tag of the workspace they are
+    /// placed on.
+    ///
+    /// Workspace rules are applied in order after the [Config::manage_hook] has been run, so
+    /// clients moved to a different workspace by the manage hook will have the rules for
+    /// their new workspace applied.
+    pub workspace_rules: HashMap<String, Vec<WorkspaceRule>>,
+    /// [BorderRule]s for overriding the border colors set in [Config::theme] for clients
+    /// matching a [Query].
+    ///
+    /// Rules are checked in order when a client is first managed and the first matching
+    /// rule is used for the lifetime of the client.
+    pub border_rules: Vec<BorderRule<X>>,
+    /// How workspaces should be re-homed when a monitor is disconnected
+    pub hotplug_policy: HotplugPolicy,
+    /// Whether or not space reserved by dock windows using _NET_WM_STRUT_PARTIAL or
+    /// _NET_WM_STRUT should be removed from the region of each screen given to layouts.
+    ///
+    /// Reserved space is recalculated when screens are detected and when docks are shown or
+    /// hidden using [toggle_docks][crate::builtin::actions::docks::toggle_docks]. It can also be
+    /// recalculated explicitly using [XConnExt::update_workarea][crate::x::XConnExt::update_workarea].
+    pub respect_struts: bool,
+    /// How requests from clients to take focus should be handled
+    pub focus_stealing_policy: FocusStealingPolicy,
     /// A [StateHook] to run before entering the main event loop
     pub startup_hook: Option<Box<dyn StateHook<X>>>,
     /// A [StateHook] to run before processing each [XEvent]
     pub event_hook: Option<Box<dyn EventHook<X>>>,
     /// A [ManageHook] to run after each new window becomes managed by the window manager
     pub manage_hook: Option<Box<dyn ManageHook<X>>>,
+    /// A [FocusChangeHook] to run each time the focused client changes
+    pub focus_change_hook: Option<Box<dyn FocusChangeHook<X>>>,
+    /// An [UnmanageHook] to run each time a client is removed from the window manager state
+    pub unmanage_hook: Option<Box<dyn UnmanageHook<X>>>,
+    /// A [MonitorChangeHook] to run each time outputs are added, removed or resized
+    pub monitor_change_hook: Option<Box<dyn MonitorChangeHook<X>>>,
+    /// How long the user needs to have been idle for before running the
+    /// [Config::idle_hook].
+    pub idle_thresholds: Vec<Duration>,
+    /// An [IdleHook] to run each time the user passes one of the [Config::idle_thresholds]
+    /// and when they become active again. Requires support from the [XConn] being used (see
+    /// [XConn::idle_time]).
+    pub idle_hook: Option<Box<dyn IdleHook<X>>>,
+    /// A [LayoutChangeHook] to run each time the active layout of a visible workspace changes
+    pub layout_change_hook: Option<Box<dyn LayoutChangeHook<X>>>,
+    /// A [WorkspaceSwitchHook] to run each time a screen switches to showing a different
+    /// workspace
+    pub workspace_switch_hook: Option<Box<dyn WorkspaceSwitchHook<X>>>,
     /// A [StateHook] to run every time the on screen X state is refreshed
     pub refresh_hook: Option<Box<dyn StateHook<X>>>,
+    /// A [StateHook] to run before exiting, either after [State::exit] has been called or
+    /// when the connection to the X server has been lost.
+    ///
+    /// If the connection has been lost then the [XConn] passed to this hook is no longer
+    /// connected so this should only be used for cleaning up any external resources.
+    pub shutdown_hook: Option<Box<dyn StateHook<X>>>,
+    /// Whether or not all clients should be mapped and removed from their frames when exiting
+    /// via [State::exit], so that another window manager is able to take over managing them.
+    pub release_clients_on_exit: bool,
+    /// The number of attempts to make at reconnecting to the X server if the connection is
+    /// lost (see [WindowManager::set_reconnect]).
+    pub reconnect_attempts: u32,
+    /// What to do when a user hook panics
+    pub hook_panic_policy: HookPanicPolicy,
 }
 
 impl<X> fmt::Debug for Config<X>
@@ -189,13 +1042,31 @@ where
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Config")
-            .field("normal_border", &self.normal_border)
-            .field("focused_border", &self.focused_border)
-            .field("border_width", &self.border_width)
+            .field("theme", &self.theme)
+            .field("rounded_corners", &self.rounded_corners)
+            .field("respect_size_hints", &self.respect_size_hints)
+            .field("reparent_clients", &self.reparent_clients)
+            .field("titlebar", &self.titlebar)
             .field("focus_follow_mouse", &self.focus_follow_mouse)
+            .field("warp_pointer_on_focus", &self.warp_pointer_on_focus)
+            .field("chord_timeout", &self.chord_timeout)
+            .field("double_click_timeout", &self.double_click_timeout)
+            .field("binding_hints", &self.binding_hints)
+            .field("replace_existing_wm", &self.replace_existing_wm)
             .field("default_layouts", &self.default_layouts)
             .field("tags", &self.tags)
             .field("floating_classes", &self.floating_classes)
+            .field("floating_window_types", &self.floating_window_types)
+            .field("float_fixed_size_clients", &self.float_fixed_size_clients)
+            .field("workspace_rules", &self.workspace_rules)
+            .field("border_rules", &self.border_rules)
+            .field("hotplug_policy", &self.hotplug_policy)
+            .field("respect_struts", &self.respect_struts)
+            .field("focus_stealing_policy", &self.focus_stealing_policy)
+            .field("reconnect_attempts", &self.reconnect_attempts)
+            .field("hook_panic_policy", &self.hook_panic_policy)
+            .field("release_clients_on_exit", &self.release_clients_on_exit)
+            .field("idle_thresholds", &self.idle_thresholds)
             .finish()
     }
 }
@@ -208,17 +1079,42 @@ where
         let strings = |slice: &[&str]| slice.iter().map(|s| s.to_string()).collect();
 
         Config {
-            normal_border: "#3c3836".try_into().expect("valid hex code"),
-            focused_border: "#cc241d".try_into().expect("valid hex code"),
-            border_width: 2,
+            theme: Theme::default(),
+            rounded_corners: None,
+            respect_size_hints: false,
+            reparent_clients: false,
+            titlebar: None,
             focus_follow_mouse: true,
+            warp_pointer_on_focus: false,
+            chord_timeout: Duration::from_secs(2),
+            double_click_timeout: Duration::from_millis(400),
+            binding_hints: None,
+            replace_existing_wm: false,
             default_layouts: LayoutStack::default(),
             tags: strings(&["1", "2", "3", "4", "5", "6", "7", "8", "9"]),
             floating_classes: strings(&["dmenu", "dunst"]),
+            floating_window_types: AUTO_FLOAT_WINDOW_TYPES.to_vec(),
+            float_fixed_size_clients: true,
+            workspace_rules: HashMap::new(),
+            border_rules: Vec::new(),
+            hotplug_policy: HotplugPolicy::default(),
+            respect_struts: false,
+            focus_stealing_policy: FocusStealingPolicy::default(),
             startup_hook: None,
             event_hook: None,
             manage_hook: None,
+            focus_change_hook: None,
+            unmanage_hook: None,
+            monitor_change_hook: None,
+            idle_thresholds: Vec::new(),
+            idle_hook: None,
+            layout_change_hook: None,
+            workspace_switch_hook: None,
             refresh_hook: None,
+            shutdown_hook: None,
+            release_clients_on_exit: false,
+            reconnect_attempts: 5,
+            hook_panic_policy: HookPanicPolicy::default(),
         }
     }
 }
@@ -269,38 +1165,177 @@ where
         };
     }
 
-    /// Set the refresh_hook or compose it with what is already set.
+    /// Set the focus_change_hook or compose it with what is already set.
     ///
     /// The new hook will run before what was there before.
-    pub fn compose_or_set_refresh_hook<H>(&mut self, hook: H)
+    pub fn compose_or_set_focus_change_hook<H>(&mut self, hook: H)
     where
-        H: StateHook<X> + 'static,
+        H: FocusChangeHook<X> + 'static,
         X: 'static,
     {
-        self.refresh_hook = match self.refresh_hook.take() {
+        self.focus_change_hook = match self.focus_change_hook.take() {
             Some(h) => Some(hook.then_boxed(h)),
             None => Some(hook.boxed()),
         };
     }
-}
-
-/// A top level struct holding all of the state required to run as an X11 window manager.
-///
-/// This allows for final configuration to be carried out before entering the main event
-/// loop.
-#[derive(Debug)]
-pub struct WindowManager<X>
-where
-    X: XConn,
-{
-    x: X,
-    /// The mutable [State] of the window manager
-    pub state: State<X>,
-    key_bindings: KeyBindings<X>,
-    mouse_bindings: MouseBindings<X>,
-}
 
-impl<X> WindowManager<X>
+    /// Set the unmanage_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_unmanage_hook<H>(&mut self, hook: H)
+    where
+        H: UnmanageHook<X> + 'static,
+        X: 'static,
+    {
+        self.unmanage_hook = match self.unmanage_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the monitor_change_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_monitor_change_hook<H>(&mut self, hook: H)
+    where
+        H: MonitorChangeHook<X> + 'static,
+        X: 'static,
+    {
+        self.monitor_change_hook = match self.monitor_change_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the idle_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_idle_hook<H>(&mut self, hook: H)
+    where
+        H: IdleHook<X> + 'static,
+        X: 'static,
+    {
+        self.idle_hook = match self.idle_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the layout_change_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_layout_change_hook<H>(&mut self, hook: H)
+    where
+        H: LayoutChangeHook<X> + 'static,
+        X: 'static,
+    {
+        self.layout_change_hook = match self.layout_change_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the workspace_switch_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_workspace_switch_hook<H>(&mut self, hook: H)
+    where
+        H: WorkspaceSwitchHook<X> + 'static,
+        X: 'static,
+    {
+        self.workspace_switch_hook = match self.workspace_switch_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the refresh_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_refresh_hook<H>(&mut self, hook: H)
+    where
+        H: StateHook<X> + 'static,
+        X: 'static,
+    {
+        self.refresh_hook = match self.refresh_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the shutdown_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_shutdown_hook<H>(&mut self, hook: H)
+    where
+        H: StateHook<X> + 'static,
+        X: 'static,
+    {
+        self.shutdown_hook = match self.shutdown_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+}
+
+// Backoff between attempts at reconnecting to the X server
+const RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+// The bindings being listed by the binding hint overlay
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HintSource {
+    Chord {
+        mode: Option<String>,
+        client_bindings: Option<usize>,
+        path: Vec<KeyCode>,
+    },
+    Mode(String),
+}
+
+// A binding hint overlay that is currently on screen
+#[derive(Debug)]
+struct ShownHints {
+    id: Xid,
+    source: HintSource,
+    hints: BindingHints,
+}
+
+// A function for establishing a new connection to the X server
+struct Reconnect<X>(Box<dyn FnMut() -> Result<X>>);
+
+impl<X> fmt::Debug for Reconnect<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reconnect").finish()
+    }
+}
+
+/// A top level struct holding all of the state required to run as an X11 window manager.
+///
+/// This allows for final configuration to be carried out before entering the main event
+/// loop.
+#[derive(Debug)]
+pub struct WindowManager<X>
+where
+    X: XConn,
+{
+    x: X,
+    /// The mutable [State] of the window manager
+    pub state: State<X>,
+    key_bindings: KeyBindings<X>,
+    client_bindings: Vec<ClientBindings<X>>,
+    mouse_bindings: MouseBindings<X>,
+    border_bindings: MouseBindings<X>,
+    keycodes: HashMap<String, u8>,
+    chord: Option<Chord>,
+    modes: HashMap<String, Mode<X>>,
+    active_mode: Option<String>,
+    hints: Option<ShownHints>,
+    reconnect: Option<Reconnect<X>>,
+    queued_events: VecDeque<XEvent>,
+}
+
+impl<X> WindowManager<X>
 where
     X: XConn,
 {
@@ -314,11 +1349,13 @@ where
         mouse_bindings: MouseBindings<X>,
         x: X,
     ) -> Result<Self> {
+        let outputs = x.output_details()?;
         let mut client_set = StackSet::try_new(
             config.default_layouts.clone(),
             config.tags.iter(),
-            x.screen_details()?,
+            outputs.iter().map(|o| o.r),
         )?;
+        client_set.update_screen_outputs(outputs);
 
         let ss = client_set.snapshot(vec![]);
         let diff = Diff::new(ss.clone(), ss);
@@ -328,76 +1365,896 @@ where
             client_set,
             extensions: AnyMap::new(),
             root: x.root(),
+            roots: x.roots(),
+            client_x_screens: HashMap::new(),
             mapped: HashSet::new(),
             pending_unmap: HashMap::new(),
+            frames: HashMap::new(),
             current_event: None,
             diff,
+            mode: None,
+            binding_changes: Vec::new(),
+            modifier_release: None,
+            drag: None,
+            last_click: None,
+            count: None,
+            scheduler: Scheduler::default(),
+            deferred: DeferredQueue::new()?,
+            named_hooks: NamedHooks::default(),
+            hook_panics: HashMap::new(),
+            idle: IdleTracker::default(),
+            bus: MessageBus::default(),
+            exit_requested: false,
+            #[cfg(feature = "restart")]
+            restart_requested: false,
+            appearance_changed: false,
+            position_settings: None,
+            stale_positions: HashSet::new(),
+            urgency_suppressed: false,
+            focus_history: VecDeque::new(),
+            urgent: Vec::new(),
+            sticky: Vec::new(),
+            border_overrides: HashMap::new(),
         };
 
         Ok(Self {
             x,
             state,
             key_bindings,
+            client_bindings: Vec::new(),
             mouse_bindings,
+            border_bindings: HashMap::new(),
+            keycodes: HashMap::new(),
+            chord: None,
+            modes: HashMap::new(),
+            active_mode: None,
+            hints: None,
+            reconnect: None,
+            queued_events: VecDeque::new(),
         })
     }
 
+    /// Set a function for establishing a new connection to the X server, to be used if the
+    /// existing connection is lost while running.
+    ///
+    /// Reconnection is attempted up to [Config::reconnect_attempts] times with an exponential
+    /// backoff. On reconnecting, all existing clients are re-managed. If the connection
+    /// can not be re-established (or no reconnect function has been set) then the
+    /// [Config::shutdown_hook] is run and [WindowManager::run] returns an error.
+    pub fn set_reconnect<F>(&mut self, f: F)
+    where
+        F: FnMut() -> Result<X> + 'static,
+    {
+        self.reconnect = Some(Reconnect(Box::new(f)));
+    }
+
+    /// Get a [DeferredSender] for injecting closures and messages into the main loop of this
+    /// WindowManager from other threads.
+    ///
+    /// Senders obtained before calling [WindowManager::run] remain valid for as long as the
+    /// window manager is running. See the [deferred] module docs for details.
+    pub fn deferred_sender(&self) -> DeferredSender<X> {
+        self.state.deferred_sender()
+    }
+
     /// Add a typed [State] extension to this WindowManager.
     pub fn add_extension<E: Any>(&mut self, extension: E) {
         self.state.add_extension(extension);
     }
 
+    /// Add a typed [State] extension to this WindowManager that is already shared with
+    /// something else (see [State::add_shared_extension]).
+    pub fn add_shared_extension<E: Any>(&mut self, extension: Arc<RefCell<E>>) {
+        self.state.add_shared_extension(extension);
+    }
+
+    /// Add a named binding [Mode] that can be entered using [State::enter_mode].
+    ///
+    /// Adding a mode with the same name as an existing mode replaces it.
+    pub fn add_mode(&mut self, mode: Mode<X>) {
+        self.modes.insert(mode.name().to_string(), mode);
+    }
+
+    /// Add a set of [ClientBindings] that take precedence over the default key bindings
+    /// while the focused client matches their query.
+    ///
+    /// Client bindings are checked in the order they were added.
+    pub fn add_client_bindings(&mut self, bindings: ClientBindings<X>) {
+        self.client_bindings.push(bindings);
+    }
+
+    /// Add [MouseBindings] that are run in place of the regular mouse bindings when a mouse
+    /// button is pressed on the border of a client.
+    ///
+    /// The edge of the border that was pressed is given by [MouseEvent::border][bindings::MouseEvent::border]. Presses on
+    /// the border of a client without any modifiers held are only seen by the window manager
+    /// when [Config::reparent_clients] is enabled. See
+    /// [border_resize][crate::builtin::actions::floating::border_resize] for resizing clients
+    /// from the edge of the border that was pressed.
+    pub fn add_border_bindings(&mut self, bindings: MouseBindings<X>) {
+        self.border_bindings.extend(bindings);
+    }
+
+    /// A reference to the [XConn] being used by this WindowManager.
+    pub fn x(&self) -> &X {
+        &self.x
+    }
+
     /// Start the WindowManager and run it until told to exit.
     ///
     /// Any provided startup hooks will be run after setting signal handlers and grabbing
     /// key / mouse bindings from the X server. Any set up you need to do should be run
     /// explicitly before calling this method or as part of a startup hook.
+    ///
+    /// If the window manager panics, any grabs are released, input focus is returned to the
+    /// root window and all managed clients are mapped before the panic continues so that
+    /// the session is left usable.
     pub fn run(mut self) -> Result<()> {
         info!("registering SIGCHILD signal handler");
-        if let Err(e) = unsafe { signal(Signal::SIGCHLD, SigHandler::SigIgn) } {
+        if let Err(e) = util::reap_children_automatically() {
             panic!("unable to set signal handler: {}", e);
         }
 
+        let res = match panic::catch_unwind(AssertUnwindSafe(|| self.run_event_loop())) {
+            Ok(res) => res,
+            Err(payload) => {
+                error!("window manager panicked: restoring X state before exiting");
+                self.restore_x_after_panic();
+                panic::resume_unwind(payload);
+            }
+        };
+
+        #[cfg(feature = "restart")]
+        if res.is_ok() && self.state.restart_requested {
+            return self.restart();
+        }
+
+        res
+    }
+
+    fn run_event_loop(&mut self) -> Result<()> {
+        self.start()?;
+
+        loop {
+            self.run_scheduled_hooks();
+            self.run_deferred();
+            self.check_idle();
+            self.dispatch_messages();
+
+            if self.state.exit_requested {
+                return self.shutdown();
+            }
+
+            // Work that is already waiting to be run should not block on the X server
+            let deferred = &mut self.state.deferred;
+            let ready = (deferred.has_actions() || deferred.has_pending()).then(Instant::now);
+            let wakeup = deferred.is_waiting().then(|| deferred.wakeup_fd());
+
+            let deadline = [
+                self.chord.as_ref().map(|c| c.deadline),
+                self.state.scheduler.next_deadline(),
+                self.state.idle.next_check,
+                ready,
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+
+            let next = match self.queued_events.pop_front() {
+                Some(event) => Ok(Some(event)),
+                None => self.next_coalesced_event(deadline, wakeup),
+            };
+
+            match next {
+                Ok(Some(event)) => {
+                    if self.process_event(event)? {
+                        return Ok(());
+                    }
+
+                    if self.state.exit_requested {
+                        return self.shutdown();
+                    }
+
+                    #[cfg(feature = "restart")]
+                    if self.state.restart_requested {
+                        return Ok(());
+                    }
+                }
+
+                Ok(None) => {
+                    if matches!(&self.chord, Some(c) if c.deadline <= Instant::now()) {
+                        self.time_out_chord();
+                    }
+                }
+
+                Err(e) if e.is_fatal_connection_error() => {
+                    error!(%e, "Lost connection to the X server");
+                    self.recover_connection()?;
+                }
+
+                Err(e) => error!(%e, "Error pulling next x event"),
+            }
+        }
+    }
+
+    /// Restart the window manager in place by `exec`ing the current binary with the same
+    /// arguments, preserving the current position of all clients.
+    ///
+    /// The X connection is closed before the new process is started so this only returns
+    /// if the state could not be saved or the current binary could not be run. See the
+    /// [restart] module docs for details.
+    #[cfg(feature = "restart")]
+    pub fn restart(self) -> Result<()> {
+        use restart::{SavedState, RESTART_STATE_ENV};
+        use std::{env, os::unix::process::CommandExt, process::Command};
+
+        info!("restarting in place");
+        let path = env::temp_dir().join(format!("penrose-restart-{}.json", std::process::id()));
+        SavedState::from_client_set(&self.state.client_set).write(&path)?;
+
+        let exe = env::current_exe()?;
+        let args: Vec<_> = env::args_os().skip(1).collect();
+
+        // Dropping the connection releases the window manager selection for the new process
+        drop(self);
+        let err = Command::new(exe)
+            .args(args)
+            .env(RESTART_STATE_ENV, &path)
+            .exec();
+
+        Err(err.into())
+    }
+
+    // Take ownership of the X server, grab bindings and manage any existing clients
+    pub(crate) fn start(&mut self) -> Result<()> {
+        info!("acquiring window manager selection");
+        self.x
+            .acquire_wm_selection(self.state.config.replace_existing_wm)?;
+
+        // Snapshot the current keymap so that bindings can be re-resolved if it changes
+        match self.x.keycodes() {
+            Ok(keycodes) => self.keycodes = keycodes,
+            Err(e) => {
+                warn!(%e, "unable to fetch keycodes: bindings will not follow keymap changes")
+            }
+        }
+
         self.grab()?;
+        self.set_root_cursors();
 
         if let Some(mut h) = self.state.config.startup_hook.take() {
             trace!("running user startup hook");
-            if let Err(e) = h.call(&mut self.state, &self.x) {
-                error!(%e, "error returned from user startup hook");
+            let x = &self.x;
+            run_hook("user startup hook", &mut self.state, |s| h.call(s, x));
+        }
+
+        if self.state.config.idle_hook.is_some() && !self.state.config.idle_thresholds.is_empty() {
+            self.state.idle.next_check = Some(Instant::now());
+        }
+
+        self.apply_mode_change()?;
+        self.apply_binding_changes()?;
+
+        if self.state.config.respect_struts {
+            self.x.update_workarea(&mut self.state)?;
+        }
+
+        self.manage_existing_clients()
+    }
+
+    // Handle a single event from the X server, returning whether or not we should exit
+    pub(crate) fn process_event(&mut self, event: XEvent) -> Result<bool> {
+        let span = span!(target: "penrose", Level::INFO, "XEvent", %event);
+        let _enter = span.enter();
+        trace!(details = ?event, "event details");
+        self.state.current_event = Some(event.clone());
+        let kind = event.kind();
+        let start = Instant::now();
+
+        let lost_wm_selection = matches!(&event, XEvent::SelectionClear(e) if e.is_wm_selection);
+
+        let res = self
+            .handle_xevent(event)
+            .and_then(|_| self.apply_mode_change())
+            .and_then(|_| self.apply_binding_changes())
+            .and_then(|_| self.apply_modifier_release())
+            .and_then(|_| self.grab_pointer_for_drag())
+            .and_then(|_| self.update_binding_hints());
+        self.x.flush();
+        self.state
+            .record_metric(|| format!("event/{kind:?}"), start.elapsed());
+
+        #[cfg(feature = "invariant-checks")]
+        self.state.enforce_invariants();
+        self.state.current_event = None;
+
+        match res {
+            Err(e) if e.is_fatal_connection_error() => {
+                error!(%e, "Lost connection to the X server");
+                self.recover_connection()?;
             }
+            Err(e) => error!(%e, "Error handling XEvent"),
+            Ok(_) => (),
         }
 
-        self.manage_existing_clients()?;
+        if lost_wm_selection {
+            info!("another window manager has taken over: exiting");
+        }
 
-        loop {
-            match self.x.next_event() {
-                Ok(event) => {
-                    let span = span!(target: "penrose", Level::INFO, "XEvent", %event);
-                    let _enter = span.enter();
-                    trace!(details = ?event, "event details");
-                    self.state.current_event = Some(event.clone());
-
-                    if let Err(e) = self.handle_xevent(event) {
-                        error!(%e, "Error handling XEvent");
+        Ok(lost_wm_selection)
+    }
+
+    // Switch the active key bindings if the requested binding mode has changed, running the
+    // exit and enter hooks of the modes involved. Errors from the hooks are logged rather
+    // than returned so that the grabbed keys always match the new mode.
+    fn apply_mode_change(&mut self) -> Result<()> {
+        if self.state.mode == self.active_mode {
+            return Ok(());
+        }
+
+        if let Some(name) = &self.state.mode {
+            if !self.modes.contains_key(name) {
+                warn!(%name, "attempt to enter an unknown binding mode");
+                self.state.mode = self.active_mode.clone();
+                return Ok(());
+            }
+        }
+
+        let previous = std::mem::replace(&mut self.active_mode, self.state.mode.clone());
+        self.state.count = None;
+        info!(from = ?previous, to = ?self.active_mode, "changing binding mode");
+
+        let x = &self.x;
+        if let Some(mode) = previous.and_then(|m| self.modes.get_mut(&m)) {
+            if let Some(h) = mode.on_exit.as_mut() {
+                if !run_hook("mode exit hook", &mut self.state, |s| h.call(s, x)).1 {
+                    mode.on_exit = None;
+                }
+            }
+        }
+
+        if let Some(mode) = self
+            .active_mode
+            .as_ref()
+            .and_then(|m| self.modes.get_mut(m))
+        {
+            if let Some(h) = mode.on_enter.as_mut() {
+                if !run_hook("mode enter hook", &mut self.state, |s| h.call(s, x)).1 {
+                    mode.on_enter = None;
+                }
+            }
+        }
+
+        self.grab()
+    }
+
+    // Apply any changes to the default key bindings requested since they were last checked,
+    // re-grabbing keys if the default bindings are active.
+    fn apply_binding_changes(&mut self) -> Result<()> {
+        if self.state.binding_changes.is_empty() {
+            return Ok(());
+        }
+
+        if self.keycodes.is_empty() {
+            self.keycodes = self.x.keycodes()?;
+        }
+
+        // Any in progress key chord may refer to bindings that are about to change
+        self.cancel_chord();
+
+        for change in std::mem::take(&mut self.state.binding_changes) {
+            match change {
+                BindingChange::Bind(spec, mut handler) => {
+                    match resolve_binding(&spec, &mut handler, &self.keycodes) {
+                        Ok(k) => {
+                            info!(%spec, "binding key");
+                            self.key_bindings.insert(k, handler);
+                        }
+                        Err(e) => error!(%e, %spec, "unable to bind key"),
+                    }
+                }
+
+                BindingChange::Unbind(spec) => match parse_binding(&spec, &self.keycodes) {
+                    Ok(k) => {
+                        info!(%spec, "unbinding key");
+                        self.key_bindings.remove(&k);
+                    }
+                    Err(e) => error!(%e, %spec, "unable to unbind key"),
+                },
+            }
+        }
+
+        if self.active_mode.is_none() {
+            self.grab()?;
+        }
+
+        Ok(())
+    }
+
+    // Run a newly registered modifier release handler straight away if no modifiers need to be
+    // released, otherwise grab the keyboard so that we are notified when they are.
+    fn apply_modifier_release(&mut self) -> Result<()> {
+        match &mut self.state.modifier_release {
+            Some(r) if r.mask == 0 => {
+                if let Some(mut r) = self.state.modifier_release.take() {
+                    if r.grabbed {
+                        self.x.ungrab_keyboard()?;
                     }
-                    self.x.flush();
+                    r.handler.call(&mut self.state, &self.x)?;
+                }
+            }
+
+            Some(r) if !r.grabbed => {
+                self.x.grab_keyboard()?;
+                r.grabbed = true;
+            }
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    // Grab the pointer for a newly started mouse drag so that we continue to receive motion
+    // events if the pointer leaves the window the drag started in.
+    fn grab_pointer_for_drag(&mut self) -> Result<()> {
+        if let Some(drag) = &mut self.state.drag {
+            if !drag.grabbed {
+                self.x.grab_pointer()?;
+                drag.grabbed = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Show, replace or hide the binding hint overlay (if enabled) to match the active key chord
+    // or binding mode.
+    fn update_binding_hints(&mut self) -> Result<()> {
+        let conf = match &self.state.config.binding_hints {
+            Some(conf) => conf,
+            None => return Ok(()),
+        };
+
+        let wanted = match (&self.chord, &self.active_mode) {
+            (Some(chord), mode) => Some(HintSource::Chord {
+                mode: mode.clone(),
+                client_bindings: chord.client_bindings,
+                path: chord.path.clone(),
+            }),
+            (None, Some(mode)) => Some(HintSource::Mode(mode.clone())),
+            (None, None) => None,
+        };
+
+        if self.hints.as_ref().map(|h| &h.source) == wanted.as_ref() {
+            return Ok(());
+        }
+
+        if let Some(shown) = self.hints.take() {
+            self.x.destroy_window(shown.id)?;
+        }
+
+        let source = match wanted {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+
+        let keycodes = &self.keycodes;
+        let entries = match &source {
+            HintSource::Chord {
+                mode,
+                client_bindings,
+                path,
+            } => {
+                let bindings = match (mode, client_bindings) {
+                    (Some(m), _) => self.modes.get_mut(m).map(|m| &mut m.bindings),
+                    (None, Some(ix)) => {
+                        self.client_bindings.get_mut(*ix).map(|cb| &mut cb.bindings)
+                    }
+                    (None, None) => Some(&mut self.key_bindings),
+                };
+
+                bindings.and_then(|b| submap_hints(b, path, keycodes))
+            }
+
+            HintSource::Mode(m) => self
+                .modes
+                .get_mut(m)
+                .map(|m| binding_hints(&mut m.bindings, &HashMap::new(), keycodes)),
+        };
+
+        let entries = match entries {
+            Some(entries) if !entries.is_empty() => entries,
+            _ => return Ok(()),
+        };
+
+        let screen = self.state.client_set.current_screen().geometry();
+        let hints = conf.hints(entries, screen);
+
+        if let Some(id) = self.x.create_overlay(self.state.root, hints.r)? {
+            trace!(%id, ?source, "showing binding hints");
+            self.x.map(id)?;
+            self.x.set_client_config(id, &[ClientConfig::StackTop])?;
+            self.x.draw_binding_hints(id, &hints)?;
+            self.hints = Some(ShownHints { id, source, hints });
+        }
+
+        Ok(())
+    }
+
+    // The key codes to grab for the active binding mode, or the default and client bindings if
+    // no mode is active
+    fn active_key_codes(&self) -> Vec<KeyCode> {
+        match self.active_mode.as_ref().and_then(|m| self.modes.get(m)) {
+            Some(mode) if mode.count_prefix => {
+                let digits = (0..=9).filter_map(|d| self.keycodes.get(&d.to_string()));
+                let codes: HashSet<KeyCode> = mode
+                    .bindings
+                    .keys()
+                    .copied()
+                    .chain(digits.map(|&code| KeyCode { mask: 0, code }))
+                    .collect();
+
+                codes.into_iter().collect()
+            }
+            Some(mode) => mode.bindings.keys().copied().collect(),
+            None => {
+                let codes: HashSet<KeyCode> = self
+                    .key_bindings
+                    .keys()
+                    .chain(
+                        self.client_bindings
+                            .iter()
+                            .flat_map(|cb| cb.bindings.keys()),
+                    )
+                    .copied()
+                    .collect();
+
+                codes.into_iter().collect()
+            }
+        }
+    }
+
+    // Run any scheduled hooks that are due, rescheduling those that repeat
+    pub(crate) fn run_scheduled_hooks(&mut self) {
+        let now = Instant::now();
+        let due = self.state.scheduler.due(now);
+        if due.is_empty() {
+            return;
+        }
+
+        for id in due {
+            let mut scheduled = match self.state.scheduler.take(id) {
+                Some(s) => s,
+                None => continue, // cancelled by an earlier hook
+            };
+
+            trace!(?id, "running scheduled hook");
+            let (x, h) = (&self.x, &mut scheduled.hook);
+            let desc = format!("scheduled hook {id:?}");
+            let (_, keep) = run_hook(&desc, &mut self.state, |s| h.call(s, x));
+            if keep {
+                self.state.scheduler.finish(scheduled, now);
+            } else {
+                self.state.scheduler.cancel(id);
+            }
+        }
+
+        self.x.flush();
+    }
+
+    // Check the idle time if it is due to be checked
+    pub(crate) fn check_idle(&mut self) {
+        if matches!(self.state.idle.next_check, Some(t) if t <= Instant::now()) {
+            self.poll_idle();
+        }
+    }
 
-                    self.state.current_event = None;
+    // Run the idle hook for any changes in the idle state of the user and work out when we
+    // next need to check the idle time.
+    pub(crate) fn poll_idle(&mut self) {
+        let idle = match self.x.idle_time() {
+            Ok(Some(idle)) => idle,
+            Ok(None) => {
+                warn!("idle time is not supported by this XConn: idle hooks will not be run");
+                self.state.idle.next_check = None;
+                return;
+            }
+            Err(e) => {
+                error!(%e, "unable to fetch idle time");
+                Duration::ZERO
+            }
+        };
+
+        let mut thresholds = self.state.config.idle_thresholds.clone();
+        thresholds.sort();
+        let events = self.state.idle.update(idle, &thresholds);
+
+        let mut hook = self.state.config.idle_hook.take();
+        for event in events {
+            if let Some(ref mut h) = hook {
+                trace!(?event, "running user idle hook");
+                let x = &self.x;
+                if !run_hook("user idle hook", &mut self.state, |s| h.call(event, s, x)).1 {
+                    hook = None;
                 }
+            }
+        }
+        self.state.config.idle_hook = hook;
 
-                Err(e) => error!(%e, "Error pulling next x event"),
+        self.state.idle.next_check = self
+            .state
+            .idle
+            .next_check_after(&thresholds)
+            .map(|d| Instant::now() + d);
+        self.x.flush();
+    }
+
+    // Wait for the next event and then read ahead any events that are already available so
+    // that bursts of redundant events can be coalesced before they are handled.
+    fn next_coalesced_event(
+        &mut self,
+        deadline: Option<Instant>,
+        wakeup: Option<RawFd>,
+    ) -> Result<Option<XEvent>> {
+        let event = match self.x.next_event_or_wakeup(deadline, wakeup)? {
+            Some(event) => event,
+            None => return Ok(None),
+        };
+
+        self.queued_events.push_back(event);
+        while self.queued_events.len() < MAX_COALESCED_EVENTS {
+            match self.x.poll_for_event()? {
+                Some(event) => self.queued_events.push_back(event),
+                None => break,
+            }
+        }
+
+        let n_read = self.queued_events.len();
+        coalesce(&mut self.queued_events);
+        if self.queued_events.len() < n_read {
+            trace!(
+                n_read,
+                n_kept = self.queued_events.len(),
+                "coalesced queued events"
+            );
+        }
+
+        Ok(self.queued_events.pop_front())
+    }
+
+    // Run any closures that have been submitted from other threads
+    pub(crate) fn run_deferred(&mut self) {
+        let mut ran = false;
+
+        for f in self.state.deferred.take_actions() {
+            trace!("running queued action");
+            let x = &self.x;
+            run_hook("queued action", &mut self.state, |s| f(s, x));
+            ran = true;
+        }
+
+        while let Some(f) = self.state.deferred.try_next() {
+            trace!("running deferred closure");
+            let x = &self.x;
+            run_hook("deferred closure", &mut self.state, |s| f(s, x));
+            ran = true;
+        }
+
+        if ran {
+            self.x.flush();
+        }
+    }
+
+    // Deliver any messages that have been published to their subscribers, including any
+    // messages published by the subscribers themselves.
+    pub(crate) fn dispatch_messages(&mut self) {
+        let mut ran = false;
+
+        while let Some(msg) = self.state.bus.next_message() {
+            for id in self.state.bus.subscribers_for(&msg) {
+                if let Some(mut handler) = self.state.bus.take(id) {
+                    trace!(msg = msg.type_name, ?id, "running message subscriber");
+                    let desc = format!("subscriber for {}", msg.type_name);
+                    let x = &self.x;
+                    let value = msg.value.as_ref();
+                    if run_hook(&desc, &mut self.state, |s| handler(value, s, x)).1 {
+                        self.state.bus.restore(id, handler);
+                    } else {
+                        self.state.bus.unsubscribe(id);
+                    }
+                    ran = true;
+                }
+            }
+        }
+
+        if ran {
+            self.x.flush();
+        }
+    }
+
+    // Cancel the active key chord (if there is one) after its timeout has passed
+    pub(crate) fn time_out_chord(&mut self) {
+        if let Some(chord) = &self.chord {
+            trace!(path = ?chord.path, "key chord timed out");
+            self.cancel_chord();
+        }
+    }
+
+    // Cancel the active key chord (if there is one), releasing the keyboard grab
+    pub(crate) fn cancel_chord(&mut self) {
+        if self.chord.take().is_some() {
+            if let Err(e) = self.x.ungrab_keyboard() {
+                error!(%e, "unable to release keyboard grab");
+            }
+            if let Err(e) = self.update_binding_hints() {
+                error!(%e, "unable to hide binding hints");
+            }
+            self.x.flush();
+        }
+    }
+
+    // Run the shutdown hook and release everything we have grabbed from the X server
+    pub(crate) fn shutdown(&mut self) -> Result<()> {
+        info!("shutting down");
+        if let Some(mut h) = self.state.config.shutdown_hook.take() {
+            trace!("running user shutdown hook");
+            let x = &self.x;
+            run_hook("user shutdown hook", &mut self.state, |s| h.call(s, x));
+        }
+
+        self.x.grab(&[], &[])?;
+        if self.state.config.release_clients_on_exit {
+            self.release_clients()?;
+        }
+        self.x.flush();
+
+        Ok(())
+    }
+
+    // Show all clients outside of any frames so that another window manager can manage them
+    fn release_clients(&mut self) -> Result<()> {
+        let clients: Vec<Xid> = self.state.client_set.clients().copied().collect();
+
+        for id in clients {
+            if let Some(frame) = self.state.frames.remove(&id) {
+                let r = self.x.client_geometry(frame)?;
+                let root = self.state.root_for_client(id);
+                self.x.reparent(id, root, Point::new(r.x, r.y))?;
+                self.x.destroy_window(frame)?;
+            }
+            self.x.set_wm_state(id, WmState::Normal)?;
+            self.x.map(id)?;
+        }
+
+        Ok(())
+    }
+
+    // Best effort attempt at leaving the X server in a usable state after a panic: a
+    // grabbed keyboard or hidden clients would otherwise outlive us. Errors (and any
+    // further panics) are ignored as there is nothing more that we can do at this point.
+    pub(crate) fn restore_x_after_panic(&mut self) {
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            let x = &self.x;
+            let results = [
+                x.ungrab_keyboard(),
+                x.ungrab_pointer(),
+                x.grab(&[], &[]),
+                x.focus(self.state.root),
+                self.release_clients(),
+            ];
+            self.x.flush();
+
+            for e in results.into_iter().filter_map(|r| r.err()) {
+                error!(%e, "error restoring X state after panic");
+            }
+        }));
+
+        if res.is_err() {
+            error!("panicked while restoring X state");
+        }
+    }
+
+    // Attempt to reconnect to the X server with backoff, running the shutdown hook and
+    // returning an error if we are unable to.
+    fn recover_connection(&mut self) -> Result<()> {
+        if let Some(mut reconnect) = self.reconnect.take() {
+            let mut backoff = RECONNECT_INITIAL_BACKOFF;
+
+            for attempt in 1..=self.state.config.reconnect_attempts {
+                info!(attempt, "attempting to reconnect to the X server");
+                match (reconnect.0)() {
+                    Ok(x) => {
+                        self.x = x;
+                        match self.reinitialize() {
+                            Ok(()) => {
+                                info!("reconnected to the X server");
+                                self.reconnect = Some(reconnect);
+                                return Ok(());
+                            }
+                            Err(e) => error!(%e, "unable to reinitialize after reconnecting"),
+                        }
+                    }
+                    Err(e) => error!(%e, "unable to reconnect to the X server"),
+                }
+
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+            }
+        }
+
+        if let Some(mut h) = self.state.config.shutdown_hook.take() {
+            trace!("running user shutdown hook");
+            let x = &self.x;
+            run_hook("user shutdown hook", &mut self.state, |s| h.call(s, x));
+        }
+
+        Err(Error::XConnectionLost)
+    }
+
+    // Re-establish our state against a new X connection. Any client state from the old
+    // connection is dropped before managing whatever clients are present on the new one.
+    fn reinitialize(&mut self) -> Result<()> {
+        self.queued_events.clear();
+        self.chord = None;
+        self.hints = None;
+        self.state.modifier_release = None;
+        self.state.drag = None;
+        self.state.last_click = None;
+        self.state.count = None;
+        self.state.root = self.x.root();
+        self.state.roots = self.x.roots();
+        self.state.client_x_screens.clear();
+        self.state.mapped.clear();
+        self.state.pending_unmap.clear();
+        self.state.frames.clear();
+
+        let clients: Vec<Xid> = self.state.client_set.clients().copied().collect();
+        for c in clients.iter() {
+            self.state.client_set.remove_client(c);
+        }
+        let ss = self.state.client_set.snapshot(vec![]);
+        self.state.diff = Diff::new(ss.clone(), ss);
+
+        handle::detect_screens(&mut self.state, &self.x)?;
+        self.x
+            .acquire_wm_selection(self.state.config.replace_existing_wm)?;
+        if let Ok(keycodes) = self.x.keycodes() {
+            self.keycodes = keycodes;
+        }
+        self.grab()?;
+        self.set_root_cursors();
+
+        self.manage_existing_clients()
+    }
+
+    // Replace the default X cursor on the root windows with a standard pointer
+    fn set_root_cursors(&self) {
+        for &root in self.state.roots.iter() {
+            if let Err(e) = self.x.set_cursor(root, Cursor::LeftPtr) {
+                warn!(%e, %root, "unable to set root window cursor");
             }
         }
     }
 
     fn grab(&self) -> Result<()> {
         trace!("grabbing key and mouse bindings");
-        let key_codes: Vec<_> = self.key_bindings.keys().copied().collect();
-        let mouse_states: Vec<_> = self
+        let key_codes = self.active_key_codes();
+        // Multiple click and border bindings share the grab of their button and modifiers. Those
+        // without modifiers are only run for clicks on frames so they are not grabbed at all.
+        let border_states = self
+            .border_bindings
+            .keys()
+            .filter(|(_, state)| !state.modifiers.is_empty());
+        let mut mouse_states: Vec<_> = self
             .mouse_bindings
             .keys()
-            .map(|(_, state)| state.clone())
+            .filter(|(_, state)| state.clicks <= 1 || !state.modifiers.is_empty())
+            .chain(border_states)
+            .map(|(_, state)| MouseState::new(state.button, state.modifiers.clone()))
             .collect();
+        mouse_states.sort_by_key(|s| (s.button(), s.mask()));
+        mouse_states.dedup();
 
         self.x.grab(&key_codes, &mouse_states)
     }
@@ -409,26 +2266,41 @@ where
             x,
             state,
             key_bindings,
+            client_bindings,
             mouse_bindings,
+            border_bindings,
+            keycodes,
+            chord,
+            modes,
+            active_mode,
+            hints,
+            ..
         } = self;
 
+        let mut should_run = true;
+        let kind = event.kind();
         let mut hook = state.config.event_hook.take();
-        let should_run = match hook {
-            Some(ref mut h) => {
-                trace!("running user event hook");
-                match h.call(&event, state, x) {
-                    Ok(should_run) => should_run,
-                    Err(e) => {
-                        error!(%e, "error returned from user event hook");
-                        true
-                    }
-                }
+        if let Some(h) = hook.as_mut().filter(|h| h.wants(kind)) {
+            trace!("running user event hook");
+            let (res, keep) = run_hook("user event hook", state, |s| h.call(&event, s, x));
+            should_run = res.unwrap_or(true);
+            if !keep {
+                hook = None;
             }
-
-            None => true,
-        };
+        }
         state.config.event_hook = hook;
 
+        run_named_hooks(state, x, |name, hook, state, x| match hook {
+            NamedHook::Event(h) if h.wants(kind) => {
+                trace!(%name, "running named event hook");
+                let desc = format!("named event hook '{name}'");
+                let (res, keep) = run_hook(&desc, state, |s| h.call(&event, s, x));
+                should_run &= res.unwrap_or(true);
+                keep
+            }
+            _ => true,
+        });
+
         if !should_run {
             trace!("User event hook returned false: skipping default handling");
             return Ok(());
@@ -440,17 +2312,47 @@ where
             ConfigureNotify(_) => (),  // Not currently handled
             ConfigureRequest(_) => (), // Not currently handled
             Enter(p) => handle::enter(*p, state, x)?,
-            Expose(_) => (), // Not currently handled
+            Expose(e) => match hints {
+                Some(shown) if shown.id == e.id => x.draw_binding_hints(e.id, &shown.hints)?,
+                _ => handle::expose(e, state, x)?,
+            },
             FocusIn(id) => handle::focus_in(*id, state, x)?,
             Destroy(xid) => handle::destroy(*xid, state, x)?,
-            KeyPress(code) => handle::keypress(*code, key_bindings, state, x)?,
+            KeyboardGroupChange(_) => (), // Available to user event hooks
+            KeymapChange => {
+                handle::keymap_change(key_bindings, client_bindings, modes, keycodes, x)?;
+                return self.grab();
+            }
+            KeyPress(code) => {
+                let (bindings, client_bindings, count_prefix) =
+                    match active_mode.as_ref().and_then(|m| modes.get_mut(m)) {
+                        Some(mode) => (&mut mode.bindings, &mut [][..], mode.count_prefix),
+                        None => (key_bindings, &mut client_bindings[..], false),
+                    };
+                if count_prefix
+                    && chord.is_none()
+                    && handle::count_digit(*code, bindings, keycodes, state)
+                {
+                    return Ok(());
+                }
+                handle::keypress(*code, bindings, client_bindings, chord, keycodes, state, x)?
+            }
+            KeyRelease(code) => handle::key_release(*code, keycodes, state, x)?,
             Leave(p) => handle::leave(*p, state, x)?,
             MappingNotify => (), // Not currently handled
             MapRequest(xid) => handle::map_request(*xid, state, x)?,
-            MouseEvent(e) => handle::mouse_event(e.clone(), mouse_bindings, state, x)?,
-            PropertyNotify(_) => (), // Not currently handled
+            MotionNotify(e) => {
+                handle::motion(e.clone(), mouse_bindings, border_bindings, state, x)?
+            }
+            MouseEvent(e) => {
+                handle::mouse_event(e.clone(), mouse_bindings, border_bindings, state, x)?
+            }
+            PropertyNotify(e) => handle::property_notify(e, state, x)?,
             RandrNotify => handle::detect_screens(state, x)?,
+            Readable(_) => (), // Available to user event hooks
             ScreenChange => handle::screen_change(state, x)?,
+            SelectionClear(_) => (), // Loss of the WM selection is handled in the main loop
+            Timer(_) => (),          // Available to user event hooks
             UnmapNotify(xid) => handle::unmap_notify(*xid, state, x)?,
         }
 
@@ -478,8 +2380,14 @@ where
             .collect();
 
         let first_tag = self.state.client_set.ordered_tags()[0].clone();
+        let saved = restart::take_saved_state();
 
-        for id in self.x.existing_clients()? {
+        // Clients are inserted as the focus of their workspace so they are managed in reverse
+        // order in order to end up in the order they had before we started.
+        let mut existing = self.existing_clients_in_stack_order()?;
+        existing.reverse();
+
+        for id in existing {
             if self.state.client_set.contains(&id)
                 || self.x.get_window_attributes(id)?.override_redirect
             {
@@ -492,11 +2400,285 @@ where
                 _ => 0, // we know that we always have at least one workspace
             };
 
-            let tag = ws_map.get(&workspace_id).unwrap_or(&first_tag);
+            let tag = match saved.as_ref().and_then(|s| s.tag_for(id)) {
+                Some(tag) => tag,
+                None => ws_map.get(&workspace_id).unwrap_or(&first_tag).as_str(),
+            };
             manage_without_refresh(id, Some(tag), &mut self.state, &self.x)?;
         }
 
+        if let Ok(Some(Prop::Window(ids))) = self
+            .x
+            .get_prop(self.x.root(), Atom::NetActiveWindow.as_ref())
+        {
+            if let Some(&id) = ids.first().filter(|id| self.state.client_set.contains(id)) {
+                info!(%id, "restoring previously active client");
+                self.state.client_set.focus_client(&id);
+            }
+        }
+
+        if let Some(saved) = saved {
+            saved.restore(&mut self.state.client_set);
+        }
+
         info!("triggering refresh");
         self.x.refresh(&mut self.state)
     }
+
+    // The existing clients sorted by their position in _NET_CLIENT_LIST_STACKING (as set by
+    // a previous window manager) with any clients not in the list following from the top of
+    // the X stacking order down.
+    fn existing_clients_in_stack_order(&self) -> Result<Vec<Xid>> {
+        // existing_clients is reported bottom to top as is _NET_CLIENT_LIST_STACKING so
+        // both are reversed to place the top-most window first
+        let mut clients = self.x.existing_clients()?;
+        clients.reverse();
+
+        let stacking = match self
+            .x
+            .get_prop(self.x.root(), Atom::NetClientListStacking.as_ref())
+        {
+            Ok(Some(Prop::Window(ids))) => ids,
+            _ => return Ok(clients),
+        };
+
+        let position: HashMap<Xid, usize> = stacking
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, &id)| (id, i))
+            .collect();
+        clients.sort_by_key(|id| position.get(id).copied().unwrap_or(usize::MAX));
+
+        Ok(clients)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::actions::{exit, modify_with, toggle_sticky},
+        x::{
+            fixtures::{record, recorded, ScriptedWm, SCREEN},
+            mock::{Request, ScriptedConn},
+            query::ClassName,
+            ClientAttr,
+        },
+    };
+    use simple_test_case::test_case;
+
+    #[test_case(None, Some(10), FocusRequestAction::Focus; "unknown request time")]
+    #[test_case(Some(20), None, FocusRequestAction::Focus; "unknown focused time")]
+    #[test_case(Some(20), Some(10), FocusRequestAction::Focus; "more recent")]
+    #[test_case(Some(10), Some(10), FocusRequestAction::Focus; "same time")]
+    #[test_case(Some(5), Some(10), FocusRequestAction::Ignore; "older")]
+    #[test_case(Some(0), None, FocusRequestAction::Ignore; "do not focus on map")]
+    #[test_case(Some(5), Some(u32::MAX - 5), FocusRequestAction::Focus; "wrapped")]
+    #[test]
+    fn focus_stealing_compares_user_time(
+        requested: Option<u32>,
+        focused: Option<u32>,
+        expected: FocusRequestAction,
+    ) {
+        let policy = FocusStealingPolicy {
+            on_steal: FocusRequestAction::Ignore,
+            always_focus_classes: vec![],
+        };
+
+        assert_eq!(policy.action_for(&[], requested, focused), expected);
+    }
+
+    #[test]
+    fn focus_stealing_always_focuses_exempt_classes() {
+        let policy = FocusStealingPolicy {
+            on_steal: FocusRequestAction::MarkUrgent,
+            always_focus_classes: vec!["dmenu".to_owned()],
+        };
+        let classes = vec!["dmenu".to_owned()];
+
+        let action = policy.action_for(&classes, Some(1), Some(10));
+
+        assert_eq!(action, FocusRequestAction::Focus);
+    }
+
+    // Clients 1 to 4 already exist with client 4 at the top of the X stacking order
+    fn existing_clients_wm(props: Vec<(Atom, Prop)>) -> WindowManager<ScriptedConn> {
+        ScriptedWm::new()
+            .with_conn(|conn| {
+                let root = conn.root();
+                let conn = (1..=4).fold(conn, |c, id| c.with_existing_client(Xid(id), SCREEN));
+
+                props
+                    .into_iter()
+                    .fold(conn, |c, (atom, p)| c.with_prop(root, atom.as_ref(), p))
+            })
+            .run()
+    }
+
+    #[test]
+    fn existing_clients_are_adopted_in_their_previous_stack_order() {
+        // Written bottom to top by the previous window manager as required by EWMH
+        let wm = existing_clients_wm(vec![
+            (
+                Atom::NetClientListStacking,
+                Prop::Window(vec![Xid(2), Xid(1), Xid(3)]),
+            ),
+            (Atom::NetActiveWindow, Prop::Window(vec![Xid(1)])),
+        ]);
+        let cs = &wm.state.client_set;
+
+        let clients: Vec<_> = cs.clients().copied().collect();
+        assert_eq!(clients, vec![Xid(3), Xid(1), Xid(2), Xid(4)]);
+        assert_eq!(cs.current_client(), Some(&Xid(1)));
+    }
+
+    #[test]
+    fn existing_clients_without_a_stacking_list_keep_the_topmost_focused() {
+        let wm = existing_clients_wm(vec![]);
+        let cs = &wm.state.client_set;
+
+        let clients: Vec<_> = cs.clients().copied().collect();
+        assert_eq!(clients, vec![Xid(4), Xid(3), Xid(2), Xid(1)]);
+        assert_eq!(cs.current_client(), Some(&Xid(4)));
+    }
+
+    #[test_case(false; "clients kept")]
+    #[test_case(true; "clients released")]
+    #[test]
+    fn exiting_runs_the_shutdown_hook_and_releases_grabs(release_clients: bool) {
+        let exit_key = KeyCode { mask: 0, code: 45 };
+        let wm = ScriptedWm::new()
+            .with_events([
+                XEvent::MapRequest(Xid(1)),
+                XEvent::KeyPress(exit_key),
+                XEvent::MapRequest(Xid(2)),
+            ])
+            .with_key(exit_key, exit())
+            .with_config(|c| {
+                c.release_clients_on_exit = release_clients;
+                c.compose_or_set_shutdown_hook(|s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                    record(s, "shutdown");
+                    Ok(())
+                });
+            })
+            .run();
+
+        assert_eq!(recorded(&wm), vec!["shutdown"]);
+        assert!(!wm.state.client_set.contains(&Xid(2)));
+
+        let requests = wm.x().requests();
+        let ix = requests
+            .iter()
+            .rposition(|r| *r == Request::Grab(vec![], vec![]))
+            .expect("grabs to be released");
+        let after = &requests[ix..];
+        assert_eq!(after.contains(&Request::Map(Xid(1))), release_clients);
+    }
+
+    #[test]
+    fn x_state_is_restored_after_a_panic() {
+        let mut wm = ScriptedWm::new().with_clients(2).run();
+        wm.state.client_set.move_client_to_tag(&Xid(1), "2");
+        let n = wm.x().requests().len();
+
+        wm.restore_x_after_panic();
+
+        let requests = wm.x().requests()[n..].to_vec();
+        let root = wm.x().root();
+        for r in [
+            Request::UngrabKeyboard,
+            Request::UngrabPointer,
+            Request::Grab(vec![], vec![]),
+            Request::Focus(root),
+            Request::Map(Xid(1)),
+            Request::Map(Xid(2)),
+        ] {
+            assert!(requests.contains(&r), "{r:?} not in {requests:?}");
+        }
+    }
+
+    #[test]
+    fn focused_accessors_work() {
+        let second = Rect::new(1000, 0, 800, 600);
+        let mut wm = ScriptedWm::new()
+            .with_conn(|conn| {
+                conn.set_screens(vec![SCREEN, second]);
+                conn
+            })
+            .with_clients(2)
+            .run();
+        wm.state.client_set.move_client_to_tag(&Xid(1), "2");
+        wm.state.client_set.move_client_to_tag(&Xid(2), "3");
+
+        let s = &wm.state;
+        assert_eq!(s.focused_tag(), "1");
+        assert_eq!(s.focused_client(), None);
+        assert_eq!(s.focused_screen_rect(), SCREEN);
+        assert_eq!(s.screen_of_client(Xid(1)).map(|s| s.index()), Some(1));
+        assert!(s.screen_of_client(Xid(2)).is_none());
+        assert!(s.screen_of_client(Xid(3)).is_none());
+    }
+
+    const KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+
+    #[test_case(0, Xid(2), 0x98971a, 0x282828; "rule client unfocused")]
+    #[test_case(1, Xid(1), 0xb8bb26, 0x3c3836; "rule client focused")]
+    #[test]
+    fn border_rules_override_theme_colors(presses: usize, focused: Xid, c1: u32, c2: u32) {
+        let class = Prop::UTF8String(vec!["emacs".to_owned(), "Emacs".to_owned()]);
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| conn.with_prop(Xid(1), Atom::WmClass.as_ref(), class))
+            .with_clients(2)
+            .with_events((0..presses).map(|_| XEvent::KeyPress(KEY)))
+            .with_key(KEY, modify_with(|cs| cs.focus_down()))
+            .with_config(|c| {
+                c.theme = Theme {
+                    normal_border: 0x3c3836ff.into(),
+                    focused_border: 0x282828ff.into(),
+                    ..Theme::default()
+                };
+                c.border_rules =
+                    vec![BorderRule::new(ClassName("Emacs"), 0x98971aff).focused(0xb8bb26ff)];
+            })
+            .run();
+
+        let last_border = |id: Xid| {
+            wm.x().requests().into_iter().rev().find_map(|r| match r {
+                Request::SetClientAttributes(w, attrs) if w == id => match attrs[..] {
+                    [ClientAttr::BorderColor(c)] => Some(c),
+                    _ => None,
+                },
+                _ => None,
+            })
+        };
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&focused));
+        assert_eq!(last_border(Xid(1)), Some(c1));
+        assert_eq!(last_border(Xid(2)), Some(c2));
+    }
+
+    #[test_case(1, "2", Some(Xid(2)); "sticky")]
+    #[test_case(2, "1", None; "toggled off")]
+    #[test]
+    fn sticky_clients_follow_workspace_switches(
+        presses: usize,
+        expected_tag: &str,
+        expected_focus: Option<Xid>,
+    ) {
+        const SWITCH_KEY: KeyCode = KeyCode { mask: 0, code: 43 };
+        let wm = ScriptedWm::new()
+            .with_clients(2)
+            .with_events((0..presses).map(|_| XEvent::KeyPress(KEY)))
+            .with_events([XEvent::KeyPress(SWITCH_KEY)])
+            .with_key(KEY, toggle_sticky())
+            .with_key(SWITCH_KEY, modify_with(|cs| cs.focus_tag("2")))
+            .run();
+
+        let cs = &wm.state.client_set;
+        assert_eq!(cs.tag_for_client(&Xid(2)), Some(expected_tag));
+        assert_eq!(cs.tag_for_client(&Xid(1)), Some("1"));
+        assert_eq!(wm.state.is_sticky(Xid(2)), presses == 1);
+        assert_eq!(cs.current_client(), expected_focus.as_ref());
+    }
 }
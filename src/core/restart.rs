@@ -0,0 +1,313 @@
+//! Restarting the window manager in place without losing track of where clients were.
+//!
+//! When the `restart` feature is enabled, [WindowManager::restart][0] (or the
+//! [restart][1] action) will save the current layout of clients on each workspace to a
+//! file, `exec` the current binary and then restore that layout on startup rather than
+//! re-managing existing clients from scratch. This allows you to recompile your config and
+//! pick up the changes without all of your windows being shuffled around.
+//!
+//! The saved state is passed to the new process using the [RESTART_STATE_ENV] environment
+//! variable and the file is removed once it has been read. Clients are still run through
+//! your manage hooks as they are picked up by the new process (so that extensions are able
+//! to track them again) before their saved position is restored.
+//!
+//!   [0]: crate::core::WindowManager::restart
+//!   [1]: crate::builtin::actions::restart
+use crate::{
+    core::{ClientSet, Xid},
+    pure::{geometry::RelativeRect, Stack},
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The environment variable used to pass the path of the saved state file to the new
+/// window manager process when restarting.
+pub const RESTART_STATE_ENV: &str = "PENROSE_RESTART_STATE";
+
+/// The saved state of a single workspace.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedWorkspace {
+    /// The tag of the workspace
+    pub tag: String,
+    /// The name of the active layout
+    pub layout: String,
+    /// The clients on the workspace in stack order
+    pub clients: Vec<Xid>,
+    /// The focused client, if there was one
+    pub focus: Option<Xid>,
+}
+
+/// The pure window manager state that is preserved across a restart.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SavedState {
+    /// The state of each workspace
+    pub workspaces: Vec<SavedWorkspace>,
+    /// The tag visible on each screen, ordered by screen index
+    pub screens: Vec<String>,
+    /// The index of the focused screen
+    pub focused_screen: usize,
+    /// Floating clients and their positions
+    pub floating: Vec<(Xid, RelativeRect)>,
+    /// Fullscreen clients and their floating position before going fullscreen
+    pub fullscreen: Vec<(Xid, Option<RelativeRect>)>,
+    /// The previously focused tag
+    pub previous_tag: String,
+}
+
+impl SavedState {
+    /// Capture the current state of the given [ClientSet].
+    pub fn from_client_set(cs: &ClientSet) -> Self {
+        let workspaces = cs
+            .workspaces()
+            .map(|w| SavedWorkspace {
+                tag: w.tag.clone(),
+                layout: w.layout_name(),
+                clients: w.clients().copied().collect(),
+                focus: w.focus().copied(),
+            })
+            .collect();
+
+        let mut screens: Vec<_> = cs.screens().collect();
+        screens.sort_by_key(|s| s.index);
+
+        Self {
+            workspaces,
+            screens: screens.iter().map(|s| s.workspace.tag.clone()).collect(),
+            focused_screen: cs.current_screen().index,
+            floating: cs.floating.iter().map(|(&id, &r)| (id, r)).collect(),
+            fullscreen: cs.fullscreen.iter().map(|(&id, &r)| (id, r)).collect(),
+            previous_tag: cs.previous_tag.clone(),
+        }
+    }
+
+    /// The tag of the workspace that the given client was on, if it is known.
+    pub fn tag_for(&self, id: Xid) -> Option<&str> {
+        self.workspaces
+            .iter()
+            .find(|w| w.clients.contains(&id))
+            .map(|w| w.tag.as_str())
+    }
+
+    /// Restore the saved state for any clients that are currently managed in the given
+    /// [ClientSet]. Clients that are not part of the saved state are left where they are,
+    /// after any restored clients on the same workspace.
+    pub fn restore(&self, cs: &mut ClientSet) {
+        for w in self.workspaces.iter() {
+            if !cs.contains_tag(&w.tag) {
+                continue;
+            }
+
+            for id in w.clients.iter() {
+                if cs.contains(id) && cs.tag_for_client(id) != Some(&w.tag) {
+                    cs.move_client_to_tag(id, &w.tag);
+                }
+            }
+
+            let ws = match cs.workspace_mut(&w.tag) {
+                Some(ws) => ws,
+                None => continue,
+            };
+
+            let current: Vec<Xid> = ws.clients().copied().collect();
+            let mut order: Vec<Xid> = w
+                .clients
+                .iter()
+                .filter(|id| current.contains(id))
+                .copied()
+                .collect();
+            let extra: Vec<Xid> = current
+                .into_iter()
+                .filter(|id| !order.contains(id))
+                .collect();
+            order.extend(extra);
+
+            if !order.is_empty() {
+                let i = w
+                    .focus
+                    .and_then(|f| order.iter().position(|&id| id == f))
+                    .unwrap_or(0);
+                let down = order.split_off(i + 1);
+                let focus = order.pop().expect("at least one element");
+                ws.stack = Some(Stack::new(order, focus, down));
+            }
+
+            for _ in 0..ws.layouts.len() {
+                if ws.layout_name() == w.layout {
+                    break;
+                }
+                ws.next_layout();
+            }
+        }
+
+        let restored: Vec<Xid> = self
+            .workspaces
+            .iter()
+            .flat_map(|w| w.clients.iter())
+            .filter(|id| cs.contains(id))
+            .copied()
+            .collect();
+
+        for id in restored.iter() {
+            cs.floating.remove(id);
+            cs.fullscreen.remove(id);
+        }
+        for (id, r) in self.floating.iter().filter(|(id, _)| restored.contains(id)) {
+            cs.floating.insert(*id, *r);
+        }
+        for (id, r) in self
+            .fullscreen
+            .iter()
+            .filter(|(id, _)| restored.contains(id))
+        {
+            cs.fullscreen.insert(*id, *r);
+        }
+
+        let n_screens = cs.screens().count();
+        for (i, tag) in self.screens.iter().enumerate().take(n_screens) {
+            if cs.contains_tag(tag) {
+                cs.focus_screen(i);
+                cs.pull_tag_to_screen(tag);
+            }
+        }
+        cs.focus_screen(self.focused_screen.min(n_screens - 1));
+
+        if cs.contains_tag(&self.previous_tag) {
+            cs.previous_tag = self.previous_tag.clone();
+        }
+    }
+}
+
+#[cfg(feature = "restart")]
+mod handoff {
+    use super::{SavedState, RESTART_STATE_ENV};
+    use crate::{Error, Result};
+    use std::{env, fs, path::Path};
+    use tracing::{info, warn};
+
+    impl SavedState {
+        /// Write this state to the given file as JSON.
+        pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+            let s = serde_json::to_string(self).map_err(|e| Error::Custom(e.to_string()))?;
+
+            Ok(fs::write(path, s)?)
+        }
+
+        /// Read a state previously written using [SavedState::write].
+        pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+            let s = fs::read_to_string(path)?;
+
+            serde_json::from_str(&s).map_err(|e| Error::Custom(e.to_string()))
+        }
+    }
+
+    // Take the state handed off by the window manager process that exec'd us, if there is one.
+    pub(crate) fn take_saved_state() -> Option<SavedState> {
+        let path = env::var_os(RESTART_STATE_ENV)?;
+        env::remove_var(RESTART_STATE_ENV);
+
+        let res = SavedState::read(&path);
+        if let Err(e) = fs::remove_file(&path) {
+            warn!(%e, "unable to remove saved restart state");
+        }
+
+        match res {
+            Ok(state) => {
+                info!("restoring state from previous window manager process");
+                Some(state)
+            }
+            Err(e) => {
+                warn!(%e, "unable to read saved restart state");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(feature = "restart")]
+pub(crate) use handoff::take_saved_state;
+
+#[cfg(not(feature = "restart"))]
+pub(crate) fn take_saved_state() -> Option<SavedState> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::layout::{MainAndStack, Monocle},
+        core::layout::Layout,
+        pure::geometry::Rect,
+        stack,
+    };
+
+    fn client_set() -> ClientSet {
+        let layouts = stack!(
+            Box::new(MainAndStack::default()) as Box<dyn Layout>,
+            Monocle::boxed()
+        );
+        let screens = vec![Rect::new(0, 0, 100, 100), Rect::new(100, 0, 100, 100)];
+
+        ClientSet::try_new(layouts, ["1", "2", "3"], screens).unwrap()
+    }
+
+    #[test]
+    fn saved_state_is_restored() {
+        let mut cs = client_set();
+        for id in [1, 2, 3] {
+            cs.insert_as_focus_for("1", Xid(id));
+        }
+        cs.insert_as_focus_for("3", Xid(4));
+        cs.focus_tag("3");
+        cs.next_layout();
+        cs.float_unchecked(Xid(2), RelativeRect::new(0.1, 0.1, 0.5, 0.5));
+        cs.focus_screen(0);
+        cs.focus_client(&Xid(2));
+
+        let saved = SavedState::from_client_set(&cs);
+
+        // A fresh process will have picked the clients up in whatever order X reports them
+        let mut restored = client_set();
+        for id in [4, 1, 3, 2, 5] {
+            restored.insert_as_focus_for("2", Xid(id));
+        }
+        restored.float_unchecked(Xid(4), RelativeRect::new(0.2, 0.2, 0.5, 0.5));
+        saved.restore(&mut restored);
+
+        assert_eq!(
+            SavedState::from_client_set(&restored).screens,
+            saved.screens
+        );
+        assert_eq!(restored.current_tag(), cs.current_tag());
+        assert_eq!(restored.current_client(), Some(&Xid(2)));
+        for tag in ["1", "3"] {
+            let (a, b) = (cs.workspace(tag).unwrap(), restored.workspace(tag).unwrap());
+            assert_eq!(
+                a.clients().collect::<Vec<_>>(),
+                b.clients().collect::<Vec<_>>()
+            );
+            assert_eq!(a.focus(), b.focus());
+            assert_eq!(a.layout_name(), b.layout_name());
+        }
+        // Unknown clients are left where they were
+        assert_eq!(restored.tag_for_client(&Xid(5)), Some("2"));
+        assert_eq!(restored.floating, cs.floating);
+    }
+
+    #[cfg(feature = "restart")]
+    #[test]
+    fn saved_state_round_trips_through_a_file() {
+        let mut cs = client_set();
+        cs.insert_as_focus_for("2", Xid(1));
+        let saved = SavedState::from_client_set(&cs);
+
+        let path = std::env::temp_dir().join(format!("penrose-test-{}.json", std::process::id()));
+        saved.write(&path).unwrap();
+        let read = SavedState::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read, saved);
+    }
+}
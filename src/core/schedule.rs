@@ -0,0 +1,277 @@
+//! Running [StateHook]s on a timer from the main event loop.
+//!
+//! Hooks can be scheduled to run once after a delay using [State::schedule_once] or
+//! repeatedly using [State::schedule_every]. Scheduled hooks are run by the window manager
+//! in between handling events so (unlike spawning a thread) they have full access to the
+//! current [State] and [XConn]:
+//!
+//! ```no_run
+//! # use penrose::{core::State, x::{XConn, XConnExt}, Result};
+//! # use std::time::Duration;
+//! fn startup_hook<X: XConn + 'static>(state: &mut State<X>, _: &X) -> Result<()> {
+//!     state.schedule_every(Duration::from_secs(60), |state: &mut State<X>, x: &X| {
+//!         // Check for clients that need attention, update a status file...
+//!         x.refresh(state)
+//!     });
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Scheduled hooks are not run while the window manager is busy handling an event, so the
+//! time at which a hook runs should be treated as a lower bound rather than an exact time.
+//! If you modify the [State] from a scheduled hook you will need to call
+//! [XConnExt::refresh][crate::x::XConnExt::refresh] yourself in order for your changes to
+//! be reflected on screen.
+//!
+//! Waiting for a scheduled hook relies on [Backend::next_event_or_wakeup][0] (which by
+//! default uses [Backend::next_event_before][1]) so hooks will only be run on time if the
+//! [XConn] you are using supports it.
+//!
+//!   [0]: crate::backend::Backend::next_event_or_wakeup
+//!   [1]: crate::backend::Backend::next_event_before
+//!   [State]: crate::core::State
+//!   [State::schedule_once]: crate::core::State::schedule_once
+//!   [State::schedule_every]: crate::core::State::schedule_every
+//!   [XConn]: crate::x::XConn
+use crate::{core::hooks::StateHook, x::XConn};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// A unique identifier for a hook scheduled with [State::schedule_once] or
+/// [State::schedule_every], allowing it to be cancelled using [State::cancel_scheduled].
+///
+///   [State::schedule_once]: crate::core::State::schedule_once
+///   [State::schedule_every]: crate::core::State::schedule_every
+///   [State::cancel_scheduled]: crate::core::State::cancel_scheduled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScheduleId(u64);
+
+pub(crate) struct Scheduled<X>
+where
+    X: XConn,
+{
+    pub(crate) id: ScheduleId,
+    pub(crate) deadline: Instant,
+    pub(crate) interval: Option<Duration>,
+    pub(crate) hook: Box<dyn StateHook<X>>,
+}
+
+impl<X: XConn> fmt::Debug for Scheduled<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduled")
+            .field("id", &self.id)
+            .field("deadline", &self.deadline)
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+/// The hooks that are currently scheduled to run.
+#[derive(Debug)]
+pub(crate) struct Scheduler<X>
+where
+    X: XConn,
+{
+    next_id: u64,
+    entries: Vec<Scheduled<X>>,
+    // The hook currently being run (if any) and whether it was cancelled while running
+    running: Option<(ScheduleId, bool)>,
+}
+
+impl<X: XConn> Default for Scheduler<X> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            entries: Vec::new(),
+            running: None,
+        }
+    }
+}
+
+impl<X> Scheduler<X>
+where
+    X: XConn,
+{
+    pub(crate) fn add(
+        &mut self,
+        deadline: Instant,
+        interval: Option<Duration>,
+        hook: Box<dyn StateHook<X>>,
+    ) -> ScheduleId {
+        let id = ScheduleId(self.next_id);
+        self.next_id += 1;
+        self.entries.push(Scheduled {
+            id,
+            deadline,
+            interval,
+            hook,
+        });
+
+        id
+    }
+
+    pub(crate) fn cancel(&mut self, id: ScheduleId) {
+        self.entries.retain(|s| s.id != id);
+        if let Some((running, cancelled)) = self.running.as_mut() {
+            if *running == id {
+                *cancelled = true;
+            }
+        }
+    }
+
+    pub(crate) fn is_scheduled(&self, id: ScheduleId) -> bool {
+        self.entries.iter().any(|s| s.id == id)
+            || matches!(self.running, Some((running, false)) if running == id)
+    }
+
+    /// The earliest deadline of any scheduled hook
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.entries.iter().map(|s| s.deadline).min()
+    }
+
+    /// The IDs of hooks that are due to run at `now`, in the order they should be run.
+    pub(crate) fn due(&self, now: Instant) -> Vec<ScheduleId> {
+        let mut due: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|s| s.deadline <= now)
+            .map(|s| (s.deadline, s.id))
+            .collect();
+        due.sort();
+
+        due.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Remove a hook in order to run it. The hook should be passed back to
+    /// [Scheduler::finish] once it has been run.
+    pub(crate) fn take(&mut self, id: ScheduleId) -> Option<Scheduled<X>> {
+        let ix = self.entries.iter().position(|s| s.id == id)?;
+        self.running = Some((id, false));
+
+        Some(self.entries.remove(ix))
+    }
+
+    /// Reschedule a hook that has been run if it repeats and was not cancelled while it was
+    /// running. Repeating hooks that have fallen more than one interval behind are rescheduled
+    /// relative to `now` rather than running repeatedly to catch up.
+    pub(crate) fn finish(&mut self, mut s: Scheduled<X>, now: Instant) {
+        let cancelled = matches!(self.running.take(), Some((id, true)) if id == s.id);
+
+        if let (Some(interval), false) = (s.interval, cancelled) {
+            let next = s.deadline + interval;
+            s.deadline = if next <= now { now + interval } else { next };
+            self.entries.push(s);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::State,
+        x::{
+            fixtures::{record, recorded, ScriptedWm},
+            mock::ScriptedConn,
+        },
+        Result,
+    };
+
+    fn noop(_: &mut State<ScriptedConn>, _: &ScriptedConn) -> Result<()> {
+        Ok(())
+    }
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    #[test]
+    fn due_hooks_are_returned_in_deadline_order() {
+        let now = Instant::now();
+        let mut s: Scheduler<ScriptedConn> = Scheduler::default();
+        let a = s.add(now + secs(2), None, Box::new(noop));
+        let b = s.add(now + secs(1), None, Box::new(noop));
+        s.add(now + secs(5), None, Box::new(noop));
+
+        assert_eq!(s.due(now), vec![]);
+        assert_eq!(s.due(now + secs(3)), vec![b, a]);
+        assert_eq!(s.next_deadline(), Some(now + secs(1)));
+    }
+
+    #[test]
+    fn one_shot_hooks_are_not_rescheduled() {
+        let now = Instant::now();
+        let mut s: Scheduler<ScriptedConn> = Scheduler::default();
+        let id = s.add(now, None, Box::new(noop));
+
+        let entry = s.take(id).unwrap();
+        s.finish(entry, now);
+
+        assert!(!s.is_scheduled(id));
+        assert_eq!(s.next_deadline(), None);
+    }
+
+    #[test]
+    fn repeating_hooks_are_rescheduled() {
+        let now = Instant::now();
+        let mut s: Scheduler<ScriptedConn> = Scheduler::default();
+        let id = s.add(now, Some(secs(5)), Box::new(noop));
+
+        let entry = s.take(id).unwrap();
+        s.finish(entry, now + secs(1));
+
+        assert!(s.is_scheduled(id));
+        assert_eq!(s.next_deadline(), Some(now + secs(5)));
+    }
+
+    #[test]
+    fn repeating_hooks_that_fall_behind_do_not_catch_up() {
+        let now = Instant::now();
+        let mut s: Scheduler<ScriptedConn> = Scheduler::default();
+        let id = s.add(now, Some(secs(5)), Box::new(noop));
+
+        let entry = s.take(id).unwrap();
+        s.finish(entry, now + secs(12));
+
+        assert_eq!(s.next_deadline(), Some(now + secs(17)));
+    }
+
+    #[test]
+    fn hooks_cancelled_while_running_are_not_rescheduled() {
+        let now = Instant::now();
+        let mut s: Scheduler<ScriptedConn> = Scheduler::default();
+        let id = s.add(now, Some(secs(5)), Box::new(noop));
+
+        let entry = s.take(id).unwrap();
+        s.cancel(id);
+        s.finish(entry, now);
+
+        assert!(!s.is_scheduled(id));
+    }
+
+    #[test]
+    fn scheduled_hooks_are_run() {
+        let mut wm = ScriptedWm::new().with_clients(2).build();
+
+        let run = |label: &'static str| {
+            move |s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+                record(s, label);
+                Ok(())
+            }
+        };
+        let once = wm.state.schedule_once(Duration::ZERO, run("once"));
+        let every = wm.state.schedule_every(Duration::ZERO, run("every"));
+        let cancelled = wm.state.schedule_once(Duration::ZERO, run("cancelled"));
+        let later = wm.state.schedule_once(secs(60), run("later"));
+        wm.state.cancel_scheduled(cancelled);
+        let wm = wm.run_script().unwrap();
+
+        assert_eq!(recorded(&wm), vec!["once", "every", "every", "every"]);
+        assert!(!wm.state.is_scheduled(once));
+        assert!(wm.state.is_scheduled(every));
+        assert!(wm.state.is_scheduled(later));
+    }
+}
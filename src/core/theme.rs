@@ -0,0 +1,177 @@
+//! Bundled appearance settings for the window manager and its decorations.
+use crate::{
+    builtin::layout::transformers::Gaps,
+    core::{
+        layout::{IntoMessage, Layout},
+        titlebar::TitlebarConfig,
+    },
+    Color,
+};
+
+fn hex(s: &str) -> Color {
+    s.try_into().expect("valid hex code")
+}
+
+/// The colors, border width and gap sizes used when drawing clients and decorations.
+///
+/// The active theme is available as [Config::theme][crate::core::Config::theme] and can be
+/// swapped out at runtime using [State::set_theme][crate::core::State::set_theme]. Layouts and
+/// decorations that want to follow the active theme can handle it as a layout
+/// [Message][crate::core::layout::Message]: it is broadcast to all layouts whenever it changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// The color to use for normal (unfocused) window borders
+    pub normal_border: Color,
+    /// The color to use for the focused window border
+    pub focused_border: Color,
+    /// The color to use for the border of clients demanding attention
+    pub urgent_border: Color,
+    /// The width in pixels to use for drawing window borders
+    pub border_width: u32,
+    /// The gap in pixels to leave around the edge of the screen
+    pub outer_gap: u32,
+    /// The gap in pixels to leave around each client
+    pub inner_gap: u32,
+    /// The foreground (text) color for bars and titlebars
+    pub bar_fg: Color,
+    /// The background color for bars and titlebars
+    pub bar_bg: Color,
+    /// The color used to highlight active elements of bars and titlebars
+    pub bar_highlight: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::gruvbox_dark()
+    }
+}
+
+impl Theme {
+    /// A dark theme based on the gruvbox color scheme. This is the default theme.
+    pub fn gruvbox_dark() -> Self {
+        Self {
+            normal_border: hex("#3c3836"),
+            focused_border: hex("#cc241d"),
+            urgent_border: hex("#d79921"),
+            border_width: 2,
+            outer_gap: 0,
+            inner_gap: 0,
+            bar_fg: hex("#ebdbb2"),
+            bar_bg: hex("#282828"),
+            bar_highlight: hex("#458588"),
+        }
+    }
+
+    /// A light theme based on the gruvbox color scheme.
+    pub fn gruvbox_light() -> Self {
+        Self {
+            normal_border: hex("#d5c4a1"),
+            focused_border: hex("#af3a03"),
+            urgent_border: hex("#b57614"),
+            border_width: 2,
+            outer_gap: 0,
+            inner_gap: 0,
+            bar_fg: hex("#3c3836"),
+            bar_bg: hex("#fbf1c7"),
+            bar_highlight: hex("#076678"),
+        }
+    }
+
+    /// A dark theme based on the nord color scheme.
+    pub fn nord() -> Self {
+        Self {
+            normal_border: hex("#3b4252"),
+            focused_border: hex("#88c0d0"),
+            urgent_border: hex("#bf616a"),
+            border_width: 2,
+            outer_gap: 0,
+            inner_gap: 0,
+            bar_fg: hex("#eceff4"),
+            bar_bg: hex("#2e3440"),
+            bar_highlight: hex("#5e81ac"),
+        }
+    }
+
+    /// Set the border width of this theme.
+    pub fn with_border_width(mut self, px: u32) -> Self {
+        self.border_width = px;
+        self
+    }
+
+    /// Set the outer and inner gap sizes of this theme.
+    pub fn with_gaps(mut self, outer_px: u32, inner_px: u32) -> Self {
+        self.outer_gap = outer_px;
+        self.inner_gap = inner_px;
+        self
+    }
+
+    /// Wrap an existing [Layout] with [Gaps] using the gap sizes of this theme.
+    ///
+    /// The gap sizes will be kept up to date if the theme is changed using
+    /// [State::set_theme][crate::core::State::set_theme].
+    pub fn gaps(&self, layout: Box<dyn Layout>) -> Box<dyn Layout> {
+        Gaps::wrap(layout, self.outer_gap, self.inner_gap)
+    }
+
+    /// A [TitlebarConfig] using the colors of this theme.
+    pub fn titlebar(&self) -> TitlebarConfig {
+        TitlebarConfig {
+            focused_fg: self.bar_fg,
+            focused_bg: self.focused_border,
+            normal_fg: self.bar_fg,
+            normal_bg: self.normal_border,
+            ..Default::default()
+        }
+    }
+}
+
+impl IntoMessage for Theme {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::{actions::key_handler, layout::Monocle},
+        core::{bindings::KeyCode, Xid},
+        pure::geometry::Rect,
+        stack,
+        x::{
+            fixtures::{ScriptedWm, SCREEN},
+            mock::{Request, ScriptedConn},
+            ClientAttr, XConnExt, XEvent,
+        },
+    };
+
+    const KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+
+    #[test]
+    fn setting_a_theme_updates_borders_gaps_and_titlebars() {
+        let wm = ScriptedWm::new()
+            .with_clients(1)
+            .with_events([XEvent::KeyPress(KEY)])
+            .with_key(
+                KEY,
+                key_handler(|state, x: &ScriptedConn| {
+                    state.set_theme(Theme::nord().with_gaps(10, 5));
+                    x.refresh(state)
+                }),
+            )
+            .with_config(|c| {
+                c.default_layouts = stack!(Theme::default().gaps(Monocle::boxed()));
+                c.titlebar = Some(Default::default());
+            })
+            .run();
+
+        let nord = Theme::nord();
+        let titlebar = wm.state.config.titlebar.as_ref().unwrap();
+        assert_eq!(titlebar.focused_bg, nord.focused_border);
+        assert_eq!(wm.state.config.theme.outer_gap, 10);
+
+        let requests = wm.x().requests();
+        let color = ClientAttr::BorderColor(nord.focused_border.rgb_u32());
+        assert!(requests.contains(&Request::SetClientAttributes(Xid(1), vec![color])));
+
+        let gapped = Rect::new(15, 15, SCREEN.w - 30, SCREEN.h - 30);
+        assert_eq!(wm.state.diff.after.positions, vec![(Xid(1), gapped)]);
+    }
+}
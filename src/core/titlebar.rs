@@ -0,0 +1,129 @@
+//! Titlebar decorations for clients that have been reparented into frame windows.
+use crate::{pure::geometry::Rect, Color};
+
+/// Configuration for the titlebars drawn on framed clients.
+///
+/// Titlebars are only drawn when [Config::reparent_clients][0] is enabled.
+///
+///   [0]: crate::core::Config::reparent_clients
+#[derive(Debug, Clone, PartialEq)]
+pub struct TitlebarConfig {
+    /// The height of each titlebar in pixels
+    pub height: u32,
+    /// The text color to use for the focused client
+    pub focused_fg: Color,
+    /// The background color to use for the focused client
+    pub focused_bg: Color,
+    /// The text color to use for unfocused clients
+    pub normal_fg: Color,
+    /// The background color to use for unfocused clients
+    pub normal_bg: Color,
+    /// Whether or not to draw a close button on the right hand side of each titlebar
+    pub close_button: bool,
+    /// The names of the layouts that titlebars should be shown for.
+    ///
+    /// If this is empty then titlebars are shown for all layouts.
+    pub layouts: Vec<String>,
+}
+
+impl Default for TitlebarConfig {
+    fn default() -> Self {
+        Self {
+            height: 18,
+            focused_fg: "#ebdbb2".try_into().expect("valid hex code"),
+            focused_bg: "#cc241d".try_into().expect("valid hex code"),
+            normal_fg: "#a89984".try_into().expect("valid hex code"),
+            normal_bg: "#3c3836".try_into().expect("valid hex code"),
+            close_button: true,
+            layouts: vec![],
+        }
+    }
+}
+
+impl TitlebarConfig {
+    /// Whether or not titlebars should be shown for clients on a workspace using the
+    /// layout with the given name.
+    pub fn shown_for_layout(&self, layout: &str) -> bool {
+        self.layouts.is_empty() || self.layouts.iter().any(|l| l == layout)
+    }
+
+    /// The region occupied by the close button for a titlebar of width `w`, relative to
+    /// the frame containing it.
+    ///
+    /// Returns `None` if close buttons are disabled or the titlebar is too narrow to
+    /// contain one.
+    pub fn close_button_region(&self, w: u32) -> Option<Rect> {
+        if !self.close_button || w < self.height {
+            return None;
+        }
+
+        Some(Rect::new(w - self.height, 0, self.height, self.height))
+    }
+
+    /// Build the [Titlebar] to draw for a client.
+    pub fn titlebar(&self, title: String, w: u32, focused: bool) -> Titlebar {
+        let (fg, bg) = if focused {
+            (self.focused_fg, self.focused_bg)
+        } else {
+            (self.normal_fg, self.normal_bg)
+        };
+
+        Titlebar {
+            title,
+            w,
+            h: self.height,
+            fg,
+            bg,
+            close_button: self.close_button_region(w),
+        }
+    }
+}
+
+/// Everything required to draw the titlebar for a single client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Titlebar {
+    /// The window title to display
+    pub title: String,
+    /// The width of the titlebar in pixels
+    pub w: u32,
+    /// The height of the titlebar in pixels
+    pub h: u32,
+    /// The text color
+    pub fg: Color,
+    /// The background color
+    pub bg: Color,
+    /// The region to draw a close button in, if there is one
+    pub close_button: Option<Rect>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test_case(&[], "Tall", true; "no layouts shows for all")]
+    #[test_case(&["Mono"], "Mono", true; "listed layout")]
+    #[test_case(&["Mono"], "Tall", false; "unlisted layout")]
+    #[test]
+    fn shown_for_layout(layouts: &[&str], layout: &str, expected: bool) {
+        let conf = TitlebarConfig {
+            layouts: layouts.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+
+        assert_eq!(conf.shown_for_layout(layout), expected);
+    }
+
+    #[test_case(true, 100, Some(Rect::new(82, 0, 18, 18)); "enabled")]
+    #[test_case(false, 100, None; "disabled")]
+    #[test_case(true, 10, None; "too narrow")]
+    #[test]
+    fn close_button_region(close_button: bool, w: u32, expected: Option<Rect>) {
+        let conf = TitlebarConfig {
+            close_button,
+            ..Default::default()
+        };
+
+        assert_eq!(conf.close_button_region(w), expected);
+    }
+}
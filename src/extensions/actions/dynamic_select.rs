@@ -1,4 +1,4 @@
-//! Dynamic selection based actions using Dmenu.
+//! Dynamic selection based actions using Dmenu (or any other launcher supported by [DMenu]).
 use crate::{
     builtin::actions::key_handler,
     core::{bindings::KeyEventHandler, State},
@@ -25,7 +25,9 @@ pub fn dmenu_focus_client<X: XConn>(config: DMenuConfig) -> Box<dyn KeyEventHand
             })
             .collect();
 
-        let menu = DMenu::new("Window:", choices.keys().collect(), config);
+        let mut titles: Vec<&String> = choices.keys().collect();
+        titles.sort();
+        let menu = DMenu::new("Window:", titles, config);
         let screen = state.client_set.current_screen().index();
 
         if let MenuMatch::Line(_, s) = menu.run(screen)? {
@@ -54,3 +56,39 @@ pub fn dmenu_focus_tag<X: XConn>(config: DMenuConfig) -> Box<dyn KeyEventHandler
         Ok(())
     })
 }
+
+/// Use [DMenu] to dynamically select a workspace and move the focused client to it.
+pub fn dmenu_move_client_to_tag<X: XConn>(config: DMenuConfig) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        if state.client_set.current_client().is_none() {
+            return Ok(());
+        }
+
+        let choices = state.client_set.ordered_tags();
+        let menu = DMenu::new("Move to:", choices, config);
+        let screen = state.client_set.current_screen().index();
+
+        if let MenuMatch::Line(_, tag) = menu.run(screen)? {
+            x.modify_and_refresh(state, |cs| cs.move_focused_to_tag(&tag))?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Use [DMenu] to dynamically select a layout by name for the current workspace.
+pub fn dmenu_focus_layout<X: XConn>(config: DMenuConfig) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let choices = state.client_set.current_workspace().layout_names();
+        let menu = DMenu::new("Layout:", choices, config);
+        let screen = state.client_set.current_screen().index();
+
+        if let MenuMatch::Line(_, name) = menu.run(screen)? {
+            x.modify_and_refresh(state, |cs| {
+                cs.current_workspace_mut().focus_layout(&name);
+            })?;
+        }
+
+        Ok(())
+    })
+}
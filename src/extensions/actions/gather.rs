@@ -0,0 +1,106 @@
+//! Actions for pulling clients together from across all workspaces.
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, State},
+    extensions::actions::matching_clients,
+    x::{Query, XConn, XConnExt},
+    Result,
+};
+use tracing::debug;
+
+/// Move every managed client matching the given [Query] to the current workspace.
+///
+/// ```no_run
+/// # use penrose::extensions::actions::gather_matching;
+/// # use penrose::x::query::ClassName;
+/// # use penrose::x11rb::RustConn;
+/// # use penrose::core::bindings::KeyEventHandler;
+/// let handler: Box<dyn KeyEventHandler<RustConn>> = gather_matching(ClassName("firefox"));
+/// ```
+pub fn gather_matching<X, Q>(query: Q) -> Box<dyn KeyEventHandler<X>>
+where
+    X: XConn,
+    Q: Query<X> + 'static,
+{
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let tag = state.client_set.current_tag().to_owned();
+
+        gather(&query, &tag, state, x)
+    })
+}
+
+/// Move every managed client matching the given [Query] to the workspace with the given tag.
+///
+/// Does nothing if there is no workspace with the given tag.
+pub fn gather_matching_to<X, Q>(query: Q, tag: impl Into<String>) -> Box<dyn KeyEventHandler<X>>
+where
+    X: XConn,
+    Q: Query<X> + 'static,
+{
+    let tag = tag.into();
+
+    key_handler(move |state: &mut State<X>, x: &X| gather(&query, &tag, state, x))
+}
+
+fn gather<X, Q>(query: &Q, tag: &str, state: &mut State<X>, x: &X) -> Result<()>
+where
+    X: XConn,
+    Q: Query<X>,
+{
+    if !state.client_set.contains_tag(tag) {
+        return Ok(());
+    }
+
+    let ids: Vec<_> = matching_clients(query, state, x)?
+        .into_iter()
+        .filter(|id| state.client_set.tag_for_client(id) != Some(tag))
+        .collect();
+
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    debug!(?ids, %tag, "gathering matching clients");
+    x.modify_and_refresh(state, |cs| {
+        for id in ids.iter() {
+            cs.move_client_to_tag(id, tag);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        x::{
+            atom::Atom, fixtures::ScriptedWm, mock::ScriptedConn, property::Prop, query::ClassName,
+        },
+        Xid,
+    };
+    use simple_test_case::test_case;
+
+    #[test_case(None, "2"; "current workspace")]
+    #[test_case(Some("3"), "3"; "named workspace")]
+    #[test]
+    fn matching_clients_are_gathered(tag: Option<&str>, expected: &str) {
+        let mut wm = ScriptedWm::new().with_clients(2).run();
+        wm.state.client_set.focus_tag("2");
+        wm.state.client_set.insert(Xid(3));
+
+        let class = Prop::UTF8String(vec!["Navigator".to_owned(), "firefox".to_owned()]);
+        let x = &ScriptedConn::new(vec![])
+            .with_prop(Xid(1), Atom::WmClass.as_ref(), class.clone())
+            .with_prop(Xid(3), Atom::WmClass.as_ref(), class);
+
+        let mut handler = match tag {
+            Some(tag) => gather_matching_to(ClassName("firefox"), tag),
+            None => gather_matching(ClassName("firefox")),
+        };
+        handler.call(&mut wm.state, x).unwrap();
+
+        let cs = &wm.state.client_set;
+        assert_eq!(cs.tag_for_client(&Xid(1)), Some(expected));
+        assert_eq!(cs.tag_for_client(&Xid(2)), Some("1"));
+        assert_eq!(cs.tag_for_client(&Xid(3)), Some(expected));
+    }
+}
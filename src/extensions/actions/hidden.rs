@@ -0,0 +1,124 @@
+//! Hiding (iconifying) clients and restoring them later.
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, State},
+    custom_error,
+    extensions::util::dmenu::{DMenu, DMenuConfig, MenuMatch},
+    x::{XConn, XConnExt},
+    Result, Xid,
+};
+use std::collections::HashMap;
+
+/// The tag used for the invisible workspace holding hidden clients.
+pub const HIDDEN_TAG: &str = "HIDDEN";
+
+/// The clients that are currently hidden, most recently hidden first.
+pub fn hidden_clients<X: XConn>(state: &State<X>) -> Vec<Xid> {
+    match state.client_set.workspace(HIDDEN_TAG) {
+        Some(w) => w.clients().copied().collect(),
+        None => vec![],
+    }
+}
+
+/// Hide the given client, unmapping it and setting its ICCCM WM_STATE to Iconic until it is
+/// restored using [restore_hidden_client].
+pub fn hide_client<X: XConn>(id: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+    if !state.client_set.contains(&id) {
+        return Ok(());
+    }
+
+    if !state.client_set.contains_tag(HIDDEN_TAG) {
+        state.client_set.add_invisible_workspace(HIDDEN_TAG)?;
+    }
+
+    x.modify_and_refresh(state, |cs| cs.move_client_to_tag(&id, HIDDEN_TAG))
+}
+
+/// Restore a hidden client to the current workspace and focus it.
+pub fn restore_hidden_client<X: XConn>(id: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+    if state.client_set.tag_for_client(&id) != Some(HIDDEN_TAG) {
+        return Ok(());
+    }
+
+    x.modify_and_refresh(state, |cs| {
+        cs.move_client_to_current_tag(&id);
+        cs.focus_client(&id);
+    })
+}
+
+/// Hide the currently focused client.
+///
+/// See [hide_client] for details.
+pub fn hide_focused<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(
+        |state: &mut State<X>, x: &X| match state.client_set.current_client() {
+            Some(&id) => hide_client(id, state, x),
+            None => Ok(()),
+        },
+    )
+}
+
+/// Restore the most recently hidden client to the current workspace.
+pub fn restore_last_hidden<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(
+        |state: &mut State<X>, x: &X| match hidden_clients(state).first() {
+            Some(&id) => restore_hidden_client(id, state, x),
+            None => Ok(()),
+        },
+    )
+}
+
+/// Use [DMenu] to select a hidden client by title and restore it to the current workspace.
+pub fn dmenu_restore_hidden<X: XConn>(config: DMenuConfig) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let hidden = hidden_clients(state);
+        if hidden.is_empty() {
+            return Ok(());
+        }
+
+        let mut choices: HashMap<String, Xid> = HashMap::new();
+        let mut titles = Vec::with_capacity(hidden.len());
+        for id in hidden {
+            let title = x.window_title(id).unwrap_or_default();
+            let title = format!("{}: {}", *id, title);
+            titles.push(title.clone());
+            choices.insert(title, id);
+        }
+
+        let menu = DMenu::new("Restore:", titles, config);
+        let screen = state.client_set.current_screen().index();
+
+        if let MenuMatch::Line(_, s) = menu.run(screen)? {
+            let &id = choices
+                .get(&s)
+                .ok_or_else(|| custom_error!("unexpected dmenu output: {}", s))?;
+
+            restore_hidden_client(id, state, x)?;
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::{fixtures::ScriptedWm, mock::ScriptedConn};
+
+    #[test]
+    fn clients_can_be_hidden_and_restored() {
+        let mut wm = ScriptedWm::new().with_clients(2).run();
+        let (state, x) = (&mut wm.state, &ScriptedConn::new(vec![]));
+
+        hide_focused().call(state, x).unwrap();
+        hide_focused().call(state, x).unwrap();
+        assert_eq!(hidden_clients(state), vec![Xid(1), Xid(2)]);
+        assert_eq!(state.client_set.current_client(), None);
+
+        state.client_set.focus_tag("2");
+        restore_last_hidden().call(state, x).unwrap();
+        assert_eq!(state.client_set.tag_for_client(&Xid(1)), Some("2"));
+        assert_eq!(state.client_set.current_client(), Some(&Xid(1)));
+        assert_eq!(hidden_clients(state), vec![Xid(2)]);
+    }
+}
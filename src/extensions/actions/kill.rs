@@ -0,0 +1,165 @@
+//! Actions for closing multiple clients at once.
+//!
+//! Clients are closed using [XConn::kill] which asks them to close via `WM_DELETE_WINDOW`
+//! when it is supported, giving them the chance to save any unsaved work.
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, State},
+    extensions::util::dmenu::{DMenu, DMenuConfig, MenuMatch},
+    x::{Query, XConn},
+    Result, Xid,
+};
+use tracing::debug;
+
+/// The managed clients (on any workspace) that match the given [Query].
+pub fn matching_clients<X, Q>(query: &Q, state: &State<X>, x: &X) -> Result<Vec<Xid>>
+where
+    X: XConn,
+    Q: Query<X> + ?Sized,
+{
+    let mut ids = Vec::new();
+    for &id in state.client_set.clients() {
+        if query.run(id, x)? {
+            ids.push(id);
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Close all of the given clients, first asking for confirmation using [DMenu] if a config
+/// for it is provided.
+///
+/// Returns `false` if there were no clients to close or closing them was not confirmed.
+pub fn kill_clients<X: XConn>(
+    ids: &[Xid],
+    confirm: Option<DMenuConfig>,
+    state: &State<X>,
+    x: &X,
+) -> Result<bool> {
+    if ids.is_empty() {
+        return Ok(false);
+    }
+
+    if let Some(config) = confirm {
+        let screen = state.client_set.current_screen().index();
+        let prompt = format!("close {} client(s)?", ids.len());
+        let menu = DMenu::new(prompt, vec!["no", "yes"], config);
+        if menu.run(screen)? != MenuMatch::Line(1, "yes".to_owned()) {
+            return Ok(false);
+        }
+    }
+
+    debug!(?ids, "closing clients");
+    for &id in ids {
+        x.kill(id)?;
+    }
+
+    Ok(true)
+}
+
+/// Close every client on the focused workspace, asking for confirmation first if a
+/// [DMenuConfig] is provided.
+///
+/// ```no_run
+/// # use penrose::extensions::{actions::kill_workspace, util::dmenu::DMenuConfig};
+/// # use penrose::x11rb::RustConn;
+/// # use penrose::core::bindings::KeyEventHandler;
+/// let handler: Box<dyn KeyEventHandler<RustConn>> = kill_workspace(Some(DMenuConfig::default()));
+/// ```
+pub fn kill_workspace<X: XConn>(confirm: Option<DMenuConfig>) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let ids: Vec<Xid> = state
+            .client_set
+            .current_workspace()
+            .clients()
+            .copied()
+            .collect();
+        kill_clients(&ids, confirm, state, x)?;
+
+        Ok(())
+    })
+}
+
+/// Close every managed client matching the given [Query], asking for confirmation first if
+/// a [DMenuConfig] is provided.
+///
+/// ```no_run
+/// # use penrose::extensions::actions::kill_matching;
+/// # use penrose::x::query::ClassName;
+/// # use penrose::x11rb::RustConn;
+/// # use penrose::core::bindings::KeyEventHandler;
+/// let handler: Box<dyn KeyEventHandler<RustConn>> = kill_matching(ClassName("firefox"), None);
+/// ```
+pub fn kill_matching<X, Q>(query: Q, confirm: Option<DMenuConfig>) -> Box<dyn KeyEventHandler<X>>
+where
+    X: XConn,
+    Q: Query<X> + 'static,
+{
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let ids = matching_clients(&query, state, x)?;
+        kill_clients(&ids, confirm, state, x)?;
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::WindowManager,
+        x::{
+            atom::Atom,
+            fixtures::ScriptedWm,
+            mock::{Request, ScriptedConn},
+            property::Prop,
+            query::ClassName,
+        },
+    };
+
+    fn killed(x: &ScriptedConn) -> Vec<Xid> {
+        x.requests()
+            .into_iter()
+            .filter_map(|r| match r {
+                Request::Kill(id) => Some(id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn wm_with_clients() -> WindowManager<ScriptedConn> {
+        let mut wm = ScriptedWm::new().with_clients(2).run();
+        wm.state.client_set.focus_tag("2");
+        wm.state.client_set.insert(Xid(3));
+
+        wm
+    }
+
+    #[test]
+    fn kill_workspace_only_closes_clients_on_the_focused_workspace() {
+        let mut wm = wm_with_clients();
+        let x = &ScriptedConn::new(vec![]);
+
+        kill_workspace(None).call(&mut wm.state, x).unwrap();
+
+        assert_eq!(killed(x), vec![Xid(3)]);
+    }
+
+    #[test]
+    fn kill_matching_closes_matching_clients_on_all_workspaces() {
+        let mut wm = wm_with_clients();
+        let class = Prop::UTF8String(vec!["Navigator".to_owned(), "firefox".to_owned()]);
+        let x = &ScriptedConn::new(vec![])
+            .with_prop(Xid(2), Atom::WmClass.as_ref(), class.clone())
+            .with_prop(Xid(3), Atom::WmClass.as_ref(), class);
+
+        kill_matching(ClassName("firefox"), None)
+            .call(&mut wm.state, x)
+            .unwrap();
+
+        let mut ids = killed(x);
+        ids.sort();
+        assert_eq!(ids, vec![Xid(2), Xid(3)]);
+    }
+}
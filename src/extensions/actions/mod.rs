@@ -2,7 +2,6 @@
 use crate::{
     builtin::actions::{key_handler, modify_with},
     core::{bindings::KeyEventHandler, layout::LayoutStack, State},
-    pure::geometry::RelativeRect,
     util::spawn,
     x::{atom::Atom, property::Prop, XConn, XConnExt},
     Result, Xid,
@@ -10,9 +9,21 @@ use crate::{
 use tracing::error;
 
 mod dynamic_select;
+mod gather;
+mod hidden;
+mod kill;
+mod power_menu;
 
 #[doc(inline)]
 pub use dynamic_select::*;
+#[doc(inline)]
+pub use gather::*;
+#[doc(inline)]
+pub use hidden::*;
+#[doc(inline)]
+pub use kill::*;
+#[doc(inline)]
+pub use power_menu::*;
 
 /// The possible valid actions to use when manipulating full screen state
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -26,6 +37,10 @@ pub enum FullScreenAction {
 }
 
 /// Set the fullscreen state of a particular client
+///
+/// Fullscreen clients have their border removed and cover the entire screen they are on,
+/// ignoring any space reserved for status bars. Their previous tiled or floating state is
+/// restored when they leave fullscreen.
 pub fn set_fullscreen_state<X: XConn>(
     id: Xid,
     action: FullScreenAction,
@@ -34,35 +49,19 @@ pub fn set_fullscreen_state<X: XConn>(
 ) -> Result<()> {
     use FullScreenAction::*;
 
-    let net_wm_state = Atom::NetWmState.as_ref();
-    let full_screen = x.intern_atom(Atom::NetWmStateFullscreen.as_ref())?;
-
-    let mut wstate = match x.get_prop(id, net_wm_state) {
-        Ok(Some(Prop::Cardinal(vals))) => vals,
-        _ => vec![],
-    };
-
-    let currently_fullscreen = wstate.contains(&full_screen);
-
-    if action == Add || (action == Toggle && !currently_fullscreen) {
-        let r = RelativeRect::fullscreen();
-        state.client_set.float_unchecked(id, r);
-        wstate.push(*full_screen);
-    } else if action == Remove || (action == Toggle && currently_fullscreen) {
-        state.client_set.sink(&id);
-        wstate.retain(|&val| val != *full_screen);
-    }
+    x.modify_and_refresh(state, |cs| match action {
+        Add => cs.enter_fullscreen(&id),
+        Remove => cs.exit_fullscreen(&id),
+        Toggle => cs.toggle_fullscreen(&id),
+    })
+}
 
-    x.set_prop(id, net_wm_state, Prop::Cardinal(wstate))?;
-    x.refresh(state)
+/// Toggle the fullscreen state of the given client.
+pub fn toggle_fullscreen_client<X: XConn>(id: Xid) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state, x: &X| set_fullscreen_state(id, FullScreenAction::Toggle, state, x))
 }
 
 /// Toggle the fullscreen state of the currently focused window.
-///
-/// **NOTE**: You will need to make use of [add_ewmh_hooks][0] for this action to
-///           work correctly.
-///
-///   [0]: crate::extensions::hooks::add_ewmh_hooks
 pub fn toggle_fullscreen<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
     key_handler(|state, x: &X| {
         let id = match state.client_set.current_client() {
@@ -0,0 +1,132 @@
+//! A menu for shutting down, rebooting, locking or leaving the current session.
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, State},
+    extensions::util::dmenu::{DMenu, DMenuConfig, MenuMatch},
+    util::spawn,
+    x::XConn,
+    Result,
+};
+use tracing::debug;
+
+/// What to do when an entry in a [power_menu] is selected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PowerAction {
+    /// Cleanly exit the window manager (see [State::exit])
+    ExitWm,
+    /// Run the given command
+    Spawn(String),
+}
+
+impl PowerAction {
+    /// Run this action.
+    pub fn run<X: XConn>(&self, state: &mut State<X>) -> Result<()> {
+        match self {
+            Self::ExitWm => {
+                state.exit();
+                Ok(())
+            }
+            Self::Spawn(cmd) => spawn(cmd.as_str()),
+        }
+    }
+}
+
+/// Config for a [power_menu].
+#[derive(Debug, Clone)]
+pub struct PowerMenuConfig {
+    /// The entries to show in the menu (in order) along with the action to run for each.
+    ///
+    /// Default: lock, logout, reboot, shutdown and exit (using `loginctl` and `systemctl`)
+    pub entries: Vec<(String, PowerAction)>,
+
+    /// Should the user be asked to confirm their choice before it is run?
+    ///
+    /// Default: true
+    pub confirm: bool,
+
+    /// Config for the [DMenu] used to display the menu
+    pub dmenu: DMenuConfig,
+}
+
+impl Default for PowerMenuConfig {
+    fn default() -> Self {
+        let cmd = |s: &str| PowerAction::Spawn(s.to_owned());
+
+        Self {
+            entries: vec![
+                ("lock".to_owned(), cmd("loginctl lock-session")),
+                ("logout".to_owned(), cmd("loginctl terminate-session self")),
+                ("reboot".to_owned(), cmd("systemctl reboot")),
+                ("shutdown".to_owned(), cmd("systemctl poweroff")),
+                ("exit".to_owned(), PowerAction::ExitWm),
+            ],
+            confirm: true,
+            dmenu: DMenuConfig::default(),
+        }
+    }
+}
+
+impl PowerMenuConfig {
+    /// Replace the action run for the entry with the given label, adding it to the end of
+    /// the menu if it is not already present.
+    pub fn with_entry(mut self, label: impl Into<String>, action: PowerAction) -> Self {
+        let label = label.into();
+        match self.entries.iter_mut().find(|(l, _)| *l == label) {
+            Some((_, a)) => *a = action,
+            None => self.entries.push((label, action)),
+        }
+
+        self
+    }
+
+    /// Remove the entry with the given label from the menu.
+    pub fn without_entry(mut self, label: &str) -> Self {
+        self.entries.retain(|(l, _)| l != label);
+        self
+    }
+
+    fn action(&self, label: &str) -> Option<&PowerAction> {
+        self.entries
+            .iter()
+            .find(|(l, _)| l == label)
+            .map(|(_, a)| a)
+    }
+}
+
+/// Use [DMenu] to select and run one of the entries in a [PowerMenuConfig].
+///
+/// ```no_run
+/// # use penrose::extensions::actions::{power_menu, PowerAction, PowerMenuConfig};
+/// # use penrose::x11rb::RustConn;
+/// # use penrose::core::bindings::KeyEventHandler;
+/// let config = PowerMenuConfig::default()
+///     .with_entry("lock", PowerAction::Spawn("slock".to_owned()))
+///     .without_entry("logout");
+///
+/// let handler: Box<dyn KeyEventHandler<RustConn>> = power_menu(config);
+/// ```
+pub fn power_menu<X: XConn>(config: PowerMenuConfig) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, _: &X| {
+        let screen = state.client_set.current_screen().index();
+        let labels: Vec<&str> = config.entries.iter().map(|(l, _)| l.as_str()).collect();
+        let menu = DMenu::new("Power:", labels, config.dmenu);
+
+        let (label, action) = match menu.run(screen)? {
+            MenuMatch::Line(_, label) => match config.action(&label) {
+                Some(action) => (label, action),
+                None => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+
+        if config.confirm {
+            let confirm = DMenu::new(format!("{label}?"), vec!["no", "yes"], config.dmenu);
+            if confirm.run(screen)? != MenuMatch::Line(1, "yes".to_owned()) {
+                return Ok(());
+            }
+        }
+
+        debug!(%label, ?action, "running power menu action");
+        action.run(state)
+    })
+}
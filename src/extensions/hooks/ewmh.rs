@@ -33,6 +33,7 @@ pub const EWMH_SUPPORTED_ATOMS: &[Atom] = &[
     Atom::NetActiveWindow,
     Atom::NetWmDesktop,
     Atom::NetWmStrut,
+    Atom::NetWmStrutPartial,
     Atom::NetWmState,
     Atom::NetWmName,
     // TODO: read up on how this works and implement
@@ -53,6 +54,7 @@ where
     config.compose_or_set_startup_hook(startup_hook);
     config.compose_or_set_refresh_hook(refresh_hook);
     config.compose_or_set_event_hook(event_hook);
+    config.compose_or_set_manage_hook(manage_hook);
 
     config
 }
@@ -79,6 +81,20 @@ pub fn startup_hook<X: XConn>(_state: &mut State<X>, x: &X) -> Result<()> {
     )
 }
 
+/// Place clients that request to be fullscreen when they are first mapped into fullscreen.
+pub fn manage_hook<X: XConn>(id: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+    if let Some(Prop::Atom(atoms)) = x.get_prop(id, Atom::NetWmState.as_ref())? {
+        if atoms
+            .iter()
+            .any(|a| a == Atom::NetWmStateFullscreen.as_ref())
+        {
+            state.client_set.enter_fullscreen(&id);
+        }
+    }
+
+    Ok(())
+}
+
 /// Intercept messages from external applications and handle them.
 ///
 /// Currently supports the following:
@@ -113,11 +129,16 @@ pub fn event_hook<X: XConn>(event: &XEvent, state: &mut State<X>, x: &X) -> Resu
         }
 
         // If the request came from a pager, the first data element should be 2.
-        // For pager requests, set the active client (see docs linked at the top of
-        // this file for more details on the semantics of this message)
+        // Pager requests always set the active client while requests from applications
+        // are subject to the focus stealing policy (see docs linked at the top of this
+        // file for more details on the semantics of this message)
         "_NET_ACTIVE_WINDOW" => {
-            if data.as_u32()[0] == 2 {
+            let data = data.as_u32();
+            if data[0] == 2 {
                 x.set_active_client(*id, state)?;
+            } else {
+                let timestamp = Some(data[1]).filter(|&t| t != 0);
+                x.handle_focus_request(*id, timestamp, state)?;
             }
         }
 
@@ -206,7 +227,7 @@ where
     X: XConn,
 {
     // FIXME: this currently isn't in stacking order
-    let ordered_clients: Vec<Xid> = cs.clients().copied().collect();
+    let mut ordered_clients: Vec<Xid> = cs.clients().copied().collect();
 
     x.set_prop(
         x.root(),
@@ -214,6 +235,8 @@ where
         Prop::Window(ordered_clients.clone()),
     )?;
 
+    // _NET_CLIENT_LIST_STACKING is bottom to top
+    ordered_clients.reverse();
     x.set_prop(
         x.root(),
         Atom::NetClientListStacking.as_ref(),
@@ -269,3 +292,53 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{FocusRequestAction, FocusStealingPolicy},
+        x::{event::ClientEventMask, fixtures::ScriptedWm},
+    };
+    use simple_test_case::test_case;
+
+    #[test_case(1, 200, Xid(1), vec![]; "newer timestamp")]
+    #[test_case(1, 50, Xid(2), vec![Xid(1)]; "older timestamp")]
+    #[test_case(1, 0, Xid(1), vec![]; "missing timestamp")]
+    #[test_case(2, 50, Xid(1), vec![]; "pager request")]
+    #[test]
+    fn net_active_window_requests_respect_the_focus_stealing_policy(
+        source: u32,
+        timestamp: u32,
+        focused: Xid,
+        urgent: Vec<Xid>,
+    ) {
+        let request = ClientMessage::new(
+            Xid(1),
+            ClientEventMask::NoEventMask,
+            Atom::NetActiveWindow.as_ref(),
+            [source, timestamp, 0, 0, 0].into(),
+        );
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| {
+                conn.with_prop(
+                    Xid(2),
+                    Atom::NetWmUserTime.as_ref(),
+                    Prop::Cardinal(vec![100]),
+                )
+            })
+            .with_config(|c| {
+                c.focus_stealing_policy = FocusStealingPolicy {
+                    on_steal: FocusRequestAction::MarkUrgent,
+                    always_focus_classes: vec![],
+                };
+                *c = add_ewmh_hooks(std::mem::take(c));
+            })
+            .with_clients(2)
+            .with_events([XEvent::ClientMessage(request)])
+            .run();
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&focused));
+        assert_eq!(wm.state.urgent_clients(), urgent);
+    }
+}
@@ -0,0 +1,226 @@
+//! Remember where floating clients were last placed, per application.
+//!
+//! The last floating position of each client is recorded against its `WM_CLASS` and re-used
+//! whenever a client with the same class is floated, either when it is first spawned or when
+//! it is later moved to the floating layer. Positions are stored relative to the screen the
+//! client was on so they carry over between screens of different sizes.
+//!
+//! If a file path is provided then the remembered positions are loaded from it on startup and
+//! written back to it whenever they change so that they persist across restarts.
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, extensions::hooks::add_floating_memory, x11rb::RustConn};
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! let path = std::env::var("HOME").unwrap() + "/.cache/penrose/floating";
+//! add_floating_memory(wm, Some(path.into()))
+//! # }
+//! ```
+use crate::{
+    core::{State, WindowManager},
+    pure::geometry::RelativeRect,
+    x::{Atom, Prop, XConn, XConnExt},
+    Result, Xid,
+};
+use std::{collections::HashMap, fs, path::PathBuf};
+use tracing::{debug, warn};
+
+/// The remembered floating positions for each application class.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FloatingMemory {
+    path: Option<PathBuf>,
+    positions: HashMap<String, RelativeRect>,
+    // The class of each client that is currently floating, if it has one
+    floating: HashMap<Xid, Option<String>>,
+}
+
+impl FloatingMemory {
+    /// Create a new, empty [FloatingMemory], loading any positions previously saved to the
+    /// given file if one is provided.
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let mut positions = HashMap::new();
+
+        if let Some(p) = path.as_ref() {
+            match fs::read_to_string(p) {
+                Ok(s) => positions = parse_positions(&s),
+                Err(e) => debug!(%e, path = ?p, "unable to load remembered floating positions"),
+            }
+        }
+
+        Self {
+            path,
+            positions,
+            floating: HashMap::new(),
+        }
+    }
+
+    /// The position last used for floating clients with the given class.
+    pub fn position_for(&self, class: &str) -> Option<RelativeRect> {
+        self.positions.get(class).copied()
+    }
+
+    /// Remember the position to use for floating clients with the given class.
+    pub fn remember(&mut self, class: impl Into<String>, r: RelativeRect) {
+        self.positions.insert(class.into(), r);
+    }
+
+    /// Write the remembered positions to disk if a path was provided.
+    pub fn save(&self) -> Result<()> {
+        let path = match self.path.as_ref() {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let mut classes: Vec<&String> = self.positions.keys().collect();
+        classes.sort();
+        let s: String = classes
+            .into_iter()
+            .map(|c| {
+                let (x, y, w, h) = self.positions[c].components();
+                format!("{x} {y} {w} {h} {c}\n")
+            })
+            .collect();
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        Ok(fs::write(path, s)?)
+    }
+}
+
+// Each line is "x y w h class" with the class last as it may contain spaces
+fn parse_positions(s: &str) -> HashMap<String, RelativeRect> {
+    s.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(5, ' ');
+            let mut next = || parts.next()?.parse::<f64>().ok();
+            let (x, y, w, h) = (next()?, next()?, next()?, next()?);
+            let class = parts.next()?.to_owned();
+
+            Some((class, RelativeRect::new(x, y, w, h)))
+        })
+        .collect()
+}
+
+/// Remember floating client positions per `WM_CLASS`, optionally persisting them to the
+/// given file.
+///
+/// See the [module level docs][self] for details.
+pub fn add_floating_memory<X>(mut wm: WindowManager<X>, path: Option<PathBuf>) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    wm.state.add_extension(FloatingMemory::new(path));
+    wm.state
+        .config
+        .compose_or_set_refresh_hook(remember_floating_positions);
+
+    wm
+}
+
+/// Restore the remembered position of newly floated clients and record the current position
+/// of all other floating clients.
+pub fn remember_floating_positions<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    let mem = state.extension::<FloatingMemory>()?;
+    let mut mem = mem.borrow_mut();
+    let cs = &mut state.client_set;
+
+    mem.floating.retain(|id, _| cs.floating.contains_key(id));
+
+    let mut restored = false;
+    let mut changed = false;
+    let ids: Vec<Xid> = cs.floating.keys().copied().collect();
+
+    for id in ids {
+        let class = match mem.floating.get(&id) {
+            Some(class) => class.clone(),
+            None => {
+                let class = window_class(id, x)?;
+                mem.floating.insert(id, class.clone());
+
+                if let Some(r) = class.as_deref().and_then(|c| mem.position_for(c)) {
+                    debug!(%id, ?class, "restoring remembered floating position");
+                    cs.floating.insert(id, r);
+                    restored = true;
+                    continue;
+                }
+
+                class
+            }
+        };
+
+        if let Some(class) = class {
+            let r = cs.floating[&id];
+            if mem.position_for(&class) != Some(r) {
+                mem.remember(class, r);
+                changed = true;
+            }
+        }
+    }
+
+    if changed {
+        if let Err(e) = mem.save() {
+            warn!(%e, "unable to save remembered floating positions");
+        }
+    }
+    drop(mem);
+
+    if restored {
+        x.refresh(state)?;
+    }
+
+    Ok(())
+}
+
+// The application class of a client (the second string in WM_CLASS)
+fn window_class<X: XConn>(id: Xid, x: &X) -> Result<Option<String>> {
+    match x.get_prop(id, Atom::WmClass.as_ref())? {
+        Some(Prop::UTF8String(strs)) => Ok(strs.get(1).or(strs.first()).cloned()),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::{
+        fixtures::{ScriptedWm, SCREEN},
+        mock::ScriptedConn,
+    };
+
+    #[test]
+    fn positions_round_trip_through_a_file() {
+        let path = std::env::temp_dir().join(format!("penrose-floating-{}", std::process::id()));
+        let mut mem = FloatingMemory::new(Some(path.clone()));
+        mem.remember("Galculator", RelativeRect::new(0.5, 0.25, 0.2, 0.3));
+        mem.remember("Some App", RelativeRect::new(0.1, 0.1, 0.5, 0.5));
+        mem.save().unwrap();
+
+        let read = FloatingMemory::new(Some(path.clone()));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read, mem);
+    }
+
+    #[test]
+    fn floated_clients_are_restored_to_their_last_position() {
+        let class = || Prop::UTF8String(vec!["galculator".to_owned(), "Galculator".to_owned()]);
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| conn.with_prop(Xid(1), Atom::WmClass.as_ref(), class()))
+            .with_config(|c| c.floating_classes = vec!["Galculator".to_owned()])
+            .with_clients(1)
+            .build();
+        let mut wm = add_floating_memory(wm, None).run_script().unwrap();
+
+        let x = &ScriptedConn::new(vec![SCREEN]).with_prop(Xid(2), Atom::WmClass.as_ref(), class());
+        let r = RelativeRect::new(0.7, 0.7, 0.2, 0.2);
+        x.modify_and_refresh(&mut wm.state, |cs| {
+            cs.floating.insert(Xid(1), r);
+        })
+        .unwrap();
+        x.unmanage(Xid(1), &mut wm.state).unwrap();
+        x.manage(Xid(2), &mut wm.state).unwrap();
+
+        assert_eq!(wm.state.client_set.floating.get(&Xid(2)), Some(&r));
+    }
+}
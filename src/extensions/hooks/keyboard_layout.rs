@@ -0,0 +1,207 @@
+//! Tracking of the active keyboard layout (XKB group) for display in status bars.
+//!
+//! The active group is stored in the [KeyboardLayouts] state extension and its name is
+//! written to the [KEYBOARD_LAYOUT_PROP] property on the root window whenever it changes so
+//! that external bars are able to display it without needing to poll `setxkbmap -query`.
+//!
+//! ```no_run
+//! # use penrose::{
+//! #     core::{bindings::KeyEventHandler, WindowManager},
+//! #     extensions::hooks::keyboard_layout::{add_keyboard_layouts, next_keyboard_layout},
+//! #     map,
+//! #     x11rb::RustConn,
+//! # };
+//! # use std::collections::HashMap;
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! // Layout names in the order they were given to `setxkbmap -layout us,de`
+//! let wm = add_keyboard_layouts(wm, ["us", "de"]);
+//!
+//! let bindings: HashMap<String, Box<dyn KeyEventHandler<RustConn>>> = map! {
+//!     map_keys: |k: &str| k.to_owned();
+//!
+//!     "M-space" => next_keyboard_layout(),
+//! };
+//! # wm
+//! # }
+//! ```
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, State, WindowManager},
+    x::{property::Prop, XConn, XEvent},
+    Result,
+};
+use tracing::debug;
+
+/// The root window property used to publish the name of the active keyboard layout.
+pub const KEYBOARD_LAYOUT_PROP: &str = "_PENROSE_KEYBOARD_LAYOUT";
+
+/// The available keyboard layouts and which of them is currently active.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KeyboardLayouts {
+    names: Vec<String>,
+    group: u8,
+}
+
+impl KeyboardLayouts {
+    /// Create a new [KeyboardLayouts] with the given layout names, in group order.
+    pub fn new(names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            names: names.into_iter().map(Into::into).collect(),
+            group: 0,
+        }
+    }
+
+    /// The index of the active keyboard group.
+    pub fn group(&self) -> u8 {
+        self.group
+    }
+
+    /// The name of the active keyboard layout.
+    ///
+    /// Groups without a configured name are named using their index.
+    pub fn name(&self) -> String {
+        match self.names.get(self.group as usize) {
+            Some(name) => name.clone(),
+            None => self.group.to_string(),
+        }
+    }
+
+    /// The names of all of the known keyboard layouts, in group order.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    // The group offset from the current one by the given amount, wrapping around
+    fn offset(&self, delta: i32) -> u8 {
+        let n = self.names.len().max(1) as i32;
+
+        (self.group as i32 + delta).rem_euclid(n) as u8
+    }
+}
+
+/// Track the active keyboard layout, naming each group using the given layout names.
+///
+/// See the [module level docs][self] for details.
+pub fn add_keyboard_layouts<X, S>(
+    mut wm: WindowManager<X>,
+    names: impl IntoIterator<Item = S>,
+) -> WindowManager<X>
+where
+    X: XConn + 'static,
+    S: Into<String>,
+{
+    wm.state.add_extension(KeyboardLayouts::new(names));
+    wm.state.config.compose_or_set_startup_hook(startup_hook);
+    wm.state.config.compose_or_set_event_hook(event_hook);
+
+    wm
+}
+
+/// Read the active keyboard group from the X server and publish its name.
+pub fn startup_hook<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    let group = x.keyboard_group()?.unwrap_or_default();
+
+    set_group(group, state, x)
+}
+
+/// Keep the [KeyboardLayouts] extension up to date with the active keyboard group.
+pub fn event_hook<X: XConn>(event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
+    if let XEvent::KeyboardGroupChange(group) = event {
+        set_group(*group, state, x)?;
+    }
+
+    Ok(true)
+}
+
+/// Switch to the next keyboard layout, wrapping around to the first.
+pub fn next_keyboard_layout<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    cycle_layout(1)
+}
+
+/// Switch to the previous keyboard layout, wrapping around to the last.
+pub fn previous_keyboard_layout<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    cycle_layout(-1)
+}
+
+/// Switch to the keyboard layout with the given group index.
+pub fn set_keyboard_layout<X: XConn>(group: u8) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        x.set_keyboard_group(group)?;
+
+        set_group(group, state, x)
+    })
+}
+
+fn cycle_layout<X: XConn>(delta: i32) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let group = state.extension::<KeyboardLayouts>()?.borrow().offset(delta);
+        x.set_keyboard_group(group)?;
+
+        set_group(group, state, x)
+    })
+}
+
+fn set_group<X: XConn>(group: u8, state: &mut State<X>, x: &X) -> Result<()> {
+    let layouts = state.extension::<KeyboardLayouts>()?;
+    let mut layouts = layouts.borrow_mut();
+    layouts.group = group;
+    let name = layouts.name();
+    debug!(group, %name, "keyboard layout changed");
+
+    x.set_prop(
+        state.root(),
+        KEYBOARD_LAYOUT_PROP,
+        Prop::UTF8String(vec![name]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::{
+        fixtures::ScriptedWm,
+        mock::{Request, ScriptedConn},
+    };
+    use simple_test_case::test_case;
+
+    #[test_case(0, 1, 1; "next")]
+    #[test_case(2, 1, 0; "next wraps")]
+    #[test_case(1, -1, 0; "previous")]
+    #[test_case(0, -1, 2; "previous wraps")]
+    #[test]
+    fn offset(group: u8, delta: i32, expected: u8) {
+        let layouts = KeyboardLayouts {
+            names: vec!["us".to_owned(), "de".to_owned(), "fr".to_owned()],
+            group,
+        };
+
+        assert_eq!(layouts.offset(delta), expected);
+    }
+
+    #[test]
+    fn group_changes_are_tracked_and_published() {
+        let wm = ScriptedWm::new()
+            .with_events([XEvent::KeyboardGroupChange(1)])
+            .build();
+        let mut wm = add_keyboard_layouts(wm, ["us", "de"]).run_script().unwrap();
+
+        let layouts = wm.state.extension::<KeyboardLayouts>().unwrap();
+        assert_eq!(layouts.borrow().name(), "de");
+
+        let x = &ScriptedConn::new(vec![]);
+        next_keyboard_layout().call(&mut wm.state, x).unwrap();
+
+        assert_eq!(layouts.borrow().name(), "us");
+        assert_eq!(
+            x.requests(),
+            vec![
+                Request::SetKeyboardGroup(0),
+                Request::SetProp(
+                    wm.state.root(),
+                    KEYBOARD_LAYOUT_PROP.to_owned(),
+                    Prop::UTF8String(vec!["us".to_owned()])
+                ),
+            ]
+        );
+    }
+}
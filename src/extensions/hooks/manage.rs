@@ -2,12 +2,55 @@
 //!
 //! Manage hooks should _not_ trigger a refresh directly: that is handled by penrose
 //! itself when the manage hook is called.
+//!
+//! Pairing a [Query] with a manage hook in a tuple gives a hook that only runs for clients
+//! matching the query, and a `Vec` of boxed manage hooks runs each hook in turn. Combined with
+//! the [Query] combinators this allows for building up a set of rules in a similar way to
+//! the `ManageHook` monoid from xmonad:
+//!
+//! ```
+//! # use penrose::{
+//! #     core::hooks::ManageHook,
+//! #     extensions::hooks::manage::{do_fullscreen, float_centered, ignore, move_to_tag},
+//! #     x::{atom::Atom, query::{AppName, ClassName, Role, WindowType}, Query, XConn},
+//! # };
+//! fn manage_hook<X: XConn + 'static>() -> Box<dyn ManageHook<X>> {
+//!     let rules: Vec<Box<dyn ManageHook<X>>> = vec![
+//!         (ClassName("mpv"), do_fullscreen()).boxed(),
+//!         (ClassName("Gimp").and(Role("gimp-toolbox")), float_centered(0.3, 0.8)).boxed(),
+//!         (ClassName("discord").or(AppName("slack")), move_to_tag("9")).boxed(),
+//!         (WindowType(Atom::NetWindowTypeNotification), ignore()).boxed(),
+//!     ];
+//!
+//!     rules.boxed()
+//! }
+//! ```
+//!
+//! For the common case of sending particular programs to a fixed workspace, [TagRules]
+//! provides a more compact way of declaring the rules:
+//!
+//! ```
+//! # use penrose::{
+//! #     core::{hooks::ManageHook, Config},
+//! #     extensions::hooks::manage::TagRules,
+//! #     x::{query::ClassName, XConn},
+//! # };
+//! # fn example<X: XConn + 'static>(mut config: Config<X>) {
+//! let rules = TagRules::new()
+//!     .rule(ClassName("firefox"), "2")
+//!     .rule_and_follow(ClassName("Spotify"), "9");
+//!
+//! config.compose_or_set_manage_hook(rules);
+//! # }
+//! ```
 use crate::{
     core::{hooks::ManageHook, State},
-    pure::geometry::Rect,
+    pure::geometry::{Point, Rect},
     x::{Query, XConn},
     Result, Xid,
 };
+use std::fmt;
+use tracing::warn;
 
 // A tuple of (query, manage hook) runs conditionally if the query holds
 // for the window being managed.
@@ -26,6 +69,20 @@ where
     }
 }
 
+// A Vec of manage hooks runs each hook in order, stopping at the first error.
+impl<X> ManageHook<X> for Vec<Box<dyn ManageHook<X>>>
+where
+    X: XConn,
+{
+    fn call(&mut self, id: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+        for h in self.iter_mut() {
+            h.call(id, state, x)?;
+        }
+
+        Ok(())
+    }
+}
+
 fn float<X: XConn>(client: Xid, r: Rect, state: &mut State<X>, _: &X) -> Result<()> {
     state.client_set.float_unchecked(client, r);
 
@@ -83,3 +140,222 @@ impl<X: XConn> ManageHook<X> for FloatingCentered {
         float(client, r, state, x)
     }
 }
+
+/// Move clients to the given workspace.
+#[derive(Debug)]
+pub struct MoveToTag(pub &'static str);
+impl<X: XConn> ManageHook<X> for MoveToTag {
+    fn call(&mut self, client: Xid, state: &mut State<X>, _: &X) -> Result<()> {
+        state.client_set.move_client_to_tag(&client, self.0);
+
+        Ok(())
+    }
+}
+
+/// A table of rules mapping [Query]s to the workspace that matching clients should be
+/// placed on when they are first managed.
+///
+/// Rules are checked in the order they were added and only the first matching rule is
+/// applied. Rules added using [TagRules::rule_and_follow] additionally focus the target
+/// workspace so that the new client is visible.
+pub struct TagRules<X: XConn> {
+    rules: Vec<(Box<dyn Query<X>>, String, bool)>,
+}
+
+impl<X: XConn> fmt::Debug for TagRules<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rules: Vec<_> = self.rules.iter().map(|(_, t, f)| (t, f)).collect();
+        f.debug_struct("TagRules").field("rules", &rules).finish()
+    }
+}
+
+impl<X: XConn> Default for TagRules<X> {
+    fn default() -> Self {
+        Self { rules: vec![] }
+    }
+}
+
+impl<X: XConn> TagRules<X> {
+    /// Create a new, empty, set of rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Place clients matching `query` on the workspace with the given tag.
+    pub fn rule(self, query: impl Query<X> + 'static, tag: impl Into<String>) -> Self {
+        self.push(query, tag, false)
+    }
+
+    /// Place clients matching `query` on the workspace with the given tag and focus them,
+    /// switching to that workspace if it is not currently focused.
+    pub fn rule_and_follow(self, query: impl Query<X> + 'static, tag: impl Into<String>) -> Self {
+        self.push(query, tag, true)
+    }
+
+    fn push(
+        mut self,
+        query: impl Query<X> + 'static,
+        tag: impl Into<String>,
+        follow: bool,
+    ) -> Self {
+        self.rules.push((query.boxed(), tag.into(), follow));
+        self
+    }
+}
+
+impl<X: XConn> ManageHook<X> for TagRules<X> {
+    fn call(&mut self, client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+        for (query, tag, follow) in self.rules.iter() {
+            if !query.run(client, x)? {
+                continue;
+            }
+
+            if !state.client_set.contains_tag(tag) {
+                warn!(%client, %tag, "unknown tag in TagRules: leaving client in place");
+                return Ok(());
+            }
+
+            state.client_set.move_client_to_tag(&client, tag);
+            if *follow {
+                state.client_set.focus_client(&client);
+            }
+
+            return Ok(());
+        }
+
+        Ok(())
+    }
+}
+
+/// Make clients fullscreen.
+#[derive(Debug)]
+pub struct Fullscreen;
+impl<X: XConn> ManageHook<X> for Fullscreen {
+    fn call(&mut self, client: Xid, state: &mut State<X>, _: &X) -> Result<()> {
+        state.client_set.enter_fullscreen(&client);
+
+        Ok(())
+    }
+}
+
+/// Do not manage clients: they are mapped on the screen where they requested to be but
+/// are otherwise left alone by the window manager.
+#[derive(Debug)]
+pub struct Ignore;
+impl<X: XConn> ManageHook<X> for Ignore {
+    fn call(&mut self, client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+        state.client_set.remove_client(&client);
+        let root = state.root_for_client(client);
+        state.client_x_screens.remove(&client);
+
+        if let Some(frame) = state.frames.remove(&client) {
+            let r = x.client_geometry(frame)?;
+            x.reparent(client, root, Point::new(r.x, r.y))?;
+            x.destroy_window(frame)?;
+        }
+
+        x.map(client)
+    }
+}
+
+/// Move clients to the given workspace.
+pub fn move_to_tag(tag: &'static str) -> MoveToTag {
+    MoveToTag(tag)
+}
+
+/// Float clients in the center of the screen using the given width and height ratios.
+///
+/// # Panics
+/// Panics if `w` or `h` are not in the range `0.0..=1.0`.
+pub fn float_centered(w: f64, h: f64) -> FloatingCentered {
+    FloatingCentered::new(w, h)
+}
+
+/// Float clients at a fixed position on the screen.
+pub fn float_at(r: Rect) -> FloatingFixed {
+    FloatingFixed(r)
+}
+
+/// Do not manage clients.
+pub fn ignore() -> Ignore {
+    Ignore
+}
+
+/// Make clients fullscreen.
+pub fn do_fullscreen() -> Fullscreen {
+    Fullscreen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::Xid,
+        x::{
+            fixtures::ScriptedWm,
+            mock::{Request, ScriptedConn},
+            query::{AppName, ClassName, WindowType},
+            Atom, Prop, XEvent,
+        },
+    };
+    use simple_test_case::test_case;
+
+    fn class(c: &str) -> Prop {
+        Prop::UTF8String(vec![c.to_owned(), c.to_owned()])
+    }
+
+    #[test]
+    fn manage_hook_rules_are_applied() {
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| {
+                conn.with_prop(Xid(1), Atom::WmClass.as_ref(), class("mpv"))
+                    .with_prop(Xid(2), Atom::WmClass.as_ref(), class("Gimp"))
+                    .with_prop(
+                        Xid(3),
+                        Atom::NetWmWindowType.as_ref(),
+                        Prop::Atom(vec![Atom::NetWindowTypeNotification.as_ref().to_owned()]),
+                    )
+            })
+            .with_clients(3)
+            .with_config(|c| {
+                let rules: Vec<Box<dyn ManageHook<ScriptedConn>>> = vec![
+                    (ClassName("mpv"), do_fullscreen()).boxed(),
+                    (ClassName("Gimp").and(AppName("Gimp")), move_to_tag("3")).boxed(),
+                    (WindowType(Atom::NetWindowTypeNotification), ignore()).boxed(),
+                ];
+                c.compose_or_set_manage_hook(rules);
+            })
+            .run();
+
+        assert!(wm.state.client_set.is_fullscreen(&Xid(1)));
+        assert_eq!(wm.state.client_set.tag_for_client(&Xid(2)), Some("3"));
+        assert!(!wm.state.client_set.contains(&Xid(3)));
+        assert!(wm.x().requests().contains(&Request::Map(Xid(3))));
+    }
+
+    #[test_case(false, "1", Xid(2); "rule")]
+    #[test_case(true, "2", Xid(1); "rule and follow")]
+    #[test]
+    fn tag_rules_are_applied(follow: bool, focused_tag: &str, focused: Xid) {
+        let rules = TagRules::new().rule(ClassName("spotify"), "missing");
+        let rules = if follow {
+            rules.rule_and_follow(ClassName("firefox"), "2")
+        } else {
+            rules.rule(ClassName("firefox"), "2")
+        };
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| {
+                conn.with_prop(Xid(1), Atom::WmClass.as_ref(), class("firefox"))
+                    .with_prop(Xid(2), Atom::WmClass.as_ref(), class("spotify"))
+            })
+            .with_events([XEvent::MapRequest(Xid(2)), XEvent::MapRequest(Xid(1))])
+            .with_config(|c| c.compose_or_set_manage_hook(rules))
+            .run();
+        let cs = &wm.state.client_set;
+
+        assert_eq!(cs.tag_for_client(&Xid(1)), Some("2"));
+        assert_eq!(cs.tag_for_client(&Xid(2)), Some("1"));
+        assert_eq!(cs.current_tag(), focused_tag);
+        assert_eq!(cs.current_client(), Some(&focused));
+    }
+}
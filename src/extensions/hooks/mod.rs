@@ -1,11 +1,22 @@
 //! Hook implementations and helpers for adding to your Penrose window manager
 pub mod ewmh;
+pub mod floating_memory;
+pub mod keyboard_layout;
 pub mod manage;
 pub mod named_scratchpads;
 pub mod startup;
+pub mod supervisor;
+pub mod system_tray;
 pub mod window_swallowing;
 
 pub use ewmh::add_ewmh_hooks;
-pub use named_scratchpads::{add_named_scratchpads, NamedScratchPad, ToggleNamedScratchPad};
-pub use startup::SpawnOnStartup;
+pub use floating_memory::add_floating_memory;
+pub use keyboard_layout::{add_keyboard_layouts, KeyboardLayouts};
+pub use named_scratchpads::{
+    add_named_scratchpads, send_to_dynamic_scratchpad, toggle_dynamic_scratchpad, NamedScratchPad,
+    ToggleNamedScratchPad,
+};
+pub use startup::{SetRootBackground, SpawnOnStartup};
+pub use supervisor::{SupervisedProgram, Supervisor};
+pub use system_tray::SystemTray;
 pub use window_swallowing::WindowSwallowing;
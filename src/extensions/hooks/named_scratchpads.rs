@@ -1,7 +1,39 @@
 //! Support for managing multiple floating scratchpad programs that can be
 //! toggled on or off on the active workspace.
+//!
+//! Each [NamedScratchPad] is given a command to spawn the program and a [Query] to identify
+//! its window once it has been spawned. Scratchpads can optionally be given a preferred
+//! geometry (relative to the screen they are shown on) and be hidden automatically when they
+//! lose focus.
+//!
+//! ```no_run
+//! # use penrose::{
+//! #     core::WindowManager,
+//! #     extensions::hooks::{add_named_scratchpads, NamedScratchPad},
+//! #     pure::geometry::RelativeRect,
+//! #     x::query::ClassName,
+//! #     x11rb::RustConn,
+//! # };
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! let (nsp, toggle_term) = NamedScratchPad::new(
+//!     "terminal",
+//!     "st -c StScratchpad",
+//!     ClassName("StScratchpad"),
+//!     |_, _: &mut _, _: &_| Ok(()),
+//!     false,
+//! );
+//! let nsp = nsp
+//!     .with_geometry(RelativeRect::new(0.1, 0.1, 0.8, 0.6))
+//!     .hide_on_focus_loss(true);
+//!
+//! // Bind `toggle_term` to a key and then register the scratchpad
+//! add_named_scratchpads(wm, vec![nsp])
+//! # }
+//! ```
 use crate::{
+    builtin::actions::key_handler,
     core::{bindings::KeyEventHandler, hooks::ManageHook, State, WindowManager},
+    pure::geometry::RelativeRect,
     util::spawn,
     x::{Query, XConn, XConnExt},
     Result, Xid,
@@ -23,6 +55,8 @@ where
     client: Option<Xid>,
     query: Box<dyn Query<X>>,
     hook: Box<dyn ManageHook<X>>,
+    geometry: Option<RelativeRect>,
+    hide_on_focus_loss: bool,
 }
 
 impl<X: XConn> fmt::Debug for NamedScratchPad<X> {
@@ -31,6 +65,8 @@ impl<X: XConn> fmt::Debug for NamedScratchPad<X> {
             .field("name", &self.name)
             .field("prog", &self.prog)
             .field("client", &self.client)
+            .field("geometry", &self.geometry)
+            .field("hide_on_focus_loss", &self.hide_on_focus_loss)
             .finish()
     }
 }
@@ -57,6 +93,8 @@ where
             client: None,
             query: Box::new(query),
             hook: Box::new(manage_hook),
+            geometry: None,
+            hide_on_focus_loss: false,
         };
 
         (
@@ -67,6 +105,19 @@ where
             },
         )
     }
+
+    /// Float this scratchpad at the given position, relative to the screen it is shown on,
+    /// whenever it is spawned or toggled on.
+    pub fn with_geometry(mut self, r: RelativeRect) -> Self {
+        self.geometry = Some(r);
+        self
+    }
+
+    /// Set whether or not this scratchpad should be hidden again when it loses focus.
+    pub fn hide_on_focus_loss(mut self, hide: bool) -> Self {
+        self.hide_on_focus_loss = hide;
+        self
+    }
 }
 
 // Private wrapper type to ensure that only this module can access this state extension
@@ -91,6 +142,9 @@ where
         .add_invisible_workspace(NSP_TAG)
         .expect("named scratchpad tag to be unique");
     wm.state.config.compose_or_set_manage_hook(manage_hook);
+    wm.state
+        .config
+        .compose_or_set_focus_change_hook(hide_on_focus_loss::<X>);
 
     wm
 }
@@ -103,6 +157,9 @@ pub fn manage_hook<X: XConn + 'static>(id: Xid, state: &mut State<X>, x: &X) ->
         if sp.client.is_none() && sp.query.run(id, x)? {
             debug!(scratchpad=sp.name, %id, "matched query for named scratchpad");
             sp.client = Some(id);
+            if let Some(r) = sp.geometry {
+                state.client_set.float_unchecked(id, r);
+            }
             return sp.hook.call(id, state, x);
         }
     }
@@ -110,6 +167,42 @@ pub fn manage_hook<X: XConn + 'static>(id: Xid, state: &mut State<X>, x: &X) ->
     Ok(())
 }
 
+/// Hide any scratchpads that have been configured to hide on focus loss once they are no longer
+/// focused. Hiding is queued to run after the current refresh has completed.
+pub fn hide_on_focus_loss<X: XConn + 'static>(
+    old: Option<Xid>,
+    new: Option<Xid>,
+    state: &mut State<X>,
+    _: &X,
+) -> Result<()> {
+    let id = match old {
+        Some(id) if old != new => id,
+        _ => return Ok(()),
+    };
+
+    let s = state.extension::<NamedScratchPadState<X>>()?;
+    let should_hide = s
+        .borrow()
+        .0
+        .values()
+        .any(|sp| sp.hide_on_focus_loss && sp.client == Some(id));
+
+    if should_hide {
+        state.queue_action(move |state, x| {
+            let visible = matches!(state.client_set.tag_for_client(&id), Some(t) if t != NSP_TAG);
+            if !visible || state.client_set.current_client() == Some(&id) {
+                return Ok(());
+            }
+
+            debug!(%id, "named scratchpad lost focus: moving to NSP tag");
+            state.client_set.move_client_to_tag(&id, NSP_TAG);
+            x.refresh(state)
+        });
+    }
+
+    Ok(())
+}
+
 /// Toggle the visibility of a NamedScratchPad.
 ///
 /// This will spawn the requested client program if it isn't currently running or
@@ -128,13 +221,14 @@ impl<X: XConn + 'static> KeyEventHandler<X> for ToggleNamedScratchPad {
         let mut s = _s.borrow_mut();
         let name = self.name;
 
-        let (id, hook) = match s.0.get_mut(&name) {
+        let (id, hook, geometry) = match s.0.get_mut(&name) {
             // Active client somewhere in the StackSet
             Some(NamedScratchPad {
                 client: Some(id),
                 hook,
+                geometry,
                 ..
-            }) if state.client_set.contains(id) => (*id, hook),
+            }) if state.client_set.contains(id) => (*id, hook, *geometry),
 
             // No active client or client is no longer in state
             Some(nsp) => {
@@ -164,6 +258,9 @@ impl<X: XConn + 'static> KeyEventHandler<X> for ToggleNamedScratchPad {
             // Toggle on / bring to current workspace
             debug!("current workspace does not contain target client: moving to tag");
             state.client_set.move_client_to_current_tag(&id);
+            if let Some(r) = geometry {
+                state.client_set.float_unchecked(id, r);
+            }
 
             if self.run_hook_on_toggle {
                 if let Err(e) = hook.call(id, state, x) {
@@ -176,3 +273,153 @@ impl<X: XConn + 'static> KeyEventHandler<X> for ToggleNamedScratchPad {
         x.refresh(state)
     }
 }
+
+// Clients that have been sent to a dynamic scratchpad slot
+#[derive(Debug, Default)]
+struct DynamicScratchPads(HashMap<String, Xid>);
+
+fn ensure_nsp_workspace<X: XConn>(state: &mut State<X>) -> Result<()> {
+    if !state.client_set.contains_tag(NSP_TAG) {
+        state.client_set.add_invisible_workspace(NSP_TAG)?;
+    }
+
+    Ok(())
+}
+
+/// Send the currently focused client to a dynamic scratchpad slot, hiding it until it is
+/// summoned again using [toggle_dynamic_scratchpad].
+///
+/// The client is floated at its current position so that it floats over whichever workspace
+/// it is summoned to. If the slot already held another client then that client is returned
+/// to the current workspace.
+pub fn send_to_dynamic_scratchpad<X>(slot: &'static str) -> Box<dyn KeyEventHandler<X>>
+where
+    X: XConn + 'static,
+{
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let id = match state.client_set.current_client() {
+            Some(&id) => id,
+            None => return Ok(()),
+        };
+        ensure_nsp_workspace(state)?;
+
+        let slots = state.extension_or_default::<DynamicScratchPads>();
+        let previous = slots.borrow_mut().0.insert(slot.to_owned(), id);
+        if let Some(prev) = previous.filter(|&p| p != id && state.client_set.contains(&p)) {
+            debug!(%slot, %prev, "returning previous dynamic scratchpad client");
+            state.client_set.move_client_to_current_tag(&prev);
+        }
+
+        if !state.client_set.floating.contains_key(&id) {
+            let r = x.client_geometry(id)?;
+            state.client_set.float(id, r)?;
+        }
+
+        debug!(%slot, %id, "sending client to dynamic scratchpad");
+        state.client_set.move_client_to_tag(&id, NSP_TAG);
+        x.refresh(state)
+    })
+}
+
+/// Toggle the visibility of the client in a dynamic scratchpad slot (see
+/// [send_to_dynamic_scratchpad]).
+///
+/// If the client is visible on the current workspace it is hidden, otherwise it is moved
+/// to the current workspace and focused.
+pub fn toggle_dynamic_scratchpad<X>(slot: &'static str) -> Box<dyn KeyEventHandler<X>>
+where
+    X: XConn + 'static,
+{
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let slots = state.extension_or_default::<DynamicScratchPads>();
+        let id = match slots.borrow().0.get(slot) {
+            Some(&id) => id,
+            None => return Ok(()),
+        };
+
+        if !state.client_set.contains(&id) {
+            debug!(%slot, %id, "dynamic scratchpad client is no longer managed");
+            slots.borrow_mut().0.remove(slot);
+            return Ok(());
+        }
+
+        if state.client_set.current_workspace().contains(&id) {
+            ensure_nsp_workspace(state)?;
+            state.client_set.move_client_to_tag(&id, NSP_TAG);
+        } else {
+            state.client_set.move_client_to_current_tag(&id);
+            state.client_set.focus_client(&id);
+        }
+
+        x.refresh(state)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::{fixtures::ScriptedWm, mock::ScriptedConn, query::ClassName, Atom, Prop};
+
+    fn wm_with_scratchpad(hide: bool) -> WindowManager<ScriptedConn> {
+        let class = |c: &str| Prop::UTF8String(vec![c.to_owned(), c.to_owned()]);
+        let (nsp, _) = NamedScratchPad::new(
+            "scratch",
+            "true",
+            ClassName("scratch"),
+            |_: Xid, _: &mut State<ScriptedConn>, _: &ScriptedConn| Ok(()),
+            false,
+        );
+        let nsp = nsp
+            .with_geometry(RelativeRect::new(0.1, 0.1, 0.5, 0.5))
+            .hide_on_focus_loss(hide);
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| conn.with_prop(Xid(1), Atom::WmClass.as_ref(), class("scratch")))
+            .with_clients(2)
+            .build();
+
+        add_named_scratchpads(wm, vec![nsp])
+    }
+
+    #[test]
+    fn dynamic_scratchpads_can_be_sent_and_summoned() {
+        let mut wm = ScriptedWm::new().with_clients(2).run();
+        let (state, x) = (&mut wm.state, &ScriptedConn::new(vec![]));
+        let (mut send, mut toggle) = (
+            send_to_dynamic_scratchpad("a"),
+            toggle_dynamic_scratchpad("a"),
+        );
+
+        send.call(state, x).unwrap();
+        assert_eq!(state.client_set.tag_for_client(&Xid(2)), Some(NSP_TAG));
+        assert!(state.client_set.floating.contains_key(&Xid(2)));
+
+        state.client_set.focus_tag("3");
+        toggle.call(state, x).unwrap();
+        assert_eq!(state.client_set.tag_for_client(&Xid(2)), Some("3"));
+        assert_eq!(state.client_set.current_client(), Some(&Xid(2)));
+
+        toggle.call(state, x).unwrap();
+        assert_eq!(state.client_set.tag_for_client(&Xid(2)), Some(NSP_TAG));
+    }
+
+    #[test]
+    fn scratchpads_float_with_their_geometry() {
+        let wm = wm_with_scratchpad(false).run_script().unwrap();
+        let cs = &wm.state.client_set;
+
+        assert_eq!(
+            cs.floating.get(&Xid(1)),
+            Some(&RelativeRect::new(0.1, 0.1, 0.5, 0.5))
+        );
+        assert_eq!(cs.tag_for_client(&Xid(1)), Some("1"));
+    }
+
+    #[test]
+    fn scratchpads_can_hide_on_focus_loss() {
+        let wm = wm_with_scratchpad(true).run_script().unwrap();
+        let cs = &wm.state.client_set;
+
+        assert_eq!(cs.tag_for_client(&Xid(1)), Some(NSP_TAG));
+        assert_eq!(cs.current_client(), Some(&Xid(2)));
+    }
+}
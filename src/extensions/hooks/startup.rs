@@ -2,7 +2,7 @@
 use crate::{
     core::{hooks::StateHook, State},
     util::spawn,
-    x::XConn,
+    x::{RootBackground, XConn},
     Result,
 };
 
@@ -30,3 +30,60 @@ where
         spawn(self.prog)
     }
 }
+
+/// Set the background of all root windows on window manager startup
+/// (see [XConn::set_root_background]).
+///
+/// ```no_run
+/// # use penrose::{extensions::hooks::SetRootBackground, x::RootBackground, x11rb::RustConn};
+/// # use penrose::core::hooks::StateHook;
+/// let hook: Box<dyn StateHook<RustConn>> =
+///     SetRootBackground::boxed(RootBackground::Color(0x282828.into()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetRootBackground {
+    bg: RootBackground,
+}
+
+impl SetRootBackground {
+    /// Create a new startup hook ready for adding to your Config
+    pub fn boxed<X>(bg: RootBackground) -> Box<dyn StateHook<X>>
+    where
+        X: XConn,
+    {
+        Box::new(Self { bg })
+    }
+}
+
+impl<X> StateHook<X> for SetRootBackground
+where
+    X: XConn,
+{
+    fn call(&mut self, state: &mut State<X>, x: &X) -> Result<()> {
+        for &root in state.roots() {
+            x.set_root_background(root, &self.bg)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::{fixtures::ScriptedWm, mock::Request};
+
+    #[test]
+    fn set_root_background_sets_the_background_of_the_root_window() {
+        let bg = RootBackground::Color(0x282828.into());
+        let wm = ScriptedWm::new()
+            .with_config(|c| c.startup_hook = Some(SetRootBackground::boxed(bg.clone())))
+            .run();
+
+        let root = wm.state.root();
+        assert!(wm
+            .x()
+            .requests()
+            .contains(&Request::SetRootBackground(root, bg)));
+    }
+}
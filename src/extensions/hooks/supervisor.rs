@@ -0,0 +1,369 @@
+//! Spawning long running programs on startup and restarting them if they exit.
+//!
+//! Programs such as compositors, status bars and tray applets are typically started once
+//! when the window manager starts. A [Supervisor] will spawn each of its
+//! [SupervisedProgram]s on startup, keep track of their PIDs and (if requested) restart
+//! them with an increasing delay between attempts if they exit:
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, extensions::hooks::{SupervisedProgram, Supervisor}, x::XConn};
+//! # use std::time::Duration;
+//! # fn example<X: XConn + 'static>(wm: WindowManager<X>) -> WindowManager<X> {
+//! let wm = Supervisor::new(vec![
+//!     SupervisedProgram::new("picom", "picom --experimental-backends")
+//!         .restart_with_backoff(Duration::from_secs(1), Duration::from_secs(60)),
+//!     SupervisedProgram::new("nm-applet", "nm-applet"),
+//! ])
+//! .add_to(wm);
+//! # wm
+//! # }
+//! ```
+//!
+//! The [Supervisor] is stored as a state extension so the current status of each program
+//! can be looked up from hooks and key bindings (for example, to display in a status bar):
+//!
+//! ```no_run
+//! # use penrose::{core::State, extensions::hooks::{supervisor::ProgramState, Supervisor}, x::XConn, Result};
+//! fn picom_is_running<X: XConn>(state: &State<X>) -> Result<bool> {
+//!     let s = state.extension::<Supervisor>()?;
+//!     let running = matches!(
+//!         s.borrow().status("picom").map(|p| &p.state),
+//!         Some(ProgramState::Running { .. })
+//!     );
+//!
+//!     Ok(running)
+//! }
+//! ```
+//!
+//! Programs are checked periodically from the main event loop using
+//! [State::schedule_every] so exits are detected within one check interval rather than
+//! immediately. Supervised programs are not stopped when the window manager exits.
+use crate::{
+    core::{State, WindowManager},
+    util::{pid_is_running, Spawn},
+    x::XConn,
+    Result,
+};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// The default interval at which supervised programs are checked to see if they are
+/// still running.
+pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The current state of a [SupervisedProgram].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgramState {
+    /// The program has not been started yet
+    NotStarted,
+    /// The program is running with the given PID
+    Running {
+        /// The PID of the running process
+        pid: u32,
+        /// When the process was spawned
+        since: Instant,
+    },
+    /// The program exited and is waiting to be restarted
+    Restarting {
+        /// When the program will next be spawned
+        at: Instant,
+    },
+    /// The program exited and is not going to be restarted
+    Exited,
+    /// The program could not be spawned
+    Failed(String),
+}
+
+/// The current status of a program being run by a [Supervisor].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramStatus {
+    /// The name used to identify this program
+    pub name: String,
+    /// The command being run
+    pub command: String,
+    /// The current state of the program
+    pub state: ProgramState,
+    /// The number of times that the program has been restarted
+    pub restarts: u32,
+}
+
+/// The delay between successive attempts at restarting a program that has exited.
+///
+/// The delay starts at `initial` and doubles after each restart up to a maximum of
+/// `max`. If the program stays running for at least `max` before exiting then the delay is
+/// reset back to `initial`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// The delay before the first restart attempt
+    pub initial: Duration,
+    /// The maximum delay between restart attempts
+    pub max: Duration,
+}
+
+impl Backoff {
+    /// The delay before restarting a program that has already failed `failures` times.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures);
+
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+/// A program to be spawned on startup and optionally restarted if it exits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupervisedProgram {
+    status: ProgramStatus,
+    backoff: Option<Backoff>,
+    failures: u32,
+}
+
+impl SupervisedProgram {
+    /// Create a new program to be spawned on startup using the given command. By default
+    /// the program is not restarted if it exits.
+    pub fn new(name: impl Into<String>, command: impl Into<String>) -> Self {
+        Self {
+            status: ProgramStatus {
+                name: name.into(),
+                command: command.into(),
+                state: ProgramState::NotStarted,
+                restarts: 0,
+            },
+            backoff: None,
+            failures: 0,
+        }
+    }
+
+    /// Restart this program if it exits, waiting between attempts as described by
+    /// [Backoff].
+    pub fn restart_with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff = Some(Backoff { initial, max });
+        self
+    }
+
+    /// The current status of this program.
+    pub fn status(&self) -> &ProgramStatus {
+        &self.status
+    }
+
+    fn spawn(&mut self, now: Instant) {
+        let name = &self.status.name;
+        if self.status.command.trim().is_empty() {
+            warn!(%name, "empty command for supervised program");
+            self.status.state = ProgramState::Failed("empty command".to_owned());
+            return;
+        }
+
+        self.status.state = match Spawn::new(&self.status.command).spawn() {
+            Ok(pid) => {
+                info!(%name, pid, "spawned supervised program");
+                ProgramState::Running { pid, since: now }
+            }
+            Err(e) => {
+                error!(%name, %e, "unable to spawn supervised program");
+                ProgramState::Failed(e.to_string())
+            }
+        };
+    }
+
+    fn check(&mut self, now: Instant) {
+        match self.status.state {
+            ProgramState::NotStarted => self.spawn(now),
+
+            ProgramState::Running { pid, since } if !pid_is_running(pid) => {
+                let name = &self.status.name;
+                let backoff = match self.backoff {
+                    Some(backoff) => backoff,
+                    None => {
+                        info!(%name, pid, "supervised program exited");
+                        self.status.state = ProgramState::Exited;
+                        return;
+                    }
+                };
+
+                if now.saturating_duration_since(since) >= backoff.max {
+                    self.failures = 0;
+                }
+                let delay = backoff.delay(self.failures);
+                self.failures += 1;
+
+                info!(%name, pid, ?delay, "supervised program exited: restarting");
+                self.status.state = ProgramState::Restarting { at: now + delay };
+            }
+
+            ProgramState::Restarting { at } if at <= now => {
+                self.status.restarts += 1;
+                self.spawn(now);
+            }
+
+            _ => (),
+        }
+    }
+}
+
+/// Spawns a set of [SupervisedProgram]s on startup and restarts them if they exit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Supervisor {
+    programs: Vec<SupervisedProgram>,
+    check_interval: Duration,
+}
+
+impl Supervisor {
+    /// Create a new supervisor for the given programs.
+    pub fn new(programs: Vec<SupervisedProgram>) -> Self {
+        Self {
+            programs,
+            check_interval: DEFAULT_CHECK_INTERVAL,
+        }
+    }
+
+    /// Set how often programs are checked to see if they are still running.
+    pub fn with_check_interval(mut self, interval: Duration) -> Self {
+        self.check_interval = interval;
+        self
+    }
+
+    /// Add this supervisor to a [WindowManager] as a state extension along with the
+    /// startup hook required to run it.
+    pub fn add_to<X>(self, mut wm: WindowManager<X>) -> WindowManager<X>
+    where
+        X: XConn + 'static,
+    {
+        let interval = self.check_interval;
+        wm.state.add_extension(self);
+        wm.state
+            .config
+            .compose_or_set_startup_hook(move |state: &mut State<X>, x: &X| {
+                check_programs(state, x)?;
+                state.schedule_every(interval, check_programs);
+
+                Ok(())
+            });
+
+        wm
+    }
+
+    /// The current status of the program with the given name, if there is one.
+    pub fn status(&self, name: &str) -> Option<&ProgramStatus> {
+        self.programs
+            .iter()
+            .map(|p| &p.status)
+            .find(|s| s.name == name)
+    }
+
+    /// The current status of each supervised program.
+    pub fn programs(&self) -> impl Iterator<Item = &ProgramStatus> {
+        self.programs.iter().map(|p| &p.status)
+    }
+
+    /// Spawn any programs that are due to be started or restarted and check whether
+    /// running programs have exited.
+    pub fn check(&mut self, now: Instant) {
+        for p in self.programs.iter_mut() {
+            p.check(now);
+        }
+    }
+}
+
+fn check_programs<X: XConn>(state: &mut State<X>, _: &X) -> Result<()> {
+    state
+        .extension::<Supervisor>()?
+        .borrow_mut()
+        .check(Instant::now());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::{
+        sys::signal::{kill, Signal},
+        unistd::Pid,
+    };
+    use simple_test_case::test_case;
+    use std::thread::sleep;
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    #[test_case(0, secs(1); "first restart")]
+    #[test_case(2, secs(4); "doubles")]
+    #[test_case(10, secs(30); "capped at max")]
+    #[test_case(u32::MAX, secs(30); "does not overflow")]
+    #[test]
+    fn backoff_delay(failures: u32, expected: Duration) {
+        let b = Backoff {
+            initial: secs(1),
+            max: secs(30),
+        };
+
+        assert_eq!(b.delay(failures), expected);
+    }
+
+    // Wait for a spawned program to exit and be picked up by a check
+    fn check_until_exited(s: &mut Supervisor, name: &str) {
+        for _ in 0..500 {
+            s.check(Instant::now());
+            if !matches!(s.status(name).unwrap().state, ProgramState::Running { .. }) {
+                return;
+            }
+            sleep(Duration::from_millis(10));
+        }
+
+        panic!("{name} did not exit");
+    }
+
+    #[test]
+    fn running_programs_are_tracked() {
+        let mut s = Supervisor::new(vec![SupervisedProgram::new("sleep", "sleep 10")]);
+        s.check(Instant::now());
+        s.check(Instant::now());
+
+        let pid = match s.status("sleep").unwrap().state {
+            ProgramState::Running { pid, .. } => pid,
+            ref state => panic!("expected sleep to be running: {state:?}"),
+        };
+        kill(Pid::from_raw(pid as i32), Signal::SIGKILL).unwrap();
+        check_until_exited(&mut s, "sleep");
+
+        assert_eq!(s.status("sleep").unwrap().state, ProgramState::Exited);
+    }
+
+    #[test]
+    fn programs_are_restarted_after_exiting() {
+        let mut s = Supervisor::new(vec![
+            SupervisedProgram::new("true", "true").restart_with_backoff(secs(5), secs(60))
+        ]);
+        s.check(Instant::now());
+        check_until_exited(&mut s, "true");
+
+        let at = match s.status("true").unwrap().state {
+            ProgramState::Restarting { at } => at,
+            ref state => panic!("expected true to be restarting: {state:?}"),
+        };
+
+        s.check(at - secs(1));
+        assert_eq!(s.status("true").unwrap().restarts, 0);
+
+        s.check(at);
+        let status = s.status("true").unwrap();
+        assert_eq!(status.restarts, 1);
+        assert!(matches!(status.state, ProgramState::Running { .. }));
+    }
+
+    #[test]
+    fn programs_that_can_not_be_spawned_are_failed() {
+        let mut s = Supervisor::new(vec![SupervisedProgram::new(
+            "missing",
+            "penrose-test-program-that-does-not-exist",
+        )
+        .restart_with_backoff(secs(1), secs(10))]);
+        s.check(Instant::now());
+
+        assert!(matches!(
+            s.status("missing").unwrap().state,
+            ProgramState::Failed(_)
+        ));
+    }
+}
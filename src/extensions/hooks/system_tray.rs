@@ -0,0 +1,358 @@
+//! An opt-in system tray following the freedesktop system tray protocol.
+//!
+//! Tray icons are embedded using the XEmbed protocol into a host window that you
+//! provide, such as a status bar. The host window can either be given up front using
+//! [SystemTray::docked_in] or set once it has been created using [dock_into]. Once it has a
+//! host window, the tray takes ownership of the `_NET_SYSTEM_TRAY_S0` selection using a
+//! dedicated manager window (see [XConn::create_selection_window]) so that dock requests from
+//! tray icons are sent to the window manager rather than to the program owning the host.
+//!
+//! See <https://specifications.freedesktop.org/systemtray-spec/systemtray-spec-latest.html>
+//! for details of the protocol.
+use crate::{
+    core::{State, WindowManager},
+    pure::geometry::{Point, Rect},
+    x::{event::ClientMessageKind, property::Prop, Atom, ClientAttr, ClientConfig, XConn, XEvent},
+    Result, Xid,
+};
+use tracing::{debug, info, warn};
+
+// Opcode sent by tray icons requesting to be docked
+const SYSTEM_TRAY_REQUEST_DOCK: u32 = 0;
+// The _NET_SYSTEM_TRAY_ORIENTATION value for a horizontal tray
+const SYSTEM_TRAY_ORIENTATION_HORZ: u32 = 0;
+// Flag in _XEMBED_INFO indicating that the embedded window should be mapped
+const XEMBED_MAPPED: u32 = 1 << 0;
+
+/// A system tray that docks tray icons into a host window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemTray {
+    icon_size: u32,
+    spacing: u32,
+    host: Option<(Xid, Rect)>,
+    manager: Option<Xid>,
+    icons: Vec<Xid>,
+    is_owner: bool,
+}
+
+impl SystemTray {
+    /// Create a new system tray with the given icon size and spacing between icons
+    /// in pixels.
+    pub fn new(icon_size: u32, spacing: u32) -> Self {
+        Self {
+            icon_size,
+            spacing,
+            host: None,
+            manager: None,
+            icons: vec![],
+            is_owner: false,
+        }
+    }
+
+    /// Dock tray icons into the given region of an existing window.
+    ///
+    /// The region is relative to the origin of the host window.
+    pub fn docked_in(mut self, id: Xid, region: Rect) -> Self {
+        self.host = Some((id, region));
+        self
+    }
+
+    /// Add this system tray to a [WindowManager] as a state extension along with the
+    /// hooks required to run it.
+    pub fn add_to<X>(self, mut wm: WindowManager<X>) -> WindowManager<X>
+    where
+        X: XConn + 'static,
+    {
+        wm.state.add_extension(self);
+        wm.state.config.compose_or_set_startup_hook(startup_hook);
+        wm.state.config.compose_or_set_event_hook(event_hook);
+
+        wm
+    }
+
+    /// The tray icons currently docked, in the order they are displayed.
+    pub fn icons(&self) -> &[Xid] {
+        &self.icons
+    }
+
+    /// Whether or not this tray currently owns the system tray selection.
+    pub fn is_owner(&self) -> bool {
+        self.is_owner
+    }
+
+    /// The window used to own the system tray selection, if one has been created.
+    pub fn manager(&self) -> Option<Xid> {
+        self.manager
+    }
+
+    /// The positions of each docked icon relative to the host window, filling the host
+    /// region from left to right.
+    ///
+    /// Icons that would overflow the host region are not given a position.
+    pub fn icon_positions(&self) -> Vec<Rect> {
+        let (_, region) = match self.host {
+            Some(host) => host,
+            None => return vec![],
+        };
+
+        let s = self.icon_size;
+        let y = region.y + region.h.saturating_sub(s) / 2;
+
+        (0..self.icons.len() as u32)
+            .map(|i| Rect::new(region.x + i * (s + self.spacing), y, s, s))
+            .take_while(|r| r.x + r.w <= region.x + region.w)
+            .collect()
+    }
+
+    fn acquire_selection<X: XConn>(&mut self, x: &X) -> Result<()> {
+        if self.host.is_none() || self.is_owner {
+            return Ok(());
+        }
+
+        let manager = match self.manager {
+            Some(id) => id,
+            None => match x.create_selection_window(x.root())? {
+                Some(id) => *self.manager.insert(id),
+                None => {
+                    warn!("unable to create a system tray manager window");
+                    return Ok(());
+                }
+            },
+        };
+
+        if !x.acquire_selection(Atom::NetSystemTrayS0.as_ref(), manager)? {
+            warn!("unable to acquire the system tray selection: is another tray running?");
+            return Ok(());
+        }
+
+        info!(%manager, "acquired the system tray selection");
+        self.is_owner = true;
+        let orientation = Prop::Cardinal(vec![SYSTEM_TRAY_ORIENTATION_HORZ]);
+        x.set_prop(
+            manager,
+            Atom::NetSystemTrayOrientation.as_ref(),
+            orientation,
+        )?;
+        x.send_client_message(
+            ClientMessageKind::TakeSystrayOwnership(x.root(), manager).as_message(x)?,
+        )
+    }
+
+    fn dock<X: XConn>(&mut self, icon: Xid, x: &X) -> Result<()> {
+        let (host, _) = match self.host {
+            Some(host) if !self.icons.contains(&icon) => host,
+            _ => return Ok(()),
+        };
+
+        debug!(%icon, %host, "docking tray icon");
+        x.set_client_attributes(icon, &[ClientAttr::ClientEventMask])?;
+        x.reparent(icon, host, Point::default())?;
+        x.send_client_message(ClientMessageKind::XEmbedNotify(icon, host).as_message(x)?)?;
+        self.icons.push(icon);
+
+        self.position_icons(x)
+    }
+
+    fn undock<X: XConn>(&mut self, icon: Xid, x: &X) -> Result<()> {
+        debug!(%icon, "removing tray icon");
+        self.icons.retain(|&id| id != icon);
+
+        self.position_icons(x)
+    }
+
+    fn position_icons<X: XConn>(&self, x: &X) -> Result<()> {
+        let positions = self.icon_positions();
+
+        for (i, &icon) in self.icons.iter().enumerate() {
+            match positions.get(i) {
+                Some(&r) => {
+                    x.set_client_config(icon, &[ClientConfig::Position(r)])?;
+                    set_icon_mapped(icon, x)?;
+                }
+                None => x.unmap(icon)?,
+            }
+        }
+
+        x.flush();
+
+        Ok(())
+    }
+}
+
+// Map or unmap a tray icon based on its _XEMBED_INFO property, mapping it if the
+// property is not set.
+fn set_icon_mapped<X: XConn>(icon: Xid, x: &X) -> Result<()> {
+    let mapped = match x.get_prop(icon, Atom::XEmbedInfo.as_ref())? {
+        Some(Prop::Cardinal(vals)) if vals.len() >= 2 => vals[1] & XEMBED_MAPPED != 0,
+        _ => true,
+    };
+
+    if mapped {
+        x.map(icon)
+    } else {
+        x.unmap(icon)
+    }
+}
+
+/// Set the window that tray icons should be docked into, taking ownership of the
+/// system tray selection if the tray does not already have it.
+///
+/// The host window may belong to another X connection (such as a status bar): tray icons
+/// are reparented into it but dock requests are always handled by the window manager.
+///
+/// This is intended for use by status bars and other host windows that are created
+/// after the [WindowManager] has started. Any icons that are already docked are moved
+/// into the new host window.
+pub fn dock_into<X>(id: Xid, region: Rect, state: &mut State<X>, x: &X) -> Result<()>
+where
+    X: XConn + 'static,
+{
+    let s = state.extension::<SystemTray>()?;
+    let mut tray = s.borrow_mut();
+
+    let changed_host = tray.host.map(|(host, _)| host) != Some(id);
+    tray.host = Some((id, region));
+
+    if changed_host {
+        for &icon in tray.icons.iter() {
+            x.reparent(icon, id, Point::default())?;
+        }
+    }
+    tray.acquire_selection(x)?;
+
+    tray.position_icons(x)
+}
+
+/// Take ownership of the system tray selection if a host window has been set.
+pub fn startup_hook<X: XConn + 'static>(state: &mut State<X>, x: &X) -> Result<()> {
+    let s = state.extension::<SystemTray>()?;
+    let mut tray = s.borrow_mut();
+
+    tray.acquire_selection(x)
+}
+
+/// Handle dock requests from tray icons along with changes to docked icons.
+pub fn event_hook<X: XConn + 'static>(event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
+    let s = state.extension::<SystemTray>()?;
+    let mut tray = s.borrow_mut();
+
+    match event {
+        XEvent::ClientMessage(m) if m.dtype == Atom::NetSystemTrayOpcode.as_ref() => {
+            if let [_, opcode, icon, ..] = m.data.as_u32()[..] {
+                let for_manager = tray.manager == Some(m.id);
+                if tray.is_owner && for_manager && opcode == SYSTEM_TRAY_REQUEST_DOCK {
+                    tray.dock(Xid(icon), x)?;
+                }
+            }
+
+            Ok(false)
+        }
+
+        XEvent::Destroy(id) if tray.icons.contains(id) => {
+            tray.undock(*id, x)?;
+
+            Ok(false)
+        }
+
+        XEvent::PropertyNotify(p)
+            if p.atom == Atom::XEmbedInfo.as_ref() && tray.icons.contains(&p.id) =>
+        {
+            tray.position_icons(x)?;
+
+            Ok(false)
+        }
+
+        XEvent::SelectionClear(e) if e.selection == Atom::NetSystemTrayS0.as_ref() => {
+            warn!("lost ownership of the system tray selection");
+            tray.is_owner = false;
+
+            Ok(false)
+        }
+
+        _ => Ok(true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::{
+        event::{ClientEventMask, ClientMessage},
+        fixtures::ScriptedWm,
+        mock::{Request, ScriptedConn, FIRST_WINDOW_ID},
+    };
+    use simple_test_case::test_case;
+
+    const HOST: Xid = Xid(100);
+    const MANAGER: Xid = Xid(FIRST_WINDOW_ID);
+
+    fn dock_request(id: Xid, icon: u32) -> XEvent {
+        XEvent::ClientMessage(ClientMessage::new(
+            id,
+            ClientEventMask::NoEventMask,
+            Atom::NetSystemTrayOpcode.as_ref(),
+            [0, SYSTEM_TRAY_REQUEST_DOCK, icon, 0, 0].into(),
+        ))
+    }
+
+    fn tray_wm(events: Vec<XEvent>) -> WindowManager<ScriptedConn> {
+        let wm = ScriptedWm::new().with_events(events).build();
+        let tray = SystemTray::new(16, 2).docked_in(HOST, Rect::new(100, 0, 40, 20));
+
+        tray.add_to(wm).run_script().unwrap()
+    }
+
+    fn tray_icons(wm: &WindowManager<ScriptedConn>) -> Vec<Xid> {
+        let s = wm.state.extension::<SystemTray>().unwrap();
+        let icons = s.borrow().icons().to_vec();
+
+        icons
+    }
+
+    #[test]
+    fn the_selection_is_owned_by_a_dedicated_manager_window() {
+        let wm = tray_wm(vec![]);
+        let requests = wm.x().requests();
+
+        assert!(requests.contains(&Request::CreateSelectionWindow(MANAGER)));
+        assert!(requests.contains(&Request::AcquireSelection(
+            Atom::NetSystemTrayS0.as_ref().to_owned(),
+            MANAGER
+        )));
+    }
+
+    #[test]
+    fn dock_requests_to_the_manager_reparent_icons_into_the_host() {
+        let wm = tray_wm(vec![dock_request(MANAGER, 5)]);
+
+        assert_eq!(tray_icons(&wm), vec![Xid(5)]);
+        assert!(wm.x().requests().contains(&Request::Reparent(Xid(5), HOST)));
+    }
+
+    #[test]
+    fn dock_requests_to_other_windows_are_ignored() {
+        let wm = tray_wm(vec![dock_request(HOST, 5)]);
+
+        assert!(tray_icons(&wm).is_empty());
+    }
+
+    #[test_case(0, vec![]; "no icons")]
+    #[test_case(1, vec![Rect::new(100, 2, 16, 16)]; "single icon")]
+    #[test_case(2, vec![Rect::new(100, 2, 16, 16), Rect::new(118, 2, 16, 16)]; "multiple icons")]
+    #[test_case(4, vec![Rect::new(100, 2, 16, 16), Rect::new(118, 2, 16, 16)]; "overflow")]
+    #[test]
+    fn icon_positions(n_icons: u32, expected: Vec<Rect>) {
+        let mut tray = SystemTray::new(16, 2).docked_in(Xid(1), Rect::new(100, 0, 40, 20));
+        tray.icons = (0..n_icons).map(|i| Xid(10 + i)).collect();
+
+        assert_eq!(tray.icon_positions(), expected);
+    }
+
+    #[test]
+    fn icon_positions_without_host_is_empty() {
+        let mut tray = SystemTray::new(16, 2);
+        tray.icons = vec![Xid(10)];
+
+        assert!(tray.icon_positions().is_empty());
+    }
+}
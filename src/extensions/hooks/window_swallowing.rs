@@ -3,6 +3,27 @@
 //! When a client window is opened from a parent that matches a given query, its takes
 //! over the parent window's position in the Stack. When the child window closes, the
 //! parent is restored in its place.
+//!
+//! Whether or not a new window is a child of the focused window is determined by walking up
+//! the process tree from its `_NET_WM_PID` so windows that do not set this property will never
+//! swallow their parent. The child is managed as normal (including running any manage hooks)
+//! before it takes the place of its parent.
+//!
+//! ```no_run
+//! # use penrose::{
+//! #     core::Config,
+//! #     extensions::hooks::WindowSwallowing,
+//! #     x::{query::ClassName, Query},
+//! #     x11rb::RustConn,
+//! # };
+//! # fn example(mut config: Config<RustConn>) {
+//! // Swallow the terminal for anything other than a nested terminal
+//! config.event_hook = Some(WindowSwallowing::boxed_with_child(
+//!     ClassName("Alacritty"),
+//!     ClassName("Alacritty").not(),
+//! ));
+//! # }
+//! ```
 use crate::{
     core::{hooks::EventHook, State},
     pure::{geometry::RelativeRect, Stack},
@@ -71,7 +92,9 @@ impl WindowSwallowingState {
         x.refresh(state)?;
         self.clear_state_for(child);
 
-        Ok(false)
+        // The child is no longer in the client set but we still need the default handling
+        // to clean up any other state held for it.
+        Ok(true)
     }
 }
 
@@ -95,6 +118,20 @@ impl<X: XConn> WindowSwallowing<X> {
         })
     }
 
+    /// Create a new window swallowing rule that only swallows the parent if the new child
+    /// window also matches the `child` query.
+    pub fn boxed_with_child<P, C>(parent: P, child: C) -> Box<dyn EventHook<X>>
+    where
+        X: 'static,
+        P: Query<X> + 'static,
+        C: Query<X> + 'static,
+    {
+        Box::new(Self {
+            parent: Box::new(parent),
+            child: Some(Box::new(child)),
+        })
+    }
+
     fn queries_hold(&self, id: Xid, parent: Xid, x: &X) -> bool {
         let parent_matches = x.query_or(false, &*self.parent, parent);
         let child_matches = match &self.child {
@@ -105,36 +142,51 @@ impl<X: XConn> WindowSwallowing<X> {
         parent_matches && child_matches
     }
 
-    fn handle_map_request(
-        &mut self,
-        child: Xid,
-        wss: &mut WindowSwallowingState,
-        state: &mut State<X>,
-        x: &X,
-    ) -> Result<bool> {
+    fn handle_map_request(&mut self, child: Xid, state: &mut State<X>, x: &X) -> Result<bool> {
         let parent = match state.client_set.current_client() {
             Some(&parent) => parent,
             None => return Ok(true), // No parent currently so run default handling
         };
 
-        if !self.queries_hold(child, parent, x) || !is_child_of(child, parent, x) {
+        if state.client_set.contains(&child)
+            || !self.queries_hold(child, parent, x)
+            || !is_child_of(child, parent, x)
+        {
             return Ok(true);
         }
 
         info!(%parent, %child, "matched queries for window swallowing");
 
-        // Set the new window as focus, replacing the parent window.
-        wss.swallowed.insert(child, parent);
-        state.client_set.modify_occupied(|mut s| {
-            s.focus = child;
-            s
-        });
+        // The child is managed by the default handling before replacing the parent so that
+        // it is set up correctly and any manage hooks are run.
+        state.queue_action(move |state, x| swallow(parent, child, state, x));
 
-        // If the parent was floating, copy that state to the child.
-        transfer_floating_state(parent, child, &mut state.client_set.floating);
+        Ok(true)
+    }
+}
 
-        Ok(false)
+// Replace the parent with the now managed child, provided that they have both ended up on
+// the same workspace.
+fn swallow<X: XConn>(parent: Xid, child: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+    let cs = &mut state.client_set;
+    let same_workspace = matches!(
+        (cs.tag_for_client(&parent), cs.tag_for_client(&child)),
+        (Some(p), Some(c)) if p == c
+    );
+    if !same_workspace {
+        return Ok(());
     }
+
+    let wss = state.extension_or_default::<WindowSwallowingState>();
+    wss.borrow_mut().swallowed.insert(child, parent);
+
+    let cs = &mut state.client_set;
+    cs.swap_clients(&parent, &child);
+    transfer_floating_state(parent, child, &mut cs.floating);
+    cs.remove_client(&parent);
+    cs.focus_client(&child);
+
+    x.refresh(state)
 }
 
 impl<X: XConn> EventHook<X> for WindowSwallowing<X> {
@@ -143,12 +195,14 @@ impl<X: XConn> EventHook<X> for WindowSwallowing<X> {
         let mut wss = _wss.borrow_mut();
 
         match *event {
-            // We intercept map requests for windows matching our child query if the
+            // We check map requests for windows matching our child query if the
             // currently focused window matches the parent query. If we're unable to
             // pull the _NET_WM_PID property for either window we bail on trying to
             // handle the new window and let the default handling deal with it.
-            // NOTE: This does _not_ trigger any user specified manage hooks.
-            XEvent::MapRequest(id) => self.handle_map_request(id, &mut wss, state, x),
+            XEvent::MapRequest(id) => {
+                drop(wss);
+                self.handle_map_request(id, state, x)
+            }
 
             // Stash state in case this is before a window closing. If the closed window
             // is one we care about then the stack ordering and any floating position will
@@ -182,9 +236,16 @@ fn is_child_of<X: XConn>(id: Xid, parent: Xid, x: &X) -> bool {
 // This will bottom out when the parent pid hits root (0) due to there being no stat file for root
 fn parent_pid(pid: u32) -> Option<u32> {
     let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
-    let s_parent_pid = stat.split_whitespace().nth(3).expect("/proc to be valid");
 
-    s_parent_pid.parse().ok()
+    parse_parent_pid(&stat)
+}
+
+// The command name is wrapped in parens and may itself contain spaces and parens so we need
+// to skip to the last closing paren before splitting the remaining fields.
+fn parse_parent_pid(stat: &str) -> Option<u32> {
+    let (_, rest) = stat.rsplit_once(')')?;
+
+    rest.split_whitespace().nth(1)?.parse().ok()
 }
 
 fn parent_pid_chain(mut pid: u32) -> Vec<u32> {
@@ -197,3 +258,18 @@ fn parent_pid_chain(mut pid: u32) -> Vec<u32> {
 
     parents
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test_case("1234 (bash) S 42 1234 1234", Some(42); "simple")]
+    #[test_case("1234 (my prog) S 42 1234 1234", Some(42); "spaces in name")]
+    #[test_case("1234 (a) b) S 42 1234 1234", Some(42); "parens in name")]
+    #[test_case("garbage", None; "invalid")]
+    #[test]
+    fn parse_parent_pid_works(stat: &str, expected: Option<u32>) {
+        assert_eq!(parse_parent_pid(stat), expected);
+    }
+}
@@ -1,6 +1,10 @@
 //! A simple wrapper for suckless' [dmenu][1] tool for providing quick text based menus
 //!
+//! Other launchers that can act as a drop in replacement for dmenu (such as [rofi][2] in its
+//! `-dmenu` mode) can be used instead by setting [DMenuConfig::launcher].
+//!
 //! [1]: https://tools.suckless.org/dmenu/
+//! [2]: https://github.com/davatorium/rofi
 use crate::{Color, Error, Result};
 use std::{
     io::{Read, Write},
@@ -8,7 +12,7 @@ use std::{
 };
 
 /// The result of attempting to match against user input in a [DMenu]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MenuMatch {
     /// The selected line along its line number (0 indexed)
     Line(usize, String),
@@ -18,9 +22,28 @@ pub enum MenuMatch {
     NoMatch,
 }
 
+/// The program used to display a [DMenu] to the user
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Launcher {
+    /// suckless' [dmenu](https://tools.suckless.org/dmenu/)
+    #[default]
+    DMenu,
+    /// [rofi](https://github.com/davatorium/rofi) running in `-dmenu` mode
+    ///
+    /// Colors are not passed to rofi: they should be set in your rofi theme instead.
+    Rofi,
+    /// Any other program accepting the same command line flags as dmenu
+    Custom(&'static str),
+}
+
 /// Config for running a [DMenu] selection
 #[derive(Debug, Copy, Clone)]
 pub struct DMenuConfig {
+    /// The program to use for displaying the menu
+    ///
+    /// Default: [Launcher::DMenu]
+    pub launcher: Launcher,
+
     /// Should line numbers be displayed to the user?
     ///
     /// Default: false
@@ -68,6 +91,7 @@ pub struct DMenuConfig {
 impl Default for DMenuConfig {
     fn default() -> Self {
         Self {
+            launcher: Launcher::DMenu,
             show_line_numbers: false,
             password_input: false,
             ignore_case: false,
@@ -80,8 +104,17 @@ impl Default for DMenuConfig {
 }
 
 impl DMenuConfig {
+    fn program(&self) -> &'static str {
+        match self.launcher {
+            Launcher::DMenu => "dmenu",
+            Launcher::Rofi => "rofi",
+            Launcher::Custom(program) => program,
+        }
+    }
+
     fn flags(&self, prompt: &str, screen_index: usize) -> Vec<String> {
         let &DMenuConfig {
+            launcher,
             password_input,
             ignore_case,
             bg_color,
@@ -91,32 +124,46 @@ impl DMenuConfig {
             ..
         } = self;
 
-        let mut s = format!(
-            "-nb {} -nf {} -sb {} -m {}",
-            bg_color.as_rgb_hex_string(),
-            fg_color.as_rgb_hex_string(),
-            selected_color.as_rgb_hex_string(),
-            screen_index,
-        );
+        let mut flags = Vec::new();
+        let mut push = |flag: &str, val: Option<String>| {
+            flags.push(flag.to_owned());
+            flags.extend(val);
+        };
+
+        if launcher == Launcher::Rofi {
+            push("-dmenu", None);
+        } else {
+            push("-nb", Some(bg_color.as_rgb_hex_string()));
+            push("-nf", Some(fg_color.as_rgb_hex_string()));
+            push("-sb", Some(selected_color.as_rgb_hex_string()));
+        }
+
+        push("-m", Some(screen_index.to_string()));
 
         if n_lines > 0 {
-            s.push_str(&format!(" -l {n_lines}"))
+            push("-l", Some(n_lines.to_string()));
         }
 
         if password_input {
-            s.push_str(" -P");
+            push(
+                if launcher == Launcher::Rofi {
+                    "-password"
+                } else {
+                    "-P"
+                },
+                None,
+            );
         }
 
         if ignore_case {
-            s.push_str(" -i");
+            push("-i", None);
         }
 
         if !prompt.is_empty() {
-            s.push_str(" -p ");
-            s.push_str(prompt);
+            push("-p", Some(prompt.to_owned()));
         }
 
-        s.split_whitespace().map(|s| s.to_owned()).collect()
+        flags
     }
 }
 
@@ -163,14 +210,20 @@ impl DMenu {
     /// ```
     pub fn run(&self, screen_index: usize) -> Result<MenuMatch> {
         let raw = self.raw_user_choice_from_dmenu(screen_index)?;
+
+        Ok(self.parse_choice(&raw))
+    }
+
+    /// Parse the raw output of the launcher into a [MenuMatch] against the choices for
+    /// this menu.
+    pub fn parse_choice(&self, raw: &str) -> MenuMatch {
         let choice = raw.trim();
 
         if choice.is_empty() {
-            return Ok(MenuMatch::NoMatch);
+            return MenuMatch::NoMatch;
         }
 
-        let res = self
-            .choices
+        self.choices
             .iter()
             .enumerate()
             .find(|(i, s)| {
@@ -183,9 +236,7 @@ impl DMenu {
             .map_or_else(
                 || MenuMatch::UserInput(choice.to_string()),
                 |(i, _)| MenuMatch::Line(i, self.choices[i].to_string()),
-            );
-
-        Ok(res)
+            )
     }
 
     fn choices_as_input_bytes(&self) -> Vec<u8> {
@@ -205,7 +256,8 @@ impl DMenu {
 
     fn raw_user_choice_from_dmenu(&self, screen_index: usize) -> Result<String> {
         let args = self.config.flags(&self.prompt, screen_index);
-        let mut proc = Command::new("dmenu")
+        let program = self.config.program();
+        let mut proc = Command::new(program)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .args(args)
@@ -213,7 +265,7 @@ impl DMenu {
 
         {
             // Taking stdin here and dropping it when the block scope ends to close it and
-            // let dmenu determine the end of input
+            // let the launcher determine the end of input
             let mut stdin = proc
                 .stdin
                 .take()
@@ -224,9 +276,51 @@ impl DMenu {
 
         let mut raw = String::new();
         proc.stdout
-            .ok_or_else(|| Error::Custom("failed to spawn dmenu".to_owned()))?
+            .ok_or_else(|| Error::Custom(format!("failed to spawn {program}")))?
             .read_to_string(&mut raw)?;
 
         Ok(raw)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test_case(Launcher::DMenu, "-nb #282828 -nf #ebdbb2 -sb #458588 -m 1 -l 10 -i -p >>>"; "dmenu")]
+    #[test_case(Launcher::Rofi, "-dmenu -m 1 -l 10 -i -p >>>"; "rofi")]
+    #[test_case(Launcher::Custom("bemenu"), "-nb #282828 -nf #ebdbb2 -sb #458588 -m 1 -l 10 -i -p >>>"; "custom")]
+    #[test]
+    fn flags_match_launcher(launcher: Launcher, expected: &str) {
+        let config = DMenuConfig {
+            launcher,
+            ignore_case: true,
+            ..Default::default()
+        };
+
+        assert_eq!(config.flags(">>>", 1).join(" "), expected);
+    }
+
+    #[test]
+    fn prompts_are_passed_as_a_single_flag() {
+        let flags = DMenuConfig::default().flags("Move to tag:", 0);
+
+        assert_eq!(flags.last().map(|s| s.as_str()), Some("Move to tag:"));
+    }
+
+    #[test_case(false, "b\n", MenuMatch::Line(1, "b".to_owned()); "line")]
+    #[test_case(true, "1   b\n", MenuMatch::Line(1, "b".to_owned()); "line with line numbers")]
+    #[test_case(false, "d\n", MenuMatch::UserInput("d".to_owned()); "user input")]
+    #[test_case(false, "\n", MenuMatch::NoMatch; "no match")]
+    #[test]
+    fn parse_choice(show_line_numbers: bool, raw: &str, expected: MenuMatch) {
+        let config = DMenuConfig {
+            show_line_numbers,
+            ..Default::default()
+        };
+        let menu = DMenu::new(">>>", vec!["a", "b", "c"], config);
+
+        assert_eq!(menu.parse_choice(raw), expected);
+    }
+}
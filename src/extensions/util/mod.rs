@@ -7,6 +7,7 @@ use crate::{
 
 pub mod debug;
 pub mod dmenu;
+pub mod prompt;
 
 /// Detect the current monitor set up and arrange the monitors if needed using [xrandr][1].
 ///
@@ -0,0 +1,448 @@
+//! A minimal text input prompt drawn by penrose itself, in the style of XMonad.Prompt.
+//!
+//! While a [Prompt] is open the keyboard is grabbed and key presses are used to edit a single
+//! line of input rather than running key bindings. Possible completions for the current input
+//! are listed below it, with `Tab` replacing the input with the first completion. `Return`
+//! submits the input and `Escape` closes the prompt without running anything.
+//!
+//! Only letters, digits, space and basic (unshifted) punctuation can be typed. The prompt is
+//! drawn using the same overlay as [binding hints][crate::core::hints] and uses the
+//! [BindingHintsConfig] from the window manager config (or its default) for styling.
+//!
+//! ```no_run
+//! # use penrose::{
+//! #     core::bindings::KeyEventHandler,
+//! #     extensions::util::prompt::{run_prompt, window_prompt, Prompt},
+//! #     map,
+//! #     x11rb::RustConn,
+//! # };
+//! # use std::collections::HashMap;
+//! let bindings: HashMap<String, Box<dyn KeyEventHandler<RustConn>>> = map! {
+//!     map_keys: |k: &str| k.to_owned();
+//!
+//!     "M-r" => run_prompt(),
+//!     "M-w" => window_prompt(),
+//!     "M-g" => Prompt::new("greet:", |name, _, _| {
+//!         println!("hello {name}!");
+//!         Ok(())
+//!     })
+//!     .into_handler(),
+//! };
+//! ```
+use crate::{
+    builtin::actions::key_handler,
+    core::{
+        bindings::{KeyCode, KeyEventHandler, ModifierKey},
+        hints::{BindingHint, BindingHints, BindingHintsConfig},
+        hooks::{EventHook, NamedHook},
+        State,
+    },
+    util::spawn,
+    x::{ClientConfig, XConn, XConnExt, XEvent, XEventKind},
+    Result, Xid,
+};
+use std::{collections::HashMap, fmt, rc::Rc};
+use tracing::{debug, warn};
+
+const PROMPT_HOOK: &str = "prompt";
+
+type Completer<X> = Rc<dyn Fn(&str, &State<X>, &X) -> Vec<String>>;
+type Submit<X> = Rc<dyn Fn(String, &mut State<X>, &X) -> Result<()>>;
+
+/// A single line text input prompt.
+///
+/// See the [module level docs][self] for details.
+pub struct Prompt<X: XConn> {
+    label: String,
+    max_completions: usize,
+    complete: Completer<X>,
+    on_submit: Submit<X>,
+}
+
+impl<X: XConn> Clone for Prompt<X> {
+    fn clone(&self) -> Self {
+        Self {
+            label: self.label.clone(),
+            max_completions: self.max_completions,
+            complete: Rc::clone(&self.complete),
+            on_submit: Rc::clone(&self.on_submit),
+        }
+    }
+}
+
+impl<X: XConn> fmt::Debug for Prompt<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Prompt")
+            .field("label", &self.label)
+            .field("max_completions", &self.max_completions)
+            .finish()
+    }
+}
+
+impl<X: XConn + 'static> Prompt<X> {
+    /// Create a new [Prompt] that runs `on_submit` with the entered text.
+    pub fn new<F>(label: impl Into<String>, on_submit: F) -> Self
+    where
+        F: Fn(String, &mut State<X>, &X) -> Result<()> + 'static,
+    {
+        Self {
+            label: label.into(),
+            max_completions: 5,
+            complete: Rc::new(|_, _, _| Vec::new()),
+            on_submit: Rc::new(on_submit),
+        }
+    }
+
+    /// Set a function for generating the possible completions of the current input.
+    pub fn with_completions<F>(mut self, complete: F) -> Self
+    where
+        F: Fn(&str, &State<X>, &X) -> Vec<String> + 'static,
+    {
+        self.complete = Rc::new(complete);
+        self
+    }
+
+    /// Set the maximum number of completions to show below the input (defaults to 5).
+    pub fn with_max_completions(mut self, max_completions: usize) -> Self {
+        self.max_completions = max_completions;
+        self
+    }
+
+    /// Open this prompt, grabbing the keyboard until it is submitted or cancelled.
+    ///
+    /// Does nothing if another prompt is already open or if the [XConn] being used does not
+    /// support overlay windows.
+    pub fn show(self, state: &mut State<X>, x: &X) -> Result<()> {
+        if state.has_hook(PROMPT_HOOK) {
+            return Ok(());
+        }
+
+        let mut keys: HashMap<u8, String> = HashMap::new();
+        for (name, code) in x.keycodes()? {
+            let better = match keys.get(&code) {
+                Some(current) => priority(&name) < priority(current),
+                None => true,
+            };
+            if better {
+                keys.insert(code, name);
+            }
+        }
+
+        let conf = state.config.binding_hints.clone().unwrap_or_default();
+        let mut active = ActivePrompt {
+            prompt: self,
+            conf,
+            keys,
+            input: String::new(),
+            completions: Vec::new(),
+            overlay: Xid(0),
+        };
+        let r = active.hints(state).r;
+
+        active.overlay = match x.create_overlay(state.root(), r)? {
+            Some(id) => id,
+            None => {
+                warn!("overlays are not supported: unable to show prompt");
+                return Ok(());
+            }
+        };
+
+        debug!(label = %active.prompt.label, "opening prompt");
+        x.map(active.overlay)?;
+        x.set_client_config(active.overlay, &[ClientConfig::StackTop])?;
+        x.grab_keyboard()?;
+        active.update(state, x)?;
+        state.add_named_hook(PROMPT_HOOK, NamedHook::Event(Box::new(active)));
+
+        Ok(())
+    }
+
+    /// Convert this prompt into a [KeyEventHandler] that opens it.
+    pub fn into_handler(self) -> Box<dyn KeyEventHandler<X>> {
+        key_handler(move |state: &mut State<X>, x: &X| self.clone().show(state, x))
+    }
+}
+
+/// A prompt for running a program.
+pub fn run_prompt<X: XConn + 'static>() -> Box<dyn KeyEventHandler<X>> {
+    Prompt::new("run:", |cmd, _, _| spawn(cmd)).into_handler()
+}
+
+/// A prompt for focusing a client by its title.
+///
+/// Completions are the titles of all managed clients containing the input (ignoring case),
+/// with the client for the first of them being focused when the prompt is submitted.
+pub fn window_prompt<X: XConn + 'static>() -> Box<dyn KeyEventHandler<X>> {
+    Prompt::new("window:", |title, state: &mut State<X>, x: &X| {
+        let titles = matching_titles(&title, state, x);
+        let id = titles
+            .iter()
+            .find(|(_, t)| *t == title)
+            .or(titles.first())
+            .map(|&(id, _)| id);
+
+        match id {
+            Some(id) => x.modify_and_refresh(state, |cs| cs.focus_client(&id)),
+            None => Ok(()),
+        }
+    })
+    .with_completions(|input, state, x| {
+        matching_titles(input, state, x)
+            .into_iter()
+            .map(|(_, t)| t)
+            .collect()
+    })
+    .into_handler()
+}
+
+fn matching_titles<X: XConn>(input: &str, state: &State<X>, x: &X) -> Vec<(Xid, String)> {
+    let input = input.to_lowercase();
+
+    state
+        .client_set
+        .clients()
+        .filter_map(|&id| x.window_title(id).ok().map(|t| (id, t)))
+        .filter(|(_, t)| t.to_lowercase().contains(&input))
+        .collect()
+}
+
+// The prompt currently accepting input
+struct ActivePrompt<X: XConn> {
+    prompt: Prompt<X>,
+    conf: BindingHintsConfig,
+    keys: HashMap<u8, String>,
+    input: String,
+    completions: Vec<String>,
+    overlay: Xid,
+}
+
+impl<X: XConn> ActivePrompt<X> {
+    // The prompt is always drawn with space for the maximum number of completions so that the
+    // overlay does not need to be resized as the completions change.
+    fn hints(&self, state: &State<X>) -> BindingHints {
+        let mut entries = vec![BindingHint {
+            key: self.prompt.label.clone(),
+            description: format!("{}_", self.input),
+        }];
+        entries.extend(
+            self.completions
+                .iter()
+                .map(|c| c.as_str())
+                .chain(std::iter::repeat(""))
+                .take(self.prompt.max_completions)
+                .map(|c| BindingHint {
+                    key: String::new(),
+                    description: c.to_owned(),
+                }),
+        );
+
+        self.conf.hints(entries, state.focused_screen_rect())
+    }
+
+    fn update(&mut self, state: &State<X>, x: &X) -> Result<()> {
+        self.completions = (self.prompt.complete)(&self.input, state, x);
+
+        x.draw_binding_hints(self.overlay, &self.hints(state))
+    }
+
+    fn close(&mut self, state: &mut State<X>, x: &X) -> Result<()> {
+        state.remove_hook(PROMPT_HOOK);
+        x.ungrab_keyboard()?;
+
+        x.destroy_window(self.overlay)
+    }
+}
+
+impl<X: XConn> EventHook<X> for ActivePrompt<X> {
+    fn call(&mut self, event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
+        match event {
+            XEvent::KeyPress(code) => match key_input(*code, &self.keys) {
+                Some(Input::Char(c)) => {
+                    self.input.push(c);
+                    self.update(state, x)?;
+                }
+                Some(Input::Backspace) => {
+                    self.input.pop();
+                    self.update(state, x)?;
+                }
+                Some(Input::Clear) => {
+                    self.input.clear();
+                    self.update(state, x)?;
+                }
+                Some(Input::Complete) => {
+                    if let Some(c) = self.completions.first() {
+                        self.input = c.clone();
+                        self.update(state, x)?;
+                    }
+                }
+                Some(Input::Submit) => {
+                    self.close(state, x)?;
+                    let input = std::mem::take(&mut self.input);
+                    debug!(label = %self.prompt.label, %input, "prompt submitted");
+                    (self.prompt.on_submit)(input, state, x)?;
+                }
+                Some(Input::Cancel) => self.close(state, x)?,
+                None => (),
+            },
+
+            XEvent::Expose(e) if e.id == self.overlay => {
+                x.draw_binding_hints(self.overlay, &self.hints(state))?;
+            }
+
+            _ => return Ok(true),
+        }
+
+        Ok(false)
+    }
+
+    fn wants(&self, kind: XEventKind) -> bool {
+        matches!(kind, XEventKind::KeyPress | XEventKind::Expose)
+    }
+}
+
+// An edit to make to the input of a prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Input {
+    Char(char),
+    Backspace,
+    Clear,
+    Complete,
+    Submit,
+    Cancel,
+}
+
+fn key_input(code: KeyCode, keys: &HashMap<u8, String>) -> Option<Input> {
+    let shift = code.mask & u16::from(ModifierKey::Shift) != 0;
+    let ctrl = code.mask & u16::from(ModifierKey::Ctrl) != 0;
+    let name = keys.get(&code.code)?.as_str();
+
+    let input = match name {
+        "Return" | "KP_Enter" => Input::Submit,
+        "Escape" => Input::Cancel,
+        "BackSpace" => Input::Backspace,
+        "Tab" => Input::Complete,
+        "u" if ctrl => Input::Clear,
+        _ if ctrl => return None,
+        _ => {
+            let c = key_char(name)?;
+            Input::Char(if shift { c.to_ascii_uppercase() } else { c })
+        }
+    };
+
+    Some(input)
+}
+
+fn key_char(name: &str) -> Option<char> {
+    let c = match name {
+        "space" => ' ',
+        "minus" => '-',
+        "equal" => '=',
+        "period" => '.',
+        "comma" => ',',
+        "slash" => '/',
+        "backslash" => '\\',
+        "semicolon" => ';',
+        "apostrophe" => '\'',
+        "grave" => '`',
+        "bracketleft" => '[',
+        "bracketright" => ']',
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_alphanumeric() => c,
+                _ => return None,
+            }
+        }
+    };
+
+    Some(c)
+}
+
+// Multiple key names can share a key code (e.g. "a" and "A") so we prefer the names we are
+// able to type, lowercase letters first.
+fn priority(name: &str) -> u8 {
+    match key_char(name) {
+        Some(c) if c.is_ascii_lowercase() || c.is_ascii_digit() => 0,
+        Some(_) if name.len() > 1 => 1,
+        Some(_) => 2,
+        None => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::{atom::Atom, fixtures::ScriptedWm, property::Prop};
+    use simple_test_case::test_case;
+
+    const SHIFT: u16 = 1;
+    const CTRL: u16 = 4;
+
+    fn keys() -> HashMap<u8, String> {
+        [
+            (38, "a"),
+            (20, "minus"),
+            (65, "space"),
+            (36, "Return"),
+            (9, "Escape"),
+            (22, "BackSpace"),
+            (23, "Tab"),
+            (30, "u"),
+            (50, "Shift_L"),
+        ]
+        .into_iter()
+        .map(|(code, name)| (code, name.to_owned()))
+        .collect()
+    }
+
+    #[test_case(38, 0, Some(Input::Char('a')); "letter")]
+    #[test_case(38, SHIFT, Some(Input::Char('A')); "shifted letter")]
+    #[test_case(20, 0, Some(Input::Char('-')); "punctuation")]
+    #[test_case(65, 0, Some(Input::Char(' ')); "space")]
+    #[test_case(36, 0, Some(Input::Submit); "submit")]
+    #[test_case(9, 0, Some(Input::Cancel); "cancel")]
+    #[test_case(22, 0, Some(Input::Backspace); "backspace")]
+    #[test_case(23, 0, Some(Input::Complete); "complete")]
+    #[test_case(30, CTRL, Some(Input::Clear); "clear")]
+    #[test_case(38, CTRL, None; "other control keys")]
+    #[test_case(50, 0, None; "modifier")]
+    #[test_case(99, 0, None; "unknown")]
+    #[test]
+    fn key_input_is_parsed(code: u8, mask: u16, expected: Option<Input>) {
+        assert_eq!(key_input(KeyCode { mask, code }, &keys()), expected);
+    }
+
+    #[test]
+    fn lowercase_names_are_preferred() {
+        let mut names = ["A", "a", "exclam", "1"];
+        names.sort_by_key(|n| priority(n));
+
+        assert_eq!(&names[..2], &["a", "1"]);
+    }
+
+    #[test]
+    fn window_prompt_focuses_the_selected_client() {
+        let open = KeyCode { mask: 0, code: 42 };
+        let press = |code| XEvent::KeyPress(KeyCode { mask: 0, code });
+        let title = |t: &str| Prop::UTF8String(vec![t.to_owned()]);
+        let wm = ScriptedWm::new()
+            .with_keycodes(&[("v", 55), ("i", 31), ("Tab", 23), ("Return", 36)])
+            .with_conn(|conn| {
+                conn.with_prop(Xid(1), Atom::WmName.as_ref(), title("vim"))
+                    .with_prop(Xid(2), Atom::WmName.as_ref(), title("firefox"))
+            })
+            .with_clients(2)
+            .with_key(open, window_prompt())
+            .with_events([
+                XEvent::KeyPress(open),
+                press(55),
+                press(31),
+                press(23),
+                press(36),
+            ])
+            .run();
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(1)));
+        assert!(!wm.state.has_hook(PROMPT_HOOK));
+    }
+}
@@ -73,6 +73,7 @@ use ::x11rb::{
 use serde::{Deserialize, Serialize};
 use std::any::TypeId;
 
+pub mod backend;
 pub mod builtin;
 pub mod core;
 pub mod extensions;
@@ -89,6 +90,15 @@ pub use crate::core::Xid;
 /// Error variants from the core penrose library.
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    /// Two key binding specs resolve to the same key
+    #[error("'{first}' and '{second}' are bound to the same key")]
+    ConflictingKeyBindings {
+        /// The first of the conflicting specs
+        first: String,
+        /// The second of the conflicting specs
+        second: String,
+    },
+
     /// A custom error message from user code or extensions
     #[error("{0}")]
     Custom(String),
@@ -123,6 +133,19 @@ pub enum Error {
         reason: String,
     },
 
+    /// Image data did not contain the expected number of pixels for its dimensions
+    #[error("Invalid image data: expected {expected} pixels for a {w}x{h} image, got {got}")]
+    InvalidImageData {
+        /// The width of the image
+        w: u32,
+        /// The height of the image
+        h: u32,
+        /// The number of pixels required
+        expected: usize,
+        /// The number of pixels provided
+        got: usize,
+    },
+
     /// IO error
     #[error(transparent)]
     Io(#[from] std::io::Error),
@@ -157,6 +180,14 @@ pub enum Error {
     #[error(transparent)]
     ParseInt(#[from] std::num::ParseIntError),
 
+    /// Unable to grab the keyboard
+    #[error("Unable to grab the keyboard: {0}")]
+    KeyboardGrabFailed(String),
+
+    /// Unable to grab the pointer
+    #[error("Unable to grab the pointer: {0}")]
+    PointerGrabFailed(String),
+
     /// There was a problem initialising randr
     #[error("Error initialising randr: {0}")]
     Randr(String),
@@ -193,6 +224,18 @@ pub enum Error {
         type_id: TypeId,
     },
 
+    /// Another window manager is already running on the display
+    #[error("Another window manager is already running: set `replace_existing_wm` in your Config to replace it")]
+    WmAlreadyRunning,
+
+    /// The running window manager did not release the WM_Sn selection when asked to
+    #[error("Timed out waiting for the existing window manager to exit")]
+    WmReplaceTimeout,
+
+    /// The connection to the X server was lost and could not be re-established
+    #[error("The connection to the X server was lost")]
+    XConnectionLost,
+
     // TODO: These backend specific errors should be abstracted out to a
     //       set of common error variants that they can be mapped to without
     //       needing to extend the enum conditionally when flags are enabled
@@ -222,6 +265,23 @@ pub enum Error {
     X11rbX11Error(X11Error),
 }
 
+impl Error {
+    /// Whether or not this error indicates that the connection to the X server has been
+    /// lost, in which case no further requests can succeed.
+    pub fn is_fatal_connection_error(&self) -> bool {
+        match self {
+            Error::XConnectionLost => true,
+
+            #[cfg(feature = "x11rb-xcb")]
+            Error::X11rbConnection(_)
+            | Error::X11rbReplyError(ReplyError::ConnectionError(_))
+            | Error::X11rbReplyOrIdError(ReplyOrIdError::ConnectionError(_)) => true,
+
+            _ => false,
+        }
+    }
+}
+
 /// A Result where the error type is a penrose [Error]
 pub type Result<T> = std::result::Result<T, Error>;
 
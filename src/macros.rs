@@ -53,6 +53,58 @@ macro_rules! map {
     };
 }
 
+/// Parse a key binding spec into a [KeySpec][crate::core::bindings::KeySpec] at compile time.
+///
+/// Invalid specs (unknown or repeated modifiers, missing key names etc) are reported as
+/// compile errors rather than failing when your window manager starts. Key names are checked
+/// against the known X keysym names when the `keysyms` feature is enabled: without it they
+/// are only checked at runtime when the bindings are resolved against the keyboard mapping.
+/// ```
+/// # use penrose::key;
+/// let k = key!("M-S-Return");
+///
+/// assert_eq!(k.name(), "Return");
+/// ```
+///
+/// ```compile_fail
+/// # use penrose::key;
+/// let k = key!("M-X-Return");
+/// ```
+#[macro_export]
+macro_rules! key {
+    ($spec:literal) => {{
+        const SPEC: $crate::core::bindings::KeySpec = $crate::core::bindings::KeySpec::parse($spec);
+        SPEC
+    }};
+}
+
+/// Create a HashMap of key bindings with keys checked at compile time by [key][crate::key].
+///
+/// The resulting map can be passed directly to
+/// [parse_keybindings][crate::core::bindings::parse_keybindings].
+/// ```
+/// # use penrose::{builtin::actions::modify_with, keymap, x11rb::RustConn};
+/// # use penrose::core::bindings::{KeyEventHandler, KeySpec};
+/// # use std::collections::HashMap;
+/// let bindings: HashMap<KeySpec, Box<dyn KeyEventHandler<RustConn>>> = keymap! {
+///     "M-j" => modify_with(|cs| cs.focus_down()),
+///     "M-k" => modify_with(|cs| cs.focus_up()),
+/// };
+/// ```
+#[macro_export]
+macro_rules! keymap {
+    {} => { ::std::collections::HashMap::<$crate::core::bindings::KeySpec, _>::new() };
+
+    { $($spec:literal => $value:expr),+ $(,)? } => {
+        {
+            let mut _map: ::std::collections::HashMap<$crate::core::bindings::KeySpec, _> =
+                ::std::collections::HashMap::new();
+            $(_map.insert($crate::key!($spec), $value);)+
+            _map
+        }
+    };
+}
+
 // Helper for popping from the middle of a linked list
 #[doc(hidden)]
 #[macro_export]
@@ -9,6 +9,7 @@ where
 {
     pub screen: usize,
     pub tag: String,
+    pub layout: String,
     pub clients: Vec<C>,
 }
 
@@ -20,6 +21,7 @@ where
         Self {
             screen: s.index,
             tag: s.workspace.tag.clone(),
+            layout: s.workspace.layout_name(),
             clients: s.workspace.clients().copied().collect(),
         }
     }
@@ -36,6 +38,7 @@ where
     pub positions: Vec<(C, Rect)>,
     pub hidden_clients: Vec<C>,
     pub killed_clients: Vec<C>,
+    pub fullscreen_clients: Vec<C>,
 }
 
 impl<C> Snapshot<C>
@@ -46,6 +49,10 @@ where
         self.positions.iter().map(|(c, _)| c)
     }
 
+    pub(crate) fn screens(&self) -> impl Iterator<Item = &ScreenState<C>> {
+        once(&self.focused).chain(self.visible.iter())
+    }
+
     pub(crate) fn all_clients(&self) -> impl Iterator<Item = &C> {
         self.focused
             .clients
@@ -94,6 +101,13 @@ where
         before != after
     }
 
+    pub fn stacking_order_changed(&self) -> bool {
+        !self
+            .before
+            .visible_clients()
+            .eq(self.after.visible_clients())
+    }
+
     pub fn newly_focused_screen(&self) -> Option<usize> {
         if self.before.focused.screen != self.after.focused.screen {
             Some(self.after.focused.screen)
@@ -134,6 +148,52 @@ where
         self.after.killed_clients.iter()
     }
 
+    pub fn entered_fullscreen(&self) -> impl Iterator<Item = &C> {
+        let before: HashSet<_> = self.before.fullscreen_clients.iter().collect();
+
+        self.after
+            .fullscreen_clients
+            .iter()
+            .filter(move |c| !before.contains(c))
+    }
+
+    // Only includes clients that are still being managed
+    pub fn left_fullscreen(&self) -> impl Iterator<Item = &C> {
+        let after: HashSet<_> = self.after.fullscreen_clients.iter().collect();
+        let managed: HashSet<_> = self.after.all_clients().collect();
+
+        self.before
+            .fullscreen_clients
+            .iter()
+            .filter(move |c| !after.contains(c) && managed.contains(c))
+    }
+
+    // (tag, old layout, new layout) for workspaces that were visible before and after
+    pub fn layout_changes(&self) -> Vec<(&str, &str, &str)> {
+        self.after
+            .screens()
+            .filter_map(|a| {
+                let b = self.before.screens().find(|b| b.tag == a.tag)?;
+                (b.layout != a.layout).then_some((
+                    a.tag.as_str(),
+                    b.layout.as_str(),
+                    a.layout.as_str(),
+                ))
+            })
+            .collect()
+    }
+
+    // (screen index, old tag, new tag) for screens that are now showing a different workspace
+    pub fn workspace_switches(&self) -> Vec<(usize, &str, &str)> {
+        self.after
+            .screens()
+            .filter_map(|a| {
+                let b = self.before.screens().find(|b| b.screen == a.screen)?;
+                (b.tag != a.tag).then_some((a.screen, b.tag.as_str(), a.tag.as_str()))
+            })
+            .collect()
+    }
+
     pub fn previous_visible_tags(&self) -> HashSet<&str> {
         once(self.before.focused.tag.as_ref())
             .chain(self.before.visible.iter().map(|s| s.tag.as_ref()))
@@ -193,6 +253,22 @@ mod tests {
         assert_eq!(diff.client_changed_position(&1), expected)
     }
 
+    #[test_case(vec![1, 2, 3], false; "unchanged")]
+    #[test_case(vec![1, 3, 2], true; "reordered")]
+    #[test_case(vec![1, 2], true; "client removed")]
+    #[test_case(vec![1, 2, 3, 4], true; "client added")]
+    #[test]
+    fn stacking_order_changed_works(after: Vec<u8>, expected: bool) {
+        let mut s = test_stack_set(1, 1);
+        let positions = |ids: Vec<u8>| ids.into_iter().map(|c| (c, Rect::default())).collect();
+        let before = s.snapshot(positions(vec![1, 2, 3]));
+        let after = s.snapshot(positions(after));
+
+        let diff = Diff::new(before, after);
+
+        assert_eq!(diff.stacking_order_changed(), expected)
+    }
+
     #[test]
     fn drag_workspace_generates_correct_diff() {
         let mut s = test_stack_set_with_stacks(
@@ -213,6 +289,37 @@ mod tests {
         assert_eq!(diff.newly_focused_screen(), Some(1));
         assert_eq!(diff.focused_client(), Some(Xid(3)));
     }
+
+    #[test_case("1", vec![]; "focused tag")]
+    #[test_case("2", vec![]; "visible tag")]
+    #[test_case("3", vec![(0, "1", "3")]; "hidden tag")]
+    #[test]
+    fn workspace_switches_works(tag: &str, expected: Vec<(usize, &str, &str)>) {
+        let mut s = test_stack_set(5, 2);
+        let before = s.snapshot(vec![]);
+        s.focus_tag(tag);
+        let after = s.snapshot(vec![]);
+
+        let diff = Diff::new(before, after);
+
+        assert_eq!(diff.workspace_switches(), expected);
+    }
+
+    #[test]
+    fn layout_changes_works() {
+        let mut s = test_stack_set(5, 2);
+        s.screens.focus.workspace.layouts = stack!(
+            Box::new(crate::builtin::layout::MainAndStack::default()),
+            crate::builtin::layout::Monocle::boxed()
+        );
+        let before = s.snapshot(vec![]);
+        s.next_layout();
+        let after = s.snapshot(vec![]);
+
+        let diff = Diff::new(before, after);
+
+        assert_eq!(diff.layout_changes(), vec![("1", "Side", "Mono")]);
+    }
 }
 
 #[cfg(test)]
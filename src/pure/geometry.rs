@@ -77,6 +77,11 @@ impl RelativeRect {
         }
     }
 
+    /// The `(x, y, w, h)` proportions making up this RelativeRect.
+    pub fn components(&self) -> (f64, f64, f64, f64) {
+        (self.x, self.y, self.w, self.h)
+    }
+
     /// All available space within a given Rect
     pub fn fullscreen() -> Self {
         Self {
@@ -135,6 +140,21 @@ impl RelativeTo for Rect {
     }
 }
 
+/// One of the four corners of a [Rect].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Corner {
+    /// The top left corner
+    TopLeft,
+    /// The top right corner
+    TopRight,
+    /// The bottom left corner
+    BottomLeft,
+    /// The bottom right corner
+    #[default]
+    BottomRight,
+}
+
 /// An X window / screen position: top left corner + extent
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Default, Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -168,6 +188,21 @@ impl Rect {
         )
     }
 
+    /// The outermost point inside of this [Rect] in the direction of the given [Corner].
+    pub fn corner_point(&self, corner: Corner) -> Point {
+        let left = self.x;
+        let right = self.x + self.w.saturating_sub(1);
+        let top = self.y;
+        let bottom = self.y + self.h.saturating_sub(1);
+
+        match corner {
+            Corner::TopLeft => Point::new(left, top),
+            Corner::TopRight => Point::new(right, top),
+            Corner::BottomLeft => Point::new(left, bottom),
+            Corner::BottomRight => Point::new(right, bottom),
+        }
+    }
+
     /// The midpoint of this rectangle
     pub fn midpoint(&self) -> Point {
         Point {
@@ -236,6 +271,14 @@ impl Rect {
         }
     }
 
+    /// Check whether this Rect and `other` overlap
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.x + other.w
+            && other.x < self.x + self.w
+            && self.y < other.y + other.h
+            && other.y < self.y + self.h
+    }
+
     /// Check whether this Rect is physically larger than `other` regardless
     /// of position.
     pub fn is_larger_than(&self, other: &Rect) -> bool {
@@ -338,6 +381,15 @@ mod tests {
     use super::*;
     use simple_test_case::test_case;
 
+    #[test_case(Corner::TopLeft, Point::new(10, 20); "top left")]
+    #[test_case(Corner::TopRight, Point::new(39, 20); "top right")]
+    #[test_case(Corner::BottomLeft, Point::new(10, 59); "bottom left")]
+    #[test_case(Corner::BottomRight, Point::new(39, 59); "bottom right")]
+    #[test]
+    fn corner_point(corner: Corner, expected: Point) {
+        assert_eq!(Rect::new(10, 20, 30, 40).corner_point(corner), expected);
+    }
+
     #[test_case(1.5, Rect::new(10, 20, 45, 40); "scale up")]
     #[test_case(0.5, Rect::new(10, 20, 15, 40); "scale down")]
     #[test_case(1.0, Rect::new(10, 20, 30, 40); "unchanged")]
@@ -367,6 +419,18 @@ mod tests {
         assert!(!r1.contains(&r2));
     }
 
+    #[test_case(Rect::new(50, 50, 100, 100), true; "overlapping")]
+    #[test_case(Rect::new(20, 20, 10, 10), true; "contained")]
+    #[test_case(Rect::new(100, 0, 10, 10), false; "touching edge")]
+    #[test_case(Rect::new(200, 200, 10, 10), false; "disjoint")]
+    #[test]
+    fn intersects(other: Rect, expected: bool) {
+        let r = Rect::new(0, 0, 100, 100);
+
+        assert_eq!(r.intersects(&other), expected);
+        assert_eq!(other.intersects(&r), expected);
+    }
+
     #[test_case(Point::new(0, 0), false; "outside")]
     #[test_case(Point::new(30, 20), true; "inside")]
     #[test_case(Point::new(10, 20), true; "top left")]
@@ -10,7 +10,7 @@ mod stack_set;
 mod workspace;
 
 #[doc(inline)]
-pub use screen::Screen;
+pub use screen::{HotplugPolicy, OutputChanges, OutputInfo, ResizedOutput, Rotation, Screen};
 #[doc(inline)]
 pub use stack::{Position, Stack};
 #[doc(inline)]
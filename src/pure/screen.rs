@@ -1,4 +1,6 @@
 use crate::pure::{geometry::Rect, Workspace};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// A wrapper around a single [Workspace] that includes the physical screen
@@ -9,6 +11,8 @@ pub struct Screen<C> {
     /// The [Workspace] current visible on this screen
     pub workspace: Workspace<C>,
     pub(crate) r: Rect,
+    pub(crate) workarea: Option<Rect>,
+    pub(crate) output: Option<OutputInfo>,
 }
 
 impl<C: fmt::Display> fmt::Display for Screen<C> {
@@ -34,4 +38,197 @@ impl<C> Screen<C> {
     pub fn geometry(&self) -> Rect {
         self.r
     }
+
+    /// The region of this [Screen] available to layouts: its full [geometry][Screen::geometry]
+    /// minus any space reserved by dock windows.
+    ///
+    /// See [Config::respect_struts][crate::core::Config::respect_struts] for details.
+    pub fn workarea(&self) -> Rect {
+        self.workarea.unwrap_or(self.r)
+    }
+
+    /// The index of the X screen (root window) that this [Screen] belongs to.
+    ///
+    /// This is always 0 unless the X server is running with multiple (non-Xinerama) screens.
+    pub fn x_screen(&self) -> usize {
+        self.output.as_ref().map(|o| o.x_screen).unwrap_or(0)
+    }
+
+    /// Details of the physical output (monitor) backing this [Screen] if known.
+    pub fn output(&self) -> Option<&OutputInfo> {
+        self.output.as_ref()
+    }
+
+    /// The name of the output backing this [Screen] (e.g. "DP-1") if known.
+    pub fn name(&self) -> Option<&str> {
+        self.output.as_ref().map(|o| o.name.as_ref())
+    }
+}
+
+/// The rotation applied to an output by the X server.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    /// No rotation
+    #[default]
+    Normal,
+    /// Rotated 90 degrees counter-clockwise
+    Left,
+    /// Rotated 180 degrees
+    Inverted,
+    /// Rotated 90 degrees clockwise
+    Right,
+}
+
+/// Information about a physical output (monitor) as reported by the X server.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct OutputInfo {
+    /// The name of the output as reported by RandR (e.g. "DP-1", "HDMI-A-0")
+    pub name: String,
+    /// Whether or not this output is the primary output
+    pub primary: bool,
+    /// The name of the connected monitor as reported in its EDID data if available
+    pub monitor_name: Option<String>,
+    /// The rotation currently applied to this output
+    pub rotation: Rotation,
+    /// The scale factor applied to this output (1.0 being unscaled)
+    pub scale: f64,
+    /// The position and size of this output in absolute screen coordinates
+    pub r: Rect,
+    /// The index of the X screen (root window) that this output belongs to
+    pub x_screen: usize,
+}
+
+impl OutputInfo {
+    /// Construct an [OutputInfo] for an output that penrose has no additional
+    /// information about beyond its geometry.
+    pub fn from_geometry(name: impl Into<String>, primary: bool, r: Rect) -> Self {
+        Self {
+            name: name.into(),
+            primary,
+            monitor_name: None,
+            rotation: Rotation::Normal,
+            scale: 1.0,
+            r,
+            x_screen: 0,
+        }
+    }
+}
+
+/// How workspaces should be re-homed when the output they were being displayed on
+/// is disconnected.
+///
+/// In all cases, the workspace last displayed on each output is remembered and will
+/// be restored to that output when it is reconnected if it is not visible elsewhere.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotplugPolicy {
+    /// Display the workspace on the primary output, hiding the workspace that was there
+    Primary,
+    /// Display the workspace on the nearest remaining output, hiding the workspace that was there
+    Nearest,
+    /// Hide the workspace until the output is reconnected
+    #[default]
+    Remember,
+}
+
+/// An output that is still connected but whose position or size has changed.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResizedOutput {
+    /// The name of the output as reported by RandR (e.g. "DP-1", "HDMI-A-0")
+    pub name: String,
+    /// The previous position and size of this output in absolute screen coordinates
+    pub old: Rect,
+    /// The new position and size of this output in absolute screen coordinates
+    pub new: Rect,
+}
+
+/// The changes made to the set of connected outputs when the monitor layout is changed,
+/// matching outputs by name.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct OutputChanges {
+    /// Outputs that were not previously connected
+    pub added: Vec<OutputInfo>,
+    /// Outputs that are no longer connected
+    pub removed: Vec<OutputInfo>,
+    /// Outputs that are still connected but have been moved or resized
+    pub resized: Vec<ResizedOutput>,
+}
+
+impl OutputChanges {
+    /// Compute the changes required to go from the `old` set of outputs to the `new` one.
+    pub fn between(old: &[OutputInfo], new: &[OutputInfo]) -> Self {
+        let added = new
+            .iter()
+            .filter(|o| !old.iter().any(|p| p.name == o.name))
+            .cloned()
+            .collect();
+
+        let removed = old
+            .iter()
+            .filter(|o| !new.iter().any(|n| n.name == o.name))
+            .cloned()
+            .collect();
+
+        let resized = new
+            .iter()
+            .filter_map(|n| {
+                let o = old.iter().find(|o| o.name == n.name)?;
+                (o.r != n.r).then(|| ResizedOutput {
+                    name: n.name.clone(),
+                    old: o.r,
+                    new: n.r,
+                })
+            })
+            .collect();
+
+        Self {
+            added,
+            removed,
+            resized,
+        }
+    }
+
+    /// Whether or not there were no changes to the connected outputs.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.resized.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(name: &str, x: u32, w: u32) -> OutputInfo {
+        OutputInfo::from_geometry(name, false, Rect::new(x, 0, w, 100))
+    }
+
+    #[test]
+    fn output_changes_between_identical_outputs_are_empty() {
+        let outputs = vec![output("eDP-1", 0, 100), output("DP-1", 100, 100)];
+
+        assert!(OutputChanges::between(&outputs, &outputs).is_empty());
+    }
+
+    #[test]
+    fn output_changes_are_matched_by_name() {
+        let old = vec![output("eDP-1", 0, 100), output("DP-1", 100, 100)];
+        let new = vec![output("eDP-1", 0, 200), output("HDMI-1", 200, 100)];
+
+        let changes = OutputChanges::between(&old, &new);
+
+        assert_eq!(changes.added, vec![output("HDMI-1", 200, 100)]);
+        assert_eq!(changes.removed, vec![output("DP-1", 100, 100)]);
+        assert_eq!(
+            changes.resized,
+            vec![ResizedOutput {
+                name: "eDP-1".to_string(),
+                old: Rect::new(0, 0, 100, 100),
+                new: Rect::new(0, 0, 200, 100),
+            }]
+        );
+    }
 }
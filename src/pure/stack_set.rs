@@ -5,13 +5,12 @@ use crate::{
         diff::{ScreenState, Snapshot},
         geometry::{Rect, RelativeRect, RelativeTo},
         workspace::check_workspace_invariants,
-        Position, Screen, Stack, Workspace,
+        HotplugPolicy, OutputInfo, Position, Screen, Stack, Workspace,
     },
     stack, Error, Result, Xid,
 };
 use std::{
-    cmp::Ordering,
-    collections::{HashMap, LinkedList},
+    collections::{HashMap, HashSet, LinkedList},
     hash::Hash,
     mem::{swap, take},
 };
@@ -25,9 +24,14 @@ where
     pub(crate) screens: Stack<Screen<C>>, // Workspaces visible on screens
     pub(crate) hidden: LinkedList<Workspace<C>>, // Workspaces not currently on any screen
     pub(crate) floating: HashMap<C, RelativeRect>, // Floating windows
-    pub(crate) previous_tag: String,      // The last tag to be focused before the current one
+    pub(crate) previous_floating: HashMap<C, RelativeRect>, // Last floating position of sunk windows
+    pub(crate) previous_tag: String, // The last tag to be focused before the current one
     pub(crate) invisible_tags: Vec<String>, // Tags that should never be focused
     pub(crate) killed_clients: Vec<C>, // clients that have been removed and need processing on the X side
+    pub(crate) output_tags: HashMap<String, String>, // The last tag displayed on each named output
+    pub(crate) transient_for: HashMap<C, C>, // Transient clients and the client they belong to
+    pub(crate) window_groups: HashMap<C, C>, // Clients and the leader of their window group
+    pub(crate) fullscreen: HashMap<C, Option<RelativeRect>>, // Fullscreen clients and their prior floating position
 }
 
 impl<C> StackSet<C>
@@ -82,6 +86,8 @@ where
                     workspace,
                     index,
                     r,
+                    workarea: None,
+                    output: None,
                 },
             ));
 
@@ -91,9 +97,14 @@ where
             screens,
             hidden,
             floating,
+            previous_floating: HashMap::new(),
             previous_tag,
             invisible_tags: vec![],
             killed_clients: vec![],
+            output_tags: HashMap::new(),
+            transient_for: HashMap::new(),
+            window_groups: HashMap::new(),
+            fullscreen: HashMap::new(),
         })
     }
 
@@ -277,12 +288,73 @@ where
 
     /// Clear the floating status of a client, returning its previous preferred
     /// screen position if the client was known, otherwise `None`.
+    ///
+    /// The previous position is remembered and can be retrieved using
+    /// [StackSet::previous_float_position] if the client is floated again later.
     pub fn sink(&mut self, client: &C) -> Option<Rect> {
-        self.floating
-            .remove(client)
+        self.fullscreen.remove(client);
+        let rr = self.floating.remove(client)?;
+
+        // Fullscreen is not a meaningful floating position to restore
+        if rr != RelativeRect::fullscreen() {
+            self.previous_floating.insert(client.clone(), rr);
+        }
+
+        Some(rr.applied_to(&self.screens.focus.r))
+    }
+
+    /// The last floating position of a client that has since been sunk back into
+    /// tiling mode, if there is one.
+    pub fn previous_float_position(&self, client: &C) -> Option<Rect> {
+        self.previous_floating
+            .get(client)
             .map(|rr| rr.applied_to(&self.screens.focus.r))
     }
 
+    /// Check whether or not the given client is currently fullscreen.
+    pub fn is_fullscreen(&self, client: &C) -> bool {
+        self.fullscreen.contains_key(client)
+    }
+
+    /// Make the given client fullscreen, covering the entire [Screen] it is on and
+    /// stacking it above all other clients.
+    ///
+    /// The current tiled or floating state of the client is remembered and restored when
+    /// it leaves fullscreen using [StackSet::exit_fullscreen].
+    pub fn enter_fullscreen(&mut self, client: &C) {
+        if !self.contains(client) || self.is_fullscreen(client) {
+            return;
+        }
+
+        let prior = self.floating.get(client).copied();
+        self.fullscreen.insert(client.clone(), prior);
+        self.floating
+            .insert(client.clone(), RelativeRect::fullscreen());
+    }
+
+    /// Restore a fullscreen client to the tiled or floating state it was in before
+    /// entering fullscreen.
+    pub fn exit_fullscreen(&mut self, client: &C) {
+        match self.fullscreen.remove(client) {
+            Some(Some(rr)) => {
+                self.floating.insert(client.clone(), rr);
+            }
+            Some(None) => {
+                self.floating.remove(client);
+            }
+            None => (),
+        }
+    }
+
+    /// Toggle the fullscreen state of the given client.
+    pub fn toggle_fullscreen(&mut self, client: &C) {
+        if self.is_fullscreen(client) {
+            self.exit_fullscreen(client);
+        } else {
+            self.enter_fullscreen(client);
+        }
+    }
+
     /// Check whether a given tag currently has any floating windows present.
     ///
     /// Returns false if the tag given is unknown to this StackSet.
@@ -294,7 +366,13 @@ where
 
     /// Delete a client from this [StackSet].
     pub fn remove_client(&mut self, client: &C) -> Option<C> {
-        self.sink(client); // Clear any floating information we might have
+        // Clear any floating, transient and group information we might have
+        self.floating.remove(client);
+        self.previous_floating.remove(client);
+        self.transient_for.remove(client);
+        self.transient_for.retain(|_, parent| parent != client);
+        self.window_groups.remove(client);
+        self.fullscreen.remove(client);
 
         self.workspaces_mut()
             .map(|w| w.remove(client))
@@ -320,6 +398,24 @@ where
         }
     }
 
+    /// Delete the currently focused client along with all other members of its
+    /// window group (see [StackSet::group_members]).
+    ///
+    /// The following diff will send a kill client message to each of these clients
+    /// on refresh.
+    pub fn kill_focused_group(&mut self) {
+        let client = match self.current_client() {
+            Some(c) => c.clone(),
+            None => return,
+        };
+
+        for c in self.group_members(&client) {
+            if let Some(c) = self.remove_client(&c) {
+                self.killed_clients.push(c);
+            }
+        }
+    }
+
     /// Move the focused client of the current [Workspace] to the focused position
     /// of the workspace matching the provided `tag`.
     pub fn move_focused_to_tag(&mut self, tag: impl AsRef<str>) {
@@ -333,15 +429,48 @@ where
             Some(c) => c,
         };
 
-        self.insert_as_focus_for(tag, c)
+        let transients = self.transients_of(&c);
+        self.insert_as_focus_for(tag, c);
+        for t in transients.iter() {
+            self.move_single_client_to_tag(t, tag);
+        }
     }
 
     /// Move the given client to the focused position of the [Workspace] matching
     /// the provided `tag`. If the client is already on the target workspace it is
     /// moved to the focused position.
+    ///
+    /// Any transient clients belonging to the given client are moved along with it.
     pub fn move_client_to_tag(&mut self, client: &C, tag: impl AsRef<str>) {
         let tag = tag.as_ref();
 
+        self.move_single_client_to_tag(client, tag);
+        for t in self.transients_of(client).iter() {
+            self.move_single_client_to_tag(t, tag);
+        }
+    }
+
+    /// Move the focused client of the current [Workspace] along with all other members
+    /// of its window group (see [StackSet::group_members]) to the workspace matching the
+    /// provided `tag`. The focused client is left as the focus of the target workspace.
+    pub fn move_focused_group_to_tag(&mut self, tag: impl AsRef<str>) {
+        let tag = tag.as_ref();
+        if self.current_tag() == tag || !self.contains_tag(tag) {
+            return;
+        }
+
+        let client = match self.current_client() {
+            Some(c) => c.clone(),
+            None => return,
+        };
+
+        for c in self.group_members(&client).iter().filter(|&c| c != &client) {
+            self.move_client_to_tag(c, tag);
+        }
+        self.move_client_to_tag(&client, tag);
+    }
+
+    fn move_single_client_to_tag(&mut self, client: &C, tag: &str) {
         if !self.contains_tag(tag) {
             return;
         }
@@ -362,6 +491,90 @@ where
         self.insert_as_focus_for(tag, c)
     }
 
+    /// Record a client as being transient for another (typically a dialog belonging to a
+    /// main application window).
+    ///
+    /// Transient clients follow their parent when it is moved between workspaces and
+    /// are always stacked directly above it.
+    pub fn set_transient_for(&mut self, client: C, parent: C) {
+        if client != parent {
+            self.transient_for.insert(client, parent);
+        }
+    }
+
+    /// The client that the given client is transient for, if there is one.
+    pub fn transient_parent(&self, client: &C) -> Option<&C> {
+        self.transient_for.get(client)
+    }
+
+    /// The clients that are transient for the given client.
+    pub fn transients_of(&self, parent: &C) -> Vec<C> {
+        self.transient_for
+            .iter()
+            .filter(|&(_, p)| p == parent)
+            .map(|(c, _)| c.clone())
+            .collect()
+    }
+
+    /// Record a client as being a member of the window group led by `leader` (as set by
+    /// the client in its `WM_HINTS` property).
+    ///
+    /// The group leader itself need not be a managed client: applications such as GIMP
+    /// commonly use an unmapped window as the leader for all of their top level windows.
+    pub fn set_window_group(&mut self, client: C, leader: C) {
+        self.window_groups.insert(client, leader);
+    }
+
+    /// The leader of the window group the given client belongs to, if there is one.
+    pub fn window_group(&self, client: &C) -> Option<&C> {
+        self.window_groups.get(client)
+    }
+
+    /// All clients in this [StackSet] that share a window group with the given client,
+    /// including the client itself.
+    ///
+    /// If the client is not part of a window group then only the client itself is
+    /// returned, and if it is not present in this [StackSet] then the result is empty.
+    pub fn group_members(&self, client: &C) -> Vec<C> {
+        if !self.contains(client) {
+            return vec![];
+        }
+
+        // Group leaders that are managed clients are unlikely to have a group of their own
+        let leader = match self.window_groups.get(client) {
+            Some(leader) => leader,
+            None if self.window_groups.values().any(|l| l == client) => client,
+            None => return vec![client.clone()],
+        };
+
+        self.clients()
+            .filter(|&c| c == leader || self.window_groups.get(c) == Some(leader))
+            .cloned()
+            .collect()
+    }
+
+    /// Swap the positions of two clients, which may be on different workspaces.
+    ///
+    /// Does nothing if either client is not present in this [StackSet].
+    pub fn swap_clients(&mut self, a: &C, b: &C) {
+        if a == b || !self.contains(a) || !self.contains(b) {
+            return;
+        }
+
+        let (a, b) = (a.clone(), b.clone());
+        for w in self.workspaces_mut() {
+            if let Some(s) = w.stack.as_mut() {
+                for c in s.iter_mut() {
+                    if *c == a {
+                        *c = b.clone();
+                    } else if *c == b {
+                        *c = a.clone();
+                    }
+                }
+            }
+        }
+    }
+
     /// Move the given client to the focused position of the current [Workspace].
     /// If the client is already on the target workspace it is moved to the focused position.
     pub fn move_client_to_current_tag(&mut self, client: &C) {
@@ -416,6 +629,24 @@ where
         wss.into_iter()
     }
 
+    /// The tag of the next empty [Workspace] after the current one in id order (wrapping
+    /// around), skipping any that have been marked as being invisible.
+    pub fn next_empty_tag(&self) -> Option<String> {
+        let wss: Vec<_> = self.ordered_workspaces().collect();
+        let current = self.current_tag();
+        let start = wss
+            .iter()
+            .position(|w| w.tag == current)
+            .map_or(0, |i| i + 1);
+
+        wss.iter()
+            .cycle()
+            .skip(start)
+            .take(wss.len())
+            .find(|w| w.is_empty() && w.tag != current)
+            .map(|w| w.tag.clone())
+    }
+
     /// Find the tag of the [Workspace] currently displayed on [Screen] `index`.
     ///
     /// Returns [None] if the index is out of bounds
@@ -643,6 +874,12 @@ where
         self.screens.iter_mut()
     }
 
+    /// Find the [Screen] backed by the output with the given name (e.g. "DP-1") if
+    /// there is one.
+    pub fn screen_with_name(&self, name: &str) -> Option<&Screen<C>> {
+        self.screens.iter().find(|s| s.name() == Some(name))
+    }
+
     /// Iterate over each [Workspace] in this [StackSet] in an arbitrary order.
     pub fn workspaces(&self) -> impl Iterator<Item = &Workspace<C>> {
         self.screens
@@ -714,7 +951,7 @@ impl StackSet<Xid> {
         let mut positions: Vec<(Xid, Rect)> = Vec::new();
 
         for s in self.screens.iter_mut() {
-            let r = s.geometry();
+            let r = s.workarea();
             let tag = &s.workspace.tag;
             let true_stack = s.workspace.stack.as_ref();
             let tiling =
@@ -729,7 +966,55 @@ impl StackSet<Xid> {
 
         positions.extend(float_positions);
 
-        positions
+        let positions = self.stack_transients_above_parents(positions);
+
+        self.stack_fullscreen_clients_on_top(positions)
+    }
+
+    // Fullscreen clients cover the entire screen they are on (rather than being positioned
+    // relative to the focused screen like other floating clients) and are kept on top.
+    fn stack_fullscreen_clients_on_top(&self, positions: Vec<(Xid, Rect)>) -> Vec<(Xid, Rect)> {
+        if self.fullscreen.is_empty() {
+            return positions;
+        }
+
+        let screen_rects: HashMap<Xid, Rect> = self
+            .screens
+            .iter()
+            .flat_map(|s| s.workspace.clients().map(move |&c| (c, s.r)))
+            .filter(|(c, _)| self.fullscreen.contains_key(c))
+            .collect();
+
+        let (fullscreen, mut ordered): (Vec<_>, Vec<_>) = positions
+            .into_iter()
+            .partition(|(c, _)| screen_rects.contains_key(c));
+
+        ordered.extend(fullscreen.into_iter().map(|(c, _)| (c, screen_rects[&c])));
+
+        ordered
+    }
+
+    // Transient clients are moved to be directly above their parent (and any other transients
+    // of the same parent that are already in place).
+    fn stack_transients_above_parents(&self, positions: Vec<(Xid, Rect)>) -> Vec<(Xid, Rect)> {
+        let present: HashSet<Xid> = positions.iter().map(|&(c, _)| c).collect();
+        let (transients, mut ordered): (Vec<_>, Vec<_>) = positions.into_iter().partition(
+            |(c, _)| matches!(self.transient_for.get(c), Some(p) if present.contains(p)),
+        );
+
+        for (c, r) in transients {
+            let parent = self.transient_for[&c];
+            let i = ordered
+                .iter()
+                .rposition(|(o, _)| *o == parent || self.transient_for.get(o) == Some(&parent));
+
+            match i {
+                Some(i) => ordered.insert(i + 1, (c, r)),
+                None => ordered.push((c, r)),
+            }
+        }
+
+        ordered
     }
 
     pub(crate) fn position_and_snapshot(&mut self) -> Snapshot<Xid> {
@@ -737,49 +1022,184 @@ impl StackSet<Xid> {
         self.snapshot(positions)
     }
 
+    // Screens without output details are matched by index using generated names.
+    #[cfg(test)]
     pub(crate) fn update_screens(&mut self, rects: Vec<Rect>) -> Result<()> {
-        let n_old = self.screens.len();
-        let n_new = rects.len();
+        for (i, s) in self.screens.iter_mut().enumerate() {
+            if s.output.is_none() {
+                s.output = Some(OutputInfo::from_geometry(
+                    format!("screen-{i}"),
+                    i == 0,
+                    s.r,
+                ));
+            }
+        }
+
+        let outputs = rects
+            .into_iter()
+            .enumerate()
+            .map(|(i, r)| OutputInfo::from_geometry(format!("screen-{i}"), i == 0, r))
+            .collect();
 
-        if n_new == 0 {
+        self.update_screens_with_outputs(outputs, HotplugPolicy::Remember)
+    }
+
+    // Outputs are assumed to be in the same order as the screen rects that were
+    // used to construct this StackSet.
+    pub(crate) fn update_screen_outputs(&mut self, outputs: Vec<OutputInfo>) {
+        for (s, o) in self.screens.iter_mut().zip(outputs) {
+            s.output = Some(o);
+        }
+    }
+
+    // Reconcile the current screens against a new set of outputs, matching screens
+    // by output name rather than by index.
+    //
+    //   - Outputs that are still present keep the workspace they were showing.
+    //   - Outputs that have returned are given back the workspace they last showed
+    //     if it is not currently on another screen.
+    //   - Remaining new outputs are filled with workspaces from removed outputs and
+    //     then from the hidden workspaces.
+    //   - Any workspaces left over from removed outputs are re-homed according to
+    //     the given [HotplugPolicy].
+    //
+    // Focus stays with the workspace that was focused before if it is still visible,
+    // otherwise it moves to the primary output.
+    pub(crate) fn update_screens_with_outputs(
+        &mut self,
+        outputs: Vec<OutputInfo>,
+        policy: HotplugPolicy,
+    ) -> Result<()> {
+        if outputs.is_empty() {
             return Err(Error::NoScreens);
         }
 
-        match n_new.cmp(&n_old) {
-            // Just a change in dimensions
-            Ordering::Equal => (),
-
-            // We have more screens now: pull in hidden workspaces to fill them
-            // If we run out of workspaces we backfill using generated defaults
-            Ordering::Greater => {
-                let padding = self.take_from_hidden(n_new - n_old);
-                for (n, w) in padding.into_iter().enumerate() {
-                    self.screens.insert_at(
-                        Position::Tail,
-                        Screen {
-                            workspace: w,
-                            index: n_old + n,
-                            r: Rect::default(),
-                        },
-                    );
+        let next_id = self.workspaces().map(|w| w.id).max().unwrap_or(0) + 1;
+        let focused_tag = self.screens.focus.workspace.tag.clone();
+        let mut removed = take(&mut self.screens).flatten();
+
+        // Screens displaying a workspace that was re-homed from another output keep their
+        // existing entry so that we can swap back when that output returns.
+        for s in removed.iter() {
+            if let Some(name) = s.name() {
+                let tag = &s.workspace.tag;
+                if !self.output_tags.iter().any(|(n, t)| n != name && t == tag) {
+                    self.output_tags.insert(name.to_string(), tag.clone());
                 }
             }
+        }
+
+        // Outputs that are still present keep their current workspace
+        let mut slots: Vec<Option<Workspace<Xid>>> = Vec::with_capacity(outputs.len());
+        for o in outputs.iter() {
+            let ix = removed.iter().position(|s| s.name() == Some(&o.name));
+            slots.push(ix.map(|i| removed.remove(i).workspace));
+        }
 
-            // We have fewer screens now: focus moves to the first screen and
-            // we drop from the back of the stack
-            Ordering::Less => {
-                let mut raw = take(&mut self.screens).flatten();
-                let removed = raw.split_off(n_new);
-                self.hidden.extend(removed.into_iter().map(|s| s.workspace));
-                self.screens = Stack::from_iter_unchecked(raw);
+        // Returning outputs are given back the workspace they last displayed. If that workspace
+        // was re-homed to another screen, that screen is given back its own previous workspace.
+        for i in 0..slots.len() {
+            let tag = match (&slots[i], self.output_tags.get(&outputs[i].name)) {
+                (None, Some(tag)) => tag.clone(),
+                _ => continue,
+            };
+
+            if let Some(j) = removed.iter().position(|s| s.workspace.tag == tag) {
+                slots[i] = Some(removed.remove(j).workspace);
+                continue;
+            } else if let Some(w) = pop_where!(self, hidden, |w: &Workspace<Xid>| w.tag == tag) {
+                slots[i] = Some(w);
+                continue;
+            }
+
+            let j = match slots
+                .iter()
+                .position(|s| matches!(s, Some(w) if w.tag == tag))
+            {
+                Some(j) => j,
+                None => continue,
+            };
+
+            let previous = match self.output_tags.get(&outputs[j].name) {
+                Some(t) if *t != tag => t.clone(),
+                _ => continue,
+            };
+
+            if let Some(w) = pop_where!(self, hidden, |w: &Workspace<Xid>| w.tag == previous) {
+                slots[i] = slots[j].replace(w);
             }
         }
 
-        // self.screens.len() is now correct so update the screen dimensions
-        for (s, r) in self.screens.iter_mut().zip(rects) {
-            s.r = r;
+        // Fill any remaining outputs from removed screens and then from hidden workspaces
+        for slot in slots.iter_mut().filter(|s| s.is_none()) {
+            if removed.is_empty() {
+                break;
+            }
+            *slot = Some(removed.remove(0).workspace);
+        }
+
+        let n_empty = slots.iter().filter(|s| s.is_none()).count();
+        let mut padding = self.take_from_hidden(n_empty, next_id).into_iter();
+        for slot in slots.iter_mut().filter(|s| s.is_none()) {
+            *slot = padding.next();
+        }
+
+        // Re-home anything left over from removed screens
+        let primary = outputs.iter().position(|o| o.primary).unwrap_or(0);
+        let mut rehomed = Vec::new();
+
+        for s in removed {
+            let target = match policy {
+                HotplugPolicy::Remember => None,
+                HotplugPolicy::Primary => Some(primary),
+                HotplugPolicy::Nearest => {
+                    let p = s.r.midpoint();
+                    outputs
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, o)| {
+                            let q = o.r.midpoint();
+                            let (dx, dy) = (p.x as i64 - q.x as i64, p.y as i64 - q.y as i64);
+                            dx * dx + dy * dy
+                        })
+                        .map(|(i, _)| i)
+                }
+            };
+
+            match target {
+                Some(i) if !rehomed.contains(&i) => {
+                    rehomed.push(i);
+                    if let Some(displaced) = slots[i].replace(s.workspace) {
+                        self.output_tags
+                            .insert(outputs[i].name.clone(), displaced.tag.clone());
+                        self.hidden.push_front(displaced);
+                    }
+                }
+                _ => self.hidden.push_front(s.workspace),
+            }
         }
 
+        let screens: Vec<Screen<Xid>> = slots
+            .into_iter()
+            .zip(outputs)
+            .enumerate()
+            .map(|(index, (workspace, o))| Screen {
+                workspace: workspace.expect("all slots to be filled"),
+                index,
+                r: o.r,
+                workarea: None,
+                output: Some(o),
+            })
+            .collect();
+
+        let focus = screens
+            .iter()
+            .position(|s| s.workspace.tag == focused_tag)
+            .unwrap_or(primary);
+
+        self.screens = Stack::from_iter_unchecked(screens);
+        self.focus_screen(focus);
+
         Ok(())
     }
 
@@ -789,8 +1209,10 @@ impl StackSet<Xid> {
     // with ones we generate with default values. In doing this we need to make sure
     // that any _invisible_ workspaces are kept to one side so that they do not end
     // up focused on a screen by mistake.
-    fn take_from_hidden(&mut self, n: usize) -> Vec<Workspace<Xid>> {
-        let next_id = self.workspaces().map(|w| w.id).max().unwrap_or(0) + 1;
+    //
+    // NOTE: `next_id` needs to be computed by the caller as the workspaces on screens
+    //       are not available while they are being reconciled.
+    fn take_from_hidden(&mut self, n: usize, next_id: usize) -> Vec<Workspace<Xid>> {
         let mut tmp = Vec::with_capacity(self.hidden.len());
         let mut hidden = LinkedList::new();
 
@@ -844,6 +1266,7 @@ where
             positions,
             hidden_clients: self.hidden_workspace_clients().copied().collect(),
             killed_clients: take(&mut self.killed_clients),
+            fullscreen_clients: self.fullscreen.keys().cloned().collect(),
         }
     }
 }
@@ -951,6 +1374,18 @@ pub mod tests {
         }
     }
 
+    #[test_case("1", vec![Some(stack!(1)), None, Some(stack!(2)), None], Some("2"); "next")]
+    #[test_case("2", vec![Some(stack!(1)), None, Some(stack!(2)), None], Some("4"); "skips current")]
+    #[test_case("4", vec![Some(stack!(1)), None, Some(stack!(2)), None], Some("2"); "wraps")]
+    #[test_case("1", vec![Some(stack!(1)), Some(stack!(2))], None; "no empty workspaces")]
+    #[test]
+    fn next_empty_tag(tag: &str, stacks: Vec<Option<Stack<u8>>>, expected: Option<&str>) {
+        let mut s = test_stack_set_with_stacks(stacks, 1);
+        s.focus_tag(tag);
+
+        assert_eq!(s.next_empty_tag().as_deref(), expected);
+    }
+
     #[test_case("1", &["1", "2"]; "current focused workspace")]
     #[test_case("2", &["1", "2"]; "visible on other screen")]
     #[test_case("3", &["3", "2"]; "currently hidden")]
@@ -1218,6 +1653,155 @@ pub mod tests {
         assert_eq!(s.workspace(tag).unwrap().focus(), Some(&client));
     }
 
+    #[test_case(0, 3, Some(stack!([3], 1, [2, 0])), Some(stack!([6, 7], 8)); "same workspace")]
+    #[test_case(1, 7, Some(stack!([0], 7, [2, 3])), Some(stack!([6, 1], 8)); "different workspaces")]
+    #[test_case(1, 9, Some(stack!([0], 1, [2, 3])), Some(stack!([6, 7], 8)); "unknown client")]
+    #[test]
+    fn swap_clients(a: u8, b: u8, first: Option<Stack<u8>>, second: Option<Stack<u8>>) {
+        let mut s = test_stack_set_with_stacks(
+            vec![Some(stack!([0], 1, [2, 3])), Some(stack!([6, 7], 8))],
+            1,
+        );
+
+        s.swap_clients(&a, &b);
+
+        assert_eq!(s.workspace("1").unwrap().stack, first);
+        assert_eq!(s.workspace("2").unwrap().stack, second);
+    }
+
+    #[test_case(true; "focused")]
+    #[test_case(false; "by id")]
+    #[test]
+    fn transients_follow_their_parent(focused: bool) {
+        let mut s = test_stack_set_with_stacks(vec![Some(stack!([1], 2, [3])), None], 1);
+        s.set_transient_for(3, 2);
+
+        if focused {
+            s.move_focused_to_tag("2");
+        } else {
+            s.move_client_to_tag(&2, "2");
+        }
+
+        assert_eq!(s.workspace("1").unwrap().stack, Some(stack!(1)));
+        let moved: Vec<u8> = s.workspace("2").unwrap().clients().copied().collect();
+        assert!(moved.contains(&2) && moved.contains(&3));
+    }
+
+    #[test]
+    fn removing_a_client_clears_transient_state() {
+        let mut s = test_stack_set_with_stacks(vec![Some(stack!(1, [2, 3]))], 1);
+        s.set_transient_for(2, 1);
+        s.set_transient_for(3, 2);
+
+        s.remove_client(&2);
+
+        assert_eq!(s.transient_parent(&2), None);
+        assert_eq!(s.transient_parent(&3), None);
+        assert!(s.transients_of(&1).is_empty());
+    }
+
+    #[test_case(1, vec![1, 2, 4]; "member")]
+    #[test_case(9, vec![]; "unknown client")]
+    #[test_case(3, vec![3]; "no group")]
+    #[test]
+    fn group_members(client: u8, expected: Vec<u8>) {
+        // 1 is the group leader and has no group hint set of its own
+        let mut s = test_stack_set_with_stacks(vec![Some(stack!(1, [2, 3])), Some(stack!(4))], 1);
+        s.set_window_group(2, 1);
+        s.set_window_group(4, 1);
+
+        let mut members = s.group_members(&client);
+        members.sort();
+
+        assert_eq!(members, expected);
+    }
+
+    #[test]
+    fn move_focused_group_to_tag_moves_all_members() {
+        let mut s = test_stack_set_with_stacks(vec![Some(stack!([1], 2, [3])), None], 1);
+        s.set_window_group(1, 10);
+        s.set_window_group(2, 10);
+
+        s.move_focused_group_to_tag("2");
+
+        assert_eq!(s.workspace("1").unwrap().stack, Some(stack!(3)));
+        let ws = s.workspace("2").unwrap();
+        assert_eq!(ws.focus(), Some(&2));
+        assert!(ws.contains(&1));
+    }
+
+    #[test]
+    fn kill_focused_group_kills_all_members() {
+        let mut s = test_stack_set_with_stacks(vec![Some(stack!([1], 2, [3]))], 1);
+        s.set_window_group(2, 3);
+
+        s.kill_focused_group();
+
+        assert_eq!(s.workspace("1").unwrap().stack, Some(stack!(1)));
+        let mut killed = s.killed_clients.clone();
+        killed.sort();
+        assert_eq!(killed, vec![2, 3]);
+        assert_eq!(s.window_group(&2), None);
+    }
+
+    #[test_case(None; "tiled")]
+    #[test_case(Some(Rect::new(10, 20, 30, 40)); "floating")]
+    #[test]
+    fn exiting_fullscreen_restores_prior_state(prior: Option<Rect>) {
+        let mut s = test_stack_set_with_stacks(vec![Some(stack!(1, [2]))], 1);
+        if let Some(r) = prior {
+            s.float(1, r).unwrap();
+        }
+
+        s.toggle_fullscreen(&1);
+        assert!(s.is_fullscreen(&1));
+        assert_eq!(s.floating.get(&1), Some(&RelativeRect::fullscreen()));
+
+        s.toggle_fullscreen(&1);
+        assert!(!s.is_fullscreen(&1));
+        let restored = s
+            .floating
+            .get(&1)
+            .map(|rr| rr.applied_to(&s.screens.focus.r));
+        assert_eq!(restored, prior);
+    }
+
+    #[test]
+    fn sinking_a_fullscreen_client_clears_fullscreen_state() {
+        let mut s = test_stack_set_with_stacks(vec![Some(stack!(1, [2]))], 1);
+        s.enter_fullscreen(&1);
+
+        s.sink(&1);
+
+        assert!(!s.is_fullscreen(&1));
+        assert!(s.floating.is_empty());
+    }
+
+    #[test]
+    fn sunk_clients_remember_their_float_position() {
+        let mut s = test_stack_set_with_stacks(vec![Some(stack!(1, [2]))], 1);
+        let r = Rect::new(100, 200, 300, 400);
+
+        s.float(1, r).unwrap();
+        assert_eq!(s.previous_float_position(&1), None);
+
+        s.sink(&1);
+        assert_eq!(s.previous_float_position(&1), Some(r));
+
+        s.remove_client(&1);
+        assert_eq!(s.previous_float_position(&1), None);
+    }
+
+    #[test]
+    fn sinking_from_fullscreen_is_not_remembered() {
+        let mut s = test_stack_set_with_stacks(vec![Some(stack!(1, [2]))], 1);
+
+        s.float_unchecked(1, RelativeRect::fullscreen());
+        s.sink(&1);
+
+        assert_eq!(s.previous_float_position(&1), None);
+    }
+
     mod visible_client_positions {
         use super::*;
 
@@ -1226,6 +1810,39 @@ pub mod tests {
             positions.iter().map(|&(id, _)| *id).collect()
         }
 
+        #[test]
+        fn transients_are_stacked_above_their_parent() {
+            let mut s = test_xid_stack_set(5, 1);
+
+            for n in 0..4 {
+                s.insert(Xid(n));
+            }
+            s.float_unchecked(Xid(2), Rect::new(0, 0, 10, 10));
+            s.set_transient_for(Xid(0), Xid(3));
+            s.set_transient_for(Xid(1), Xid(2));
+
+            let order = stack_order(&mut s);
+            let pos = |n: u32| order.iter().position(|&c| c == n).unwrap();
+
+            assert_eq!(pos(0), pos(3) + 1);
+            assert_eq!(pos(1), pos(2) + 1);
+        }
+
+        #[test]
+        fn fullscreen_clients_cover_their_screen_and_are_on_top() {
+            let mut s = test_xid_stack_set(5, 1);
+
+            for n in 0..4 {
+                s.insert(Xid(n));
+            }
+            s.float_unchecked(Xid(2), Rect::new(0, 0, 10, 10));
+            s.enter_fullscreen(&Xid(1));
+
+            let positions = s.visible_client_positions();
+
+            assert_eq!(positions.last(), Some(&(Xid(1), s.screens.focus.r)));
+        }
+
         #[test]
         fn floating_client_positions_are_respected() {
             let mut s = test_xid_stack_set(5, 2);
@@ -1358,6 +1975,124 @@ pub mod tests {
 
         assert!(matches!(res, Err(Error::NoScreens)));
     }
+
+    #[test]
+    fn screens_can_be_found_by_output_name() {
+        let mut ss: StackSet<Xid> =
+            StackSet::try_new(LayoutStack::default(), ["1", "2"], vec![Rect::default(); 2])
+                .expect("enough workspaces to cover the number of screens");
+
+        assert!(ss.screen_with_name("DP-1").is_none());
+
+        ss.update_screen_outputs(vec![
+            OutputInfo::from_geometry("eDP-1", true, Rect::default()),
+            OutputInfo::from_geometry("DP-1", false, Rect::default()),
+        ]);
+
+        let s = ss.screen_with_name("DP-1").expect("screen to be found");
+        assert_eq!(s.index(), 1);
+        assert_eq!(s.workspace.tag, "2");
+    }
+
+    fn output(name: &str, primary: bool, x: u32) -> OutputInfo {
+        OutputInfo::from_geometry(name, primary, Rect::new(x, 0, 100, 100))
+    }
+
+    fn stack_set_with_outputs(outputs: Vec<OutputInfo>) -> StackSet<Xid> {
+        let mut ss: StackSet<Xid> = StackSet::try_new(
+            LayoutStack::default(),
+            ["1", "2", "3", "4"],
+            outputs.iter().map(|o| o.r),
+        )
+        .expect("enough workspaces to cover the number of screens");
+        ss.update_screen_outputs(outputs);
+
+        ss
+    }
+
+    fn tags_by_output(ss: &StackSet<Xid>) -> Vec<(String, String)> {
+        let mut tags: Vec<_> = ss
+            .screens()
+            .map(|s| (s.name().unwrap().to_string(), s.workspace.tag.clone()))
+            .collect();
+        tags.sort();
+
+        tags
+    }
+
+    fn owned(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn updating_outputs_matches_screens_by_name() {
+        let mut ss = stack_set_with_outputs(vec![output("A", true, 0), output("B", false, 100)]);
+
+        ss.update_screens_with_outputs(
+            vec![output("B", false, 100), output("A", true, 0)],
+            HotplugPolicy::Remember,
+        )
+        .unwrap();
+
+        assert_eq!(tags_by_output(&ss), owned(&[("A", "1"), ("B", "2")]));
+    }
+
+    #[test_case(HotplugPolicy::Remember, &[("A", "1"), ("B", "2")], "1"; "remember")]
+    #[test_case(HotplugPolicy::Primary, &[("A", "3"), ("B", "2")], "3"; "primary")]
+    #[test_case(HotplugPolicy::Nearest, &[("A", "1"), ("B", "3")], "3"; "nearest")]
+    #[test]
+    fn removed_outputs_are_rehomed_using_policy(
+        policy: HotplugPolicy,
+        expected: &[(&str, &str)],
+        focused_tag: &str,
+    ) {
+        let mut ss = stack_set_with_outputs(vec![
+            output("A", true, 0),
+            output("B", false, 100),
+            output("C", false, 200),
+        ]);
+        ss.focus_screen(2);
+
+        ss.update_screens_with_outputs(vec![output("A", true, 0), output("B", false, 100)], policy)
+            .unwrap();
+
+        assert_eq!(tags_by_output(&ss), owned(expected));
+        assert_eq!(ss.current_tag(), focused_tag);
+    }
+
+    #[test_case(HotplugPolicy::Remember; "remember")]
+    #[test_case(HotplugPolicy::Primary; "primary")]
+    #[test_case(HotplugPolicy::Nearest; "nearest")]
+    #[test]
+    fn reconnected_outputs_have_their_workspace_restored(policy: HotplugPolicy) {
+        let outputs = vec![
+            output("A", true, 0),
+            output("B", false, 100),
+            output("C", false, 200),
+        ];
+        let mut ss = stack_set_with_outputs(outputs.clone());
+
+        ss.update_screens_with_outputs(vec![output("A", true, 0)], policy)
+            .unwrap();
+        ss.update_screens_with_outputs(outputs, policy).unwrap();
+
+        assert_eq!(
+            tags_by_output(&ss),
+            owned(&[("A", "1"), ("B", "2"), ("C", "3")])
+        );
+    }
+
+    #[test]
+    fn updating_outputs_with_empty_vec_is_an_error() {
+        let mut ss = stack_set_with_outputs(vec![output("A", true, 0)]);
+
+        let res = ss.update_screens_with_outputs(vec![], HotplugPolicy::Remember);
+
+        assert!(matches!(res, Err(Error::NoScreens)));
+    }
 }
 
 #[cfg(test)]
@@ -130,6 +130,18 @@ impl<T> Workspace<T> {
     pub fn previous_layout(&mut self) {
         self.layouts.focus_up();
     }
+
+    /// The names of all layouts available to this workspace.
+    pub fn layout_names(&self) -> Vec<String> {
+        self.layouts.names()
+    }
+
+    /// Switch to the first available layout for this workspace with the given name.
+    ///
+    /// Returns `false` and leaves the current layout unchanged if there is no such layout.
+    pub fn focus_layout(&mut self, name: &str) -> bool {
+        self.layouts.focus_layout(name)
+    }
 }
 
 impl<T: PartialEq> Workspace<T> {
@@ -1,52 +1,217 @@
 //! Utility functions for use in other parts of penrose
+//!
+//! # Child processes
+//!
+//! The window manager ignores `SIGCHLD` while it is running so that spawned programs are
+//! reaped automatically by the kernel when they exit rather than being left as zombies.
+//! The downside of this is that it is not possible to `wait` on spawned programs in order
+//! to find out their exit status: use [pid_is_running] to check whether a program spawned
+//! with [Spawn] is still running instead.
 use crate::Result;
+use nix::{
+    errno::Errno,
+    sys::{
+        signal::{kill, signal, SigHandler, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::Pid,
+};
 use std::{
+    fs,
     io::Read,
-    process::{Command, Stdio},
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
 };
 use tracing::debug;
 
-/// Run an external command
+/// An external program to be spawned, along with the environment it should be run in.
 ///
-/// This redirects the process stdout and stderr to /dev/null.
-pub fn spawn<S: Into<String>>(cmd: S) -> Result<()> {
-    let s = cmd.into();
-    let parts: Vec<&str> = s.split_whitespace().collect();
-    let result = if parts.len() > 1 {
-        Command::new(parts[0])
-            .args(&parts[1..])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-    } else {
-        Command::new(parts[0])
+/// The stdout and stderr of the spawned process are redirected to /dev/null.
+///
+/// ```no_run
+/// # use penrose::{util::Spawn, Result};
+/// # fn example() -> Result<()> {
+/// let pid = Spawn::new("alacritty --class scratch")
+///     .env("TERM_THEME", "dark")
+///     .current_dir("/tmp")
+///     .spawn()?;
+///
+/// // Only start a notification daemon if there isn't one running already
+/// Spawn::new("dunst").spawn_once()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Spawn {
+    program: String,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    current_dir: Option<PathBuf>,
+}
+
+impl Spawn {
+    /// Create a new [Spawn] from a command line, splitting it on whitespace into the program
+    /// to run and its arguments.
+    pub fn new(cmd: impl AsRef<str>) -> Self {
+        let mut parts = cmd.as_ref().split_whitespace().map(|s| s.to_owned());
+
+        Self {
+            program: parts.next().unwrap_or_default(),
+            args: parts.collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Add an argument to pass to the program.
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Add multiple arguments to pass to the program.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(|s| s.into()));
+        self
+    }
+
+    /// Set an environment variable for the spawned process.
+    pub fn env(mut self, key: impl Into<String>, val: impl Into<String>) -> Self {
+        self.env.push((key.into(), val.into()));
+        self
+    }
+
+    /// Set the working directory for the spawned process.
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Spawn the program, returning the PID of the new process.
+    pub fn spawn(&self) -> Result<u32> {
+        debug!(program = %self.program, args = ?self.args, "spawning subprocess");
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args)
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
             .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-    };
+            .stderr(Stdio::null());
+
+        if let Some(dir) = &self.current_dir {
+            cmd.current_dir(dir);
+        }
 
-    match result {
-        Ok(_) => Ok(()),
-        Err(e) => Err(e.into()),
+        Ok(cmd.spawn()?.id())
+    }
+
+    /// Spawn the program unless an instance of it is already running (see [is_running]),
+    /// returning the PID of the new process if one was started.
+    pub fn spawn_once(&self) -> Result<Option<u32>> {
+        let name = Path::new(&self.program)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or(&self.program);
+
+        if is_running(name) {
+            debug!(%name, "program is already running");
+            return Ok(None);
+        }
+
+        self.spawn().map(Some)
     }
 }
 
+/// Run an external command
+///
+/// This redirects the process stdout and stderr to /dev/null.
+pub fn spawn<S: Into<String>>(cmd: S) -> Result<()> {
+    Spawn::new(cmd.into()).spawn().map(|_| ())
+}
+
 /// Run an external command with the specified command line arguments
 ///
 /// This redirects the process stdout and stderr to /dev/null.
 pub fn spawn_with_args<S: Into<String>>(cmd: S, args: &[&str]) -> Result<()> {
-    let result = Command::new(cmd.into())
-        .args(args)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn();
+    let s = Spawn {
+        program: cmd.into(),
+        ..Default::default()
+    };
+
+    s.args(args.iter().copied()).spawn().map(|_| ())
+}
 
-    match result {
-        Ok(_) => Ok(()),
-        Err(e) => Err(e.into()),
+/// Run an external command unless an instance of it is already running.
+///
+/// See [Spawn::spawn_once] for details.
+pub fn spawn_once<S: Into<String>>(cmd: S) -> Result<()> {
+    Spawn::new(cmd.into()).spawn_once().map(|_| ())
+}
+
+/// Check whether there is a running process (other than this one) for the named program.
+///
+/// A process matches if either its name as reported by the kernel or the file name of the
+/// first argument of its command line is equal to `name`.
+pub fn is_running(name: &str) -> bool {
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    // The kernel truncates process names to 15 bytes
+    let comm_name = &name.as_bytes()[..name.len().min(15)];
+    let own_pid = process::id().to_string();
+
+    entries.flatten().any(|e| {
+        let pid = e.file_name();
+        if !pid.to_string_lossy().bytes().all(|b| b.is_ascii_digit()) || pid == *own_pid {
+            return false;
+        }
+
+        let path = e.path();
+        let comm_matches = fs::read(path.join("comm"))
+            .map(|c| c.strip_suffix(b"\n").unwrap_or(&c) == comm_name)
+            .unwrap_or(false);
+        let argv0_matches = fs::read(path.join("cmdline"))
+            .ok()
+            .and_then(|c| {
+                let argv0 = c.split(|&b| b == 0).next()?.to_vec();
+                let argv0 = String::from_utf8(argv0).ok()?;
+                let file_name = Path::new(&argv0).file_name()?.to_str()?.to_owned();
+
+                Some(file_name == name)
+            })
+            .unwrap_or(false);
+
+        comm_matches || argv0_matches
+    })
+}
+
+/// Check whether the process with the given PID is still running.
+///
+/// Spawned processes are normally reaped automatically (see the [module level docs][self])
+/// in which case all we can do is check whether or not the PID still exists.
+pub fn pid_is_running(pid: u32) -> bool {
+    let pid = Pid::from_raw(pid as i32);
+
+    match waitpid(pid, Some(WaitPidFlag::WNOHANG)) {
+        Ok(WaitStatus::StillAlive) => true,
+        Ok(_) => false,
+        Err(Errno::ECHILD) => kill(pid, None).is_ok(),
+        Err(e) => {
+            debug!(%e, %pid, "unable to check status of process");
+            false
+        }
     }
 }
 
+// Ignore SIGCHLD so that spawned processes are reaped automatically when they exit
+pub(crate) fn reap_children_automatically() -> Result<()> {
+    unsafe { signal(Signal::SIGCHLD, SigHandler::SigIgn) }.map_err(std::io::Error::from)?;
+
+    Ok(())
+}
+
 /// Run an external command and return its output.
 ///
 /// NOTE: std::process::Command::output will not work within penrose due to the
@@ -107,3 +272,60 @@ pub fn spawn_for_output_with_args<S: Into<String>>(
 pub fn notify(msg: &str) -> std::io::Result<()> {
     Command::new("notify-send").arg(msg).output().map(|_| ())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_splits_the_command_line() {
+        let s = Spawn::new("  st -e  htop ").arg("-d").args(["10"]);
+
+        assert_eq!(s.program, "st");
+        assert_eq!(s.args, vec!["-e", "htop", "-d", "10"]);
+    }
+
+    #[test]
+    fn spawned_processes_can_be_tracked() {
+        let pid = Spawn::new("sleep 10").spawn().unwrap();
+        assert!(pid_is_running(pid));
+        // The process name may not have been updated yet if another test is spawning
+        // processes at the same time
+        let found = (0..100).any(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            is_running("sleep")
+        });
+        assert!(found);
+
+        kill(Pid::from_raw(pid as i32), Signal::SIGKILL).unwrap();
+        // Not reaped automatically as the test process is not ignoring SIGCHLD
+        let _ = waitpid(Pid::from_raw(pid as i32), None);
+
+        assert!(!pid_is_running(pid));
+    }
+
+    #[test]
+    fn spawn_sets_the_environment_and_working_directory() {
+        let dir = std::env::temp_dir();
+        let out = dir.join(format!("penrose-spawn-test-{}", process::id()));
+        let pid = Spawn::new("sh -c")
+            .arg("echo \"$PENROSE_TEST $(pwd)\" > \"$1\"")
+            .args(["sh", out.to_str().unwrap()])
+            .env("PENROSE_TEST", "set")
+            .current_dir(&dir)
+            .spawn()
+            .unwrap();
+        let _ = waitpid(Pid::from_raw(pid as i32), None);
+
+        let written = fs::read_to_string(&out).unwrap();
+        fs::remove_file(&out).unwrap();
+        let expected_dir = dir.canonicalize().unwrap();
+
+        assert_eq!(written.trim(), format!("set {}", expected_dir.display()));
+    }
+
+    #[test]
+    fn is_running_is_false_for_unknown_programs() {
+        assert!(!is_running("penrose-test-program-that-does-not-exist"));
+    }
+}
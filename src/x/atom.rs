@@ -92,6 +92,9 @@ pub enum Atom {
     /// _NET_WM_DESKTOP
     #[strum(serialize = "_NET_WM_DESKTOP")]
     NetWmDesktop,
+    /// _NET_WM_ICON
+    #[strum(serialize = "_NET_WM_ICON")]
+    NetWmIcon,
     /// _NET_WM_NAME
     #[strum(serialize = "_NET_WM_NAME")]
     NetWmName,
@@ -101,6 +104,12 @@ pub enum Atom {
     /// _NET_WM_STRUT
     #[strum(serialize = "_NET_WM_STRUT")]
     NetWmStrut,
+    /// _NET_WM_STRUT_PARTIAL
+    #[strum(serialize = "_NET_WM_STRUT_PARTIAL")]
+    NetWmStrutPartial,
+    /// _NET_WORKAREA
+    #[strum(serialize = "_NET_WORKAREA")]
+    NetWorkarea,
     /// _NET_WM_STATE_DEMANDS_ATTENTION
     #[strum(serialize = "_NET_WM_STATE_DEMANDS_ATTENTION")]
     NetWmStateDemandsAttention,
@@ -110,9 +119,15 @@ pub enum Atom {
     /// _NET_WM_STATE_HIDDEN
     #[strum(serialize = "_NET_WM_STATE_HIDDEN")]
     NetWmStateHidden,
+    /// _NET_WM_USER_TIME
+    #[strum(serialize = "_NET_WM_USER_TIME")]
+    NetWmUserTime,
     /// _NET_WM_WINDOW_TYPE
     #[strum(serialize = "_NET_WM_WINDOW_TYPE")]
     NetWmWindowType,
+    /// _NET_WM_WINDOW_OPACITY
+    #[strum(serialize = "_NET_WM_WINDOW_OPACITY")]
+    NetWmWindowOpacity,
     /// _XEMBED
     #[strum(serialize = "_XEMBED")]
     XEmbed,
@@ -160,6 +175,12 @@ pub enum Atom {
     /// _NET_WM_WINDOW_TYPE_NORMAL
     #[strum(serialize = "_NET_WM_WINDOW_TYPE_NORMAL")]
     NetWindowTypeNormal,
+    /// _XROOTPMAP_ID
+    #[strum(serialize = "_XROOTPMAP_ID")]
+    XRootPmapId,
+    /// ESETROOT_PMAP_ID
+    #[strum(serialize = "ESETROOT_PMAP_ID")]
+    ESetRootPmapId,
 }
 
 /// Clients with one of these window types will be auto floated
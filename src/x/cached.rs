@@ -0,0 +1,483 @@
+//! A caching wrapper around an [XConn] impl for reducing round trips to the X server
+use crate::{
+    core::{
+        bindings::{KeyCode, MouseState},
+        hints::BindingHints,
+        titlebar::Titlebar,
+    },
+    pure::{
+        geometry::{Point, Rect},
+        OutputInfo,
+    },
+    x::{
+        event::ClientMessage,
+        property::{Prop, WindowAttributes, WmState},
+        Atom, Backend, ClientAttr, ClientConfig, ClientConfigBatch, Cursor, RootBackground, XConn,
+        XEvent,
+    },
+    Result, Xid,
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    os::unix::io::RawFd,
+    time::{Duration, Instant},
+};
+use tracing::trace;
+
+/// The properties that are cached by a [CachedConn] by default.
+///
+/// These are properties that are typically set once by a client when it is first
+/// created and then not modified for the lifetime of the window.
+pub const DEFAULT_CACHED_PROPS: &[Atom] =
+    &[Atom::WmClass, Atom::WmProtocols, Atom::NetWmWindowType];
+
+/// An [XConn] wrapper that memoizes property lookups for rarely changing properties.
+///
+/// Cached values are invalidated when a `PropertyNotify` event is received for the property
+/// in question, when the property is set via this connection or when the owning window is
+/// destroyed. As invalidation relies on `PropertyNotify` events, values are only cached for
+/// windows that have had one of the event masks from [ClientAttr] selected via this connection:
+/// lookups for any other window are always passed through. All other [XConn] methods are passed through to the wrapped connection.
+///
+/// ```no_run
+/// # use penrose::{x::CachedConn, x11rb::RustConn};
+/// # fn example() -> penrose::Result<()> {
+/// let conn = CachedConn::new(RustConn::new()?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CachedConn<X>
+where
+    X: XConn,
+{
+    inner: X,
+    cached_props: Vec<String>,
+    cache: RefCell<HashMap<(Xid, String), Option<Prop>>>,
+    watched: RefCell<HashSet<Xid>>,
+}
+
+impl<X> CachedConn<X>
+where
+    X: XConn,
+{
+    /// Wrap an existing [XConn], caching the properties in [DEFAULT_CACHED_PROPS].
+    pub fn new(inner: X) -> Self {
+        let props: Vec<String> = DEFAULT_CACHED_PROPS
+            .iter()
+            .map(|a| a.as_ref().to_string())
+            .collect();
+
+        Self::new_with_cached_props(inner, props)
+    }
+
+    /// Wrap an existing [XConn], caching the named properties.
+    pub fn new_with_cached_props(inner: X, cached_props: Vec<String>) -> Self {
+        Self {
+            inner,
+            cached_props,
+            cache: RefCell::new(HashMap::new()),
+            watched: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Get a handle to the wrapped connection.
+    pub fn inner(&self) -> &X {
+        &self.inner
+    }
+
+    /// Drop all cached property values.
+    pub fn clear_cache(&self) {
+        self.cache.borrow_mut().clear();
+    }
+
+    fn is_cached_prop(&self, client: Xid, prop_name: &str) -> bool {
+        self.watched.borrow().contains(&client) && self.cached_props.iter().any(|p| p == prop_name)
+    }
+
+    fn invalidate(&self, client: Xid, prop_name: &str) {
+        self.cache
+            .borrow_mut()
+            .remove(&(client, prop_name.to_string()));
+    }
+
+    fn invalidate_for_event(&self, event: &XEvent) {
+        match event {
+            XEvent::PropertyNotify(e) => self.invalidate(e.id, &e.atom),
+            XEvent::Destroy(id) => self.invalidate_client(*id),
+            _ => (),
+        }
+    }
+
+    fn invalidate_client(&self, client: Xid) {
+        self.watched.borrow_mut().remove(&client);
+        self.cache.borrow_mut().retain(|(id, _), _| *id != client);
+    }
+}
+
+impl<X> Backend for CachedConn<X>
+where
+    X: XConn,
+{
+    fn screen_details(&self) -> Result<Vec<Rect>> {
+        self.inner.screen_details()
+    }
+
+    fn cursor_position(&self) -> Result<Point> {
+        self.inner.cursor_position()
+    }
+
+    fn output_details(&self) -> Result<Vec<OutputInfo>> {
+        self.inner.output_details()
+    }
+
+    fn grab(&self, key_codes: &[KeyCode], mouse_states: &[MouseState]) -> Result<()> {
+        self.inner.grab(key_codes, mouse_states)
+    }
+
+    fn keycodes(&self) -> Result<HashMap<String, u8>> {
+        self.inner.keycodes()
+    }
+
+    fn next_event(&self) -> Result<XEvent> {
+        let event = self.inner.next_event()?;
+        self.invalidate_for_event(&event);
+
+        Ok(event)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn client_geometry(&self, client: Xid) -> Result<Rect> {
+        self.inner.client_geometry(client)
+    }
+
+    fn existing_clients(&self) -> Result<Vec<Xid>> {
+        self.inner.existing_clients()
+    }
+
+    fn map(&self, client: Xid) -> Result<()> {
+        self.inner.map(client)
+    }
+
+    fn unmap(&self, client: Xid) -> Result<()> {
+        self.inner.unmap(client)
+    }
+
+    fn kill(&self, client: Xid) -> Result<()> {
+        self.inner.kill(client)
+    }
+
+    fn focus(&self, client: Xid) -> Result<()> {
+        self.inner.focus(client)
+    }
+
+    fn set_client_config(&self, client: Xid, data: &[ClientConfig]) -> Result<()> {
+        self.inner.set_client_config(client, data)
+    }
+
+    fn set_client_config_batch(&self, batch: ClientConfigBatch) -> Result<()> {
+        self.inner.set_client_config_batch(batch)
+    }
+
+    fn warp_pointer(&self, id: Xid, x: i16, y: i16) -> Result<()> {
+        self.inner.warp_pointer(id, x, y)
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        self.inner.grab_pointer()
+    }
+
+    fn set_cursor(&self, id: Xid, cursor: Cursor) -> Result<()> {
+        self.inner.set_cursor(id, cursor)
+    }
+
+    fn set_active_grab_cursor(&self, cursor: Cursor) -> Result<()> {
+        self.inner.set_active_grab_cursor(cursor)
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        self.inner.ungrab_pointer()
+    }
+
+    fn grab_keyboard(&self) -> Result<()> {
+        self.inner.grab_keyboard()
+    }
+
+    fn ungrab_keyboard(&self) -> Result<()> {
+        self.inner.ungrab_keyboard()
+    }
+
+    fn next_event_before(&self, deadline: Instant) -> Result<Option<XEvent>> {
+        let event = self.inner.next_event_before(deadline)?;
+        if let Some(event) = &event {
+            self.invalidate_for_event(event);
+        }
+
+        Ok(event)
+    }
+
+    fn poll_for_event(&self) -> Result<Option<XEvent>> {
+        let event = self.inner.poll_for_event()?;
+        if let Some(event) = &event {
+            self.invalidate_for_event(event);
+        }
+
+        Ok(event)
+    }
+
+    fn next_event_or_wakeup(
+        &self,
+        deadline: Option<Instant>,
+        wakeup: Option<RawFd>,
+    ) -> Result<Option<XEvent>> {
+        let event = self.inner.next_event_or_wakeup(deadline, wakeup)?;
+        if let Some(event) = &event {
+            self.invalidate_for_event(event);
+        }
+
+        Ok(event)
+    }
+}
+
+impl<X> XConn for CachedConn<X>
+where
+    X: XConn,
+{
+    fn root(&self) -> Xid {
+        self.inner.root()
+    }
+
+    fn roots(&self) -> Vec<Xid> {
+        self.inner.roots()
+    }
+
+    fn client_root(&self, client: Xid) -> Result<Xid> {
+        self.inner.client_root(client)
+    }
+
+    fn intern_atom(&self, atom: &str) -> Result<Xid> {
+        self.inner.intern_atom(atom)
+    }
+
+    fn atom_name(&self, xid: Xid) -> Result<String> {
+        self.inner.atom_name(xid)
+    }
+
+    fn get_prop(&self, client: Xid, prop_name: &str) -> Result<Option<Prop>> {
+        if !self.is_cached_prop(client, prop_name) {
+            return self.inner.get_prop(client, prop_name);
+        }
+
+        let key = (client, prop_name.to_string());
+        if let Some(prop) = self.cache.borrow().get(&key) {
+            trace!(%client, prop_name, "using cached property value");
+            return Ok(prop.clone());
+        }
+
+        let prop = self.inner.get_prop(client, prop_name)?;
+        self.cache.borrow_mut().insert(key, prop.clone());
+
+        Ok(prop)
+    }
+
+    fn get_window_attributes(&self, client: Xid) -> Result<WindowAttributes> {
+        self.inner.get_window_attributes(client)
+    }
+
+    fn set_wm_state(&self, client: Xid, wm_state: WmState) -> Result<()> {
+        self.inner.set_wm_state(client, wm_state)
+    }
+
+    fn set_prop(&self, client: Xid, name: &str, val: Prop) -> Result<()> {
+        self.invalidate(client, name);
+        self.inner.set_prop(client, name, val)
+    }
+
+    fn set_client_attributes(&self, client: Xid, attrs: &[ClientAttr]) -> Result<()> {
+        self.inner.set_client_attributes(client, attrs)?;
+
+        // All of the event masks we set include PropertyChange so from this point on we
+        // will be notified when cached values need to be invalidated
+        if attrs
+            .iter()
+            .any(|a| !matches!(a, ClientAttr::BorderColor(_)))
+        {
+            self.watched.borrow_mut().insert(client);
+        }
+
+        Ok(())
+    }
+
+    fn set_rounded_corners(
+        &self,
+        client: Xid,
+        r: Rect,
+        border: u32,
+        radius: Option<u32>,
+    ) -> Result<()> {
+        self.inner.set_rounded_corners(client, r, border, radius)
+    }
+
+    fn send_client_message(&self, msg: ClientMessage) -> Result<()> {
+        self.inner.send_client_message(msg)
+    }
+
+    fn acquire_wm_selection(&self, replace: bool) -> Result<()> {
+        self.inner.acquire_wm_selection(replace)
+    }
+
+    fn acquire_selection(&self, selection: &str, owner: Xid) -> Result<bool> {
+        self.inner.acquire_selection(selection, owner)
+    }
+
+    fn create_selection_window(&self, root: Xid) -> Result<Option<Xid>> {
+        self.inner.create_selection_window(root)
+    }
+
+    fn create_frame(&self, root: Xid, r: Rect) -> Result<Option<Xid>> {
+        self.inner.create_frame(root, r)
+    }
+
+    fn reparent(&self, client: Xid, parent: Xid, p: Point) -> Result<()> {
+        self.inner.reparent(client, parent, p)
+    }
+
+    fn draw_titlebar(&self, frame: Xid, titlebar: &Titlebar) -> Result<()> {
+        self.inner.draw_titlebar(frame, titlebar)
+    }
+
+    fn create_overlay(&self, root: Xid, r: Rect) -> Result<Option<Xid>> {
+        self.inner.create_overlay(root, r)
+    }
+
+    fn draw_binding_hints(&self, id: Xid, hints: &BindingHints) -> Result<()> {
+        self.inner.draw_binding_hints(id, hints)
+    }
+
+    fn destroy_window(&self, id: Xid) -> Result<()> {
+        self.inner.destroy_window(id)
+    }
+
+    fn set_root_background(&self, root: Xid, bg: &RootBackground) -> Result<()> {
+        self.inner.set_root_background(root, bg)
+    }
+
+    fn idle_time(&self) -> Result<Option<Duration>> {
+        self.inner.idle_time()
+    }
+
+    fn keyboard_group(&self) -> Result<Option<u8>> {
+        self.inner.keyboard_group()
+    }
+
+    fn set_keyboard_group(&self, group: u8) -> Result<()> {
+        self.inner.set_keyboard_group(group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::{event::PropertyEvent, mock::ScriptedConn};
+    use simple_test_case::test_case;
+
+    fn class(name: &str) -> Prop {
+        Prop::UTF8String(vec![name.to_owned()])
+    }
+
+    // Client 1 is watched for property changes, client 2 is not
+    fn cached_conn(events: Vec<XEvent>) -> CachedConn<ScriptedConn> {
+        let mut inner = ScriptedConn::new(vec![]).with_events(events);
+        for id in [Xid(1), Xid(2)] {
+            for atom in [Atom::WmClass, Atom::WmProtocols, Atom::WmName] {
+                inner = inner.with_prop(id, atom.as_ref(), class("old"));
+            }
+        }
+
+        let conn = CachedConn::new(inner);
+        conn.set_client_attributes(Xid(1), &[ClientAttr::ClientEventMask])
+            .unwrap();
+
+        conn
+    }
+
+    // Read the property through the cache, update it behind the cache's back and then
+    // read it again
+    fn read_after_change(conn: &CachedConn<ScriptedConn>, id: Xid, atom: Atom) -> Prop {
+        conn.get_prop(id, atom.as_ref()).unwrap();
+        conn.inner()
+            .set_prop(id, atom.as_ref(), class("new"))
+            .unwrap();
+
+        conn.get_prop(id, atom.as_ref()).unwrap().unwrap()
+    }
+
+    #[test_case(Xid(1), Atom::WmClass, "old"; "cached prop for watched client")]
+    #[test_case(Xid(1), Atom::WmName, "new"; "uncached prop for watched client")]
+    #[test_case(Xid(2), Atom::WmClass, "new"; "cached prop for unwatched client")]
+    #[test]
+    fn only_cached_props_for_watched_clients_are_memoized(id: Xid, atom: Atom, expected: &str) {
+        let conn = cached_conn(vec![]);
+
+        assert_eq!(read_after_change(&conn, id, atom), class(expected));
+    }
+
+    #[test]
+    fn property_notify_invalidates_the_cache() {
+        let conn = cached_conn(vec![XEvent::PropertyNotify(PropertyEvent {
+            id: Xid(1),
+            atom: Atom::WmClass.as_ref().to_string(),
+            is_root: false,
+        })]);
+
+        read_after_change(&conn, Xid(1), Atom::WmClass);
+        read_after_change(&conn, Xid(1), Atom::WmProtocols);
+        conn.next_event().unwrap();
+
+        let class_prop = conn.get_prop(Xid(1), Atom::WmClass.as_ref()).unwrap();
+        let protocols = conn.get_prop(Xid(1), Atom::WmProtocols.as_ref()).unwrap();
+
+        assert_eq!(class_prop, Some(class("new")));
+        assert_eq!(protocols, Some(class("old")));
+    }
+
+    #[test]
+    fn destroy_invalidates_all_props_for_the_client() {
+        let conn = cached_conn(vec![XEvent::Destroy(Xid(1))]);
+
+        read_after_change(&conn, Xid(1), Atom::WmClass);
+        read_after_change(&conn, Xid(1), Atom::WmProtocols);
+        conn.next_event().unwrap();
+
+        for atom in [Atom::WmClass, Atom::WmProtocols] {
+            let prop = conn.get_prop(Xid(1), atom.as_ref()).unwrap();
+            assert_eq!(prop, Some(class("new")));
+        }
+    }
+
+    #[test]
+    fn destroyed_clients_are_no_longer_watched() {
+        let conn = cached_conn(vec![XEvent::Destroy(Xid(1))]);
+        conn.next_event().unwrap();
+
+        let prop = read_after_change(&conn, Xid(1), Atom::WmClass);
+
+        assert_eq!(prop, class("new"));
+    }
+
+    #[test]
+    fn set_prop_invalidates_the_cache() {
+        let conn = cached_conn(vec![]);
+
+        conn.get_prop(Xid(1), Atom::WmClass.as_ref()).unwrap();
+        conn.set_prop(Xid(1), Atom::WmClass.as_ref(), class("new"))
+            .unwrap();
+        let prop = conn.get_prop(Xid(1), Atom::WmClass.as_ref()).unwrap();
+
+        assert_eq!(prop, Some(class("new")));
+    }
+}
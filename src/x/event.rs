@@ -1,6 +1,6 @@
 //! Data types for working with X events
 use crate::{
-    core::bindings::{KeyCode, MouseEvent},
+    core::bindings::{KeyCode, ModifierKey, MouseButton, MouseEvent},
     pure::geometry::{Point, Rect},
     x::{Atom, XConn},
     Result, Xid,
@@ -31,22 +31,39 @@ pub enum XEvent {
     FocusIn(Xid),
     /// A client window has been closed
     Destroy(Xid),
+    /// The active keyboard group (layout) has changed to the given group index
+    KeyboardGroupChange(u8),
+    /// The keyboard mapping has changed (e.g. via `setxkbmap`) and key bindings need to be
+    /// re-resolved against the new keymap
+    KeymapChange,
     /// A grabbed key combination has been entered by the user
     KeyPress(KeyCode),
+    /// A key has been released while grabbed (see [State::on_modifier_release][0])
+    ///
+    /// [0]: crate::core::State::on_modifier_release
+    KeyRelease(KeyCode),
     /// The mouse pointer has left the current client window
     Leave(PointerChange),
     /// Keybindings have changed
     MappingNotify,
     /// A client window is requesting to be positioned and rendered on the screen.
     MapRequest(Xid),
+    /// The mouse pointer has moved
+    MotionNotify(MotionEvent),
     /// The mouse has moved or a mouse button has been pressed
     MouseEvent(MouseEvent),
     /// A client property has changed in some way
     PropertyNotify(PropertyEvent),
     /// A randr action has occured (new outputs, resolution change etc)
     RandrNotify,
+    /// A file descriptor being watched by the connection is ready to be read from
+    Readable(i32),
     /// Focus has moved to a different screen
     ScreenChange,
+    /// Ownership of a selection has been lost
+    SelectionClear(SelectionClearEvent),
+    /// A timer registered with the connection has fired
+    Timer(u64),
     /// A client is being unmapped
     UnmapNotify(Xid),
 }
@@ -63,19 +80,112 @@ impl std::fmt::Display for XEvent {
             Expose(_) => write!(f, "Expose"),
             FocusIn(_) => write!(f, "FocusIn"),
             Destroy(_) => write!(f, "Destroy"),
+            KeyboardGroupChange(_) => write!(f, "KeyboardGroupChange"),
+            KeymapChange => write!(f, "KeymapChange"),
             KeyPress(_) => write!(f, "KeyPress"),
+            KeyRelease(_) => write!(f, "KeyRelease"),
             Leave(_) => write!(f, "Leave"),
             MappingNotify => write!(f, "MappingNotify"),
             MapRequest(_) => write!(f, "MapRequest"),
+            MotionNotify(_) => write!(f, "MotionNotify"),
             MouseEvent(_) => write!(f, "MouseEvent"),
             PropertyNotify(_) => write!(f, "PropertyNotify"),
             RandrNotify => write!(f, "RandrNotify"),
+            Readable(_) => write!(f, "Readable"),
             ScreenChange => write!(f, "ScreenChange"),
+            SelectionClear(_) => write!(f, "SelectionClear"),
+            Timer(_) => write!(f, "Timer"),
             UnmapNotify(_) => write!(f, "UnmapNotify"),
         }
     }
 }
 
+impl XEvent {
+    /// The [XEventKind] of this event.
+    pub fn kind(&self) -> XEventKind {
+        use XEvent::*;
+
+        match self {
+            ClientMessage(_) => XEventKind::ClientMessage,
+            ConfigureNotify(_) => XEventKind::ConfigureNotify,
+            ConfigureRequest(_) => XEventKind::ConfigureRequest,
+            Enter(_) => XEventKind::Enter,
+            Expose(_) => XEventKind::Expose,
+            FocusIn(_) => XEventKind::FocusIn,
+            Destroy(_) => XEventKind::Destroy,
+            KeyboardGroupChange(_) => XEventKind::KeyboardGroupChange,
+            KeymapChange => XEventKind::KeymapChange,
+            KeyPress(_) => XEventKind::KeyPress,
+            KeyRelease(_) => XEventKind::KeyRelease,
+            Leave(_) => XEventKind::Leave,
+            MappingNotify => XEventKind::MappingNotify,
+            MapRequest(_) => XEventKind::MapRequest,
+            MotionNotify(_) => XEventKind::MotionNotify,
+            MouseEvent(_) => XEventKind::MouseEvent,
+            PropertyNotify(_) => XEventKind::PropertyNotify,
+            RandrNotify => XEventKind::RandrNotify,
+            Readable(_) => XEventKind::Readable,
+            ScreenChange => XEventKind::ScreenChange,
+            SelectionClear(_) => XEventKind::SelectionClear,
+            Timer(_) => XEventKind::Timer,
+            UnmapNotify(_) => XEventKind::UnmapNotify,
+        }
+    }
+}
+
+/// The kind of an [XEvent] without any of its associated data, used to declare which events
+/// an [EventHook][crate::core::hooks::EventHook] is interested in.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum XEventKind {
+    /// [XEvent::ClientMessage]
+    ClientMessage,
+    /// [XEvent::ConfigureNotify]
+    ConfigureNotify,
+    /// [XEvent::ConfigureRequest]
+    ConfigureRequest,
+    /// [XEvent::Enter]
+    Enter,
+    /// [XEvent::Expose]
+    Expose,
+    /// [XEvent::FocusIn]
+    FocusIn,
+    /// [XEvent::Destroy]
+    Destroy,
+    /// [XEvent::KeyboardGroupChange]
+    KeyboardGroupChange,
+    /// [XEvent::KeymapChange]
+    KeymapChange,
+    /// [XEvent::KeyPress]
+    KeyPress,
+    /// [XEvent::KeyRelease]
+    KeyRelease,
+    /// [XEvent::Leave]
+    Leave,
+    /// [XEvent::MappingNotify]
+    MappingNotify,
+    /// [XEvent::MapRequest]
+    MapRequest,
+    /// [XEvent::MotionNotify]
+    MotionNotify,
+    /// [XEvent::MouseEvent]
+    MouseEvent,
+    /// [XEvent::PropertyNotify]
+    PropertyNotify,
+    /// [XEvent::RandrNotify]
+    RandrNotify,
+    /// [XEvent::Readable]
+    Readable,
+    /// [XEvent::ScreenChange]
+    ScreenChange,
+    /// [XEvent::SelectionClear]
+    SelectionClear,
+    /// [XEvent::Timer]
+    Timer,
+    /// [XEvent::UnmapNotify]
+    UnmapNotify,
+}
+
 /// Known common client message formats.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ClientMessageKind {
@@ -303,6 +413,23 @@ pub struct PointerChange {
     pub same_screen: bool,
 }
 
+/// Pointer motion, compressed so that only the most recent position is reported when
+/// multiple motion events are queued for the same window
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MotionEvent {
+    /// The ID of the window the motion was reported for
+    pub id: Xid,
+    /// Absolute coordinate of the pointer
+    pub rpt: Point,
+    /// Coordinate of the pointer relative to top-left of the window itself
+    pub wpt: Point,
+    /// The mouse buttons held during the motion
+    pub buttons: Vec<MouseButton>,
+    /// The modifier keys held during the motion
+    pub modifiers: Vec<ModifierKey>,
+}
+
 /// A property change on a known client
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -314,3 +441,15 @@ pub struct PropertyEvent {
     /// Is this window the root window?
     pub is_root: bool,
 }
+
+/// Ownership of a selection being lost to another client
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SelectionClearEvent {
+    /// The ID of the window that previously owned the selection
+    pub id: Xid,
+    /// The name of the selection that was lost
+    pub selection: String,
+    /// Is this the WM_Sn manager selection held by the window manager?
+    pub is_wm_selection: bool,
+}
@@ -0,0 +1,254 @@
+//! Shared fixtures for tests that need a [WindowManager] running against a [ScriptedConn]
+use crate::{
+    builtin::layout::{MainAndStack, Monocle},
+    core::{
+        bindings::{
+            parse_keybindings, KeyBindings, KeyCode, KeyEventHandler, ModifierKey, MouseBindings,
+            MouseButton, MouseEvent, MouseEventHandler, MouseEventKind, MouseState,
+        },
+        layout::Layout,
+        titlebar::TitlebarConfig,
+        Config, State, WindowManager,
+    },
+    pure::geometry::{Point, Rect},
+    stack,
+    x::{
+        event::MotionEvent,
+        mock::{Request, ScriptedConn},
+        ClientConfig, XConn, XEvent,
+    },
+    Xid,
+};
+use std::collections::HashMap;
+
+/// The single screen used by a [ScriptedWm] unless the connection is given other screens.
+pub(crate) const SCREEN: Rect = Rect {
+    x: 0,
+    y: 0,
+    w: 1000,
+    h: 800,
+};
+
+/// A builder for a [WindowManager] running against a [ScriptedConn].
+///
+/// Key bindings can be given either as a [KeyCode] or as a string spec: specs are parsed
+/// against the keycodes set using [ScriptedWm::with_keycodes] when the window manager is
+/// built.
+pub(crate) struct ScriptedWm<X: XConn = ScriptedConn> {
+    conn: ScriptedConn,
+    wrap: Box<dyn FnOnce(ScriptedConn) -> X>,
+    config: Config<X>,
+    key_bindings: KeyBindings<X>,
+    str_bindings: HashMap<&'static str, Box<dyn KeyEventHandler<X>>>,
+    mouse_bindings: MouseBindings<X>,
+    frames: Vec<(Xid, Xid)>,
+}
+
+impl ScriptedWm {
+    pub(crate) fn new() -> Self {
+        Self::wrapped(|conn| conn)
+    }
+
+    /// Build the window manager and run all of the scripted events.
+    pub(crate) fn run(self) -> WindowManager<ScriptedConn> {
+        self.build().run_script().unwrap()
+    }
+}
+
+impl<X: XConn> ScriptedWm<X> {
+    /// Run the window manager against another [XConn] wrapping the [ScriptedConn], such as
+    /// a [TimedConn](crate::x::TimedConn).
+    pub(crate) fn wrapped(wrap: impl FnOnce(ScriptedConn) -> X + 'static) -> Self {
+        Self {
+            conn: ScriptedConn::new(vec![SCREEN]),
+            wrap: Box::new(wrap),
+            config: Config::default(),
+            key_bindings: HashMap::new(),
+            str_bindings: HashMap::new(),
+            mouse_bindings: HashMap::new(),
+            frames: vec![],
+        }
+    }
+
+    /// Modify the underlying connection, such as to set properties on clients.
+    pub(crate) fn with_conn(mut self, f: impl FnOnce(ScriptedConn) -> ScriptedConn) -> Self {
+        self.conn = f(self.conn);
+        self
+    }
+
+    /// Map clients 1 to n in order, leaving client n focused.
+    pub(crate) fn with_clients(self, n: u32) -> Self {
+        self.with_events((1..=n).map(|id| XEvent::MapRequest(Xid(id))))
+    }
+
+    pub(crate) fn with_events(mut self, events: impl IntoIterator<Item = XEvent>) -> Self {
+        self.conn = self.conn.with_events(events);
+        self
+    }
+
+    pub(crate) fn with_keycodes(mut self, keycodes: &[(&str, u8)]) -> Self {
+        let keycodes = keycodes
+            .iter()
+            .map(|&(name, code)| (name.to_string(), code))
+            .collect();
+        self.conn = self.conn.with_keycodes(keycodes);
+        self
+    }
+
+    pub(crate) fn with_config(mut self, f: impl FnOnce(&mut Config<X>)) -> Self {
+        f(&mut self.config);
+        self
+    }
+
+    pub(crate) fn with_key(mut self, k: KeyCode, handler: Box<dyn KeyEventHandler<X>>) -> Self {
+        self.key_bindings.insert(k, handler);
+        self
+    }
+
+    pub(crate) fn with_key_spec(
+        mut self,
+        spec: &'static str,
+        handler: Box<dyn KeyEventHandler<X>>,
+    ) -> Self {
+        self.str_bindings.insert(spec, handler);
+        self
+    }
+
+    pub(crate) fn with_mouse(
+        mut self,
+        kind: MouseEventKind,
+        state: MouseState,
+        handler: Box<dyn MouseEventHandler<X>>,
+    ) -> Self {
+        self.mouse_bindings.insert((kind, state), handler);
+        self
+    }
+
+    /// Treat the given client as being wrapped by a frame window.
+    ///
+    /// [ScriptedConn] does not support creating frames so the frame is recorded directly in
+    /// the window manager state.
+    pub(crate) fn with_frame(mut self, client: Xid, frame: Xid) -> Self {
+        self.frames.push((client, frame));
+        self
+    }
+
+    /// Build the window manager without running any of the scripted events.
+    pub(crate) fn build(self) -> WindowManager<X> {
+        let conn = (self.wrap)(self.conn);
+        let mut key_bindings = parse_keybindings(self.str_bindings, &conn).unwrap();
+        key_bindings.extend(self.key_bindings);
+
+        let mut wm =
+            WindowManager::new(self.config, key_bindings, self.mouse_bindings, conn).unwrap();
+        wm.state.frames.extend(self.frames);
+
+        wm
+    }
+}
+
+/// Labels recorded by test handlers in the order that they were run.
+#[derive(Debug, Default)]
+pub(crate) struct Recorded(Vec<String>);
+
+/// Record that a handler was run so that it can be checked using [recorded].
+pub(crate) fn record(state: &mut State<ScriptedConn>, label: impl Into<String>) {
+    state
+        .extension_or_default::<Recorded>()
+        .borrow_mut()
+        .0
+        .push(label.into());
+}
+
+/// The labels passed to [record] while running the window manager.
+pub(crate) fn recorded(wm: &WindowManager<ScriptedConn>) -> Vec<String> {
+    match wm.state.extension::<Recorded>() {
+        Ok(r) => r.borrow().0.clone(),
+        Err(_) => vec![],
+    }
+}
+
+/// A key handler that only calls [record] with the given label.
+pub(crate) fn record_key(label: &'static str) -> Box<dyn KeyEventHandler<ScriptedConn>> {
+    Box::new(move |s: &mut State<ScriptedConn>, _: &ScriptedConn| {
+        record(s, label);
+        Ok(())
+    })
+}
+
+/// Every position set for the given client, in the order they were requested.
+pub(crate) fn positions_of(wm: &WindowManager<ScriptedConn>, id: Xid) -> Vec<Rect> {
+    wm.x()
+        .requests()
+        .into_iter()
+        .filter_map(|r| match r {
+            Request::SetClientConfig(c, conf) if c == id => conf.iter().find_map(|c| match c {
+                ClientConfig::Position(r) => Some(*r),
+                _ => None,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The keys grabbed by the most recent grab request, sorted by key code.
+pub(crate) fn last_grabbed_keys(wm: &WindowManager<ScriptedConn>) -> Vec<KeyCode> {
+    let last_grab = wm
+        .x()
+        .requests()
+        .into_iter()
+        .rev()
+        .find(|r| matches!(r, Request::Grab(..)));
+
+    match last_grab {
+        Some(Request::Grab(mut keys, _)) => {
+            keys.sort_by_key(|k| k.code);
+            keys
+        }
+        _ => panic!("keys were not grabbed"),
+    }
+}
+
+/// The requests matching the given predicate, such as keyboard and pointer grabs.
+pub(crate) fn requests_matching(
+    wm: &WindowManager<ScriptedConn>,
+    pred: impl Fn(&Request) -> bool,
+) -> Vec<Request> {
+    wm.x().requests().into_iter().filter(|r| pred(r)).collect()
+}
+
+/// Use [MainAndStack] and [Monocle] as the layouts, with titlebars only shown for [Monocle].
+pub(crate) fn monocle_titlebars(c: &mut Config<ScriptedConn>) {
+    c.default_layouts = stack!(
+        Box::new(MainAndStack::default()) as Box<dyn Layout>,
+        Monocle::boxed()
+    );
+    c.titlebar = Some(TitlebarConfig {
+        layouts: vec![Monocle::boxed().name()],
+        ..Default::default()
+    });
+}
+
+/// A mouse event over the given window at (x, y) with Meta held.
+pub(crate) fn meta_mouse_event(
+    id: Xid,
+    button: MouseButton,
+    kind: MouseEventKind,
+    x: i16,
+    y: i16,
+) -> XEvent {
+    let state = MouseState::new(button, vec![ModifierKey::Meta]);
+
+    XEvent::MouseEvent(MouseEvent::new(id, x, y, x, y, state, kind))
+}
+
+/// Pointer motion over the root window to (x, y).
+pub(crate) fn motion(x: u32, y: u32) -> XEvent {
+    XEvent::MotionNotify(MotionEvent {
+        id: Xid(0),
+        rpt: Point::new(x, y),
+        wpt: Point::new(x, y),
+        buttons: vec![],
+        modifiers: vec![],
+    })
+}
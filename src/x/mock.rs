@@ -0,0 +1,569 @@
+//! A scripted [XConn] for testing window manager configurations without an X server.
+//!
+//! A [ScriptedConn] replays a pre-defined sequence of [XEvent]s and responds to property
+//! and geometry queries using values that you provide up front. Every request made by
+//! the window manager is recorded so that you can assert on what would have been sent
+//! to a real X server.
+//!
+//! ```no_run
+//! # use penrose::{core::{Config, WindowManager}, pure::geometry::Rect, Result, Xid};
+//! # use penrose::x::{mock::{Request, ScriptedConn}, XEvent};
+//! # use std::collections::HashMap;
+//! # fn example() -> Result<()> {
+//! let conn = ScriptedConn::new(vec![Rect::new(0, 0, 1920, 1080)])
+//!     .with_client(Xid::from(1), Rect::new(0, 0, 400, 300))
+//!     .with_events([XEvent::MapRequest(Xid::from(1))]);
+//!
+//! let wm = WindowManager::new(Config::default(), HashMap::new(), HashMap::new(), conn)?;
+//! let wm = wm.run_script()?;
+//!
+//! assert_eq!(wm.state.client_set.current_client(), Some(&Xid::from(1)));
+//! assert!(wm.x().requests().contains(&Request::Map(Xid::from(1))));
+//! # Ok(())
+//! # }
+//! ```
+use crate::{
+    core::{
+        bindings::{KeyCode, MouseState},
+        hints::BindingHints,
+        WindowManager,
+    },
+    pure::geometry::{Point, Rect},
+    x::{
+        event::ClientMessage,
+        property::{MapState, Prop, WindowAttributes, WindowClass, WmState},
+        Backend, ClientAttr, ClientConfig, RootBackground, XConn, XEvent,
+    },
+    Error, Result, Xid,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// The geometry reported for clients that have not been given one explicitly.
+pub const DEFAULT_CLIENT_GEOMETRY: Rect = Rect {
+    x: 0,
+    y: 0,
+    w: 100,
+    h: 100,
+};
+
+// Atoms are assigned sequential IDs starting from here to avoid clashing with client IDs
+const FIRST_ATOM_ID: u32 = 1 << 20;
+// Windows created by the window manager (overlays and selection windows) are assigned
+// sequential IDs starting from here for the same reason
+pub(crate) const FIRST_WINDOW_ID: u32 = 1 << 21;
+
+/// A request made to a [ScriptedConn] that would modify the state of the X server.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// A client was mapped
+    Map(Xid),
+    /// A client was unmapped
+    Unmap(Xid),
+    /// A client was killed
+    Kill(Xid),
+    /// A client was given input focus
+    Focus(Xid),
+    /// The WM_STATE of a client was set
+    SetWmState(Xid, WmState),
+    /// A property was set on a window
+    SetProp(Xid, String, Prop),
+    /// Attributes were set on a window
+    SetClientAttributes(Xid, Vec<ClientAttr>),
+    /// A window was repositioned, resized or restacked
+    SetClientConfig(Xid, Vec<ClientConfig>),
+    /// A client message was sent
+    SendClientMessage(ClientMessage),
+    /// The pointer was warped to a position within a window
+    WarpPointer(Xid, i16, i16),
+    /// Key and mouse bindings were grabbed
+    Grab(Vec<KeyCode>, Vec<MouseState>),
+    /// The keyboard was grabbed, such as while waiting for the next key of a key chord
+    GrabKeyboard,
+    /// A keyboard grab was released
+    UngrabKeyboard,
+    /// The pointer was grabbed for the duration of a mouse drag
+    GrabPointer,
+    /// A pointer grab was released
+    UngrabPointer,
+    /// Ownership of the named selection was taken for the given window
+    AcquireSelection(String, Xid),
+    /// A window for owning selections was created
+    CreateSelectionWindow(Xid),
+    /// A window was reparented into the given parent window
+    Reparent(Xid, Xid),
+    /// An overlay window was created with the given geometry
+    CreateOverlay(Xid, Rect),
+    /// Binding hints were drawn to an overlay window, one entry per line
+    DrawBindingHints(Xid, Vec<String>),
+    /// A window created by the window manager was destroyed
+    DestroyWindow(Xid),
+    /// The background of a root window was set
+    SetRootBackground(Xid, RootBackground),
+    /// The keyboard was locked to the given group
+    SetKeyboardGroup(u8),
+}
+
+/// An [XConn] that replays a scripted sequence of events and records every request made
+/// to it.
+///
+/// See the [module level docs][self] for an example of how to use this to test a
+/// configuration.
+#[derive(Debug, Default)]
+pub struct ScriptedConn {
+    screens: RefCell<Vec<Rect>>,
+    existing: Vec<Xid>,
+    cursor: Point,
+    events: RefCell<VecDeque<XEvent>>,
+    props: RefCell<HashMap<(Xid, String), Prop>>,
+    geometry: RefCell<HashMap<Xid, Rect>>,
+    attributes: RefCell<HashMap<Xid, WindowAttributes>>,
+    atoms: RefCell<Vec<String>>,
+    keycodes: HashMap<String, u8>,
+    requests: RefCell<Vec<Request>>,
+    windows: Cell<u32>,
+    idle_times: RefCell<VecDeque<Duration>>,
+}
+
+impl ScriptedConn {
+    /// Create a new scripted connection with the given screens and no events.
+    pub fn new(screens: Vec<Rect>) -> Self {
+        Self {
+            screens: RefCell::new(screens),
+            ..Default::default()
+        }
+    }
+
+    /// Append the given events to the end of the script.
+    pub fn with_events(self, events: impl IntoIterator<Item = XEvent>) -> Self {
+        self.events.borrow_mut().extend(events);
+        self
+    }
+
+    /// Add a client window with the given geometry.
+    pub fn with_client(self, id: Xid, r: Rect) -> Self {
+        self.geometry.borrow_mut().insert(id, r);
+        self
+    }
+
+    /// Add a client window that is already mapped when the window manager starts.
+    pub fn with_existing_client(mut self, id: Xid, r: Rect) -> Self {
+        self.existing.push(id);
+        let attrs = WindowAttributes::new(false, MapState::Viewable, WindowClass::InputOutput);
+        self.attributes.borrow_mut().insert(id, attrs);
+        self.with_client(id, r)
+    }
+
+    /// Set the value of a property for a window.
+    pub fn with_prop(self, id: Xid, name: impl Into<String>, prop: Prop) -> Self {
+        self.props.borrow_mut().insert((id, name.into()), prop);
+        self
+    }
+
+    /// Set the window attributes reported for a window.
+    pub fn with_attributes(self, id: Xid, attrs: WindowAttributes) -> Self {
+        self.attributes.borrow_mut().insert(id, attrs);
+        self
+    }
+
+    /// Set the position of the mouse cursor.
+    pub fn with_cursor_position(mut self, p: Point) -> Self {
+        self.cursor = p;
+        self
+    }
+
+    /// Set the key names and codes reported as the current keyboard mapping.
+    pub fn with_keycodes(mut self, keycodes: HashMap<String, u8>) -> Self {
+        self.keycodes = keycodes;
+        self
+    }
+
+    /// Set the idle times to report from successive calls to [XConn::idle_time]. Once these have
+    /// all been reported, idle time is reported as being unsupported.
+    pub fn with_idle_times(self, idle_times: impl IntoIterator<Item = Duration>) -> Self {
+        self.idle_times.borrow_mut().extend(idle_times);
+        self
+    }
+
+    /// Replace the screens reported by this connection, as if the monitor layout had been
+    /// changed. A [XEvent::RandrNotify] is needed for the window manager to pick up the change.
+    pub fn set_screens(&self, screens: Vec<Rect>) {
+        *self.screens.borrow_mut() = screens;
+    }
+
+    /// Append an event to the end of the script.
+    pub fn push_event(&self, event: XEvent) {
+        self.events.borrow_mut().push_back(event);
+    }
+
+    /// The number of scripted events that have yet to be processed.
+    pub fn remaining_events(&self) -> usize {
+        self.events.borrow().len()
+    }
+
+    /// All requests made so far, in the order they were made.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.borrow().clone()
+    }
+
+    /// Clear the requests recorded so far.
+    pub fn clear_requests(&self) {
+        self.requests.borrow_mut().clear();
+    }
+
+    // Windows without explicit attributes are left reporting as unmapped
+    fn set_map_state(&self, client: Xid, map_state: MapState) {
+        if let Some(attrs) = self.attributes.borrow_mut().get_mut(&client) {
+            attrs.map_state = map_state;
+        }
+    }
+
+    fn record(&self, req: Request) -> Result<()> {
+        self.requests.borrow_mut().push(req);
+        Ok(())
+    }
+
+    fn next_window_id(&self) -> Xid {
+        let id = Xid(FIRST_WINDOW_ID + self.windows.get());
+        self.windows.set(self.windows.get() + 1);
+
+        id
+    }
+
+    fn pop_event(&self) -> Option<XEvent> {
+        self.events.borrow_mut().pop_front()
+    }
+}
+
+impl Backend for ScriptedConn {
+    fn screen_details(&self) -> Result<Vec<Rect>> {
+        Ok(self.screens.borrow().clone())
+    }
+
+    fn cursor_position(&self) -> Result<Point> {
+        Ok(self.cursor)
+    }
+
+    fn keycodes(&self) -> Result<HashMap<String, u8>> {
+        Ok(self.keycodes.clone())
+    }
+
+    fn grab(&self, key_codes: &[KeyCode], mouse_states: &[MouseState]) -> Result<()> {
+        self.record(Request::Grab(key_codes.to_vec(), mouse_states.to_vec()))
+    }
+
+    fn next_event(&self) -> Result<XEvent> {
+        self.pop_event().ok_or(Error::XConnectionLost)
+    }
+
+    fn flush(&self) {}
+
+    fn client_geometry(&self, client: Xid) -> Result<Rect> {
+        Ok(self
+            .geometry
+            .borrow()
+            .get(&client)
+            .copied()
+            .unwrap_or(DEFAULT_CLIENT_GEOMETRY))
+    }
+
+    fn existing_clients(&self) -> Result<Vec<Xid>> {
+        Ok(self.existing.clone())
+    }
+
+    fn map(&self, client: Xid) -> Result<()> {
+        self.set_map_state(client, MapState::Viewable);
+        self.record(Request::Map(client))
+    }
+
+    fn unmap(&self, client: Xid) -> Result<()> {
+        self.set_map_state(client, MapState::Unmapped);
+        self.record(Request::Unmap(client))
+    }
+
+    fn kill(&self, client: Xid) -> Result<()> {
+        self.record(Request::Kill(client))
+    }
+
+    fn focus(&self, client: Xid) -> Result<()> {
+        self.record(Request::Focus(client))
+    }
+
+    fn set_client_config(&self, client: Xid, data: &[ClientConfig]) -> Result<()> {
+        for c in data.iter() {
+            if let ClientConfig::Position(r) = c {
+                self.geometry.borrow_mut().insert(client, *r);
+            }
+        }
+
+        self.record(Request::SetClientConfig(client, data.to_vec()))
+    }
+
+    fn warp_pointer(&self, id: Xid, x: i16, y: i16) -> Result<()> {
+        self.record(Request::WarpPointer(id, x, y))
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        self.record(Request::GrabPointer)
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        self.record(Request::UngrabPointer)
+    }
+
+    fn grab_keyboard(&self) -> Result<()> {
+        self.record(Request::GrabKeyboard)
+    }
+
+    fn ungrab_keyboard(&self) -> Result<()> {
+        self.record(Request::UngrabKeyboard)
+    }
+
+    // Scripted events arrive instantly so running out of events is treated as timing out
+    fn next_event_before(&self, _: Instant) -> Result<Option<XEvent>> {
+        Ok(self.pop_event())
+    }
+}
+
+impl XConn for ScriptedConn {
+    fn root(&self) -> Xid {
+        Xid(0)
+    }
+
+    fn intern_atom(&self, atom: &str) -> Result<Xid> {
+        let mut atoms = self.atoms.borrow_mut();
+        let ix = match atoms.iter().position(|a| a == atom) {
+            Some(ix) => ix,
+            None => {
+                atoms.push(atom.to_string());
+                atoms.len() - 1
+            }
+        };
+
+        Ok(Xid(FIRST_ATOM_ID + ix as u32))
+    }
+
+    fn atom_name(&self, xid: Xid) -> Result<String> {
+        let ix = xid
+            .0
+            .checked_sub(FIRST_ATOM_ID)
+            .ok_or(Error::UnknownClient)?;
+
+        self.atoms
+            .borrow()
+            .get(ix as usize)
+            .cloned()
+            .ok_or(Error::UnknownClient)
+    }
+
+    fn get_prop(&self, client: Xid, prop_name: &str) -> Result<Option<Prop>> {
+        Ok(self
+            .props
+            .borrow()
+            .get(&(client, prop_name.to_string()))
+            .cloned())
+    }
+
+    fn get_window_attributes(&self, client: Xid) -> Result<WindowAttributes> {
+        Ok(self
+            .attributes
+            .borrow()
+            .get(&client)
+            .cloned()
+            .unwrap_or_else(|| {
+                WindowAttributes::new(false, MapState::Unmapped, WindowClass::InputOutput)
+            }))
+    }
+
+    fn set_wm_state(&self, client: Xid, wm_state: WmState) -> Result<()> {
+        self.record(Request::SetWmState(client, wm_state))
+    }
+
+    fn set_prop(&self, client: Xid, name: &str, val: Prop) -> Result<()> {
+        self.props
+            .borrow_mut()
+            .insert((client, name.to_string()), val.clone());
+
+        self.record(Request::SetProp(client, name.to_string(), val))
+    }
+
+    fn set_client_attributes(&self, client: Xid, attrs: &[ClientAttr]) -> Result<()> {
+        self.record(Request::SetClientAttributes(client, attrs.to_vec()))
+    }
+
+    fn send_client_message(&self, msg: ClientMessage) -> Result<()> {
+        self.record(Request::SendClientMessage(msg))
+    }
+
+    fn acquire_selection(&self, selection: &str, owner: Xid) -> Result<bool> {
+        self.record(Request::AcquireSelection(selection.to_string(), owner))?;
+
+        Ok(true)
+    }
+
+    fn create_selection_window(&self, _root: Xid) -> Result<Option<Xid>> {
+        let id = self.next_window_id();
+        self.record(Request::CreateSelectionWindow(id))?;
+
+        Ok(Some(id))
+    }
+
+    fn reparent(&self, client: Xid, parent: Xid, _p: Point) -> Result<()> {
+        self.record(Request::Reparent(client, parent))
+    }
+
+    fn create_overlay(&self, _root: Xid, r: Rect) -> Result<Option<Xid>> {
+        let id = self.next_window_id();
+        self.record(Request::CreateOverlay(id, r))?;
+
+        Ok(Some(id))
+    }
+
+    fn draw_binding_hints(&self, id: Xid, hints: &BindingHints) -> Result<()> {
+        self.record(Request::DrawBindingHints(id, hints.lines()))
+    }
+
+    fn destroy_window(&self, id: Xid) -> Result<()> {
+        self.record(Request::DestroyWindow(id))
+    }
+
+    fn set_root_background(&self, root: Xid, bg: &RootBackground) -> Result<()> {
+        self.record(Request::SetRootBackground(root, bg.clone()))
+    }
+
+    fn idle_time(&self) -> Result<Option<Duration>> {
+        Ok(self.idle_times.borrow_mut().pop_front())
+    }
+
+    fn set_keyboard_group(&self, group: u8) -> Result<()> {
+        self.record(Request::SetKeyboardGroup(group))
+    }
+}
+
+impl WindowManager<ScriptedConn> {
+    /// Start this window manager and process each scripted event in turn, returning
+    /// the window manager once all events have been handled so that its state can be
+    /// inspected.
+    ///
+    /// Startup hooks are run and existing clients are managed before any events are
+    /// processed, in the same way as when calling [WindowManager::run].
+    pub fn run_script(mut self) -> Result<Self> {
+        self.start()?;
+        self.run_scheduled_hooks();
+
+        while let Some(event) = self.x().pop_event() {
+            if self.process_event(event)? {
+                break;
+            }
+            self.run_scheduled_hooks();
+            self.run_deferred();
+            self.dispatch_messages();
+
+            if self.state.exit_requested {
+                self.shutdown()?;
+                return Ok(self);
+            }
+        }
+
+        // Wait for any background work to complete before applying the results
+        loop {
+            let waiting = self.state.deferred.is_waiting() || self.state.deferred.has_actions();
+            self.run_deferred();
+            self.dispatch_messages();
+            if !waiting {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        // Any key chord still waiting for its next key times out once the script is exhausted
+        self.time_out_chord();
+
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::actions::modify_with,
+        core::{bindings::KeyEventHandler, Config},
+        x::{
+            event::{SelectionClearEvent, XEvent},
+            fixtures::SCREEN,
+        },
+    };
+    use std::collections::HashMap;
+
+    const KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+
+    fn wm_with(conn: ScriptedConn) -> WindowManager<ScriptedConn> {
+        let mut key_bindings: HashMap<KeyCode, Box<dyn KeyEventHandler<ScriptedConn>>> =
+            HashMap::new();
+        key_bindings.insert(KEY, modify_with(|cs| cs.focus_down()));
+
+        WindowManager::new(Config::default(), key_bindings, HashMap::new(), conn).unwrap()
+    }
+
+    #[test]
+    fn existing_clients_are_managed_on_startup() {
+        let conn = ScriptedConn::new(vec![SCREEN]).with_existing_client(Xid(1), SCREEN);
+        let wm = wm_with(conn).run_script().unwrap();
+
+        assert!(wm.state.client_set.contains(&Xid(1)));
+    }
+
+    #[test]
+    fn map_requests_are_managed() {
+        let conn = ScriptedConn::new(vec![SCREEN])
+            .with_events([XEvent::MapRequest(Xid(1)), XEvent::MapRequest(Xid(2))]);
+        let wm = wm_with(conn).run_script().unwrap();
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(2)));
+        assert!(wm.x().requests().contains(&Request::Map(Xid(1))));
+        assert!(wm.x().requests().contains(&Request::Map(Xid(2))));
+        assert_eq!(wm.x().remaining_events(), 0);
+    }
+
+    #[test]
+    fn key_bindings_are_run() {
+        let conn = ScriptedConn::new(vec![SCREEN]).with_events([
+            XEvent::MapRequest(Xid(1)),
+            XEvent::MapRequest(Xid(2)),
+            XEvent::KeyPress(KEY),
+        ]);
+        let wm = wm_with(conn).run_script().unwrap();
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(1)));
+        let last_focus = wm
+            .x()
+            .requests()
+            .into_iter()
+            .rev()
+            .find(|r| matches!(r, Request::Focus(_)));
+        assert_eq!(last_focus, Some(Request::Focus(Xid(1))));
+    }
+
+    #[test]
+    fn losing_the_wm_selection_stops_the_script() {
+        let conn = ScriptedConn::new(vec![SCREEN]).with_events([
+            XEvent::SelectionClear(SelectionClearEvent {
+                id: Xid(0),
+                selection: "WM_S0".to_string(),
+                is_wm_selection: true,
+            }),
+            XEvent::MapRequest(Xid(1)),
+        ]);
+        let wm = wm_with(conn).run_script().unwrap();
+
+        assert!(!wm.state.client_set.contains(&Xid(1)));
+        assert_eq!(wm.x().remaining_events(), 1);
+    }
+}
@@ -2,27 +2,46 @@
 use crate::{
     builtin::layout::messages::Hide,
     core::{
-        bindings::{KeyCode, MouseState},
-        ClientSet, Config, State,
+        hints::BindingHints,
+        hooks::{run_hook, run_named_hooks, NamedHook, UnmanagedClient},
+        titlebar::Titlebar,
+        ClientSet, Config, FocusRequestAction, State, Theme, WorkspaceRule,
     },
     pure::geometry::{Point, Rect},
-    x::{atom::AUTO_FLOAT_WINDOW_TYPES, event::ClientMessage, property::WmState},
-    Color, Result, Xid,
+    x::{
+        event::ClientMessage,
+        property::{MapState, WmState},
+    },
+    Color, Error, Result, Xid,
 };
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use tracing::{error, trace};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    time::{Duration, Instant},
+};
+use tracing::{trace, warn};
 
 pub mod atom;
+pub mod cached;
 pub mod event;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod mock;
 pub mod property;
 pub mod query;
+#[cfg(feature = "recording")]
+pub mod recording;
+pub mod timed;
 
+pub use crate::backend::{Backend, BackendExt};
 pub use atom::Atom;
-pub use event::XEvent;
-pub use property::{Prop, WindowAttributes};
+pub use cached::CachedConn;
+pub use event::{XEvent, XEventKind};
+pub use property::{Prop, WindowAttributes, WmIcon};
 pub use query::Query;
+pub use timed::TimedConn;
 
 /// A window type to be specified when creating a new window in the X server
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -37,6 +56,22 @@ pub enum WinType {
     InputOutput(Atom),
 }
 
+/// Mouse cursors that can be displayed by the window manager.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Cursor {
+    /// The standard left pointing arrow
+    LeftPtr,
+    /// A four way arrow used while moving a window
+    Move,
+    /// A corner cursor used while resizing a window from its bottom right corner
+    Resize,
+    /// A pointing hand
+    Hand,
+    /// A watch / busy indicator
+    Watch,
+}
+
 /// On screen configuration options for X clients (not all are curently implemented)
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -55,6 +90,82 @@ pub enum ClientConfig {
     StackBottom,
 }
 
+/// A batch of [ClientConfig] changes for one or more client windows that should be
+/// applied to the X server together.
+///
+/// Multiple changes pushed for the same client are combined so that they can be
+/// applied in a single request where the [XConn] impl supports it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientConfigBatch {
+    changes: Vec<(Xid, Vec<ClientConfig>)>,
+}
+
+impl ClientConfigBatch {
+    /// Create a new empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a [ClientConfig] change for the given client.
+    pub fn push(&mut self, client: Xid, conf: ClientConfig) {
+        match self.changes.iter_mut().find(|(id, _)| *id == client) {
+            Some((_, confs)) => confs.push(conf),
+            None => self.changes.push((client, vec![conf])),
+        }
+    }
+
+    /// The number of clients with pending changes in this batch.
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Whether or not there are any pending changes in this batch.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Iterate over the pending changes in this batch in the order they were first queued.
+    pub fn iter(&self) -> impl Iterator<Item = (Xid, &[ClientConfig])> {
+        self.changes
+            .iter()
+            .map(|(id, confs)| (*id, confs.as_slice()))
+    }
+}
+
+/// A background for a root window (see [XConn::set_root_background]).
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub enum RootBackground {
+    /// Fill the root window with a solid color
+    Color(Color),
+    /// Tile the root window with an image, starting from the top left corner
+    Image {
+        /// The width of the image in pixels
+        w: u32,
+        /// The height of the image in pixels
+        h: u32,
+        /// Pixel data for the image in ARGB format, row by row
+        argb: Vec<u32>,
+    },
+}
+
+impl RootBackground {
+    /// Check that the pixel data for an [RootBackground::Image] matches its dimensions.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::Image { w, h, argb } if argb.len() != (*w as usize) * (*h as usize) => {
+                Err(Error::InvalidImageData {
+                    w: *w,
+                    h: *h,
+                    expected: (*w as usize) * (*h as usize),
+                    got: argb.len(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Attributes for an X11 client window (not all are curently implemented)
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -71,66 +182,191 @@ pub enum ClientAttr {
 
 /// A handle on a running X11 connection that we can use for issuing X requests.
 ///
-/// XConn is intended as an abstraction layer to allow for communication with the underlying
-/// display system (assumed to be X) using whatever mechanism the implementer wishes. In theory, it
-/// should be possible to write an implementation that allows penrose to run on systems not using X
-/// as the windowing system but X idioms and high level event types / client interations are
-/// assumed.
-pub trait XConn {
+/// XConn extends [Backend] with the parts of talking to the display server that are specific
+/// to X: atoms, properties, window attributes, client messages, selections and reparenting.
+/// The operations required for managing, positioning and focusing client windows are defined
+/// on [Backend] so that they can be provided by back ends for other display servers.
+pub trait XConn: Backend {
     /// The ID of the window manager root window.
     fn root(&self) -> Xid;
-    /// Ask the X server for the dimensions of each currently available screen.
-    fn screen_details(&self) -> Result<Vec<Rect>>;
-    /// Ask the X server for the current (x, y) coordinate of the mouse cursor.
-    fn cursor_position(&self) -> Result<Point>;
-
-    /// Grab the specified key and mouse states, intercepting them for processing within
-    /// the window manager itself.
-    fn grab(&self, key_codes: &[KeyCode], mouse_states: &[MouseState]) -> Result<()>;
-    /// Block and wait for the next event from the X server so it can be processed.
-    fn next_event(&self) -> Result<XEvent>;
-    /// Flush any pending events to the X server.
-    fn flush(&self);
+
+    /// The IDs of the root windows of all X screens being managed, in screen order.
+    ///
+    /// This only differs from [XConn::root] for classic multi-screen (non-Xinerama) setups,
+    /// in which case the root returned by [XConn::root] should be first. The default
+    /// implementation returns only [XConn::root].
+    fn roots(&self) -> Vec<Xid> {
+        vec![self.root()]
+    }
+
+    /// The root window of the X screen that the given client belongs to.
+    ///
+    /// The default implementation returns [XConn::root].
+    fn client_root(&self, client: Xid) -> Result<Xid> {
+        let _ = client;
+        Ok(self.root())
+    }
 
     /// Look up the [Xid] of a given [Atom] name. If it is not currently interned, intern it.
     fn intern_atom(&self, atom: &str) -> Result<Xid>;
+
     /// Look up the string name of a given [Atom] by its [Xid].
     fn atom_name(&self, xid: Xid) -> Result<String>;
 
-    /// Look up the current dimensions and position of a given client window.
-    fn client_geometry(&self, client: Xid) -> Result<Rect>;
-    /// Ask the X server for the IDs of all currently known client windows
-    fn existing_clients(&self) -> Result<Vec<Xid>>;
-
-    /// Map the given client window to the screen with its current geometry, making it visible.
-    fn map(&self, client: Xid) -> Result<()>;
-    /// Unmap the given client window from the screen, hiding it.
-    fn unmap(&self, client: Xid) -> Result<()>;
-    /// Kill the given client window, closing it.
-    fn kill(&self, client: Xid) -> Result<()>;
-    /// Set X input focus to be held by the given client window.
-    fn focus(&self, client: Xid) -> Result<()>;
-
     /// Look up a specific property on a given client window.
     fn get_prop(&self, client: Xid, prop_name: &str) -> Result<Option<Prop>>;
+
     /// Request the [WindowAttributes] for a given client window from the X server.
     fn get_window_attributes(&self, client: Xid) -> Result<WindowAttributes>;
 
     /// Set the current [WmState] for a given client window.
     fn set_wm_state(&self, client: Xid, wm_state: WmState) -> Result<()>;
+
     /// Set a specific property on a given client window.
     fn set_prop(&self, client: Xid, name: &str, val: Prop) -> Result<()>;
+
     /// Set one or more [ClientAttr] for a given client window.
     fn set_client_attributes(&self, client: Xid, attrs: &[ClientAttr]) -> Result<()>;
-    /// Set the [ClientConfig] for a given client window.
-    fn set_client_config(&self, client: Xid, data: &[ClientConfig]) -> Result<()>;
+
+    /// Apply a mask to the given client window so that its corners are rounded with the
+    /// given radius, or remove any existing mask if `radius` is `None`.
+    ///
+    /// `r` is the geometry of the client window excluding its border and `border` is the
+    /// current border width of the window. Implementations should do nothing if `r` has a
+    /// width or height of zero. The default implementation does nothing.
+    fn set_rounded_corners(
+        &self,
+        client: Xid,
+        r: Rect,
+        border: u32,
+        radius: Option<u32>,
+    ) -> Result<()> {
+        let _ = (client, r, border, radius);
+        Ok(())
+    }
+
     /// Send a [ClientMessage] to a given client.
     fn send_client_message(&self, msg: ClientMessage) -> Result<()>;
 
-    /// Reposition the mouse cursor to the given (x, y) coordinates within the specified window.
-    /// This method should not be called directly: use `warp_pointer_to_window` or `warp_pointer_to_screen`
-    /// instead.
-    fn warp_pointer(&self, id: Xid, x: i16, y: i16) -> Result<()>;
+    /// Take ownership of the ICCCM WM_Sn manager selection for each screen being managed.
+    ///
+    /// If another window manager already owns any of the selections then this should return
+    /// [Error::WmAlreadyRunning][crate::Error::WmAlreadyRunning] unless `replace` is true,
+    /// in which case the existing window manager should be asked to exit before continuing.
+    /// The default implementation does nothing.
+    fn acquire_wm_selection(&self, replace: bool) -> Result<()> {
+        let _ = replace;
+        Ok(())
+    }
+
+    /// Take ownership of the named selection for the given owner window, returning whether
+    /// or not ownership was successfully acquired.
+    ///
+    /// The default implementation returns `false` to indicate that selections are not supported.
+    fn acquire_selection(&self, selection: &str, owner: Xid) -> Result<bool> {
+        let _ = (selection, owner);
+        Ok(false)
+    }
+
+    /// Create an unmapped window as a child of `root` that can own a selection on behalf of
+    /// the window manager (such as the system tray selection) and receive the client messages
+    /// that are sent to the selection owner.
+    ///
+    /// The default implementation returns `None` to indicate that selection windows are not
+    /// supported.
+    fn create_selection_window(&self, root: Xid) -> Result<Option<Xid>> {
+        let _ = root;
+        Ok(None)
+    }
+
+    /// Create an unmapped frame window as a child of `root` with the given geometry for
+    /// wrapping a client when [Config::reparent_clients] is enabled.
+    ///
+    /// Frames must redirect configure and map requests made by their children to the window
+    /// manager. The default implementation returns `None` to indicate that frames are not
+    /// supported, in which case clients are not reparented.
+    fn create_frame(&self, root: Xid, r: Rect) -> Result<Option<Xid>> {
+        let _ = (root, r);
+        Ok(None)
+    }
+
+    /// Reparent the given client window so that it is a child of `parent`, positioned at `p`
+    /// relative to the parent's origin.
+    ///
+    /// The default implementation does nothing.
+    fn reparent(&self, client: Xid, parent: Xid, p: Point) -> Result<()> {
+        let _ = (client, parent, p);
+        Ok(())
+    }
+
+    /// Draw the given [Titlebar] at the top of a frame window created by [XConn::create_frame].
+    ///
+    /// The default implementation does nothing.
+    fn draw_titlebar(&self, frame: Xid, titlebar: &Titlebar) -> Result<()> {
+        let _ = (frame, titlebar);
+        Ok(())
+    }
+
+    /// Create an unmapped, override-redirect overlay window as a child of `root` with the
+    /// given geometry for displaying [BindingHints] when [Config::binding_hints] is set.
+    ///
+    /// The default implementation returns `None` to indicate that overlays are not supported,
+    /// in which case no hints are shown.
+    fn create_overlay(&self, root: Xid, r: Rect) -> Result<Option<Xid>> {
+        let _ = (root, r);
+        Ok(None)
+    }
+
+    /// Draw the given [BindingHints] into an overlay window created by
+    /// [XConn::create_overlay].
+    ///
+    /// The default implementation does nothing.
+    fn draw_binding_hints(&self, id: Xid, hints: &BindingHints) -> Result<()> {
+        let _ = (id, hints);
+        Ok(())
+    }
+
+    /// Destroy a window created by the window manager, such as a frame or overlay.
+    ///
+    /// The default implementation does nothing.
+    fn destroy_window(&self, id: Xid) -> Result<()> {
+        let _ = id;
+        Ok(())
+    }
+
+    /// Set the background of the given root window, also setting the `_XROOTPMAP_ID` and
+    /// `ESETROOT_PMAP_ID` properties so that programs using pseudo-transparency are able to
+    /// find it.
+    ///
+    /// The default implementation does nothing.
+    fn set_root_background(&self, root: Xid, bg: &RootBackground) -> Result<()> {
+        let _ = (root, bg);
+        Ok(())
+    }
+
+    /// How long it has been since the user last provided keyboard or mouse input.
+    ///
+    /// The default implementation returns `None` to indicate that idle time is not supported,
+    /// in which case idle hooks will not be run.
+    fn idle_time(&self) -> Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    /// The index of the currently active keyboard group (layout).
+    ///
+    /// The default implementation returns `None` to indicate that keyboard groups are not
+    /// supported.
+    fn keyboard_group(&self) -> Result<Option<u8>> {
+        Ok(None)
+    }
+
+    /// Lock the keyboard to the given group (layout) index.
+    ///
+    /// The default implementation does nothing.
+    fn set_keyboard_group(&self, group: u8) -> Result<()> {
+        let _ = group;
+        Ok(())
+    }
 }
 
 /// Extended functionality for [XConn] impls in order to run the window manager.
@@ -145,19 +381,90 @@ pub trait XConnExt: XConn + Sized {
     }
     /// Establish the window manager state for the given client window and refresh the
     /// current X state.
+    ///
+    /// The new client is only given focus if allowed by the [FocusStealingPolicy][0]
+    /// set in the current [Config].
+    ///
+    ///   [0]: crate::core::FocusStealingPolicy
     fn manage(&self, id: Xid, state: &mut State<Self>) -> Result<()> {
         trace!(%id, "managing new client");
+        let user_time = self.client_user_time(id)?;
+        let action = self.resolve_focus_request(id, user_time, state)?;
+        let focused = state.client_set.current_client().copied();
+
         manage_without_refresh(id, None, state, self)?;
+
+        if action != FocusRequestAction::Focus {
+            trace!(%id, ?action, "preventing new client from stealing focus");
+            if let Some(prev) = focused {
+                state.client_set.focus_client(&prev);
+            }
+            if action == FocusRequestAction::MarkUrgent {
+                state.set_urgent(id, true);
+                self.set_client_demands_attention(id, true)?;
+            }
+        }
+
         self.refresh(state)
     }
 
     /// Remove the window manager state for the given client window and refresh the
     /// current X state.
+    ///
+    /// If the client was being managed then the user [UnmanageHook][0] (if there is one)
+    /// is run once the client has been removed.
+    ///
+    ///   [0]: crate::core::hooks::UnmanageHook
     fn unmanage(&self, client: Xid, state: &mut State<Self>) -> Result<()> {
         trace!(?client, "removing client");
+        let unmanaged = state
+            .client_set
+            .tag_for_client(&client)
+            .map(|tag| UnmanagedClient {
+                id: client,
+                tag: tag.to_owned(),
+                floating: state.client_set.floating.contains_key(&client),
+                fullscreen: state.client_set.is_fullscreen(&client),
+                transient_for: state.client_set.transient_for.get(&client).copied(),
+                x_screen: state.x_screen_for_client(client),
+            });
+
         self.modify_and_refresh(state, |cs| {
             cs.remove_client(&client);
-        })
+        })?;
+        let root = state.root_for_client(client);
+        state.client_x_screens.remove(&client);
+
+        // Clients that withdraw themselves are returned to the root window where they were
+        // before being removed from their frame
+        if let Some(frame) = state.frames.remove(&client) {
+            let r = self.client_geometry(frame)?;
+            self.reparent(client, root, Point::new(r.x, r.y))?;
+            self.destroy_window(frame)?;
+        }
+
+        if let Some(unmanaged) = unmanaged {
+            let mut hook = state.config.unmanage_hook.take();
+            if let Some(ref mut h) = hook {
+                trace!(id = %client, "running user unmanage hook");
+                let desc = "user unmanage hook";
+                if !run_hook(desc, state, |s| h.call(&unmanaged, s, self)).1 {
+                    hook = None;
+                }
+            }
+            state.config.unmanage_hook = hook;
+
+            run_named_hooks(state, self, |name, hook, state, x| match hook {
+                NamedHook::Unmanage(h) => {
+                    trace!(%name, id = %client, "running named unmanage hook");
+                    let desc = format!("named unmanage hook '{name}'");
+                    run_hook(&desc, state, |s| h.call(&unmanaged, s, x)).1
+                }
+                _ => true,
+            });
+        }
+
+        Ok(())
     }
 
     /// Display a client on the screen by mapping it and setting its WmState to Normal
@@ -208,29 +515,107 @@ pub trait XConnExt: XConn + Sized {
     where
         F: FnMut(&mut ClientSet),
     {
+        let start = Instant::now();
         f(&mut state.client_set); // NOTE: mutating the existing state
+        state.move_sticky_clients();
 
         let ss = state.client_set.position_and_snapshot();
         state.diff.update(ss);
 
         notify_killed(self, state)?;
         set_window_props(self, state)?;
+        apply_appearance_changes(self, state)?;
         notify_hidden_workspaces(state);
-        self.position_clients(state.config.border_width, &state.diff.after.positions)?;
+        set_fullscreen_props(self, state)?;
+        invalidate_positions(state);
+        invalidate_framed_clients_for_layout_changes(state);
+        position_changed_clients(self, state)?;
+        draw_changed_titlebars(self, state)?;
+        state.appearance_changed = false;
+        state.stale_positions.clear();
         set_window_visibility(self, state)?;
         set_focus(self, state)?;
+        state.update_focus_history();
+        if let Some(id) = state.clear_focused_urgency() {
+            self.set_client_demands_attention(id, false)?;
+        }
         handle_pointer_change(self, state)?;
 
         // TODO: clear enterWindow events from the event queue if this was because of mouse focus (?)
 
-        let mut hook = state.config.refresh_hook.take();
-        if let Some(ref mut h) = hook {
-            trace!("running user refresh hook");
-            if let Err(e) = h.call(state, self) {
-                error!(%e, "error returned from user refresh hook");
-            }
-        }
-        state.config.refresh_hook = hook;
+        let focus_changes: Vec<(Option<Xid>, Option<Xid>)> = if state.diff.focused_client_changed()
+        {
+            vec![(
+                state.diff.before.focused_client,
+                state.diff.after.focused_client,
+            )]
+        } else {
+            vec![]
+        };
+        run_user_and_named_hooks(
+            "focus change",
+            &focus_changes,
+            state,
+            self,
+            |c| &mut c.focus_change_hook,
+            |h| match h {
+                NamedHook::FocusChange(h) => Some(h),
+                _ => None,
+            },
+            |h, &(old, new), s, x| h.call(old, new, s, x),
+        );
+
+        let switches: Vec<(usize, String, String)> = state
+            .diff
+            .workspace_switches()
+            .into_iter()
+            .map(|(s, old, new)| (s, old.to_owned(), new.to_owned()))
+            .collect();
+        run_user_and_named_hooks(
+            "workspace switch",
+            &switches,
+            state,
+            self,
+            |c| &mut c.workspace_switch_hook,
+            |h| match h {
+                NamedHook::WorkspaceSwitch(h) => Some(h),
+                _ => None,
+            },
+            |h, (screen, old, new), s, x| h.call(*screen, old, new, s, x),
+        );
+
+        let layout_changes: Vec<(String, String, String)> = state
+            .diff
+            .layout_changes()
+            .into_iter()
+            .map(|(tag, old, new)| (tag.to_owned(), old.to_owned(), new.to_owned()))
+            .collect();
+        run_user_and_named_hooks(
+            "layout change",
+            &layout_changes,
+            state,
+            self,
+            |c| &mut c.layout_change_hook,
+            |h| match h {
+                NamedHook::LayoutChange(h) => Some(h),
+                _ => None,
+            },
+            |h, (tag, old, new), s, x| h.call(tag, old, new, s, x),
+        );
+
+        run_user_and_named_hooks(
+            "refresh",
+            &[()],
+            state,
+            self,
+            |c| &mut c.refresh_hook,
+            |h| match h {
+                NamedHook::Refresh(h) => Some(h),
+                _ => None,
+            },
+            |h, _, s, x| h.call(s, x),
+        );
+        state.record_metric(|| "refresh".to_owned(), start.elapsed());
 
         Ok(())
     }
@@ -242,7 +627,14 @@ pub trait XConnExt: XConn + Sized {
     }
 
     /// Check whether or not the given client should be assigned floating status or not.
-    fn client_should_float(&self, client: Xid, floating_classes: &[String]) -> Result<bool> {
+    ///
+    /// Transient clients are always floated, along with any clients matching the
+    /// [floating classes][Config::floating_classes], [floating window
+    /// types][Config::floating_window_types] or that have a fixed size if
+    /// [Config::float_fixed_size_clients] is set.
+    fn client_should_float(&self, client: Xid, config: &Config<Self>) -> Result<bool> {
+        let floating_classes = &config.floating_classes;
+
         trace!(%client, "fetching WmTransientFor prop");
         if let Some(prop) = self.get_prop(client, Atom::WmTransientFor.as_ref())? {
             trace!(?prop, "window is transient: setting to floating state");
@@ -257,17 +649,59 @@ pub trait XConnExt: XConn + Sized {
             }
         }
 
-        let float_types: Vec<&str> = AUTO_FLOAT_WINDOW_TYPES.iter().map(|a| a.as_ref()).collect();
+        let float_types: Vec<&str> = config
+            .floating_window_types
+            .iter()
+            .map(|a| a.as_ref())
+            .collect();
 
         trace!(%client, "fetching NetWmWindowType prop");
         let p = self.get_prop(client, Atom::NetWmWindowType.as_ref())?;
-        let should_float = if let Some(Prop::Atom(atoms)) = p {
-            atoms.iter().any(|a| float_types.contains(&a.as_ref()))
-        } else {
-            false
-        };
+        if let Some(Prop::Atom(atoms)) = p {
+            if atoms.iter().any(|a| float_types.contains(&a.as_ref())) {
+                trace!(%client, ?atoms, "window has a floating type: setting to floating state");
+                return Ok(true);
+            }
+        }
+
+        if config.float_fixed_size_clients {
+            trace!(%client, "fetching WmNormalHints prop");
+            if let Some(Prop::WmNormalHints(hints)) =
+                self.get_prop(client, Atom::WmNormalHints.as_ref())?
+            {
+                if hints.is_fixed_size() {
+                    trace!(%client, "window has a fixed size: setting to floating state");
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// The leader of the window group the given client belongs to, as set in its
+    /// `WM_HINTS` property, if there is one.
+    fn client_window_group(&self, client: Xid) -> Result<Option<Xid>> {
+        match self.get_prop(client, Atom::WmHints.as_ref())? {
+            Some(Prop::WmHints(hints)) => Ok(hints.window_group()),
+            _ => Ok(None),
+        }
+    }
+
+    /// Check whether any member of the window group of the given client has its urgency
+    /// hint set.
+    ///
+    /// For clients that are not part of a window group this only checks the client itself.
+    fn group_is_urgent(&self, client: Xid, cs: &ClientSet) -> Result<bool> {
+        for c in cs.group_members(&client) {
+            if let Some(Prop::WmHints(hints)) = self.get_prop(c, Atom::WmHints.as_ref())? {
+                if hints.is_urgent() {
+                    return Ok(true);
+                }
+            }
+        }
 
-        Ok(should_float)
+        Ok(false)
     }
 
     /// Update the border color of the given client window.
@@ -281,11 +715,11 @@ pub trait XConnExt: XConn + Sized {
 
     /// Set the initial window properties for a newly managed window.
     fn set_initial_properties(&self, client: Xid, config: &Config<Self>) -> Result<()> {
-        let Config {
+        let Theme {
             normal_border,
             border_width,
             ..
-        } = config;
+        } = &config.theme;
 
         let conf = &[ClientConfig::BorderPx(*border_width)];
         let attrs = &[
@@ -299,12 +733,8 @@ pub trait XConnExt: XConn + Sized {
     }
 
     /// Update the geometry of a given client based on the given [Rect].
-    fn position_client(&self, client: Xid, mut r: Rect) -> Result<()> {
-        let p = Atom::WmNormalHints.as_ref();
-        if let Ok(Some(Prop::WmNormalHints(hints))) = self.get_prop(client, p) {
-            trace!(%client, ?hints, "client has WmNormalHints: applying size hints");
-            r = hints.apply_to(r);
-        }
+    fn position_client(&self, client: Xid, r: Rect) -> Result<()> {
+        let r = apply_size_hints(self, client, r);
 
         trace!(%client, ?r, "positioning client");
         self.set_client_config(client, &[ClientConfig::Position(r)])
@@ -317,32 +747,93 @@ pub trait XConnExt: XConn + Sized {
     ///
     /// See `restack` for details of stacking order is determined.
     fn position_clients(&self, border: u32, positions: &[(Xid, Rect)]) -> Result<()> {
-        self.restack(positions.iter().map(|(id, _)| id))?;
+        let mut batch = ClientConfigBatch::new();
+        let mut previous = None;
 
         for &(c, r) in positions.iter() {
-            let r = r.shrink_in(border);
-            self.position_client(c, r)?;
+            if let Some(p) = previous {
+                batch.push(c, ClientConfig::StackAbove(p));
+            }
+            let r = apply_size_hints(self, c, r.shrink_in(border));
+            batch.push(c, ClientConfig::Position(r));
+            previous = Some(c);
         }
 
-        Ok(())
+        self.set_client_config_batch(batch)
     }
 
-    /// Restack the given windows in, each one above the last.
-    fn restack<'a, I>(&self, mut ids: I) -> Result<()>
-    where
-        I: Iterator<Item = &'a Xid>,
-    {
-        let mut previous = match ids.next() {
-            Some(id) => *id,
-            None => return Ok(()), // nothing to stack
+    /// Handle a request from a client to take focus, following the [FocusStealingPolicy][0]
+    /// set in the current [Config].
+    ///
+    /// `timestamp` should be the time of the user interaction that triggered the request
+    /// if it is known.
+    ///
+    ///   [0]: crate::core::FocusStealingPolicy
+    fn handle_focus_request(
+        &self,
+        client: Xid,
+        timestamp: Option<u32>,
+        state: &mut State<Self>,
+    ) -> Result<()> {
+        match self.resolve_focus_request(client, timestamp, state)? {
+            FocusRequestAction::Focus => self.set_active_client(client, state),
+            FocusRequestAction::MarkUrgent => {
+                state.set_urgent(client, true);
+                let w = state.outer_window(client);
+                self.set_client_border_color(w, state.config.theme.urgent_border)?;
+                self.set_client_demands_attention(client, true)
+            }
+            FocusRequestAction::Ignore => Ok(()),
+        }
+    }
+
+    /// Determine how a request from a client to take focus should be handled based on the
+    /// [FocusStealingPolicy][0] set in the current [Config].
+    ///
+    ///   [0]: crate::core::FocusStealingPolicy
+    fn resolve_focus_request(
+        &self,
+        client: Xid,
+        timestamp: Option<u32>,
+        state: &State<Self>,
+    ) -> Result<FocusRequestAction> {
+        let focused = match state.client_set.current_client() {
+            Some(&focused) if focused != client => focused,
+            _ => return Ok(FocusRequestAction::Focus),
         };
 
-        for &id in ids {
-            self.set_client_config(id, &[ClientConfig::StackAbove(previous)])?;
-            previous = id;
+        let classes = match self.get_prop(client, Atom::WmClass.as_ref())? {
+            Some(Prop::UTF8String(strs)) => strs,
+            _ => vec![],
+        };
+        let focused_time = self.client_user_time(focused)?;
+        let policy = &state.config.focus_stealing_policy;
+
+        match policy.action_for(&classes, timestamp, focused_time) {
+            FocusRequestAction::MarkUrgent if state.urgency_suppressed => {
+                Ok(FocusRequestAction::Ignore)
+            }
+            action => Ok(action),
         }
+    }
 
-        Ok(())
+    /// The time of the last user interaction with the given client as reported by its
+    /// `_NET_WM_USER_TIME` property, if it has been set.
+    fn client_user_time(&self, client: Xid) -> Result<Option<u32>> {
+        match self.get_prop(client, Atom::NetWmUserTime.as_ref())? {
+            Some(Prop::Cardinal(vals)) => Ok(vals.first().copied()),
+            _ => Ok(None),
+        }
+    }
+
+    /// Set or clear the `_NET_WM_STATE_DEMANDS_ATTENTION` state for the given client.
+    fn set_client_demands_attention(&self, client: Xid, demands_attention: bool) -> Result<()> {
+        set_net_wm_state(
+            self,
+            client,
+            Atom::NetWmStateDemandsAttention,
+            demands_attention,
+        )
     }
 
     /// Update the currently focused client and refresh the X state.
@@ -350,13 +841,6 @@ pub trait XConnExt: XConn + Sized {
         self.modify_and_refresh(state, |cs| cs.focus_client(&client))
     }
 
-    /// Warp the mouse cursor to the center of the given client window.
-    fn warp_pointer_to_window(&self, id: Xid) -> Result<()> {
-        let r = self.client_geometry(id)?;
-
-        self.warp_pointer(id, r.w as i16 / 2, r.h as i16 / 2)
-    }
-
     /// Warp the mouse cursor to the center of the given screen.
     fn warp_pointer_to_screen(&self, state: &mut State<Self>, screen_index: usize) -> Result<()> {
         let maybe_screen = state.client_set.screens().find(|s| s.index == screen_index);
@@ -376,6 +860,57 @@ pub trait XConnExt: XConn + Sized {
         self.warp_pointer(self.root(), x, y)
     }
 
+    /// The part of the given screen region that is not reserved by dock windows such as
+    /// external status bars.
+    ///
+    /// Reserved space is read from the _NET_WM_STRUT_PARTIAL property of each mapped window,
+    /// falling back to _NET_WM_STRUT if it is not set.
+    fn effective_screen_rect(&self, state: &State<Self>, mut r: Rect) -> Result<Rect> {
+        let (w, h) = state.client_set.screens().fold((0, 0), |(w, h), s| {
+            (w.max(s.r.x + s.r.w), h.max(s.r.y + s.r.h))
+        });
+        let root = Rect::new(0, 0, w, h);
+
+        for id in self.existing_clients()? {
+            if self.get_window_attributes(id)?.map_state != MapState::Viewable {
+                continue;
+            }
+
+            for atom in [Atom::NetWmStrutPartial, Atom::NetWmStrut] {
+                if let Some(Prop::Cardinal(strut)) = self.get_prop(id, atom.as_ref())? {
+                    r = apply_strut(r, root, &strut);
+                    break;
+                }
+            }
+        }
+
+        Ok(r)
+    }
+
+    /// Recalculate the [workarea][crate::pure::Screen::workarea] of each screen from the space
+    /// reserved by dock windows and set _NET_WORKAREA on the root window.
+    ///
+    /// Clients are repositioned on the next refresh.
+    fn update_workarea(&self, state: &mut State<Self>) -> Result<()> {
+        let rects: Vec<Rect> = state.client_set.screens().map(|s| s.r).collect();
+        let mut workareas = Vec::with_capacity(rects.len());
+        for r in rects {
+            workareas.push(self.effective_screen_rect(state, r)?);
+        }
+
+        for (s, r) in state.client_set.screens.iter_mut().zip(workareas) {
+            s.workarea = if r == s.r { None } else { Some(r) };
+        }
+
+        let Rect { x, y, w, h } = state.client_set.current_screen().workarea();
+        let n_workspaces = state.client_set.ordered_tags().len();
+        self.set_prop(
+            self.root(),
+            Atom::NetWorkarea.as_ref(),
+            Prop::Cardinal([x, y, w, h].repeat(n_workspaces)),
+        )
+    }
+
     /// Request the title of a given client window following ICCCM/EWMH standards.
     fn window_title(&self, id: Xid) -> Result<String> {
         match query::str_prop(Atom::WmName, id, self) {
@@ -407,6 +942,50 @@ pub trait XConnExt: XConn + Sized {
         }
     }
 
+    /// Request the icon for a given window via the _NET_WM_ICON property, selecting the
+    /// image that best matches `preferred_size` if multiple sizes are available.
+    ///
+    /// See [WmIcon::best_match] for details of how the icon is selected.
+    ///
+    /// **NOTE**: Not all programs set this property.
+    fn client_icon(&self, id: Xid, preferred_size: u32) -> Result<Option<WmIcon>> {
+        match self.get_prop(id, Atom::NetWmIcon.as_ref())? {
+            Some(Prop::Icon(icons)) => Ok(WmIcon::best_match(&icons, preferred_size).cloned()),
+            Some(Prop::Cardinal(raw)) => {
+                let icons = WmIcon::try_from_bytes(&raw)?;
+                Ok(WmIcon::best_match(&icons, preferred_size).cloned())
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Request the current opacity of a given window via the _NET_WM_WINDOW_OPACITY property.
+    ///
+    /// The returned value is in the range `0.0..=1.0` with windows that do not have the
+    /// property set being treated as fully opaque.
+    fn client_opacity(&self, id: Xid) -> Result<f64> {
+        match self.get_prop(id, Atom::NetWmWindowOpacity.as_ref())? {
+            Some(Prop::Cardinal(vals)) if !vals.is_empty() => Ok(vals[0] as f64 / u32::MAX as f64),
+            _ => Ok(1.0),
+        }
+    }
+
+    /// Set the opacity of a given window via the _NET_WM_WINDOW_OPACITY property.
+    ///
+    /// `opacity` is clamped to the range `0.0..=1.0`.
+    ///
+    /// **NOTE**: This property is only honoured if you are running a compositor, which will
+    /// read it from the top-level window: for framed clients it needs to be set on the frame.
+    fn set_client_opacity(&self, id: Xid, opacity: f64) -> Result<()> {
+        let val = (opacity.clamp(0.0, 1.0) * u32::MAX as f64).round() as u32;
+
+        self.set_prop(
+            id,
+            Atom::NetWmWindowOpacity.as_ref(),
+            Prop::Cardinal(vec![val]),
+        )
+    }
+
     /// Run the provided [Query], returning the result.
     fn query(&self, query: &dyn Query<Self>, id: Xid) -> Result<bool> {
         query.run(id, self)
@@ -422,6 +1001,262 @@ pub trait XConnExt: XConn + Sized {
 // Auto impl XConnExt for all XConn impls
 impl<T> XConnExt for T where T: XConn {}
 
+// Remove the space reserved by a _NET_WM_STRUT(_PARTIAL) from a screen. Struts are given
+// as distances from the edges of the root window, with partial struts also specifying the
+// range along that edge that they cover.
+fn apply_strut(mut r: Rect, root: Rect, strut: &[u32]) -> Rect {
+    if strut.len() < 4 {
+        return r;
+    }
+
+    let range = |i: usize, lo: u32, len: u32| match strut.get(i..i + 2) {
+        Some(&[start, end]) if strut.len() >= 12 => start < lo + len && end >= lo,
+        _ => true,
+    };
+    let (left, right, top, bottom) = (strut[0], strut[1], strut[2], strut[3]);
+
+    if left > r.x && range(4, r.y, r.h) {
+        let x = left.min(r.x + r.w);
+        r.w -= x - r.x;
+        r.x = x;
+    }
+
+    let limit = root.w.saturating_sub(right);
+    if right > 0 && limit < r.x + r.w && range(6, r.y, r.h) {
+        r.w = limit.saturating_sub(r.x);
+    }
+
+    if top > r.y && range(8, r.x, r.w) {
+        let y = top.min(r.y + r.h);
+        r.h -= y - r.y;
+        r.y = y;
+    }
+
+    let limit = root.h.saturating_sub(bottom);
+    if bottom > 0 && limit < r.y + r.h && range(10, r.x, r.w) {
+        r.h = limit.saturating_sub(r.y);
+    }
+
+    r
+}
+
+fn apply_size_hints<X: XConn>(x: &X, client: Xid, r: Rect) -> Rect {
+    let p = Atom::WmNormalHints.as_ref();
+    if let Ok(Some(Prop::WmNormalHints(hints))) = x.get_prop(client, p) {
+        trace!(%client, ?hints, "client has WmNormalHints: applying size hints");
+        return hints.apply_to(r);
+    }
+
+    r
+}
+
+// When respecting size hints, tiled clients are fitted inside of the position they have been
+// assigned by the layout rather than being stretched to fill it
+fn fit_to_size_hints<X: XConn>(x: &X, client: Xid, r: Rect) -> Rect {
+    let p = Atom::WmNormalHints.as_ref();
+    if let Ok(Some(Prop::WmNormalHints(hints))) = x.get_prop(client, p) {
+        trace!(%client, ?hints, "client has WmNormalHints: fitting within tiled position");
+        return hints.fit_within(r);
+    }
+
+    r
+}
+
+// Fullscreen clients have no border and are marked with _NET_WM_STATE_FULLSCREEN
+fn set_fullscreen_props<X: XConn>(x: &X, state: &State<X>) -> Result<()> {
+    for &c in state.diff.entered_fullscreen() {
+        trace!(%c, "client entered fullscreen");
+        x.set_client_config(state.outer_window(c), &[ClientConfig::BorderPx(0)])?;
+        set_net_wm_state(x, c, Atom::NetWmStateFullscreen, true)?;
+    }
+
+    for &c in state.diff.left_fullscreen() {
+        trace!(%c, "client left fullscreen");
+        let border = state.config.theme.border_width;
+        x.set_client_config(state.outer_window(c), &[ClientConfig::BorderPx(border)])?;
+        set_net_wm_state(x, c, Atom::NetWmStateFullscreen, false)?;
+    }
+
+    Ok(())
+}
+
+// Add or remove a single atom from the _NET_WM_STATE of a client, leaving the property
+// untouched if it is already in the requested state
+fn set_net_wm_state<X: XConn>(x: &X, client: Xid, atom: Atom, present: bool) -> Result<()> {
+    let net_wm_state = Atom::NetWmState.as_ref();
+    let atom = atom.as_ref();
+
+    let mut wstate = match x.get_prop(client, net_wm_state)? {
+        Some(Prop::Atom(atoms)) => atoms,
+        _ => vec![],
+    };
+
+    if wstate.iter().any(|a| a == atom) == present {
+        return Ok(());
+    }
+
+    wstate.retain(|a| a != atom);
+    if present {
+        wstate.push(atom.to_owned());
+    }
+
+    x.set_prop(client, net_wm_state, Prop::Atom(wstate))
+}
+
+/// The config settings that affect how clients are positioned within the space assigned to
+/// them by the layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PositionSettings {
+    border_width: u32,
+    titlebar_height: Option<u32>,
+    rounded_corners: Option<u32>,
+    respect_size_hints: bool,
+}
+
+impl PositionSettings {
+    fn new<X: XConn>(config: &Config<X>) -> Self {
+        Self {
+            border_width: config.theme.border_width,
+            titlebar_height: config.titlebar.as_ref().map(|tb| tb.height),
+            rounded_corners: config.rounded_corners,
+            respect_size_hints: config.respect_size_hints,
+        }
+    }
+}
+
+// If the appearance or any of the settings used when positioning clients have changed since
+// the last refresh then every visible client needs repositioning, even if its layout position
+// is unchanged.
+fn invalidate_positions<X: XConn>(state: &mut State<X>) {
+    let settings = PositionSettings::new(&state.config);
+    if state.position_settings == Some(settings) && !state.appearance_changed {
+        return;
+    }
+
+    trace!(
+        ?settings,
+        "appearance changed: repositioning all visible clients"
+    );
+    state.position_settings = Some(settings);
+    let visible = state.diff.after.positions.iter().map(|&(c, _)| c);
+    state.stale_positions.extend(visible);
+}
+
+// Titlebars are shown per layout, so framed clients on workspaces whose layout has changed
+// need repositioning and redrawing even if their layout positions are unchanged.
+fn invalidate_framed_clients_for_layout_changes<X: XConn>(state: &mut State<X>) {
+    for (tag, _, _) in state.diff.layout_changes() {
+        if let Some(w) = state.client_set.workspace(tag) {
+            let framed = w.clients().filter(|c| state.frames.contains_key(c));
+            state.stale_positions.extend(framed);
+        }
+    }
+}
+
+// Whether or not a visible client needs to be repositioned on this refresh
+fn needs_positioning<X: XConn>(state: &State<X>, client: &Xid) -> bool {
+    state.diff.client_changed_position(client) || state.stale_positions.contains(client)
+}
+
+// Run the user hook selected by `user` followed by every named hook selected by `named`, calling
+// each of them once for each of the given `calls`. Hooks that panic are removed as described
+// in run_hook.
+fn run_user_and_named_hooks<X, H, A, C>(
+    kind: &str,
+    calls: &[A],
+    state: &mut State<X>,
+    x: &X,
+    user: fn(&mut Config<X>) -> &mut Option<Box<H>>,
+    named: fn(&mut NamedHook<X>) -> Option<&mut Box<H>>,
+    call: C,
+) where
+    X: XConn,
+    H: ?Sized,
+    A: fmt::Debug,
+    C: Fn(&mut H, &A, &mut State<X>, &X) -> Result<()>,
+{
+    if calls.is_empty() {
+        return;
+    }
+
+    let mut hook = user(&mut state.config).take();
+    if let Some(h) = hook.as_mut() {
+        let desc = format!("user {kind} hook");
+        for args in calls.iter() {
+            trace!(?args, "running {desc}");
+            if !run_hook(&desc, state, |s| call(h, args, s, x)).1 {
+                hook = None;
+                break;
+            }
+        }
+    }
+    *user(&mut state.config) = hook;
+
+    run_named_hooks(state, x, |name, hook, state, x| match named(hook) {
+        Some(h) => {
+            let desc = format!("named {kind} hook '{name}'");
+            calls.iter().all(|args| {
+                trace!(%name, ?args, "running {desc}");
+                run_hook(&desc, state, |s| call(h, args, s, x)).1
+            })
+        }
+        None => true,
+    });
+}
+
+// Restack and position the visible clients in a single batch, skipping any requests
+// for clients whose position and stacking are unchanged since the last refresh.
+fn position_changed_clients<X: XConn>(x: &X, state: &State<X>) -> Result<()> {
+    let border = state.config.theme.border_width;
+    let restack = state.diff.stacking_order_changed();
+    let mut batch = ClientConfigBatch::new();
+    let mut repositioned = Vec::new();
+    let mut previous = None;
+
+    for &(c, r) in state.diff.after.positions.iter() {
+        let w = state.outer_window(c);
+        if let (true, Some(p)) = (restack, previous) {
+            batch.push(w, ClientConfig::StackAbove(p));
+        }
+        if needs_positioning(state, &c) {
+            let shrunk = if state.client_set.is_fullscreen(&c) {
+                r
+            } else if state.config.respect_size_hints && !state.client_set.floating.contains_key(&c)
+            {
+                fit_to_size_hints(x, c, r.shrink_in(border))
+            } else {
+                apply_size_hints(x, c, r.shrink_in(border))
+            };
+            batch.push(w, ClientConfig::Position(shrunk));
+            if w != c {
+                let th = titlebar_height(state, c).min(shrunk.h);
+                let inner = Rect::new(0, th, shrunk.w, shrunk.h - th);
+                batch.push(c, ClientConfig::Position(inner));
+            }
+            repositioned.push((w, r, shrunk));
+        }
+        previous = Some(w);
+    }
+
+    if batch.is_empty() {
+        trace!("no client positions have changed: skipping positioning");
+        return Ok(());
+    }
+
+    x.set_client_config_batch(batch)?;
+
+    if let Some(radius) = state.config.rounded_corners {
+        for (c, r, shrunk) in repositioned {
+            // Clients covering an entire screen (fullscreen) should not be rounded
+            let fills_screen = state.client_set.screens().any(|s| s.r == r);
+            let radius = if fills_screen { None } else { Some(radius) };
+            x.set_rounded_corners(c, shrunk, border, radius)?;
+        }
+    }
+
+    Ok(())
+}
+
 // The main logic for inserting a new client into the StackSet without any refresh
 // of the X state. In normal window manager operation, the `manage` method on XConnExt
 // is always used: this is provided independently to support managing existing clients
@@ -432,30 +1267,216 @@ pub(crate) fn manage_without_refresh<X: XConn>(
     state: &mut State<X>,
     x: &X,
 ) -> Result<()> {
-    let should_float = x.client_should_float(id, &state.config.floating_classes)?;
+    let should_float = x.client_should_float(id, &state.config)?;
     let r = x.client_geometry(id)?;
 
-    match tag {
+    // Transient clients are placed on the same workspace as their parent (unless we have been
+    // explicitly told otherwise) and centered over it.
+    let parent = managed_transient_parent(id, state, x)?;
+    let parent_tag = parent
+        .and_then(|p| state.client_set.tag_for_client(&p))
+        .map(|t| t.to_string());
+
+    // Clients on a different X screen to the one currently focused are placed on the
+    // workspace visible on the first screen belonging to their X screen.
+    let root = x.client_root(id)?;
+    let x_screen = state.x_screen_for_root(root).unwrap_or(0);
+    state.client_x_screens.insert(id, x_screen);
+    let x_screen_tag = if state.client_set.current_screen().x_screen() == x_screen {
+        None
+    } else {
+        state
+            .client_set
+            .screens()
+            .find(|s| s.x_screen() == x_screen)
+            .map(|s| s.workspace.tag.clone())
+    };
+
+    match tag.or(parent_tag.as_deref()).or(x_screen_tag.as_deref()) {
         Some(tag) => state.client_set.insert_as_focus_for(tag, id),
         None => state.client_set.insert(id),
     }
 
-    if should_float {
+    if let Some(p) = parent {
+        trace!(%id, parent = %p, "client is transient: centering over parent");
+        let pr = x.client_geometry(state.outer_window(p))?;
+        state.client_set.set_transient_for(id, p);
+        state
+            .client_set
+            .float_unchecked(id, r.centered_in(&pr).unwrap_or(r));
+    } else if should_float {
         state.client_set.float_unchecked(id, r);
     }
 
+    if let Some(leader) = x.client_window_group(id)? {
+        trace!(%id, %leader, "client is part of a window group");
+        state.client_set.set_window_group(id, leader);
+    }
+
+    if state.config.reparent_clients {
+        frame_client(id, r, state, x)?;
+    }
+
     let mut hook = state.config.manage_hook.take();
     if let Some(ref mut h) = hook {
         trace!("running user manage hook");
-        if let Err(e) = h.call(id, state, x) {
-            error!(%e, "error returned from user manage hook");
+        if !run_hook("user manage hook", state, |s| h.call(id, s, x)).1 {
+            hook = None;
         }
     }
     state.config.manage_hook = hook;
 
+    run_named_hooks(state, x, |name, hook, state, x| match hook {
+        NamedHook::Manage(h) => {
+            trace!(%name, "running named manage hook");
+            let desc = format!("named manage hook '{name}'");
+            run_hook(&desc, state, |s| h.call(id, s, x)).1
+        }
+        _ => true,
+    });
+
+    apply_workspace_rules(id, r, state);
+
+    Ok(())
+}
+
+// Apply any workspace rules for the workspace a newly managed client has been placed on
+fn apply_workspace_rules<X: XConn>(id: Xid, r: Rect, state: &mut State<X>) {
+    let tag = match state.client_set.tag_for_client(&id) {
+        Some(tag) => tag.to_owned(),
+        None => return, // the client was removed by the manage hook
+    };
+    let rules = match state.config.workspace_rules.get(&tag) {
+        Some(rules) => rules.clone(),
+        None => return,
+    };
+
+    let r_screen = state
+        .client_set
+        .screens()
+        .find(|s| s.workspace.tag == tag)
+        .unwrap_or_else(|| state.client_set.current_screen())
+        .r;
+
+    for rule in rules {
+        trace!(%id, %tag, ?rule, "applying workspace rule");
+        match rule {
+            WorkspaceRule::Float => state.client_set.float_unchecked(id, r),
+            WorkspaceRule::FloatAt(r) => state.client_set.float_unchecked(id, r),
+            WorkspaceRule::FloatCentered(w, h) => {
+                match r_screen.scale_w(w).scale_h(h).centered_in(&r_screen) {
+                    Some(r) => state.client_set.float_unchecked(id, r),
+                    None => warn!(%id, w, h, "invalid ratios for centered workspace rule"),
+                }
+            }
+            WorkspaceRule::Tile => {
+                state.client_set.sink(&id);
+            }
+            WorkspaceRule::Fullscreen => state.client_set.enter_fullscreen(&id),
+        }
+    }
+}
+
+// The height of the titlebar for a framed client given the layout of the workspace it is on
+fn titlebar_height<X: XConn>(state: &State<X>, client: Xid) -> u32 {
+    let tb = match (&state.config.titlebar, state.frame_for(client)) {
+        (Some(tb), Some(_)) if !state.client_set.is_fullscreen(&client) => tb,
+        _ => return 0,
+    };
+
+    let shown = state
+        .client_set
+        .tag_for_client(&client)
+        .and_then(|tag| state.client_set.workspace(tag))
+        .map(|w| tb.shown_for_layout(&w.layout_name()))
+        .unwrap_or(false);
+
+    if shown {
+        tb.height
+    } else {
+        0
+    }
+}
+
+/// Redraw the titlebar for a framed client if it has one.
+pub(crate) fn draw_titlebar<X: XConn>(client: Xid, state: &State<X>, x: &X) -> Result<()> {
+    let (frame, tb) = match (state.frame_for(client), &state.config.titlebar) {
+        (Some(frame), Some(tb)) if titlebar_height(state, client) > 0 => (frame, tb),
+        _ => return Ok(()),
+    };
+
+    let positions = &state.diff.after.positions;
+    let w = match positions.iter().find(|&&(c, _)| c == client) {
+        Some((_, r)) => r.shrink_in(state.config.theme.border_width).w,
+        None => return Ok(()), // not currently visible
+    };
+
+    let focused = state.client_set.current_client() == Some(&client);
+    let title = x.window_title(client)?;
+
+    x.draw_titlebar(frame, &tb.titlebar(title, w, focused))
+}
+
+// Titlebars need redrawing when their size or focus state changes
+fn draw_changed_titlebars<X: XConn>(x: &X, state: &State<X>) -> Result<()> {
+    if state.config.titlebar.is_none() {
+        return Ok(());
+    }
+
+    let mut to_draw: Vec<Xid> = state
+        .diff
+        .after
+        .positions
+        .iter()
+        .map(|&(c, _)| c)
+        .filter(|c| state.appearance_changed || needs_positioning(state, c))
+        .collect();
+
+    if state.diff.focused_client_changed() {
+        to_draw.extend(state.diff.before.focused_client);
+        to_draw.extend(state.diff.after.focused_client);
+    }
+
+    to_draw.sort();
+    to_draw.dedup();
+    for c in to_draw {
+        draw_titlebar(c, state, x)?;
+    }
+
     Ok(())
 }
 
+// Wrap a client in a frame window owned by the window manager if frames are supported
+fn frame_client<X: XConn>(id: Xid, r: Rect, state: &mut State<X>, x: &X) -> Result<()> {
+    let frame = match x.create_frame(state.root_for_client(id), r)? {
+        Some(frame) => frame,
+        None => return Ok(()),
+    };
+
+    // Reparenting a mapped window (such as an existing client on startup) unmaps it first so
+    // we need to ignore the resulting UnmapNotify
+    if x.get_window_attributes(id)?.map_state == MapState::Viewable {
+        *state.pending_unmap.entry(id).or_insert(0) += 1;
+    }
+
+    trace!(%id, %frame, "reparenting client into frame");
+    x.reparent(id, frame, Point::default())?;
+    state.frames.insert(id, frame);
+
+    Ok(())
+}
+
+// The parent of a transient client if it is a client that we are managing
+fn managed_transient_parent<X: XConn>(id: Xid, state: &State<X>, x: &X) -> Result<Option<Xid>> {
+    match x.get_prop(id, Atom::WmTransientFor.as_ref())? {
+        Some(Prop::Window(ids)) => Ok(ids
+            .first()
+            .copied()
+            .filter(|&p| p != id && state.client_set.contains(&p))),
+        _ => Ok(None),
+    }
+}
+
 fn notify_killed<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
     for &c in state.diff.killed_clients() {
         x.kill(c)?;
@@ -465,17 +1486,68 @@ fn notify_killed<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
 }
 
 fn set_window_props<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
+    let cs = &state.client_set;
+    state.border_overrides.retain(|id, _| cs.contains(id));
+
     for &c in state.diff.new_clients() {
         x.set_initial_properties(c, &state.config)?;
+
+        for rule in state.config.border_rules.iter() {
+            if rule.query.run(c, x)? {
+                trace!(%c, ?rule, "applying border rule");
+                state
+                    .border_overrides
+                    .insert(c, (rule.normal, rule.focused));
+                break;
+            }
+        }
+
+        // Borders for framed clients are drawn by the frame
+        if let Some(frame) = state.frame_for(c) {
+            let border_width = state.config.theme.border_width;
+            x.set_client_config(c, &[ClientConfig::BorderPx(0)])?;
+            x.set_client_config(frame, &[ClientConfig::BorderPx(border_width)])?;
+        }
+
+        if state.frame_for(c).is_some() || state.border_overrides.contains_key(&c) {
+            x.set_client_border_color(state.outer_window(c), state.border_color(c, false))?;
+        }
     }
 
     if let Some(focused) = state.diff.before.focused_client {
-        x.set_client_border_color(focused, state.config.normal_border)?;
+        let w = state.outer_window(focused);
+        x.set_client_border_color(w, state.border_color(focused, false))?;
     }
 
     if let Some(&focused) = state.client_set.current_client() {
         trace!(?focused, "setting border for focused client");
-        x.set_client_border_color(focused, state.config.focused_border)?;
+        let w = state.outer_window(focused);
+        x.set_client_border_color(w, state.border_color(focused, true))?;
+    }
+
+    Ok(())
+}
+
+// Re-apply border settings to all managed clients if they have been changed since the last
+// refresh. Newly managed clients have already been set up by set_window_props, fullscreen
+// clients have no border and titlebars are redrawn by draw_changed_titlebars.
+fn apply_appearance_changes<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
+    if !state.appearance_changed {
+        return Ok(());
+    }
+
+    let border_width = state.config.theme.border_width;
+    let focused = state.client_set.current_client().copied();
+
+    for &c in state.client_set.clients() {
+        if state.client_set.is_fullscreen(&c) {
+            continue;
+        }
+
+        let w = state.outer_window(c);
+        trace!(%c, %w, "re-applying border settings");
+        x.set_client_config(w, &[ClientConfig::BorderPx(border_width)])?;
+        x.set_client_border_color(w, state.border_color(c, Some(c) == focused))?;
     }
 
     Ok(())
@@ -491,14 +1563,19 @@ fn notify_hidden_workspaces<X: XConn>(state: &mut State<X>) {
         .for_each(|ws| ws.broadcast_message(Hide));
 }
 
-// Warp the cursor if this diff resulted in a focus change
+// Warp the cursor if this diff resulted in a focus change that wasn't triggered by the mouse
 fn handle_pointer_change<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
-    if !state.config.focus_follow_mouse {
+    if !state.config.warp_pointer_on_focus {
         return Ok(());
     }
 
     trace!("checking if focus should change");
-    if !matches!(state.current_event, Some(XEvent::Enter(_))) {
+    let mouse_initiated = matches!(
+        state.current_event,
+        Some(XEvent::Enter(_) | XEvent::Leave(_) | XEvent::MotionNotify(_) | XEvent::MouseEvent(_))
+    );
+
+    if !mouse_initiated {
         if let Some(id) = state.diff.focused_client() {
             trace!("focused client changed");
             // If the current workspace has any floating windows then warping the
@@ -541,11 +1618,17 @@ fn set_window_visibility<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
     for &c in state.diff.visible_clients() {
         trace!(?c, "revealing client");
         x.reveal(c, &state.client_set, &mut state.mapped)?;
+        if let Some(frame) = state.frame_for(c) {
+            x.map(frame)?;
+        }
     }
 
     for &c in state.diff.hidden_clients() {
         trace!(?c, "hiding client");
         x.hide(c, &mut state.mapped, &mut state.pending_unmap)?;
+        if let Some(frame) = state.frame_for(c) {
+            x.unmap(frame)?;
+        }
     }
 
     for &c in state.diff.withdrawn_clients() {
@@ -558,8 +1641,313 @@ fn set_window_visibility<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
 
 fn set_focus<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
     if let Some(&id) = state.client_set.current_client() {
+        if state.diff.focused_client_changed() {
+            x.set_client_demands_attention(id, false)?;
+        }
         x.focus(id)
     } else {
         x.focus(state.root)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::actions::{key_handler, modify_with},
+        core::{bindings::KeyCode, titlebar::TitlebarConfig, FocusStealingPolicy, WorkspaceRule},
+        extensions::hooks::manage::move_to_tag,
+        x::{
+            event::{PointerChange, PropertyEvent},
+            fixtures::{monocle_titlebars, positions_of, ScriptedWm, SCREEN},
+            mock::{Request, ScriptedConn},
+            property::{WmHints, WmNormalHints},
+            query::ClassName,
+        },
+    };
+    use simple_test_case::test_case;
+
+    const KEY: KeyCode = KeyCode { mask: 0, code: 42 };
+
+    #[test]
+    fn changed_size_hints_reposition_the_client() {
+        let wm = ScriptedWm::new()
+            .with_clients(1)
+            .with_events([XEvent::PropertyNotify(PropertyEvent {
+                id: Xid(1),
+                atom: Atom::WmNormalHints.as_ref().to_string(),
+                is_root: false,
+            })])
+            .run();
+
+        assert_eq!(positions_of(&wm, Xid(1)).len(), 2);
+    }
+
+    #[test]
+    fn changed_position_settings_reposition_all_clients() {
+        let wm = ScriptedWm::new()
+            .with_clients(2)
+            .with_key(
+                KEY,
+                key_handler(|state, x: &ScriptedConn| {
+                    state.config.rounded_corners = Some(5);
+                    x.refresh(state)
+                }),
+            )
+            .with_events([XEvent::KeyPress(KEY)])
+            .run();
+
+        // Client 1 is positioned when it is mapped and again when client 2 is mapped
+        assert_eq!(positions_of(&wm, Xid(1)).len(), 3);
+        assert_eq!(positions_of(&wm, Xid(2)).len(), 2);
+    }
+
+    #[test]
+    fn urgency_is_shared_by_window_groups() {
+        // Client 3 is urgent and in the window group led by client 1
+        let hints = WmHints::try_from_bytes(&[0b101000000, 0, 0, 0, 0, 0, 0, 0, 1]).unwrap();
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| conn.with_prop(Xid(3), Atom::WmHints.as_ref(), Prop::WmHints(hints)))
+            .with_events([
+                XEvent::MapRequest(Xid(1)),
+                XEvent::MapRequest(Xid(3)),
+                XEvent::MapRequest(Xid(2)),
+                XEvent::PropertyNotify(PropertyEvent {
+                    id: Xid(3),
+                    atom: Atom::WmHints.as_ref().to_owned(),
+                    is_root: false,
+                }),
+            ])
+            .run();
+
+        let mut urgent = wm.state.urgent_clients();
+        urgent.sort();
+        assert_eq!(urgent, vec![Xid(1), Xid(3)]);
+
+        let color = wm.state.config.theme.urgent_border.rgb_u32();
+        let requests = wm.x().requests();
+        for id in [Xid(1), Xid(3)] {
+            let r = Request::SetClientAttributes(id, vec![ClientAttr::BorderColor(color)]);
+            assert!(requests.contains(&r), "{r:?} not in {requests:?}");
+        }
+    }
+
+    #[test_case(200, FocusRequestAction::MarkUrgent, Xid(2), vec![]; "newer user time")]
+    #[test_case(50, FocusRequestAction::MarkUrgent, Xid(1), vec![Xid(2)]; "older user time")]
+    #[test_case(0, FocusRequestAction::MarkUrgent, Xid(1), vec![Xid(2)]; "zero user time")]
+    #[test_case(0, FocusRequestAction::Ignore, Xid(1), vec![]; "zero user time ignored")]
+    #[test_case(0, FocusRequestAction::Focus, Xid(2), vec![]; "stealing allowed")]
+    #[test]
+    fn new_clients_respect_the_focus_stealing_policy(
+        user_time: u32,
+        on_steal: FocusRequestAction,
+        focused: Xid,
+        urgent: Vec<Xid>,
+    ) {
+        let user_time_prop = |t| Prop::Cardinal(vec![t]);
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| {
+                conn.with_prop(Xid(1), Atom::NetWmUserTime.as_ref(), user_time_prop(100))
+                    .with_prop(
+                        Xid(2),
+                        Atom::NetWmUserTime.as_ref(),
+                        user_time_prop(user_time),
+                    )
+            })
+            .with_config(|c| {
+                c.focus_stealing_policy = FocusStealingPolicy {
+                    on_steal,
+                    always_focus_classes: vec![],
+                }
+            })
+            .with_clients(2)
+            .run();
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&focused));
+        assert_eq!(wm.state.urgent_clients(), urgent);
+    }
+
+    #[test]
+    fn layout_changes_reposition_framed_clients() {
+        // A single client has the same position in both layouts
+        let wm = ScriptedWm::new()
+            .with_config(monocle_titlebars)
+            .with_frame(Xid(1), Xid(10))
+            .with_clients(1)
+            .with_key(KEY, modify_with(|cs| cs.next_layout()))
+            .with_events([XEvent::KeyPress(KEY)])
+            .run();
+
+        let th = TitlebarConfig::default().height;
+        let r = wm.state.diff.after.positions[..]
+            .iter()
+            .find(|&&(c, _)| c == Xid(1))
+            .map(|&(_, r)| r.shrink_in(wm.state.config.theme.border_width))
+            .unwrap();
+        let inner = Rect::new(0, th, r.w, r.h - th);
+
+        assert_eq!(positions_of(&wm, Xid(1)).last(), Some(&inner));
+    }
+
+    #[test]
+    fn workspace_rules_are_applied_after_the_manage_hook() {
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| {
+                conn.with_prop(
+                    Xid(2),
+                    Atom::WmClass.as_ref(),
+                    Prop::UTF8String(vec!["mpv".to_owned(), "mpv".to_owned()]),
+                )
+            })
+            .with_clients(3)
+            .with_config(|c| {
+                c.workspace_rules = [
+                    ("1".to_owned(), vec![WorkspaceRule::FloatCentered(0.5, 0.5)]),
+                    ("3".to_owned(), vec![WorkspaceRule::Fullscreen]),
+                ]
+                .into_iter()
+                .collect();
+                c.compose_or_set_manage_hook((ClassName("mpv"), move_to_tag("3")));
+            })
+            .run();
+
+        let cs = &wm.state.client_set;
+        assert!(cs.floating.contains_key(&Xid(1)));
+        assert!(cs.floating.contains_key(&Xid(3)));
+        assert_eq!(cs.tag_for_client(&Xid(2)), Some("3"));
+        assert!(cs.is_fullscreen(&Xid(2)));
+        assert!(!cs.is_fullscreen(&Xid(1)));
+    }
+
+    #[test]
+    fn appearance_changes_are_applied_to_all_clients_on_refresh() {
+        let wm = ScriptedWm::new()
+            .with_clients(2)
+            .with_events([XEvent::KeyPress(KEY), XEvent::KeyPress(KEY)])
+            .with_key(
+                KEY,
+                key_handler(|state, x: &ScriptedConn| {
+                    if state.config.theme.border_width != 5 {
+                        state.set_border_colors(0x111111ff, 0x222222ff);
+                        state.set_border_width(5);
+                    }
+                    x.refresh(state)
+                }),
+            )
+            .run();
+
+        let requests = wm.x().requests();
+        for r in [
+            Request::SetClientConfig(Xid(1), vec![ClientConfig::BorderPx(5)]),
+            Request::SetClientConfig(Xid(2), vec![ClientConfig::BorderPx(5)]),
+            Request::SetClientAttributes(Xid(1), vec![ClientAttr::BorderColor(0x111111)]),
+            Request::SetClientAttributes(Xid(2), vec![ClientAttr::BorderColor(0x222222)]),
+        ] {
+            assert!(requests.contains(&r), "{r:?} not in {requests:?}");
+        }
+
+        // Only applied on the first key press
+        let n = requests
+            .iter()
+            .filter(|&r| r == &Request::SetClientConfig(Xid(1), vec![ClientConfig::BorderPx(5)]))
+            .count();
+        assert_eq!(n, 1);
+
+        // Clients are repositioned to account for the new border width
+        for &(c, r) in wm.state.diff.after.positions.iter() {
+            assert_eq!(positions_of(&wm, c).last(), Some(&r.shrink_in(5)));
+        }
+    }
+
+    #[test]
+    fn appearance_changes_are_not_applied_to_fullscreen_clients() {
+        let wm = ScriptedWm::new()
+            .with_clients(2)
+            .with_events([XEvent::KeyPress(KEY), XEvent::KeyPress(KEY)])
+            .with_key(
+                KEY,
+                key_handler(|state, x: &ScriptedConn| {
+                    if state.client_set.is_fullscreen(&Xid(2)) {
+                        state.set_border_width(5);
+                        x.refresh(state)
+                    } else {
+                        x.modify_and_refresh(state, |cs| cs.enter_fullscreen(&Xid(2)))
+                    }
+                }),
+            )
+            .run();
+
+        let requests = wm.x().requests();
+        let r = Request::SetClientConfig(Xid(1), vec![ClientConfig::BorderPx(5)]);
+        assert!(requests.contains(&r), "{r:?} not in {requests:?}");
+        let r = Request::SetClientConfig(Xid(2), vec![ClientConfig::BorderPx(5)]);
+        assert!(!requests.contains(&r), "{r:?} in {requests:?}");
+        assert_eq!(positions_of(&wm, Xid(2)).last(), Some(&SCREEN));
+    }
+
+    #[test_case(false, XEvent::KeyPress(KEY), 0; "disabled")]
+    #[test_case(true, XEvent::KeyPress(KEY), 3; "keyboard focus change")]
+    #[test_case(
+        true,
+        XEvent::Enter(PointerChange {
+            id: Xid(1),
+            abs: Point::new(10, 10),
+            relative: Point::new(10, 10),
+            same_screen: true,
+        }),
+        2;
+        "mouse focus change"
+    )]
+    #[test]
+    fn pointer_is_warped_on_non_mouse_focus_changes(enabled: bool, event: XEvent, warps: usize) {
+        let wm = ScriptedWm::new()
+            .with_clients(2)
+            .with_events([event])
+            .with_key(KEY, modify_with(|cs| cs.focus_down()))
+            .with_config(|c| c.warp_pointer_on_focus = enabled)
+            .run();
+
+        let n_warps = wm
+            .x()
+            .requests()
+            .into_iter()
+            .filter(|r| matches!(r, Request::WarpPointer(..)))
+            .count();
+
+        assert_eq!(wm.state.client_set.current_client(), Some(&Xid(1)));
+        assert_eq!(n_warps, warps);
+    }
+
+    #[test_case(vec![Atom::NetWindowTypeDialog], true, vec![Xid(1), Xid(2)]; "defaults")]
+    #[test_case(vec![], true, vec![Xid(2)]; "no floating types")]
+    #[test_case(vec![Atom::NetWindowTypeDialog], false, vec![Xid(1)]; "fixed size tiled")]
+    #[test]
+    fn clients_are_floated_by_type_and_size(
+        floating_window_types: Vec<Atom>,
+        float_fixed_size_clients: bool,
+        expected: Vec<Xid>,
+    ) {
+        let dialog = Prop::Atom(vec![Atom::NetWindowTypeDialog.as_ref().to_owned()]);
+        let fixed = Rect::new(0, 0, 300, 200);
+        let hints = WmNormalHints::new(Default::default(), None, Some(fixed), Some(fixed), None);
+        let wm = ScriptedWm::new()
+            .with_conn(|conn| {
+                conn.with_prop(Xid(1), Atom::NetWmWindowType.as_ref(), dialog)
+                    .with_prop(
+                        Xid(2),
+                        Atom::WmNormalHints.as_ref(),
+                        Prop::WmNormalHints(hints),
+                    )
+            })
+            .with_clients(3)
+            .with_config(|c| {
+                c.floating_window_types = floating_window_types;
+                c.float_fixed_size_clients = float_fixed_size_clients;
+            })
+            .run();
+
+        let mut floating: Vec<Xid> = wm.state.client_set.floating.keys().copied().collect();
+        floating.sort();
+        assert_eq!(floating, expected);
+    }
+}
@@ -18,6 +18,8 @@ pub enum Prop {
     Bytes(Vec<u32>),
     /// A cardinal number
     Cardinal(Vec<u32>),
+    /// One or more icon images decoded from the _NET_WM_ICON property
+    Icon(Vec<WmIcon>),
     /// UTF-8 encoded string data
     UTF8String(Vec<String>),
     /// An X window IDs
@@ -216,6 +218,20 @@ impl WmHints {
             window_group: raw[8],
         })
     }
+
+    /// The group leader of the window group this client belongs to, if it has set one.
+    pub fn window_group(&self) -> Option<Xid> {
+        if self.flags.contains(WmHintsFlags::WINDOW_GROUP_HINT) && self.window_group != 0 {
+            Some(Xid(self.window_group))
+        } else {
+            None
+        }
+    }
+
+    /// Whether or not this client has set its urgency hint.
+    pub fn is_urgent(&self) -> bool {
+        self.flags.contains(WmHintsFlags::URGENCY_HINT)
+    }
 }
 
 /// Client requested hints about window geometry.
@@ -256,6 +272,15 @@ impl WmNormalHints {
         }
     }
 
+    /// Whether or not these hints specify a fixed size for the client, with its min and
+    /// max sizes being set to the same (non-zero) value.
+    pub fn is_fixed_size(&self) -> bool {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => min.w > 0 && min.h > 0 && (min.w, min.h) == (max.w, max.h),
+            _ => false,
+        }
+    }
+
     /// Apply these size hints to a given [Rect].
     ///
     /// NOTE: currently only the max size is respected
@@ -277,6 +302,32 @@ impl WmNormalHints {
         r
     }
 
+    /// Fit a client respecting these size hints within the given slot (such as a tiled
+    /// position from a layout).
+    ///
+    /// The width and height are clamped independently to the min and max sizes, with
+    /// clients smaller than the slot being centered within it rather than stretched to
+    /// fill it. A max size of 0 in either dimension is treated as unconstrained.
+    pub fn fit_within(&self, slot: Rect) -> Rect {
+        let mut r = slot;
+
+        if let Some(max) = self.max {
+            if max.w > 0 {
+                r.w = r.w.min(max.w);
+            }
+            if max.h > 0 {
+                r.h = r.h.min(max.h);
+            }
+        }
+
+        if let Some(min) = self.min {
+            r.w = r.w.max(min.w);
+            r.h = r.h.max(min.h);
+        }
+
+        r.centered_in(&slot).unwrap_or(r)
+    }
+
     /// Try to construct a [WmNormalHints] instance from raw bytes.
     ///
     /// This method expects a slice of 18 u32s corresponding to the C struct layout shown below.
@@ -340,6 +391,101 @@ impl WmNormalHints {
     }
 }
 
+/// A single icon image for a client window as set in the `_NET_WM_ICON` property.
+///
+/// Clients may provide multiple icons at different sizes: see [WmIcon::try_from_bytes]
+/// for details of the raw data format.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct WmIcon {
+    pub(crate) w: u32,
+    pub(crate) h: u32,
+    pub(crate) argb: Vec<u32>,
+}
+
+impl WmIcon {
+    /// Create a new instance from component parts
+    pub fn new(w: u32, h: u32, argb: Vec<u32>) -> Self {
+        Self { w, h, argb }
+    }
+
+    /// The width of this icon in pixels
+    pub fn width(&self) -> u32 {
+        self.w
+    }
+
+    /// The height of this icon in pixels
+    pub fn height(&self) -> u32 {
+        self.h
+    }
+
+    /// The raw pixel data for this icon as 0xAARRGGBB u32s in row major order.
+    pub fn argb(&self) -> &[u32] {
+        &self.argb
+    }
+
+    /// The pixel data for this icon as RGBA bytes in row major order.
+    pub fn rgba(&self) -> Vec<u8> {
+        self.argb
+            .iter()
+            .flat_map(|&p| {
+                let [a, r, g, b] = p.to_be_bytes();
+                [r, g, b, a]
+            })
+            .collect()
+    }
+
+    /// Try to parse all of the icons contained in the raw `_NET_WM_ICON` property data.
+    ///
+    /// The data is an array of 32 bit packed CARDINAL ARGB with high byte being A, low byte
+    /// being B. The first two cardinals are width, height. Data is in rows, left to right
+    /// and top to bottom. Multiple icons may be present one after another.
+    pub fn try_from_bytes(raw: &[u32]) -> Result<Vec<Self>> {
+        let mut icons = Vec::new();
+        let mut remaining = raw;
+
+        while !remaining.is_empty() {
+            let (w, h) = match remaining {
+                [w, h, ..] => (*w, *h),
+                _ => {
+                    return Err(Error::InvalidHints {
+                        reason: "truncated icon header in _NET_WM_ICON".to_owned(),
+                    })
+                }
+            };
+
+            let n_pixels = (w as usize) * (h as usize);
+            let data = &remaining[2..];
+            if data.len() < n_pixels {
+                return Err(Error::InvalidHints {
+                    reason: format!(
+                        "_NET_WM_ICON claimed {w}x{h} pixels but only {} were present",
+                        data.len()
+                    ),
+                });
+            }
+
+            icons.push(Self::new(w, h, data[..n_pixels].to_vec()));
+            remaining = &data[n_pixels..];
+        }
+
+        Ok(icons)
+    }
+
+    /// Select the icon that best matches `preferred_size` from a set of icons.
+    ///
+    /// The smallest icon that is at least `preferred_size` pixels in both dimensions
+    /// is returned if there is one, otherwise the largest available icon.
+    pub fn best_match(icons: &[Self], preferred_size: u32) -> Option<&Self> {
+        let large_enough = icons
+            .iter()
+            .filter(|i| i.w >= preferred_size && i.h >= preferred_size)
+            .min_by_key(|i| i.w * i.h);
+
+        large_enough.or_else(|| icons.iter().max_by_key(|i| i.w * i.h))
+    }
+}
+
 /// Window Attributes honoured by penose.
 ///
 /// Only a small subset of window attributes are checked and honoured by penrose. This list may be
@@ -386,3 +532,83 @@ impl WindowAttributes {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test]
+    fn wm_icon_parses_multiple_icons() {
+        let raw = [1, 1, 0xff112233, 2, 1, 0xff000000, 0x00ffffff];
+        let icons = WmIcon::try_from_bytes(&raw).unwrap();
+
+        assert_eq!(
+            icons,
+            vec![
+                WmIcon::new(1, 1, vec![0xff112233]),
+                WmIcon::new(2, 1, vec![0xff000000, 0x00ffffff]),
+            ]
+        );
+    }
+
+    #[test]
+    fn wm_icon_rejects_truncated_data() {
+        assert!(WmIcon::try_from_bytes(&[2, 2, 0, 0, 0]).is_err());
+        assert!(WmIcon::try_from_bytes(&[2]).is_err());
+    }
+
+    #[test]
+    fn wm_icon_rgba_reorders_channels() {
+        let icon = WmIcon::new(1, 1, vec![0x80112233]);
+
+        assert_eq!(icon.rgba(), vec![0x11, 0x22, 0x33, 0x80]);
+    }
+
+    #[test]
+    fn wm_icon_best_match_prefers_smallest_large_enough() {
+        let icons = vec![
+            WmIcon::new(16, 16, vec![0; 256]),
+            WmIcon::new(64, 64, vec![0; 4096]),
+            WmIcon::new(32, 32, vec![0; 1024]),
+        ];
+
+        assert_eq!(WmIcon::best_match(&icons, 20).map(|i| i.w), Some(32));
+        assert_eq!(WmIcon::best_match(&icons, 128).map(|i| i.w), Some(64));
+        assert_eq!(WmIcon::best_match(&[], 16), None);
+    }
+
+    fn size_hints(min: Option<(u32, u32)>, max: Option<(u32, u32)>) -> WmNormalHints {
+        let r = |(w, h)| Rect::new(0, 0, w, h);
+
+        WmNormalHints::new(
+            WmNormalHintsFlags::empty(),
+            None,
+            min.map(r),
+            max.map(r),
+            None,
+        )
+    }
+
+    #[test_case(None, None, false; "no hints")]
+    #[test_case(Some((100, 50)), None, false; "min only")]
+    #[test_case(Some((100, 50)), Some((200, 50)), false; "different min and max")]
+    #[test_case(Some((0, 0)), Some((0, 0)), false; "zero size")]
+    #[test_case(Some((100, 50)), Some((100, 50)), true; "same min and max")]
+    #[test]
+    fn is_fixed_size(min: Option<(u32, u32)>, max: Option<(u32, u32)>, expected: bool) {
+        assert_eq!(size_hints(min, max).is_fixed_size(), expected);
+    }
+
+    #[test_case(None, None, Rect::new(10, 10, 200, 100); "no hints")]
+    #[test_case(None, Some((100, 50)), Rect::new(60, 35, 100, 50); "max smaller than slot")]
+    #[test_case(None, Some((100, 0)), Rect::new(60, 10, 100, 100); "max width only")]
+    #[test_case(None, Some((400, 400)), Rect::new(10, 10, 200, 100); "max larger than slot")]
+    #[test_case(Some((300, 50)), None, Rect::new(10, 10, 300, 100); "min larger than slot")]
+    #[test]
+    fn fit_within(min: Option<(u32, u32)>, max: Option<(u32, u32)>, expected: Rect) {
+        let slot = Rect::new(10, 10, 200, 100);
+
+        assert_eq!(size_hints(min, max).fit_within(slot), expected);
+    }
+}
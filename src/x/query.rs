@@ -7,9 +7,65 @@ use std::fmt;
 
 /// A query to be run against client windows for identifying specific windows
 /// or programs.
+///
+/// Queries can be combined using [Query::and], [Query::or] and [Query::not]:
+/// ```
+/// # use penrose::x::{query::{AppName, ClassName, Title}, Query, XConn};
+/// # fn example<X: XConn + 'static>() -> impl Query<X> {
+/// ClassName("Firefox")
+///     .and(Title("Library").not())
+///     .or(AppName("pavucontrol"))
+/// # }
+/// ```
 pub trait Query<X: XConn> {
     /// Run this query for a given window ID.
     fn run(&self, id: Xid, x: &X) -> Result<bool>;
+
+    /// Convert to a trait object
+    fn boxed(self) -> Box<dyn Query<X>>
+    where
+        Self: Sized + 'static,
+    {
+        Box::new(self)
+    }
+
+    /// Combine this query with another, requiring that both hold.
+    ///
+    /// `other` is not run if this query does not hold.
+    fn and<Q>(self, other: Q) -> AndQuery<X>
+    where
+        Self: Sized + 'static,
+        Q: Query<X> + 'static,
+    {
+        AndQuery {
+            first: Box::new(self),
+            second: Box::new(other),
+        }
+    }
+
+    /// Combine this query with another, requiring that at least one of them holds.
+    ///
+    /// `other` is not run if this query holds.
+    fn or<Q>(self, other: Q) -> OrQuery<X>
+    where
+        Self: Sized + 'static,
+        Q: Query<X> + 'static,
+    {
+        OrQuery {
+            first: Box::new(self),
+            second: Box::new(other),
+        }
+    }
+
+    /// Invert this query.
+    fn not(self) -> NotQuery<X>
+    where
+        Self: Sized + 'static,
+    {
+        NotQuery {
+            inner: Box::new(self),
+        }
+    }
 }
 
 impl<X: XConn> fmt::Debug for Box<dyn Query<X>> {
@@ -18,6 +74,68 @@ impl<X: XConn> fmt::Debug for Box<dyn Query<X>> {
     }
 }
 
+impl<X: XConn> Query<X> for Box<dyn Query<X>> {
+    fn run(&self, id: Xid, x: &X) -> Result<bool> {
+        (**self).run(id, x)
+    }
+}
+
+/// The result of combining two queries using [Query::and]
+#[derive(Debug)]
+pub struct AndQuery<X>
+where
+    X: XConn,
+{
+    first: Box<dyn Query<X>>,
+    second: Box<dyn Query<X>>,
+}
+
+impl<X> Query<X> for AndQuery<X>
+where
+    X: XConn,
+{
+    fn run(&self, id: Xid, x: &X) -> Result<bool> {
+        Ok(self.first.run(id, x)? && self.second.run(id, x)?)
+    }
+}
+
+/// The result of combining two queries using [Query::or]
+#[derive(Debug)]
+pub struct OrQuery<X>
+where
+    X: XConn,
+{
+    first: Box<dyn Query<X>>,
+    second: Box<dyn Query<X>>,
+}
+
+impl<X> Query<X> for OrQuery<X>
+where
+    X: XConn,
+{
+    fn run(&self, id: Xid, x: &X) -> Result<bool> {
+        Ok(self.first.run(id, x)? || self.second.run(id, x)?)
+    }
+}
+
+/// The result of inverting a query using [Query::not]
+#[derive(Debug)]
+pub struct NotQuery<X>
+where
+    X: XConn,
+{
+    inner: Box<dyn Query<X>>,
+}
+
+impl<X> Query<X> for NotQuery<X>
+where
+    X: XConn,
+{
+    fn run(&self, id: Xid, x: &X) -> Result<bool> {
+        Ok(!self.inner.run(id, x)?)
+    }
+}
+
 pub(super) fn str_prop<X>(prop: impl AsRef<str>, id: Xid, x: &X) -> Result<Option<Vec<String>>>
 where
     X: XConn,
@@ -50,7 +168,7 @@ where
 }
 
 /// A [Query] for fetching a window's application name (the first string returned
-/// under the WM_CLASS property).
+/// under the WM_CLASS property, also known as the instance name).
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct AppName(pub &'static str);
 
@@ -83,6 +201,38 @@ where
     }
 }
 
+/// A [Query] for fetching a window's role (the WM_WINDOW_ROLE property).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Role(pub &'static str);
+
+impl<X> Query<X> for Role
+where
+    X: XConn,
+{
+    fn run(&self, id: Xid, x: &X) -> Result<bool> {
+        match str_prop("WM_WINDOW_ROLE", id, x)? {
+            Some(strs) if !strs.is_empty() => Ok(strs[0] == self.0),
+            _ => Ok(false),
+        }
+    }
+}
+
+/// A [Query] for checking whether a window has the given _NET_WM_WINDOW_TYPE.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WindowType(pub Atom);
+
+impl<X> Query<X> for WindowType
+where
+    X: XConn,
+{
+    fn run(&self, id: Xid, x: &X) -> Result<bool> {
+        match x.get_prop(id, Atom::NetWmWindowType.as_ref())? {
+            Some(Prop::Atom(types)) => Ok(types.iter().any(|t| t == self.0.as_ref())),
+            _ => Ok(false),
+        }
+    }
+}
+
 /// A [Query] for fetching a string property from a client window.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct StringProperty(pub &'static str, pub &'static str);
@@ -98,3 +248,51 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::mock::ScriptedConn;
+    use simple_test_case::test_case;
+
+    fn conn() -> ScriptedConn {
+        ScriptedConn::new(vec![])
+            .with_prop(
+                Xid(1),
+                Atom::WmClass.as_ref(),
+                Prop::UTF8String(vec!["Navigator".to_owned(), "firefox".to_owned()]),
+            )
+            .with_prop(
+                Xid(1),
+                "WM_WINDOW_ROLE",
+                Prop::UTF8String(vec!["browser".to_owned()]),
+            )
+            .with_prop(
+                Xid(1),
+                Atom::NetWmWindowType.as_ref(),
+                Prop::Atom(vec![Atom::NetWindowTypeNormal.as_ref().to_owned()]),
+            )
+    }
+
+    #[test_case(ClassName("firefox").boxed(), true; "class")]
+    #[test_case(AppName("Navigator").boxed(), true; "instance")]
+    #[test_case(Role("browser").boxed(), true; "role")]
+    #[test_case(WindowType(Atom::NetWindowTypeNormal).boxed(), true; "window type")]
+    #[test_case(WindowType(Atom::NetWindowTypeDialog).boxed(), false; "other window type")]
+    #[test_case(ClassName("firefox").and(Role("browser")).boxed(), true; "and both hold")]
+    #[test_case(ClassName("firefox").and(Role("popup")).boxed(), false; "and one holds")]
+    #[test_case(ClassName("mpv").or(Role("browser")).boxed(), true; "or one holds")]
+    #[test_case(ClassName("mpv").or(Role("popup")).boxed(), false; "or neither hold")]
+    #[test_case(ClassName("mpv").not().boxed(), true; "not")]
+    #[test]
+    fn queries_work(q: Box<dyn Query<ScriptedConn>>, expected: bool) {
+        assert_eq!(q.run(Xid(1), &conn()).unwrap(), expected);
+    }
+
+    #[test]
+    fn queries_against_windows_without_props_do_not_hold() {
+        let q = ClassName("firefox").or(Role("browser"));
+
+        assert!(!q.run(Xid(2), &conn()).unwrap());
+    }
+}
@@ -0,0 +1,584 @@
+//! Recording and replaying of the interactions between penrose and the X server.
+//!
+//! A [RecordingConn] wraps an existing [XConn] and writes every event received from the
+//! X server, every query response and every request made to a file as newline delimited
+//! JSON. The resulting [Recording] can later be replayed against a [ScriptedConn] using
+//! [replay] in order to reproduce the state the window manager ended up in.
+//!
+//! ```no_run
+//! # use penrose::{x::recording::RecordingConn, x11rb::RustConn};
+//! # fn example() -> penrose::Result<()> {
+//! let conn = RecordingConn::new(RustConn::new()?, "/tmp/penrose-recording.jsonl")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! This module requires the `recording` feature to be enabled.
+use crate::{
+    core::{
+        bindings::{KeyBindings, KeyCode, MouseBindings, MouseState},
+        hints::BindingHints,
+        titlebar::Titlebar,
+        Config, WindowManager,
+    },
+    pure::{
+        geometry::{Point, Rect},
+        OutputInfo,
+    },
+    x::{
+        event::ClientMessage,
+        mock::{Request, ScriptedConn},
+        property::{Prop, WindowAttributes, WmState},
+        Backend, ClientAttr, ClientConfig, ClientConfigBatch, Cursor, RootBackground, XConn,
+        XEvent,
+    },
+    Error, Result, Xid,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    os::unix::io::RawFd,
+    path::Path,
+    time::{Duration, Instant},
+};
+use tracing::error;
+
+/// A single entry in a [Recording].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Entry {
+    /// The screens reported by the X server
+    Screens(Vec<Rect>),
+    /// The clients that already existed when the window manager started
+    ExistingClients(Vec<Xid>),
+    /// An event received from the X server
+    Event(XEvent),
+    /// The value of a property when it was queried
+    Prop {
+        /// The window that was queried
+        id: Xid,
+        /// The name of the property
+        name: String,
+        /// The value returned
+        prop: Option<Prop>,
+    },
+    /// The geometry of a window when it was queried
+    Geometry {
+        /// The window that was queried
+        id: Xid,
+        /// The geometry returned
+        r: Rect,
+    },
+    /// The attributes of a window when they were queried
+    Attributes {
+        /// The window that was queried
+        id: Xid,
+        /// The attributes returned
+        attrs: WindowAttributes,
+    },
+    /// A request made to the X server
+    Request(Request),
+}
+
+/// A sequence of [Entry] values read from a file written by a [RecordingConn].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recording {
+    entries: Vec<Entry>,
+}
+
+impl Recording {
+    /// Load a recording from a file written by a [RecordingConn].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let f = BufReader::new(File::open(path)?);
+        let mut entries = Vec::new();
+
+        for line in f.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(&line).map_err(|e| Error::Custom(e.to_string()))?;
+            entries.push(entry);
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// All entries in this recording in the order they were recorded.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// The events received from the X server in the order they were received.
+    pub fn events(&self) -> Vec<XEvent> {
+        self.entries
+            .iter()
+            .flat_map(|e| match e {
+                Entry::Event(event) => Some(event.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The requests made to the X server in the order they were made.
+    pub fn requests(&self) -> Vec<Request> {
+        self.entries
+            .iter()
+            .flat_map(|e| match e {
+                Entry::Request(req) => Some(req.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Build a [ScriptedConn] that will replay the events in this recording.
+    ///
+    /// Queries made against the returned connection are answered using the first value
+    /// that was recorded for them.
+    pub fn to_conn(&self) -> ScriptedConn {
+        let mut screens = Vec::new();
+        let mut existing = Vec::new();
+        let mut props = HashMap::new();
+        let mut geometry = HashMap::new();
+        let mut attributes = HashMap::new();
+
+        for e in self.entries.iter() {
+            match e {
+                Entry::Screens(rs) if screens.is_empty() => screens = rs.clone(),
+                Entry::ExistingClients(ids) if existing.is_empty() => existing = ids.clone(),
+                Entry::Prop {
+                    id,
+                    name,
+                    prop: Some(p),
+                } => {
+                    props
+                        .entry((*id, name.clone()))
+                        .or_insert_with(|| p.clone());
+                }
+                Entry::Geometry { id, r } => {
+                    geometry.entry(*id).or_insert(*r);
+                }
+                Entry::Attributes { id, attrs } => {
+                    attributes.entry(*id).or_insert_with(|| attrs.clone());
+                }
+                _ => (),
+            }
+        }
+
+        let mut conn = ScriptedConn::new(screens);
+        for id in existing {
+            let r = geometry.get(&id).copied().unwrap_or_default();
+            conn = conn.with_existing_client(id, r);
+        }
+        for (id, r) in geometry {
+            conn = conn.with_client(id, r);
+        }
+        for (id, attrs) in attributes {
+            conn = conn.with_attributes(id, attrs);
+        }
+        for ((id, name), p) in props {
+            conn = conn.with_prop(id, name, p);
+        }
+
+        conn.with_events(self.events())
+    }
+}
+
+/// Replay a recording written by a [RecordingConn] through a new [WindowManager] using
+/// the given config and bindings, returning the window manager once all recorded events
+/// have been processed.
+///
+/// The requests made during the replay are available from the [ScriptedConn] returned
+/// by [WindowManager::x] and can be compared against [Recording::requests].
+pub fn replay(
+    path: impl AsRef<Path>,
+    config: Config<ScriptedConn>,
+    key_bindings: KeyBindings<ScriptedConn>,
+    mouse_bindings: MouseBindings<ScriptedConn>,
+) -> Result<WindowManager<ScriptedConn>> {
+    let conn = Recording::load(path)?.to_conn();
+    let wm = WindowManager::new(config, key_bindings, mouse_bindings, conn)?;
+
+    wm.run_script()
+}
+
+/// An [XConn] wrapper that records all events, query responses and requests to a file.
+///
+/// See the [module level docs][self] for details.
+#[derive(Debug)]
+pub struct RecordingConn<X>
+where
+    X: XConn,
+{
+    inner: X,
+    out: RefCell<BufWriter<File>>,
+}
+
+impl<X> RecordingConn<X>
+where
+    X: XConn,
+{
+    /// Wrap an existing [XConn], writing a recording to the given path. Any existing
+    /// file at that path is truncated.
+    pub fn new(inner: X, path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            inner,
+            out: RefCell::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    /// Get a handle to the wrapped connection.
+    pub fn inner(&self) -> &X {
+        &self.inner
+    }
+
+    // Failing to record shouldn't take down the window manager so errors are only logged
+    fn record(&self, entry: Entry) {
+        let mut out = self.out.borrow_mut();
+        let res = serde_json::to_writer(&mut *out, &entry)
+            .map_err(|e| Error::Custom(e.to_string()))
+            .and_then(|_| Ok(writeln!(out)?));
+
+        if let Err(e) = res {
+            error!(%e, "unable to write recording entry");
+        }
+    }
+
+    fn record_request(&self, req: Request) {
+        self.record(Entry::Request(req));
+    }
+}
+
+impl<X> Backend for RecordingConn<X>
+where
+    X: XConn,
+{
+    fn screen_details(&self) -> Result<Vec<Rect>> {
+        let rs = self.inner.screen_details()?;
+        self.record(Entry::Screens(rs.clone()));
+
+        Ok(rs)
+    }
+
+    fn cursor_position(&self) -> Result<Point> {
+        self.inner.cursor_position()
+    }
+
+    fn output_details(&self) -> Result<Vec<OutputInfo>> {
+        let outputs = self.inner.output_details()?;
+        self.record(Entry::Screens(outputs.iter().map(|o| o.r).collect()));
+
+        Ok(outputs)
+    }
+
+    fn grab(&self, key_codes: &[KeyCode], mouse_states: &[MouseState]) -> Result<()> {
+        self.record_request(Request::Grab(key_codes.to_vec(), mouse_states.to_vec()));
+        self.inner.grab(key_codes, mouse_states)
+    }
+
+    fn keycodes(&self) -> Result<HashMap<String, u8>> {
+        self.inner.keycodes()
+    }
+
+    fn next_event(&self) -> Result<XEvent> {
+        let event = self.inner.next_event()?;
+        self.record(Entry::Event(event.clone()));
+
+        Ok(event)
+    }
+
+    fn flush(&self) {
+        if let Err(e) = self.out.borrow_mut().flush() {
+            error!(%e, "unable to flush recording");
+        }
+        self.inner.flush()
+    }
+
+    fn client_geometry(&self, client: Xid) -> Result<Rect> {
+        let r = self.inner.client_geometry(client)?;
+        self.record(Entry::Geometry { id: client, r });
+
+        Ok(r)
+    }
+
+    fn existing_clients(&self) -> Result<Vec<Xid>> {
+        let ids = self.inner.existing_clients()?;
+        self.record(Entry::ExistingClients(ids.clone()));
+
+        Ok(ids)
+    }
+
+    fn map(&self, client: Xid) -> Result<()> {
+        self.record_request(Request::Map(client));
+        self.inner.map(client)
+    }
+
+    fn unmap(&self, client: Xid) -> Result<()> {
+        self.record_request(Request::Unmap(client));
+        self.inner.unmap(client)
+    }
+
+    fn kill(&self, client: Xid) -> Result<()> {
+        self.record_request(Request::Kill(client));
+        self.inner.kill(client)
+    }
+
+    fn focus(&self, client: Xid) -> Result<()> {
+        self.record_request(Request::Focus(client));
+        self.inner.focus(client)
+    }
+
+    fn set_client_config(&self, client: Xid, data: &[ClientConfig]) -> Result<()> {
+        self.record_request(Request::SetClientConfig(client, data.to_vec()));
+        self.inner.set_client_config(client, data)
+    }
+
+    fn set_client_config_batch(&self, batch: ClientConfigBatch) -> Result<()> {
+        for (id, confs) in batch.iter() {
+            self.record_request(Request::SetClientConfig(id, confs.to_vec()));
+        }
+        self.inner.set_client_config_batch(batch)
+    }
+
+    fn warp_pointer(&self, id: Xid, x: i16, y: i16) -> Result<()> {
+        self.record_request(Request::WarpPointer(id, x, y));
+        self.inner.warp_pointer(id, x, y)
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        self.inner.grab_pointer()
+    }
+
+    fn set_cursor(&self, id: Xid, cursor: Cursor) -> Result<()> {
+        self.inner.set_cursor(id, cursor)
+    }
+
+    fn set_active_grab_cursor(&self, cursor: Cursor) -> Result<()> {
+        self.inner.set_active_grab_cursor(cursor)
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        self.inner.ungrab_pointer()
+    }
+
+    fn grab_keyboard(&self) -> Result<()> {
+        self.record_request(Request::GrabKeyboard);
+        self.inner.grab_keyboard()
+    }
+
+    fn ungrab_keyboard(&self) -> Result<()> {
+        self.record_request(Request::UngrabKeyboard);
+        self.inner.ungrab_keyboard()
+    }
+
+    fn next_event_before(&self, deadline: Instant) -> Result<Option<XEvent>> {
+        let event = self.inner.next_event_before(deadline)?;
+        if let Some(event) = &event {
+            self.record(Entry::Event(event.clone()));
+        }
+
+        Ok(event)
+    }
+
+    fn poll_for_event(&self) -> Result<Option<XEvent>> {
+        let event = self.inner.poll_for_event()?;
+        if let Some(event) = &event {
+            self.record(Entry::Event(event.clone()));
+        }
+
+        Ok(event)
+    }
+
+    fn next_event_or_wakeup(
+        &self,
+        deadline: Option<Instant>,
+        wakeup: Option<RawFd>,
+    ) -> Result<Option<XEvent>> {
+        let event = self.inner.next_event_or_wakeup(deadline, wakeup)?;
+        if let Some(event) = &event {
+            self.record(Entry::Event(event.clone()));
+        }
+
+        Ok(event)
+    }
+}
+
+impl<X> XConn for RecordingConn<X>
+where
+    X: XConn,
+{
+    fn root(&self) -> Xid {
+        self.inner.root()
+    }
+
+    fn roots(&self) -> Vec<Xid> {
+        self.inner.roots()
+    }
+
+    fn client_root(&self, client: Xid) -> Result<Xid> {
+        self.inner.client_root(client)
+    }
+
+    fn intern_atom(&self, atom: &str) -> Result<Xid> {
+        self.inner.intern_atom(atom)
+    }
+
+    fn atom_name(&self, xid: Xid) -> Result<String> {
+        self.inner.atom_name(xid)
+    }
+
+    fn get_prop(&self, client: Xid, prop_name: &str) -> Result<Option<Prop>> {
+        let prop = self.inner.get_prop(client, prop_name)?;
+        self.record(Entry::Prop {
+            id: client,
+            name: prop_name.to_string(),
+            prop: prop.clone(),
+        });
+
+        Ok(prop)
+    }
+
+    fn get_window_attributes(&self, client: Xid) -> Result<WindowAttributes> {
+        let attrs = self.inner.get_window_attributes(client)?;
+        self.record(Entry::Attributes {
+            id: client,
+            attrs: attrs.clone(),
+        });
+
+        Ok(attrs)
+    }
+
+    fn set_wm_state(&self, client: Xid, wm_state: WmState) -> Result<()> {
+        self.record_request(Request::SetWmState(client, wm_state.clone()));
+        self.inner.set_wm_state(client, wm_state)
+    }
+
+    fn set_prop(&self, client: Xid, name: &str, val: Prop) -> Result<()> {
+        self.record_request(Request::SetProp(client, name.to_string(), val.clone()));
+        self.inner.set_prop(client, name, val)
+    }
+
+    fn set_client_attributes(&self, client: Xid, attrs: &[ClientAttr]) -> Result<()> {
+        self.record_request(Request::SetClientAttributes(client, attrs.to_vec()));
+        self.inner.set_client_attributes(client, attrs)
+    }
+
+    fn set_rounded_corners(
+        &self,
+        client: Xid,
+        r: Rect,
+        border: u32,
+        radius: Option<u32>,
+    ) -> Result<()> {
+        self.inner.set_rounded_corners(client, r, border, radius)
+    }
+
+    fn send_client_message(&self, msg: ClientMessage) -> Result<()> {
+        self.record_request(Request::SendClientMessage(msg.clone()));
+        self.inner.send_client_message(msg)
+    }
+
+    fn acquire_wm_selection(&self, replace: bool) -> Result<()> {
+        self.inner.acquire_wm_selection(replace)
+    }
+
+    fn acquire_selection(&self, selection: &str, owner: Xid) -> Result<bool> {
+        self.inner.acquire_selection(selection, owner)
+    }
+
+    fn create_selection_window(&self, root: Xid) -> Result<Option<Xid>> {
+        self.inner.create_selection_window(root)
+    }
+
+    fn create_frame(&self, root: Xid, r: Rect) -> Result<Option<Xid>> {
+        self.inner.create_frame(root, r)
+    }
+
+    fn reparent(&self, client: Xid, parent: Xid, p: Point) -> Result<()> {
+        self.inner.reparent(client, parent, p)
+    }
+
+    fn draw_titlebar(&self, frame: Xid, titlebar: &Titlebar) -> Result<()> {
+        self.inner.draw_titlebar(frame, titlebar)
+    }
+
+    fn create_overlay(&self, root: Xid, r: Rect) -> Result<Option<Xid>> {
+        self.inner.create_overlay(root, r)
+    }
+
+    fn draw_binding_hints(&self, id: Xid, hints: &BindingHints) -> Result<()> {
+        self.inner.draw_binding_hints(id, hints)
+    }
+
+    fn destroy_window(&self, id: Xid) -> Result<()> {
+        self.inner.destroy_window(id)
+    }
+
+    fn set_root_background(&self, root: Xid, bg: &RootBackground) -> Result<()> {
+        self.inner.set_root_background(root, bg)
+    }
+
+    fn idle_time(&self) -> Result<Option<Duration>> {
+        self.inner.idle_time()
+    }
+
+    fn keyboard_group(&self) -> Result<Option<u8>> {
+        self.inner.keyboard_group()
+    }
+
+    fn set_keyboard_group(&self, group: u8) -> Result<()> {
+        self.inner.set_keyboard_group(group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::fixtures::ScriptedWm;
+    use std::{collections::HashMap, env::temp_dir, process::id};
+
+    #[test]
+    fn recordings_replay_to_the_same_state() {
+        let path = temp_dir().join(format!("penrose-recording-test-{}.jsonl", id()));
+
+        let recording_path = path.clone();
+        let mut wm =
+            ScriptedWm::wrapped(move |conn| RecordingConn::new(conn, recording_path).unwrap())
+                .with_conn(|conn| conn.with_client(Xid(1), Rect::new(10, 10, 200, 100)))
+                .with_clients(2)
+                .build();
+
+        // Drive the recording connection by hand as run_script is specific to ScriptedConn
+        wm.start().unwrap();
+        while let Ok(event) = wm.x().next_event() {
+            wm.process_event(event).unwrap();
+        }
+        wm.x().flush();
+        let cs = &wm.state.client_set;
+        let original: Vec<_> = cs
+            .workspaces()
+            .map(|w| w.clients().copied().collect::<Vec<_>>())
+            .collect();
+        let original_focus = cs.current_client().copied();
+
+        let recording = Recording::load(&path).unwrap();
+        assert_eq!(
+            recording.events(),
+            vec![XEvent::MapRequest(Xid(1)), XEvent::MapRequest(Xid(2))]
+        );
+
+        let replayed = replay(&path, Config::default(), HashMap::new(), HashMap::new()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let cs = &replayed.state.client_set;
+        let clients: Vec<_> = cs
+            .workspaces()
+            .map(|w| w.clients().copied().collect::<Vec<_>>())
+            .collect();
+        assert_eq!(clients, original);
+        assert_eq!(cs.current_client().copied(), original_focus);
+        assert_eq!(replayed.x().requests(), recording.requests());
+    }
+}
@@ -0,0 +1,336 @@
+//! A wrapper around an [XConn] impl for timing round trips to the X server
+use crate::{
+    core::{
+        bindings::{KeyCode, MouseState},
+        hints::BindingHints,
+        metrics::Metrics,
+        titlebar::Titlebar,
+    },
+    pure::{
+        geometry::{Point, Rect},
+        OutputInfo,
+    },
+    x::{
+        event::ClientMessage,
+        property::{Prop, WindowAttributes, WmState},
+        Backend, ClientAttr, ClientConfig, ClientConfigBatch, Cursor, RootBackground, XConn,
+        XEvent,
+    },
+    Result, Xid,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    os::unix::io::RawFd,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// An [XConn] wrapper that records how long each query to the X server takes.
+///
+/// Timings are recorded as `x/<method name>` in a shared [Metrics] which can be added to the
+/// window manager as a [State][crate::core::State] extension using
+/// [WindowManager::add_shared_extension][crate::core::WindowManager::add_shared_extension].
+/// Requests that do not wait for a reply from the X server are passed through untimed.
+///
+/// See the [metrics][crate::core::metrics] module docs for details.
+#[derive(Debug)]
+pub struct TimedConn<X>
+where
+    X: XConn,
+{
+    inner: X,
+    metrics: Arc<RefCell<Metrics>>,
+}
+
+impl<X> TimedConn<X>
+where
+    X: XConn,
+{
+    /// Wrap an existing [XConn], recording timings into a new [Metrics].
+    pub fn new(inner: X) -> Self {
+        Self::new_with_metrics(inner, Default::default())
+    }
+
+    /// Wrap an existing [XConn], recording timings into the given [Metrics].
+    pub fn new_with_metrics(inner: X, metrics: Arc<RefCell<Metrics>>) -> Self {
+        Self { inner, metrics }
+    }
+
+    /// Get a handle to the wrapped connection.
+    pub fn inner(&self) -> &X {
+        &self.inner
+    }
+
+    /// Get a handle to the [Metrics] being recorded by this connection.
+    pub fn metrics(&self) -> Arc<RefCell<Metrics>> {
+        Arc::clone(&self.metrics)
+    }
+
+    // Timings are skipped rather than panicking if the metrics are currently borrowed
+    fn timed<T>(&self, name: &str, f: impl FnOnce(&X) -> T) -> T {
+        let start = Instant::now();
+        let res = f(&self.inner);
+        if let Ok(mut m) = self.metrics.try_borrow_mut() {
+            m.record(format!("x/{name}"), start.elapsed());
+        }
+
+        res
+    }
+}
+
+impl<X> Backend for TimedConn<X>
+where
+    X: XConn,
+{
+    fn screen_details(&self) -> Result<Vec<Rect>> {
+        self.timed("screen_details", |x| x.screen_details())
+    }
+
+    fn cursor_position(&self) -> Result<Point> {
+        self.timed("cursor_position", |x| x.cursor_position())
+    }
+
+    fn output_details(&self) -> Result<Vec<OutputInfo>> {
+        self.timed("output_details", |x| x.output_details())
+    }
+
+    fn grab(&self, key_codes: &[KeyCode], mouse_states: &[MouseState]) -> Result<()> {
+        self.inner.grab(key_codes, mouse_states)
+    }
+
+    fn keycodes(&self) -> Result<HashMap<String, u8>> {
+        self.timed("keycodes", |x| x.keycodes())
+    }
+
+    fn next_event(&self) -> Result<XEvent> {
+        self.inner.next_event()
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn client_geometry(&self, client: Xid) -> Result<Rect> {
+        self.timed("client_geometry", |x| x.client_geometry(client))
+    }
+
+    fn existing_clients(&self) -> Result<Vec<Xid>> {
+        self.timed("existing_clients", |x| x.existing_clients())
+    }
+
+    fn map(&self, client: Xid) -> Result<()> {
+        self.inner.map(client)
+    }
+
+    fn unmap(&self, client: Xid) -> Result<()> {
+        self.inner.unmap(client)
+    }
+
+    fn kill(&self, client: Xid) -> Result<()> {
+        self.inner.kill(client)
+    }
+
+    fn focus(&self, client: Xid) -> Result<()> {
+        self.inner.focus(client)
+    }
+
+    fn set_client_config(&self, client: Xid, data: &[ClientConfig]) -> Result<()> {
+        self.inner.set_client_config(client, data)
+    }
+
+    fn set_client_config_batch(&self, batch: ClientConfigBatch) -> Result<()> {
+        self.inner.set_client_config_batch(batch)
+    }
+
+    fn warp_pointer(&self, id: Xid, x: i16, y: i16) -> Result<()> {
+        self.inner.warp_pointer(id, x, y)
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        self.timed("grab_pointer", |x| x.grab_pointer())
+    }
+
+    fn set_cursor(&self, id: Xid, cursor: Cursor) -> Result<()> {
+        self.inner.set_cursor(id, cursor)
+    }
+
+    fn set_active_grab_cursor(&self, cursor: Cursor) -> Result<()> {
+        self.inner.set_active_grab_cursor(cursor)
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        self.inner.ungrab_pointer()
+    }
+
+    fn grab_keyboard(&self) -> Result<()> {
+        self.timed("grab_keyboard", |x| x.grab_keyboard())
+    }
+
+    fn ungrab_keyboard(&self) -> Result<()> {
+        self.inner.ungrab_keyboard()
+    }
+
+    fn next_event_before(&self, deadline: Instant) -> Result<Option<XEvent>> {
+        self.inner.next_event_before(deadline)
+    }
+
+    fn poll_for_event(&self) -> Result<Option<XEvent>> {
+        self.inner.poll_for_event()
+    }
+
+    fn next_event_or_wakeup(
+        &self,
+        deadline: Option<Instant>,
+        wakeup: Option<RawFd>,
+    ) -> Result<Option<XEvent>> {
+        self.inner.next_event_or_wakeup(deadline, wakeup)
+    }
+}
+
+impl<X> XConn for TimedConn<X>
+where
+    X: XConn,
+{
+    fn root(&self) -> Xid {
+        self.inner.root()
+    }
+
+    fn roots(&self) -> Vec<Xid> {
+        self.inner.roots()
+    }
+
+    fn client_root(&self, client: Xid) -> Result<Xid> {
+        self.timed("client_root", |x| x.client_root(client))
+    }
+
+    fn intern_atom(&self, atom: &str) -> Result<Xid> {
+        self.timed("intern_atom", |x| x.intern_atom(atom))
+    }
+
+    fn atom_name(&self, xid: Xid) -> Result<String> {
+        self.timed("atom_name", |x| x.atom_name(xid))
+    }
+
+    fn get_prop(&self, client: Xid, prop_name: &str) -> Result<Option<Prop>> {
+        self.timed("get_prop", |x| x.get_prop(client, prop_name))
+    }
+
+    fn get_window_attributes(&self, client: Xid) -> Result<WindowAttributes> {
+        self.timed("get_window_attributes", |x| x.get_window_attributes(client))
+    }
+
+    fn set_wm_state(&self, client: Xid, wm_state: WmState) -> Result<()> {
+        self.inner.set_wm_state(client, wm_state)
+    }
+
+    fn set_prop(&self, client: Xid, name: &str, val: Prop) -> Result<()> {
+        self.inner.set_prop(client, name, val)
+    }
+
+    fn set_client_attributes(&self, client: Xid, attrs: &[ClientAttr]) -> Result<()> {
+        self.inner.set_client_attributes(client, attrs)
+    }
+
+    fn set_rounded_corners(
+        &self,
+        client: Xid,
+        r: Rect,
+        border: u32,
+        radius: Option<u32>,
+    ) -> Result<()> {
+        self.inner.set_rounded_corners(client, r, border, radius)
+    }
+
+    fn send_client_message(&self, msg: ClientMessage) -> Result<()> {
+        self.inner.send_client_message(msg)
+    }
+
+    fn acquire_wm_selection(&self, replace: bool) -> Result<()> {
+        self.timed("acquire_wm_selection", |x| x.acquire_wm_selection(replace))
+    }
+
+    fn acquire_selection(&self, selection: &str, owner: Xid) -> Result<bool> {
+        self.timed("acquire_selection", |x| {
+            x.acquire_selection(selection, owner)
+        })
+    }
+
+    fn create_selection_window(&self, root: Xid) -> Result<Option<Xid>> {
+        self.inner.create_selection_window(root)
+    }
+
+    fn create_frame(&self, root: Xid, r: Rect) -> Result<Option<Xid>> {
+        self.inner.create_frame(root, r)
+    }
+
+    fn reparent(&self, client: Xid, parent: Xid, p: Point) -> Result<()> {
+        self.inner.reparent(client, parent, p)
+    }
+
+    fn draw_titlebar(&self, frame: Xid, titlebar: &Titlebar) -> Result<()> {
+        self.inner.draw_titlebar(frame, titlebar)
+    }
+
+    fn create_overlay(&self, root: Xid, r: Rect) -> Result<Option<Xid>> {
+        self.inner.create_overlay(root, r)
+    }
+
+    fn draw_binding_hints(&self, id: Xid, hints: &BindingHints) -> Result<()> {
+        self.inner.draw_binding_hints(id, hints)
+    }
+
+    fn destroy_window(&self, id: Xid) -> Result<()> {
+        self.inner.destroy_window(id)
+    }
+
+    fn set_root_background(&self, root: Xid, bg: &RootBackground) -> Result<()> {
+        self.inner.set_root_background(root, bg)
+    }
+
+    fn idle_time(&self) -> Result<Option<Duration>> {
+        self.timed("idle_time", |x| x.idle_time())
+    }
+
+    fn keyboard_group(&self) -> Result<Option<u8>> {
+        self.timed("keyboard_group", |x| x.keyboard_group())
+    }
+
+    fn set_keyboard_group(&self, group: u8) -> Result<()> {
+        self.inner.set_keyboard_group(group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x::{fixtures::ScriptedWm, mock::ScriptedConn};
+
+    #[test]
+    fn events_refreshes_and_queries_are_timed() {
+        let mut wm = ScriptedWm::wrapped(TimedConn::new)
+            .with_conn(|conn| conn.with_client(Xid(1), Rect::new(10, 10, 200, 100)))
+            .with_clients(1)
+            .build();
+        let metrics = wm.x().metrics();
+        wm.add_shared_extension(Arc::clone(&metrics));
+
+        wm.start().unwrap();
+        while let Ok(event) = wm.x().next_event() {
+            wm.process_event(event).unwrap();
+        }
+
+        let m = metrics.borrow();
+        assert_eq!(m.histogram("event/MapRequest").unwrap().count(), 1);
+        assert!(m.histogram("refresh").unwrap().count() >= 1);
+        assert!(m.histogram("x/get_prop").unwrap().count() >= 1);
+        assert!(m.histogram("event/KeyPress").is_none());
+    }
+
+    #[test]
+    fn selection_windows_are_created_by_the_inner_conn() {
+        let conn = TimedConn::new(ScriptedConn::new(vec![Rect::new(0, 0, 1000, 800)]));
+
+        assert!(conn.create_selection_window(conn.root()).unwrap().is_some());
+    }
+}
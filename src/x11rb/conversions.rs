@@ -5,7 +5,7 @@ use crate::{
     x::{
         event::{
             ClientEventMask, ClientMessage, ClientMessageData, ConfigureEvent, ExposeEvent,
-            PointerChange, PropertyEvent,
+            MotionEvent, PointerChange, PropertyEvent, SelectionClearEvent,
         },
         XConn, XEvent,
     },
@@ -17,6 +17,7 @@ use tracing::warn;
 use x11rb::{
     connection::Connection,
     protocol::{
+        xkb::StatePart,
         xproto::{ClientMessageEvent, KeyButMask, ModMask},
         Event,
     },
@@ -28,9 +29,17 @@ pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Resu
 
         Event::RandrScreenChangeNotify(_) => Ok(Some(XEvent::ScreenChange)),
 
+        Event::XkbNewKeyboardNotify(_) | Event::XkbMapNotify(_) => Ok(Some(XEvent::KeymapChange)),
+
+        Event::XkbStateNotify(event)
+            if u16::from(event.changed) & u16::from(StatePart::GROUP_STATE) != 0 =>
+        {
+            Ok(Some(XEvent::KeyboardGroupChange(event.group.into())))
+        }
+
         Event::ButtonPress(event) => Ok(to_mouse_state(event.detail, event.state).map(|state| {
             XEvent::MouseEvent(MouseEvent::new(
-                Xid(event.event),
+                event_window(event.event, event.child),
                 event.root_x,
                 event.root_y,
                 event.event_x,
@@ -42,7 +51,7 @@ pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Resu
 
         Event::ButtonRelease(event) => Ok(to_mouse_state(event.detail, event.state).map(|state| {
             XEvent::MouseEvent(MouseEvent::new(
-                Xid(event.event),
+                event_window(event.event, event.child),
                 event.root_x,
                 event.root_y,
                 event.event_x,
@@ -52,18 +61,13 @@ pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Resu
             ))
         })),
 
-        // FIXME: The 5 is due to https://github.com/sminez/penrose/issues/113
-        Event::MotionNotify(event) => Ok(to_mouse_state(5, event.state).map(|state| {
-            XEvent::MouseEvent(MouseEvent::new(
-                Xid(event.event),
-                event.root_x,
-                event.root_y,
-                event.event_x,
-                event.event_y,
-                state,
-                MouseEventKind::Motion,
-            ))
-        })),
+        Event::MotionNotify(event) => Ok(Some(XEvent::MotionNotify(MotionEvent {
+            id: event_window(event.event, event.child),
+            rpt: Point::new(event.root_x as u32, event.root_y as u32),
+            wpt: Point::new(event.event_x as u32, event.event_y as u32),
+            buttons: held_buttons(event.state),
+            modifiers: held_modifiers(event.state),
+        }))),
 
         Event::KeyPress(event) => {
             let code = KeyCode {
@@ -76,6 +80,17 @@ pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Resu
             )))
         }
 
+        Event::KeyRelease(event) => {
+            let code = KeyCode {
+                mask: event.state.into(),
+                code: event.detail,
+            };
+            let numlock = ModMask::M2;
+            Ok(Some(XEvent::KeyRelease(
+                code.ignoring_modifier(numlock.into()),
+            )))
+        }
+
         Event::MapRequest(event) => Ok(Some(XEvent::MapRequest(Xid(event.window)))),
 
         Event::UnmapNotify(event) => Ok(Some(XEvent::UnmapNotify(Xid(event.window)))),
@@ -104,7 +119,7 @@ pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Resu
                 event.width as u32,
                 event.height as u32,
             ),
-            is_root: event.window == *conn.root(),
+            is_root: conn.is_root(event.window),
         }))),
 
         Event::ConfigureRequest(event) => Ok(Some(XEvent::ConfigureRequest(ConfigureEvent {
@@ -115,7 +130,7 @@ pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Resu
                 event.width as u32,
                 event.height as u32,
             ),
-            is_root: event.window == *conn.root(),
+            is_root: conn.is_root(event.window),
         }))),
 
         Event::Expose(event) => Ok(Some(XEvent::Expose(ExposeEvent {
@@ -134,9 +149,20 @@ pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Resu
         Event::PropertyNotify(event) => Ok(Some(XEvent::PropertyNotify(PropertyEvent {
             id: Xid(event.window),
             atom: conn.atom_name(Xid(event.atom))?,
-            is_root: event.window == *conn.root(),
+            is_root: conn.is_root(event.window),
         }))),
 
+        Event::SelectionClear(event) => {
+            let selection = conn.atom_name(Xid(event.selection))?;
+            let is_wm_selection = conn.is_wm_selection(&selection);
+
+            Ok(Some(XEvent::SelectionClear(SelectionClearEvent {
+                id: Xid(event.owner),
+                selection,
+                is_wm_selection,
+            })))
+        }
+
         Event::Error(err) => Err(Error::X11rbX11Error(err)),
 
         // NOTE: Ignoring other event types
@@ -144,24 +170,54 @@ pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Resu
     }
 }
 
-fn to_mouse_state(detail: u8, state: KeyButMask) -> Option<MouseState> {
-    fn is_held(key: &ModifierKey, mask: u16) -> bool {
-        mask & u16::from(*key) > 0
+// Pointer events from grabs on the root window report the window under the pointer as the
+// child of the root so we prefer that when it is set.
+fn event_window(event: u32, child: u32) -> Xid {
+    if child == x11rb::NONE {
+        Xid(event)
+    } else {
+        Xid(child)
     }
+}
+
+fn held_modifiers(state: KeyButMask) -> Vec<ModifierKey> {
+    let state = u16::from(state);
+
+    ModifierKey::iter()
+        .filter(|m| state & u16::from(*m) > 0)
+        .collect()
+}
+
+fn held_buttons(state: KeyButMask) -> Vec<MouseButton> {
+    [
+        (KeyButMask::BUTTON1, MouseButton::Left),
+        (KeyButMask::BUTTON2, MouseButton::Middle),
+        (KeyButMask::BUTTON3, MouseButton::Right),
+        (KeyButMask::BUTTON4, MouseButton::ScrollUp),
+        (KeyButMask::BUTTON5, MouseButton::ScrollDown),
+    ]
+    .into_iter()
+    .filter(|&(mask, _)| u16::from(state) & u16::from(mask) > 0)
+    .map(|(_, button)| button)
+    .collect()
+}
+
+fn to_mouse_state(detail: u8, state: KeyButMask) -> Option<MouseState> {
     let button = match detail {
         1 => MouseButton::Left,
         2 => MouseButton::Middle,
         3 => MouseButton::Right,
         4 => MouseButton::ScrollUp,
         5 => MouseButton::ScrollDown,
+        6 => MouseButton::ScrollLeft,
+        7 => MouseButton::ScrollRight,
         _ => {
             warn!(button = detail, "dropping unknown mouse button event");
             return None;
         }
     };
-    let state = u16::from(state);
-    let modifiers = ModifierKey::iter().filter(|m| is_held(m, state)).collect();
-    Some(MouseState { button, modifiers })
+    let modifiers = held_modifiers(state);
+    Some(MouseState::new(button, modifiers))
 }
 
 fn to_client_message<C: Connection>(conn: &Conn<C>, event: ClientMessageEvent) -> Result<XEvent> {
@@ -0,0 +1,170 @@
+//! Tracking the keyboard mapping using the XKB extension
+#[cfg(not(feature = "keysyms"))]
+use crate::core::bindings::keycodes_from_xmodmap;
+use crate::Result;
+#[cfg(feature = "keysyms")]
+use penrose_keysyms::XKeySym;
+use std::collections::HashMap;
+#[cfg(feature = "keysyms")]
+use strum::IntoEnumIterator;
+use x11rb::{
+    connection::Connection,
+    protocol::xkb::{self, ConnectionExt as _, EventType, MapPart, SelectEventsAux, StatePart},
+};
+
+// The XKB version we require: 1.0 is sufficient for everything we make use of
+const XKB_VER: (u16, u16) = (1, 0);
+
+/// Enable the XKB extension for this connection, returning whether or not it is available.
+///
+/// If XKB is available then we also select for the events needed to track changes to the
+/// keyboard mapping and the active keyboard group.
+pub(crate) fn init_xkb<C: Connection>(conn: &C) -> Result<bool> {
+    if conn
+        .extension_information(xkb::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        return Ok(false);
+    }
+
+    let reply = conn.xkb_use_extension(XKB_VER.0, XKB_VER.1)?.reply()?;
+    if !reply.supported {
+        return Ok(false);
+    }
+
+    // We only care about group changes when it comes to state notifications, otherwise we
+    // would get an event for every modifier key press
+    let details = SelectEventsAux {
+        bitcase2: Some(xkb::SelectEventsAuxBitcase2 {
+            affect_state: StatePart::GROUP_STATE,
+            state_details: StatePart::GROUP_STATE,
+        }),
+        ..Default::default()
+    };
+
+    conn.xkb_select_events(
+        xkb::ID::USE_CORE_KBD.into(),
+        EventType::from(0u16),
+        EventType::NEW_KEYBOARD_NOTIFY | EventType::MAP_NOTIFY,
+        MapPart::KEY_SYMS,
+        MapPart::KEY_SYMS,
+        &details,
+    )?
+    .check()?;
+
+    Ok(true)
+}
+
+/// Fetch the current key symbol mapping for the core keyboard and resolve it to a mapping
+/// of key names to key codes.
+#[cfg(feature = "keysyms")]
+pub(crate) fn keycodes<C: Connection>(conn: &C) -> Result<HashMap<String, u8>> {
+    let reply = conn
+        .xkb_get_map(
+            xkb::ID::USE_CORE_KBD.into(),
+            MapPart::KEY_SYMS,
+            MapPart::from(0u16),
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            xkb::VMod::from(0u16),
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )?
+        .reply()?;
+
+    let sym_maps = reply.map.syms_rtrn.unwrap_or_default();
+    let syms: Vec<Vec<Vec<u32>>> = sym_maps
+        .iter()
+        .map(|m| {
+            let n_groups = (m.group_info & 0x0f) as usize;
+            let width = m.width as usize;
+
+            (0..n_groups)
+                .filter_map(|g| m.syms.get(g * width..(g + 1) * width))
+                .map(|s| s.to_vec())
+                .collect()
+        })
+        .collect();
+
+    Ok(keycodes_from_syms(reply.first_key_sym, &syms))
+}
+
+/// Without the keysyms feature we are unable to name the keysyms returned by XKB so we
+/// fall back to using xmodmap.
+#[cfg(not(feature = "keysyms"))]
+pub(crate) fn keycodes<C: Connection>(_: &C) -> Result<HashMap<String, u8>> {
+    keycodes_from_xmodmap()
+}
+
+// Resolve per-key, per-group keysym lists (indexed from first_key_code) to key names.
+//
+// Where a keysym appears on multiple keys we prefer the lowest group followed by the lowest
+// key code so that bindings resolve against the primary layout.
+#[cfg(feature = "keysyms")]
+fn keycodes_from_syms(first_key_code: u8, syms: &[Vec<Vec<u32>>]) -> HashMap<String, u8> {
+    let max_groups = syms.iter().map(|groups| groups.len()).max().unwrap_or(0);
+    let mut sym_codes: HashMap<u32, u8> = HashMap::new();
+
+    for group in 0..max_groups {
+        for (i, groups) in syms.iter().enumerate() {
+            let code = match u8::try_from(first_key_code as usize + i) {
+                Ok(code) => code,
+                Err(_) => break,
+            };
+
+            for &sym in groups.get(group).into_iter().flatten() {
+                sym_codes.entry(sym).or_insert(code);
+            }
+        }
+    }
+
+    XKeySym::iter()
+        .filter_map(|k| {
+            sym_codes
+                .get(&k.as_u32())
+                .map(|&code| (k.as_ref().to_owned(), code))
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "keysyms"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keycodes_are_offset_from_the_first_key_code() {
+        let syms = vec![
+            vec![vec![0x0061, 0x0041]], // a A
+            vec![vec![0xff0d]],         // Return
+        ];
+
+        let codes = keycodes_from_syms(38, &syms);
+
+        assert_eq!(codes.get("a"), Some(&38));
+        assert_eq!(codes.get("A"), Some(&38));
+        assert_eq!(codes.get("Return"), Some(&39));
+    }
+
+    #[test]
+    fn lower_groups_are_preferred() {
+        let syms = vec![
+            vec![vec![0x0061], vec![0x0062]], // a | b
+            vec![vec![0x0062], vec![0x0061]], // b | a
+        ];
+
+        let codes = keycodes_from_syms(10, &syms);
+
+        assert_eq!(codes.get("a"), Some(&10));
+        assert_eq!(codes.get("b"), Some(&11));
+    }
+}
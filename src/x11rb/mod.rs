@@ -17,30 +17,54 @@
 //! [1]: https://www.x.org/releases/X11R7.6/doc/xproto/x11protocol.html
 //! [2]: https://gitlab.freedesktop.org/xorg/proto/randrproto/-/blob/master/randrproto.txt
 use crate::{
-    core::bindings::{KeyCode, MouseState},
-    pure::geometry::{Point, Rect},
+    core::{
+        bindings::{keycodes_from_xmodmap, KeyCode, MouseState},
+        hints::BindingHints,
+        titlebar::Titlebar,
+    },
+    pure::{
+        geometry::{Point, Rect},
+        OutputInfo,
+    },
     x::{
         self,
         atom::Atom,
         event::{ClientEventMask, ClientMessage, ClientMessageKind},
-        property::{Prop, WindowAttributes, WmHints, WmNormalHints, WmState},
-        ClientAttr, ClientConfig, WinType, XConn, XConnExt, XEvent,
+        property::{Prop, WindowAttributes, WmHints, WmIcon, WmNormalHints, WmState},
+        Backend, ClientAttr, ClientConfig, Cursor, RootBackground, WinType, XConn, XConnExt,
+        XEvent,
     },
     Error, Result, Xid,
 };
-use std::{collections::HashMap, str::FromStr};
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    os::unix::io::{AsRawFd, RawFd},
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
 use strum::IntoEnumIterator;
-use tracing::error;
+use tracing::{error, info};
 use x11rb::{
     connection::Connection,
     protocol::{
         randr::{self, ConnectionExt as _, NotifyMask},
+        screensaver::ConnectionExt as _,
+        shape::{self, ConnectionExt as _, SK, SO},
+        xkb::{self, ConnectionExt as _},
         xproto::{
-            AtomEnum, ChangeWindowAttributesAux, ClientMessageData, ClientMessageEvent,
-            ColormapAlloc, ConfigureWindowAux, ConnectionExt as _, CreateWindowAux, EventMask,
-            GrabMode, InputFocus, MapState, ModMask, PropMode, StackMode, WindowClass,
-            CLIENT_MESSAGE_EVENT,
+            Arc, AtomEnum, ButtonIndex, ChangeGCAux, ChangeWindowAttributesAux, ClientMessageData,
+            ClientMessageEvent, ColormapAlloc, ConfigureWindowAux, ConnectionExt as _, CreateGCAux,
+            CreateWindowAux, EventMask, FillStyle, Grab, GrabMode, GrabStatus, ImageFormat,
+            InputFocus, MapState, ModMask, MotionNotifyEvent, PropMode, Rectangle, Segment,
+            SetMode, StackMode, WindowClass, CLIENT_MESSAGE_EVENT,
         },
+        Event,
     },
     rust_connection::RustConnection,
     wrapper::ConnectionExt as _,
@@ -49,11 +73,41 @@ use x11rb::{
 };
 
 pub mod conversions;
+mod keymap;
+mod outputs;
+#[cfg(feature = "x11rb-async")]
+pub mod reactor;
 
 use conversions::convert_event;
 
 const RANDR_VER: (u32, u32) = (1, 2);
 
+// The prefix of the manager selection held for each screen of the display (WM_S0, WM_S1, ...)
+const WM_SELECTION_PREFIX: &str = "WM_S";
+
+// The property appended to (without changing it) in order to obtain a server timestamp
+const TIMESTAMP_PROP: &str = "_PENROSE_TIMESTAMP";
+
+// How long to wait for an existing window manager to exit when replacing it
+const WM_REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The core X font used for drawing titlebars: "fixed" is always available
+const TITLEBAR_FONT: &str = "fixed";
+
+// The core X font containing the standard cursor glyphs
+const CURSOR_FONT: &str = "cursor";
+
+// Glyph indices within the cursor font (see X11/cursorfont.h)
+fn cursor_glyph(cursor: Cursor) -> u16 {
+    match cursor {
+        Cursor::LeftPtr => 68,
+        Cursor::Move => 52,
+        Cursor::Resize => 14,
+        Cursor::Hand => 60,
+        Cursor::Watch => 150,
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct Atoms {
     atoms: HashMap<Atom, u32>,
@@ -93,7 +147,13 @@ impl Atoms {
 pub struct Conn<C: Connection> {
     conn: C,
     root: u32,
+    roots: Vec<u32>,
     atoms: Atoms,
+    xkb: bool,
+    pending_events: RefCell<VecDeque<Event>>,
+    cursors: RefCell<HashMap<Cursor, u32>>,
+    root_pixmaps: RefCell<HashMap<u32, u32>>,
+    fd: RawFd,
 }
 
 /// A pure rust based connection to the X server using a [RustConnection].
@@ -104,8 +164,9 @@ impl Conn<RustConnection> {
     /// [x11rb::rust_connection::RustConnection].
     pub fn new() -> Result<Self> {
         let (conn, _) = RustConnection::connect(None).map_err(Error::from)?;
+        let fd = conn.stream().as_raw_fd();
 
-        Self::new_for_connection(conn)
+        Self::new_for_connection(conn, fd)
     }
 }
 
@@ -117,8 +178,9 @@ impl Conn<XCBConnection> {
     /// [x11rb::xcb_ffi::XCBConnection].
     pub fn new() -> Result<Self> {
         let (conn, _) = XCBConnection::connect(None).map_err(Error::from)?;
+        let fd = conn.as_raw_fd();
 
-        Self::new_for_connection(conn)
+        Self::new_for_connection(conn, fd)
     }
 }
 
@@ -126,9 +188,12 @@ impl<C> Conn<C>
 where
     C: Connection,
 {
-    fn new_for_connection(conn: C) -> Result<Self> {
-        let root = conn.setup().roots[0].root;
+    fn new_for_connection(conn: C, fd: RawFd) -> Result<Self> {
+        let roots: Vec<u32> = conn.setup().roots.iter().map(|s| s.root).collect();
+        let root = roots[0];
         conn.prefetch_extension_information(randr::X11_EXTENSION_NAME)?;
+        conn.prefetch_extension_information(shape::X11_EXTENSION_NAME)?;
+        conn.prefetch_extension_information(xkb::X11_EXTENSION_NAME)?;
         let atoms = Atoms::new(&conn)?;
 
         let extension_info = conn.extension_information(randr::X11_EXTENSION_NAME)?;
@@ -148,20 +213,178 @@ where
         }
 
         let mask = NotifyMask::OUTPUT_CHANGE | NotifyMask::CRTC_CHANGE | NotifyMask::SCREEN_CHANGE;
-        conn.randr_select_input(root, mask)?;
+        for &r in roots.iter() {
+            conn.randr_select_input(r, mask)?;
+        }
 
-        let xconn = Self { conn, root, atoms };
+        let xkb = keymap::init_xkb(&conn)?;
+        if !xkb {
+            info!("XKB extension not available: falling back to xmodmap for keycodes");
+        }
+
+        let xconn = Self {
+            conn,
+            root,
+            roots,
+            atoms,
+            xkb,
+            pending_events: RefCell::new(VecDeque::new()),
+            cursors: RefCell::new(HashMap::new()),
+            root_pixmaps: RefCell::new(HashMap::new()),
+            fd,
+        };
 
-        xconn.set_client_attributes(Xid(root), &[ClientAttr::RootEventMask])?;
+        for &r in xconn.roots.iter() {
+            xconn.set_client_attributes(Xid(r), &[ClientAttr::RootEventMask])?;
+        }
 
         Ok(xconn)
     }
 
+    // Create (or fetch the previously created) X cursor for the given cursor kind
+    fn cursor(&self, cursor: Cursor) -> Result<u32> {
+        if let Some(&id) = self.cursors.borrow().get(&cursor) {
+            return Ok(id);
+        }
+
+        let font = self.conn.generate_id()?;
+        self.conn.open_font(font, CURSOR_FONT.as_bytes())?;
+
+        let id = self.conn.generate_id()?;
+        let glyph = cursor_glyph(cursor);
+        self.conn.create_glyph_cursor(
+            id,
+            font,
+            font,
+            glyph,
+            glyph + 1, // the mask for each glyph immediately follows it in the font
+            0,
+            0,
+            0,
+            u16::MAX,
+            u16::MAX,
+            u16::MAX,
+        )?;
+        self.conn.close_font(font)?;
+        self.cursors.borrow_mut().insert(cursor, id);
+
+        Ok(id)
+    }
+
+    // Compress motion and convert a raw x11rb event, returning None if the event is not
+    // one that penrose is interested in.
+    fn process_raw_event(&self, event: Event) -> Result<Option<XEvent>> {
+        let event = match event {
+            Event::MotionNotify(e) => Event::MotionNotify(self.compress_motion(e)?),
+            event => event,
+        };
+
+        convert_event(self, event)
+    }
+
+    /// Return the next event from the X server if one is already available, without
+    /// blocking to wait for one.
+    pub fn poll_for_event(&self) -> Result<Option<XEvent>> {
+        loop {
+            let pending = self.pending_events.borrow_mut().pop_front();
+            let event = match pending {
+                Some(event) => event,
+                None => match self.conn.poll_for_event()? {
+                    Some(event) => event,
+                    None => return Ok(None),
+                },
+            };
+
+            if let Some(event) = self.process_raw_event(event)? {
+                return Ok(Some(event));
+            }
+        }
+    }
+
+    // Whether or not the given window is the root window of one of the managed X screens
+    pub(crate) fn is_root(&self, id: u32) -> bool {
+        self.roots.contains(&id)
+    }
+
+    // Whether or not the given selection is the manager selection for one of the managed X screens
+    pub(crate) fn is_wm_selection(&self, selection: &str) -> bool {
+        match selection.strip_prefix(WM_SELECTION_PREFIX) {
+            Some(n) => n.parse().is_ok_and(|n: usize| n < self.roots.len()),
+            None => false,
+        }
+    }
+
     /// Get a handle to the underlying connection.
     pub fn connection(&self) -> &C {
         &self.conn
     }
 
+    // Drop all but the most recent of any pending motion events for the same window.
+    // The first non-motion event we encounter is held back to be returned next.
+    fn compress_motion(&self, mut event: MotionNotifyEvent) -> Result<MotionNotifyEvent> {
+        // Events already held back arrived before anything still waiting on the connection
+        if !self.pending_events.borrow().is_empty() {
+            return Ok(event);
+        }
+
+        while let Some(next) = self.conn.poll_for_event()? {
+            match next {
+                Event::MotionNotify(e) if e.event == event.event => event = e,
+                other => {
+                    self.pending_events.borrow_mut().push_back(other);
+                    break;
+                }
+            }
+        }
+
+        Ok(event)
+    }
+
+    // ICCCM 2.1: obtain a real server timestamp by appending zero bytes to a property on a
+    // window that we own and waiting for the resulting PropertyNotify event. Any other events
+    // received while waiting are held back to be returned as normal.
+    fn server_time(&self, id: u32) -> Result<u32> {
+        let prop = *self.intern_atom(TIMESTAMP_PROP)?;
+        let mask = self
+            .conn
+            .get_window_attributes(id)?
+            .reply()?
+            .your_event_mask;
+        let aux = ChangeWindowAttributesAux::new().event_mask(mask | EventMask::PROPERTY_CHANGE);
+        self.conn.change_window_attributes(id, &aux)?;
+        self.conn
+            .change_property8(PropMode::APPEND, id, prop, AtomEnum::STRING, &[])?;
+        self.conn.flush()?;
+
+        let time = loop {
+            match self.conn.wait_for_event()? {
+                Event::PropertyNotify(e) if e.window == id && e.atom == prop => break e.time,
+                other => self.pending_events.borrow_mut().push_back(other),
+            }
+        };
+
+        let aux = ChangeWindowAttributesAux::new().event_mask(mask);
+        self.conn.change_window_attributes(id, &aux)?;
+
+        Ok(time)
+    }
+
+    // Block until the given window is destroyed or we time out, queueing any other events so
+    // that they are returned by the next call to next_event.
+    fn wait_for_destroy(&self, id: u32) -> Result<()> {
+        let deadline = Instant::now() + WM_REPLACE_TIMEOUT;
+
+        while Instant::now() < deadline {
+            match self.conn.poll_for_event()? {
+                Some(Event::DestroyNotify(e)) if e.window == id => return Ok(()),
+                Some(other) => self.pending_events.borrow_mut().push_back(other),
+                None => thread::sleep(Duration::from_millis(50)),
+            }
+        }
+
+        Err(Error::WmReplaceTimeout)
+    }
+
     /// Create and map a new window to the screen with the specified [WinType].
     pub fn create_window(&self, ty: WinType, r: Rect, managed: bool) -> Result<Xid> {
         let (ty, mut win_aux, class) = match ty {
@@ -225,43 +448,61 @@ where
     }
 }
 
-impl<C> XConn for Conn<C>
+impl<C> Backend for Conn<C>
 where
     C: Connection,
 {
-    fn root(&self) -> Xid {
-        self.root.into()
-    }
-
     fn screen_details(&self) -> Result<Vec<Rect>> {
-        let resources = self.conn.randr_get_screen_resources(self.root)?.reply()?;
+        let mut rects = Vec::new();
+
+        for &root in self.roots.iter() {
+            let resources = self.conn.randr_get_screen_resources(root)?.reply()?;
+
+            // Send queries for all CRTCs
+            let crtcs = resources
+                .crtcs
+                .iter()
+                .map(|c| {
+                    self.conn
+                        .randr_get_crtc_info(*c, 0)
+                        .map_err(|err| err.into())
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            rects.extend(
+                crtcs
+                    .into_iter()
+                    .flat_map(|cookie| cookie.reply().ok())
+                    .filter(|reply| reply.width > 0)
+                    .map(|reply| {
+                        Rect::new(
+                            reply.x as u32,
+                            reply.y as u32,
+                            reply.width as u32,
+                            reply.height as u32,
+                        )
+                    }),
+            );
+        }
 
-        // Send queries for all CRTCs
-        let crtcs = resources
-            .crtcs
-            .iter()
-            .map(|c| {
-                self.conn
-                    .randr_get_crtc_info(*c, 0)
-                    .map_err(|err| err.into())
-            })
-            .collect::<Result<Vec<_>>>()?;
+        Ok(rects)
+    }
 
-        let rects = crtcs
-            .into_iter()
-            .flat_map(|cookie| cookie.reply().ok())
-            .filter(|reply| reply.width > 0)
-            .map(|reply| {
-                Rect::new(
-                    reply.x as u32,
-                    reply.y as u32,
-                    reply.width as u32,
-                    reply.height as u32,
-                )
-            })
-            .collect();
+    fn output_details(&self) -> Result<Vec<OutputInfo>> {
+        let mut outputs = Vec::new();
+        for (x_screen, &root) in self.roots.iter().enumerate() {
+            outputs.extend(outputs::output_details(&self.conn, root, x_screen)?);
+        }
 
-        Ok(rects)
+        Ok(outputs)
+    }
+
+    fn keycodes(&self) -> Result<HashMap<String, u8>> {
+        if self.xkb {
+            keymap::keycodes(&self.conn)
+        } else {
+            keycodes_from_xmodmap()
+        }
     }
 
     fn cursor_position(&self) -> Result<Point> {
@@ -278,33 +519,41 @@ where
         let mode = GrabMode::ASYNC;
         let mask = EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::BUTTON_MOTION;
 
-        for m in modifiers.iter() {
-            for k in key_codes.iter() {
-                self.conn.grab_key(
-                    false,               // don't pass grabbed events through to the client
-                    self.root,           // the window to grab: in this case the root window
-                    (k.mask | m).into(), // modifiers to grab
-                    k.code,              // keycode to grab
-                    mode,                // don't lock pointer input while grabbing
-                    mode,                // don't lock keyboard input while grabbing
-                )?;
+        // Release any existing grabs so that re-grabbing after a keymap change does not leave
+        // stale key codes bound
+        for &root in self.roots.iter() {
+            self.conn.ungrab_key(Grab::ANY, root, ModMask::ANY)?;
+            self.conn
+                .ungrab_button(ButtonIndex::ANY, root, ModMask::ANY)?;
+
+            for m in modifiers.iter() {
+                for k in key_codes.iter() {
+                    self.conn.grab_key(
+                        false,               // don't pass grabbed events through to the client
+                        root,                // the window to grab: in this case the root window
+                        (k.mask | m).into(), // modifiers to grab
+                        k.code,              // keycode to grab
+                        mode,                // don't lock pointer input while grabbing
+                        mode,                // don't lock keyboard input while grabbing
+                    )?;
+                }
             }
-        }
 
-        for m in modifiers.iter() {
-            for state in mouse_states.iter() {
-                let button = state.button().into();
-                self.conn.grab_button(
-                    false,                     // don't pass grabbed events through to the client
-                    self.root,                 // the window to grab: in this case the root window
-                    mask,                      // which events are reported to the client
-                    mode,                      // don't lock pointer input while grabbing
-                    mode,                      // don't lock keyboard input while grabbing
-                    x11rb::NONE,               // don't confine the cursor to a specific window
-                    x11rb::NONE,               // don't change the cursor type
-                    button,                    // the button to grab
-                    (state.mask() | m).into(), // modifiers to grab
-                )?;
+            for m in modifiers.iter() {
+                for state in mouse_states.iter() {
+                    let button = state.button().into();
+                    self.conn.grab_button(
+                        false,                     // don't pass grabbed events through to the client
+                        root,        // the window to grab: in this case the root window
+                        mask,        // which events are reported to the client
+                        mode,        // don't lock pointer input while grabbing
+                        mode,        // don't lock keyboard input while grabbing
+                        x11rb::NONE, // don't confine the cursor to a specific window
+                        x11rb::NONE, // don't change the cursor type
+                        button,      // the button to grab
+                        (state.mask() | m).into(), // modifiers to grab
+                    )?;
+                }
             }
         }
 
@@ -315,8 +564,13 @@ where
 
     fn next_event(&self) -> Result<XEvent> {
         loop {
-            let event = self.conn.wait_for_event()?;
-            if let Some(event) = convert_event(self, event)? {
+            let pending = self.pending_events.borrow_mut().pop_front();
+            let event = match pending {
+                Some(event) => event,
+                None => self.conn.wait_for_event()?,
+            };
+
+            if let Some(event) = self.process_raw_event(event)? {
                 return Ok(event);
             }
         }
@@ -326,28 +580,6 @@ where
         self.conn.flush().unwrap_or(());
     }
 
-    fn intern_atom(&self, atom: &str) -> Result<Xid> {
-        let id = match Atom::from_str(atom) {
-            Ok(known) => self.atoms.known_atom(known),
-            Err(_) => self.conn.intern_atom(false, atom.as_bytes())?.reply()?.atom,
-        };
-
-        Ok(Xid(id))
-    }
-
-    fn atom_name(&self, xid: Xid) -> Result<String> {
-        // Is the atom already known?
-        if let Some(atom) = self.atoms.atom_name(*xid) {
-            return Ok(atom.as_ref().to_string());
-        }
-
-        // Nope, ask the X11 server
-        let reply = self.conn.get_atom_name(*xid)?.reply()?;
-        let name = String::from_utf8(reply.name).map_err(Error::from)?;
-
-        Ok(name)
-    }
-
     fn client_geometry(&self, id: Xid) -> Result<Rect> {
         let res = self.conn.get_geometry(*id)?.reply()?;
 
@@ -360,8 +592,11 @@ where
     }
 
     fn existing_clients(&self) -> Result<Vec<Xid>> {
-        let raw_ids = self.conn.query_tree(self.root)?.reply()?.children;
-        let ids = raw_ids.into_iter().map(Xid).collect();
+        let mut ids = Vec::new();
+        for &root in self.roots.iter() {
+            let raw_ids = self.conn.query_tree(root)?.reply()?.children;
+            ids.extend(raw_ids.into_iter().map(Xid));
+        }
 
         Ok(ids)
     }
@@ -401,13 +636,210 @@ where
         Ok(())
     }
 
+    fn set_client_config(&self, id: Xid, data: &[ClientConfig]) -> Result<()> {
+        let mut aux = ConfigureWindowAux::new();
+        for conf in data.iter() {
+            match conf {
+                ClientConfig::BorderPx(px) => aux = aux.border_width(*px),
+                ClientConfig::Position(r) => {
+                    aux = aux.x(r.x as i32).y(r.y as i32).width(r.w).height(r.h);
+                }
+                ClientConfig::StackBelow(s) => aux = aux.sibling(s.0).stack_mode(StackMode::BELOW),
+                ClientConfig::StackAbove(s) => aux = aux.sibling(s.0).stack_mode(StackMode::ABOVE),
+                ClientConfig::StackBottom => aux = aux.stack_mode(StackMode::BELOW),
+                ClientConfig::StackTop => aux = aux.stack_mode(StackMode::ABOVE),
+            }
+        }
+        self.conn.configure_window(*id, &aux)?;
+
+        Ok(())
+    }
+
+    fn warp_pointer(&self, id: Xid, x: i16, y: i16) -> Result<()> {
+        self.conn.warp_pointer(x11rb::NONE, *id, 0, 0, 0, 0, x, y)?;
+
+        Ok(())
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        let mask = EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION;
+        let reply = self
+            .conn
+            .grab_pointer(
+                false,           // don't pass grabbed events through to the client
+                self.root,       // the window to grab: in this case the root window
+                mask,            // which events are reported to the client
+                GrabMode::ASYNC, // don't lock pointer input while grabbing
+                GrabMode::ASYNC, // don't lock keyboard input while grabbing
+                x11rb::NONE,     // don't confine the cursor to a specific window
+                x11rb::NONE,     // don't change the cursor type
+                CURRENT_TIME,
+            )?
+            .reply()?;
+
+        if reply.status != GrabStatus::SUCCESS {
+            return Err(Error::PointerGrabFailed(format!("{:?}", reply.status)));
+        }
+
+        Ok(())
+    }
+
+    fn set_cursor(&self, id: Xid, cursor: Cursor) -> Result<()> {
+        let aux = ChangeWindowAttributesAux::new().cursor(self.cursor(cursor)?);
+        self.conn.change_window_attributes(*id, &aux)?;
+
+        Ok(())
+    }
+
+    fn set_active_grab_cursor(&self, cursor: Cursor) -> Result<()> {
+        // This needs to match the event mask used for the passive button grabs in `grab`
+        let mask = EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::BUTTON_MOTION;
+        self.conn
+            .change_active_pointer_grab(self.cursor(cursor)?, CURRENT_TIME, mask)?;
+
+        Ok(())
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        self.conn.ungrab_pointer(CURRENT_TIME)?;
+
+        Ok(())
+    }
+
+    fn grab_keyboard(&self) -> Result<()> {
+        let reply = self
+            .conn
+            .grab_keyboard(
+                false,     // don't pass grabbed events through to the client
+                self.root, // the window to grab: in this case the root window
+                CURRENT_TIME,
+                GrabMode::ASYNC, // don't lock pointer input while grabbing
+                GrabMode::ASYNC, // don't lock keyboard input while grabbing
+            )?
+            .reply()?;
+
+        if reply.status != GrabStatus::SUCCESS {
+            return Err(Error::KeyboardGrabFailed(format!("{:?}", reply.status)));
+        }
+
+        Ok(())
+    }
+
+    fn ungrab_keyboard(&self) -> Result<()> {
+        self.conn.ungrab_keyboard(CURRENT_TIME)?;
+
+        Ok(())
+    }
+
+    fn poll_for_event(&self) -> Result<Option<XEvent>> {
+        Conn::poll_for_event(self)
+    }
+
+    fn next_event_before(&self, deadline: Instant) -> Result<Option<XEvent>> {
+        self.next_event_or_wakeup(Some(deadline), None)
+    }
+
+    fn next_event_or_wakeup(
+        &self,
+        deadline: Option<Instant>,
+        wakeup: Option<RawFd>,
+    ) -> Result<Option<XEvent>> {
+        loop {
+            if let Some(event) = self.poll_for_event()? {
+                return Ok(Some(event));
+            }
+
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if deadline <= now {
+                        return Ok(None);
+                    }
+
+                    deadline
+                        .duration_since(now)
+                        .as_millis()
+                        .clamp(1, i32::MAX as u128) as i32
+                }
+                None => -1,
+            };
+
+            // Make sure that any pending requests have been sent before we block
+            self.flush();
+
+            let mut fds: Vec<PollFd> = std::iter::once(self.fd)
+                .chain(wakeup)
+                .map(|fd| PollFd::new(fd, PollFlags::POLLIN))
+                .collect();
+
+            match poll(&mut fds, timeout) {
+                Ok(_) | Err(Errno::EINTR) => (),
+                Err(e) => return Err(std::io::Error::from(e).into()),
+            }
+
+            let readable = PollFlags::POLLIN | PollFlags::POLLHUP | PollFlags::POLLERR;
+            if wakeup.is_some() && fds[1].revents().is_some_and(|r| r.intersects(readable)) {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+impl<C> XConn for Conn<C>
+where
+    C: Connection,
+{
+    fn root(&self) -> Xid {
+        self.root.into()
+    }
+
+    fn roots(&self) -> Vec<Xid> {
+        self.roots.iter().copied().map(Xid).collect()
+    }
+
+    fn client_root(&self, client: Xid) -> Result<Xid> {
+        Ok(Xid(self.conn.query_tree(*client)?.reply()?.root))
+    }
+
+    fn intern_atom(&self, atom: &str) -> Result<Xid> {
+        let id = match Atom::from_str(atom) {
+            Ok(known) => self.atoms.known_atom(known),
+            Err(_) => self.conn.intern_atom(false, atom.as_bytes())?.reply()?.atom,
+        };
+
+        Ok(Xid(id))
+    }
+
+    fn atom_name(&self, xid: Xid) -> Result<String> {
+        // Is the atom already known?
+        if let Some(atom) = self.atoms.atom_name(*xid) {
+            return Ok(atom.as_ref().to_string());
+        }
+
+        // Nope, ask the X11 server
+        let reply = self.conn.get_atom_name(*xid)?.reply()?;
+        let name = String::from_utf8(reply.name).map_err(Error::from)?;
+
+        Ok(name)
+    }
+
     fn get_prop(&self, id: Xid, prop_name: &str) -> Result<Option<Prop>> {
         let atom = *self.intern_atom(prop_name)?;
-        let r = self
+        let mut r = self
             .conn
             .get_property(false, *id, atom, AtomEnum::ANY, 0, 1024)?
             .reply()?;
 
+        // Large properties such as _NET_WM_ICON will not fit in our initial request so
+        // we need to request the remaining data if there is any.
+        if r.bytes_after > 0 {
+            let len = 1024 + r.bytes_after.div_ceil(4);
+            r = self
+                .conn
+                .get_property(false, *id, atom, AtomEnum::ANY, 0, len)?
+                .reply()?;
+        }
+
         let prop_type = match r.type_ {
             0 => return Ok(None), // Null response
             id => self.atom_name(Xid(id))?,
@@ -425,6 +857,18 @@ where
                     .collect::<Result<Vec<String>>>()?,
             ),
 
+            "CARDINAL" if prop_name == Atom::NetWmIcon.as_ref() => {
+                Prop::Icon(WmIcon::try_from_bytes(
+                    &r.value32()
+                        .ok_or_else(|| Error::InvalidPropertyData {
+                            id,
+                            prop: prop_name.to_owned(),
+                            ty: prop_type.to_owned(),
+                        })?
+                        .collect::<Vec<_>>(),
+                )?)
+            }
+
             "CARDINAL" => Prop::Cardinal(
                 r.value32()
                     .ok_or_else(|| Error::InvalidPropertyData {
@@ -568,6 +1012,14 @@ where
 
             Prop::Cardinal(vals) => (AtomEnum::CARDINAL, vals),
 
+            Prop::Icon(icons) => (
+                AtomEnum::CARDINAL,
+                icons
+                    .into_iter()
+                    .flat_map(|i| [i.w, i.h].into_iter().chain(i.argb))
+                    .collect(),
+            ),
+
             Prop::Window(ids) => (AtomEnum::WINDOW, ids.into_iter().map(|id| *id).collect()),
 
             // FIXME: handle changing WmHints and WmNormalHints correctly in change_prop
@@ -591,18 +1043,13 @@ where
         let client_unmap_mask =
             EventMask::ENTER_WINDOW | EventMask::LEAVE_WINDOW | EventMask::PROPERTY_CHANGE;
 
-        let root_event_mask = EventMask::PROPERTY_CHANGE
-            | EventMask::SUBSTRUCTURE_REDIRECT
-            | EventMask::SUBSTRUCTURE_NOTIFY
-            | EventMask::BUTTON_MOTION;
-
         let mut aux = ChangeWindowAttributesAux::new();
         for conf in attrs.iter() {
             match conf {
                 ClientAttr::BorderColor(c) => aux = aux.border_pixel(*c),
                 ClientAttr::ClientEventMask => aux = aux.event_mask(client_event_mask),
                 ClientAttr::ClientUnmapMask => aux = aux.event_mask(client_unmap_mask),
-                ClientAttr::RootEventMask => aux = aux.event_mask(root_event_mask),
+                ClientAttr::RootEventMask => aux = aux.event_mask(root_event_mask()),
             }
         }
         self.conn.change_window_attributes(*id, &aux)?;
@@ -610,21 +1057,89 @@ where
         Ok(())
     }
 
-    fn set_client_config(&self, id: Xid, data: &[ClientConfig]) -> Result<()> {
-        let mut aux = ConfigureWindowAux::new();
-        for conf in data.iter() {
-            match conf {
-                ClientConfig::BorderPx(px) => aux = aux.border_width(*px),
-                ClientConfig::Position(r) => {
-                    aux = aux.x(r.x as i32).y(r.y as i32).width(r.w).height(r.h);
-                }
-                ClientConfig::StackBelow(s) => aux = aux.sibling(s.0).stack_mode(StackMode::BELOW),
-                ClientConfig::StackAbove(s) => aux = aux.sibling(s.0).stack_mode(StackMode::ABOVE),
-                ClientConfig::StackBottom => aux = aux.stack_mode(StackMode::BELOW),
-                ClientConfig::StackTop => aux = aux.stack_mode(StackMode::ABOVE),
-            }
+    fn set_rounded_corners(
+        &self,
+        id: Xid,
+        r: Rect,
+        border: u32,
+        radius: Option<u32>,
+    ) -> Result<()> {
+        if r.w == 0 || r.h == 0 {
+            return Ok(()); // Nothing to mask
         }
-        self.conn.configure_window(*id, &aux)?;
+
+        if self
+            .conn
+            .extension_information(shape::X11_EXTENSION_NAME)?
+            .is_none()
+        {
+            return Ok(()); // Shape extension not supported
+        }
+
+        let radius = match radius {
+            Some(radius) if radius > 0 => radius,
+            _ => {
+                self.conn
+                    .shape_mask(SO::SET, SK::BOUNDING, *id, 0, 0, x11rb::NONE)?;
+                return Ok(());
+            }
+        };
+
+        // The bounding shape includes the window border
+        let w = (r.w + 2 * border) as u16;
+        let h = (r.h + 2 * border) as u16;
+        let d = (2 * radius).min(w as u32).min(h as u32) as u16;
+        let rad = d / 2;
+
+        let pixmap = self.conn.generate_id()?;
+        let gc = self.conn.generate_id()?;
+        self.conn.create_pixmap(1, pixmap, *id, w, h)?;
+        self.conn
+            .create_gc(gc, pixmap, &CreateGCAux::new().foreground(0))?;
+
+        // Clear the mask and then draw the visible region as a rounded rectangle
+        let full = Rectangle {
+            x: 0,
+            y: 0,
+            width: w,
+            height: h,
+        };
+        self.conn.poly_fill_rectangle(pixmap, gc, &[full])?;
+        self.conn.change_gc(gc, &ChangeGCAux::new().foreground(1))?;
+
+        let arc = |x: u16, y: u16| Arc {
+            x: x as i16,
+            y: y as i16,
+            width: d,
+            height: d,
+            angle1: 0,
+            angle2: 360 * 64,
+        };
+        let arcs = [arc(0, 0), arc(w - d, 0), arc(0, h - d), arc(w - d, h - d)];
+        self.conn.poly_fill_arc(pixmap, gc, &arcs)?;
+
+        let rects = [
+            Rectangle {
+                x: rad as i16,
+                y: 0,
+                width: w - 2 * rad,
+                height: h,
+            },
+            Rectangle {
+                x: 0,
+                y: rad as i16,
+                width: w,
+                height: h - 2 * rad,
+            },
+        ];
+        self.conn.poly_fill_rectangle(pixmap, gc, &rects)?;
+
+        let offset = -(border as i16);
+        self.conn
+            .shape_mask(SO::SET, SK::BOUNDING, *id, offset, offset, pixmap)?;
+
+        self.conn.free_gc(gc)?;
+        self.conn.free_pixmap(pixmap)?;
 
         Ok(())
     }
@@ -655,9 +1170,465 @@ where
         Ok(())
     }
 
-    fn warp_pointer(&self, id: Xid, x: i16, y: i16) -> Result<()> {
-        self.conn.warp_pointer(x11rb::NONE, *id, 0, 0, 0, 0, x, y)?;
+    fn create_frame(&self, root: Xid, r: Rect) -> Result<Option<Xid>> {
+        let setup = self.conn.setup();
+        let screen = setup
+            .roots
+            .iter()
+            .find(|s| s.root == *root)
+            .unwrap_or(&setup.roots[0]);
+        let id = Xid(self.conn.generate_id()?);
+
+        // We need to intercept configure and map requests from the client being framed in the
+        // same way that we do for top level windows. Button presses and exposure events are
+        // needed for titlebars.
+        let win_aux = CreateWindowAux::new()
+            .event_mask(
+                EventMask::SUBSTRUCTURE_REDIRECT | EventMask::EXPOSURE | EventMask::BUTTON_PRESS,
+            )
+            .background_pixel(screen.black_pixel)
+            .border_pixel(screen.black_pixel);
+
+        let Rect { x, y, w, h } = r;
+        self.conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            *id,
+            screen.root,
+            x as i16,
+            y as i16,
+            w as u16,
+            h as u16,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &win_aux,
+        )?;
+
+        Ok(Some(id))
+    }
+
+    fn reparent(&self, client: Xid, parent: Xid, p: Point) -> Result<()> {
+        // Clients in our frames are added to the save-set so that they are returned to the
+        // root window rather than being destroyed along with their frame if we exit
+        let mode = if self.is_root(*parent) {
+            SetMode::DELETE
+        } else {
+            SetMode::INSERT
+        };
+
+        self.conn.change_save_set(mode, *client)?;
+        self.conn
+            .reparent_window(*client, *parent, p.x as i16, p.y as i16)?;
+
+        Ok(())
+    }
+
+    fn draw_titlebar(&self, frame: Xid, titlebar: &Titlebar) -> Result<()> {
+        let Titlebar {
+            title,
+            w,
+            h,
+            fg,
+            bg,
+            close_button,
+        } = titlebar;
+
+        let font = self.conn.generate_id()?;
+        let gc = self.conn.generate_id()?;
+        self.conn.open_font(font, TITLEBAR_FONT.as_bytes())?;
+        self.conn.create_gc(
+            gc,
+            *frame,
+            &CreateGCAux::new()
+                .foreground(bg.rgb_u32())
+                .background(bg.rgb_u32())
+                .font(font),
+        )?;
+
+        let bar = Rectangle {
+            x: 0,
+            y: 0,
+            width: *w as u16,
+            height: *h as u16,
+        };
+        self.conn.poly_fill_rectangle(*frame, gc, &[bar])?;
+        self.conn
+            .change_gc(gc, &ChangeGCAux::new().foreground(fg.rgb_u32()))?;
+
+        // Core fonts only support latin-1 text so anything else is replaced
+        let info = self.conn.query_font(font)?.reply()?;
+        let (ascent, descent) = (info.font_ascent as i32, info.font_descent as i32);
+        let char_w = (info.max_bounds.character_width as u32).max(1);
+        let padding = (*h as i32 - ascent - descent).max(0) as u32 / 2;
+        let available = close_button.map(|r| r.x).unwrap_or(*w);
+        let max_chars = (available.saturating_sub(2 * padding) / char_w).min(255) as usize;
+        let text: Vec<u8> = title
+            .chars()
+            .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+            .take(max_chars)
+            .collect();
+
+        let baseline = padding as i16 + ascent as i16;
+        self.conn
+            .image_text8(*frame, gc, padding as i16, baseline, &text)?;
+
+        if let Some(r) = close_button {
+            let inset = (r.h / 4) as i16;
+            let (x1, y1) = (r.x as i16 + inset, r.y as i16 + inset);
+            let (x2, y2) = ((r.x + r.w) as i16 - inset, (r.y + r.h) as i16 - inset);
+            let cross = [
+                Segment { x1, y1, x2, y2 },
+                Segment {
+                    x1,
+                    y1: y2,
+                    x2,
+                    y2: y1,
+                },
+            ];
+            self.conn.poly_segment(*frame, gc, &cross)?;
+        }
+
+        self.conn.free_gc(gc)?;
+        self.conn.close_font(font)?;
+        self.flush();
+
+        Ok(())
+    }
+
+    fn create_overlay(&self, root: Xid, r: Rect) -> Result<Option<Xid>> {
+        let setup = self.conn.setup();
+        let screen = setup
+            .roots
+            .iter()
+            .find(|s| s.root == *root)
+            .unwrap_or(&setup.roots[0]);
+        let id = Xid(self.conn.generate_id()?);
+
+        // Overlays are never managed so we don't want to see our own map requests for them
+        let win_aux = CreateWindowAux::new()
+            .event_mask(EventMask::EXPOSURE)
+            .override_redirect(1)
+            .background_pixel(screen.black_pixel)
+            .border_pixel(screen.black_pixel);
+
+        let Rect { x, y, w, h } = r;
+        self.conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            *id,
+            screen.root,
+            x as i16,
+            y as i16,
+            w as u16,
+            h as u16,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &win_aux,
+        )?;
+
+        Ok(Some(id))
+    }
+
+    fn draw_binding_hints(&self, id: Xid, hints: &BindingHints) -> Result<()> {
+        let BindingHints {
+            r,
+            line_height,
+            fg,
+            bg,
+            ..
+        } = hints;
+
+        let font = self.conn.generate_id()?;
+        let gc = self.conn.generate_id()?;
+        self.conn.open_font(font, TITLEBAR_FONT.as_bytes())?;
+        self.conn.create_gc(
+            gc,
+            *id,
+            &CreateGCAux::new()
+                .foreground(bg.rgb_u32())
+                .background(bg.rgb_u32())
+                .font(font),
+        )?;
+
+        let background = Rectangle {
+            x: 0,
+            y: 0,
+            width: r.w as u16,
+            height: r.h as u16,
+        };
+        self.conn.poly_fill_rectangle(*id, gc, &[background])?;
+        self.conn
+            .change_gc(gc, &ChangeGCAux::new().foreground(fg.rgb_u32()))?;
+
+        let info = self.conn.query_font(font)?.reply()?;
+        let (ascent, descent) = (info.font_ascent as i32, info.font_descent as i32);
+        let char_w = (info.max_bounds.character_width as u32).max(1);
+        let lh = *line_height as i32;
+        let padding = (lh - ascent - descent).max(0) / 2;
+        let max_chars = (r.w.saturating_sub(lh as u32) / char_w).min(255) as usize;
+
+        // Lines are offset by half a line so that there is padding above the first entry
+        for (i, line) in hints.lines().iter().enumerate() {
+            let text: Vec<u8> = line
+                .chars()
+                .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+                .take(max_chars)
+                .collect();
+            let baseline = lh / 2 + i as i32 * lh + padding + ascent;
+            self.conn
+                .image_text8(*id, gc, (lh / 2) as i16, baseline as i16, &text)?;
+        }
+
+        self.conn.free_gc(gc)?;
+        self.conn.close_font(font)?;
+        self.flush();
+
+        Ok(())
+    }
+
+    fn destroy_window(&self, id: Xid) -> Result<()> {
+        self.conn.destroy_window(*id)?;
+
+        Ok(())
+    }
+
+    fn set_root_background(&self, root: Xid, bg: &RootBackground) -> Result<()> {
+        bg.validate()?;
+
+        let setup = self.conn.setup();
+        let screen = setup
+            .roots
+            .iter()
+            .find(|s| s.root == *root)
+            .unwrap_or(&setup.roots[0]);
+        let (w, h, depth) = (
+            screen.width_in_pixels,
+            screen.height_in_pixels,
+            screen.root_depth,
+        );
+
+        let pixmap = self.conn.generate_id()?;
+        let gc = self.conn.generate_id()?;
+        self.conn.create_pixmap(depth, pixmap, *root, w, h)?;
+
+        match bg {
+            RootBackground::Color(c) => {
+                self.conn
+                    .create_gc(gc, pixmap, &CreateGCAux::new().foreground(c.rgb_u32()))?;
+            }
+
+            RootBackground::Image {
+                w: img_w,
+                h: img_h,
+                argb,
+            } => {
+                let tile = self.conn.generate_id()?;
+                let (img_w, img_h) = (*img_w as u16, *img_h as u16);
+                self.conn.create_pixmap(depth, tile, *root, img_w, img_h)?;
+                self.conn.create_gc(gc, tile, &CreateGCAux::new())?;
+
+                // Images are sent a chunk of rows at a time to stay within the maximum request size
+                let row_bytes = (img_w as usize * 4).max(1);
+                let max_rows = (self.conn.maximum_request_bytes().saturating_sub(32) / row_bytes)
+                    .clamp(1, u16::MAX as usize);
+                for (i, rows) in argb.chunks(img_w.max(1) as usize * max_rows).enumerate() {
+                    let data: Vec<u8> = rows.iter().flat_map(|p| p.to_le_bytes()).collect();
+                    self.conn.put_image(
+                        ImageFormat::Z_PIXMAP,
+                        tile,
+                        gc,
+                        img_w,
+                        (rows.len() / img_w.max(1) as usize) as u16,
+                        0,
+                        (i * max_rows) as i16,
+                        0,
+                        depth,
+                        &data,
+                    )?;
+                }
+
+                let aux = ChangeGCAux::new().fill_style(FillStyle::TILED).tile(tile);
+                self.conn.change_gc(gc, &aux)?;
+                self.conn.free_pixmap(tile)?;
+            }
+        }
+
+        let full = Rectangle {
+            x: 0,
+            y: 0,
+            width: w,
+            height: h,
+        };
+        self.conn.poly_fill_rectangle(pixmap, gc, &[full])?;
+        self.conn.free_gc(gc)?;
+
+        for atom in [Atom::XRootPmapId, Atom::ESetRootPmapId] {
+            let a = *self.intern_atom(atom.as_ref())?;
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                *root,
+                a,
+                AtomEnum::PIXMAP,
+                &[pixmap],
+            )?;
+        }
+
+        let aux = ChangeWindowAttributesAux::new().background_pixmap(pixmap);
+        self.conn.change_window_attributes(*root, &aux)?;
+        self.conn.clear_area(false, *root, 0, 0, 0, 0)?;
+
+        // Pixmaps are only freed once they have been replaced so that programs using
+        // pseudo-transparency are still able to read the current background.
+        if let Some(old) = self.root_pixmaps.borrow_mut().insert(*root, pixmap) {
+            self.conn.free_pixmap(old)?;
+        }
+        self.flush();
+
+        Ok(())
+    }
+
+    fn idle_time(&self) -> Result<Option<Duration>> {
+        let info = self.conn.screensaver_query_info(self.root)?.reply()?;
+
+        Ok(Some(Duration::from_millis(info.ms_since_user_input as u64)))
+    }
+
+    fn keyboard_group(&self) -> Result<Option<u8>> {
+        if !self.xkb {
+            return Ok(None);
+        }
+
+        let reply = self
+            .conn
+            .xkb_get_state(xkb::ID::USE_CORE_KBD.into())?
+            .reply()?;
+
+        Ok(Some(reply.group.into()))
+    }
+
+    fn set_keyboard_group(&self, group: u8) -> Result<()> {
+        if !self.xkb {
+            return Ok(());
+        }
+
+        self.conn.xkb_latch_lock_state(
+            xkb::ID::USE_CORE_KBD.into(),
+            ModMask::from(0u16),
+            ModMask::from(0u16),
+            true,
+            group.into(),
+            ModMask::from(0u16),
+            false,
+            0,
+        )?;
 
         Ok(())
     }
+
+    fn acquire_selection(&self, selection: &str, owner: Xid) -> Result<bool> {
+        let selection = *self.intern_atom(selection)?;
+        let time = self.server_time(*owner)?;
+        self.conn.set_selection_owner(*owner, selection, time)?;
+        let current_owner = self.conn.get_selection_owner(selection)?.reply()?.owner;
+
+        Ok(current_owner == *owner)
+    }
+
+    fn create_selection_window(&self, root: Xid) -> Result<Option<Xid>> {
+        let id = Xid(self.conn.generate_id()?);
+
+        // Selection windows are never mapped: they only need to exist in order to own the
+        // selection and receive the client messages sent to the owner
+        let win_aux = CreateWindowAux::new().override_redirect(1);
+        self.conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            *id,
+            *root,
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            x11rb::COPY_FROM_PARENT,
+            &win_aux,
+        )?;
+
+        Ok(Some(id))
+    }
+
+    fn acquire_wm_selection(&self, replace: bool) -> Result<()> {
+        let mut selections = Vec::with_capacity(self.roots.len());
+        let mut current_owners = Vec::new();
+
+        for (x_screen, &root) in self.roots.iter().enumerate() {
+            let selection = *self.intern_atom(&format!("{WM_SELECTION_PREFIX}{x_screen}"))?;
+            let current_owner = self.conn.get_selection_owner(selection)?.reply()?.owner;
+
+            if current_owner != x11rb::NONE {
+                if !replace {
+                    return Err(Error::WmAlreadyRunning);
+                }
+
+                // We need to know when the current owner exits so that we can take over
+                let aux = ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY);
+                self.conn.change_window_attributes(current_owner, &aux)?;
+                if !current_owners.contains(&current_owner) {
+                    current_owners.push(current_owner);
+                }
+            }
+
+            selections.push((root, selection));
+        }
+
+        let win = self.create_window(WinType::CheckWin, Rect::new(0, 0, 1, 1), false)?;
+        let time = self.server_time(*win)?;
+
+        for &(_, selection) in selections.iter() {
+            self.conn.set_selection_owner(*win, selection, time)?;
+
+            if self.conn.get_selection_owner(selection)?.reply()?.owner != *win {
+                return Err(Error::WmAlreadyRunning);
+            }
+        }
+
+        for current_owner in current_owners {
+            info!(
+                owner = current_owner,
+                "waiting for existing window manager to exit"
+            );
+            self.wait_for_destroy(current_owner)?;
+        }
+
+        // ICCCM 2.8: announce that we are now the owner of the manager selections
+        let manager = *self.intern_atom(Atom::Manager.as_ref())?;
+        for (root, selection) in selections {
+            let event = ClientMessageEvent::new(32, root, manager, [time, selection, *win, 0, 0]);
+            self.conn
+                .send_event(false, root, EventMask::STRUCTURE_NOTIFY, event)?;
+        }
+
+        // Window managers that do not support the manager selection will still hold
+        // SubstructureRedirect on the root window so we need to check that explicitly.
+        let aux = ChangeWindowAttributesAux::new().event_mask(root_event_mask());
+        for &root in self.roots.iter() {
+            if self
+                .conn
+                .change_window_attributes(root, &aux)?
+                .check()
+                .is_err()
+            {
+                return Err(Error::WmAlreadyRunning);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn root_event_mask() -> EventMask {
+    EventMask::PROPERTY_CHANGE
+        | EventMask::SUBSTRUCTURE_REDIRECT
+        | EventMask::SUBSTRUCTURE_NOTIFY
+        | EventMask::BUTTON_MOTION
 }
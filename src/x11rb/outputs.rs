@@ -0,0 +1,182 @@
+//! Querying detailed output information using the RandR extension
+use crate::{
+    pure::{geometry::Rect, OutputInfo, Rotation},
+    Result,
+};
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        randr::{self, ConnectionExt as _},
+        xproto::{AtomEnum, ConnectionExt as _},
+    },
+};
+
+// Length in 32bit units to request when reading EDID data: base EDID blocks are 128 bytes
+const EDID_LONG_LENGTH: u32 = 32;
+// Descriptor tag for the "Display Product Name" block in EDID data
+const EDID_MONITOR_NAME_TAG: u8 = 0xfc;
+
+/// Query the X server for details of the output backing each active CRTC.
+///
+/// Outputs are returned in CRTC order, matching the order of `screen_details` for [Conn][0].
+/// Each output is tagged with the index of the X screen that `root` belongs to.
+/// Where multiple outputs are mirrored on a single CRTC, the primary output is preferred
+/// followed by the first output listed by the X server.
+///
+///   [0]: crate::x11rb::Conn
+pub(crate) fn output_details<C: Connection>(
+    conn: &C,
+    root: u32,
+    x_screen: usize,
+) -> Result<Vec<OutputInfo>> {
+    let resources = conn.randr_get_screen_resources(root)?.reply()?;
+    let ts = resources.config_timestamp;
+
+    // Requires RandR >= 1.3 so we fall back to having no primary output if this fails
+    let primary = conn
+        .randr_get_output_primary(root)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .map(|reply| reply.output)
+        .unwrap_or(x11rb::NONE);
+
+    let edid = conn.intern_atom(false, b"EDID")?.reply()?.atom;
+    let mut outputs = Vec::new();
+
+    for &crtc in resources.crtcs.iter() {
+        let info = match conn.randr_get_crtc_info(crtc, ts)?.reply() {
+            Ok(info) if info.width > 0 => info,
+            _ => continue,
+        };
+
+        let output = match info.outputs.iter().find(|&&o| o == primary) {
+            Some(&o) => o,
+            None => match info.outputs.first() {
+                Some(&o) => o,
+                None => continue,
+            },
+        };
+
+        let output_info = conn.randr_get_output_info(output, ts)?.reply()?;
+        let name = String::from_utf8_lossy(&output_info.name).to_string();
+
+        let monitor_name = conn
+            .randr_get_output_property(
+                output,
+                edid,
+                AtomEnum::ANY,
+                0,
+                EDID_LONG_LENGTH,
+                false,
+                false,
+            )?
+            .reply()
+            .ok()
+            .and_then(|reply| monitor_name_from_edid(&reply.data));
+
+        let rotation = to_rotation(info.rotation);
+
+        // When an output has been scaled, the CRTC size differs from the size of the mode
+        // being used to drive it.
+        let scale = resources
+            .modes
+            .iter()
+            .find(|m| m.id == info.mode)
+            .map(|m| match rotation {
+                Rotation::Left | Rotation::Right => m.height,
+                Rotation::Normal | Rotation::Inverted => m.width,
+            })
+            .filter(|&w| w > 0)
+            .map(|w| info.width as f64 / w as f64)
+            .unwrap_or(1.0);
+
+        outputs.push(OutputInfo {
+            name,
+            primary: output == primary,
+            monitor_name,
+            rotation,
+            scale,
+            r: Rect::new(
+                info.x as u32,
+                info.y as u32,
+                info.width as u32,
+                info.height as u32,
+            ),
+            x_screen,
+        });
+    }
+
+    Ok(outputs)
+}
+
+fn to_rotation(r: randr::Rotation) -> Rotation {
+    let r = u16::from(r);
+
+    if r & u16::from(randr::Rotation::ROTATE90) > 0 {
+        Rotation::Left
+    } else if r & u16::from(randr::Rotation::ROTATE180) > 0 {
+        Rotation::Inverted
+    } else if r & u16::from(randr::Rotation::ROTATE270) > 0 {
+        Rotation::Right
+    } else {
+        Rotation::Normal
+    }
+}
+
+// The base EDID block contains four 18 byte descriptors starting at offset 54. Display
+// descriptors begin with three zero bytes followed by a tag byte and a zero byte, with
+// the remaining 13 bytes holding the data for the descriptor. For the monitor name this
+// is ASCII text terminated by a newline and padded with spaces.
+fn monitor_name_from_edid(edid: &[u8]) -> Option<String> {
+    if edid.len() < 128 {
+        return None;
+    }
+
+    edid[54..126]
+        .chunks_exact(18)
+        .find(|d| d[0..3] == [0, 0, 0] && d[3] == EDID_MONITOR_NAME_TAG)
+        .map(|d| {
+            let raw = &d[5..18];
+            let end = raw.iter().position(|&b| b == b'\n').unwrap_or(raw.len());
+
+            String::from_utf8_lossy(&raw[..end]).trim().to_string()
+        })
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edid_with_descriptor(tag: u8, data: &[u8]) -> Vec<u8> {
+        let mut edid = vec![0xff; 128];
+        let mut descriptor = vec![0, 0, 0, tag, 0];
+        descriptor.extend_from_slice(data);
+        descriptor.resize(18, b' ');
+        edid[72..90].copy_from_slice(&descriptor);
+
+        edid
+    }
+
+    #[test]
+    fn monitor_name_is_parsed_from_edid() {
+        let edid = edid_with_descriptor(EDID_MONITOR_NAME_TAG, b"DELL U2720Q\n");
+
+        assert_eq!(
+            monitor_name_from_edid(&edid),
+            Some("DELL U2720Q".to_string())
+        );
+    }
+
+    #[test]
+    fn other_descriptors_are_ignored() {
+        let edid = edid_with_descriptor(0xff, b"SERIAL1234\n");
+
+        assert_eq!(monitor_name_from_edid(&edid), None);
+    }
+
+    #[test]
+    fn truncated_edid_is_ignored() {
+        assert_eq!(monitor_name_from_edid(&[0; 64]), None);
+    }
+}
@@ -0,0 +1,572 @@
+//! A single threaded event loop combining X events, timers and file descriptor readiness.
+//!
+//! An [AsyncConn] wraps a [RustConn] and multiplexes reading from the X server with any
+//! timers and file descriptors that have been registered with it. Expired timers are
+//! delivered to the window manager as [XEvent::Timer] events and watched file descriptors
+//! that are ready to be read from as [XEvent::Readable] events, both of which are passed to
+//! your event hook without any default handling. This allows for periodic hooks and IPC
+//! (for example reading commands from a fifo or unix socket) without the need to spawn
+//! additional threads and communicate back to the window manager.
+//!
+//! Events from the X server are always prioritised over timers and watched file
+//! descriptors. Replies to property fetches and other queries are read from the same
+//! connection so any events arriving while waiting for a reply are queued and returned
+//! by the next call to [Backend::next_event].
+//!
+//! ```no_run
+//! # use penrose::{core::State, x::XEvent, x11rb::reactor::AsyncConn, Result};
+//! # use std::time::Duration;
+//! fn startup_hook(_: &mut State<AsyncConn>, x: &AsyncConn) -> Result<()> {
+//!     x.set_interval(Duration::from_secs(5));
+//!
+//!     Ok(())
+//! }
+//!
+//! fn event_hook(event: &XEvent, _: &mut State<AsyncConn>, _: &AsyncConn) -> Result<bool> {
+//!     if let XEvent::Timer(id) = event {
+//!         println!("timer {id} fired");
+//!     }
+//!
+//!     Ok(true)
+//! }
+//! ```
+//!
+//! This module requires the `x11rb-async` feature to be enabled.
+use crate::{
+    core::{
+        bindings::{KeyCode, MouseState},
+        hints::BindingHints,
+        titlebar::Titlebar,
+    },
+    pure::{
+        geometry::{Point, Rect},
+        OutputInfo,
+    },
+    x::{
+        event::ClientMessage,
+        property::{Prop, WindowAttributes, WmState},
+        Backend, ClientAttr, ClientConfig, ClientConfigBatch, Cursor, RootBackground, XConn,
+        XEvent,
+    },
+    x11rb::RustConn,
+    Result, Xid,
+};
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    os::unix::io::{AsRawFd, RawFd},
+    time::{Duration, Instant},
+};
+use tracing::trace;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Timer {
+    id: u64,
+    deadline: Instant,
+    interval: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct Timers {
+    next_id: u64,
+    timers: Vec<Timer>,
+}
+
+impl Timers {
+    fn add(&mut self, deadline: Instant, interval: Option<Duration>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.timers.push(Timer {
+            id,
+            deadline,
+            interval,
+        });
+
+        id
+    }
+
+    fn cancel(&mut self, id: u64) {
+        self.timers.retain(|t| t.id != id);
+    }
+
+    // Pop the earliest expired timer, rescheduling it if it repeats. Repeating timers that
+    // have fallen more than one interval behind are rescheduled relative to `now` rather
+    // than firing repeatedly to catch up.
+    fn fire_expired(&mut self, now: Instant) -> Option<u64> {
+        let (ix, t) = self
+            .timers
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, t)| t.deadline <= now)
+            .min_by_key(|(_, t)| t.deadline)?;
+
+        match t.interval {
+            Some(interval) => {
+                let next = t.deadline + interval;
+                self.timers[ix].deadline = if next <= now { now + interval } else { next };
+            }
+            None => {
+                self.timers.remove(ix);
+            }
+        }
+
+        Some(t.id)
+    }
+
+    // The poll timeout in milliseconds until the next timer is due, or -1 to block
+    // indefinitely if there are no timers.
+    fn poll_timeout(&self, now: Instant) -> i32 {
+        match self.timers.iter().map(|t| t.deadline).min() {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(now);
+                let ms =
+                    remaining.as_millis() + u128::from(remaining.subsec_nanos() % 1_000_000 != 0);
+
+                ms.min(i32::MAX as u128) as i32
+            }
+            None => -1,
+        }
+    }
+}
+
+/// An [XConn] that runs X event handling, timers and file descriptor watching from a
+/// single event loop.
+///
+/// See the [module level docs][self] for details.
+#[derive(Debug)]
+pub struct AsyncConn {
+    inner: RustConn,
+    timers: RefCell<Timers>,
+    watched: RefCell<Vec<RawFd>>,
+    ready: RefCell<VecDeque<RawFd>>,
+}
+
+impl AsyncConn {
+    /// Connect to the X server using a new [RustConn].
+    pub fn new() -> Result<Self> {
+        Ok(Self::new_for_conn(RustConn::new()?))
+    }
+
+    /// Wrap an existing [RustConn].
+    pub fn new_for_conn(inner: RustConn) -> Self {
+        Self {
+            inner,
+            timers: Default::default(),
+            watched: Default::default(),
+            ready: Default::default(),
+        }
+    }
+
+    /// Get a handle to the wrapped connection.
+    pub fn inner(&self) -> &RustConn {
+        &self.inner
+    }
+
+    /// Register a timer that fires once after the given delay, returning the ID that will
+    /// be sent in the resulting [XEvent::Timer] event.
+    pub fn set_timeout(&self, delay: Duration) -> u64 {
+        self.timers.borrow_mut().add(Instant::now() + delay, None)
+    }
+
+    /// Register a timer that fires repeatedly at the given interval, returning the ID that
+    /// will be sent in each resulting [XEvent::Timer] event.
+    pub fn set_interval(&self, interval: Duration) -> u64 {
+        self.timers
+            .borrow_mut()
+            .add(Instant::now() + interval, Some(interval))
+    }
+
+    /// Cancel a previously registered timer.
+    ///
+    /// Cancelling a timer that has already fired or been cancelled is a no-op.
+    pub fn cancel_timer(&self, id: u64) {
+        self.timers.borrow_mut().cancel(id);
+    }
+
+    /// Watch a file descriptor, generating an [XEvent::Readable] event each time it is
+    /// ready to be read from.
+    ///
+    /// Readiness is level triggered: if the data available on the file descriptor is not
+    /// consumed when handling the event then it will be reported again.
+    pub fn watch_fd(&self, fd: RawFd) {
+        let mut watched = self.watched.borrow_mut();
+        if !watched.contains(&fd) {
+            watched.push(fd);
+        }
+    }
+
+    /// Stop watching a file descriptor.
+    pub fn unwatch_fd(&self, fd: RawFd) {
+        self.watched.borrow_mut().retain(|&f| f != fd);
+        self.ready.borrow_mut().retain(|&f| f != fd);
+    }
+
+    // Run the event loop until there is an event to return, the deadline (if any) passes or
+    // the wakeup file descriptor (if any) becomes readable.
+    fn next_event_until(
+        &self,
+        deadline: Option<Instant>,
+        wakeup: Option<RawFd>,
+    ) -> Result<Option<XEvent>> {
+        loop {
+            if let Some(event) = self.inner.poll_for_event()? {
+                return Ok(Some(event));
+            }
+
+            let now = Instant::now();
+            if let Some(id) = self.timers.borrow_mut().fire_expired(now) {
+                return Ok(Some(XEvent::Timer(id)));
+            }
+
+            if let Some(fd) = self.ready.borrow_mut().pop_front() {
+                return Ok(Some(XEvent::Readable(fd)));
+            }
+
+            let mut timeout = self.timers.borrow().poll_timeout(now);
+            if let Some(deadline) = deadline {
+                if deadline <= now {
+                    return Ok(None);
+                }
+                let ms = deadline
+                    .duration_since(now)
+                    .as_millis()
+                    .min(i32::MAX as u128) as i32;
+                timeout = if timeout < 0 { ms } else { timeout.min(ms) };
+            }
+
+            // Make sure that any pending requests have been sent before we block
+            self.inner.flush();
+            if self.wait(timeout, wakeup)? {
+                return Ok(None);
+            }
+        }
+    }
+
+    // Block until the X connection or one of the watched file descriptors is readable or
+    // the next timer is due, returning true if we were woken by the wakeup file descriptor.
+    fn wait(&self, timeout: i32, wakeup: Option<RawFd>) -> Result<bool> {
+        let x_fd = self.inner.connection().stream().as_raw_fd();
+        let watched = self.watched.borrow().clone();
+        let mut fds: Vec<PollFd> = std::iter::once(x_fd)
+            .chain(watched.iter().copied())
+            .chain(wakeup)
+            .map(|fd| PollFd::new(fd, PollFlags::POLLIN))
+            .collect();
+
+        trace!(timeout, n_fds = fds.len(), "polling for readiness");
+        match poll(&mut fds, timeout) {
+            Ok(_) | Err(Errno::EINTR) => (),
+            Err(e) => return Err(std::io::Error::from(e).into()),
+        }
+
+        let readable = PollFlags::POLLIN | PollFlags::POLLHUP | PollFlags::POLLERR;
+        let mut ready = self.ready.borrow_mut();
+        for (fd, pfd) in watched.iter().zip(&fds[1..]) {
+            if pfd.revents().is_some_and(|r| r.intersects(readable)) {
+                ready.push_back(*fd);
+            }
+        }
+
+        let woken = wakeup.is_some()
+            && fds
+                .last()
+                .and_then(|pfd| pfd.revents())
+                .is_some_and(|r| r.intersects(readable));
+
+        Ok(woken)
+    }
+}
+
+impl Backend for AsyncConn {
+    fn screen_details(&self) -> Result<Vec<Rect>> {
+        self.inner.screen_details()
+    }
+
+    fn cursor_position(&self) -> Result<Point> {
+        self.inner.cursor_position()
+    }
+
+    fn output_details(&self) -> Result<Vec<OutputInfo>> {
+        self.inner.output_details()
+    }
+
+    fn grab(&self, key_codes: &[KeyCode], mouse_states: &[MouseState]) -> Result<()> {
+        self.inner.grab(key_codes, mouse_states)
+    }
+
+    fn keycodes(&self) -> Result<HashMap<String, u8>> {
+        self.inner.keycodes()
+    }
+
+    fn next_event(&self) -> Result<XEvent> {
+        loop {
+            if let Some(event) = self.next_event_until(None, None)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn client_geometry(&self, client: Xid) -> Result<Rect> {
+        self.inner.client_geometry(client)
+    }
+
+    fn existing_clients(&self) -> Result<Vec<Xid>> {
+        self.inner.existing_clients()
+    }
+
+    fn map(&self, client: Xid) -> Result<()> {
+        self.inner.map(client)
+    }
+
+    fn unmap(&self, client: Xid) -> Result<()> {
+        self.inner.unmap(client)
+    }
+
+    fn kill(&self, client: Xid) -> Result<()> {
+        self.inner.kill(client)
+    }
+
+    fn focus(&self, client: Xid) -> Result<()> {
+        self.inner.focus(client)
+    }
+
+    fn set_client_config(&self, client: Xid, data: &[ClientConfig]) -> Result<()> {
+        self.inner.set_client_config(client, data)
+    }
+
+    fn set_client_config_batch(&self, batch: ClientConfigBatch) -> Result<()> {
+        self.inner.set_client_config_batch(batch)
+    }
+
+    fn warp_pointer(&self, id: Xid, x: i16, y: i16) -> Result<()> {
+        self.inner.warp_pointer(id, x, y)
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        self.inner.grab_pointer()
+    }
+
+    fn set_cursor(&self, id: Xid, cursor: Cursor) -> Result<()> {
+        self.inner.set_cursor(id, cursor)
+    }
+
+    fn set_active_grab_cursor(&self, cursor: Cursor) -> Result<()> {
+        self.inner.set_active_grab_cursor(cursor)
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        self.inner.ungrab_pointer()
+    }
+
+    fn grab_keyboard(&self) -> Result<()> {
+        self.inner.grab_keyboard()
+    }
+
+    fn ungrab_keyboard(&self) -> Result<()> {
+        self.inner.ungrab_keyboard()
+    }
+
+    fn next_event_before(&self, deadline: Instant) -> Result<Option<XEvent>> {
+        self.next_event_until(Some(deadline), None)
+    }
+
+    fn poll_for_event(&self) -> Result<Option<XEvent>> {
+        self.inner.poll_for_event()
+    }
+
+    fn next_event_or_wakeup(
+        &self,
+        deadline: Option<Instant>,
+        wakeup: Option<RawFd>,
+    ) -> Result<Option<XEvent>> {
+        self.next_event_until(deadline, wakeup)
+    }
+}
+
+impl XConn for AsyncConn {
+    fn root(&self) -> Xid {
+        self.inner.root()
+    }
+
+    fn roots(&self) -> Vec<Xid> {
+        self.inner.roots()
+    }
+
+    fn client_root(&self, client: Xid) -> Result<Xid> {
+        self.inner.client_root(client)
+    }
+
+    fn intern_atom(&self, atom: &str) -> Result<Xid> {
+        self.inner.intern_atom(atom)
+    }
+
+    fn atom_name(&self, xid: Xid) -> Result<String> {
+        self.inner.atom_name(xid)
+    }
+
+    fn get_prop(&self, client: Xid, prop_name: &str) -> Result<Option<Prop>> {
+        self.inner.get_prop(client, prop_name)
+    }
+
+    fn get_window_attributes(&self, client: Xid) -> Result<WindowAttributes> {
+        self.inner.get_window_attributes(client)
+    }
+
+    fn set_wm_state(&self, client: Xid, wm_state: WmState) -> Result<()> {
+        self.inner.set_wm_state(client, wm_state)
+    }
+
+    fn set_prop(&self, client: Xid, name: &str, val: Prop) -> Result<()> {
+        self.inner.set_prop(client, name, val)
+    }
+
+    fn set_client_attributes(&self, client: Xid, attrs: &[ClientAttr]) -> Result<()> {
+        self.inner.set_client_attributes(client, attrs)
+    }
+
+    fn set_rounded_corners(
+        &self,
+        client: Xid,
+        r: Rect,
+        border: u32,
+        radius: Option<u32>,
+    ) -> Result<()> {
+        self.inner.set_rounded_corners(client, r, border, radius)
+    }
+
+    fn send_client_message(&self, msg: ClientMessage) -> Result<()> {
+        self.inner.send_client_message(msg)
+    }
+
+    fn acquire_wm_selection(&self, replace: bool) -> Result<()> {
+        self.inner.acquire_wm_selection(replace)
+    }
+
+    fn acquire_selection(&self, selection: &str, owner: Xid) -> Result<bool> {
+        self.inner.acquire_selection(selection, owner)
+    }
+
+    fn create_selection_window(&self, root: Xid) -> Result<Option<Xid>> {
+        self.inner.create_selection_window(root)
+    }
+
+    fn create_frame(&self, root: Xid, r: Rect) -> Result<Option<Xid>> {
+        self.inner.create_frame(root, r)
+    }
+
+    fn reparent(&self, client: Xid, parent: Xid, p: Point) -> Result<()> {
+        self.inner.reparent(client, parent, p)
+    }
+
+    fn draw_titlebar(&self, frame: Xid, titlebar: &Titlebar) -> Result<()> {
+        self.inner.draw_titlebar(frame, titlebar)
+    }
+
+    fn create_overlay(&self, root: Xid, r: Rect) -> Result<Option<Xid>> {
+        self.inner.create_overlay(root, r)
+    }
+
+    fn draw_binding_hints(&self, id: Xid, hints: &BindingHints) -> Result<()> {
+        self.inner.draw_binding_hints(id, hints)
+    }
+
+    fn destroy_window(&self, id: Xid) -> Result<()> {
+        self.inner.destroy_window(id)
+    }
+
+    fn set_root_background(&self, root: Xid, bg: &RootBackground) -> Result<()> {
+        self.inner.set_root_background(root, bg)
+    }
+
+    fn idle_time(&self) -> Result<Option<Duration>> {
+        self.inner.idle_time()
+    }
+
+    fn keyboard_group(&self) -> Result<Option<u8>> {
+        self.inner.keyboard_group()
+    }
+
+    fn set_keyboard_group(&self, group: u8) -> Result<()> {
+        self.inner.set_keyboard_group(group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn one_shot_timers_fire_once() {
+        let now = Instant::now();
+        let mut timers = Timers::default();
+        let id = timers.add(now + 10 * MS, None);
+
+        assert_eq!(timers.fire_expired(now), None);
+        assert_eq!(timers.fire_expired(now + 10 * MS), Some(id));
+        assert_eq!(timers.fire_expired(now + 20 * MS), None);
+    }
+
+    #[test]
+    fn repeating_timers_are_rescheduled() {
+        let now = Instant::now();
+        let mut timers = Timers::default();
+        let id = timers.add(now + 10 * MS, Some(10 * MS));
+
+        assert_eq!(timers.fire_expired(now + 10 * MS), Some(id));
+        assert_eq!(timers.fire_expired(now + 15 * MS), None);
+        assert_eq!(timers.fire_expired(now + 20 * MS), Some(id));
+    }
+
+    #[test]
+    fn repeating_timers_do_not_fire_repeatedly_to_catch_up() {
+        let now = Instant::now();
+        let mut timers = Timers::default();
+        let id = timers.add(now + 10 * MS, Some(10 * MS));
+
+        assert_eq!(timers.fire_expired(now + 100 * MS), Some(id));
+        assert_eq!(timers.fire_expired(now + 100 * MS), None);
+        assert_eq!(timers.poll_timeout(now + 100 * MS), 10);
+    }
+
+    #[test]
+    fn earliest_timer_fires_first() {
+        let now = Instant::now();
+        let mut timers = Timers::default();
+        let late = timers.add(now + 20 * MS, None);
+        let early = timers.add(now + 10 * MS, None);
+
+        assert_eq!(timers.fire_expired(now + 30 * MS), Some(early));
+        assert_eq!(timers.fire_expired(now + 30 * MS), Some(late));
+    }
+
+    #[test]
+    fn cancelled_timers_do_not_fire() {
+        let now = Instant::now();
+        let mut timers = Timers::default();
+        let id = timers.add(now, None);
+        timers.cancel(id);
+
+        assert_eq!(timers.fire_expired(now), None);
+        assert_eq!(timers.poll_timeout(now), -1);
+    }
+
+    #[test]
+    fn poll_timeout_rounds_up_to_the_next_millisecond() {
+        let now = Instant::now();
+        let mut timers = Timers::default();
+        timers.add(now + Duration::from_micros(1500), None);
+
+        assert_eq!(timers.poll_timeout(now), 2);
+    }
+}